@@ -0,0 +1,83 @@
+/*
+ * C bindings for embedding Stack.
+ *
+ * A StackEngine holds an engine along with the context which programs run
+ * in, so the stack and definitions are kept between calls to stack_eval.
+ *
+ * Strings returned by stack_value_to_string and stack_value_type are owned
+ * by the caller and must be freed with stack_string_free. The string
+ * returned by stack_last_error is owned by the engine, and is valid until
+ * the next call to stack_eval.
+ *
+ * Panics never unwind into C. If one happens, stack_eval returns STACK_PANIC
+ * and clears the engine's stack and definitions, and the other functions
+ * return NULL, zero or nothing.
+ */
+
+#ifndef STACK_H
+#define STACK_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The program ran successfully. */
+#define STACK_OK 0
+/* The source couldn't be parsed. */
+#define STACK_PARSE_ERROR 1
+/* The program failed while running. */
+#define STACK_RUN_ERROR 2
+/* An argument was null or invalid, such as a string which isn't UTF-8. */
+#define STACK_INVALID_ARGUMENT 3
+/* The engine panicked, and its stack and definitions were cleared. */
+#define STACK_PANIC 4
+
+typedef struct StackEngine StackEngine;
+
+/* Creates an engine with an empty stack. */
+StackEngine *stack_engine_new(void);
+
+/* Frees an engine, which can't be used after. */
+void stack_engine_free(StackEngine *engine);
+
+/*
+ * Runs source, returning STACK_OK or the kind of error. The message of the
+ * error can be read with stack_last_error. If the engine panics, this
+ * returns STACK_PANIC and the stack and definitions are cleared.
+ */
+int stack_eval(StackEngine *engine, const char *source);
+
+/*
+ * Returns the message of the error from the last call to stack_eval, or
+ * NULL if it succeeded.
+ */
+const char *stack_last_error(const StackEngine *engine);
+
+/* Returns the amount of values on the stack. */
+size_t stack_stack_len(const StackEngine *engine);
+
+/* Removes every value from the stack. */
+void stack_stack_clear(StackEngine *engine);
+
+/*
+ * Returns the value at index of the stack, counting from the bottom, as a
+ * string, or NULL if there isn't one.
+ */
+char *stack_value_to_string(const StackEngine *engine, size_t index);
+
+/*
+ * Returns the type of the value at index of the stack, such as "integer",
+ * or NULL if there isn't one.
+ */
+char *stack_value_type(const StackEngine *engine, size_t index);
+
+/* Frees a string returned by this library, which can't be used after. */
+void stack_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* STACK_H */
@@ -0,0 +1,376 @@
+//! C bindings for embedding Stack, declared in `include/stack.h`.
+//!
+//! A `StackEngine` holds an engine along with the context which programs run
+//! in, so the stack and definitions are kept between calls to `stack_eval`.
+//!
+//! ```c
+//! StackEngine *engine = stack_engine_new();
+//!
+//! if (stack_eval(engine, "1 2 +") != STACK_OK) {
+//!   fprintf(stderr, "%s\n", stack_last_error(engine));
+//! }
+//!
+//! char *top = stack_value_to_string(engine, stack_stack_len(engine) - 1);
+//! printf("%s\n", top);
+//!
+//! stack_string_free(top);
+//! stack_engine_free(engine);
+//! ```
+//!
+//! Strings returned by `stack_value_to_string` are owned by the caller and
+//! must be freed with `stack_string_free`. The string returned by
+//! `stack_last_error` is owned by the engine, and is valid until the next
+//! call to `stack_eval`.
+//!
+//! Panics never unwind into C. If one happens, `stack_eval` returns
+//! `STACK_PANIC` and clears the engine's stack and definitions, and the other
+//! functions return null, zero or nothing.
+
+use core::ffi::{c_char, c_int, CStr};
+use std::{any::Any, ffi::CString, panic::AssertUnwindSafe};
+
+use stack_core::prelude::*;
+
+/// The program ran successfully.
+pub const STACK_OK: c_int = 0;
+/// The source couldn't be parsed.
+pub const STACK_PARSE_ERROR: c_int = 1;
+/// The program failed while running.
+pub const STACK_RUN_ERROR: c_int = 2;
+/// An argument was null or invalid, such as a string which isn't UTF-8.
+pub const STACK_INVALID_ARGUMENT: c_int = 3;
+/// The engine panicked, and its stack and definitions were cleared.
+pub const STACK_PANIC: c_int = 4;
+
+pub struct StackEngine {
+  engine: Engine,
+  context: Context,
+  error: Option<CString>,
+}
+
+impl StackEngine {
+  fn eval(&mut self, source: &str) -> Result<(), (c_int, String)> {
+    let source = Source::new("", source);
+    self.context.add_source(source.clone());
+
+    let mut lexer = Lexer::new(source);
+    let exprs =
+      parse(&mut lexer).map_err(|e| (STACK_PARSE_ERROR, e.to_string()))?;
+
+    let context = core::mem::take(&mut self.context);
+    match self.engine.run(context, exprs) {
+      Ok(context) => {
+        self.context = context;
+        Ok(())
+      }
+      Err(e) => {
+        let message = e.to_string();
+        self.context = *e.context;
+        Err((STACK_RUN_ERROR, message))
+      }
+    }
+  }
+
+  fn value(&self, index: usize) -> Option<&Expr> {
+    self.context.stack().get(index)
+  }
+}
+
+/// Converts `string` for C, replacing any nul bytes, which C can't represent.
+fn c_string(string: String) -> CString {
+  CString::new(string.replace('\0', "?")).unwrap()
+}
+
+/// Runs `f`, returning the message of its panic instead of unwinding, since
+/// unwinding into C is undefined behaviour.
+fn catch<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+  std::panic::catch_unwind(AssertUnwindSafe(f)).map_err(panic_message)
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+  let message = payload
+    .downcast_ref::<&str>()
+    .copied()
+    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+    .unwrap_or("unknown panic");
+
+  format!("panicked: {message}")
+}
+
+/// Creates an engine with an empty stack.
+#[no_mangle]
+pub extern "C" fn stack_engine_new() -> *mut StackEngine {
+  catch(|| {
+    Box::into_raw(Box::new(StackEngine {
+      engine: Engine::new(),
+      context: Context::new(),
+      error: None,
+    }))
+  })
+  .unwrap_or(core::ptr::null_mut())
+}
+
+/// Frees an engine.
+///
+/// # Safety
+///
+/// `engine` must be null or have come from [`stack_engine_new`], and can't be
+/// used after.
+#[no_mangle]
+pub unsafe extern "C" fn stack_engine_free(engine: *mut StackEngine) {
+  if !engine.is_null() {
+    let _ = catch(|| drop(Box::from_raw(engine)));
+  }
+}
+
+/// Runs `source`, returning [`STACK_OK`] or the kind of error. The message of
+/// the error can be read with [`stack_last_error`]. If the engine panics, this
+/// returns [`STACK_PANIC`] and the stack and definitions are cleared.
+///
+/// # Safety
+///
+/// `engine` must be null or a valid engine, and `source` must be null or a
+/// nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn stack_eval(
+  engine: *mut StackEngine,
+  source: *const c_char,
+) -> c_int {
+  let Some(engine) = engine.as_mut() else {
+    return STACK_INVALID_ARGUMENT;
+  };
+
+  let result = match source.as_ref() {
+    Some(_) => match CStr::from_ptr(source).to_str() {
+      Ok(source) => catch(|| engine.eval(source)).unwrap_or_else(|message| {
+        engine.context = Context::new();
+        Err((STACK_PANIC, message))
+      }),
+      Err(_) => Err((STACK_INVALID_ARGUMENT, "source isn't UTF-8".into())),
+    },
+    None => Err((STACK_INVALID_ARGUMENT, "source is null".into())),
+  };
+
+  match result {
+    Ok(()) => {
+      engine.error = None;
+      STACK_OK
+    }
+    Err((code, message)) => {
+      engine.error = Some(c_string(message));
+      code
+    }
+  }
+}
+
+/// Returns the message of the error from the last call to [`stack_eval`], or
+/// null if it succeeded.
+///
+/// # Safety
+///
+/// `engine` must be null or a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn stack_last_error(
+  engine: *const StackEngine,
+) -> *const c_char {
+  engine
+    .as_ref()
+    .and_then(|x| x.error.as_ref())
+    .map_or(core::ptr::null(), |x| x.as_ptr())
+}
+
+/// Returns the amount of values on the stack.
+///
+/// # Safety
+///
+/// `engine` must be null or a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn stack_stack_len(engine: *const StackEngine) -> usize {
+  catch(|| engine.as_ref().map_or(0, |x| x.context.stack().len())).unwrap_or(0)
+}
+
+/// Removes every value from the stack.
+///
+/// # Safety
+///
+/// `engine` must be null or a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn stack_stack_clear(engine: *mut StackEngine) {
+  if let Some(engine) = engine.as_mut() {
+    let _ = catch(|| engine.context.stack_mut().clear());
+  }
+}
+
+/// Returns the value at `index` of the stack, counting from the bottom, as a
+/// string, or null if there isn't one. The string must be freed with
+/// [`stack_string_free`].
+///
+/// # Safety
+///
+/// `engine` must be null or a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn stack_value_to_string(
+  engine: *const StackEngine,
+  index: usize,
+) -> *mut c_char {
+  catch(|| {
+    engine
+      .as_ref()
+      .and_then(|x| x.value(index))
+      .map_or(core::ptr::null_mut(), |x| {
+        c_string(x.to_string()).into_raw()
+      })
+  })
+  .unwrap_or(core::ptr::null_mut())
+}
+
+/// Returns the type of the value at `index` of the stack, such as
+/// `"integer"`, or null if there isn't one. The string must be freed with
+/// [`stack_string_free`].
+///
+/// # Safety
+///
+/// `engine` must be null or a valid engine.
+#[no_mangle]
+pub unsafe extern "C" fn stack_value_type(
+  engine: *const StackEngine,
+  index: usize,
+) -> *mut c_char {
+  catch(|| {
+    engine
+      .as_ref()
+      .and_then(|x| x.value(index))
+      .map_or(core::ptr::null_mut(), |x| {
+        c_string(x.kind.type_of().to_string()).into_raw()
+      })
+  })
+  .unwrap_or(core::ptr::null_mut())
+}
+
+/// Frees a string returned by this library.
+///
+/// # Safety
+///
+/// `string` must be null or have come from this library, and can't be used
+/// after.
+#[no_mangle]
+pub unsafe extern "C" fn stack_string_free(string: *mut c_char) {
+  if !string.is_null() {
+    let _ = catch(|| drop(CString::from_raw(string)));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn value(engine: *const StackEngine, index: usize) -> Option<String> {
+    unsafe {
+      let string = stack_value_to_string(engine, index);
+      if string.is_null() {
+        return None;
+      }
+
+      let value = CStr::from_ptr(string).to_str().unwrap().to_string();
+      stack_string_free(string);
+
+      Some(value)
+    }
+  }
+
+  #[test]
+  fn evaluates_and_reads_the_stack() {
+    let engine = stack_engine_new();
+
+    unsafe {
+      assert_eq!(stack_eval(engine, c"2 'x def x 3 +".as_ptr()), STACK_OK);
+      assert!(stack_last_error(engine).is_null());
+      assert_eq!(stack_eval(engine, c"\"a\"".as_ptr()), STACK_OK);
+
+      assert_eq!(stack_stack_len(engine), 2);
+      assert_eq!(value(engine, 0).as_deref(), Some("5"));
+      assert_eq!(value(engine, 2), None);
+
+      let ty = stack_value_type(engine, 1);
+      assert_eq!(CStr::from_ptr(ty).to_str(), Ok("string"));
+      stack_string_free(ty);
+
+      stack_engine_free(engine);
+    }
+  }
+
+  #[test]
+  fn reports_errors() {
+    let engine = stack_engine_new();
+
+    unsafe {
+      assert_eq!(stack_eval(engine, c"1 +".as_ptr()), STACK_RUN_ERROR);
+      let error = CStr::from_ptr(stack_last_error(engine)).to_str().unwrap();
+      assert!(error.starts_with("stack underflow"));
+
+      assert_eq!(stack_eval(engine, c"(".as_ptr()), STACK_PARSE_ERROR);
+      assert_eq!(
+        stack_eval(engine, core::ptr::null()),
+        STACK_INVALID_ARGUMENT
+      );
+      assert_eq!(
+        stack_eval(core::ptr::null_mut(), c"1".as_ptr()),
+        STACK_INVALID_ARGUMENT
+      );
+
+      stack_engine_free(engine);
+    }
+  }
+
+  #[test]
+  fn catches_panics() {
+    assert_eq!(catch(|| 1), Ok(1));
+    assert_eq!(
+      catch(|| -> i32 { panic!("oh no") }),
+      Err("panicked: oh no".into())
+    );
+    assert_eq!(
+      catch(|| -> i32 { panic!("{}", 1) }),
+      Err("panicked: 1".into())
+    );
+  }
+
+  #[test]
+  fn header_declares_every_status() {
+    let header = include_str!("../include/stack.h");
+
+    for (name, code) in [
+      ("STACK_OK", STACK_OK),
+      ("STACK_PARSE_ERROR", STACK_PARSE_ERROR),
+      ("STACK_RUN_ERROR", STACK_RUN_ERROR),
+      ("STACK_INVALID_ARGUMENT", STACK_INVALID_ARGUMENT),
+      ("STACK_PANIC", STACK_PANIC),
+    ] {
+      assert!(
+        header.contains(&format!("#define {name} {code}\n")),
+        "{name} isn't defined as {code} in stack.h"
+      );
+    }
+  }
+
+  #[test]
+  fn header_declares_every_function() {
+    let header = include_str!("../include/stack.h");
+    let source = include_str!("lib.rs");
+
+    for line in source.lines() {
+      let Some(name) = line
+        .split_once("extern \"C\" fn ")
+        .and_then(|(_, x)| x.split('(').next())
+      else {
+        continue;
+      };
+
+      assert!(
+        header.contains(&format!(" {name}("))
+          || header.contains(&format!("*{name}(")),
+        "{name} isn't declared in stack.h"
+      );
+    }
+  }
+}
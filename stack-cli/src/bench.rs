@@ -0,0 +1,124 @@
+//! Measurements for `stack bench`.
+//!
+//! Allocations are counted by [`CountingAlloc`], which the `stack` binary
+//! installs as its global allocator.
+
+use core::{
+  alloc::{GlobalAlloc, Layout},
+  fmt,
+  sync::atomic::{AtomicUsize, Ordering},
+};
+use std::{alloc::System, time::Duration};
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting the allocations made with it.
+pub struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+    System.alloc_zeroed(layout)
+  }
+
+  unsafe fn realloc(
+    &self,
+    ptr: *mut u8,
+    layout: Layout,
+    new_size: usize,
+  ) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+    System.realloc(ptr, layout, new_size)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+}
+
+/// The allocations made so far, which are only counted when
+/// [`CountingAlloc`] is the global allocator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Allocations {
+  pub count: usize,
+  pub bytes: usize,
+}
+
+impl Allocations {
+  pub fn now() -> Self {
+    Self {
+      count: ALLOCATIONS.load(Ordering::Relaxed),
+      bytes: ALLOCATED_BYTES.load(Ordering::Relaxed),
+    }
+  }
+
+  /// Returns the allocations made since `earlier`.
+  pub fn since(self, earlier: Self) -> Self {
+    Self {
+      count: self.count - earlier.count,
+      bytes: self.bytes - earlier.bytes,
+    }
+  }
+}
+
+/// The measurements of a single run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sample {
+  pub parse: Duration,
+  pub run: Duration,
+  /// The amount of expressions which were evaluated.
+  pub ops: usize,
+  pub allocations: Allocations,
+}
+
+/// The measurements of every run of a benchmark.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BenchReport {
+  pub name: String,
+  pub samples: Vec<Sample>,
+}
+
+impl fmt::Display for BenchReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let Some(last) = self.samples.last() else {
+      return write!(f, "{}: no runs", self.name);
+    };
+
+    writeln!(f, "{}: {} runs", self.name, self.samples.len())?;
+
+    let mut times = |name: &str, time: fn(&Sample) -> Duration| {
+      let mut times = self.samples.iter().map(time).collect::<Vec<_>>();
+      times.sort();
+
+      let mean = times.iter().sum::<Duration>() / times.len() as u32;
+      writeln!(
+        f,
+        "  {name:<7} min {:>12?}  median {:>12?}  mean {:>12?}",
+        times[0],
+        times[times.len() / 2],
+        mean
+      )
+    };
+    times("parse", |x| x.parse)?;
+    times("run", |x| x.run)?;
+
+    // Every run evaluates the same program, but the first can include
+    // one-off work such as interning symbols, so the last is reported.
+    writeln!(f, "  ops     {} per run", last.ops)?;
+    write!(
+      f,
+      "  allocs  {} per run ({:.1} KiB)",
+      last.allocations.count,
+      last.allocations.bytes as f64 / 1024.0
+    )
+  }
+}
@@ -0,0 +1,160 @@
+//! The documentation generator for `stack doc`.
+//!
+//! A file is documented from the definitions it makes when it's run, like
+//! when it's imported, and a native module from the functions it has. Each
+//! word is listed with its stack effect, where that's known, and the text of
+//! its `##` doc comments. A declared stack effect is preferred over one
+//! worked out from the body of a function.
+
+use core::fmt::Write;
+
+use stack_core::{
+  effect::StackEffect,
+  internal::scope::{Meta, Scope},
+  lint,
+  prelude::*,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocItem {
+  pub name: String,
+  pub effect: Option<StackEffect>,
+  pub doc: Option<String>,
+}
+
+impl DocItem {
+  /// Documents the item `name` of `scope`, if it's defined.
+  pub fn from_scope(scope: &Scope, name: Symbol) -> Option<Self> {
+    let meta = scope.meta(name).cloned().unwrap_or_default();
+    let effect = meta.effect.or_else(|| {
+      let (pops, pushes) = match scope.get_val(name)?.kind {
+        ExprKind::Function { ref body, .. } => lint::stack_effect(body)?,
+        _ => (0, 1),
+      };
+
+      Some(StackEffect::from_counts(pops, pushes))
+    });
+
+    Some(Self {
+      name: name.as_str().to_owned(),
+      effect,
+      doc: meta.doc,
+    })
+  }
+
+  pub fn to_markdown(&self) -> String {
+    let mut markdown = format!("## `{}`\n", self.name);
+
+    if let Some(ref effect) = self.effect {
+      let _ = write!(markdown, "\n`{effect}`\n");
+    }
+
+    if let Some(ref doc) = self.doc {
+      let _ = write!(markdown, "\n{doc}\n");
+    }
+
+    markdown
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Docs {
+  pub title: String,
+  pub items: Vec<DocItem>,
+}
+
+impl Docs {
+  /// Documents what's defined in the scope of `context`.
+  pub fn from_context(title: String, context: &Context) -> Self {
+    let scope = context.scope();
+    let mut items = scope
+      .items
+      .keys()
+      .filter_map(|name| DocItem::from_scope(scope, *name))
+      .collect::<Vec<_>>();
+    items.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
+    Self { title, items }
+  }
+
+  /// Documents the functions of `module`.
+  pub fn from_module(module: &Module) -> Self {
+    let name = module.name();
+    let mut items = module
+      .func_names()
+      .map(|func| {
+        let meta = Meta::from_doc(module.doc(func).map(str::to_owned));
+
+        DocItem {
+          name: format!("{name}:{func}"),
+          effect: meta.effect,
+          doc: meta.doc,
+        }
+      })
+      .collect::<Vec<_>>();
+    items.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
+    Self {
+      title: name.as_str().to_owned(),
+      items,
+    }
+  }
+
+  pub fn to_markdown(&self) -> String {
+    let mut markdown = format!("# {}\n", self.title);
+
+    for item in self.items.iter() {
+      let _ = write!(markdown, "\n{}", item.to_markdown());
+    }
+
+    markdown
+  }
+
+  pub fn to_html(&self) -> String {
+    let mut html = String::new();
+    let title = escape(&self.title);
+
+    let _ = write!(
+      html,
+      "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+       <title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+
+    for item in self.items.iter() {
+      let name = escape(&item.name);
+      let _ = writeln!(html, "<h2 id=\"{name}\"><code>{name}</code></h2>");
+
+      if let Some(ref effect) = item.effect {
+        let _ =
+          writeln!(html, "<p><code>{}</code></p>", escape(&effect.to_string()));
+      }
+
+      if let Some(ref doc) = item.doc {
+        html.push_str(&doc_to_html(doc));
+      }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+  }
+}
+
+/// Renders the Markdown in a doc comment, if the Markdown renderer is
+/// available.
+#[cfg(feature = "stack-std")]
+fn doc_to_html(doc: &str) -> String {
+  stack_std::md::to_html(doc)
+}
+
+#[cfg(not(feature = "stack-std"))]
+fn doc_to_html(doc: &str) -> String {
+  format!("<pre>{}</pre>\n", escape(doc))
+}
+
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
@@ -0,0 +1,361 @@
+//! An interactive tutorial, which is run with `stack learn`.
+//!
+//! The lessons are stored as data in `lessons.toml`. Each lesson is some text
+//! followed by exercises, which are solved by writing code that leaves the
+//! expected stack behind. The code is run with the journal enabled, so a wrong
+//! answer can be explained by replaying what each step did.
+//!
+//! Progress is saved to `learn.toml` in the config directory, so a tutorial
+//! can be continued later.
+
+use core::fmt;
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use reedline::{DefaultPrompt, DefaultPromptSegment, Reedline, Signal};
+use stack_core::prelude::*;
+use toml_edit::{value, Array, Document, Item};
+
+const LESSONS: &str = include_str!("lessons.toml");
+
+/// The amount of operations an answer can run, so that a mistake such as an
+/// infinite loop doesn't hang the tutorial.
+const FUEL: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lesson {
+  pub name: String,
+  pub title: String,
+  pub text: String,
+  pub exercises: Vec<Exercise>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exercise {
+  pub prompt: String,
+  /// The stack which solves the exercise, written as data.
+  pub expected: String,
+  pub hint: String,
+}
+
+/// Returns the lessons, in order.
+///
+/// # Panics
+///
+/// Panics if `lessons.toml` is malformed.
+pub fn lessons() -> Vec<Lesson> {
+  let document = LESSONS.parse::<Document>().expect("valid lessons");
+  let field = |item: &Item, key: &str| {
+    item
+      .get(key)
+      .and_then(Item::as_str)
+      .unwrap_or_else(|| panic!("lessons are missing '{key}'"))
+      .trim()
+      .to_string()
+  };
+
+  document
+    .get("lesson")
+    .and_then(Item::as_array_of_tables)
+    .into_iter()
+    .flatten()
+    .map(|table| {
+      let item = Item::Table(table.clone());
+
+      Lesson {
+        name: field(&item, "name"),
+        title: field(&item, "title"),
+        text: field(&item, "text"),
+        exercises: item
+          .get("exercise")
+          .and_then(Item::as_array_of_tables)
+          .into_iter()
+          .flatten()
+          .map(|table| {
+            let item = Item::Table(table.clone());
+
+            Exercise {
+              prompt: field(&item, "prompt"),
+              expected: field(&item, "expected"),
+              hint: field(&item, "hint"),
+            }
+          })
+          .collect(),
+      }
+    })
+    .collect()
+}
+
+/// The exercises which have been solved, by lesson and index.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Progress {
+  pub solved: Vec<(String, usize)>,
+}
+
+impl Progress {
+  /// Returns the path progress is saved to, if there's a config directory.
+  pub fn path() -> Option<PathBuf> {
+    config_dir().map(|x| x.join("stack").join("learn.toml"))
+  }
+
+  /// Loads the progress from `path`, which is empty if it doesn't exist.
+  pub fn load(path: &Path) -> Result<Self, LearnError> {
+    let source = match fs::read_to_string(path) {
+      Ok(source) => source,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => {
+        return Ok(Self::default())
+      }
+      Err(e) => return Err(LearnError::Io(path.into(), e)),
+    };
+
+    let document = source
+      .parse::<Document>()
+      .map_err(|e| LearnError::Parse(path.into(), e))?;
+
+    let solved = document
+      .get("solved")
+      .and_then(Item::as_table_like)
+      .into_iter()
+      .flat_map(|table| table.iter())
+      .flat_map(|(lesson, item)| {
+        item
+          .as_array()
+          .into_iter()
+          .flatten()
+          .filter_map(|x| x.as_integer())
+          .map(move |x| (lesson.to_string(), x as usize))
+      })
+      .collect();
+
+    Ok(Self { solved })
+  }
+
+  pub fn save(&self, path: &Path) -> Result<(), LearnError> {
+    let mut document = Document::new();
+    document["solved"] = toml_edit::table();
+
+    for (lesson, index) in self.solved.iter() {
+      let item = &mut document["solved"][lesson.as_str()];
+
+      if item.is_none() {
+        *item = value(Array::new());
+      }
+
+      if let Some(array) = item.as_array_mut() {
+        array.push(*index as i64);
+      }
+    }
+
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir).map_err(|e| LearnError::Io(dir.into(), e))?;
+    }
+
+    fs::write(path, document.to_string())
+      .map_err(|e| LearnError::Io(path.into(), e))
+  }
+
+  pub fn is_solved(&self, lesson: &str, index: usize) -> bool {
+    self.solved.iter().any(|(x, i)| x == lesson && *i == index)
+  }
+
+  pub fn solve(&mut self, lesson: &str, index: usize) {
+    if !self.is_solved(lesson, index) {
+      self.solved.push((lesson.into(), index));
+    }
+  }
+}
+
+#[derive(Debug)]
+pub enum LearnError {
+  Io(PathBuf, io::Error),
+  Parse(PathBuf, toml_edit::TomlError),
+  Repl(io::Error),
+  UnknownLesson(String),
+}
+
+impl std::error::Error for LearnError {}
+
+impl fmt::Display for LearnError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(path, e) => write!(f, "{}: {e}", path.display()),
+      Self::Parse(path, e) => write!(f, "{}: {e}", path.display()),
+      Self::Repl(e) => write!(f, "{e}"),
+      Self::UnknownLesson(name) => write!(f, "unknown lesson '{name}'"),
+    }
+  }
+}
+
+/// The result of checking an answer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+  Solved,
+  /// The code ran, but left the wrong stack. Each step of the run is
+  /// described, oldest first.
+  WrongStack {
+    stack: Vec<Expr>,
+    steps: Vec<String>,
+  },
+  Failed(String),
+}
+
+/// Runs `code` and checks whether it solves `exercise`.
+pub fn check(engine: &Engine, exercise: &Exercise, code: &str) -> Outcome {
+  let mut lexer = Lexer::new(Source::new("learn", code));
+  let exprs = match parse(&mut lexer) {
+    Ok(exprs) => exprs,
+    Err(e) => return Outcome::Failed(e.to_string()),
+  };
+
+  let context = Context::new().with_journal(None);
+  let context = match engine.run(context, exprs) {
+    Ok(context) => context,
+    Err(e) => return Outcome::Failed(e.to_string()),
+  };
+
+  let mut lexer =
+    Lexer::new(Source::new("expected", exercise.expected.as_str()));
+  let expected = parse(&mut lexer).unwrap_or_default();
+
+  if context.stack() == expected.as_slice() {
+    return Outcome::Solved;
+  }
+
+  let steps = context
+    .journal()
+    .iter()
+    .flat_map(|journal| journal.entries())
+    .map(|entry| format!("{entry:#}"))
+    .filter(|entry| !entry.is_empty())
+    .collect();
+
+  Outcome::WrongStack {
+    stack: context.stack().to_vec(),
+    steps,
+  }
+}
+
+/// Runs the tutorial from the first unsolved exercise, or from the start of
+/// the lesson `start`.
+pub fn run(start: Option<&str>) -> Result<(), LearnError> {
+  let lessons = lessons();
+  let path = Progress::path();
+  let mut progress = match path {
+    Some(ref path) => Progress::load(path)?,
+    None => Progress::default(),
+  };
+
+  let skip = match start {
+    Some(name) => lessons
+      .iter()
+      .position(|lesson| lesson.name == name)
+      .ok_or_else(|| LearnError::UnknownLesson(name.into()))?,
+    None => lessons
+      .iter()
+      .position(|lesson| {
+        (0..lesson.exercises.len())
+          .any(|i| !progress.is_solved(&lesson.name, i))
+      })
+      .unwrap_or(lessons.len()),
+  };
+
+  let engine = Engine::new().with_fuel(Some(FUEL));
+  let mut repl = Reedline::create();
+  let prompt = DefaultPrompt::new(
+    DefaultPromptSegment::Basic("learn".into()),
+    DefaultPromptSegment::Empty,
+  );
+
+  println!("Commands: :hint, :skip, :lessons, :exit");
+
+  for lesson in lessons.iter().skip(skip) {
+    println!("\n# {}\n\n{}", lesson.title, lesson.text);
+
+    for (index, exercise) in lesson.exercises.iter().enumerate() {
+      if start.is_none() && progress.is_solved(&lesson.name, index) {
+        continue;
+      }
+
+      println!(
+        "\nExercise {}/{}: {}",
+        index + 1,
+        lesson.exercises.len(),
+        exercise.prompt
+      );
+
+      let mut attempts = 0;
+
+      loop {
+        let line = match repl.read_line(&prompt).map_err(LearnError::Repl)? {
+          Signal::Success(line) => line,
+          Signal::CtrlC | Signal::CtrlD => return Ok(()),
+        };
+
+        match line.trim() {
+          "" => continue,
+          ":exit" => return Ok(()),
+          ":skip" => break,
+          ":hint" => {
+            println!("hint: {}", exercise.hint);
+            continue;
+          }
+          ":lessons" => {
+            for lesson in lessons.iter() {
+              println!("{} ({})", lesson.title, lesson.name);
+            }
+
+            continue;
+          }
+          _ => {}
+        }
+
+        attempts += 1;
+
+        match check(&engine, exercise, &line) {
+          Outcome::Solved => {
+            println!("Correct!");
+
+            progress.solve(&lesson.name, index);
+            if let Some(ref path) = path {
+              progress.save(path)?;
+            }
+
+            break;
+          }
+          Outcome::WrongStack { stack, steps } => {
+            println!("Not quite. Expected: {}", exercise.expected);
+            print!("Found:");
+            stack.iter().for_each(|x| print!(" {x:#}"));
+            println!();
+
+            if !steps.is_empty() {
+              println!("Here's what your code did:");
+              steps.iter().for_each(|step| println!("  {step}"));
+            }
+          }
+          Outcome::Failed(e) => println!("error: {e}"),
+        }
+
+        // Give the hint once it's clear that it's needed.
+        if attempts == 2 {
+          println!("hint: {}", exercise.hint);
+        }
+      }
+    }
+  }
+
+  println!("\nYou've finished every lesson!");
+
+  Ok(())
+}
+
+fn config_dir() -> Option<PathBuf> {
+  let non_empty = |name| std::env::var_os(name).filter(|x| !x.is_empty());
+
+  non_empty("XDG_CONFIG_HOME")
+    .map(PathBuf::from)
+    .or_else(|| non_empty("APPDATA").map(PathBuf::from))
+    .or_else(|| non_empty("HOME").map(|x| PathBuf::from(x).join(".config")))
+}
@@ -1,14 +1,29 @@
 use core::fmt;
-use std::io::{self, prelude::Write};
+use std::{
+  io::{self, prelude::Write, IsTerminal},
+  sync::OnceLock,
+  time::Duration,
+};
 
 use crossterm::{
   cursor::{self, MoveTo},
   style::Print,
   terminal, QueueableCommand,
 };
-use stack_core::prelude::*;
+use stack_core::{
+  prelude::*,
+  pretty::{self, pretty, PrettyOptions},
+};
 
+pub mod bench;
+pub mod doc;
+pub mod learn;
+pub mod lsp;
+pub mod package;
+pub mod report;
 pub mod server;
+pub mod test;
+pub mod watch;
 
 pub fn ok_or_exit<T, E>(result: Result<T, E>) -> T
 where
@@ -23,24 +38,197 @@ where
   }
 }
 
-pub fn print_stack(context: &Context) {
-  print!("stack:");
+/// The exit code when the code fails to parse. Other failures exit with 1.
+pub const PARSE_ERROR_CODE: i32 = 2;
 
-  core::iter::repeat(" ")
-    .zip(context.stack())
-    .for_each(|(sep, x)| print!("{sep}{x:#}"));
+/// Returns the parsed [`Expr`]s, or prints the error and exits with
+/// [`PARSE_ERROR_CODE`].
+pub fn parsed_or_exit(result: Result<Vec<Expr>, ParseError>) -> Vec<Expr> {
+  match result {
+    Ok(x) => x,
+    Err(e) => {
+      eprintln!("error: {e}");
+      std::process::exit(PARSE_ERROR_CODE);
+    }
+  }
+}
+
+/// Returns the [`Context`] of a finished run, or otherwise exits with the code
+/// given to `exit`, or prints the error and exits with 1.
+pub fn ran_or_exit(result: Result<Context, RunError>) -> Context {
+  if let Some(code) = result.as_ref().err().and_then(RunError::exit_code) {
+    std::process::exit(code);
+  }
 
-  println!()
+  ok_or_exit(result)
 }
 
-pub fn eprint_stack(context: &Context) {
-  eprint!("stack:");
+/// How the final stack of a run is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+  /// The stack on one line, with colors.
+  #[default]
+  Pretty,
+  /// Each value on its own line, with strings unquoted, for piping.
+  Plain,
+  /// The stack as a JSON array of its values, as in [`stack_json`].
+  Json,
+}
+
+/// Returns the stack as a JSON array of its values, as printed by `--output
+/// json` and written by `--result-file`.
+///
+/// Values with no JSON equivalent, such as functions, are written as their
+/// source, and floats which aren't finite as the strings `"NaN"`,
+/// `"Infinity"` and `"-Infinity"`.
+pub fn stack_json(context: &Context) -> serde_json::Result<String> {
+  let values = context.stack().iter().map(json_value).collect();
+  serde_json::to_string_pretty(&serde_json::Value::Array(values))
+}
+
+/// Prints the stack in the given format.
+pub fn output_stack(context: &Context, format: OutputFormat) {
+  match format {
+    OutputFormat::Pretty => print_stack(context),
+    OutputFormat::Plain => {
+      for x in context.stack() {
+        match x.kind {
+          ExprKind::String(ref x) => println!("{x}"),
+          ref x => println!("{}", context.float_format().display(x)),
+        }
+      }
+    }
+    OutputFormat::Json => println!("{}", ok_or_exit(stack_json(context))),
+  }
+}
+
+/// Converts a value into JSON, writing values with no JSON equivalent, such as
+/// functions, as their source.
+#[cfg(feature = "stack-std")]
+fn json_value(x: &Expr) -> serde_json::Value {
+  stack_std::json::to_json_with(&x.kind, non_finite_json)
+    .unwrap_or_else(|| serde_json::Value::String(x.to_string()))
+}
+
+/// Names a float which isn't finite like JavaScript does, since JSON has no
+/// numbers for them.
+#[cfg(feature = "stack-std")]
+fn non_finite_json(x: f64) -> serde_json::Value {
+  let name = match x {
+    f64::INFINITY => "Infinity",
+    f64::NEG_INFINITY => "-Infinity",
+    _ => "NaN",
+  };
+
+  serde_json::Value::String(name.into())
+}
+
+#[cfg(not(feature = "stack-std"))]
+fn json_value(x: &Expr) -> serde_json::Value {
+  let mut x = x.clone();
+  x.recursively_strip_info();
+
+  serde_json::to_value(&x.kind)
+    .unwrap_or_else(|_| serde_json::Value::String(x.to_string()))
+}
+
+/// Returns how long a run took and how many expressions it evaluated, as
+/// printed by `run --time` and the REPL's `:time on`.
+pub fn timing_line(elapsed: Duration, exprs: usize) -> String {
+  let plural = if exprs == 1 { "" } else { "s" };
+  format!("time: {elapsed:.2?}, {exprs} expr{plural} evaluated")
+}
+
+/// Returns how many expressions have been evaluated with the [`Context`] of a
+/// run, whether it finished or not.
+pub fn ops_of(result: &Result<Context, RunError>) -> usize {
+  match result {
+    Ok(context) => context.ops(),
+    Err(e) => e.context.ops(),
+  }
+}
 
-  core::iter::repeat(" ")
-    .zip(context.stack())
-    .for_each(|(sep, x)| eprint!("{sep}{x:#}"));
+/// When to color the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ColorMode {
+  /// When printing to a terminal, unless `NO_COLOR` is set.
+  #[default]
+  Auto,
+  Always,
+  Never,
+}
 
-  eprintln!()
+impl ColorMode {
+  pub fn enabled(self) -> bool {
+    match self {
+      Self::Auto => {
+        std::env::var_os("NO_COLOR").is_none_or(|x| x.is_empty())
+          && io::stdout().is_terminal()
+      }
+      Self::Always => true,
+      Self::Never => false,
+    }
+  }
+}
+
+/// Returns the width of the terminal, or 80 if stdout isn't one.
+pub fn terminal_width() -> usize {
+  match io::stdout().is_terminal() {
+    true => terminal::size().map_or(80, |(width, _)| width as usize),
+    false => 80,
+  }
+}
+
+static PRETTY_OPTIONS: OnceLock<PrettyOptions> = OnceLock::new();
+
+/// Sets how [`print_stack`] and [`eprint_stack`] print values. It can only be
+/// set once, before anything is printed.
+pub fn set_pretty_options(options: PrettyOptions) {
+  let _ = PRETTY_OPTIONS.set(options);
+}
+
+/// Returns the stack pretty-printed, on one line if it fits, or otherwise with
+/// each value on its own lines.
+pub fn pretty_stack(context: &Context) -> String {
+  let options = PrettyOptions {
+    float: context.float_format(),
+    ..PRETTY_OPTIONS.get().copied().unwrap_or_default()
+  };
+  let stack = context.stack();
+  let mut out = String::from("stack:");
+
+  let width = out.len()
+    + stack
+      .iter()
+      .map(|x| 1 + pretty::width(x, options))
+      .sum::<usize>();
+
+  if width <= options.max_width {
+    for x in stack {
+      out.push(' ');
+      out.push_str(&pretty(x, options));
+    }
+  } else {
+    let options = PrettyOptions {
+      max_width: options.max_width.saturating_sub(2),
+      ..options
+    };
+
+    for x in stack {
+      out.push_str("\n  ");
+      out.push_str(&pretty(x, options).replace('\n', "\n  "));
+    }
+  }
+
+  out
+}
+
+pub fn print_stack(context: &Context) {
+  println!("{}", pretty_stack(context));
+}
+
+pub fn eprint_stack(context: &Context) {
+  eprintln!("{}", pretty_stack(context));
 }
 
 pub fn clear_screen() -> io::Result<()> {
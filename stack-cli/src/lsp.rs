@@ -0,0 +1,537 @@
+use core::str::FromStr;
+use std::{
+  collections::HashMap,
+  io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+use stack_core::{
+  internal::scope::Meta, lexer::Span, lint, parser::ParsedSource, prelude::*,
+};
+
+const BUILTINS: &str = include_str!("../../docs/src/reference/builtins.md");
+
+/// Runs a language server over STDIN and STDOUT until the client exits.
+pub fn serve(engine: &Engine) -> io::Result<()> {
+  let mut reader = io::stdin().lock();
+  let mut writer = io::stdout().lock();
+
+  let mut server = Server {
+    engine,
+    documents: HashMap::new(),
+    shutdown: false,
+  };
+
+  while let Some(message) = read_message(&mut reader)? {
+    let method = message["method"].as_str().unwrap_or_default();
+    let params = &message["params"];
+
+    match message.get("id") {
+      Some(id) => {
+        let response = match server.request(method, params) {
+          Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+          Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+          }),
+        };
+
+        write_message(&mut writer, &response)?;
+      }
+      None if method == "exit" => break,
+      None => {
+        for notification in server.notify(method, params) {
+          write_message(&mut writer, &notification)?;
+        }
+      }
+    }
+  }
+
+  if !server.shutdown {
+    std::process::exit(1);
+  }
+
+  Ok(())
+}
+
+// MARK: Transport
+
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+  let mut length = None;
+
+  loop {
+    let mut line = String::new();
+
+    if reader.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+
+    let line = line.trim_end();
+
+    if line.is_empty() {
+      break;
+    }
+
+    if let Some(x) = line.strip_prefix("Content-Length:") {
+      length = x.trim().parse::<usize>().ok();
+    }
+  }
+
+  let length = length.ok_or_else(|| {
+    io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+  })?;
+
+  let mut body = vec![0; length];
+  reader.read_exact(&mut body)?;
+
+  serde_json::from_slice(&body)
+    .map(Some)
+    .map_err(io::Error::from)
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+  let body = message.to_string();
+
+  write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+  writer.flush()
+}
+
+// MARK: Server
+
+fn diagnostics(document: &ParsedSource) -> Value {
+  let source = document.source();
+  let diagnostics = document
+    .error()
+    .iter()
+    .map(|error| {
+      json!({
+        "range": range(source.source(), error.kind.token().span),
+        "severity": 1,
+        "source": "stack",
+        "message": error.kind.to_string(),
+      })
+    })
+    .chain(lint::lint(document.exprs()).into_iter().filter_map(|lint| {
+      let info = lint.expr.info?;
+
+      Some(json!({
+        "range": range(source.source(), info.span),
+        "severity": 2,
+        "source": "stack",
+        "message": lint.kind.to_string(),
+      }))
+    }))
+    .collect::<Vec<_>>();
+
+  json!({ "uri": source.name(), "diagnostics": diagnostics })
+}
+
+struct Server<'a> {
+  engine: &'a Engine,
+  documents: HashMap<String, ParsedSource>,
+  shutdown: bool,
+}
+
+impl Server<'_> {
+  fn request(
+    &mut self,
+    method: &str,
+    params: &Value,
+  ) -> Result<Value, (i64, String)> {
+    match method {
+      "initialize" => Ok(json!({
+        "capabilities": {
+          "textDocumentSync": 2,
+          "hoverProvider": true,
+          "definitionProvider": true,
+          "completionProvider": { "triggerCharacters": [":"] },
+        },
+        "serverInfo": {
+          "name": "stack",
+          "version": env!("CARGO_PKG_VERSION"),
+        },
+      })),
+      "shutdown" => {
+        self.shutdown = true;
+        Ok(Value::Null)
+      }
+      "textDocument/hover" => Ok(self.hover(params).unwrap_or(Value::Null)),
+      "textDocument/definition" => {
+        Ok(self.definition(params).unwrap_or(Value::Null))
+      }
+      "textDocument/completion" => Ok(self.completion(params)),
+      _ => Err((-32601, format!("unknown method '{method}'"))),
+    }
+  }
+
+  fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+    let Some(uri) = params["textDocument"]["uri"].as_str() else {
+      return Vec::new();
+    };
+
+    match method {
+      "textDocument/didOpen" => {
+        let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+        self
+          .documents
+          .insert(uri.into(), ParsedSource::new(Source::new(uri, text)));
+      }
+      "textDocument/didChange" => {
+        let Some(document) = self.documents.get_mut(uri) else {
+          return Vec::new();
+        };
+
+        let changes = params["contentChanges"].as_array();
+        for change in changes.into_iter().flatten() {
+          let text = change["text"].as_str().unwrap_or_default();
+          let source = document.source().source();
+
+          match (
+            offset(source, &change["range"]["start"]),
+            offset(source, &change["range"]["end"]),
+          ) {
+            (Some(start), Some(end)) if start <= end => {
+              document.edit(start..end, text)
+            }
+            _ => *document = ParsedSource::new(Source::new(uri, text)),
+          }
+        }
+      }
+      "textDocument/didClose" => {
+        self.documents.remove(uri);
+
+        return vec![json!({
+          "jsonrpc": "2.0",
+          "method": "textDocument/publishDiagnostics",
+          "params": { "uri": uri, "diagnostics": [] },
+        })];
+      }
+      _ => return Vec::new(),
+    }
+
+    self
+      .documents
+      .get(uri)
+      .map(|document| {
+        json!({
+          "jsonrpc": "2.0",
+          "method": "textDocument/publishDiagnostics",
+          "params": diagnostics(document),
+        })
+      })
+      .into_iter()
+      .collect()
+  }
+
+  fn document_at(&self, params: &Value) -> Option<(&ParsedSource, usize)> {
+    let uri = params["textDocument"]["uri"].as_str()?;
+    let document = self.documents.get(uri)?;
+    let offset = offset(document.source().source(), &params["position"])?;
+
+    Some((document, offset))
+  }
+
+  fn hover(&self, params: &Value) -> Option<Value> {
+    let (document, offset) = self.document_at(params)?;
+    let (expr, symbol) = symbol_at(document.exprs(), offset)?;
+    let name = symbol.as_str();
+
+    let contents = if Intrinsic::from_str(name).is_ok() {
+      builtin_docs(name)
+        .map(str::to_owned)
+        .unwrap_or_else(|| format!("`{name}` (intrinsic)"))
+    } else if let Some(definition) =
+      find_definition(document.exprs(), symbol, offset)
+    {
+      let info = definition.name.info.as_ref()?;
+      let line = document
+        .source()
+        .location(info.span.start)
+        .and_then(|location| document.source().line(location.line))
+        .unwrap_or_default()
+        .trim();
+
+      let mut contents = format!("```clj\n{line}\n```");
+
+      let info = definition.value.unwrap_or(definition.name).info.as_ref();
+      let effect = info.and_then(|info| Meta::from_doc(info.doc()).effect);
+
+      if let Some(effect) = effect {
+        contents.push_str(&format!("\n\n`{effect}`"));
+      }
+
+      let comments = info
+        .map(|info| info.leading_comments().collect::<Vec<_>>().join("\n"))
+        .unwrap_or_default();
+
+      if !comments.is_empty() {
+        contents.push_str("\n\n");
+        contents.push_str(&comments);
+      }
+
+      contents
+    } else if self.is_module_func(name) {
+      format!("`{name}` (module function)")
+    } else {
+      return None;
+    };
+
+    let span = expr.info.as_ref()?.span;
+
+    Some(json!({
+      "contents": { "kind": "markdown", "value": contents },
+      "range": range(document.source().source(), span),
+    }))
+  }
+
+  fn definition(&self, params: &Value) -> Option<Value> {
+    let (document, offset) = self.document_at(params)?;
+    let (_, symbol) = symbol_at(document.exprs(), offset)?;
+    let definition = find_definition(document.exprs(), symbol, offset)?;
+    let span = definition.name.info.as_ref()?.span;
+
+    Some(json!({
+      "uri": document.source().name(),
+      "range": range(document.source().source(), span),
+    }))
+  }
+
+  fn completion(&self, params: &Value) -> Value {
+    let mut items = HashMap::new();
+
+    for name in Intrinsic::all_as_slice() {
+      items.insert(name.to_string(), 14);
+    }
+
+    for module in self.engine.modules() {
+      for func in module.func_names() {
+        items.insert(format!("{}:{func}", module.name()), 3);
+      }
+    }
+
+    if let Some((document, _)) = self.document_at(params) {
+      let mut definitions = Vec::new();
+      definitions_in(document.exprs(), &mut definitions);
+
+      for definition in definitions {
+        items.entry(definition.symbol.to_string()).or_insert(6);
+      }
+    }
+
+    let mut items = items
+      .into_iter()
+      .map(|(label, kind)| json!({ "label": label, "kind": kind }))
+      .collect::<Vec<_>>();
+    items.sort_by(|a, b| a["label"].as_str().cmp(&b["label"].as_str()));
+
+    Value::Array(items)
+  }
+
+  fn is_module_func(&self, name: &str) -> bool {
+    name.split_once(':').is_some_and(|(module, func)| {
+      self
+        .engine
+        .module(&Symbol::from_ref(module))
+        .is_some_and(|module| module.func(Symbol::from_ref(func)).is_some())
+    })
+  }
+}
+
+// MARK: Analysis
+
+/// Returns the section of the built-in docs for an intrinsic.
+fn builtin_docs(name: &str) -> Option<&'static str> {
+  let heading = format!("(`{name}`)");
+  let start =
+    BUILTINS
+      .match_indices("\n### ")
+      .map(|(i, _)| i + 1)
+      .find(|&i| {
+        BUILTINS[i..]
+          .lines()
+          .next()
+          .is_some_and(|line| line.ends_with(&heading))
+      })?;
+
+  let end = BUILTINS[start + 1..]
+    .find("\n#")
+    .map(|i| start + 1 + i)
+    .unwrap_or(BUILTINS.len());
+
+  Some(BUILTINS[start..end].trim())
+}
+
+fn children(expr: &Expr) -> Vec<&Expr> {
+  match &expr.kind {
+    ExprKind::Lazy(x) => vec![x],
+    ExprKind::List(x) => x.iter().collect(),
+    ExprKind::Function { body: x, .. } | ExprKind::SExpr { body: x, .. } => {
+      x.iter().collect()
+    }
+    ExprKind::Record(x) => x.values().collect(),
+    _ => Vec::new(),
+  }
+}
+
+/// Returns the innermost symbol at `offset`, along with its [`Expr`].
+fn symbol_at(exprs: &[Expr], offset: usize) -> Option<(&Expr, Symbol)> {
+  exprs.iter().find_map(|expr| {
+    let span = expr.info.as_ref()?.span;
+
+    if offset < span.start || offset > span.end {
+      return None;
+    }
+
+    if let Some(x) = children(expr)
+      .into_iter()
+      .find_map(|x| symbol_at(core::slice::from_ref(x), offset))
+    {
+      return Some(x);
+    }
+
+    match expr.kind {
+      ExprKind::Symbol(x) => Some((expr, x)),
+      // The call of an s-expression directly follows the parenthesis.
+      ExprKind::SExpr { call, .. }
+        if offset <= span.start + 1 + call.as_str().len() =>
+      {
+        Some((expr, call))
+      }
+      _ => None,
+    }
+  })
+}
+
+struct Definition<'a> {
+  symbol: Symbol,
+  name: &'a Expr,
+  value: Option<&'a Expr>,
+}
+
+/// Finds the symbols defined via `def` and `let`.
+fn definitions_in<'a>(
+  exprs: &'a [Expr],
+  definitions: &mut Vec<Definition<'a>>,
+) {
+  let is_call = |expr: Option<&Expr>, name: &str| matches!(expr.map(|x| &x.kind), Some(ExprKind::Symbol(x)) if x.as_str() == name);
+
+  for (i, expr) in exprs.iter().enumerate() {
+    match &expr.kind {
+      // '(fn ...) 'name def
+      ExprKind::Lazy(x) if is_call(exprs.get(i + 1), "def") => {
+        if let ExprKind::Symbol(symbol) = x.kind {
+          definitions.push(Definition {
+            symbol,
+            name: x,
+            value: i.checked_sub(1).and_then(|i| exprs.get(i)),
+          });
+        }
+      }
+      // '(...) [a b] let
+      ExprKind::List(x) if is_call(exprs.get(i + 1), "let") => {
+        for name in x {
+          if let ExprKind::Symbol(symbol) = name.kind {
+            definitions.push(Definition {
+              symbol,
+              name,
+              value: None,
+            });
+          }
+        }
+      }
+      // (def 'name ...)
+      ExprKind::SExpr { call, body } if call.as_str() == "def" => {
+        for x in body {
+          if let ExprKind::Lazy(name) = &x.kind {
+            if let ExprKind::Symbol(symbol) = name.kind {
+              definitions.push(Definition {
+                symbol,
+                name,
+                value: body.iter().find(|y| !core::ptr::eq(*y, x)),
+              });
+              break;
+            }
+          }
+        }
+      }
+      _ => {}
+    }
+
+    for child in children(expr) {
+      definitions_in(core::slice::from_ref(child), definitions);
+    }
+  }
+}
+
+/// Returns the last definition of `symbol` before `offset`, or the first
+/// definition if there are none before it.
+fn find_definition(
+  exprs: &[Expr],
+  symbol: Symbol,
+  offset: usize,
+) -> Option<Definition<'_>> {
+  let mut definitions = Vec::new();
+  definitions_in(exprs, &mut definitions);
+
+  let start = |definition: &Definition| {
+    definition.name.info.as_ref().map(|info| info.span.start)
+  };
+
+  let mut definitions = definitions
+    .into_iter()
+    .filter(|definition| definition.symbol == symbol)
+    .collect::<Vec<_>>();
+  definitions.sort_by_key(start);
+
+  let index = definitions
+    .iter()
+    .rposition(|definition| start(definition).is_some_and(|x| x <= offset))
+    .unwrap_or(0);
+
+  (index < definitions.len()).then(|| definitions.swap_remove(index))
+}
+
+// MARK: Positions
+
+/// Converts a byte index into a position, with UTF-16 columns.
+fn position(source: &str, index: usize) -> Value {
+  let before = source.get(..index.min(source.len())).unwrap_or(source);
+  let line = before.matches('\n').count();
+  let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+  let character = before[line_start..]
+    .chars()
+    .map(char::len_utf16)
+    .sum::<usize>();
+
+  json!({ "line": line, "character": character })
+}
+
+fn range(source: &str, span: Span) -> Value {
+  json!({
+    "start": position(source, span.start),
+    "end": position(source, span.end),
+  })
+}
+
+/// Converts a position, with UTF-16 columns, into a byte index.
+fn offset(source: &str, position: &Value) -> Option<usize> {
+  let line = position["line"].as_u64()? as usize;
+  let character = position["character"].as_u64()? as usize;
+
+  let line_start = match line {
+    0 => 0,
+    line => source.match_indices('\n').nth(line - 1)?.0 + 1,
+  };
+
+  let mut units = 0;
+  for (i, c) in source[line_start..].char_indices() {
+    if units >= character || c == '\n' {
+      return Some(line_start + i);
+    }
+
+    units += c.len_utf16();
+  }
+
+  Some(source.len())
+}
@@ -1,10 +1,13 @@
+use core::str::FromStr;
 use std::{
-  io::Read,
+  collections::HashSet,
+  io::{BufReader, BufWriter, LineWriter, Read, Write},
   path::{Path, PathBuf},
-  sync::Arc,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
 };
 
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, Parser};
 use codespan_reporting::{
   diagnostic::{Diagnostic, Label},
   files::SimpleFiles,
@@ -13,46 +16,233 @@ use codespan_reporting::{
     termcolor::{ColorChoice, StandardStream},
   },
 };
-use notify::{
-  Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
-};
 use reedline::{DefaultPrompt, DefaultPromptSegment, Reedline, Signal};
 use stack_cli::{
-  clear_screen, eprint_stack, ok_or_exit, print_stack, server::listen,
+  bench::{Allocations, BenchReport, CountingAlloc, Sample},
+  clear_screen,
+  doc::{DocItem, Docs},
+  eprint_stack, learn, lsp, ok_or_exit, ops_of, output_stack,
+  package::{Manifest, MANIFEST_NAME},
+  parsed_or_exit, print_stack, ran_or_exit,
+  report::{Limits, LimitsReport},
+  server::listen,
+  set_pretty_options, stack_json, terminal_width,
+  test::{self, TestOptions, TestReport},
+  timing_line,
+  watch::{run_hook, source_files, status_line, ClearMode, FileWatcher},
+  ColorMode, OutputFormat, PARSE_ERROR_CODE,
+};
+use stack_core::{
+  breakpoint::{expand_break_directives, BreakpointHook},
+  float::FloatFormat,
+  format::{format_exprs, FormatOptions},
+  import, lint,
+  prelude::*,
+  pretty::PrettyOptions,
+  profile::Profiler,
+  trace::Tracer,
 };
-use stack_core::prelude::*;
+#[cfg(feature = "stack-std")]
+use stack_std::sandbox::SandboxPolicy;
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
 
 fn main() {
-  let cli = Cli::parse();
+  let mut cli = Cli::parse();
 
+  // `stack -e <code>` is short for `stack run -e <code>`.
+  if cli.subcommand.is_none() {
+    if cli.exprs.is_empty() {
+      Cli::command()
+        .error(
+          ErrorKind::MissingSubcommand,
+          "a subcommand or `-e` is needed",
+        )
+        .exit();
+    }
+
+    cli.subcommand = Some(Subcommand::run_exprs(
+      core::mem::take(&mut cli.exprs),
+      core::mem::take(&mut cli.args),
+    ));
+  }
+
+  let color = cli.color.enabled();
+  let color_choice = match color {
+    true => ColorChoice::Always,
+    false => ColorChoice::Never,
+  };
+  if !color {
+    yansi::disable();
+  }
+  set_pretty_options(PrettyOptions {
+    color,
+    max_width: terminal_width(),
+    max_string: cli.max_string,
+    max_items: cli.max_items,
+    ..Default::default()
+  });
+
+  let intrinsics = intrinsic_filter(&cli);
+  let manifest = ok_or_exit(find_manifest(&cli));
   let new_context = || {
-    if cli.journal {
+    let context = if cli.journal {
       Context::new().with_journal(Some(cli.journal_length.unwrap_or(20)))
     } else {
       Context::new()
-    }
+    };
+
+    context
+      .with_intrinsics(intrinsics.clone())
+      .with_float_format(cli.float_format)
   };
 
-  let mut engine =
-    Engine::new().with_debug_hook(Some(Arc::new(|s| eprintln!("{s}"))));
+  let mut engine = Engine::new()
+    .with_search_paths(
+      cli
+        .include
+        .iter()
+        .cloned()
+        .chain(import::env_search_paths())
+        .collect(),
+    )
+    .with_packages(
+      manifest
+        .as_ref()
+        .map(Manifest::packages)
+        .unwrap_or_default(),
+    )
+    .with_debug_hook(Some(Arc::new(|s| eprintln!("{s}"))))
+    .with_breakpoint_hook(breakpoint_hook(&cli))
+    .with_watch_hook(Some(Arc::new(|event, _| eprintln!("{event:#}"))))
+    .with_tracer(ok_or_exit(tracer(&cli)))
+    .with_effect_checks(cli.check_effects);
+  for name in cli.watchpoints.iter() {
+    engine.add_watchpoint(Symbol::from_ref(name.as_str()));
+  }
   let mut context = new_context();
 
   #[cfg(feature = "stack-std")]
   {
-    if cli.enable_all || cli.enable_str {
+    let policy = sandbox_policy(&cli);
+    let enable_all = enables_all(&cli);
+    let args = match cli.subcommand {
+      Some(Subcommand::Run { ref args, .. }) => args.clone(),
+      _ => Vec::new(),
+    };
+
+    if enable_all || cli.enable_str {
       engine.add_module(stack_std::str::module());
     }
 
-    if cli.enable_all || cli.enable_fs {
-      engine.add_module(stack_std::fs::module(cli.sandbox));
+    if enable_all || cli.enable_fs {
+      engine.add_module(stack_std::fs::module(&policy));
     }
 
-    if cli.enable_all || cli.enable_scope {
+    if enable_all || cli.enable_scope {
       engine.add_module(stack_std::scope::module());
     }
+
+    if enable_all || cli.enable_sb {
+      engine.add_module(stack_std::sb::module());
+    }
+
+    if enable_all || cli.enable_rope {
+      engine.add_module(stack_std::rope::module());
+    }
+
+    if enable_all || cli.enable_template {
+      engine.add_module(stack_std::template::module());
+    }
+
+    if enable_all || cli.enable_md {
+      engine.add_module(stack_std::md::module());
+    }
+
+    if enable_all || cli.enable_json {
+      engine.add_module(stack_std::json::module());
+    }
+
+    if enable_all || cli.enable_toml {
+      engine.add_module(stack_std::toml::module());
+    }
+
+    if enable_all || cli.enable_yaml {
+      engine.add_module(stack_std::yaml::module());
+    }
+
+    if enable_all || cli.enable_csv {
+      engine.add_module(stack_std::csv::module());
+    }
+
+    if enable_all || cli.enable_proc {
+      engine.add_module(stack_std::proc::module(&policy));
+    }
+
+    if enable_all || cli.enable_env {
+      engine.add_module(stack_std::env::module(&policy, args.clone()));
+    }
+
+    if enable_all || cli.enable_io {
+      engine.add_module(stack_std::io::module());
+    }
+
+    if enable_all || cli.enable_time {
+      engine.add_module(stack_std::time::module());
+    }
+
+    if enable_all || cli.enable_rand {
+      engine.add_module(stack_std::rand::module());
+    }
+
+    if enable_all || cli.enable_math {
+      engine.add_module(stack_std::math::module());
+    }
+
+    if enable_all || cli.enable_hash {
+      engine.add_module(stack_std::hash::module());
+    }
+
+    if enable_all || cli.enable_encode {
+      engine.add_module(stack_std::encode::module());
+    }
+
+    if enable_all || cli.enable_task {
+      engine.add_module(stack_std::task::module());
+      engine.add_module(stack_std::chan::module());
+    }
+
+    // Imports are run by the engine rather than a module, so they're kept
+    // within the sandbox separately.
+    engine.set_import_guard(policy.import_guard());
+
+    // The modules which aren't enabled can still be imported by name.
+    for (name, loader) in stack_std::loaders(&policy, args) {
+      engine.add_module_loader(name, loader);
+    }
   }
 
-  match cli.subcommand {
+  for path in cli.plugins.iter() {
+    // SAFETY: Plugins are trusted, the same as the stack binary itself.
+    ok_or_exit(unsafe { engine.load_plugin(path) });
+  }
+
+  let output_result = |context: &Context| {
+    if let Some(path) = &cli.result_file {
+      ok_or_exit(std::fs::write(path, ok_or_exit(stack_json(context))));
+    }
+
+    if !cli.quiet {
+      output_stack(context, cli.output);
+    }
+  };
+
+  let Some(subcommand) = cli.subcommand.take() else {
+    unreachable!("the subcommand defaults to `run`");
+  };
+
+  match subcommand {
     Subcommand::Stdin => {
       let mut stdin = std::io::stdin();
       let mut source = String::new();
@@ -61,13 +251,16 @@ fn main() {
 
       let source = Source::new("stdin", source);
       let mut lexer = Lexer::new(source);
-      let exprs = ok_or_exit(parse(&mut lexer));
+      let mut exprs = parsed_or_exit(parse(&mut lexer));
+      ok_or_exit(context.check_intrinsics(&exprs));
+      expand_break_directives(&mut exprs);
 
-      context = ok_or_exit(engine.run(context, exprs));
-      print_stack(&context);
+      context = ran_or_exit(engine.run(context, exprs));
+      output_result(&context);
     }
     Subcommand::Repl => {
       let mut repl = Reedline::create();
+      let mut time = false;
       let prompt = DefaultPrompt::new(
         DefaultPromptSegment::Empty,
         DefaultPromptSegment::Empty,
@@ -82,32 +275,86 @@ fn main() {
             break;
           }
           Signal::Success(line) => {
-            if line.starts_with(':') {
-              match &line.as_str()[1..] {
-                "exit" => break,
-                "clear" => {
+            if let Some(command) = line.strip_prefix(':') {
+              let (command, arg) = match command.split_once(' ') {
+                Some((command, arg)) => (command, Some(arg.trim())),
+                None => (command, None),
+              };
+
+              match (command, arg) {
+                ("exit", None) => break,
+                ("clear", None) => {
                   ok_or_exit(repl.clear_screen());
                 }
-                "reset" => {
+                ("reset", None) => {
                   context = new_context();
                   println!("Reset context");
                 }
-                command => eprintln!("error: unknown command '{command}'"),
+                ("save-state", Some(path)) => {
+                  match context.snapshot().save(path) {
+                    Ok(()) => println!("Saved state to {path}"),
+                    Err(e) => eprintln!("error: {e}"),
+                  }
+                }
+                ("load-state", Some(path)) => match Snapshot::load(path) {
+                  Ok(snapshot) => {
+                    context.restore(snapshot);
+                    println!("Loaded state from {path}");
+                    print_stack(&context);
+                  }
+                  Err(e) => eprintln!("error: {e}"),
+                },
+                ("doc", Some(name)) => {
+                  let name = Symbol::from_ref(name);
+
+                  match DocItem::from_scope(context.scope(), name) {
+                    Some(item) => print!("{}", item.to_markdown()),
+                    None => eprintln!("error: '{name}' isn't defined"),
+                  }
+                }
+                ("save-state" | "load-state", None) => {
+                  eprintln!("error: ':{command}' needs a file path")
+                }
+                ("time", None) => {
+                  time = !time;
+                  println!("Timing is {}", if time { "on" } else { "off" });
+                }
+                ("time", Some("on" | "off")) => {
+                  time = arg == Some("on");
+                  println!("Timing is {}", if time { "on" } else { "off" });
+                }
+                ("time", Some(_)) => {
+                  eprintln!("error: ':time' takes 'on' or 'off'")
+                }
+                ("doc", None) => eprintln!("error: ':doc' needs a name"),
+                _ => eprintln!("error: unknown command '{line}'"),
               }
             } else {
               let source = Source::new("repl", line);
               let mut lexer = Lexer::new(source);
               let exprs = ok_or_exit(parse(&mut lexer));
 
-              context = match engine.run(context, exprs) {
+              let ops = context.ops();
+              let start = Instant::now();
+              let result = engine.run(context, exprs);
+
+              if time {
+                let exprs = ops_of(&result) - ops;
+                eprintln!("{}", timing_line(start.elapsed(), exprs));
+              }
+
+              context = match result {
                 Ok(context) => {
                   print_stack(&context);
                   context
                 }
+                Err(e) if e.reason == RunErrorReason::Exit => {
+                  std::process::exit(e.exit_code().unwrap_or(1))
+                }
                 Err(e) => {
                   eprintln!("error: {e}");
                   eprint_stack(&e.context);
-                  e.context
+                  *e.context
                 }
               }
             }
@@ -115,21 +362,181 @@ fn main() {
         }
       }
     }
-    Subcommand::Run { input, watch } => {
+    Subcommand::Run {
+      input,
+      exprs,
+      limits_report: true,
+      fuel,
+      stack_limit,
+      timeout,
+      ..
+    } => {
+      let limits = Limits {
+        fuel,
+        stack_limit,
+        timeout_ms: timeout,
+      };
+      let mut engine = engine.with_fuel(fuel).with_stack_limit(stack_limit);
+
+      let source = ok_or_exit(run_source(input.as_deref(), &exprs));
+      context.add_source(source.clone());
+
+      let mut lexer = Lexer::new(source);
+      let (report, code) = match parse(&mut lexer) {
+        Ok(exprs) => {
+          let start = Instant::now();
+          let result = match (context.check_intrinsics(&exprs), timeout) {
+            (Err(e), _) => Err(e),
+            (Ok(()), Some(timeout)) => engine.run_with_timeout(
+              context,
+              exprs,
+              Duration::from_millis(timeout),
+            ),
+            (Ok(()), None) => engine.run(context, exprs),
+          };
+
+          let code = match result {
+            Ok(_) => 0,
+            Err(ref e) => e.exit_code().unwrap_or(1),
+          };
+
+          (
+            LimitsReport::from_run(limits, result, start.elapsed()),
+            code,
+          )
+        }
+        Err(e) => {
+          (LimitsReport::from_parse_error(limits, &e), PARSE_ERROR_CODE)
+        }
+      };
+
+      println!("{}", ok_or_exit(serde_json::to_string_pretty(&report)));
+
+      if code != 0 {
+        std::process::exit(code);
+      }
+    }
+    Subcommand::Run {
+      input,
+      exprs,
+      watch: false,
+      checkpoint: Some(checkpoint),
+      checkpoint_every,
+      fuel,
+      stack_limit,
+      ..
+    } => {
+      let engine = engine.with_fuel(fuel).with_stack_limit(stack_limit);
+
+      let source = ok_or_exit(run_source(input.as_deref(), &exprs));
+      let mut lexer = Lexer::new(source);
+      let mut exprs = parsed_or_exit(parse(&mut lexer));
+      ok_or_exit(context.check_intrinsics(&exprs));
+      expand_break_directives(&mut exprs);
+
+      context = ok_or_exit(run_checkpointed(
+        engine,
+        Checkpoint { context, exprs },
+        &checkpoint,
+        checkpoint_every,
+      ));
+      output_result(&context);
+    }
+    Subcommand::Resume {
+      snapshot,
+      checkpoint_every,
+    } => {
+      let file = ok_or_exit(std::fs::File::open(&snapshot));
+      let checkpoint =
+        ok_or_exit(serde_json::from_reader(BufReader::new(file)));
+
+      context = ok_or_exit(run_checkpointed(
+        engine,
+        checkpoint,
+        &snapshot,
+        checkpoint_every,
+      ));
+      output_result(&context);
+    }
+    Subcommand::Run {
+      input,
+      exprs,
+      watch,
+      clear,
+      on_success,
+      on_failure,
+      fuel,
+      stack_limit,
+      timeout,
+      report,
+      profile,
+      profile_folded,
+      stats,
+      time,
+      ..
+    } => {
+      let profiler = (profile || profile_folded.is_some()).then(Profiler::new);
+      let mut engine = engine
+        .with_fuel(fuel)
+        .with_stack_limit(stack_limit)
+        .with_profiler(profiler.clone());
+
       if !watch {
-        let source = ok_or_exit(Source::from_path(input));
+        let source = ok_or_exit(run_source(input.as_deref(), &exprs));
+        context.add_source(source.clone());
+
         let mut lexer = Lexer::new(source);
-        let exprs = ok_or_exit(parse(&mut lexer));
+        let mut exprs = parsed_or_exit(parse(&mut lexer).inspect_err(|e| {
+          save_report(report.as_deref(), || ReportBundle::from_parse_error(e))
+        }));
+        ok_or_exit(context.check_intrinsics(&exprs));
+        expand_break_directives(&mut exprs);
 
-        context = ok_or_exit(engine.run(context, exprs));
-        print_stack(&context);
-      } else {
-        let (tx, rx) = std::sync::mpsc::channel();
+        let ops = context.ops();
+        let start = Instant::now();
+        let result = match timeout {
+          Some(timeout) => engine.run_with_timeout(
+            context,
+            exprs,
+            Duration::from_millis(timeout),
+          ),
+          None if stats => {
+            let (result, stats) = engine.run_with_stats(context, exprs);
+            eprintln!("{stats}");
 
-        let mut watcher =
-          ok_or_exit(RecommendedWatcher::new(tx, Config::default()));
-        ok_or_exit(watcher.watch(&input, RecursiveMode::NonRecursive));
+            result
+          }
+          None => engine.run(context, exprs),
+        };
 
+        if time {
+          let exprs = ops_of(&result) - ops;
+          eprintln!("{}", timing_line(start.elapsed(), exprs));
+        }
+
+        if let Some(profiler) = profiler {
+          if profile {
+            eprintln!("{}", profiler.profile());
+          }
+
+          if let Some(path) = profile_folded {
+            ok_or_exit(std::fs::write(path, profiler.folded()));
+          }
+        }
+
+        context = ran_or_exit(result.inspect_err(|e| {
+          if e.exit_code().is_none() {
+            save_report(report.as_deref(), || ReportBundle::from_run_error(e))
+          }
+        }));
+        output_result(&context);
+      } else {
+        let Some(input) = input else {
+          unreachable!("`--watch` conflicts with `-e`");
+        };
+        let mut watcher = ok_or_exit(FileWatcher::new());
+
+        // Returns the context of the run, and whether it finished.
         let run_file = |input| {
           let mut context = new_context();
 
@@ -137,7 +544,7 @@ fn main() {
             Ok(source) => source,
             Err(e) => {
               eprintln!("error: {e}");
-              return context;
+              return (context, false);
             }
           };
 
@@ -145,22 +552,28 @@ fn main() {
 
           let mut lexer = Lexer::new(source);
 
-          let exprs = match parse(&mut lexer) {
+          let mut exprs = match parse(&mut lexer) {
             Ok(exprs) => exprs,
             Err(e) => {
               eprintln!("error: {e}");
-              return context;
+              return (context, false);
             }
           };
 
+          if let Err(e) = context.check_intrinsics(&exprs) {
+            eprintln!("error: {e}");
+            return (context, false);
+          }
+          expand_break_directives(&mut exprs);
+
           match engine.run(context, exprs) {
             Ok(context) => {
-              print_stack(&context);
+              output_result(&context);
               if let Some(journal) = context.journal() {
                 eprintln!("{:#}", journal);
               }
 
-              context
+              (context, true)
             }
             Err(e) => {
               if let Some(info) = &e.expr.info {
@@ -182,7 +595,7 @@ fn main() {
                   .with_labels(vec![Label::primary(file_id, span)
                     .with_message("error occurs here")]);
 
-                let writer = StandardStream::stderr(ColorChoice::Always);
+                let writer = StandardStream::stderr(color_choice);
                 let config = codespan_reporting::term::Config::default();
 
                 // TODO: Should we do anything for this error or can we just unwrap?
@@ -195,41 +608,488 @@ fn main() {
                 eprintln!("{}", journal);
               }
 
-              e.context
+              (*e.context, false)
             }
           }
         };
 
-        ok_or_exit(clear_screen());
-        let context = run_file(&input);
+        loop {
+          if clear.should_clear() {
+            ok_or_exit(clear_screen());
+          }
 
-        ok_or_exit(context.sources().try_for_each(|source| {
-          watcher
-            .watch(Path::new(source.0.as_str()), RecursiveMode::NonRecursive)
-        }));
+          let start = Instant::now();
+          let (context, ok) = run_file(&input);
+          let elapsed = start.elapsed();
 
-        for event in rx {
-          if let Event {
-            kind: EventKind::Modify(_),
-            ..
-          } = ok_or_exit(event)
-          {
-            ok_or_exit(clear_screen());
-            run_file(&input);
+          // The files it imports are watched too, which are found again after
+          // every run. A failed run may not have reached all of its imports,
+          // so the files from before are still watched after one.
+          let mut files = source_files(&context).collect::<HashSet<_>>();
+          files.insert(input.clone());
+          if !ok {
+            files.extend(watcher.files().iter().cloned());
+          }
+          watcher.watch(files);
+
+          eprintln!("{}", status_line(ok, elapsed, watcher.files().len()));
+
+          let hook = if ok { &on_success } else { &on_failure };
+          if let Some(command) = hook {
+            run_hook(command);
+          }
+
+          if !watcher.wait() {
+            break;
           }
         }
       }
     }
+    Subcommand::Bench { input, runs } => {
+      let source = ok_or_exit(Source::from_path(&input));
+      let mut report = BenchReport {
+        name: input.display().to_string(),
+        samples: Vec::new(),
+      };
+
+      for _ in 0..runs {
+        let mut context = new_context();
+        context.add_source(source.clone());
+
+        let allocations = Allocations::now();
+        let start = Instant::now();
+        let mut lexer = Lexer::new(source.clone());
+        let exprs = ok_or_exit(parse(&mut lexer));
+        let parse = start.elapsed();
+
+        let start = Instant::now();
+        let context = ok_or_exit(engine.run(context, exprs));
+        let run = start.elapsed();
+
+        report.samples.push(Sample {
+          parse,
+          run,
+          ops: context.ops(),
+          allocations: Allocations::now().since(allocations),
+        });
+      }
+
+      println!("{report}");
+    }
+    Subcommand::Test {
+      path,
+      snapshot,
+      update_snapshots,
+    } => {
+      let options = TestOptions {
+        snapshot,
+        update_snapshots,
+      };
+      let mut report = TestReport::default();
+
+      for file in ok_or_exit(test::discover(&path)) {
+        let results = test::run_file(&engine, new_context(), &file, &options);
+
+        for result in results.iter() {
+          println!("{result}");
+        }
+        report.results.extend(results);
+      }
+
+      println!("\n{} passed, {} failed", report.passed(), report.failed());
+
+      if report.failed() != 0 {
+        std::process::exit(1);
+      }
+    }
+    Subcommand::Doc { target, html } => {
+      let path = Path::new(&target);
+
+      let docs = if path.is_file() {
+        let source = ok_or_exit(Source::from_path(path));
+        context.add_source(source.clone());
+
+        let mut lexer = Lexer::new(source);
+        let exprs = ok_or_exit(parse(&mut lexer));
+        let context = ok_or_exit(engine.run(context, exprs));
+
+        Docs::from_context(target, &context)
+      } else {
+        match engine.module(&Symbol::from_ref(target.as_str())) {
+          Some(module) => Docs::from_module(module),
+          None => ok_or_exit(Err(format!("no file or module named {target}"))),
+        }
+      };
+
+      match html {
+        true => print!("{}", docs.to_html()),
+        false => print!("{}", docs.to_markdown()),
+      }
+    }
+    Subcommand::Fmt { input, check } => {
+      let source = ok_or_exit(Source::from_path(&input));
+      let mut lexer = Lexer::new(source.clone());
+      let exprs = ok_or_exit(parse(&mut lexer));
+
+      let formatted = format_exprs(&exprs, FormatOptions::default());
+
+      if formatted != source.source() {
+        if check {
+          eprintln!("{} is not formatted", input.display());
+          std::process::exit(1);
+        }
+
+        ok_or_exit(std::fs::write(&input, formatted));
+      }
+    }
+    Subcommand::Lint { input } => {
+      let source = ok_or_exit(Source::from_path(&input));
+      let mut lexer = Lexer::new(source.clone());
+      let exprs = ok_or_exit(parse(&mut lexer));
+
+      let lints = lint::lint(&exprs);
+
+      let mut files = SimpleFiles::new();
+      let file_id = files.add(source.name(), source.source());
+
+      let writer = StandardStream::stderr(color_choice);
+      let config = codespan_reporting::term::Config::default();
+
+      for lint in lints.iter() {
+        let mut diagnostic =
+          Diagnostic::warning().with_message(lint.kind.to_string());
+
+        if let Some(info) = &lint.expr.info {
+          diagnostic = diagnostic.with_labels(vec![Label::primary(
+            file_id,
+            info.span.start..info.span.end,
+          )
+          .with_message("value made here")]);
+        }
+
+        let _ = term::emit(&mut writer.lock(), &config, &files, &diagnostic);
+      }
+
+      if !lints.is_empty() {
+        std::process::exit(1);
+      }
+    }
+    Subcommand::Learn { lesson } => ok_or_exit(learn::run(lesson.as_deref())),
+    Subcommand::Install => {
+      match manifest {
+        Some(manifest) => ok_or_exit(manifest.install(|name, dir| {
+          println!("installed {name} ({})", dir.display())
+        })),
+        None => ok_or_exit(Err(format!("could not find {MANIFEST_NAME}"))),
+      }
+    }
     Subcommand::Serve => listen(),
+    Subcommand::Lsp => ok_or_exit(lsp::serve(&engine)),
+  }
+}
+
+const DEFAULT_CHECKPOINT_EVERY: usize = 1_000_000;
+
+/// Returns the code given with `-e`, joined by newlines, or else the code of
+/// the input file.
+fn run_source(
+  input: Option<&Path>,
+  exprs: &[String],
+) -> std::io::Result<Source> {
+  match input {
+    Some(input) if exprs.is_empty() => Source::from_path(input),
+    _ => Ok(Source::new("-e", exprs.join("\n"))),
+  }
+}
+
+/// Finds the package manifest for the input file, or for the current directory
+/// if there isn't one.
+fn find_manifest(cli: &Cli) -> Result<Option<Manifest>, String> {
+  let dir = match &cli.subcommand {
+    Some(
+      Subcommand::Run {
+        input: Some(input), ..
+      }
+      | Subcommand::Bench { input, .. },
+    ) => input
+      .canonicalize()
+      .ok()
+      .and_then(|x| x.parent().map(Path::to_path_buf)),
+    Some(Subcommand::Test { path, .. }) => {
+      path.canonicalize().ok().and_then(|x| match x.is_dir() {
+        true => Some(x),
+        false => x.parent().map(Path::to_path_buf),
+      })
+    }
+    _ => None,
+  };
+
+  let dir = match dir {
+    Some(dir) => dir,
+    None => std::env::current_dir().map_err(|e| e.to_string())?,
+  };
+
+  Manifest::find(&dir).map_err(|e| e.to_string())
+}
+
+/// Writes the report bundle made by `bundle` to `path`, if there is one.
+fn save_report(path: Option<&Path>, bundle: impl FnOnce() -> ReportBundle) {
+  let Some(path) = path else {
+    return;
+  };
+
+  let bundle = bundle().with_version("stack-cli", env!("CARGO_PKG_VERSION"));
+
+  match bundle.save(path) {
+    Ok(()) => eprintln!("wrote report to {}", path.display()),
+    Err(e) => eprintln!("error: failed to write report: {e}"),
   }
 }
 
+/// Returns the intrinsics allowed by the CLI flags, exiting if any of the
+/// names are unknown.
+fn intrinsic_filter(cli: &Cli) -> IntrinsicFilter {
+  let intrinsics = |names: &[String]| {
+    names
+      .iter()
+      .map(|name| {
+        Intrinsic::from_str(name).map_err(|e| format!("{e} '{name}'"))
+      })
+      .map(ok_or_exit)
+      .collect::<HashSet<_>>()
+  };
+
+  if !cli.allow_intrinsics.is_empty() {
+    IntrinsicFilter::Allow(intrinsics(&cli.allow_intrinsics))
+  } else if !cli.deny_intrinsics.is_empty() {
+    IntrinsicFilter::Deny(intrinsics(&cli.deny_intrinsics))
+  } else {
+    IntrinsicFilter::All
+  }
+}
+
+/// Returns whether all of the standard modules are enabled, which they are by
+/// default for `run` unless any have been enabled individually.
+#[cfg(feature = "stack-std")]
+fn enables_all(cli: &Cli) -> bool {
+  let enables_any = [
+    cli.enable_str,
+    cli.enable_fs,
+    cli.enable_scope,
+    cli.enable_sb,
+    cli.enable_rope,
+    cli.enable_template,
+    cli.enable_md,
+    cli.enable_json,
+    cli.enable_toml,
+    cli.enable_yaml,
+    cli.enable_csv,
+    cli.enable_proc,
+    cli.enable_env,
+    cli.enable_io,
+    cli.enable_time,
+    cli.enable_rand,
+    cli.enable_math,
+    cli.enable_hash,
+    cli.enable_encode,
+    cli.enable_task,
+  ]
+  .contains(&true);
+
+  cli.enable_all
+    || matches!(
+      cli.subcommand,
+      Some(
+        Subcommand::Run { .. }
+          | Subcommand::Bench { .. }
+          | Subcommand::Test { .. }
+          | Subcommand::Doc { .. }
+      )
+    ) && !enables_any
+}
+
+/// Returns the capabilities granted by the CLI flags. Without a sandbox,
+/// everything is allowed.
+#[cfg(feature = "stack-std")]
+fn sandbox_policy(cli: &Cli) -> SandboxPolicy {
+  if !cli.sandbox {
+    return SandboxPolicy::unrestricted();
+  }
+
+  let mut policy = SandboxPolicy::new()
+    .with_run(cli.allow_run)
+    .with_env(cli.allow_env);
+
+  policy = match cli.allow_read {
+    Some(ref paths) if paths.is_empty() => policy.with_read_all(),
+    Some(ref paths) => paths.iter().fold(policy, |x, path| x.with_read(path)),
+    None => policy,
+  };
+
+  policy = match cli.allow_write {
+    Some(ref paths) if paths.is_empty() => policy.with_write_all(),
+    Some(ref paths) => paths.iter().fold(policy, |x, path| x.with_write(path)),
+    None => policy,
+  };
+
+  match cli.allow_net {
+    Some(ref hosts) if hosts.is_empty() => policy.with_net_all(),
+    Some(ref hosts) => hosts.iter().fold(policy, |x, host| x.with_net(host)),
+    None => policy,
+  }
+}
+
+/// Returns a hook which pauses at the breakpoints enabled by the CLI flags,
+/// until enter is pressed.
+fn breakpoint_hook(cli: &Cli) -> Option<BreakpointHook> {
+  if !cli.break_all && cli.breakpoints.is_empty() {
+    return None;
+  }
+
+  let break_all = cli.break_all;
+  let breakpoints = cli
+    .breakpoints
+    .iter()
+    .map(|name| Symbol::from_ref(name.as_str()))
+    .collect::<HashSet<_>>();
+
+  Some(Arc::new(move |name, context, expr| {
+    let enabled = break_all || name.is_some_and(|x| breakpoints.contains(&x));
+
+    if !enabled {
+      return;
+    }
+
+    match (name, &expr.info) {
+      (Some(name), Some(info)) => eprintln!("paused at '{name}' ({info})"),
+      (Some(name), None) => eprintln!("paused at '{name}'"),
+      (None, Some(info)) => eprintln!("paused at {info}"),
+      (None, None) => eprintln!("paused"),
+    }
+
+    eprint_stack(context);
+    eprint!("press enter to continue");
+
+    let _ = std::io::stdin().read_line(&mut String::new());
+  }))
+}
+
+/// Returns a tracer which writes to STDERR or the trace file, if tracing is
+/// enabled.
+fn tracer(cli: &Cli) -> std::io::Result<Option<Tracer>> {
+  if !cli.trace && cli.trace_file.is_none() && !cli.trace_json {
+    return Ok(None);
+  }
+
+  // Lines are written as they're traced, so that nothing is lost if the
+  // process exits because of an error.
+  let writer: Box<dyn Write + Send> = match cli.trace_file {
+    Some(ref path) => Box::new(LineWriter::new(std::fs::File::create(path)?)),
+    None => Box::new(std::io::stderr()),
+  };
+  let writer = Mutex::new(writer);
+  let is_json = cli.trace_json;
+
+  Ok(Some(Arc::new(move |event| {
+    let line = match is_json {
+      true => {
+        let stack = |stack: &[Expr]| {
+          stack.iter().map(|x| x.to_string()).collect::<Vec<_>>()
+        };
+
+        serde_json::json!({
+          "depth": event.depth,
+          "expr": event.expr.to_string(),
+          "location": event.expr.info.as_ref().map(|x| x.to_string()),
+          "before": stack(event.before),
+          "after": stack(event.after),
+          "error": event.error.map(|x| x.to_string()),
+        })
+        .to_string()
+      }
+      false => event.to_string(),
+    };
+
+    let mut writer = writer.lock().unwrap_or_else(|x| x.into_inner());
+    let _ = writeln!(writer, "{line}");
+  })))
+}
+
+/// Runs a [`Checkpoint`] on a [`Scheduler`], saving a new checkpoint to `path`
+/// after every `every` operations.
+///
+/// A task can only be checkpointed between top-level expressions, so `every`
+/// may be overrun by a long-running expression.
+///
+/// The checkpoint file is removed once the run has finished successfully.
+fn run_checkpointed(
+  engine: Engine,
+  checkpoint: Checkpoint,
+  path: &Path,
+  every: usize,
+) -> Result<Context, String> {
+  let mut scheduler = Scheduler::new(engine).with_slice(every);
+  let id = scheduler.resume(checkpoint);
+
+  while scheduler.step().is_some() {
+    if let Some(mut checkpoint) = scheduler.checkpoint(id) {
+      checkpoint
+        .exprs
+        .iter_mut()
+        .for_each(Expr::recursively_strip_info);
+
+      save_checkpoint(&checkpoint, path).map_err(|e| e.to_string())?;
+    }
+  }
+
+  match scheduler.remove(id) {
+    Some(Ok(context)) => {
+      let _ = std::fs::remove_file(path);
+      Ok(context)
+    }
+    Some(Err(e)) => {
+      if let Some(code) = e.exit_code() {
+        let _ = std::fs::remove_file(path);
+        std::process::exit(code);
+      }
+
+      eprint_stack(&e.context);
+      Err(e.to_string())
+    }
+    None => unreachable!(),
+  }
+}
+
+fn save_checkpoint(
+  checkpoint: &Checkpoint,
+  path: &Path,
+) -> std::io::Result<()> {
+  // Write to a temporary file first, so that an interruption can't leave a
+  // partially written checkpoint behind.
+  let temp = path.with_extension("tmp");
+
+  let mut writer = BufWriter::new(std::fs::File::create(&temp)?);
+  serde_json::to_writer(&mut writer, checkpoint)?;
+  writer.flush()?;
+  drop(writer);
+
+  std::fs::rename(temp, path)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default, clap::Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
   #[command(subcommand)]
-  subcommand: Subcommand,
+  subcommand: Option<Subcommand>,
+
+  /// Runs this code instead of a file, like `run -e`. The code of each `-e`
+  /// is run in order.
+  #[arg(short = 'e', long = "expr", value_name = "CODE")]
+  exprs: Vec<String>,
+  /// The arguments to pass to the code given with `-e`, after `--`.
+  #[arg(last = true, requires = "exprs")]
+  args: Vec<String>,
 
   /// Whether to enable stack journaling.
   #[arg(short, long)]
@@ -239,12 +1099,93 @@ struct Cli {
   #[arg(long, alias = "jl")]
   journal_length: Option<usize>,
 
-  /// Whether to run a sandbox variant of the enabled standard modules.
+  /// Pause at the breakpoints with this name.
+  #[arg(long = "break", value_name = "NAME")]
+  breakpoints: Vec<String>,
+  /// Pause at every breakpoint.
+  #[arg(long)]
+  break_all: bool,
+  /// Add a directory to search for imports in, before those in `STACK_PATH`.
+  #[arg(short = 'I', long, value_name = "DIR")]
+  include: Vec<PathBuf>,
+
+  /// Only allow this intrinsic to be used.
+  #[arg(long = "allow-intrinsic", value_name = "NAME")]
+  allow_intrinsics: Vec<String>,
+  /// Forbid this intrinsic from being used.
+  #[arg(
+    long = "deny-intrinsic",
+    value_name = "NAME",
+    conflicts_with = "allow_intrinsics"
+  )]
+  deny_intrinsics: Vec<String>,
+
+  /// Log whenever `def`, `let` or `set` changes this name.
+  #[arg(long = "watchpoint", value_name = "NAME")]
+  watchpoints: Vec<String>,
+  /// Error when a function doesn't pop and push as many values as its
+  /// declared stack effect says.
+  #[arg(long)]
+  check_effects: bool,
+
+  /// Write the final stack as JSON to this file, separately from anything the
+  /// script prints.
+  #[arg(long, value_name = "FILE")]
+  result_file: Option<PathBuf>,
+  /// How to print the final stack of `run` and `stdin`.
+  #[arg(long, value_name = "FORMAT", default_value = "pretty")]
+  output: OutputFormat,
+  /// Don't print the final stack of `run` and `stdin`.
+  #[arg(short, long, conflicts_with = "output")]
+  quiet: bool,
+  /// When to color the output.
+  #[arg(long, value_name = "WHEN", default_value = "auto")]
+  color: ColorMode,
+  /// The amount of characters of a string which are printed in the stack.
+  #[arg(long, value_name = "CHARS", default_value_t = PrettyOptions::default().max_string)]
+  max_string: usize,
+  /// The amount of items of a collection which are printed in the stack.
+  #[arg(long, value_name = "N", default_value_t = PrettyOptions::default().max_items)]
+  max_items: usize,
+  /// How floats are printed, as `digits=N,sci=N`: the significant digits
+  /// shown, and the power of ten from which scientific notation is used.
+  #[arg(long, value_name = "SPEC", default_value_t)]
+  float_format: FloatFormat,
+
+  /// Whether to run a sandbox variant of the enabled standard modules, which
+  /// can only do what the `--allow-*` flags grant.
   #[arg(short, long)]
   #[cfg(feature = "stack-std")]
   sandbox: bool,
 
-  /// Enable all standard modules.
+  /// Allow reading these paths in a sandbox, or any path if none are given.
+  #[arg(long, value_name = "PATH", num_args = 0.., require_equals = true, value_delimiter = ',')]
+  #[cfg(feature = "stack-std")]
+  allow_read: Option<Vec<PathBuf>>,
+
+  /// Allow writing these paths in a sandbox, or any path if none are given.
+  #[arg(long, value_name = "PATH", num_args = 0.., require_equals = true, value_delimiter = ',')]
+  #[cfg(feature = "stack-std")]
+  allow_write: Option<Vec<PathBuf>>,
+
+  /// Allow connecting to these hosts in a sandbox, or any host if none are
+  /// given.
+  #[arg(long, value_name = "HOST", num_args = 0.., require_equals = true, value_delimiter = ',')]
+  #[cfg(feature = "stack-std")]
+  allow_net: Option<Vec<String>>,
+
+  /// Allow running other programs in a sandbox.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  allow_run: bool,
+
+  /// Allow reading and changing environment variables in a sandbox.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  allow_env: bool,
+
+  /// Enable all standard modules, which `run` does unless any are enabled
+  /// individually.
   #[arg(long)]
   #[cfg(feature = "stack-std")]
   enable_all: bool,
@@ -260,8 +1201,92 @@ struct Cli {
   #[arg(long)]
   #[cfg(feature = "stack-std")]
   enable_scope: bool,
+  /// Enable the string builder standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_sb: bool,
+  /// Enable the rope standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_rope: bool,
+  /// Enable the template standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_template: bool,
+  /// Enable the Markdown standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_md: bool,
+  /// Enable the JSON standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_json: bool,
+  /// Enable the TOML standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_toml: bool,
+  /// Enable the YAML standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_yaml: bool,
+  /// Enable the CSV standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_csv: bool,
+  /// Enable the process standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_proc: bool,
+  /// Enable the environment standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_env: bool,
+  /// Enable the input/output standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_io: bool,
+  /// Enable the time standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_time: bool,
+  /// Enable the random number standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_rand: bool,
+  /// Enable the math standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_math: bool,
+  /// Enable the hashing standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_hash: bool,
+  /// Enable the encoding standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_encode: bool,
+  /// Enable the task and channel standard modules.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_task: bool,
+
+  /// Load a native module from a dynamic library.
+  #[arg(long = "plugin", value_name = "PATH")]
+  plugins: Vec<PathBuf>,
+
+  /// Print each evaluated expression with the stack before and after it.
+  #[arg(long)]
+  trace: bool,
+  /// Write the trace to this file instead of STDERR.
+  #[arg(long, value_name = "FILE")]
+  trace_file: Option<PathBuf>,
+  /// Write the trace as JSON lines.
+  #[arg(long)]
+  trace_json: bool,
 }
 
+// It's only made once, so `Run` being large doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, PartialEq, Eq, Default, clap::Subcommand)]
 enum Subcommand {
   /// Runs a REPL [alias >].
@@ -271,16 +1296,195 @@ enum Subcommand {
   /// Runs the code supplied via STDIN [alias -].
   #[command(alias = "-")]
   Stdin,
-  /// Runs the code from an input file path.
+  /// Runs the code from an input file path, or given with `-e`.
   Run {
     /// The input file path.
-    input: PathBuf,
+    #[arg(required_unless_present = "exprs")]
+    input: Option<PathBuf>,
+
+    /// Runs this code instead of a file. The code of each `-e` is run in
+    /// order.
+    #[arg(short = 'e', long = "expr", value_name = "CODE", conflicts_with_all = ["input", "watch"])]
+    exprs: Vec<String>,
 
     /// Whether to watch the file and re-run it if there are changes.
     #[arg(short, long)]
     watch: bool,
+    /// When to clear the screen before each run while watching.
+    #[arg(
+      long,
+      value_name = "WHEN",
+      default_value = "auto",
+      requires = "watch"
+    )]
+    clear: ClearMode,
+    /// Runs this shell command after each run which finishes while watching.
+    #[arg(long, value_name = "CMD", requires = "watch")]
+    on_success: Option<String>,
+    /// Runs this shell command after each run which fails while watching.
+    #[arg(long, value_name = "CMD", requires = "watch")]
+    on_failure: Option<String>,
+
+    /// Periodically saves the progress to this path, so that it can be
+    /// continued with `resume` if interrupted.
+    ///
+    /// Checkpoints are only taken between top-level expressions, so a single
+    /// long-running expression, such as a loop, isn't checkpointed while it
+    /// runs and is re-run from its start when resumed.
+    #[arg(long, conflicts_with = "watch")]
+    checkpoint: Option<PathBuf>,
+
+    /// The amount of operations to run between checkpoints. This is a
+    /// minimum, as a checkpoint waits for the top-level expression that is
+    /// running to finish.
+    #[arg(long, default_value_t = DEFAULT_CHECKPOINT_EVERY)]
+    checkpoint_every: usize,
+
+    /// Prints a JSON report of the resource use, final stack and diagnostics
+    /// instead of the stack.
+    #[arg(long, conflicts_with_all = ["watch", "checkpoint"])]
+    limits_report: bool,
+    /// The maximum amount of operations to run.
+    #[arg(long)]
+    fuel: Option<usize>,
+    /// The maximum length of the stack.
+    #[arg(long, value_name = "LEN")]
+    stack_limit: Option<usize>,
+    /// The maximum amount of milliseconds to run for.
+    #[arg(long, value_name = "MS", conflicts_with_all = ["watch", "checkpoint"])]
+    timeout: Option<u64>,
+
+    /// If the run fails, writes a zip archive of the sources, error, journal
+    /// and version info to this path, to attach to a bug report.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["watch", "checkpoint", "limits_report"])]
+    report: Option<PathBuf>,
+
+    /// Prints how long each intrinsic and function took, and how many times
+    /// it was called, once the run has finished.
+    #[arg(long, conflicts_with_all = ["watch", "checkpoint", "limits_report"])]
+    profile: bool,
+    /// Writes the time spent in each call stack to this path, in the folded
+    /// format read by flame graph tools.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["watch", "checkpoint", "limits_report"])]
+    profile_folded: Option<PathBuf>,
+    /// Prints how many expressions were evaluated, how many times each
+    /// intrinsic was called, and the peak stack and scope sizes, once the run
+    /// has finished.
+    #[arg(long, conflicts_with_all = ["watch", "checkpoint", "limits_report", "timeout"])]
+    stats: bool,
+    /// Prints how long the run took and how many expressions it evaluated,
+    /// once it has finished.
+    #[arg(long, conflicts_with_all = ["watch", "checkpoint", "limits_report"])]
+    time: bool,
+
+    /// The arguments to pass to the script, after `--`.
+    #[arg(last = true)]
+    args: Vec<String>,
+  },
+  /// Continues running from a checkpoint.
+  Resume {
+    /// The checkpoint file path, which is updated as the run progresses.
+    snapshot: PathBuf,
+
+    /// The amount of operations to run between checkpoints. This is a
+    /// minimum, as a checkpoint waits for the top-level expression that is
+    /// running to finish.
+    #[arg(long, default_value_t = DEFAULT_CHECKPOINT_EVERY)]
+    checkpoint_every: usize,
+  },
+
+  /// Runs a file a number of times, reporting how long parsing and running
+  /// it took, how many expressions it evaluated and how much it allocated.
+  Bench {
+    /// The input file path.
+    input: PathBuf,
+
+    /// The amount of times to run it.
+    #[arg(short = 'n', long, default_value_t = 10)]
+    runs: usize,
   },
+  /// Runs the tests in the `*_test.stack` files in a directory, or in a
+  /// single file.
+  Test {
+    /// The directory or file to test.
+    #[arg(default_value = ".")]
+    path: PathBuf,
+
+    /// Compares what each test prints and its final stack with its snapshot,
+    /// recording the snapshots which don't exist yet.
+    #[arg(long)]
+    snapshot: bool,
+    /// Replaces the snapshots with what each test prints and its final stack.
+    #[arg(long)]
+    update_snapshots: bool,
+  },
+
+  /// Prints the documentation of the words defined by a file, or of the
+  /// functions of a module, as Markdown.
+  Doc {
+    /// The file path or module name.
+    target: String,
+
+    /// Prints HTML instead of Markdown.
+    #[arg(long)]
+    html: bool,
+  },
+
+  /// Formats a file in place.
+  Fmt {
+    /// The input file path.
+    input: PathBuf,
+
+    /// Whether to only check if the file is formatted, exiting with a
+    /// non-zero status if it isn't.
+    #[arg(long)]
+    check: bool,
+  },
+
+  /// Reports values which are made and then dropped without being used,
+  /// exiting with a non-zero status if there are any.
+  Lint {
+    /// The input file path.
+    input: PathBuf,
+  },
+
+  /// Runs an interactive tutorial, continuing from where it was left.
+  Learn {
+    /// The name of a lesson to start from instead.
+    lesson: Option<String>,
+  },
+
+  /// Fetches the dependencies listed in `stack.toml`.
+  Install,
 
   // TODO: add host and port as options
   Serve,
+  /// Runs a language server over STDIN and STDOUT.
+  Lsp,
+}
+
+impl Subcommand {
+  /// Returns a `run` of the code given with `-e`, with the default options.
+  fn run_exprs(exprs: Vec<String>, args: Vec<String>) -> Self {
+    Self::Run {
+      input: None,
+      exprs,
+      watch: false,
+      clear: ClearMode::default(),
+      on_success: None,
+      on_failure: None,
+      checkpoint: None,
+      checkpoint_every: DEFAULT_CHECKPOINT_EVERY,
+      limits_report: false,
+      fuel: None,
+      stack_limit: None,
+      timeout: None,
+      report: None,
+      profile: false,
+      profile_folded: None,
+      stats: false,
+      time: false,
+      args,
+    }
+  }
 }
@@ -0,0 +1,235 @@
+//! Packages, which are described by a `stack.toml` manifest.
+//!
+//! ```toml
+//! [package]
+//! name = "app"
+//!
+//! [dependencies]
+//! json = { path = "../json" }
+//! http = { git = "https://example.com/http.git", rev = "v1.0.0" }
+//! ```
+//!
+//! Git dependencies are fetched into the package cache with `stack install`.
+//! Each dependency can then be imported from with `"name:path" import`.
+
+use core::fmt;
+use std::{
+  collections::{BTreeMap, HashMap},
+  fs, io,
+  path::{Component, Path, PathBuf},
+  process::Command,
+};
+
+use toml_edit::{Document, Item};
+
+/// The file name of a package manifest.
+pub const MANIFEST_NAME: &str = "stack.toml";
+/// The directory, relative to the package root, which git dependencies are
+/// fetched into.
+pub const CACHE_DIR: &str = ".stack/packages";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+  /// The directory containing the manifest.
+  pub root: PathBuf,
+  pub name: Option<String>,
+  pub dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dependency {
+  /// A package in a local directory, relative to the package root.
+  Path(PathBuf),
+  /// A package in a git repository, optionally pinned to a revision.
+  Git { url: String, rev: Option<String> },
+}
+
+#[derive(Debug)]
+pub enum PackageError {
+  Io(PathBuf, io::Error),
+  Parse(PathBuf, toml_edit::TomlError),
+  InvalidDependency(String),
+  InvalidName(String),
+  InvalidRev(String, String),
+  MissingDependency(String, PathBuf),
+  Git(String),
+}
+
+impl std::error::Error for PackageError {}
+
+impl fmt::Display for PackageError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(path, e) => write!(f, "{}: {e}", path.display()),
+      Self::Parse(path, e) => write!(f, "{}: {e}", path.display()),
+      Self::InvalidDependency(name) => write!(
+        f,
+        "dependency '{name}' must have either a 'path' or a 'git' key"
+      ),
+      Self::InvalidName(name) => write!(
+        f,
+        "dependency name '{name}' must be a single path component"
+      ),
+      Self::InvalidRev(name, rev) => {
+        write!(f, "dependency '{name}' has an invalid rev '{rev}'")
+      }
+      Self::MissingDependency(name, path) => {
+        write!(f, "dependency '{name}' not found at {}", path.display())
+      }
+      Self::Git(name) => write!(f, "failed to fetch dependency '{name}'"),
+    }
+  }
+}
+
+impl Manifest {
+  /// Finds the manifest in `dir` or its closest ancestor which has one.
+  pub fn find(dir: &Path) -> Result<Option<Self>, PackageError> {
+    match dir
+      .ancestors()
+      .map(|x| x.join(MANIFEST_NAME))
+      .find(|x| x.is_file())
+    {
+      Some(path) => Self::load(&path).map(Some),
+      None => Ok(None),
+    }
+  }
+
+  pub fn load(path: &Path) -> Result<Self, PackageError> {
+    let source =
+      fs::read_to_string(path).map_err(|e| PackageError::Io(path.into(), e))?;
+    let document = source
+      .parse::<Document>()
+      .map_err(|e| PackageError::Parse(path.into(), e))?;
+
+    let name = document
+      .get("package")
+      .and_then(|x| x.get("name"))
+      .and_then(Item::as_str)
+      .map(Into::into);
+
+    let mut dependencies = BTreeMap::new();
+    if let Some(table) =
+      document.get("dependencies").and_then(Item::as_table_like)
+    {
+      for (name, item) in table.iter() {
+        // The name is used as a directory in the package cache, so it mustn't
+        // be able to point outside of it.
+        let mut components = Path::new(name).components();
+        if !matches!(
+          (components.next(), components.next()),
+          (Some(Component::Normal(_)), None)
+        ) {
+          return Err(PackageError::InvalidName(name.into()));
+        }
+
+        let dependency = match (
+          item.get("path").and_then(Item::as_str),
+          item.get("git").and_then(Item::as_str),
+        ) {
+          (Some(path), None) => Dependency::Path(path.into()),
+          (None, Some(url)) => Dependency::Git {
+            url: url.into(),
+            rev: item.get("rev").and_then(Item::as_str).map(Into::into),
+          },
+          _ => return Err(PackageError::InvalidDependency(name.into())),
+        };
+
+        // A rev starting with `-` would be read by git as an option.
+        if let Dependency::Git { rev: Some(rev), .. } = &dependency {
+          if rev.is_empty() || rev.starts_with('-') {
+            return Err(PackageError::InvalidRev(name.into(), rev.clone()));
+          }
+        }
+
+        dependencies.insert(name.into(), dependency);
+      }
+    }
+
+    Ok(Self {
+      root: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+      name,
+      dependencies,
+    })
+  }
+
+  /// Returns the directory that the dependency `name` is (or will be, once
+  /// installed) in.
+  pub fn dependency_dir(&self, name: &str) -> Option<PathBuf> {
+    self
+      .dependencies
+      .get(name)
+      .map(|dependency| self.dir_of(name, dependency))
+  }
+
+  fn dir_of(&self, name: &str, dependency: &Dependency) -> PathBuf {
+    match dependency {
+      Dependency::Path(path) => self.root.join(path),
+      Dependency::Git { .. } => self.root.join(CACHE_DIR).join(name),
+    }
+  }
+
+  /// Returns the directory of each dependency, to be passed to
+  /// [`Engine::with_packages`].
+  ///
+  /// [`Engine::with_packages`]: stack_core::engine::Engine::with_packages
+  pub fn packages(&self) -> HashMap<String, PathBuf> {
+    self
+      .dependencies
+      .iter()
+      .map(|(name, dependency)| (name.clone(), self.dir_of(name, dependency)))
+      .collect()
+  }
+
+  /// Fetches the git dependencies into the package cache and checks that the
+  /// path dependencies exist.
+  ///
+  /// `on_install` is called with the name and directory of each dependency.
+  pub fn install(
+    &self,
+    mut on_install: impl FnMut(&str, &Path),
+  ) -> Result<(), PackageError> {
+    for (name, dependency) in self.dependencies.iter() {
+      let dir = self.dir_of(name, dependency);
+
+      match dependency {
+        Dependency::Path(_) => {
+          if !dir.is_dir() {
+            return Err(PackageError::MissingDependency(name.clone(), dir));
+          }
+        }
+        Dependency::Git { url, rev } => {
+          if dir.is_dir() {
+            git(name, Command::new("git").arg("-C").arg(&dir).arg("fetch"))?;
+          } else {
+            // Otherwise, a URL starting with `-` would be read as an option.
+            git(
+              name,
+              Command::new("git").args(["clone", "--"]).arg(url).arg(&dir),
+            )?;
+          }
+
+          if let Some(rev) = rev {
+            git(
+              name,
+              Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(["checkout", "--quiet", rev, "--"]),
+            )?;
+          }
+        }
+      }
+
+      on_install(name, &dir);
+    }
+
+    Ok(())
+  }
+}
+
+fn git(name: &str, command: &mut Command) -> Result<(), PackageError> {
+  match command.status() {
+    Ok(status) if status.success() => Ok(()),
+    _ => Err(PackageError::Git(name.into())),
+  }
+}
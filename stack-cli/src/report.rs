@@ -0,0 +1,156 @@
+//! The JSON report written by `stack run --limits-report`.
+//!
+//! It's meant for automated evaluation of submissions, so it's always written,
+//! even when the script fails to parse or hits a limit.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use stack_core::{
+  format::{format_exprs, FormatOptions},
+  prelude::*,
+  source::Location,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LimitsReport {
+  pub status: Status,
+  pub limits: Limits,
+  pub usage: Usage,
+  /// The final stack, formatted as source code.
+  pub stack: Vec<String>,
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+  Ok,
+  ParseError,
+  RunError,
+  OutOfFuel,
+  StackOverflow,
+  Timeout,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Limits {
+  pub fuel: Option<usize>,
+  pub stack_limit: Option<usize>,
+  pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct Usage {
+  /// The amount of operations that were run.
+  pub ops: usize,
+  /// The largest length the stack reached.
+  pub peak_stack_len: usize,
+  pub elapsed_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+  pub message: String,
+  pub file: Option<String>,
+  pub line: Option<usize>,
+  pub column: Option<usize>,
+}
+
+impl LimitsReport {
+  /// Creates a report for a script that failed to parse.
+  pub fn from_parse_error(limits: Limits, error: &ParseError) -> Self {
+    let location = error.kind.location(&error.source);
+
+    Self {
+      status: Status::ParseError,
+      limits,
+      usage: Usage::default(),
+      stack: Vec::new(),
+      diagnostics: vec![Diagnostic::new(
+        error.kind.to_string(),
+        Some(error.source.name()),
+        location,
+      )],
+    }
+  }
+
+  /// Creates a report from the result of running a script.
+  pub fn from_run(
+    limits: Limits,
+    result: Result<Context, RunError>,
+    elapsed: Duration,
+  ) -> Self {
+    let (status, context, diagnostics) = match result {
+      Ok(context) => (Status::Ok, context, Vec::new()),
+      Err(error) => {
+        let status = match error.reason {
+          RunErrorReason::OutOfFuel => Status::OutOfFuel,
+          RunErrorReason::StackOverflow => Status::StackOverflow,
+          RunErrorReason::Timeout => Status::Timeout,
+          _ => Status::RunError,
+        };
+
+        let diagnostic = match &error.expr.info {
+          Some(info) => Diagnostic::new(
+            error.reason.to_string(),
+            Some(info.source.name()),
+            info.source.location(info.span.start),
+          ),
+          None => Diagnostic::new(
+            format!("{} caused by {}", error.reason, error.expr),
+            None,
+            None,
+          ),
+        };
+
+        (status, *error.context, vec![diagnostic])
+      }
+    };
+
+    Self {
+      status,
+      limits,
+      usage: Usage {
+        ops: context.ops(),
+        peak_stack_len: context.peak_stack_len(),
+        elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+      },
+      stack: context
+        .stack()
+        .iter()
+        .map(|expr| {
+          let expr = Expr {
+            kind: expr.kind.clone(),
+            info: None,
+          };
+
+          format_exprs(&[expr], FormatOptions::default())
+            .trim_end()
+            .into()
+        })
+        .collect(),
+      diagnostics,
+    }
+  }
+
+  #[inline]
+  pub fn is_ok(&self) -> bool {
+    self.status == Status::Ok
+  }
+}
+
+impl Diagnostic {
+  fn new(
+    message: String,
+    file: Option<&str>,
+    location: Option<Location>,
+  ) -> Self {
+    Self {
+      message,
+      file: file.map(Into::into),
+      line: location.map(|x| x.line.get()),
+      column: location.map(|x| x.column.get()),
+    }
+  }
+}
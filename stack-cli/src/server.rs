@@ -64,6 +64,7 @@ pub struct CommandErrorPayload {
   pub value: String,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "status", rename_all = "snake_case")]
 pub enum Outgoing {
@@ -71,6 +72,7 @@ pub enum Outgoing {
   Error(OutgoingError),
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum OkPayload {
@@ -267,6 +269,7 @@ pub fn handle(
   }
 }
 
+#[allow(clippy::result_large_err)]
 pub fn listen() {
   let eng_mutex = Rc::new(Mutex::new(Engine::new()));
   let ctx_mutex = Rc::new(Mutex::new(Context::new()));
@@ -0,0 +1,446 @@
+//! The test runner for `stack test`.
+//!
+//! Tests are found in `*_test.stack` files. Each file is run, then each
+//! definition made with `def-test`, which is named with a `test:` prefix, is
+//! called with an empty stack. A file without any of these is a test itself,
+//! which passes if it runs without an error.
+//!
+//! With snapshots, what each test prints and its final stack are also
+//! compared with what was recorded in a `.snap` file next to the test file.
+//! A test without a recorded snapshot records one and passes.
+
+use core::fmt;
+use std::{
+  io,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+};
+
+use stack_core::{
+  prelude::*,
+  pretty::{self, PrettyOptions},
+};
+
+/// The prefix of the names which `def-test` defines.
+pub const TEST_PREFIX: &str = "test:";
+
+/// Returns the test files in `path`, or `path` itself if it's a file.
+///
+/// Directories are searched recursively, except for hidden ones, such as
+/// `.stack` where packages are installed, and `target`.
+pub fn discover(path: &Path) -> io::Result<Vec<PathBuf>> {
+  if path.is_file() {
+    return Ok(vec![path.to_path_buf()]);
+  }
+
+  let mut files = Vec::new();
+  let mut dirs = vec![path.to_path_buf()];
+
+  while let Some(dir) = dirs.pop() {
+    for entry in std::fs::read_dir(dir)? {
+      let path = entry?.path();
+      let name = path
+        .file_name()
+        .map(|x| x.to_string_lossy())
+        .unwrap_or_default();
+
+      if path.is_dir() {
+        if !name.starts_with('.') && name != "target" {
+          dirs.push(path);
+        }
+      } else if name.ends_with("_test.stack") {
+        files.push(path);
+      }
+    }
+  }
+
+  files.sort();
+  Ok(files)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TestOptions {
+  /// Whether to compare the output of each test with its snapshot.
+  pub snapshot: bool,
+  /// Whether to replace the snapshots instead of comparing them.
+  pub update_snapshots: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+  Passed,
+  /// Passed, and recorded a new snapshot.
+  Recorded,
+  Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+  /// The file, followed by the test's name if it was defined with `def-test`.
+  pub name: String,
+  pub outcome: Outcome,
+}
+
+impl TestResult {
+  #[inline]
+  pub fn is_passed(&self) -> bool {
+    !matches!(self.outcome, Outcome::Failed(_))
+  }
+}
+
+impl fmt::Display for TestResult {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.outcome {
+      Outcome::Passed => write!(f, "ok    {}", self.name),
+      Outcome::Recorded => write!(f, "ok    {} (recorded)", self.name),
+      Outcome::Failed(ref reason) => {
+        write!(f, "FAIL  {}", self.name)?;
+        reason.lines().try_for_each(|x| write!(f, "\n      {x}"))
+      }
+    }
+  }
+}
+
+/// The results of every test which was run.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TestReport {
+  pub results: Vec<TestResult>,
+}
+
+impl TestReport {
+  #[inline]
+  pub fn passed(&self) -> usize {
+    self.results.iter().filter(|x| x.is_passed()).count()
+  }
+
+  #[inline]
+  pub fn failed(&self) -> usize {
+    self.results.len() - self.passed()
+  }
+}
+
+/// The name of the snapshot of a file without any `def-test`s.
+const FILE_SNAPSHOT: &str = "main";
+
+/// Runs the tests in the file at `path`, starting each from `context`.
+pub fn run_file(
+  engine: &Engine,
+  mut context: Context,
+  path: &Path,
+  options: &TestOptions,
+) -> Vec<TestResult> {
+  let file = path.display().to_string();
+  let failed = |reason: String| {
+    vec![TestResult {
+      name: file.clone(),
+      outcome: Outcome::Failed(reason),
+    }]
+  };
+
+  let source = match Source::from_path(path) {
+    Ok(x) => x,
+    Err(e) => return failed(e.to_string()),
+  };
+  context.add_source(source.clone());
+
+  let mut lexer = Lexer::new(source);
+  let exprs = match parse(&mut lexer) {
+    Ok(x) => x,
+    Err(e) => return failed(e.to_string()),
+  };
+
+  let mut snapshots = match options.snapshot || options.update_snapshots {
+    true => match Snapshots::load(path.with_extension("snap")) {
+      Ok(x) => Some(x),
+      Err(e) => return failed(e.to_string()),
+    },
+    false => None,
+  };
+
+  // What's printed is captured, to compare with the snapshots.
+  let output = Arc::new(Mutex::new(String::new()));
+  let mut engine = engine.clone();
+  if snapshots.is_some() {
+    capture_output(&mut engine, &output);
+  }
+
+  let mut check = |name: &str, result: Result<Context, RunError>| {
+    let context = match result {
+      Ok(x) => x,
+      Err(e) => return Outcome::Failed(describe(&e)),
+    };
+
+    let Some(ref mut snapshots) = snapshots else {
+      return Outcome::Passed;
+    };
+
+    let mut output = output.lock().unwrap_or_else(|x| x.into_inner());
+    let actual = snapshot(&core::mem::take(&mut *output), &context);
+
+    match snapshots.get(name) {
+      Some(expected) if options.update_snapshots || expected == actual => {
+        snapshots.insert(name, actual);
+        Outcome::Passed
+      }
+      Some(expected) => Outcome::Failed(format!(
+        "snapshot doesn't match (- expected, + actual)\n{}",
+        diff(expected, &actual)
+      )),
+      None => {
+        snapshots.insert(name, actual);
+        Outcome::Recorded
+      }
+    }
+  };
+
+  let result = engine.run(context, exprs);
+  let tests = match result {
+    Ok(ref context) => {
+      let mut tests = context
+        .scope_items()
+        .map(|(name, _)| *name)
+        .filter(|x| x.as_str().starts_with(TEST_PREFIX))
+        .collect::<Vec<_>>();
+      tests.sort_by(|lhs, rhs| lhs.as_str().cmp(rhs.as_str()));
+      tests
+    }
+    Err(_) => Vec::new(),
+  };
+
+  let results = if tests.is_empty() {
+    vec![TestResult {
+      name: file.clone(),
+      outcome: check(FILE_SNAPSHOT, result),
+    }]
+  } else {
+    let context = result.unwrap_or_else(|e| *e.context);
+    // What the file itself printed isn't part of any test.
+    output.lock().unwrap_or_else(|x| x.into_inner()).clear();
+
+    tests
+      .into_iter()
+      .map(|test| {
+        let mut context = context.clone();
+        context.stack_mut().clear();
+
+        let name = &test.as_str()[TEST_PREFIX.len()..];
+        let result = engine.run_expr(context, ExprKind::Symbol(test).into());
+
+        TestResult {
+          name: format!("{file} {name}"),
+          outcome: check(name, result),
+        }
+      })
+      .collect()
+  };
+
+  if let Some(snapshots) = snapshots {
+    if let Err(e) = snapshots.save() {
+      return failed(e.to_string());
+    }
+  }
+
+  results
+}
+
+/// Makes `print`, `pretty` and `table` write to `output` instead of STDOUT,
+/// all without colors, so that snapshots don't depend on the terminal.
+fn capture_output(engine: &mut Engine, output: &Arc<Mutex<String>>) {
+  for intrinsic in [Intrinsic::Print, Intrinsic::Pretty] {
+    let output = output.clone();
+    engine.override_intrinsic(
+      intrinsic,
+      Arc::new(move |_, mut context, expr| {
+        let val = context.stack_pop(&expr)?;
+        let mut output = output.lock().unwrap_or_else(|x| x.into_inner());
+        let val = context.float_format().display(&val.kind);
+        output.push_str(&format!("{val}\n"));
+
+        Ok(context)
+      }),
+    );
+  }
+
+  let output = output.clone();
+  engine.override_intrinsic(
+    Intrinsic::Table,
+    Arc::new(move |_, mut context, expr| {
+      let columns = context.stack_pop(&expr)?;
+      let rows = context.stack_pop(&expr)?;
+
+      let options = PrettyOptions {
+        color: false,
+        float: context.float_format(),
+        ..Default::default()
+      };
+
+      match pretty::table(&rows, &columns, options) {
+        Some(table) => {
+          let mut output = output.lock().unwrap_or_else(|x| x.into_inner());
+          output.push_str(&format!("{table}\n"));
+
+          Ok(context)
+        }
+        None => Err(RunError {
+          reason: RunErrorReason::InvalidArgument,
+          context: Box::new(context),
+          expr: Box::new(expr),
+        }),
+      }
+    }),
+  );
+}
+
+/// Returns the snapshot of a test, which is what it printed followed by its
+/// final stack.
+fn snapshot(output: &str, context: &Context) -> String {
+  let mut snapshot = output.to_owned();
+  snapshot.push_str("stack:");
+
+  for expr in context.stack() {
+    snapshot.push_str(&format!(" {expr}"));
+  }
+
+  snapshot
+}
+
+/// The snapshots of the tests in one file.
+///
+/// Each snapshot is written as its name followed by a colon, then each of
+/// its lines indented by two spaces, so that any output can be recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Snapshots {
+  path: PathBuf,
+  entries: Vec<(String, String)>,
+  is_changed: bool,
+}
+
+impl Snapshots {
+  /// Loads the snapshots at `path`, if there are any.
+  fn load(path: PathBuf) -> io::Result<Self> {
+    let source = match std::fs::read_to_string(&path) {
+      Ok(x) => x,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+      Err(e) => return Err(e),
+    };
+
+    let mut entries: Vec<(String, Vec<&str>)> = Vec::new();
+    for line in source.lines() {
+      match (line.strip_prefix("  "), entries.last_mut()) {
+        (Some(line), Some((_, lines))) => lines.push(line),
+        (None, _) if line.is_empty() => {}
+        (None, _) => {
+          let name = line.strip_suffix(':').unwrap_or(line);
+          entries.push((name.into(), Vec::new()));
+        }
+        (Some(_), None) => {
+          return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} has a line without a name", path.display()),
+          ))
+        }
+      }
+    }
+
+    Ok(Self {
+      path,
+      entries: entries
+        .into_iter()
+        .map(|(name, lines)| (name, lines.join("\n")))
+        .collect(),
+      is_changed: false,
+    })
+  }
+
+  fn get(&self, name: &str) -> Option<&str> {
+    self
+      .entries
+      .iter()
+      .find(|(x, _)| x == name)
+      .map(|(_, x)| x.as_str())
+  }
+
+  fn insert(&mut self, name: &str, snapshot: String) {
+    match self.entries.iter_mut().find(|(x, _)| x == name) {
+      Some((_, x)) if *x == snapshot => return,
+      Some((_, x)) => *x = snapshot,
+      None => self.entries.push((name.into(), snapshot)),
+    }
+
+    self.is_changed = true;
+  }
+
+  /// Writes the snapshots, if any were changed.
+  fn save(&self) -> io::Result<()> {
+    if !self.is_changed {
+      return Ok(());
+    }
+
+    let mut source = String::new();
+    for (name, snapshot) in self.entries.iter() {
+      source.push_str(&format!("{name}:\n"));
+
+      for line in snapshot.lines() {
+        source.push_str(&format!("  {line}\n"));
+      }
+    }
+
+    std::fs::write(&self.path, source)
+  }
+}
+
+/// Returns the lines of `expected` and `actual`, with those only in
+/// `expected` prefixed by `-` and those only in `actual` prefixed by `+`.
+fn diff(expected: &str, actual: &str) -> String {
+  let expected = expected.lines().collect::<Vec<_>>();
+  let actual = actual.lines().collect::<Vec<_>>();
+
+  // The length of the longest common subsequence of the lines after each
+  // pair of lines.
+  let mut lengths = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+  for i in (0..expected.len()).rev() {
+    for j in (0..actual.len()).rev() {
+      lengths[i][j] = match expected[i] == actual[j] {
+        true => lengths[i + 1][j + 1] + 1,
+        false => lengths[i + 1][j].max(lengths[i][j + 1]),
+      };
+    }
+  }
+
+  let mut lines = Vec::new();
+  let (mut i, mut j) = (0, 0);
+  while i < expected.len() || j < actual.len() {
+    if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+      lines.push(format!("  {}", expected[i]));
+      (i, j) = (i + 1, j + 1);
+    } else if j == actual.len()
+      || (i < expected.len() && lengths[i + 1][j] >= lengths[i][j + 1])
+    {
+      lines.push(format!("- {}", expected[i]));
+      i += 1;
+    } else {
+      lines.push(format!("+ {}", actual[j]));
+      j += 1;
+    }
+  }
+
+  lines.join("\n")
+}
+
+/// Describes why a test failed, including the values which `assert-eq`
+/// compared.
+fn describe(e: &RunError) -> String {
+  let stack = e.context.stack();
+
+  match (e.reason, &e.expr.kind) {
+    (RunErrorReason::AssertionFailed, ExprKind::Symbol(x))
+      if x.as_str() == Intrinsic::AssertEq.as_str() && stack.len() >= 2 =>
+    {
+      let actual = &stack[stack.len() - 2];
+      let expected = &stack[stack.len() - 1];
+
+      format!("{e}\nexpected: {expected}\n  actual: {actual}")
+    }
+    _ => e.to_string(),
+  }
+}
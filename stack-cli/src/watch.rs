@@ -0,0 +1,171 @@
+//! Watching the files of `stack run --watch`.
+//!
+//! The parent directories of the files are watched rather than the files
+//! themselves, so that changes are still seen when an editor saves by
+//! replacing the file. Changes which happen close together, such as an editor
+//! writing a file in several steps, are only reported once.
+
+use std::{
+  collections::HashSet,
+  io::IsTerminal,
+  path::{Path, PathBuf},
+  process::Command,
+  sync::mpsc::{channel, Receiver, RecvTimeoutError},
+  time::Duration,
+};
+
+use notify::{
+  Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use stack_core::prelude::*;
+
+/// How long to wait for more changes before reporting them.
+pub const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// When to clear the screen before each run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ClearMode {
+  /// Only when printing to a terminal.
+  #[default]
+  Auto,
+  /// Never, so the output of every run is kept.
+  Never,
+}
+
+impl ClearMode {
+  pub fn should_clear(self) -> bool {
+    match self {
+      Self::Auto => std::io::stdout().is_terminal(),
+      Self::Never => false,
+    }
+  }
+}
+
+pub struct FileWatcher {
+  watcher: RecommendedWatcher,
+  events: Receiver<notify::Result<Event>>,
+  files: HashSet<PathBuf>,
+  dirs: HashSet<PathBuf>,
+}
+
+impl FileWatcher {
+  pub fn new() -> notify::Result<Self> {
+    let (tx, events) = channel();
+
+    Ok(Self {
+      watcher: RecommendedWatcher::new(tx, Config::default())?,
+      events,
+      files: HashSet::new(),
+      dirs: HashSet::new(),
+    })
+  }
+
+  /// Returns the files which are being watched.
+  #[inline]
+  pub fn files(&self) -> &HashSet<PathBuf> {
+    &self.files
+  }
+
+  /// Watches these files instead of the ones watched before.
+  pub fn watch<P>(&mut self, files: impl IntoIterator<Item = P>)
+  where
+    P: AsRef<Path>,
+  {
+    self.files = files
+      .into_iter()
+      .filter_map(|file| std::path::absolute(file).ok())
+      .collect();
+
+    let dirs = self
+      .files
+      .iter()
+      .filter_map(|file| file.parent().map(Path::to_path_buf))
+      .collect::<HashSet<_>>();
+
+    for dir in self.dirs.difference(&dirs) {
+      let _ = self.watcher.unwatch(dir);
+    }
+
+    for dir in dirs.difference(&self.dirs) {
+      if let Err(e) = self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+        eprintln!("error: couldn't watch {}: {e}", dir.display());
+      }
+    }
+
+    self.dirs = dirs;
+  }
+
+  /// Blocks until a watched file changes, and then until there have been no
+  /// more changes for [`DEBOUNCE`].
+  ///
+  /// Returns `false` if the changes can no longer be received.
+  pub fn wait(&self) -> bool {
+    loop {
+      match self.events.recv() {
+        Ok(event) if self.is_change(&event) => break,
+        Ok(_) => {}
+        Err(_) => return false,
+      }
+    }
+
+    loop {
+      match self.events.recv_timeout(DEBOUNCE) {
+        Ok(_) => {}
+        Err(RecvTimeoutError::Timeout) => return true,
+        Err(RecvTimeoutError::Disconnected) => return false,
+      }
+    }
+  }
+
+  fn is_change(&self, event: &notify::Result<Event>) -> bool {
+    match event {
+      Ok(event) => {
+        matches!(
+          event.kind,
+          EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) && event.paths.iter().any(|path| self.files.contains(path))
+      }
+      Err(e) => {
+        eprintln!("error: {e}");
+        false
+      }
+    }
+  }
+}
+
+/// Returns the files which the sources of a run were read from, which
+/// includes every file it imported.
+pub fn source_files(context: &Context) -> impl Iterator<Item = PathBuf> + '_ {
+  context
+    .sources()
+    .map(|(name, _)| PathBuf::from(name.as_str()))
+    .filter(|path| path.is_file())
+}
+
+/// Returns the line printed after each run, saying when it finished, how long
+/// it took and how many files are being watched.
+pub fn status_line(ok: bool, elapsed: Duration, watching: usize) -> String {
+  let time = chrono::Local::now().format("%H:%M:%S");
+  let outcome = if ok { "finished" } else { "failed" };
+  let plural = if watching == 1 { "" } else { "s" };
+
+  format!(
+    "[{time}] {outcome} in {elapsed:.2?}, watching {watching} file{plural}"
+  )
+}
+
+/// Runs a hook command with the system shell, waiting for it to finish.
+///
+/// It's reported if it can't be run or fails, but the watching continues.
+pub fn run_hook(command: &str) {
+  #[cfg(windows)]
+  let status = Command::new("cmd").args(["/C", command]).status();
+  #[cfg(not(windows))]
+  let status = Command::new("sh").args(["-c", command]).status();
+
+  match status {
+    Ok(status) if status.success() => {}
+    Ok(status) => eprintln!("error: `{command}` exited with {status}"),
+    Err(e) => eprintln!("error: couldn't run `{command}`: {e}"),
+  }
+}
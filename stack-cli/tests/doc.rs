@@ -0,0 +1,25 @@
+//! Tests for `stack doc`, run against the built binary.
+
+use std::process::Command;
+
+fn stack(args: &[&str]) -> String {
+  let output = Command::new(env!("CARGO_BIN_EXE_stack"))
+    .args(args)
+    .output()
+    .unwrap();
+  assert!(output.status.success(), "{output:?}");
+
+  String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+#[cfg(feature = "stack-std")]
+fn documents_std_modules() {
+  let markdown = stack(&["doc", "str"]);
+
+  assert!(markdown.starts_with("# str\n"));
+  assert!(markdown.contains(
+    "## `str:trim`\n\n`( s -- s )`\n\nRemoves the whitespace around a \
+     string.\n"
+  ));
+}
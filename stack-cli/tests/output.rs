@@ -0,0 +1,39 @@
+//! Tests for how the final stack is output, run against the built binary.
+
+use std::{fs, process::Command};
+
+const SOURCE: &str = r#"'(fn) 1.5 [1 nil] {a: "b"}
+1.0 0.0 / -1.0 0.0 / 0.0 0.0 /"#;
+
+const JSON: &str =
+  r#"["(fn)",1.5,[1,null],{"a":"b"},"Infinity","-Infinity","NaN"]"#;
+
+fn compact(json: &str) -> String {
+  json.split_whitespace().collect()
+}
+
+#[test]
+#[cfg(feature = "stack-std")]
+fn writes_the_same_json_to_the_result_file() {
+  let dir =
+    std::env::temp_dir().join(format!("stack-output-{}", std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let script = dir.join("script.stack");
+  let result = dir.join("result.json");
+  fs::write(&script, SOURCE).unwrap();
+
+  let output = Command::new(env!("CARGO_BIN_EXE_stack"))
+    .arg("--result-file")
+    .arg(&result)
+    .args(["--output", "json", "run"])
+    .arg(&script)
+    .output()
+    .unwrap();
+  assert!(output.status.success(), "{output:?}");
+
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  assert_eq!(compact(&stdout), JSON);
+  assert_eq!(compact(&fs::read_to_string(&result).unwrap()), JSON);
+
+  fs::remove_dir_all(&dir).unwrap();
+}
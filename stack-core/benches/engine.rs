@@ -0,0 +1,83 @@
+//! Benchmarks for lexing, parsing and running representative programs.
+//!
+//! Run them with `cargo bench -p stack-core`, optionally with a filter such
+//! as `cargo bench -p stack-core -- fib`. Each prints the fastest and median
+//! time over a number of runs.
+
+use std::time::{Duration, Instant};
+
+use stack_core::prelude::*;
+
+const FIB: &str = "
+'(fn! dupe 1 > '[dupe 1 - fib swap 2 - fib +] if) 'fib def
+20 fib
+";
+
+const LISTS: &str = "
+0 10000 range collect
+'(fn 2 *) map
+'(fn 3 % 0 =) filter
+0 '(fn +) fold
+";
+
+const STRINGS: &str = "
+\"\" 'acc def
+2000 '[acc \"ab\" concat 'acc set] times
+acc len
+";
+
+const PROGRAMS: [(&str, &str); 3] =
+  [("fib", FIB), ("lists", LISTS), ("strings", STRINGS)];
+
+/// Runs `f` until it has run for about a second, or at least 10 times.
+fn bench(name: &str, filter: Option<&str>, mut f: impl FnMut()) {
+  if filter.is_some_and(|x| !name.contains(x)) {
+    return;
+  }
+
+  let mut times = Vec::new();
+  let start = Instant::now();
+
+  while times.len() < 10 || start.elapsed() < Duration::from_secs(1) {
+    let start = Instant::now();
+    f();
+    times.push(start.elapsed());
+  }
+
+  times.sort();
+  println!(
+    "{name:<24} min {:>12?}  median {:>12?}  ({} runs)",
+    times[0],
+    times[times.len() / 2],
+    times.len()
+  );
+}
+
+fn main() {
+  // Cargo passes `--bench`, along with any filter after `--`.
+  let filter = std::env::args().skip(1).find(|x| !x.starts_with('-'));
+  let filter = filter.as_deref();
+
+  // A large source made of every program, to measure the front end.
+  let large =
+    Source::new("large", PROGRAMS.map(|(_, x)| x).concat().repeat(500));
+
+  bench("lex/large", filter, || {
+    let mut lexer = Lexer::new(large.clone());
+    while lexer.next().kind != stack_core::lexer::TokenKind::Eof {}
+  });
+
+  bench("parse/large", filter, || {
+    parse(&mut Lexer::new(large.clone())).unwrap();
+  });
+
+  let engine = Engine::new();
+  for (name, code) in PROGRAMS {
+    let source = Source::new(name, code);
+    let exprs = parse(&mut Lexer::new(source)).unwrap();
+
+    bench(&format!("run/{name}"), filter, || {
+      engine.run(Context::new(), exprs.clone()).unwrap();
+    });
+  }
+}
@@ -0,0 +1,30 @@
+//! Lexes and parses arbitrary input, which must never panic, and checks that
+//! whatever parses is parsed the same once it's formatted.
+//!
+//! Run with `cargo fuzz run parse` from `stack-core`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use stack_core::{
+  format::{format_exprs, FormatOptions},
+  lexer::TokenKind,
+  prelude::*,
+};
+
+fuzz_target!(|data: &[u8]| {
+  let source = String::from_utf8_lossy(data);
+
+  let mut lexer = Lexer::new(Source::new("", source.as_ref()));
+  while lexer.next().kind != TokenKind::Eof {}
+
+  let mut lexer = Lexer::new(Source::new("", source.as_ref()));
+  let Ok(exprs) = parse(&mut lexer) else {
+    return;
+  };
+
+  let formatted = format_exprs(&exprs, FormatOptions::default());
+  let mut lexer = Lexer::new(Source::new("", formatted.as_str()));
+
+  assert_eq!(parse(&mut lexer).ok(), Some(exprs), "{formatted}");
+});
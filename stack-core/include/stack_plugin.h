@@ -0,0 +1,86 @@
+/*
+ * The interface for Stack plugins, which are native modules loaded from
+ * dynamic libraries with `stack run --plugin path`.
+ *
+ * A plugin exports a stack_plugin function, which returns a StackPlugin
+ * naming the module and listing its functions:
+ *
+ *   static int add(const StackValue *args, size_t len, StackValue *out) {
+ *     out->kind = STACK_KIND_INTEGER;
+ *     out->data.integer = args[0].data.integer + args[1].data.integer;
+ *     return 0;
+ *   }
+ *
+ *   static const StackPluginFunc funcs[] = {{"add", 2, add}};
+ *   static const StackPlugin plugin = {
+ *     STACK_PLUGIN_ABI_VERSION, "math", funcs, 1, NULL,
+ *   };
+ *
+ *   const StackPlugin *stack_plugin(void) { return &plugin; }
+ *
+ * A function takes its arguments from the stack, the last being the top, and
+ * writes its result to out. If it returns anything other than 0, it failed,
+ * and an error holding out is pushed instead. Strings passed to a function
+ * are only valid during the call. Strings written to out are copied and then
+ * given back to free_string, if it isn't NULL.
+ */
+
+#ifndef STACK_PLUGIN_H
+#define STACK_PLUGIN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define STACK_PLUGIN_ABI_VERSION 1
+
+#define STACK_KIND_NIL 0
+#define STACK_KIND_BOOLEAN 1
+#define STACK_KIND_INTEGER 2
+#define STACK_KIND_FLOAT 3
+#define STACK_KIND_STRING 4
+
+/* A UTF-8 string, which isn't nul-terminated. */
+typedef struct StackStr {
+  const char *ptr;
+  size_t len;
+} StackStr;
+
+typedef struct StackValue {
+  /* One of the STACK_KIND_* constants. */
+  uint32_t kind;
+  union {
+    bool boolean;
+    int64_t integer;
+    double float_;
+    StackStr string;
+  } data;
+} StackValue;
+
+typedef struct StackPluginFunc {
+  const char *name;
+  size_t arity;
+  int (*call)(const StackValue *args, size_t len, StackValue *out);
+} StackPluginFunc;
+
+typedef struct StackPlugin {
+  uint32_t abi_version;
+  /* The name of the module. */
+  const char *name;
+  const StackPluginFunc *funcs;
+  size_t funcs_len;
+  /* Frees a string which a function wrote to out. */
+  void (*free_string)(const char *ptr, size_t len);
+} StackPlugin;
+
+const StackPlugin *stack_plugin(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* STACK_PLUGIN_H */
@@ -0,0 +1,411 @@
+//! An index-based syntax tree, which parses without allocating per node.
+//!
+//! [`parse_in`] parses into an [`Arena`], which keeps every node of every
+//! tree in one [`Vec`] and refers to them by [`NodeId`]. A list's items are
+//! a contiguous run of ids, so parsing a large file only grows a few buffers
+//! rather than allocating a [`Vec`] for each list. Clearing the arena keeps
+//! its buffers, so tools which reparse often, such as the language server,
+//! can reuse one arena.
+//!
+//! Nodes don't hold comments. [`Arena::to_expr`] converts a node into the
+//! [`Expr`] tree which the engine runs.
+
+use core::ops::Range;
+use std::collections::HashMap;
+
+use crate::{
+  expr::{Expr, ExprInfo, ExprKind, FnScope},
+  lexer::{Lexer, Span, TokenKind},
+  parser::{self, ParseError, ParseErrorKind},
+  scope::Scope,
+  source::Source,
+  symbol::Symbol,
+};
+
+/// The index of a [`Node`] in an [`Arena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(u32);
+
+/// A contiguous run of ids in an [`Arena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeRange {
+  start: u32,
+  end: u32,
+}
+
+impl NodeRange {
+  #[inline]
+  fn range(self) -> Range<usize> {
+    self.start as usize..self.end as usize
+  }
+
+  #[inline]
+  pub fn len(self) -> usize {
+    (self.end - self.start) as usize
+  }
+
+  #[inline]
+  pub fn is_empty(self) -> bool {
+    self.start == self.end
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+  pub kind: NodeKind,
+  pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+  /// A node without children, such as a number or a symbol.
+  Atom(ExprKind),
+  Lazy(NodeId),
+  List(NodeRange),
+  /// The entries of a record, in the order they were written.
+  Record(NodeRange),
+  Function {
+    is_scoped: bool,
+    body: NodeRange,
+  },
+  SExpr {
+    call: Symbol,
+    body: NodeRange,
+  },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Arena {
+  nodes: Vec<Node>,
+  children: Vec<NodeId>,
+  entries: Vec<(Symbol, NodeId)>,
+  /// The children of the lists which are being parsed, which are moved to
+  /// `children` once each list ends.
+  scratch: Vec<NodeId>,
+}
+
+impl Arena {
+  #[inline]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Returns the amount of nodes.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+
+  /// Removes every node, keeping the memory for reuse.
+  pub fn clear(&mut self) {
+    self.nodes.clear();
+    self.children.clear();
+    self.entries.clear();
+    self.scratch.clear();
+  }
+
+  #[inline]
+  pub fn node(&self, id: NodeId) -> &Node {
+    &self.nodes[id.0 as usize]
+  }
+
+  /// Returns the items of a list, or the body of a function or s-expression.
+  #[inline]
+  pub fn children(&self, range: NodeRange) -> &[NodeId] {
+    &self.children[range.range()]
+  }
+
+  /// Returns the entries of a record.
+  #[inline]
+  pub fn entries(&self, range: NodeRange) -> &[(Symbol, NodeId)] {
+    &self.entries[range.range()]
+  }
+
+  /// Converts the node `id`, which was parsed from `source`, into an
+  /// [`Expr`].
+  pub fn to_expr(&self, id: NodeId, source: &Source) -> Expr {
+    let node = self.node(id);
+    let body = |range| {
+      self
+        .children(range)
+        .iter()
+        .map(|x| self.to_expr(*x, source))
+        .collect::<Vec<_>>()
+    };
+
+    let kind = match node.kind {
+      NodeKind::Atom(ref x) => x.clone(),
+      NodeKind::Lazy(x) => ExprKind::Lazy(Box::new(self.to_expr(x, source))),
+      NodeKind::List(x) => ExprKind::List(body(x).into()),
+      NodeKind::Record(x) => ExprKind::Record(
+        self
+          .entries(x)
+          .iter()
+          .map(|(key, value)| (*key, self.to_expr(*value, source)))
+          .collect::<HashMap<_, _>>()
+          .into(),
+      ),
+      NodeKind::Function { is_scoped, body: x } => ExprKind::Function {
+        scope: match is_scoped {
+          true => FnScope::Scoped(Scope::new()),
+          false => FnScope::Scopeless,
+        },
+        body: body(x),
+      },
+      NodeKind::SExpr { call, body: x } => ExprKind::SExpr {
+        call,
+        body: body(x),
+      },
+    };
+
+    Expr {
+      kind,
+      info: Some(ExprInfo::new(source.clone(), node.span)),
+    }
+  }
+
+  fn push(&mut self, kind: NodeKind, span: Span) -> NodeId {
+    let id = NodeId(self.nodes.len() as u32);
+    self.nodes.push(Node { kind, span });
+    id
+  }
+
+  /// Moves the ids in `scratch` from `start` onwards to `children`.
+  fn take_children(&mut self, start: usize) -> NodeRange {
+    let range = NodeRange {
+      start: self.children.len() as u32,
+      end: (self.children.len() + self.scratch.len() - start) as u32,
+    };
+    self.children.extend(self.scratch.drain(start..));
+
+    range
+  }
+}
+
+/// Parses the source of `lexer` into `arena`, returning the ids of the
+/// top-level nodes.
+///
+/// The same source parses into the same tree as [`parse`](parser::parse),
+/// other than comments.
+pub fn parse_in(
+  lexer: &mut Lexer,
+  arena: &mut Arena,
+) -> Result<NodeRange, ParseError> {
+  let start = arena.scratch.len();
+
+  loop {
+    match lexer.peek().kind {
+      TokenKind::Eof => break,
+      _ => {
+        let id = parse_node(lexer, arena)?;
+        arena.scratch.push(id);
+      }
+    }
+  }
+
+  Ok(arena.take_children(start))
+}
+
+fn parse_node(
+  lexer: &mut Lexer,
+  arena: &mut Arena,
+) -> Result<NodeId, ParseError> {
+  let source = lexer.source();
+  let token = lexer.next();
+  let span = |end: Span| Span {
+    start: token.span.start,
+    end: end.end,
+  };
+
+  match token.kind {
+    TokenKind::Apostrophe => {
+      let id = parse_node(lexer, arena)?;
+      let end = arena.node(id).span;
+
+      Ok(arena.push(NodeKind::Lazy(id), span(end)))
+    }
+    TokenKind::LeftSquare => {
+      let (body, end) = parse_body(lexer, arena, TokenKind::RightSquare)?;
+
+      Ok(arena.push(NodeKind::List(body), span(end)))
+    }
+    TokenKind::LeftParen => {
+      let (body, end) = parse_body(lexer, arena, TokenKind::RightParen)?;
+
+      let call = match arena.children(body).first() {
+        Some(x) => match arena.node(*x).kind {
+          NodeKind::Atom(ExprKind::Symbol(x)) => Some(x),
+          _ => None,
+        },
+        None => None,
+      };
+      let Some(call) = call else {
+        return Err(ParseError {
+          kind: ParseErrorKind::Parenthetical(token),
+          source,
+        });
+      };
+
+      // The call itself isn't part of the body.
+      let body = NodeRange {
+        start: body.start + 1,
+        end: body.end,
+      };
+      let kind = match call.as_str() {
+        "fn" => NodeKind::Function {
+          is_scoped: true,
+          body,
+        },
+        "fn!" => NodeKind::Function {
+          is_scoped: false,
+          body,
+        },
+        _ => NodeKind::SExpr { call, body },
+      };
+
+      Ok(arena.push(kind, span(end)))
+    }
+    TokenKind::LeftCurly => {
+      let mut entries = Vec::new();
+
+      let end = loop {
+        if lexer.peek().kind == TokenKind::RightCurly {
+          break lexer.next().span;
+        }
+
+        let key = parse_node(lexer, arena)?;
+        let key = parser::record_key(arena.to_expr(key, &source).kind);
+        parser::skip_record_colon(lexer);
+
+        // A key without a value is ignored.
+        if lexer.peek().kind == TokenKind::RightCurly {
+          continue;
+        }
+
+        let value = parse_node(lexer, arena)?;
+        match entries.iter_mut().find(|(x, _)| *x == key) {
+          Some((_, x)) => *x = value,
+          None => entries.push((key, value)),
+        }
+      };
+
+      let range = NodeRange {
+        start: arena.entries.len() as u32,
+        end: (arena.entries.len() + entries.len()) as u32,
+      };
+      arena.entries.extend(entries);
+
+      Ok(arena.push(NodeKind::Record(range), span(end)))
+    }
+    _ => {
+      let kind = parser::parse_atom(&source, token)?;
+
+      Ok(arena.push(NodeKind::Atom(kind), token.span))
+    }
+  }
+}
+
+/// Parses nodes until `end`, returning them and the span of `end`.
+fn parse_body(
+  lexer: &mut Lexer,
+  arena: &mut Arena,
+  end: TokenKind,
+) -> Result<(NodeRange, Span), ParseError> {
+  let start = arena.scratch.len();
+
+  loop {
+    let token = lexer.peek();
+
+    if token.kind == end {
+      lexer.next();
+      return Ok((arena.take_children(start), token.span));
+    }
+
+    let id = parse_node(lexer, arena)?;
+    arena.scratch.push(id);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::prelude::*;
+
+  fn parse_both(code: &str) -> (Vec<Expr>, Vec<Expr>) {
+    let source = Source::new("", code);
+    let exprs = parse(&mut Lexer::new(source.clone())).unwrap();
+
+    let mut arena = Arena::new();
+    let roots = parse_in(&mut Lexer::new(source.clone()), &mut arena).unwrap();
+    let nodes = arena
+      .children(roots)
+      .iter()
+      .map(|x| arena.to_expr(*x, &source))
+      .collect();
+
+    (exprs, nodes)
+  }
+
+  #[test]
+  fn parses_the_same_tree_as_parse() {
+    let (exprs, nodes) = parse_both(
+      "1 2.5 \"a\" 'b [1 [2 'c]] {a: 1 \"b\" : [2] a: 3} \
+       (fn 1 +) (fn! drop) (+ 1 2) ; comment\n nil _",
+    );
+
+    assert_eq!(exprs, nodes);
+  }
+
+  #[test]
+  fn keeps_spans() {
+    let source = Source::new("", "'[1 2]");
+    let mut arena = Arena::new();
+    let roots = parse_in(&mut Lexer::new(source), &mut arena).unwrap();
+
+    let root = arena.node(arena.children(roots)[0]);
+    assert_eq!(root.span, Span { start: 0, end: 6 });
+
+    let NodeKind::Lazy(list) = root.kind else {
+      panic!("expected a lazy node");
+    };
+    let NodeKind::List(items) = arena.node(list).kind else {
+      panic!("expected a list");
+    };
+    assert_eq!(items.len(), 2);
+    assert_eq!(arena.node(list).span, Span { start: 1, end: 6 });
+  }
+
+  #[test]
+  fn reports_the_same_errors_as_parse() {
+    for code in ["(1 2)", "[1", "]", "0x"] {
+      let source = Source::new("", code);
+      let err = parse(&mut Lexer::new(source.clone())).unwrap_err();
+
+      let mut arena = Arena::new();
+      assert_eq!(
+        parse_in(&mut Lexer::new(source), &mut arena).unwrap_err(),
+        err,
+        "{code}"
+      );
+    }
+  }
+
+  #[test]
+  fn reuses_memory_when_cleared() {
+    let mut arena = Arena::new();
+    let source = Source::new("", "[1 2 3] [4 5]");
+
+    parse_in(&mut Lexer::new(source.clone()), &mut arena).unwrap();
+    assert_eq!(arena.len(), 7);
+
+    arena.clear();
+    assert!(arena.is_empty());
+    parse_in(&mut Lexer::new(source), &mut arena).unwrap();
+    assert_eq!(arena.len(), 7);
+  }
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use crate::{
+  context::Context,
+  expr::{Expr, ExprInfo, ExprKind},
+  lexer::Span,
+  symbol::Symbol,
+};
+
+/// Called when a `breakpoint` is reached, with its name (if any), the current
+/// [`Context`] and the `breakpoint` [`Expr`].
+#[cfg(not(feature = "sync"))]
+pub type BreakpointHook = Arc<dyn Fn(Option<Symbol>, &Context, &Expr)>;
+#[cfg(feature = "sync")]
+pub type BreakpointHook =
+  Arc<dyn Fn(Option<Symbol>, &Context, &Expr) + Send + Sync>;
+
+/// Returns the name of a `#break` comment directive, or [`None`] if the
+/// comment isn't one.
+///
+/// The name is empty for unnamed breakpoints.
+pub fn break_directive(comment: &str) -> Option<&str> {
+  let rest = comment.strip_prefix("#break")?;
+
+  (rest.is_empty() || rest.starts_with(char::is_whitespace))
+    .then(|| rest.trim())
+}
+
+/// Inserts a `breakpoint` in place of each `; #break [name]` comment in
+/// top-level code and function bodies.
+pub fn expand_break_directives(exprs: &mut Vec<Expr>) {
+  // Comments at the end of the source dangle from the last expression.
+  let last = exprs.last().and_then(|expr| expr.info.clone());
+
+  expand(exprs);
+
+  if let Some(info) = last {
+    exprs.extend(
+      info
+        .comments
+        .dangling
+        .iter()
+        .filter(|span| span.start >= info.span.end)
+        .filter_map(|span| breakpoint(&info, *span)),
+    );
+  }
+}
+
+fn expand(exprs: &mut Vec<Expr>) {
+  let mut expanded = Vec::with_capacity(exprs.len());
+
+  for mut expr in exprs.drain(..) {
+    let info = expr.info.clone();
+
+    if let Some(info) = &info {
+      expanded.extend(
+        info
+          .comments
+          .leading
+          .iter()
+          .filter_map(|span| breakpoint(info, *span)),
+      );
+    }
+
+    expand_function(&mut expr);
+    expanded.push(expr);
+
+    if let Some(info) = &info {
+      expanded.extend(
+        info
+          .comments
+          .trailing
+          .iter()
+          .filter_map(|span| breakpoint(info, *span)),
+      );
+    }
+  }
+
+  *exprs = expanded;
+}
+
+fn expand_function(expr: &mut Expr) {
+  match &mut expr.kind {
+    ExprKind::Lazy(x) => expand_function(x),
+    ExprKind::Function { body, .. } => {
+      expand(body);
+
+      if let Some(info) = &expr.info {
+        body.extend(
+          info
+            .comments
+            .dangling
+            .iter()
+            .filter(|span| span.start < info.span.end)
+            .filter_map(|span| breakpoint(info, *span)),
+        );
+      }
+    }
+    _ => {}
+  }
+}
+
+fn breakpoint(info: &ExprInfo, span: Span) -> Option<Expr> {
+  let name = match break_directive(info.comment_text(span))? {
+    "" => ExprKind::Nil,
+    name => ExprKind::String(name.into()),
+  };
+
+  Some(Expr {
+    kind: ExprKind::SExpr {
+      call: Symbol::from_ref("breakpoint"),
+      body: vec![name.into()],
+    },
+    info: Some(ExprInfo::new(info.source.clone(), span)),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::Mutex;
+
+  use crate::prelude::*;
+
+  #[test]
+  fn expands_directives() {
+    let source =
+      "; #break\n1 ; #break one\n'(fn\n  ; #break two\n  2\n  ; #break three\n) ; #break four\n; #breakfast\n; #break end";
+    let mut lexer = Lexer::new(Source::new("", source));
+    let mut exprs = parse(&mut lexer).unwrap();
+    expand_break_directives(&mut exprs);
+
+    let names = |exprs: &[Expr]| {
+      exprs
+        .iter()
+        .map(|expr| match &expr.kind {
+          ExprKind::SExpr { body, .. } => format!("break {}", body[0]),
+          kind => kind.to_string(),
+        })
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      names(&exprs),
+      vec![
+        "break nil",
+        "1",
+        "break one",
+        "(fn (breakpoint two) 2 (breakpoint three))",
+        "break four",
+        "break end",
+      ]
+    );
+  }
+
+  #[test]
+  fn calls_the_hook() {
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let hook_names = names.clone();
+
+    let engine =
+      Engine::new().with_breakpoint_hook(Some(Arc::new(move |name, _, _| {
+        hook_names.lock().unwrap().push(name.map(|x| x.to_string()));
+      })));
+
+    let mut lexer =
+      Lexer::new(Source::new("", "nil breakpoint \"a\" breakpoint"));
+    let exprs = parse(&mut lexer).unwrap();
+    let context = engine.run(Context::new(), exprs).unwrap();
+
+    assert!(context.stack().is_empty());
+    assert_eq!(*names.lock().unwrap(), vec![None, Some("a".into())]);
+  }
+}
@@ -0,0 +1,306 @@
+//! Report bundles, which package everything needed to reproduce a failed run
+//! into a zip archive that can be attached to a bug report.
+//!
+//! A bundle made from a [`RunError`] contains:
+//!
+//! - `version.txt`, with the version of this crate (and of any others added
+//!   with [`ReportBundle::with_version`]) and the platform.
+//! - `error.txt`, with the error and where it happened.
+//! - `stack.txt`, with the stack when it failed.
+//! - `sources/`, with each source file that was run.
+//! - `journal.txt` and `trace.txt`, with the journal and the last operations
+//!   that ran, if the journal was enabled.
+//!
+//! The archive is written without compression, so that it doesn't need any
+//! dependencies beyond a checksum.
+
+use std::{
+  fs::File,
+  io::{self, BufWriter, Write},
+  path::Path,
+};
+
+use crate::{
+  engine::RunError, journal::JournalEntry, parser::ParseError, source::Source,
+};
+
+/// The amount of journal entries in `trace.txt`.
+pub const TRACE_LEN: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportBundle {
+  versions: Vec<(String, String)>,
+  files: Vec<(String, Vec<u8>)>,
+}
+
+impl Default for ReportBundle {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl ReportBundle {
+  /// Creates an empty [`ReportBundle`], which only has the version info.
+  pub fn new() -> Self {
+    Self {
+      versions: vec![("stack-core".into(), env!("CARGO_PKG_VERSION").into())],
+      files: Vec::new(),
+    }
+  }
+
+  /// Creates a [`ReportBundle`] for a failed run.
+  pub fn from_run_error(error: &RunError) -> Self {
+    let mut bundle = Self::new();
+
+    let mut message = format!("{error}\n\nreason: {:?}\n", error.reason);
+    if let Some(ref info) = error.expr.info {
+      message.push_str(&format!("location: {info}\n"));
+    }
+    bundle.add_file("error.txt", message);
+
+    let stack = error
+      .context
+      .stack()
+      .iter()
+      .map(|x| format!("{x:?}\n"))
+      .collect::<String>();
+    bundle.add_file("stack.txt", stack);
+
+    let mut sources = error.context.sources().collect::<Vec<_>>();
+    sources.sort_by_key(|(name, _)| name.as_str());
+    for (_, source) in sources {
+      bundle.add_source(source);
+    }
+
+    if let Some(journal) = error.context.journal() {
+      bundle.add_file("journal.txt", journal.to_string());
+
+      let entries = journal.entries();
+      let trace = entries
+        .iter()
+        .skip(entries.len().saturating_sub(TRACE_LEN))
+        .map(trace_line)
+        .collect::<String>();
+      bundle.add_file("trace.txt", trace);
+    }
+
+    bundle
+  }
+
+  /// Creates a [`ReportBundle`] for source code which failed to parse.
+  pub fn from_parse_error(error: &ParseError) -> Self {
+    let mut bundle = Self::new();
+
+    bundle.add_file("error.txt", format!("{error}\n"));
+    bundle.add_source(&error.source);
+
+    bundle
+  }
+
+  /// Adds the version of another component, such as the program embedding
+  /// Stack, to `version.txt`.
+  pub fn with_version(mut self, name: &str, version: &str) -> Self {
+    self.versions.push((name.into(), version.into()));
+    self
+  }
+
+  /// Adds a file to the bundle, replacing any with the same name.
+  pub fn add_file<C>(&mut self, name: &str, contents: C) -> &mut Self
+  where
+    C: Into<Vec<u8>>,
+  {
+    self.files.retain(|(x, _)| x != name);
+    self.files.push((name.into(), contents.into()));
+    self
+  }
+
+  /// Adds a source file to `sources/`.
+  pub fn add_source(&mut self, source: &Source) -> &mut Self {
+    // Keep the archive's paths relative, even for absolute source paths.
+    let name = source
+      .name()
+      .trim_start_matches(['/', '\\'])
+      .replace("..", "__");
+
+    self.add_file(&format!("sources/{name}"), source.source())
+  }
+
+  /// Returns the names and contents of the files in the bundle, including
+  /// `version.txt`.
+  pub fn files(&self) -> Vec<(String, Vec<u8>)> {
+    let mut version = self
+      .versions
+      .iter()
+      .map(|(name, version)| format!("{name} {version}\n"))
+      .collect::<String>();
+    version.push_str(&format!(
+      "platform {}-{}\n",
+      std::env::consts::ARCH,
+      std::env::consts::OS
+    ));
+
+    core::iter::once(("version.txt".to_string(), version.into_bytes()))
+      .chain(self.files.iter().cloned())
+      .collect()
+  }
+
+  /// Writes the bundle as a zip archive.
+  pub fn write_zip<W>(&self, writer: W) -> io::Result<()>
+  where
+    W: Write,
+  {
+    write_zip(writer, &self.files())
+  }
+
+  /// Writes the bundle as a zip archive to `path`.
+  pub fn save<P>(&self, path: P) -> io::Result<()>
+  where
+    P: AsRef<Path>,
+  {
+    let mut writer = BufWriter::new(File::create(path)?);
+    self.write_zip(&mut writer)?;
+    writer.flush()
+  }
+}
+
+fn trace_line(entry: &JournalEntry) -> String {
+  let location = entry
+    .ops
+    .iter()
+    .find_map(|op| op.info())
+    .map(|info| info.to_string())
+    .unwrap_or_else(|| "?".into());
+
+  format!("{location}\t{entry:#}\n")
+}
+
+/// Writes `files` as a zip archive, with each file stored uncompressed.
+fn write_zip<W>(mut writer: W, files: &[(String, Vec<u8>)]) -> io::Result<()>
+where
+  W: Write,
+{
+  const VERSION: u16 = 20;
+  // The names are UTF-8.
+  const FLAGS: u16 = 1 << 11;
+  // 1980-01-01 00:00, which is the earliest date a zip can hold.
+  const TIME: u16 = 0;
+  const DATE: u16 = (1 << 5) | 1;
+
+  let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "too large");
+  let u16_len = |x: usize| u16::try_from(x).map_err(|_| too_large());
+  let u32_len = |x: usize| u32::try_from(x).map_err(|_| too_large());
+
+  let mut offset = 0u32;
+  let mut central = Vec::new();
+
+  for (name, contents) in files {
+    let crc = crc32fast::hash(contents);
+    let size = u32_len(contents.len())?;
+    let name_len = u16_len(name.len())?;
+
+    let mut header = Vec::new();
+    header.extend(0x04034b50u32.to_le_bytes());
+    header.extend(VERSION.to_le_bytes());
+    header.extend(FLAGS.to_le_bytes());
+    header.extend(0u16.to_le_bytes()); // stored
+    header.extend(TIME.to_le_bytes());
+    header.extend(DATE.to_le_bytes());
+    header.extend(crc.to_le_bytes());
+    header.extend(size.to_le_bytes());
+    header.extend(size.to_le_bytes());
+    header.extend(name_len.to_le_bytes());
+    header.extend(0u16.to_le_bytes()); // extra field length
+    header.extend(name.as_bytes());
+
+    writer.write_all(&header)?;
+    writer.write_all(contents)?;
+
+    central.extend(0x02014b50u32.to_le_bytes());
+    central.extend(VERSION.to_le_bytes()); // made by
+    central.extend(VERSION.to_le_bytes()); // needed
+    central.extend(FLAGS.to_le_bytes());
+    central.extend(0u16.to_le_bytes()); // stored
+    central.extend(TIME.to_le_bytes());
+    central.extend(DATE.to_le_bytes());
+    central.extend(crc.to_le_bytes());
+    central.extend(size.to_le_bytes());
+    central.extend(size.to_le_bytes());
+    central.extend(name_len.to_le_bytes());
+    central.extend([0; 12]); // extra, comment, disk and attributes
+    central.extend(offset.to_le_bytes());
+    central.extend(name.as_bytes());
+
+    offset = offset
+      .checked_add(u32_len(header.len() + contents.len())?)
+      .ok_or_else(too_large)?;
+  }
+
+  let count = u16_len(files.len())?;
+
+  writer.write_all(&central)?;
+  writer.write_all(&0x06054b50u32.to_le_bytes())?;
+  writer.write_all(&[0; 4])?; // disk numbers
+  writer.write_all(&count.to_le_bytes())?;
+  writer.write_all(&count.to_le_bytes())?;
+  writer.write_all(&u32_len(central.len())?.to_le_bytes())?;
+  writer.write_all(&offset.to_le_bytes())?;
+  writer.write_all(&0u16.to_le_bytes()) // comment length
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::prelude::*;
+
+  #[test]
+  fn bundles_failed_runs() {
+    let source = Source::new("main.stack", "1 2 missing");
+    let mut lexer = Lexer::new(source.clone());
+    let exprs = parse(&mut lexer).unwrap();
+
+    let mut context = Context::new().with_journal(None);
+    context.add_source(source);
+
+    let error = Engine::new().run(context, exprs).unwrap_err();
+    let files = ReportBundle::from_run_error(&error)
+      .with_version("test", "1.0.0")
+      .files();
+
+    let names = files.iter().map(|(x, _)| x.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+      names,
+      vec![
+        "version.txt",
+        "error.txt",
+        "stack.txt",
+        "sources/main.stack",
+        "journal.txt",
+        "trace.txt"
+      ]
+    );
+
+    let version = String::from_utf8(files[0].1.clone()).unwrap();
+    assert!(version.contains("test 1.0.0"));
+
+    let error = String::from_utf8(files[1].1.clone()).unwrap();
+    assert!(error.contains("unknown call"));
+    assert!(error.contains("main.stack:1:5"));
+  }
+
+  #[test]
+  fn writes_zips() {
+    let mut bundle = ReportBundle::new();
+    bundle.add_file("a.txt", "hello");
+
+    let mut zip = Vec::new();
+    bundle.write_zip(&mut zip).unwrap();
+
+    assert!(zip.starts_with(&0x04034b50u32.to_le_bytes()));
+    // The end of central directory record is the last 22 bytes, and counts
+    // both files.
+    let end = &zip[zip.len() - 22..];
+    assert_eq!(end[..4], 0x06054b50u32.to_le_bytes());
+    assert_eq!(end[10..12], 2u16.to_le_bytes());
+  }
+}
@@ -1,10 +1,11 @@
-use core::{cell::RefCell, fmt};
-use std::{borrow::BorrowMut, rc::Rc};
+use core::fmt;
+
+use crate::shared::Shared;
 
 #[derive(PartialEq, Clone)]
 pub struct Chain<T> {
-  value: Rc<RefCell<T>>,
-  child: Option<Rc<RefCell<Chain<T>>>>,
+  value: Shared<T>,
+  child: Option<Shared<Chain<T>>>,
   root: bool,
 }
 
@@ -20,24 +21,24 @@ where
 impl<T> Chain<T> {
   pub fn new(value: T) -> Self {
     Self {
-      value: Rc::new(RefCell::new(value)),
+      value: Shared::new(value),
       child: None,
       root: true,
     }
   }
 
-  pub fn link(&mut self) -> Rc<RefCell<Self>> {
-    let child = Rc::new(RefCell::new(Self {
+  pub fn link(&mut self) -> Shared<Self> {
+    let child = Shared::new(Self {
       value: self.value.clone(),
       child: None,
       root: false,
-    }));
-    *self.child.borrow_mut() = Some(child.clone());
+    });
+    self.child = Some(child.clone());
 
     child
   }
 
-  pub fn root(&self) -> Rc<RefCell<T>> {
+  pub fn root(&self) -> Shared<T> {
     self.value.clone()
   }
 
@@ -54,7 +55,7 @@ where
     self.value.borrow().clone()
   }
 
-  fn unlink_with_rc(&mut self, value: Rc<RefCell<T>>, new_root: bool) {
+  fn unlink_with_shared(&mut self, value: Shared<T>, new_root: bool) {
     let mut new_root = new_root;
 
     if new_root {
@@ -65,16 +66,16 @@ where
     self.value = value.clone();
 
     if let Some(child) = &self.child {
-      RefCell::borrow_mut(child).unlink_with_rc(value, new_root);
+      child.borrow_mut().unlink_with_shared(value, new_root);
     }
   }
 
   pub fn unlink_with(&mut self, val: T) {
-    self.unlink_with_rc(Rc::new(RefCell::new(val)), true);
+    self.unlink_with_shared(Shared::new(val), true);
   }
 
   pub fn set(&mut self, val: T) {
-    *RefCell::borrow_mut(&self.value) = val;
+    *self.value.borrow_mut() = val;
   }
 }
 
@@ -111,7 +112,7 @@ mod tests {
   fn change_value_with_link() {
     let mut chain = Chain::new(1);
     let link = chain.link();
-    RefCell::borrow_mut(&link).set(2);
+    link.borrow_mut().set(2);
 
     assert_eq!(chain.val(), 2);
     assert_eq!(link.borrow().val(), 2);
@@ -121,13 +122,13 @@ mod tests {
   fn unlink_chain() {
     let mut a = Chain::new(1);
     let b = a.link();
-    let c = RefCell::borrow_mut(&b).link();
+    let c = b.borrow_mut().link();
 
     assert_eq!(a.val(), 1);
     assert_eq!(b.borrow().val(), 1);
     assert_eq!(c.borrow().val(), 1);
 
-    RefCell::borrow_mut(&b).unlink_with(2);
+    b.borrow_mut().unlink_with(2);
 
     assert_eq!(a.val(), 1);
     assert_eq!(b.borrow().val(), 2);
@@ -144,13 +145,13 @@ mod tests {
     assert_eq!(b.borrow().val(), 1);
     assert_eq!(clone.borrow().val(), 1);
 
-    RefCell::borrow_mut(&b).set(2);
+    b.borrow_mut().set(2);
 
     assert_eq!(a.val(), 2);
     assert_eq!(b.borrow().val(), 2);
     assert_eq!(clone.borrow().val(), 2);
 
-    RefCell::borrow_mut(&b).unlink_with(3);
+    b.borrow_mut().unlink_with(3);
 
     assert_eq!(a.val(), 2);
     assert_eq!(b.borrow().val(), 3);
@@ -161,13 +162,13 @@ mod tests {
   fn unlinked_children_dont_propagate_changes() {
     let mut a = Chain::new(1);
     let b = a.link();
-    let c = RefCell::borrow_mut(&b).link();
+    let c = b.borrow_mut().link();
 
     assert_eq!(a.val(), 1);
     assert_eq!(b.borrow().val(), 1);
     assert_eq!(c.borrow().val(), 1);
 
-    RefCell::borrow_mut(&b).unlink_with(2);
+    b.borrow_mut().unlink_with(2);
 
     a.set(4);
 
@@ -180,13 +181,13 @@ mod tests {
   fn unlinking_and_roots() {
     let mut a = Chain::new(1);
     let b = a.link();
-    let c = RefCell::borrow_mut(&b).link();
+    let c = b.borrow_mut().link();
 
     assert!(a.root);
     assert!(!b.borrow().root);
     assert!(!c.borrow().root);
 
-    RefCell::borrow_mut(&b).unlink_with(2);
+    b.borrow_mut().unlink_with(2);
 
     assert!(a.root);
     assert!(b.borrow().root);
@@ -0,0 +1,134 @@
+//! A compatibility layer for the API of the old `stack` crate.
+//!
+//! The old crate ran code with a [`Program`], which owned its stack and scope,
+//! and exposed [`lex`] and [`parse`] as free functions over strings. These are
+//! re-implemented on top of [`Engine`] and [`Context`] so that existing code
+//! keeps working while it's migrated, but each is deprecated in favour of its
+//! replacement:
+//!
+//! - [`Program`] becomes an [`Engine`] and a [`Context`].
+//! - [`lex`] becomes a [`Lexer`] over a [`Source`].
+//! - [`parse`] becomes [`parse`](crate::parser::parse) with a [`Lexer`].
+
+#![allow(deprecated)]
+
+use core::fmt;
+use std::collections::HashMap;
+
+use crate::{
+  lexer::{Token, TokenKind},
+  prelude::*,
+};
+
+/// Runs code, keeping its stack and scope between runs.
+#[deprecated(note = "use an `Engine` and a `Context` instead")]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+  pub stack: Vec<Expr>,
+  pub scope: HashMap<Symbol, Expr>,
+}
+
+/// An error from [`Program::eval_string`].
+#[deprecated(note = "use `ParseError` and `RunError` instead")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+  Parse(ParseError),
+  Run(Box<RunError>),
+}
+
+impl std::error::Error for EvalError {}
+
+impl fmt::Display for EvalError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Parse(e) => write!(f, "{e}"),
+      Self::Run(e) => write!(f, "{e}"),
+    }
+  }
+}
+
+impl Program {
+  #[deprecated(note = "use `Context::new` instead")]
+  #[inline]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Parses and runs `code`.
+  #[deprecated(note = "use `parse` and `Engine::run` instead")]
+  pub fn eval_string(&mut self, code: &str) -> Result<(), EvalError> {
+    let exprs = parse(code).map_err(EvalError::Parse)?;
+    self.eval(exprs).map_err(|e| EvalError::Run(Box::new(e)))
+  }
+
+  /// Runs `exprs`.
+  #[deprecated(note = "use `Engine::run` instead")]
+  pub fn eval(&mut self, exprs: Vec<Expr>) -> Result<(), RunError> {
+    let context = Engine::new().run(self.context(), exprs)?;
+
+    self.stack = context.stack().to_vec();
+    self.scope = context
+      .scope_items()
+      .filter_map(|(name, item)| item.borrow().val().map(|x| (*name, x)))
+      .collect();
+
+    Ok(())
+  }
+
+  fn context(&self) -> Context {
+    let mut context = Context::new();
+    context.stack_mut().extend(self.stack.iter().cloned());
+
+    for (name, item) in self.scope.iter() {
+      context.def_scope_item(*name, item.clone());
+    }
+
+    context
+  }
+}
+
+/// Returns the tokens of `code`, up to but not including the end of the file.
+#[deprecated(note = "use a `Lexer` instead")]
+pub fn lex(code: &str) -> Vec<Token> {
+  let mut lexer = Lexer::new(Source::new("", code));
+
+  core::iter::from_fn(|| {
+    let token = lexer.next();
+    (token.kind != TokenKind::Eof).then_some(token)
+  })
+  .collect()
+}
+
+/// Parses `code` into expressions.
+#[deprecated(note = "use `stack_core::parser::parse` with a `Lexer` instead")]
+pub fn parse(code: &str) -> Result<Vec<Expr>, ParseError> {
+  let mut lexer = Lexer::new(Source::new("", code));
+  crate::parser::parse(&mut lexer)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn programs_keep_their_state() {
+    let mut program = Program::new();
+    program.eval_string("1 'a def 2").unwrap();
+    program.eval_string("a +").unwrap();
+
+    assert_eq!(
+      program.stack.iter().map(|x| &x.kind).collect::<Vec<_>>(),
+      vec![&ExprKind::Integer(3)]
+    );
+    assert_eq!(
+      program.scope.get(&Symbol::from_ref("a")).map(|x| &x.kind),
+      Some(&ExprKind::Integer(1))
+    );
+  }
+
+  #[test]
+  fn lexes_and_parses_strings() {
+    assert_eq!(lex("1 +").len(), 2);
+    assert_eq!(parse("1 [2]").unwrap().len(), 2);
+  }
+}
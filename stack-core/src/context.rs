@@ -1,25 +1,50 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::collections::HashMap;
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::{
-  chain::Chain,
   engine::{RunError, RunErrorReason},
   expr::{Expr, ExprKind},
+  float::FloatFormat,
+  import::Imports,
+  intrinsic::IntrinsicFilter,
   journal::{Journal, JournalOp},
-  scope::{Scanner, Scope},
+  rng::Rng,
+  scheduler::Mailbox,
+  scope::{contains_function, Scanner, Scope, Val},
+  snapshot::{self, Snapshot},
   source::Source,
   symbol::Symbol,
   vec_one::VecOne,
 };
 
 // TODO: This API could be a lot nicer.
-#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Context {
   stack: Vec<Expr>,
   scopes: VecOne<Scope>,
   journal: Option<Journal>,
   sources: HashMap<Symbol, Source>,
+  #[cfg_attr(feature = "serde", serde(default))]
+  ops: usize,
+  #[cfg_attr(feature = "serde", serde(default))]
+  peak_stack_len: usize,
+  #[cfg_attr(feature = "serde", serde(default))]
+  mailbox: Mailbox,
+  #[cfg_attr(feature = "serde", serde(default))]
+  intrinsics: IntrinsicFilter,
+  #[cfg_attr(feature = "serde", serde(default))]
+  imports: Imports,
+  /// The lowest stack length reached within each pushed scope, used to find
+  /// the items which a scope leaves behind.
+  #[cfg_attr(feature = "serde", serde(default))]
+  scope_floors: Vec<usize>,
+  #[cfg_attr(feature = "serde", serde(default))]
+  rng: Rng,
+  #[cfg_attr(feature = "serde", serde(default))]
+  float_format: FloatFormat,
 }
 
 impl Context {
@@ -30,6 +55,14 @@ impl Context {
       scopes: VecOne::new(Scope::new()),
       journal: None,
       sources: HashMap::new(),
+      ops: 0,
+      peak_stack_len: 0,
+      mailbox: Mailbox::default(),
+      intrinsics: IntrinsicFilter::All,
+      imports: Imports::default(),
+      scope_floors: Vec::new(),
+      rng: Rng::default(),
+      float_format: FloatFormat::default(),
     }
   }
 
@@ -49,6 +82,45 @@ impl Context {
     self
   }
 
+  /// Restricts which intrinsics can be used with this [`Context`].
+  #[inline]
+  pub fn with_intrinsics(mut self, intrinsics: IntrinsicFilter) -> Self {
+    self.intrinsics = intrinsics;
+    self
+  }
+
+  #[inline]
+  pub fn intrinsics(&self) -> &IntrinsicFilter {
+    &self.intrinsics
+  }
+
+  /// Checks that `exprs` don't call any intrinsics which this [`Context`]
+  /// forbids, before they're run.
+  ///
+  /// Forbidden intrinsics are still caught when they're run, but this reports
+  /// them up front, even if they'd never be reached.
+  pub fn check_intrinsics(&self, exprs: &[Expr]) -> Result<(), RunError> {
+    match self.intrinsics.find_forbidden(exprs) {
+      Some(expr) => Err(RunError {
+        reason: RunErrorReason::ForbiddenIntrinsic,
+        context: Box::new(self.clone()),
+        expr: Box::new(expr),
+      }),
+      None => Ok(()),
+    }
+  }
+
+  /// Returns the files which have been imported with this [`Context`].
+  #[inline]
+  pub fn imports(&self) -> &Imports {
+    &self.imports
+  }
+
+  #[inline]
+  pub(crate) fn imports_mut(&mut self) -> &mut Imports {
+    &mut self.imports
+  }
+
   #[inline]
   pub fn add_source(&mut self, source: Source) {
     self.sources.insert(Symbol::from_ref(source.name()), source);
@@ -81,6 +153,83 @@ impl Context {
     &mut self.stack
   }
 
+  /// Returns the number of expressions evaluated with this [`Context`].
+  #[inline]
+  pub fn ops(&self) -> usize {
+    self.ops
+  }
+
+  /// Returns the largest length the stack has reached with this [`Context`].
+  #[inline]
+  pub fn peak_stack_len(&self) -> usize {
+    self.peak_stack_len
+  }
+
+  #[inline]
+  pub(crate) fn tick(&mut self) {
+    self.ops = self.ops.saturating_add(1);
+  }
+
+  /// Returns a [`Snapshot`] of the stack and scopes.
+  pub fn snapshot(&self) -> Snapshot {
+    let mut scopes = self.scopes.iter().map(snapshot::scope_values);
+
+    Snapshot {
+      stack: self.stack.clone(),
+      scope: scopes.next().unwrap_or_default(),
+      lets: scopes.collect(),
+    }
+  }
+
+  /// Replaces the stack and scopes with those from a [`Snapshot`].
+  ///
+  /// Everything else, such as the sources and allowed intrinsics, is kept.
+  pub fn restore(&mut self, snapshot: Snapshot) {
+    self.stack = snapshot.stack;
+    self.scopes = VecOne::new(snapshot::scope_from_values(snapshot.scope));
+    self
+      .scopes
+      .extend(snapshot.lets.into_iter().map(snapshot::scope_from_values));
+    self.scope_floors.clear();
+  }
+
+  #[inline]
+  pub fn mailbox(&self) -> &Mailbox {
+    &self.mailbox
+  }
+
+  #[inline]
+  pub fn mailbox_mut(&mut self) -> &mut Mailbox {
+    &mut self.mailbox
+  }
+
+  #[inline]
+  pub fn rng(&self) -> &Rng {
+    &self.rng
+  }
+
+  #[inline]
+  pub fn rng_mut(&mut self) -> &mut Rng {
+    &mut self.rng
+  }
+
+  /// Sets how floats are shown when they're printed.
+  #[inline]
+  pub fn with_float_format(mut self, float_format: FloatFormat) -> Self {
+    self.float_format = float_format;
+    self
+  }
+
+  #[inline]
+  pub fn float_format(&self) -> FloatFormat {
+    self.float_format
+  }
+
+  #[inline]
+  pub fn set_float_format(&mut self, float_format: FloatFormat) {
+    self.float_format = float_format;
+  }
+
   #[inline]
   pub fn journal(&self) -> &Option<Journal> {
     &self.journal
@@ -100,8 +249,8 @@ impl Context {
         Ok(expr) => Ok(expr),
         Err((expr, reason)) => Err(RunError {
           reason,
-          context: self.clone(),
-          expr,
+          context: Box::new(self.clone()),
+          expr: Box::new(expr),
         }),
       }
     } else {
@@ -117,6 +266,7 @@ impl Context {
     }
 
     self.stack.push(expr);
+    self.peak_stack_len = self.peak_stack_len.max(self.stack.len());
 
     Ok(())
   }
@@ -125,6 +275,7 @@ impl Context {
     let expr = self.scan_expr(expr)?;
 
     self.stack.push(expr);
+    self.peak_stack_len = self.peak_stack_len.max(self.stack.len());
 
     Ok(())
   }
@@ -132,6 +283,7 @@ impl Context {
   pub fn stack_pop(&mut self, expr: &Expr) -> Result<Expr, RunError> {
     match self.stack.pop() {
       Some(expr) => {
+        self.lower_scope_floor();
         if let Some(journal) = self.journal_mut() {
           journal.push_op(JournalOp::Pop(expr.clone()));
         }
@@ -139,19 +291,22 @@ impl Context {
       }
       None => Err(RunError {
         reason: RunErrorReason::StackUnderflow,
-        context: self.clone(),
-        expr: expr.clone(),
+        context: Box::new(self.clone()),
+        expr: Box::new(expr.clone()),
       }),
     }
   }
 
   pub fn stack_silent_pop(&mut self, expr: &Expr) -> Result<Expr, RunError> {
     match self.stack.pop() {
-      Some(expr) => Ok(expr),
+      Some(expr) => {
+        self.lower_scope_floor();
+        Ok(expr)
+      }
       None => Err(RunError {
         reason: RunErrorReason::StackUnderflow,
-        context: self.clone(),
-        expr: expr.clone(),
+        context: Box::new(self.clone()),
+        expr: Box::new(expr.clone()),
       }),
     }
   }
@@ -162,9 +317,7 @@ impl Context {
   }
 
   #[inline]
-  pub fn scope_items(
-    &self,
-  ) -> impl Iterator<Item = (&Symbol, &Rc<RefCell<Chain<Option<Expr>>>>)> {
+  pub fn scope_items(&self) -> impl Iterator<Item = (&Symbol, &Val)> {
     self.scopes.last().items.iter()
   }
 
@@ -212,8 +365,8 @@ impl Context {
       }
       Err(reason) => Err(RunError {
         reason,
-        context: self.clone(),
-        expr,
+        context: Box::new(self.clone()),
+        expr: Box::new(expr),
       }),
     }
   }
@@ -223,6 +376,12 @@ impl Context {
     self.scopes.last_mut().remove(symbol);
   }
 
+  /// Returns how many scopes there are, including the global one.
+  #[inline]
+  pub fn scope_depth(&self) -> usize {
+    self.scopes.len()
+  }
+
   #[inline]
   pub fn push_scope(&mut self, scope: Scope) {
     // if let Some(journal) = self.journal_mut() {
@@ -234,15 +393,59 @@ impl Context {
     //   }
     // }
     self.scopes.push(scope);
+    self.scope_floors.push(self.stack.len());
   }
 
-  #[inline]
+  /// Pops the current scope, which functions nested in the items it leaves on
+  /// the stack capture first.
+  ///
+  /// Functions pushed on their own capture their scope straight away, but
+  /// those within lists or records (such as a record of functions returned
+  /// from a `let`) are only captured once they leave the scope. Lists which
+  /// use the names bound in the scope, such as `'[x 1 +]`, are turned into
+  /// functions which capture it.
   pub fn pop_scope(&mut self) {
+    if self.scopes.len() > 1 {
+      if let Some(floor) = self.scope_floors.pop() {
+        let floor = floor.min(self.stack.len());
+
+        if self.stack[floor..].iter().any(|expr| {
+          !expr.kind.is_function()
+            && (contains_function(&expr.kind)
+              || matches!(
+                expr.kind.unlazy(),
+                ExprKind::List(_) | ExprKind::Record(_) | ExprKind::Map(_)
+              ))
+        }) {
+          let names = self.scopes.last().local_names();
+          let mut duplicate = self.scopes.last().duplicate();
+          let mut scanner = Scanner::new(&mut duplicate);
+
+          for expr in self.stack[floor..].iter_mut() {
+            if !expr.kind.is_function() {
+              *expr = scanner.capture_blocks(expr.clone(), &names);
+            }
+          }
+        }
+
+        if let Some(outer) = self.scope_floors.last_mut() {
+          *outer = (*outer).min(floor);
+        }
+      }
+    }
+
     self.scopes.try_pop();
   }
+
+  #[inline]
+  fn lower_scope_floor(&mut self) {
+    if let Some(floor) = self.scope_floors.last_mut() {
+      *floor = (*floor).min(self.stack.len());
+    }
+  }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "serde"))]
 mod tests {
   use super::*;
 
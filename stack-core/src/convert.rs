@@ -0,0 +1,394 @@
+//! Conversions between Rust values and [`Expr`]s, for typed host functions.
+//!
+//! [`Module::add_fn`] takes a plain Rust function, such as
+//! `|a: f64, b: f64| a.powf(b)`, whose arguments implement [`FromExpr`] and
+//! whose result implements [`IntoExpr`]. The arguments are popped from the
+//! stack, the last argument being the top, and the result is pushed.
+//!
+//! Too few items on the stack is a [`RunErrorReason::StackUnderflow`], which
+//! leaves the stack as it was, and an item of the wrong type is a
+//! [`RunErrorReason::InvalidArgument`].
+//!
+//! With the `derive` feature, both traits can be derived for structs and
+//! enums, which are converted into records. See `stack-derive` for how.
+//!
+//! [`Module::add_fn`]: crate::module::Module::add_fn
+
+use core::any::Any;
+use std::sync::Arc;
+
+use compact_str::CompactString;
+#[cfg(feature = "derive")]
+pub use stack_derive::{FromExpr, IntoExpr};
+
+use crate::{
+  context::Context,
+  engine::{RunError, RunErrorReason},
+  expr::{Expr, ExprKind},
+  foreign::Foreign,
+  shared::MaybeSync,
+  symbol::Symbol,
+};
+
+/// Converts an [`Expr`] into a Rust value.
+pub trait FromExpr: Sized {
+  /// Returns the value, or [`None`] if the [`Expr`] is the wrong type.
+  fn from_expr(expr: Expr) -> Option<Self>;
+}
+
+/// Converts a Rust value into an [`Expr`].
+pub trait IntoExpr {
+  fn into_expr(self) -> Expr;
+}
+
+impl FromExpr for Expr {
+  #[inline]
+  fn from_expr(expr: Expr) -> Option<Self> {
+    Some(expr)
+  }
+}
+
+impl FromExpr for ExprKind {
+  #[inline]
+  fn from_expr(expr: Expr) -> Option<Self> {
+    Some(expr.kind)
+  }
+}
+
+/// Implements [`FromExpr`] for the types wrapped by an [`ExprKind`] variant.
+macro_rules! impl_from_expr {
+  ($($ty:ty => $variant:ident),* $(,)?) => {
+    $(
+      impl FromExpr for $ty {
+        #[inline]
+        fn from_expr(expr: Expr) -> Option<Self> {
+          match expr.kind {
+            ExprKind::$variant(x) => Some(x.into()),
+            _ => None,
+          }
+        }
+      }
+    )*
+  };
+}
+
+impl_from_expr! {
+  bool => Boolean,
+  i64 => Integer,
+  String => String,
+  CompactString => String,
+  char => Char,
+  Symbol => Symbol,
+  Foreign => Foreign,
+}
+
+/// A foreign value holding a `T`.
+impl<T> FromExpr for Arc<T>
+where
+  T: Any + Send + Sync,
+{
+  #[inline]
+  fn from_expr(expr: Expr) -> Option<Self> {
+    match expr.kind {
+      ExprKind::Foreign(x) => x.downcast(),
+      _ => None,
+    }
+  }
+}
+
+/// Integers are converted too, so that functions on floats take either.
+impl FromExpr for f64 {
+  #[inline]
+  fn from_expr(expr: Expr) -> Option<Self> {
+    match expr.kind {
+      ExprKind::Float(x) => Some(x),
+      ExprKind::Integer(x) => Some(x as f64),
+      _ => None,
+    }
+  }
+}
+
+impl<T> FromExpr for Vec<T>
+where
+  T: FromExpr,
+{
+  fn from_expr(expr: Expr) -> Option<Self> {
+    match expr.kind {
+      ExprKind::List(x) => x.into_iter().map(T::from_expr).collect(),
+      _ => None,
+    }
+  }
+}
+
+/// `nil` is converted into [`None`].
+impl<T> FromExpr for Option<T>
+where
+  T: FromExpr,
+{
+  #[inline]
+  fn from_expr(expr: Expr) -> Option<Self> {
+    match expr.kind {
+      ExprKind::Nil => Some(None),
+      _ => T::from_expr(expr).map(Some),
+    }
+  }
+}
+
+impl IntoExpr for Expr {
+  #[inline]
+  fn into_expr(self) -> Expr {
+    self
+  }
+}
+
+impl IntoExpr for ExprKind {
+  #[inline]
+  fn into_expr(self) -> Expr {
+    self.into()
+  }
+}
+
+/// Implements [`IntoExpr`] for the types wrapped by an [`ExprKind`] variant.
+macro_rules! impl_into_expr {
+  ($($ty:ty => $variant:ident),* $(,)?) => {
+    $(
+      impl IntoExpr for $ty {
+        #[inline]
+        fn into_expr(self) -> Expr {
+          ExprKind::$variant(self.into()).into()
+        }
+      }
+    )*
+  };
+}
+
+impl_into_expr! {
+  bool => Boolean,
+  i64 => Integer,
+  f64 => Float,
+  String => String,
+  &str => String,
+  CompactString => String,
+  char => Char,
+  Symbol => Symbol,
+  Foreign => Foreign,
+}
+
+/// Pushes a foreign value.
+impl<T> IntoExpr for Arc<T>
+where
+  T: Any + Send + Sync,
+{
+  #[inline]
+  fn into_expr(self) -> Expr {
+    ExprKind::Foreign(Foreign::from_arc(self)).into()
+  }
+}
+
+/// Pushes `nil`.
+impl IntoExpr for () {
+  #[inline]
+  fn into_expr(self) -> Expr {
+    ExprKind::Nil.into()
+  }
+}
+
+impl<T> IntoExpr for Vec<T>
+where
+  T: IntoExpr,
+{
+  #[inline]
+  fn into_expr(self) -> Expr {
+    ExprKind::List(self.into_iter().map(IntoExpr::into_expr).collect()).into()
+  }
+}
+
+/// [`None`] is converted into `nil`.
+impl<T> IntoExpr for Option<T>
+where
+  T: IntoExpr,
+{
+  #[inline]
+  fn into_expr(self) -> Expr {
+    self.map_or(ExprKind::Nil.into(), IntoExpr::into_expr)
+  }
+}
+
+/// An [`Err`] is converted into an error value holding its message, the same
+/// as the standard modules push when something fails.
+impl<T, E> IntoExpr for Result<T, E>
+where
+  T: IntoExpr,
+  E: ToString,
+{
+  #[inline]
+  fn into_expr(self) -> Expr {
+    match self {
+      Ok(x) => x.into_expr(),
+      Err(e) => {
+        ExprKind::Error(Box::new(ExprKind::String(e.to_string().into()).into()))
+          .into()
+      }
+    }
+  }
+}
+
+/// A Rust function which can be added to a [`Module`] with
+/// [`Module::add_fn`]. `Args` is the tuple of its argument types.
+///
+/// [`Module`]: crate::module::Module
+/// [`Module::add_fn`]: crate::module::Module::add_fn
+pub trait HostFn<Args>: MaybeSync + 'static {
+  fn call(&self, context: Context, expr: &Expr) -> Result<Context, RunError>;
+}
+
+/// Implements [`HostFn`] for functions taking each of the argument types.
+macro_rules! impl_host_fn {
+  ($($arg:ident),*) => {
+    impl<T, R, $($arg,)*> HostFn<($($arg,)*)> for T
+    where
+      T: Fn($($arg),*) -> R + MaybeSync + 'static,
+      R: IntoExpr,
+      $($arg: FromExpr,)*
+    {
+      #[allow(
+        non_snake_case,
+        unused_mut,
+        unused_variables,
+        clippy::reversed_empty_ranges
+      )]
+      fn call(
+        &self,
+        mut context: Context,
+        expr: &Expr,
+      ) -> Result<Context, RunError> {
+        const ARITY: usize = <[&str]>::len(&[$(stringify!($arg)),*]);
+
+        if context.stack().len() < ARITY {
+          return Err(RunError {
+            reason: RunErrorReason::StackUnderflow,
+            context: Box::new(context),
+            expr: Box::new(expr.clone()),
+          });
+        }
+
+        let mut args = Vec::with_capacity(ARITY);
+        for _ in 0..ARITY {
+          args.push(context.stack_pop(expr)?);
+        }
+        let mut args = args.into_iter().rev();
+
+        $(
+          let $arg = match $arg::from_expr(args.next().unwrap()) {
+            Some(x) => x,
+            None => {
+              return Err(RunError {
+                reason: RunErrorReason::InvalidArgument,
+                context: Box::new(context),
+                expr: Box::new(expr.clone()),
+              })
+            }
+          };
+        )*
+
+        context.stack_push(self($($arg),*).into_expr())?;
+
+        Ok(context)
+      }
+    }
+  };
+}
+
+impl_host_fn!();
+impl_host_fn!(A);
+impl_host_fn!(A, B);
+impl_host_fn!(A, B, C);
+impl_host_fn!(A, B, C, D);
+impl_host_fn!(A, B, C, D, E);
+impl_host_fn!(A, B, C, D, E, F);
+
+#[cfg(test)]
+mod tests {
+  use crate::prelude::*;
+
+  fn run(module: Module, code: &str) -> Result<Context, RunError> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    Engine::new().with_module(module).run(Context::new(), exprs)
+  }
+
+  fn module() -> Module {
+    Module::new(Symbol::from_ref("test"))
+      .with_fn(Symbol::from_ref("pow"), |a: f64, b: f64| a.powf(b))
+      .with_fn(Symbol::from_ref("len"), |x: Vec<i64>| x.len() as i64)
+      .with_fn(Symbol::from_ref("first"), |x: String| x.chars().next())
+      .with_fn(Symbol::from_ref("parse"), |x: String| x.parse::<i64>())
+  }
+
+  #[test]
+  fn converts_arguments_and_results() {
+    let context =
+      run(module(), "2 3 test:pow [1 2 3] test:len \"\" test:first").unwrap();
+
+    assert_eq!(
+      context.stack(),
+      &[
+        ExprKind::Float(8.0).into(),
+        ExprKind::Integer(3).into(),
+        ExprKind::Nil.into(),
+      ]
+    );
+  }
+
+  #[test]
+  fn converts_errors_into_values() {
+    let context = run(module(), "\"1\" test:parse \"a\" test:parse").unwrap();
+
+    assert_eq!(context.stack()[0], ExprKind::Integer(1).into());
+    assert!(matches!(context.stack()[1].kind, ExprKind::Error(_)));
+  }
+
+  #[test]
+  fn errors_on_invalid_arguments() {
+    let err = run(module(), "2 \"a\" test:pow").unwrap_err();
+    assert_eq!(err.reason, RunErrorReason::InvalidArgument);
+
+    let err = run(module(), "[1 \"a\"] test:len").unwrap_err();
+    assert_eq!(err.reason, RunErrorReason::InvalidArgument);
+  }
+
+  #[test]
+  fn errors_without_changing_the_stack_on_underflow() {
+    let err = run(module(), "2 test:pow").unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::StackUnderflow);
+    assert_eq!(err.context.stack(), &[ExprKind::Integer(2).into()]);
+  }
+
+  #[test]
+  fn passes_foreign_values_between_functions() {
+    use std::sync::{Arc, Mutex};
+
+    let module = Module::new(Symbol::from_ref("counter"))
+      .with_fn(Symbol::from_ref("new"), || Arc::new(Mutex::new(0i64)))
+      .with_fn(Symbol::from_ref("incr"), |x: Arc<Mutex<i64>>| {
+        *x.lock().unwrap() += 1;
+        x
+      })
+      .with_fn(Symbol::from_ref("get"), |x: Arc<Mutex<i64>>| {
+        *x.lock().unwrap()
+      });
+
+    let context = run(
+      module.clone(),
+      "counter:new counter:incr counter:incr counter:get",
+    )
+    .unwrap();
+    assert_eq!(context.stack(), &[ExprKind::Integer(2).into()]);
+
+    let err = run(module, "[] counter:get").unwrap_err();
+    assert_eq!(err.reason, RunErrorReason::InvalidArgument);
+  }
+}
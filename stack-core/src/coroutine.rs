@@ -0,0 +1,230 @@
+//! Generators, which run a block a step at a time.
+//!
+//! `generator` turns a block into a [`Generator`]. Each `resume` runs the
+//! block until it next calls `yield`, and pushes the value it yielded. Once
+//! the block has finished, `resume` pushes `nil` and `done?` is `true`.
+//!
+//! The block runs in a new context which shares the definitions of the one
+//! which made it, so it can `yield` from within loops and functions. This is
+//! done by running it on its own thread, which only runs while the generator
+//! is being resumed, hence generators need the `sync` feature. The thread has
+//! a stack of [`THREAD_STACK_SIZE`], so that it can yield from deep within
+//! nested calls.
+//!
+//! Each generator holds its thread, and the memory reserved for its stack,
+//! until it has finished or the last handle to it is dropped. A script which
+//! keeps many unfinished generators around keeps as many threads, and once
+//! the OS won't spawn another, `generator` fails with
+//! [`RunErrorReason::ThreadSpawnFailed`] rather than aborting the run.
+//!
+//! [`RunErrorReason::ThreadSpawnFailed`]: crate::engine::RunErrorReason::ThreadSpawnFailed
+
+use std::{
+  cell::RefCell,
+  sync::{
+    mpsc::{self, Receiver, Sender},
+    Mutex,
+  },
+  thread,
+};
+
+use crate::{
+  context::Context,
+  engine::{Engine, RunError, THREAD_STACK_SIZE},
+  expr::Expr,
+};
+
+/// What a generator did when it was resumed.
+enum Event {
+  Yield(Expr),
+  Done(Box<Result<(), RunError>>),
+}
+
+struct State {
+  resume: Sender<()>,
+  events: Receiver<Event>,
+  is_done: bool,
+}
+
+/// A block which can be run a step at a time.
+pub struct Generator {
+  state: Mutex<State>,
+}
+
+/// The ends of the channels which the thread of a generator uses.
+struct Yielder {
+  events: Sender<Event>,
+  resume: Receiver<()>,
+}
+
+/// The reason a `yield` couldn't continue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YieldError {
+  /// It wasn't called from within a generator.
+  OutsideGenerator,
+  /// The generator was dropped, so it'll never be resumed.
+  Dropped,
+}
+
+thread_local! {
+  static YIELDER: RefCell<Option<Yielder>> = const { RefCell::new(None) };
+}
+
+impl Generator {
+  /// Creates a generator which runs `block` in a new context, sharing the
+  /// definitions of `context`. Nothing is run until it's first resumed.
+  ///
+  /// Returns an error if the generator's thread couldn't be spawned.
+  pub fn new(
+    engine: &Engine,
+    context: &Context,
+    block: Expr,
+  ) -> std::io::Result<Self> {
+    let (resume, resume_rx) = mpsc::channel();
+    let (events_tx, events) = mpsc::channel();
+
+    let mut context = context.clone();
+    context.stack_mut().clear();
+    *context.journal_mut() = None;
+
+    let engine = engine.clone();
+    thread::Builder::new()
+      .name("generator".into())
+      .stack_size(THREAD_STACK_SIZE)
+      .spawn(move || {
+        // The generator was dropped without being resumed.
+        if resume_rx.recv().is_err() {
+          return;
+        }
+
+        let done = events_tx.clone();
+        YIELDER.with(|x| {
+          *x.borrow_mut() = Some(Yielder {
+            events: events_tx,
+            resume: resume_rx,
+          })
+        });
+
+        let result = engine.call_expr(context, block).map(drop);
+        let _ = done.send(Event::Done(Box::new(result)));
+      })?;
+
+    Ok(Self {
+      state: Mutex::new(State {
+        resume,
+        events,
+        is_done: false,
+      }),
+    })
+  }
+
+  /// Runs the block until it next yields, returning the value it yielded, or
+  /// [`None`] if it has finished.
+  pub fn resume(&self) -> Result<Option<Expr>, RunError> {
+    let mut state = self.state.lock().unwrap_or_else(|x| x.into_inner());
+
+    if state.is_done {
+      return Ok(None);
+    }
+
+    let event = match state.resume.send(()) {
+      Ok(()) => state.events.recv().ok(),
+      Err(_) => None,
+    };
+
+    match event {
+      Some(Event::Yield(value)) => Ok(Some(value)),
+      Some(Event::Done(result)) => {
+        state.is_done = true;
+        result.map(|_| None)
+      }
+      // The thread panicked.
+      None => {
+        state.is_done = true;
+        Ok(None)
+      }
+    }
+  }
+
+  /// Returns whether the block has finished.
+  pub fn is_done(&self) -> bool {
+    self.state.lock().unwrap_or_else(|x| x.into_inner()).is_done
+  }
+}
+
+/// Passes `value` to whatever resumed the current generator, and waits until
+/// it's resumed again.
+pub fn yield_value(value: Expr) -> Result<(), YieldError> {
+  YIELDER.with(|x| {
+    let yielder = x.borrow();
+    let yielder = yielder.as_ref().ok_or(YieldError::OutsideGenerator)?;
+
+    yielder
+      .events
+      .send(Event::Yield(value))
+      .map_err(|_| YieldError::Dropped)?;
+    yielder.resume.recv().map_err(|_| YieldError::Dropped)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::prelude::*;
+
+  fn run(code: &str) -> Result<Context, RunError> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    Engine::new().run(Context::new(), exprs)
+  }
+
+  #[test]
+  fn yields_from_within_loops() {
+    let context = run(
+      "0 'i def
+      '[[i 3 <] [i yield i 1 + 'i set] while] generator 'g def
+      g resume g resume g done? g resume g resume g done? g resume",
+    )
+    .unwrap();
+
+    assert_eq!(
+      context.stack(),
+      &[
+        ExprKind::Integer(0).into(),
+        ExprKind::Integer(1).into(),
+        ExprKind::Boolean(false).into(),
+        ExprKind::Integer(2).into(),
+        ExprKind::Nil.into(),
+        ExprKind::Boolean(true).into(),
+        ExprKind::Nil.into(),
+      ]
+    );
+  }
+
+  #[test]
+  fn yields_from_within_nested_calls() {
+    let context =
+      run("'[[[[[[1 yield] call] call] call] call] call] generator resume")
+        .unwrap();
+
+    assert_eq!(context.stack(), &[ExprKind::Integer(1).into()]);
+  }
+
+  #[test]
+  fn passes_errors_to_the_resumer() {
+    let err =
+      run("'[1 yield \"boom\" throw] generator 'g def g resume g resume")
+        .unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::Thrown);
+    assert_eq!(err.context.stack(), &[ExprKind::Integer(1).into()]);
+  }
+
+  #[test]
+  fn errors_on_yield_outside_of_a_generator() {
+    let err = run("1 yield").unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::InvalidYield);
+  }
+}
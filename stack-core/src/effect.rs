@@ -0,0 +1,138 @@
+//! Stack effects, which describe what a word pops and pushes.
+//!
+//! A definition declares its stack effect with a line of its `##` doc
+//! comments written in the usual notation:
+//!
+//! ```clj
+//! ## ( a b -- c )
+//! ## Adds two numbers.
+//! '(fn +) 'add def
+//! ```
+//!
+//! The declaration is kept in the scope metadata of the definition, and
+//! [`Engine::with_effect_checks`] verifies it whenever the word is called.
+//!
+//! [`Engine::with_effect_checks`]: crate::engine::Engine::with_effect_checks
+
+use core::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StackEffect {
+  /// The names of the values it pops, with the top of the stack last.
+  pub inputs: Vec<String>,
+  /// The names of the values it pushes, with the top of the stack last.
+  pub outputs: Vec<String>,
+}
+
+impl StackEffect {
+  /// Creates a stack effect which names its values `a`, `b`, `c` and so on.
+  pub fn from_counts(pops: usize, pushes: usize) -> Self {
+    let name = |i: usize| {
+      let letter = (b'a' + (i % 26) as u8) as char;
+      match i / 26 {
+        0 => letter.to_string(),
+        n => format!("{letter}{n}"),
+      }
+    };
+
+    Self {
+      inputs: (0..pops).map(name).collect(),
+      outputs: (pops..pops + pushes).map(name).collect(),
+    }
+  }
+
+  #[inline]
+  pub fn pops(&self) -> usize {
+    self.inputs.len()
+  }
+
+  #[inline]
+  pub fn pushes(&self) -> usize {
+    self.outputs.len()
+  }
+}
+
+impl FromStr for StackEffect {
+  type Err = InvalidStackEffect;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let inner = s
+      .trim()
+      .strip_prefix('(')
+      .and_then(|s| s.strip_suffix(')'))
+      .ok_or(InvalidStackEffect)?;
+
+    let words = inner.split_whitespace().collect::<Vec<_>>();
+    let mut sides = words.split(|word| *word == "--");
+    let owned = |words: &[&str]| words.iter().map(|x| x.to_string()).collect();
+
+    // There must be exactly one `--`.
+    let (Some(inputs), Some(outputs), None) =
+      (sides.next(), sides.next(), sides.next())
+    else {
+      return Err(InvalidStackEffect);
+    };
+
+    Ok(Self {
+      inputs: owned(inputs),
+      outputs: owned(outputs),
+    })
+  }
+}
+
+impl fmt::Display for StackEffect {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "(")?;
+
+    for input in self.inputs.iter() {
+      write!(f, " {input}")?;
+    }
+
+    write!(f, " --")?;
+
+    for output in self.outputs.iter() {
+      write!(f, " {output}")?;
+    }
+
+    write!(f, " )")
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidStackEffect;
+
+impl std::error::Error for InvalidStackEffect {}
+
+impl fmt::Display for InvalidStackEffect {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "expected a stack effect such as ( a b -- c )")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_stack_effects() {
+    let effect = "( a b -- c )".parse::<StackEffect>().unwrap();
+    assert_eq!(effect.inputs, vec!["a", "b"]);
+    assert_eq!(effect.outputs, vec!["c"]);
+
+    let effect = "(--)".parse::<StackEffect>();
+    assert_eq!(effect, Ok(StackEffect::default()));
+
+    assert_eq!("( a b )".parse::<StackEffect>(), Err(InvalidStackEffect));
+    assert_eq!(
+      "( a -- b -- c )".parse::<StackEffect>(),
+      Err(InvalidStackEffect)
+    );
+    assert_eq!("a -- b".parse::<StackEffect>(), Err(InvalidStackEffect));
+  }
+
+  #[test]
+  fn displays_stack_effects() {
+    assert_eq!(StackEffect::from_counts(2, 1).to_string(), "( a b -- c )");
+    assert_eq!(StackEffect::from_counts(0, 0).to_string(), "( -- )");
+  }
+}
@@ -1,27 +1,72 @@
-use core::{fmt, str::FromStr};
+use core::fmt;
 use std::{
-  collections::HashMap,
+  collections::{HashMap, HashSet},
+  path::{Path, PathBuf},
   sync::Arc,
   time::{Duration, Instant},
 };
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::{
+  breakpoint::BreakpointHook,
   context::Context,
+  effect::StackEffect,
   expr::{Expr, ExprKind, FnScope},
   intrinsic::Intrinsic,
   journal::JournalOp,
-  module::Module,
+  module::{Func, Module, ModuleLoader},
+  profile::Profiler,
+  shared::Shared,
+  stats::RunStats,
   symbol::Symbol,
+  trace::{self, TraceEvent, Tracer},
+  watchpoint::{WatchEvent, WatchHook},
 };
 
+/// The stack size of the threads which run blocks on their own, such as
+/// generators.
+///
+/// Each nested call takes a lot of stack, especially in debug builds, so this
+/// is larger than the 2 MiB which Rust gives new threads, and than the 8 MiB
+/// which the main thread usually has.
+pub const THREAD_STACK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Called with the output of `debug`.
+#[cfg(not(feature = "sync"))]
+pub type DebugHook = Arc<dyn Fn(String)>;
+#[cfg(feature = "sync")]
+pub type DebugHook = Arc<dyn Fn(String) + Send + Sync>;
+
+/// Decides whether a file can be imported, given its resolved path.
+#[cfg(not(feature = "sync"))]
+pub type ImportGuard = Arc<dyn Fn(&Path) -> bool>;
+#[cfg(feature = "sync")]
+pub type ImportGuard = Arc<dyn Fn(&Path) -> bool + Send + Sync>;
+
 #[derive(Clone, Default)]
 pub struct Engine {
   modules: HashMap<Symbol, Module>,
+  loaders: HashMap<Symbol, ModuleLoader>,
+  loaded: Shared<HashMap<Symbol, Module>>,
   start_time: Option<Instant>,
   timeout: Option<Duration>,
-  debug_hook: Option<Arc<dyn Fn(String)>>,
+  fuel: Option<usize>,
+  stack_limit: Option<usize>,
+  search_paths: Vec<PathBuf>,
+  packages: HashMap<String, PathBuf>,
+  import_guard: Option<ImportGuard>,
+  debug_hook: Option<DebugHook>,
+  breakpoint_hook: Option<BreakpointHook>,
+  watchpoints: HashSet<Symbol>,
+  watch_hook: Option<WatchHook>,
+  profiler: Option<Profiler>,
+  tracer: Option<Tracer>,
+  stats: Option<Shared<RunStats>>,
+  removed_intrinsics: HashSet<Intrinsic>,
+  intrinsic_overrides: HashMap<Intrinsic, Func>,
+  check_effects: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -36,12 +81,34 @@ impl Engine {
   pub fn new() -> Self {
     Self {
       modules: HashMap::new(),
+      loaders: HashMap::new(),
+      loaded: Shared::default(),
       start_time: None,
       timeout: None,
+      fuel: None,
+      stack_limit: None,
+      search_paths: Vec::new(),
+      packages: HashMap::new(),
+      import_guard: None,
       debug_hook: None,
+      breakpoint_hook: None,
+      watchpoints: HashSet::new(),
+      watch_hook: None,
+      profiler: None,
+      tracer: None,
+      stats: None,
+      removed_intrinsics: HashSet::new(),
+      intrinsic_overrides: HashMap::new(),
+      check_effects: false,
     }
   }
 
+  /// Creates an [`EngineBuilder`], which can also change the intrinsics.
+  #[inline]
+  pub fn builder() -> EngineBuilder {
+    EngineBuilder::new()
+  }
+
   #[inline]
   pub fn with_module(mut self, module: Module) -> Self {
     self.add_module(module);
@@ -54,25 +121,317 @@ impl Engine {
     self
   }
 
+  /// Calls `func` instead of an intrinsic.
+  #[inline]
+  pub fn override_intrinsic(
+    &mut self,
+    intrinsic: Intrinsic,
+    func: Func,
+  ) -> &mut Self {
+    self.removed_intrinsics.remove(&intrinsic);
+    self.intrinsic_overrides.insert(intrinsic, func);
+    self
+  }
+
+  /// Loads a native module from the dynamic library at `path`, returning the
+  /// name of the module. See [`plugin`](crate::plugin) for the interface.
+  ///
+  /// # Safety
+  ///
+  /// Loading a library runs its initialisation code, and the library must
+  /// follow the plugin interface.
+  #[cfg(feature = "plugin")]
+  pub unsafe fn load_plugin(
+    &mut self,
+    path: impl AsRef<std::path::Path>,
+  ) -> Result<Symbol, crate::plugin::PluginError> {
+    let module = crate::plugin::load(path)?;
+    let name = module.name();
+    self.add_module(module);
+
+    Ok(name)
+  }
+
+  /// Makes a module available to `import` by name, without creating it until
+  /// it's first imported.
   #[inline]
-  pub fn with_debug_hook(
+  pub fn with_module_loader(
     mut self,
-    debug_hook: Option<Arc<dyn Fn(String)>>,
+    name: Symbol,
+    loader: ModuleLoader,
   ) -> Self {
+    self.add_module_loader(name, loader);
+    self
+  }
+
+  #[inline]
+  pub fn add_module_loader(
+    &mut self,
+    name: Symbol,
+    loader: ModuleLoader,
+  ) -> &mut Self {
+    self.loaded.borrow_mut().remove(&name);
+    self.loaders.insert(name, loader);
+    self
+  }
+
+  /// Returns whether `name` is a module which can be imported, loading it if
+  /// it hasn't been already.
+  pub(crate) fn load_module(&self, name: Symbol) -> bool {
+    if self.modules.contains_key(&name)
+      || self.loaded.borrow().contains_key(&name)
+    {
+      return true;
+    }
+
+    match self.loaders.get(&name) {
+      Some(loader) => {
+        self.loaded.borrow_mut().insert(name, loader());
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Returns a function of a module, if the module has been added or imported
+  /// into the [`Context`].
+  fn module_func(
+    &self,
+    context: &Context,
+    module: Symbol,
+    func: Symbol,
+  ) -> Option<Func> {
+    if let Some(module) = self.modules.get(&module) {
+      return module.func(func);
+    }
+
+    if context.imports().is_module_imported(module) && self.load_module(module)
+    {
+      return self.loaded.borrow().get(&module)?.func(func);
+    }
+
+    None
+  }
+
+  #[inline]
+  pub fn with_debug_hook(mut self, debug_hook: Option<DebugHook>) -> Self {
     self.debug_hook = debug_hook;
     self
   }
 
+  /// Limits the amount of operations a [`Context`] can run, erroring with
+  /// [`RunErrorReason::OutOfFuel`] once they've been used up.
+  #[inline]
+  pub fn with_fuel(mut self, fuel: Option<usize>) -> Self {
+    self.fuel = fuel;
+    self
+  }
+
+  /// Limits the length of the stack, erroring with
+  /// [`RunErrorReason::StackOverflow`] if it's exceeded before an operation.
+  #[inline]
+  pub fn with_stack_limit(mut self, stack_limit: Option<usize>) -> Self {
+    self.stack_limit = stack_limit;
+    self
+  }
+
+  /// Sets the directories searched by `import`, in order, after the directory
+  /// of the importing file.
+  #[inline]
+  pub fn with_search_paths(mut self, search_paths: Vec<PathBuf>) -> Self {
+    self.search_paths = search_paths;
+    self
+  }
+
+  #[inline]
+  pub fn add_search_path(&mut self, path: PathBuf) -> &mut Self {
+    self.search_paths.push(path);
+    self
+  }
+
+  #[inline]
+  pub fn search_paths(&self) -> &[PathBuf] {
+    &self.search_paths
+  }
+
+  /// Sets the packages which can be imported from with `name:path`, mapping
+  /// each package name to its directory.
+  #[inline]
+  pub fn with_packages(mut self, packages: HashMap<String, PathBuf>) -> Self {
+    self.packages = packages;
+    self
+  }
+
+  #[inline]
+  pub fn add_package(&mut self, name: String, dir: PathBuf) -> &mut Self {
+    self.packages.insert(name, dir);
+    self
+  }
+
+  #[inline]
+  pub fn packages(&self) -> &HashMap<String, PathBuf> {
+    &self.packages
+  }
+
+  /// Sets the guard which every file is checked against before it's
+  /// imported, such as to keep a sandboxed script within the files it can
+  /// read. Files which it rejects fail with
+  /// [`RunErrorReason::ForbiddenImport`].
+  ///
+  /// Without a guard, any file can be imported.
+  #[inline]
+  pub fn with_import_guard(
+    mut self,
+    import_guard: Option<ImportGuard>,
+  ) -> Self {
+    self.import_guard = import_guard;
+    self
+  }
+
+  #[inline]
+  pub fn set_import_guard(
+    &mut self,
+    import_guard: Option<ImportGuard>,
+  ) -> &mut Self {
+    self.import_guard = import_guard;
+    self
+  }
+
+  /// Returns whether the file at `path` can be imported.
+  #[inline]
+  pub fn can_import(&self, path: &Path) -> bool {
+    self.import_guard.as_ref().is_none_or(|guard| guard(path))
+  }
+
+  /// Sets the hook that is called when a `breakpoint` is reached.
+  ///
+  /// Breakpoints do nothing without a hook.
+  #[inline]
+  pub fn with_breakpoint_hook(
+    mut self,
+    breakpoint_hook: Option<BreakpointHook>,
+  ) -> Self {
+    self.breakpoint_hook = breakpoint_hook;
+    self
+  }
+
+  /// Watches a name, calling the watch hook whenever `def`, `let` or `set`
+  /// changes it.
+  #[inline]
+  pub fn with_watchpoint(mut self, name: Symbol) -> Self {
+    self.add_watchpoint(name);
+    self
+  }
+
+  #[inline]
+  pub fn add_watchpoint(&mut self, name: Symbol) -> &mut Self {
+    self.watchpoints.insert(name);
+    self
+  }
+
+  #[inline]
+  pub fn remove_watchpoint(&mut self, name: Symbol) -> &mut Self {
+    self.watchpoints.remove(&name);
+    self
+  }
+
+  /// Sets the hook that is called when a watched name changes.
+  #[inline]
+  pub fn with_watch_hook(mut self, watch_hook: Option<WatchHook>) -> Self {
+    self.watch_hook = watch_hook;
+    self
+  }
+
+  /// Checks that calls to functions with a declared stack effect pop and
+  /// push as many values as it says, erroring with
+  /// [`RunErrorReason::StackEffectMismatch`] otherwise.
+  ///
+  /// See [`effect`](crate::effect) for how effects are declared.
+  #[inline]
+  pub fn with_effect_checks(mut self, check_effects: bool) -> Self {
+    self.check_effects = check_effects;
+    self
+  }
+
+  /// Sets the [`Profiler`] which records the time spent in each call.
+  #[inline]
+  pub fn with_profiler(mut self, profiler: Option<Profiler>) -> Self {
+    self.profiler = profiler;
+    self
+  }
+
+  #[inline]
+  pub fn profiler(&self) -> Option<&Profiler> {
+    self.profiler.as_ref()
+  }
+
+  /// Sets the [`Tracer`] which is called with each evaluated expression.
+  #[inline]
+  pub fn with_tracer(mut self, tracer: Option<Tracer>) -> Self {
+    self.tracer = tracer;
+    self
+  }
+
+  #[inline]
+  pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> &mut Self {
+    self.tracer = tracer;
+    self
+  }
+
+  #[inline]
+  pub fn tracer(&self) -> Option<Tracer> {
+    self.tracer.clone()
+  }
+
+  /// Runs `f` as a call to `name`, recording it if there's a profiler.
+  #[inline]
+  fn profiled<T>(&self, name: Symbol, f: impl FnOnce() -> T) -> T {
+    match self.profiler {
+      Some(ref profiler) => profiler.call(name, f),
+      None => f(),
+    }
+  }
+
   #[inline]
   pub fn module(&self, symbol: &Symbol) -> Option<&Module> {
     self.modules.get(symbol)
   }
 
   #[inline]
-  pub fn debug_hook(&self) -> Option<Arc<dyn Fn(String)>> {
+  pub fn modules(&self) -> impl Iterator<Item = &Module> {
+    self.modules.values()
+  }
+
+  #[inline]
+  pub fn debug_hook(&self) -> Option<DebugHook> {
     self.debug_hook.clone()
   }
 
+  #[inline]
+  pub fn breakpoint_hook(&self) -> Option<BreakpointHook> {
+    self.breakpoint_hook.clone()
+  }
+
+  #[inline]
+  pub fn watch_hook(&self) -> Option<WatchHook> {
+    self.watch_hook.clone()
+  }
+
+  /// Returns whether changes to `name` are reported to the watch hook.
+  #[inline]
+  pub fn is_watched(&self, name: &Symbol) -> bool {
+    self.watch_hook.is_some() && self.watchpoints.contains(name)
+  }
+
+  /// Calls the watch hook with `event`, if its name is watched.
+  pub fn watch(&self, context: &Context, event: WatchEvent) {
+    if let Some(hook) = self.watch_hook.as_ref() {
+      if self.watchpoints.contains(&event.name) {
+        hook(&event, context);
+      }
+    }
+  }
+
   pub fn run(
     &self,
     mut context: Context,
@@ -85,6 +444,249 @@ impl Engine {
     Ok(context)
   }
 
+  /// Runs `exprs` like [`Engine::run`], returning the [`RunStats`] of the
+  /// run alongside its result.
+  pub fn run_with_stats(
+    &self,
+    context: Context,
+    exprs: Vec<Expr>,
+  ) -> (Result<Context, RunError>, RunStats) {
+    let stats = Shared::new(RunStats::new());
+    let engine = Self {
+      stats: Some(stats.clone()),
+      ..self.clone()
+    };
+
+    let ops = context.ops();
+    let result = engine.run(context, exprs);
+    let stats = Self::finish_stats(&stats, ops, &result);
+
+    (result, stats)
+  }
+
+  /// Runs `exprs` like [`Engine::run_async`], returning the [`RunStats`] of
+  /// the run alongside its result.
+  #[cfg(feature = "async")]
+  pub async fn run_async_with_stats(
+    &self,
+    context: Context,
+    exprs: Vec<Expr>,
+  ) -> (Result<Context, RunError>, RunStats) {
+    let stats = Shared::new(RunStats::new());
+    let engine = Self {
+      stats: Some(stats.clone()),
+      ..self.clone()
+    };
+
+    let ops = context.ops();
+    let result = engine.run_async(context, exprs).await;
+    let stats = Self::finish_stats(&stats, ops, &result);
+
+    (result, stats)
+  }
+
+  /// Takes the [`RunStats`] recorded during a run which started after `ops`
+  /// expressions had been evaluated.
+  fn finish_stats(
+    stats: &Shared<RunStats>,
+    ops: usize,
+    result: &Result<Context, RunError>,
+  ) -> RunStats {
+    let context = match result {
+      Ok(ref context) => context,
+      Err(ref e) => &e.context,
+    };
+
+    let mut stats = core::mem::take(&mut *stats.borrow_mut());
+    stats.exprs_evaluated = context.ops() - ops;
+    stats.max_stack_depth = context.peak_stack_len();
+    stats.record_scope(context);
+
+    stats
+  }
+
+  /// Runs each of the independent programs in `jobs` on a pool of threads,
+  /// returning their results in the same order.
+  ///
+  /// Each thread takes the next job which hasn't been started yet, so that
+  /// a few long programs don't hold up the rest. The threads have a stack of
+  /// [`THREAD_STACK_SIZE`], so a program can recurse as deeply as it could
+  /// in a generator.
+  #[cfg(feature = "sync")]
+  pub fn run_many(
+    &self,
+    jobs: Vec<(Context, Vec<Expr>)>,
+  ) -> Vec<Result<Context, RunError>> {
+    use std::sync::{
+      atomic::{AtomicUsize, Ordering},
+      Mutex,
+    };
+
+    let len = jobs.len();
+    let threads = std::thread::available_parallelism()
+      .map_or(1, |x| x.get())
+      .min(len);
+
+    let jobs = jobs
+      .into_iter()
+      .map(Some)
+      .map(Mutex::new)
+      .collect::<Vec<_>>();
+    let results = (0..len).map(|_| Mutex::new(None)).collect::<Vec<_>>();
+    let next = AtomicUsize::new(0);
+
+    let work = || loop {
+      let i = next.fetch_add(1, Ordering::Relaxed);
+      let Some(job) = jobs.get(i) else {
+        break;
+      };
+
+      let (context, exprs) =
+        job.lock().unwrap().take().expect("job taken twice");
+      *results[i].lock().unwrap() = Some(self.run(context, exprs));
+    };
+
+    std::thread::scope(|s| {
+      let spawned = (0..threads)
+        .filter(|_| {
+          std::thread::Builder::new()
+            .stack_size(THREAD_STACK_SIZE)
+            .spawn_scoped(s, work)
+            .is_ok()
+        })
+        .count();
+
+      // Without any threads, the jobs are run on the calling thread instead.
+      if spawned == 0 {
+        work();
+      }
+    });
+
+    results
+      .into_iter()
+      .map(|x| x.into_inner().unwrap().expect("job wasn't run"))
+      .collect()
+  }
+
+  /// Runs `exprs` like [`Engine::run`], except that functions added with
+  /// [`Module::add_async_func`] are awaited when they're called at the top
+  /// level, instead of blocking the thread. The calls are traced, profiled
+  /// and counted in [`RunStats`] like any other.
+  ///
+  /// Those called anywhere else, such as within a function body, block the
+  /// thread where that can't deadlock the runtime, and fail with
+  /// [`RunErrorReason::NestedAsyncCall`] otherwise. See [`future`] for more.
+  ///
+  /// [`future`]: crate::future
+  #[cfg(feature = "async")]
+  pub async fn run_async(
+    &self,
+    mut context: Context,
+    exprs: Vec<Expr>,
+  ) -> Result<Context, RunError> {
+    for expr in exprs {
+      let func = match expr.kind {
+        ExprKind::Symbol(x) => self.async_func(&context, x),
+        _ => None,
+      };
+
+      context = match func {
+        Some(func) => self.eval_async(context, expr, func).await?,
+        None => crate::future::polling(|| self.run_expr(context, expr))?,
+      };
+    }
+
+    Ok(context)
+  }
+
+  /// Awaits a call to an async function, with the same checks and hooks as
+  /// [`Engine::run_expr`] has for a call to a native one.
+  #[cfg(feature = "async")]
+  async fn eval_async(
+    &self,
+    mut context: Context,
+    expr: Expr,
+    func: crate::future::AsyncFunc,
+  ) -> Result<Context, RunError> {
+    if let Some(reason) = self.exceeded_limit(&context) {
+      return Err(RunError {
+        context: Box::new(context),
+        expr: Box::new(expr),
+        reason,
+      });
+    }
+
+    let before = self.tracer.as_ref().map(|_| context.stack().to_vec());
+
+    context.tick();
+    if let Some(ref stats) = self.stats {
+      stats.borrow_mut().record_scope(&context);
+    }
+    if let Some(journal) = context.journal_mut() {
+      journal.commit();
+      journal.push_op(JournalOp::FnCall(expr.clone()));
+    }
+
+    let future = func(context, expr.clone());
+    let result = match (self.profiler.as_ref(), &expr.kind) {
+      (Some(profiler), ExprKind::Symbol(x)) => {
+        profiler.call_async(*x, future).await
+      }
+      _ => future.await,
+    };
+    let result = result.map(|mut context| {
+      if let Some(journal) = context.journal_mut() {
+        journal.commit();
+      }
+
+      context
+    });
+
+    if let (Some(tracer), Some(before)) = (self.tracer.as_ref(), before) {
+      let (after, error) = match result {
+        Ok(ref context) => (context.stack(), None),
+        Err(ref e) => (e.context.stack(), Some(e.reason)),
+      };
+
+      tracer(&TraceEvent {
+        depth: trace::depth(),
+        expr: &expr,
+        before: &before,
+        after,
+        error,
+      });
+    }
+
+    result
+  }
+
+  /// Returns the async function which `symbol` calls, if it calls one.
+  #[cfg(feature = "async")]
+  fn async_func(
+    &self,
+    context: &Context,
+    symbol: Symbol,
+  ) -> Option<crate::future::AsyncFunc> {
+    if Intrinsic::from_symbol(symbol)
+      .is_some_and(|x| !self.removed_intrinsics.contains(&x))
+    {
+      return None;
+    }
+
+    let (module, func) = symbol.split_namespace()?;
+
+    if let Some(module) = self.modules.get(&module) {
+      return module.async_func(func);
+    }
+
+    if context.imports().is_module_imported(module) && self.load_module(module)
+    {
+      return self.loaded.borrow().get(&module)?.async_func(func);
+    }
+
+    None
+  }
+
   pub fn run_with_timeout(
     &mut self,
     mut context: Context,
@@ -108,26 +710,87 @@ impl Engine {
   ) -> Result<Context, RunError> {
     let expr = context.scan_expr(expr)?;
     match expr.kind {
-      ExprKind::List(exprs) => self.run(context, exprs),
+      ExprKind::List(exprs) => self.run(context, exprs.into_inner()),
       _ => self.run_expr(context, expr),
     }
   }
 
+  /// Returns the limit which `context` has exceeded, if any.
+  fn exceeded_limit(&self, context: &Context) -> Option<RunErrorReason> {
+    if let (Some(start_time), Some(timeout)) = (self.start_time, self.timeout) {
+      if start_time.elapsed() > timeout {
+        return Some(RunErrorReason::Timeout);
+      }
+    }
+
+    if self.fuel.is_some_and(|fuel| context.ops() >= fuel) {
+      return Some(RunErrorReason::OutOfFuel);
+    }
+
+    if self
+      .stack_limit
+      .is_some_and(|limit| context.stack().len() > limit)
+    {
+      return Some(RunErrorReason::StackOverflow);
+    }
+
+    None
+  }
+
   pub fn run_expr(
+    &self,
+    context: Context,
+    expr: Expr,
+  ) -> Result<Context, RunError> {
+    let Some(tracer) = self.tracer.as_ref() else {
+      return self.eval_expr(context, expr);
+    };
+
+    let before = context.stack().to_vec();
+    let (depth, result) =
+      trace::nested(|| self.eval_expr(context, expr.clone()));
+    let (after, error) = match result {
+      Ok(ref context) => (context.stack(), None),
+      Err(ref e) => (e.context.stack(), Some(e.reason)),
+    };
+
+    tracer(&TraceEvent {
+      depth,
+      expr: &expr,
+      before: &before,
+      after,
+      error,
+    });
+
+    result
+  }
+
+  fn eval_expr(
     &self,
     mut context: Context,
     expr: Expr,
   ) -> Result<Context, RunError> {
-    if let (Some(start_time), Some(timeout)) = (self.start_time, self.timeout) {
-      if start_time.elapsed() > timeout {
-        return Err(RunError {
-          context,
-          expr,
-          reason: RunErrorReason::Timeout,
-        });
-      }
+    if let Some(reason) = self.exceeded_limit(&context) {
+      return Err(RunError {
+        context: Box::new(context),
+        expr: Box::new(expr),
+        reason,
+      });
     }
 
+    #[cfg(feature = "sync")]
+    if let Err(reason) = crate::scheduler::preempt(&mut context) {
+      return Err(RunError {
+        context: Box::new(context),
+        expr: Box::new(expr),
+        reason,
+      });
+    }
+
+    context.tick();
+    if let Some(ref stats) = self.stats {
+      stats.borrow_mut().record_scope(&context);
+    }
     let expr = context.scan_expr(expr)?;
 
     if let ExprKind::SExpr { call, body } = &expr.kind {
@@ -157,7 +820,7 @@ impl Engine {
         }
       }
 
-      if let Ok(intrinsic) = Intrinsic::from_str(call.as_str()) {
+      if let Some(intrinsic) = Intrinsic::from_symbol(*call) {
         if intrinsic.has_flipped_s_expr_args() {
           // TODO: use a for loop and iterate normally, instead of reversing
           args.reverse();
@@ -183,8 +846,15 @@ impl Engine {
       | ExprKind::Integer(_)
       | ExprKind::Float(_)
       | ExprKind::String(_)
+      | ExprKind::Char(_)
+      | ExprKind::Bytes(_)
       | ExprKind::List(_)
-      | ExprKind::Record(_) => {
+      | ExprKind::Record(_)
+      | ExprKind::Map(_)
+      | ExprKind::Set(_)
+      | ExprKind::Seq(_)
+      | ExprKind::Error(_)
+      | ExprKind::Foreign(_) => {
         context.stack_push(expr)?;
         Ok(context)
       }
@@ -194,53 +864,79 @@ impl Engine {
           journal.commit();
         }
 
-        if let Ok(intrinsic) = Intrinsic::from_str(x.as_str()) {
+        if let Some(intrinsic) = Intrinsic::from_symbol(x)
+          .filter(|x| !self.removed_intrinsics.contains(x))
+        {
+          if !context.intrinsics().allows(intrinsic) {
+            return Err(RunError {
+              context: Box::new(context),
+              expr: Box::new(expr),
+              reason: RunErrorReason::ForbiddenIntrinsic,
+            });
+          }
+
           if let Some(journal) = context.journal_mut() {
             journal.commit();
             journal.push_op(JournalOp::FnCall(expr.clone()));
           }
-          let mut context = intrinsic.run(self, context, expr)?;
+          if let Some(ref stats) = self.stats {
+            stats.borrow_mut().record_intrinsic(intrinsic);
+          }
+          let mut context = self.profiled(x, || {
+            match self.intrinsic_overrides.get(&intrinsic) {
+              Some(func) => func(self, context, expr),
+              None => intrinsic.run(self, context, expr),
+            }
+          })?;
           if let Some(journal) = context.journal_mut() {
             journal.commit();
           }
 
           Ok(context)
-        } else if let Some((namespace, func)) = x.as_str().split_once(':') {
-          if let Some(func) = self
-            .modules
-            .get(&Symbol::from_ref(namespace))
-            .and_then(|module| module.func(Symbol::from_ref(func)))
-          {
-            if let Some(journal) = context.journal_mut() {
-              journal.push_op(JournalOp::FnCall(expr.clone()));
-            }
-            context = func(self, context, expr)?;
-            if let Some(journal) = context.journal_mut() {
-              journal.commit();
-            }
-            Ok(context)
-          } else {
-            Err(RunError {
-              context: context.clone(),
-              expr,
-              reason: RunErrorReason::UnknownCall,
-            })
+        } else if let Some(func) =
+          x.split_namespace().and_then(|(namespace, func)| {
+            self.module_func(&context, namespace, func)
+          })
+        {
+          if let Some(journal) = context.journal_mut() {
+            journal.push_op(JournalOp::FnCall(expr.clone()));
+          }
+          context = self.profiled(x, || func(self, context, expr))?;
+          if let Some(journal) = context.journal_mut() {
+            journal.commit();
           }
+          Ok(context)
+        // Namespaced symbols which aren't native module functions might have
+        // been defined by an imported stack module.
         } else if let Some(item) = context.scope_item(x) {
           if let ExprKind::Function { scope, body } = item.kind {
-            let mut _call_result = CallResult::None;
-            let mut is_recur = false;
-            loop {
-              _call_result =
-                self.call_fn(&expr, &scope, &body, context, is_recur);
-              is_recur = true;
-
-              match _call_result {
-                CallResult::Recur(c) => context = c,
-                CallResult::Once(result) => return result,
-                CallResult::None => unreachable!(),
-              }
-            }
+            let effect = self
+              .check_effects
+              .then(|| context.scope().meta(x)?.effect.clone())
+              .flatten();
+
+            let call = |mut context| {
+              self.profiled(x, || {
+                let mut _call_result = CallResult::None;
+                let mut is_recur = false;
+                loop {
+                  _call_result =
+                    self.call_fn(&expr, &scope, &body, context, is_recur);
+                  is_recur = true;
+
+                  match _call_result {
+                    CallResult::Recur(c) => context = c,
+                    CallResult::Once(result) => return result,
+                    CallResult::None => unreachable!(),
+                  }
+                }
+              })
+            };
+
+            return match effect {
+              Some(effect) => self.checked_call(context, &expr, &effect, call),
+              None => call(context),
+            };
           }
           if let ExprKind::SExpr { .. } = item.kind {
             self.call_expr(context, item)
@@ -257,14 +953,24 @@ impl Engine {
           }
         } else {
           Err(RunError {
-            context: context.clone(),
-            expr,
+            context: Box::new(context.clone()),
+            expr: Box::new(expr),
             reason: RunErrorReason::UnknownCall,
           })
         }
       }
       ExprKind::Lazy(x) => {
-        context.stack_push(*x.clone())?;
+        let mut x = *x;
+
+        // Doc comments are written before the `'`, but they document what's
+        // quoted, such as the function in `'(fn ...) 'name def`.
+        if let (Some(outer), Some(inner)) = (&expr.info, &mut x.info) {
+          if inner.comments.leading.is_empty() {
+            inner.comments.leading.clone_from(&outer.comments.leading);
+          }
+        }
+
+        context.stack_push(x)?;
         Ok(context)
       }
       ExprKind::Function {
@@ -289,6 +995,35 @@ impl Engine {
     }
   }
 
+  /// Runs `call` with `context`, erroring unless the stack has enough values
+  /// for `effect` beforehand and its length changed by as much as `effect`
+  /// says afterwards.
+  fn checked_call(
+    &self,
+    context: Context,
+    expr: &Expr,
+    effect: &StackEffect,
+    call: impl FnOnce(Context) -> Result<Context, RunError>,
+  ) -> Result<Context, RunError> {
+    let mismatch = |context| RunError {
+      context: Box::new(context),
+      expr: Box::new(expr.clone()),
+      reason: RunErrorReason::StackEffectMismatch,
+    };
+
+    let len = context.stack().len();
+    let Some(base) = len.checked_sub(effect.pops()) else {
+      return Err(mismatch(context));
+    };
+
+    let context = call(context)?;
+
+    match context.stack().len() == base + effect.pushes() {
+      true => Ok(context),
+      false => Err(mismatch(context)),
+    }
+  }
+
   /// Handles auto-calling symbols (calls) when they're pushed to the stack
   /// This is also triggered by the `call` keyword
   pub fn call_fn(
@@ -347,23 +1082,188 @@ impl Engine {
 
         CallResult::Once(Ok(context))
       }
+      // Loops can't be broken out of from within a function they call.
+      Err(RunError {
+        reason: RunErrorReason::Break | RunErrorReason::Continue,
+        context,
+        expr,
+      }) => CallResult::Once(Err(RunError {
+        reason: RunErrorReason::InvalidBreak,
+        context,
+        expr,
+      })),
       Err(err) => CallResult::Once(Err(err)),
     }
   }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Configures an [`Engine`] before it's built.
+///
+/// Unlike the `with_*` methods of [`Engine`], this can also remove or
+/// override individual intrinsics. A removed intrinsic is no longer special,
+/// so its name can be defined like any other, and an overridden one calls
+/// the given function instead.
+#[derive(Clone, Default)]
+pub struct EngineBuilder {
+  engine: Engine,
+}
+
+impl EngineBuilder {
+  #[inline]
+  pub fn new() -> Self {
+    Self {
+      engine: Engine::new(),
+    }
+  }
+
+  #[inline]
+  pub fn module(mut self, module: Module) -> Self {
+    self.engine.add_module(module);
+    self
+  }
+
+  #[inline]
+  pub fn module_loader(mut self, name: Symbol, loader: ModuleLoader) -> Self {
+    self.engine.add_module_loader(name, loader);
+    self
+  }
+
+  /// Removes an intrinsic, so that calling it is an unknown call unless the
+  /// script defines it.
+  #[inline]
+  pub fn remove_intrinsic(mut self, intrinsic: Intrinsic) -> Self {
+    self.engine.removed_intrinsics.insert(intrinsic);
+    self.engine.intrinsic_overrides.remove(&intrinsic);
+    self
+  }
+
+  /// Calls `func` instead of an intrinsic.
+  #[inline]
+  pub fn override_intrinsic(
+    mut self,
+    intrinsic: Intrinsic,
+    func: Func,
+  ) -> Self {
+    self.engine.override_intrinsic(intrinsic, func);
+    self
+  }
+
+  #[inline]
+  pub fn fuel(mut self, fuel: usize) -> Self {
+    self.engine.fuel = Some(fuel);
+    self
+  }
+
+  #[inline]
+  pub fn stack_limit(mut self, stack_limit: usize) -> Self {
+    self.engine.stack_limit = Some(stack_limit);
+    self
+  }
+
+  #[inline]
+  pub fn search_path(mut self, path: PathBuf) -> Self {
+    self.engine.add_search_path(path);
+    self
+  }
+
+  #[inline]
+  pub fn package(mut self, name: String, dir: PathBuf) -> Self {
+    self.engine.add_package(name, dir);
+    self
+  }
+
+  #[inline]
+  pub fn debug_hook(mut self, debug_hook: DebugHook) -> Self {
+    self.engine.debug_hook = Some(debug_hook);
+    self
+  }
+
+  #[inline]
+  pub fn breakpoint_hook(mut self, breakpoint_hook: BreakpointHook) -> Self {
+    self.engine.breakpoint_hook = Some(breakpoint_hook);
+    self
+  }
+
+  #[inline]
+  pub fn watchpoint(mut self, name: Symbol) -> Self {
+    self.engine.add_watchpoint(name);
+    self
+  }
+
+  #[inline]
+  pub fn watch_hook(mut self, watch_hook: WatchHook) -> Self {
+    self.engine.watch_hook = Some(watch_hook);
+    self
+  }
+
+  #[inline]
+  pub fn import_guard(mut self, import_guard: ImportGuard) -> Self {
+    self.engine.import_guard = Some(import_guard);
+    self
+  }
+
+  #[inline]
+  pub fn tracer(mut self, tracer: Tracer) -> Self {
+    self.engine.tracer = Some(tracer);
+    self
+  }
+
+  #[inline]
+  pub fn profiler(mut self, profiler: Profiler) -> Self {
+    self.engine.profiler = Some(profiler);
+    self
+  }
+
+  #[inline]
+  pub fn effect_checks(mut self) -> Self {
+    self.engine.check_effects = true;
+    self
+  }
+
+  #[inline]
+  pub fn build(self) -> Engine {
+    self.engine
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RunError {
   pub reason: RunErrorReason,
-  pub context: Context,
-  pub expr: Expr,
+  // Both are boxed so that results which fail with a [`RunError`] stay
+  // small, since they're returned from every step of a run.
+  pub context: Box<Context>,
+  pub expr: Box<Expr>,
+}
+
+impl RunError {
+  /// Returns the code given to `exit`, if that's what ended the run.
+  pub fn exit_code(&self) -> Option<i32> {
+    match (self.reason, &self.expr.kind) {
+      (RunErrorReason::Exit, ExprKind::Integer(x)) => i32::try_from(*x).ok(),
+      _ => None,
+    }
+  }
 }
 
 impl std::error::Error for RunError {}
 
 impl fmt::Display for RunError {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    write!(f, "{} caused by ", self.reason)?;
+    write!(f, "{} ", self.reason)?;
+
+    if let Some(code) = self.exit_code() {
+      write!(f, "{code} ")?;
+    }
+
+    // The thrown value usually says more than the reason does.
+    if let (RunErrorReason::Thrown, ExprKind::Error(ref x)) =
+      (self.reason, &self.expr.kind)
+    {
+      write!(f, "{x} ")?;
+    }
+
+    write!(f, "caused by ")?;
 
     if let Some(ref info) = self.expr.info {
       write!(f, "{}", info)
@@ -373,14 +1273,39 @@ impl fmt::Display for RunError {
   }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
 pub enum RunErrorReason {
   StackUnderflow,
   DoubleError,
   AssertionFailed,
   Halt,
+  Exit,
   InvalidLet,
   Timeout,
+  OutOfFuel,
+  StackOverflow,
+  ForbiddenIntrinsic,
+  ForbiddenImport,
+  InvalidImport,
+  ImportNotFound,
+  CircularImport,
+  InvalidModule,
+  InvalidMatch,
+  PatternMismatch,
+  InvalidArgument,
+  DivideByZero,
+  Thrown,
+  Break,
+  Continue,
+  InvalidBreak,
+  InvalidYield,
+  NeedsSync,
+  NestedAsyncCall,
+  StackEffectMismatch,
+  Cancelled,
+  ThreadSpawnFailed,
 
   // Scope Errors
   UnknownCall,
@@ -398,8 +1323,37 @@ impl fmt::Display for RunErrorReason {
       Self::DoubleError => write!(f, "double error"),
       Self::AssertionFailed => write!(f, "assertion failed"),
       Self::Halt => write!(f, "halt"),
+      Self::Exit => write!(f, "exit"),
       Self::InvalidLet => write!(f, "invalid let"),
       Self::Timeout => write!(f, "exceeded timeout"),
+      Self::OutOfFuel => write!(f, "ran out of fuel"),
+      Self::StackOverflow => write!(f, "exceeded stack limit"),
+      Self::ForbiddenIntrinsic => write!(f, "forbidden intrinsic"),
+      Self::ForbiddenImport => write!(f, "forbidden import"),
+      Self::InvalidImport => write!(f, "invalid import"),
+      Self::ImportNotFound => write!(f, "imported file not found"),
+      Self::CircularImport => write!(f, "circular import"),
+      Self::InvalidModule => write!(f, "invalid module"),
+      Self::InvalidMatch => write!(f, "invalid match"),
+      Self::Cancelled => write!(f, "task was cancelled"),
+      Self::ThreadSpawnFailed => write!(f, "couldn't spawn a thread"),
+      Self::PatternMismatch => write!(f, "value doesn't match the pattern"),
+      Self::InvalidArgument => write!(f, "invalid argument"),
+      Self::DivideByZero => write!(f, "divide by zero"),
+      Self::Thrown => write!(f, "uncaught error"),
+      Self::Break => write!(f, "break outside of a loop"),
+      Self::Continue => write!(f, "continue outside of a loop"),
+      Self::InvalidBreak => {
+        write!(f, "break or continue out of a function")
+      }
+      Self::InvalidYield => write!(f, "yield outside of a generator"),
+      Self::NeedsSync => write!(f, "needs the sync feature"),
+      Self::NestedAsyncCall => {
+        write!(f, "async function called where it can't be awaited")
+      }
+      Self::StackEffectMismatch => {
+        write!(f, "stack effect doesn't match its declaration")
+      }
       Self::UnknownCall => write!(f, "unknown call"),
       Self::InvalidDefinition => write!(f, "invalid definition"),
       Self::InvalidFunction => write!(f, "invalid function"),
@@ -412,6 +1366,8 @@ impl fmt::Display for RunErrorReason {
 
 #[cfg(test)]
 mod tests {
+  use std::{collections::HashSet, sync::Arc};
+
   use crate::prelude::*;
 
   // TODO: Move test for scopes/vars into src/scope.rs?
@@ -617,6 +1573,7 @@ mod tests {
   }
 
   #[test]
+  #[cfg(feature = "serde")]
   fn test_ser_and_de() {
     let source = Source::new("", "0 'a def 2 2 + '(fn)");
     let mut lexer = Lexer::new(source);
@@ -631,4 +1588,277 @@ mod tests {
 
     assert_eq!(context, ser_context);
   }
+
+  #[test]
+  fn runs_out_of_fuel() {
+    let source = Source::new("", "1 2 + 3 +");
+    let mut lexer = Lexer::new(source);
+    let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+    let engine = Engine::new().with_fuel(Some(4));
+    let err = engine.run(Context::new(), exprs).unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::OutOfFuel);
+    assert_eq!(err.context.ops(), 4);
+    assert_eq!(
+      err.context.stack(),
+      &[ExprKind::Integer(3).into(), ExprKind::Integer(3).into()]
+    );
+  }
+
+  #[test]
+  fn exceeds_stack_limit() {
+    let source = Source::new("", "1 2 drop 3 4 5 drop");
+    let mut lexer = Lexer::new(source);
+    let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+    let engine = Engine::new().with_stack_limit(Some(3));
+    let err = engine.run(Context::new(), exprs).unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::StackOverflow);
+    assert_eq!(err.context.peak_stack_len(), 4);
+  }
+
+  #[test]
+  fn errors_on_let_pattern_mismatches() {
+    let source = Source::new("", "[1 2 3] '[a] '[[a b]] let");
+    let mut lexer = Lexer::new(source);
+    let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+    let engine = Engine::new();
+    let err = engine.run(Context::new(), exprs).unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::PatternMismatch);
+  }
+
+  #[test]
+  fn checks_declared_stack_effects() {
+    let run = |code: &str| {
+      let source = Source::new("", code);
+      let mut lexer = Lexer::new(source);
+      let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+      Engine::new()
+        .with_effect_checks(true)
+        .run(Context::new(), exprs)
+        .map(|context| context.stack().to_vec())
+        .map_err(|err| err.reason)
+    };
+
+    let add = "## ( a b -- c )\n'(fn +) 'add def ";
+    let bad = "## ( a b -- c )\n'(fn drop drop) 'add def ";
+
+    assert_eq!(
+      run(&format!("{add} 1 2 add")),
+      Ok(vec![ExprKind::Integer(3).into()])
+    );
+    assert_eq!(
+      run(&format!("{add} 1 add")),
+      Err(RunErrorReason::StackEffectMismatch)
+    );
+    assert_eq!(
+      run(&format!("{bad} 1 2 add")),
+      Err(RunErrorReason::StackEffectMismatch)
+    );
+  }
+
+  #[test]
+  fn exits_with_a_code() {
+    let run = |code: &str| {
+      let source = Source::new("", code);
+      let mut lexer = Lexer::new(source);
+      let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+      Engine::new().run(Context::new(), exprs)
+    };
+
+    let err = run("1 3 exit 2").unwrap_err();
+    assert_eq!(err.reason, RunErrorReason::Exit);
+    assert_eq!(err.exit_code(), Some(3));
+    assert_eq!(err.context.stack(), &[ExprKind::Integer(1).into()]);
+
+    // Exiting can't be caught.
+    let err = run("'(fn 0 exit) try").unwrap_err();
+    assert_eq!(err.exit_code(), Some(0));
+
+    let err = run("255 exit").unwrap_err();
+    assert_eq!(err.exit_code(), Some(255));
+
+    for code in ["\"0\" exit", "-1 exit", "256 exit"] {
+      let err = run(code).unwrap_err();
+      assert_eq!(err.reason, RunErrorReason::InvalidArgument, "{code}");
+      assert_eq!(err.exit_code(), None, "{code}");
+    }
+  }
+
+  #[test]
+  fn forbids_intrinsics_at_run_time() {
+    let source = Source::new("", "0 'a def 1 'a set");
+    let mut lexer = Lexer::new(source);
+    let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+    let engine = Engine::new();
+    let context = Context::new().with_intrinsics(IntrinsicFilter::Deny(
+      HashSet::from_iter([Intrinsic::Set]),
+    ));
+    let err = engine.run(context, exprs).unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::ForbiddenIntrinsic);
+    assert_eq!(err.expr.to_string(), "set");
+    assert_eq!(
+      err.context.scope_item(Symbol::from_ref("a")),
+      Some(ExprKind::Integer(0).into())
+    );
+  }
+
+  #[test]
+  fn forbids_intrinsics_before_running() {
+    let source = Source::new("", "1 2 + 'halt '(fn [(print 1)])");
+    let mut lexer = Lexer::new(source);
+    let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+    let context = Context::new().with_intrinsics(IntrinsicFilter::Allow(
+      HashSet::from_iter([Intrinsic::Add]),
+    ));
+    let err = context.check_intrinsics(&exprs).unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::ForbiddenIntrinsic);
+    assert_eq!(err.expr.to_string(), "(print 1)");
+
+    let context = Context::new().with_intrinsics(IntrinsicFilter::Deny(
+      HashSet::from_iter([Intrinsic::Halt]),
+    ));
+    assert!(context.check_intrinsics(&exprs).is_ok());
+  }
+
+  #[test]
+  fn can_remove_intrinsics() {
+    let engine = Engine::builder().remove_intrinsic(Intrinsic::Print).build();
+
+    let source = Source::new("", "1 print");
+    let mut lexer = Lexer::new(source);
+    let exprs = crate::parser::parse(&mut lexer).unwrap();
+    let err = engine.run(Context::new(), exprs).unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::UnknownCall);
+
+    let source = Source::new("", "'(fn 2 *) 'print def 1 print");
+    let mut lexer = Lexer::new(source);
+    let exprs = crate::parser::parse(&mut lexer).unwrap();
+    let context = engine.run(Context::new(), exprs).unwrap();
+
+    assert_eq!(context.stack(), &[ExprKind::Integer(2).into()]);
+  }
+
+  #[test]
+  fn can_override_intrinsics() {
+    let engine = Engine::builder()
+      .override_intrinsic(
+        Intrinsic::Add,
+        Arc::new(|_, mut context, expr| {
+          let rhs = context.stack_pop(&expr)?;
+          let lhs = context.stack_pop(&expr)?;
+
+          let kind = match (lhs.kind, rhs.kind) {
+            (ExprKind::String(lhs), ExprKind::String(rhs)) => {
+              ExprKind::String(lhs + &rhs)
+            }
+            (lhs, rhs) => (lhs + rhs).unwrap_or(ExprKind::Nil),
+          };
+          context.stack_push(kind.into())?;
+
+          Ok(context)
+        }),
+      )
+      .build();
+
+    let source = Source::new("", "\"a\" \"b\" + 1 2 +");
+    let mut lexer = Lexer::new(source);
+    let exprs = crate::parser::parse(&mut lexer).unwrap();
+    let context = engine.run(Context::new(), exprs).unwrap();
+
+    assert_eq!(
+      context.stack(),
+      &[
+        ExprKind::String("ab".into()).into(),
+        ExprKind::Integer(3).into()
+      ]
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "sync")]
+  fn runs_many_programs_in_order() {
+    let jobs = (0..16)
+      .map(|i| {
+        let source = Source::new("", format!("{i} 2 *"));
+        let mut lexer = Lexer::new(source);
+        let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+        (Context::new(), exprs)
+      })
+      .chain(core::iter::once((
+        Context::new(),
+        vec![ExprKind::Symbol(Symbol::from_ref("+")).into()],
+      )))
+      .collect();
+
+    let results = Engine::new().run_many(jobs);
+
+    assert_eq!(results.len(), 17);
+    for (i, result) in results[..16].iter().enumerate() {
+      assert_eq!(
+        result.as_ref().unwrap().stack(),
+        &[ExprKind::Integer(i as i64 * 2).into()]
+      );
+    }
+    assert_eq!(
+      results[16].as_ref().unwrap_err().reason,
+      RunErrorReason::StackUnderflow
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "sync")]
+  fn runs_many_deeply_recursive_programs() {
+    let jobs = (0..4)
+      .map(|_| {
+        let source =
+          Source::new("", "nil 'f def '(fn dupe 0 > '[1 - f] if) 'f set 60 f");
+        let mut lexer = Lexer::new(source);
+        let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+        (Context::new(), exprs)
+      })
+      .collect();
+
+    for result in Engine::new().run_many(jobs) {
+      assert_eq!(result.unwrap().stack(), &[ExprKind::Integer(0).into()]);
+    }
+  }
+
+  #[test]
+  fn rejects_malformed_match_cases() {
+    let run = |code: &str| {
+      let source = Source::new("", code);
+      let mut lexer = Lexer::new(source);
+      let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+      Engine::new().run(Context::new(), exprs)
+    };
+
+    for code in [
+      "1 [[(foo n) [n]]] match",
+      "1 [[(integer a b) [a]]] match",
+      "1 [[_ [0]] [[(foo)] [1]]] match",
+      "1 [[1]] match",
+      "1 1 match",
+    ] {
+      let err = run(code).unwrap_err();
+      assert_eq!(err.reason, RunErrorReason::InvalidMatch, "{code}");
+    }
+
+    let context = run("1 [[2 [0]]] match").unwrap();
+    assert_eq!(context.stack(), &[ExprKind::Nil.into()]);
+  }
 }
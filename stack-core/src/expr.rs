@@ -2,13 +2,18 @@ use core::{cmp::Ordering, fmt, hash::Hash, ops};
 use std::collections::HashMap;
 
 use compact_str::CompactString;
+use indexmap::{IndexMap, IndexSet};
 use internment::Intern;
-use serde::Deserialize;
-use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use crate::{lexer::Span, scope::Scope, source::Source, symbol::Symbol};
+use crate::{
+  foreign::Foreign, lexer::Span, persistent::Persistent, scope::Scope,
+  seq::Seq, source::Source, symbol::Symbol,
+};
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Expr {
   pub kind: ExprKind,
   pub info: Option<ExprInfo>,
@@ -34,6 +39,11 @@ impl Expr {
           expr.recursively_strip_info();
         }
       }
+      ExprKind::Map(ref mut exprs) => {
+        for (_, expr) in exprs.iter_mut() {
+          expr.recursively_strip_info();
+        }
+      }
       ExprKind::Function {
         body: ref mut exprs,
         ..
@@ -91,6 +101,81 @@ impl fmt::Display for Expr {
   }
 }
 
+/// A key of an [`ExprKind::Map`], or an item of an [`ExprKind::Set`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MapKey {
+  Integer(i64),
+  String(CompactString),
+  Symbol(Symbol),
+}
+
+impl MapKey {
+  /// Returns the key for `kind`, or [`None`] if it can't be used as one.
+  pub fn from_kind(kind: &ExprKind) -> Option<Self> {
+    match kind {
+      ExprKind::Integer(x) => Some(Self::Integer(*x)),
+      ExprKind::String(x) => Some(Self::String(x.clone())),
+      ExprKind::Symbol(x) => Some(Self::Symbol(*x)),
+      _ => None,
+    }
+  }
+}
+
+impl From<MapKey> for ExprKind {
+  fn from(value: MapKey) -> Self {
+    match value {
+      MapKey::Integer(x) => Self::Integer(x),
+      MapKey::String(x) => Self::String(x),
+      MapKey::Symbol(x) => Self::Symbol(x),
+    }
+  }
+}
+
+impl fmt::Display for MapKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Integer(x) => write!(f, "{x}"),
+      Self::String(x) => write!(f, "{x}"),
+      Self::Symbol(x) => write!(f, "{}", x.as_str()),
+    }
+  }
+}
+
+/// Returns `c` as a char literal, such as `'a'` or `'\n'`.
+pub fn char_literal(c: char) -> String {
+  match c {
+    '\n' => "'\\n'".into(),
+    '\t' => "'\\t'".into(),
+    '\r' => "'\\r'".into(),
+    '\0' => "'\\0'".into(),
+    '\\' => "'\\\\'".into(),
+    '\'' => "'\\''".into(),
+    c => format!("'{c}'"),
+  }
+}
+
+/// Returns the literal for `bytes`, such as `b"a\xFF"`.
+pub fn bytes_literal(bytes: &[u8]) -> String {
+  let mut literal = String::from("b\"");
+
+  for &byte in bytes {
+    match byte {
+      b'\n' => literal.push_str("\\n"),
+      b'\t' => literal.push_str("\\t"),
+      b'\r' => literal.push_str("\\r"),
+      b'\0' => literal.push_str("\\0"),
+      b'\\' => literal.push_str("\\\\"),
+      b'"' => literal.push_str("\\\""),
+      b' '..=b'~' => literal.push(byte as char),
+      _ => literal.push_str(&format!("\\x{byte:02X}")),
+    }
+  }
+
+  literal.push('"');
+  literal
+}
+
 pub fn display_fn_scope(scope: &FnScope) -> String {
   match scope {
     FnScope::Scoped(..) => "fn",
@@ -99,7 +184,8 @@ pub fn display_fn_scope(scope: &FnScope) -> String {
   .into()
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FnScope {
   Scoped(Scope),
   Scopeless,
@@ -117,7 +203,9 @@ impl FnScope {
   }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
 pub enum ExprKind {
   Nil,
 
@@ -125,16 +213,35 @@ pub enum ExprKind {
   Integer(i64),
   Float(f64),
   String(CompactString),
+  Char(char),
+  Bytes(Vec<u8>),
 
   Symbol(Symbol),
 
   Lazy(Box<Expr>),
-  List(Vec<Expr>),
-  Record(HashMap<Symbol, Expr>),
-
-  Function { scope: FnScope, body: Vec<Expr> },
-  SExpr { call: Symbol, body: Vec<Expr> },
+  List(Persistent<Vec<Expr>>),
+  Record(Persistent<HashMap<Symbol, Expr>>),
+  Map(IndexMap<MapKey, Expr>),
+  Set(IndexSet<MapKey>),
+  Seq(Box<Seq>),
+
+  Function {
+    scope: FnScope,
+    body: Vec<Expr>,
+  },
+  SExpr {
+    call: Symbol,
+    body: Vec<Expr>,
+  },
   Underscore,
+
+  /// An error value, which wraps the thrown value or a record describing a
+  /// runtime error.
+  Error(Box<Expr>),
+
+  /// An opaque Rust value from a native module, which can't be serialized.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  Foreign(Foreign),
 }
 
 impl ExprKind {
@@ -179,6 +286,29 @@ impl ExprKind {
     }
   }
 
+  /// The names returned by [`ExprKind::type_of`].
+  pub const TYPES: &'static [&'static str] = &[
+    "nil",
+    "boolean",
+    "integer",
+    "float",
+    "string",
+    "char",
+    "bytes",
+    "symbol",
+    "lazy",
+    "list",
+    "record",
+    "map",
+    "set",
+    "seq",
+    "function",
+    "s-expression",
+    "underscore",
+    "error",
+    "foreign",
+  ];
+
   pub fn type_of(&self) -> &str {
     match self {
       ExprKind::Nil => "nil",
@@ -187,16 +317,25 @@ impl ExprKind {
       ExprKind::Integer(_) => "integer",
       ExprKind::Float(_) => "float",
       ExprKind::String(_) => "string",
+      ExprKind::Char(_) => "char",
+      ExprKind::Bytes(_) => "bytes",
 
       ExprKind::Symbol(_) => "symbol",
 
       ExprKind::Lazy(_) => "lazy",
       ExprKind::List(_) => "list",
       ExprKind::Record(_) => "record",
+      ExprKind::Map(_) => "map",
+      ExprKind::Set(_) => "set",
+      ExprKind::Seq(_) => "seq",
 
       ExprKind::Function { .. } => "function",
       ExprKind::SExpr { .. } => "s-expression",
       ExprKind::Underscore => "underscore",
+
+      ExprKind::Error(_) => "error",
+
+      ExprKind::Foreign(_) => "foreign",
     }
   }
 }
@@ -210,12 +349,17 @@ impl PartialEq for ExprKind {
       (Self::Integer(lhs), Self::Integer(rhs)) => lhs == rhs,
       (Self::Float(lhs), Self::Float(rhs)) => lhs == rhs,
       (Self::String(lhs), Self::String(rhs)) => lhs == rhs,
+      (Self::Char(lhs), Self::Char(rhs)) => lhs == rhs,
+      (Self::Bytes(lhs), Self::Bytes(rhs)) => lhs == rhs,
 
       (Self::Symbol(lhs), Self::Symbol(rhs)) => lhs == rhs,
 
       (Self::Lazy(lhs), Self::Lazy(rhs)) => lhs == rhs,
       (Self::List(lhs), Self::List(rhs)) => lhs == rhs,
       (Self::Record(lhs), Self::Record(rhs)) => lhs == rhs,
+      (Self::Map(lhs), Self::Map(rhs)) => lhs == rhs,
+      (Self::Set(lhs), Self::Set(rhs)) => lhs == rhs,
+      (Self::Seq(lhs), Self::Seq(rhs)) => lhs == rhs,
 
       (
         Self::Function {
@@ -241,6 +385,10 @@ impl PartialEq for ExprKind {
 
       (Self::Underscore, Self::Underscore) => true,
 
+      (Self::Error(lhs), Self::Error(rhs)) => lhs == rhs,
+
+      (Self::Foreign(lhs), Self::Foreign(rhs)) => lhs == rhs,
+
       _ => false,
     }
   }
@@ -259,6 +407,8 @@ impl PartialOrd for ExprKind {
       (Self::String(lhs), Self::String(rhs)) => {
         lhs.eq(rhs).then_some(Ordering::Equal)
       }
+      (Self::Char(lhs), Self::Char(rhs)) => lhs.partial_cmp(rhs),
+      (Self::Bytes(lhs), Self::Bytes(rhs)) => lhs.partial_cmp(rhs),
 
       (Self::Symbol(lhs), Self::Symbol(rhs)) => {
         lhs.eq(rhs).then_some(Ordering::Equal)
@@ -268,6 +418,12 @@ impl PartialOrd for ExprKind {
       (Self::List(lhs), Self::List(rhs)) => {
         lhs.eq(rhs).then_some(Ordering::Equal)
       }
+      (Self::Map(lhs), Self::Map(rhs)) => {
+        lhs.eq(rhs).then_some(Ordering::Equal)
+      }
+      (Self::Set(lhs), Self::Set(rhs)) => {
+        lhs.eq(rhs).then_some(Ordering::Equal)
+      }
 
       _ => None,
     }
@@ -324,8 +480,12 @@ impl ops::Div for ExprKind {
 
   fn div(self, rhs: Self) -> Self::Output {
     match (self, rhs) {
+      (Self::Integer(lhs), Self::Integer(0)) => {
+        Err((Self::Integer(lhs), Self::Integer(0)))
+      }
+      // Only `i64::MIN / -1` overflows, which saturates.
       (Self::Integer(lhs), Self::Integer(rhs)) => {
-        Ok(Self::Integer(lhs.saturating_div(rhs)))
+        Ok(Self::Integer(lhs.checked_div(rhs).unwrap_or(i64::MAX)))
       }
       (Self::Float(lhs), Self::Float(rhs)) => Ok(Self::Float(lhs / rhs)),
 
@@ -339,7 +499,13 @@ impl ops::Rem for ExprKind {
 
   fn rem(self, rhs: Self) -> Self::Output {
     match (self, rhs) {
-      (Self::Integer(lhs), Self::Integer(rhs)) => Ok(Self::Integer(lhs % rhs)),
+      (Self::Integer(lhs), Self::Integer(0)) => {
+        Err((Self::Integer(lhs), Self::Integer(0)))
+      }
+      // Only `i64::MIN % -1` overflows, which has no remainder.
+      (Self::Integer(lhs), Self::Integer(rhs)) => {
+        Ok(Self::Integer(lhs.checked_rem(rhs).unwrap_or(0)))
+      }
       (Self::Float(lhs), Self::Float(rhs)) => Ok(Self::Float(lhs % rhs)),
 
       (lhs, rhs) => Err((lhs, rhs)),
@@ -362,6 +528,8 @@ impl fmt::Display for ExprKind {
         Self::String(x) => {
           write!(f, "{}{}{}", "\"".green(), x.green(), "\"".green(),)
         }
+        Self::Char(x) => write!(f, "{}", char_literal(*x).green()),
+        Self::Bytes(x) => write!(f, "{}", bytes_literal(x).green()),
 
         Self::Symbol(x) => write!(f, "{}", x.as_str().blue()),
 
@@ -389,6 +557,33 @@ impl fmt::Display for ExprKind {
 
           write!(f, "}}")
         }
+        Self::Map(x) => {
+          write!(f, "#{{")?;
+
+          core::iter::once("")
+            .chain(core::iter::repeat(", "))
+            .zip(x.iter())
+            .try_for_each(|(sep, (key, value))| {
+              let key: Expr = ExprKind::from(key.clone()).into();
+              write!(f, "{sep}{key:#}: {value:#}")
+            })?;
+
+          write!(f, "}}")
+        }
+        Self::Set(x) => {
+          write!(f, "{}", "#[".yellow())?;
+
+          core::iter::once("")
+            .chain(core::iter::repeat(" "))
+            .zip(x.iter())
+            .try_for_each(|(sep, x)| {
+              let x: Expr = ExprKind::from(x.clone()).into();
+              write!(f, "{sep}{x:#}")
+            })?;
+
+          write!(f, "{}", "]".yellow())
+        }
+        Self::Seq(x) => write!(f, "{}{x}{}", "#seq(".yellow(), ")".yellow()),
 
         Self::Function { scope, body } => {
           write!(f, "{}", "(".yellow())?;
@@ -417,6 +612,12 @@ impl fmt::Display for ExprKind {
           write!(f, "{}", ")".yellow())
         }
         Self::Underscore => write!(f, "_"),
+
+        Self::Error(x) => {
+          write!(f, "{}{} {x:#}{}", "(".yellow(), "error".red(), ")".yellow())
+        }
+
+        Self::Foreign(x) => write!(f, "{}", x.to_string().magenta()),
       }
     } else {
       match self {
@@ -426,6 +627,8 @@ impl fmt::Display for ExprKind {
         Self::Integer(x) => write!(f, "{x}"),
         Self::Float(x) => write!(f, "{x}"),
         Self::String(x) => write!(f, "{x}"),
+        Self::Char(x) => write!(f, "{x}"),
+        Self::Bytes(x) => write!(f, "{}", bytes_literal(x)),
 
         Self::Symbol(x) => write!(f, "{}", x.as_str()),
 
@@ -452,6 +655,29 @@ impl fmt::Display for ExprKind {
 
           write!(f, "}}")
         }
+        Self::Map(x) => {
+          write!(f, "#{{")?;
+
+          core::iter::once("")
+            .chain(core::iter::repeat(", "))
+            .zip(x.iter())
+            .try_for_each(|(sep, (key, value))| {
+              write!(f, "{sep}{key}: {value}")
+            })?;
+
+          write!(f, "}}")
+        }
+        Self::Set(x) => {
+          write!(f, "#[")?;
+
+          core::iter::once("")
+            .chain(core::iter::repeat(" "))
+            .zip(x.iter())
+            .try_for_each(|(sep, x)| write!(f, "{sep}{x}"))?;
+
+          write!(f, "]")
+        }
+        Self::Seq(x) => write!(f, "#seq({x})"),
 
         Self::Function { scope, body } => {
           write!(f, "(")?;
@@ -480,6 +706,10 @@ impl fmt::Display for ExprKind {
           write!(f, ")")
         }
         Self::Underscore => write!(f, "_"),
+
+        Self::Error(x) => write!(f, "(error {x})"),
+
+        Self::Foreign(x) => write!(f, "{x}"),
       }
     }
   }
@@ -525,10 +755,87 @@ impl fmt::Display for ErrorInner {
   }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ExprInfo {
   pub source: Source,
   pub span: Span,
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub comments: Comments,
+}
+
+impl ExprInfo {
+  /// Creates an [`ExprInfo`] without any [`Comments`].
+  #[inline]
+  pub fn new(source: Source, span: Span) -> Self {
+    Self {
+      source,
+      span,
+      comments: Comments::default(),
+    }
+  }
+
+  /// Returns the text of a comment, without the `;`s and surrounding
+  /// whitespace.
+  pub fn comment_text(&self, span: Span) -> &str {
+    self
+      .source
+      .source()
+      .get(span.to_range())
+      .unwrap_or_default()
+      .trim_start_matches(';')
+      .trim()
+  }
+
+  /// Returns the text of the leading comments.
+  pub fn leading_comments(&self) -> impl Iterator<Item = &str> {
+    self
+      .comments
+      .leading
+      .iter()
+      .map(|span| self.comment_text(*span))
+  }
+
+  /// Returns the text of the leading `##` doc comments, without the `##`s,
+  /// or [`None`] if there aren't any.
+  pub fn doc(&self) -> Option<String> {
+    let lines = self
+      .comments
+      .leading
+      .iter()
+      .filter_map(|span| {
+        let text = self.source.source().get(span.to_range())?;
+        let text = text.strip_prefix("##")?;
+
+        Some(text.strip_prefix(' ').unwrap_or(text).trim_end())
+      })
+      .collect::<Vec<_>>();
+
+    (!lines.is_empty()).then(|| lines.join("\n"))
+  }
+}
+
+/// The [`Span`]s of the comments attached to an [`Expr`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Comments {
+  /// The comments on the lines before the expression.
+  pub leading: Vec<Span>,
+  /// The comment on the same line after the expression.
+  pub trailing: Option<Span>,
+  /// The comments after the last item of a list or parenthetical, before its
+  /// closing delimiter. For the last top-level expression, these are the
+  /// comments at the end of the source.
+  pub dangling: Vec<Span>,
+}
+
+impl Comments {
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.leading.is_empty()
+      && self.trailing.is_none()
+      && self.dangling.is_empty()
+  }
 }
 
 impl fmt::Display for ExprInfo {
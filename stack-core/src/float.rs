@@ -0,0 +1,240 @@
+//! How floats are shown when they're printed.
+//!
+//! By default, a float is shown with the fewest digits which read back as the
+//! same float, which can be a lot of noise, such as `0.30000000000000004`. A
+//! [`FloatFormat`] rounds floats to some significant digits and switches to
+//! scientific notation for very large or small ones.
+//!
+//! This only changes how floats are shown: [`format`](crate::format) and
+//! casting a float to a string are always exact.
+
+use core::{fmt, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::expr::ExprKind;
+
+/// Options for showing floats, held by each [`Context`].
+///
+/// [`Context`]: crate::context::Context
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FloatFormat {
+  /// The amount of significant digits which are shown, or [`None`] for as
+  /// many as are needed to be exact.
+  pub digits: Option<u8>,
+  /// The power of ten from which scientific notation is used, for floats at
+  /// least `10^n` or less than `10^-n`, or [`None`] to never use it.
+  pub sci_threshold: Option<u8>,
+}
+
+impl FloatFormat {
+  /// Formats a float.
+  pub fn format(self, x: f64) -> String {
+    if !x.is_finite() {
+      return x.to_string();
+    }
+
+    let digits = self.digits.map(|x| usize::from(x.max(1)));
+
+    if let Some(threshold) = self.sci_threshold.map(i32::from) {
+      let exponent = match x {
+        0.0 => 0,
+        x => x.abs().log10().floor() as i32,
+      };
+
+      if exponent >= threshold || exponent < -threshold {
+        return match digits {
+          Some(digits) => trim_exponent(&format!("{:.*e}", digits - 1, x)),
+          None => format!("{x:e}"),
+        };
+      }
+    }
+
+    match digits {
+      // Rounding through scientific notation keeps the significant digits,
+      // however large or small the float is.
+      Some(digits) => format!("{:.*e}", digits - 1, x)
+        .parse::<f64>()
+        .unwrap_or(x)
+        .to_string(),
+      None => x.to_string(),
+    }
+  }
+
+  /// Returns a [`Display`](fmt::Display) of an [`ExprKind`], with the floats
+  /// in it formatted, including those within collections.
+  #[inline]
+  pub fn display(self, kind: &ExprKind) -> Formatted<'_> {
+    Formatted { kind, format: self }
+  }
+}
+
+/// Removes the trailing zeros of the mantissa of a float in scientific
+/// notation.
+fn trim_exponent(x: &str) -> String {
+  match x.split_once('e') {
+    Some((mantissa, exponent)) if mantissa.contains('.') => {
+      let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+      format!("{mantissa}e{exponent}")
+    }
+    _ => x.into(),
+  }
+}
+
+/// Parses `digits=N,sci=N`, where either can be left out, or `default`.
+impl FromStr for FloatFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut format = Self::default();
+
+    if s == "default" {
+      return Ok(format);
+    }
+
+    for option in s.split(',').map(str::trim) {
+      let (key, value) = option
+        .split_once('=')
+        .ok_or_else(|| format!("expected `key=value`, found `{option}`"))?;
+
+      match key.trim() {
+        "digits" => match value.trim().parse() {
+          Ok(0) | Err(_) => {
+            return Err(format!("invalid amount of digits `{value}`"))
+          }
+          Ok(x) => format.digits = Some(x),
+        },
+        "sci" => match value.trim().parse() {
+          Ok(x) => format.sci_threshold = Some(x),
+          Err(_) => {
+            return Err(format!("invalid scientific threshold `{value}`"))
+          }
+        },
+        key => return Err(format!("unknown float format option `{key}`")),
+      }
+    }
+
+    Ok(format)
+  }
+}
+
+impl fmt::Display for FloatFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match (self.digits, self.sci_threshold) {
+      (None, None) => write!(f, "default"),
+      (Some(digits), None) => write!(f, "digits={digits}"),
+      (None, Some(sci)) => write!(f, "sci={sci}"),
+      (Some(digits), Some(sci)) => write!(f, "digits={digits},sci={sci}"),
+    }
+  }
+}
+
+/// The [`Display`](fmt::Display) returned by [`FloatFormat::display`].
+#[derive(Debug, Clone, Copy)]
+pub struct Formatted<'a> {
+  kind: &'a ExprKind,
+  format: FloatFormat,
+}
+
+impl fmt::Display for Formatted<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let format = self.format;
+
+    match self.kind {
+      ExprKind::Float(x) => write!(f, "{}", format.format(*x)),
+      ExprKind::Lazy(x) => write!(f, "{}", format.display(&x.kind)),
+      ExprKind::List(x) => {
+        write!(f, "[")?;
+
+        core::iter::once("")
+          .chain(core::iter::repeat(" "))
+          .zip(x.iter())
+          .try_for_each(|(sep, x)| {
+            write!(f, "{sep}{}", format.display(&x.kind))
+          })?;
+
+        write!(f, "]")
+      }
+      ExprKind::Record(x) => {
+        write!(f, "{{")?;
+
+        core::iter::once("")
+          .chain(core::iter::repeat(", "))
+          .zip(x.iter())
+          .try_for_each(|(sep, (key, value))| {
+            write!(f, "{sep}{key}: {}", format.display(&value.kind))
+          })?;
+
+        write!(f, "}}")
+      }
+      ExprKind::Map(x) => {
+        write!(f, "#{{")?;
+
+        core::iter::once("")
+          .chain(core::iter::repeat(", "))
+          .zip(x.iter())
+          .try_for_each(|(sep, (key, value))| {
+            write!(f, "{sep}{key}: {}", format.display(&value.kind))
+          })?;
+
+        write!(f, "}}")
+      }
+      ExprKind::Error(x) => {
+        write!(f, "(error {})", format.display(&x.kind))
+      }
+      kind => write!(f, "{kind}"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn formats_floats() {
+    let default = FloatFormat::default();
+    assert_eq!(default.format(0.1 + 0.2), "0.30000000000000004");
+    assert_eq!(default.format(1e20), "100000000000000000000");
+
+    let digits = FloatFormat {
+      digits: Some(3),
+      sci_threshold: None,
+    };
+    assert_eq!(digits.format(core::f64::consts::PI), "3.14");
+    assert_eq!(digits.format(0.1 + 0.2), "0.3");
+    assert_eq!(digits.format(123456.0), "123000");
+
+    let sci = FloatFormat {
+      digits: Some(3),
+      sci_threshold: Some(6),
+    };
+    assert_eq!(sci.format(1.5e20), "1.5e20");
+    assert_eq!(sci.format(-0.000_000_123_45), "-1.23e-7");
+    assert_eq!(sci.format(1234.5), "1230");
+    assert_eq!(sci.format(0.0), "0");
+    assert_eq!(sci.format(f64::NAN), "NaN");
+  }
+
+  #[test]
+  fn parses_float_formats() {
+    assert_eq!("default".parse(), Ok(FloatFormat::default()));
+    assert_eq!(
+      "digits=6, sci=12".parse(),
+      Ok(FloatFormat {
+        digits: Some(6),
+        sci_threshold: Some(12),
+      })
+    );
+    assert_eq!(
+      "sci=3".parse::<FloatFormat>().map(|x| x.to_string()),
+      Ok("sci=3".into())
+    );
+
+    assert!("digits=0".parse::<FloatFormat>().is_err());
+    assert!("digits".parse::<FloatFormat>().is_err());
+    assert!("width=3".parse::<FloatFormat>().is_err());
+  }
+}
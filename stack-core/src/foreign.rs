@@ -0,0 +1,151 @@
+//! Opaque Rust values, which native modules can put on the stack.
+//!
+//! A [`Foreign`] value wraps any Rust type, such as a file handle or a
+//! database connection, so that it can be passed around by a script and
+//! given back to the module which made it. Scripts can't look inside one;
+//! they only see its type, as `<foreign File>`.
+//!
+//! Cloning a [`Foreign`] shares the value, and two are only equal if they
+//! share the same value. A value which needs to change should use a
+//! [`Mutex`](std::sync::Mutex) or similar.
+
+use core::{any::Any, fmt};
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct Foreign {
+  value: Arc<dyn Any + Send + Sync>,
+  type_name: &'static str,
+}
+
+impl Foreign {
+  /// Creates a [`Foreign`] holding `value`.
+  #[inline]
+  pub fn new<T>(value: T) -> Self
+  where
+    T: Any + Send + Sync,
+  {
+    Self::from_arc(Arc::new(value))
+  }
+
+  /// Creates a [`Foreign`] sharing the value of `value`.
+  #[inline]
+  pub fn from_arc<T>(value: Arc<T>) -> Self
+  where
+    T: Any + Send + Sync,
+  {
+    Self {
+      value,
+      type_name: core::any::type_name::<T>(),
+    }
+  }
+
+  /// Returns the name of the type of the value, without its path or generics.
+  pub fn type_name(&self) -> &'static str {
+    let name = self.type_name;
+    let end = name.find('<').unwrap_or(name.len());
+    let start = name[..end].rfind("::").map_or(0, |i| i + 2);
+
+    &name[start..end]
+  }
+
+  /// Returns whether the value is a `T`.
+  #[inline]
+  pub fn is<T>(&self) -> bool
+  where
+    T: Any,
+  {
+    self.value.is::<T>()
+  }
+
+  /// Returns a reference to the value, or [`None`] if it isn't a `T`.
+  #[inline]
+  pub fn downcast_ref<T>(&self) -> Option<&T>
+  where
+    T: Any,
+  {
+    self.value.downcast_ref()
+  }
+
+  /// Returns the shared value, or [`None`] if it isn't a `T`.
+  #[inline]
+  pub fn downcast<T>(&self) -> Option<Arc<T>>
+  where
+    T: Any + Send + Sync,
+  {
+    self.value.clone().downcast().ok()
+  }
+}
+
+impl PartialEq for Foreign {
+  #[inline]
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.value, &other.value)
+  }
+}
+
+impl fmt::Debug for Foreign {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_tuple("Foreign").field(&self.type_name).finish()
+  }
+}
+
+impl fmt::Display for Foreign {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "<foreign {}>", self.type_name())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+
+  use super::*;
+
+  struct Connection {
+    queries: Mutex<usize>,
+  }
+
+  #[test]
+  fn downcasts_to_the_wrapped_type() {
+    let foreign = Foreign::new(Connection {
+      queries: Mutex::new(0),
+    });
+
+    assert!(foreign.is::<Connection>());
+    assert!(foreign.downcast_ref::<String>().is_none());
+
+    *foreign
+      .downcast::<Connection>()
+      .unwrap()
+      .queries
+      .lock()
+      .unwrap() += 1;
+    assert_eq!(
+      *foreign
+        .downcast_ref::<Connection>()
+        .unwrap()
+        .queries
+        .lock()
+        .unwrap(),
+      1
+    );
+  }
+
+  #[test]
+  fn compares_by_identity() {
+    let foreign = Foreign::new(1);
+
+    assert_eq!(foreign, foreign.clone());
+    assert_ne!(foreign, Foreign::new(1));
+  }
+
+  #[test]
+  fn displays_the_type_name() {
+    assert_eq!(Foreign::new(1i64).to_string(), "<foreign i64>");
+    assert_eq!(
+      Foreign::new(Vec::<Connection>::new()).to_string(),
+      "<foreign Vec>"
+    );
+  }
+}
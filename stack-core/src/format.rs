@@ -0,0 +1,660 @@
+use std::collections::HashMap;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+  expr::{bytes_literal, char_literal, display_fn_scope, Expr, ExprKind},
+  lexer::{Lexer, Span, TokenKind},
+  source::Source,
+  symbol::Symbol,
+};
+
+/// Options for [`format_exprs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FormatOptions {
+  /// The amount of spaces per indentation level.
+  pub indent: usize,
+  /// The width that lines are wrapped at, where possible.
+  pub max_width: usize,
+}
+
+impl Default for FormatOptions {
+  #[inline]
+  fn default() -> Self {
+    Self {
+      indent: 2,
+      max_width: 80,
+    }
+  }
+}
+
+/// Pretty-prints [`Expr`]s into their canonical source form.
+///
+/// When the [`Expr`]s have been parsed from a [`Source`], comments, line
+/// breaks and (at most one) blank line between expressions are preserved.
+/// Lists and functions are kept on one line if they were written on one line
+/// and fit within [`FormatOptions::max_width`], otherwise their items are
+/// indented on the following lines, with the closing delimiter on its own
+/// line. Records are written as `{a: 1, b: 2}`, keep the order their entries
+/// were written in, and are broken with one entry per line the same way, or
+/// if they have comments.
+///
+/// [`Source`]: crate::source::Source
+pub fn format_exprs(exprs: &[Expr], options: FormatOptions) -> String {
+  let source = exprs
+    .iter()
+    .find_map(|expr| expr.info.as_ref())
+    .map(|info| info.source.source());
+  let end = source.map(str::len).unwrap_or_default();
+
+  let mut formatter = Formatter {
+    options,
+    out: String::new(),
+  };
+  formatter.write_lines(&lines(exprs, source, 0, end), 0);
+
+  if !formatter.out.is_empty() {
+    formatter.out.push('\n');
+  }
+
+  formatter.out
+}
+
+#[derive(Debug, Default)]
+struct Line<'a> {
+  exprs: Vec<&'a Expr>,
+  comment: Option<&'a str>,
+  blank_before: bool,
+}
+
+impl<'a> Line<'a> {
+  #[inline]
+  fn is_empty(&self) -> bool {
+    self.exprs.is_empty() && self.comment.is_none()
+  }
+}
+
+#[derive(Debug, Default)]
+struct LineBuilder<'a> {
+  lines: Vec<Line<'a>>,
+  line: Line<'a>,
+  newlines: usize,
+}
+
+impl<'a> LineBuilder<'a> {
+  fn begin(&mut self) {
+    if self.line.is_empty() {
+      self.line.blank_before = self.newlines >= 2 && !self.lines.is_empty();
+    }
+
+    self.newlines = 0;
+  }
+
+  fn break_line(&mut self) {
+    if !self.line.is_empty() {
+      self.lines.push(core::mem::take(&mut self.line));
+    }
+  }
+
+  fn expr(&mut self, expr: &'a Expr) {
+    self.begin();
+    self.line.exprs.push(expr);
+  }
+
+  fn comment(&mut self, comment: &'a str) {
+    // A comment on the same line as an expression trails it, otherwise it is
+    // given a line of its own.
+    if self.newlines == 0 && !self.line.exprs.is_empty() {
+      self.line.comment = Some(comment);
+    } else {
+      self.break_line();
+      self.begin();
+      self.line.comment = Some(comment);
+    }
+
+    self.break_line();
+  }
+
+  fn gap(&mut self, gap: &'a str) {
+    let mut rest = gap;
+
+    while let Some(c) = rest.chars().next() {
+      match c {
+        '\n' => {
+          self.break_line();
+          self.newlines += 1;
+          rest = &rest[1..];
+        }
+        // The `#!` line of a script is kept like a comment.
+        _ if rest.starts_with(';')
+          || rest.starts_with("##")
+          || rest.starts_with("#!") =>
+        {
+          let end = rest.find('\n').unwrap_or(rest.len());
+          self.comment(rest[..end].trim_end());
+          rest = &rest[end..];
+        }
+        c => rest = &rest[c.len_utf8()..],
+      }
+    }
+  }
+
+  fn finish(mut self) -> Vec<Line<'a>> {
+    self.break_line();
+    self.lines
+  }
+}
+
+/// Groups `exprs` into lines, using the source text between `start` and `end`
+/// to find line breaks and comments.
+fn lines<'a>(
+  exprs: &'a [Expr],
+  source: Option<&'a str>,
+  start: usize,
+  end: usize,
+) -> Vec<Line<'a>> {
+  let mut builder = LineBuilder::default();
+  let mut cursor = start;
+
+  for expr in exprs {
+    if let Some(span) = span_in(expr, source) {
+      if let Some(gap) =
+        source.and_then(|source| source.get(cursor..span.start))
+      {
+        builder.gap(gap);
+      }
+
+      cursor = span.end;
+    }
+
+    builder.expr(expr);
+  }
+
+  if let Some(gap) = source.and_then(|source| source.get(cursor..end)) {
+    builder.gap(gap);
+  }
+
+  builder.finish()
+}
+
+/// Returns the [`Span`] of an [`Expr`], if it was parsed from `source`.
+fn span_in(expr: &Expr, source: Option<&str>) -> Option<Span> {
+  let info = expr.info.as_ref()?;
+  let source = source?;
+
+  core::ptr::eq(info.source.source(), source).then_some(info.span)
+}
+
+/// Returns the source text and the range between the delimiters of a list or
+/// parenthetical [`Expr`], skipping the call for the latter.
+fn inner_range(expr: &Expr) -> Option<(&str, usize, usize)> {
+  let info = expr.info.as_ref()?;
+  let source = info.source.source();
+  let Span { start, end } = info.span;
+
+  let start = match expr.kind {
+    ExprKind::List(_) => start + 1,
+    ExprKind::Function { .. } | ExprKind::SExpr { .. } => {
+      let rest = source.get(start + 1..end)?;
+      let trimmed = rest.trim_start();
+      let call = trimmed
+        .find(|c: char| c.is_whitespace() || "()[]{}'\";".contains(c))
+        .unwrap_or(trimmed.len());
+
+      start + 1 + (rest.len() - trimmed.len()) + call
+    }
+    _ => return None,
+  };
+
+  let end = end.checked_sub(1)?;
+  (start <= end).then_some((source, start, end))
+}
+
+/// Returns the entries of a record in the order they were written, or by key
+/// for those which weren't parsed from a source.
+fn record_entries(record: &HashMap<Symbol, Expr>) -> Vec<(&str, &Expr)> {
+  let mut entries = record
+    .iter()
+    .map(|(k, v)| (k.as_str(), v))
+    .collect::<Vec<_>>();
+  entries.sort_by_key(|(k, v)| (v.info.as_ref().map(|x| x.span.start), *k));
+
+  entries
+}
+
+/// Returns a record key followed by its colon, as in `{a: 1}`, which is
+/// quoted if it can't be written as a symbol.
+fn record_key(key: &str) -> String {
+  let text = format!("{key}:");
+  let token = Lexer::new(Source::new("", text.as_str())).next();
+
+  if !key.is_empty()
+    && token.kind == TokenKind::Symbol
+    && token.span.end == text.len()
+  {
+    text
+  } else {
+    format!("{}:", escape(key))
+  }
+}
+
+/// Returns whether a record or any of its values have comments attached.
+fn has_comments(expr: &Expr, entries: &[(&str, &Expr)]) -> bool {
+  let has = |expr: &Expr| {
+    expr.info.as_ref().is_some_and(|info| {
+      !info.comments.leading.is_empty() || info.comments.trailing.is_some()
+    })
+  };
+
+  dangling(expr).next().is_some() || entries.iter().any(|(_, v)| has(v))
+}
+
+/// Returns the comments after the last entry of a record.
+///
+/// Those of the last top-level [`Expr`] are the comments at the end of the
+/// source, so only the ones within its braces are returned.
+fn dangling(expr: &Expr) -> impl Iterator<Item = Span> + '_ {
+  expr.info.iter().flat_map(|info| {
+    info
+      .comments
+      .dangling
+      .iter()
+      .copied()
+      .filter(|x| x.end <= info.span.end)
+  })
+}
+
+/// Returns whether the entries of a record were written on separate lines.
+fn is_multiline(expr: &Expr, entries: &[(&str, &Expr)]) -> bool {
+  let Some(info) = &expr.info else {
+    return false;
+  };
+  let source = info.source.source();
+
+  let mut cursor = info.span.start;
+  let mut gaps = Vec::new();
+
+  for (_, value) in entries {
+    if let Some(span) = span_in(value, Some(source)) {
+      gaps.push(cursor..span.start);
+      cursor = span.end;
+    }
+  }
+  gaps.push(cursor..info.span.end);
+
+  gaps
+    .into_iter()
+    .any(|gap| source.get(gap).is_some_and(|gap| gap.contains('\n')))
+}
+
+/// Returns the text of a comment attached to an [`Expr`].
+fn comment_text(expr: &Expr, span: Span) -> &str {
+  expr
+    .info
+    .as_ref()
+    .and_then(|info| info.source.source().get(span.to_range()))
+    .unwrap_or_default()
+    .trim_end()
+}
+
+#[inline]
+fn width(s: &str) -> usize {
+  s.graphemes(true).count()
+}
+
+fn escape(s: &str) -> String {
+  let mut escaped = String::with_capacity(s.len() + 2);
+  escaped.push('"');
+
+  for c in s.chars() {
+    match c {
+      '\n' => escaped.push_str("\\n"),
+      '\t' => escaped.push_str("\\t"),
+      '\r' => escaped.push_str("\\r"),
+      '\0' => escaped.push_str("\\0"),
+      '"' => escaped.push_str("\\\""),
+      '\\' => escaped.push_str("\\\\"),
+      c => escaped.push(c),
+    }
+  }
+
+  escaped.push('"');
+  escaped
+}
+
+fn atom(expr: &Expr) -> String {
+  if let Some(info) = &expr.info {
+    if let Some(slice) = info.source.source().get(info.span.to_range()) {
+      return slice.into();
+    }
+  }
+
+  match &expr.kind {
+    ExprKind::Float(x) if x.is_finite() && x.fract() == 0.0 => {
+      format!("{x:.1}")
+    }
+    ExprKind::String(x) => escape(x),
+    ExprKind::Char(x) => char_literal(*x),
+    ExprKind::Bytes(x) => bytes_literal(x),
+    kind => kind.to_string(),
+  }
+}
+
+struct Formatter {
+  options: FormatOptions,
+  out: String,
+}
+
+impl Formatter {
+  fn column(&self) -> usize {
+    width(self.out.rsplit('\n').next().unwrap_or_default())
+  }
+
+  fn write_indent(&mut self, indent: usize) {
+    self.out.extend(core::iter::repeat_n(' ', indent));
+  }
+
+  fn write_lines(&mut self, lines: &[Line], indent: usize) {
+    for (i, line) in lines.iter().enumerate() {
+      if i > 0 {
+        self.out.push('\n');
+
+        if line.blank_before {
+          self.out.push('\n');
+        }
+      }
+
+      self.write_indent(indent);
+
+      for (j, expr) in line.exprs.iter().enumerate() {
+        if j > 0 {
+          let wraps = self.flat(expr).is_some_and(|flat| {
+            self.column() + 1 + width(&flat) > self.options.max_width
+          });
+
+          if wraps {
+            self.out.push('\n');
+            self.write_indent(indent);
+          } else {
+            self.out.push(' ');
+          }
+        }
+
+        self.write_expr(expr, indent);
+      }
+
+      if let Some(comment) = line.comment {
+        if !line.exprs.is_empty() {
+          self.out.push(' ');
+        }
+
+        self.out.push_str(comment);
+      }
+    }
+  }
+
+  fn write_expr(&mut self, expr: &Expr, indent: usize) {
+    if let Some(flat) = self.flat(expr) {
+      let is_container = matches!(
+        expr.kind.unlazy(),
+        ExprKind::List(_)
+          | ExprKind::Function { .. }
+          | ExprKind::SExpr { .. }
+          | ExprKind::Record(_)
+      );
+
+      if !is_container || self.column() + width(&flat) <= self.options.max_width
+      {
+        self.out.push_str(&flat);
+        return;
+      }
+    }
+
+    match &expr.kind {
+      ExprKind::Lazy(x) => {
+        self.out.push('\'');
+        self.write_expr(x, indent);
+      }
+      ExprKind::List(items) => {
+        self.write_container(expr, "[", items, "]", indent)
+      }
+      ExprKind::Function { scope, body } => self.write_container(
+        expr,
+        &format!("({}", display_fn_scope(scope)),
+        body,
+        ")",
+        indent,
+      ),
+      ExprKind::SExpr { call, body } => {
+        self.write_container(expr, &format!("({call}"), body, ")", indent)
+      }
+      ExprKind::Record(record) => self.write_record(expr, record, indent),
+      _ => self.out.push_str(&atom(expr)),
+    }
+  }
+
+  fn write_container(
+    &mut self,
+    expr: &Expr,
+    open: &str,
+    items: &[Expr],
+    close: &str,
+    indent: usize,
+  ) {
+    let lines = match inner_range(expr) {
+      Some((source, start, end)) => lines(items, Some(source), start, end),
+      None => lines(items, None, 0, 0),
+    };
+
+    self.out.push_str(open);
+
+    if !lines.is_empty() {
+      self.out.push('\n');
+      self.write_lines(&lines, indent + self.options.indent);
+      self.out.push('\n');
+      self.write_indent(indent);
+    }
+
+    self.out.push_str(close);
+  }
+
+  /// Writes a record with each entry on its own line, keeping the comments
+  /// which were attached to its values by the parser.
+  fn write_record(
+    &mut self,
+    expr: &Expr,
+    record: &HashMap<Symbol, Expr>,
+    indent: usize,
+  ) {
+    let inner = indent + self.options.indent;
+
+    self.out.push_str("{\n");
+
+    for (k, value) in record_entries(record) {
+      let info = value.info.as_ref();
+
+      let leading = info.map(|x| x.comments.leading.as_slice());
+
+      for comment in leading.unwrap_or_default() {
+        self.write_indent(inner);
+        self.out.push_str(comment_text(value, *comment));
+        self.out.push('\n');
+      }
+
+      self.write_indent(inner);
+      self.out.push_str(&record_key(k));
+      self.out.push(' ');
+      self.write_expr(value, inner);
+
+      if let Some(comment) = info.and_then(|x| x.comments.trailing) {
+        self.out.push(' ');
+        self.out.push_str(comment_text(value, comment));
+      }
+
+      self.out.push('\n');
+    }
+
+    for comment in dangling(expr) {
+      self.write_indent(inner);
+      self.out.push_str(comment_text(expr, comment));
+      self.out.push('\n');
+    }
+
+    self.write_indent(indent);
+    self.out.push('}');
+  }
+
+  /// Returns the single-line form of an [`Expr`], unless it must be broken
+  /// over multiple lines.
+  fn flat(&self, expr: &Expr) -> Option<String> {
+    match &expr.kind {
+      ExprKind::Lazy(x) => self.flat(x).map(|x| format!("'{x}")),
+      ExprKind::List(items) => {
+        self.flat_container(expr, "[".into(), items, "]")
+      }
+      ExprKind::Function { scope, body } => self.flat_container(
+        expr,
+        format!("({}", display_fn_scope(scope)),
+        body,
+        ")",
+      ),
+      ExprKind::SExpr { call, body } => {
+        self.flat_container(expr, format!("({call}"), body, ")")
+      }
+      ExprKind::Record(record) => {
+        let entries = record_entries(record);
+
+        if has_comments(expr, &entries) || is_multiline(expr, &entries) {
+          return None;
+        }
+
+        let entries = entries
+          .into_iter()
+          .map(|(k, v)| self.flat(v).map(|v| format!("{} {v}", record_key(k))))
+          .collect::<Option<Vec<_>>>()?;
+
+        Some(format!("{{{}}}", entries.join(", ")))
+      }
+      _ => Some(atom(expr)),
+    }
+  }
+
+  fn flat_container(
+    &self,
+    expr: &Expr,
+    open: String,
+    items: &[Expr],
+    close: &str,
+  ) -> Option<String> {
+    if let Some((source, start, end)) = inner_range(expr) {
+      let lines = lines(items, Some(source), start, end);
+
+      if lines.len() > 1 || lines.iter().any(|line| line.comment.is_some()) {
+        return None;
+      }
+    }
+
+    let items = items
+      .iter()
+      .map(|x| self.flat(x))
+      .collect::<Option<Vec<_>>>()?;
+
+    let sep = if open == "[" || items.is_empty() {
+      ""
+    } else {
+      " "
+    };
+    Some(format!("{open}{sep}{}{close}", items.join(" ")))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::*;
+  use crate::prelude::*;
+
+  fn format(source: &str, options: FormatOptions) -> String {
+    let mut lexer = Lexer::new(Source::new("", source));
+    let exprs = parse(&mut lexer).unwrap();
+
+    format_exprs(&exprs, options)
+  }
+
+  #[test_case("1   2\n\n\n3" => "1 2\n\n3\n" ; "collapses whitespace")]
+  #[test_case("'(fn   a b) 'f def" => "'(fn a b) 'f def\n" ; "functions")]
+  #[test_case("[ 1  2 ]  []  '(fn)" => "[1 2] [] '(fn)\n" ; "empty containers")]
+  #[test_case("; hi\n1 ; one\n2" => "; hi\n1 ; one\n2\n" ; "comments")]
+  #[test_case("#!/usr/bin/env stack run\n1   2" => "#!/usr/bin/env stack run\n1 2\n" ; "shebang")]
+  #[test_case(
+    "'(fn\n1 ; one\n[2\n3])" => "'(fn\n  1 ; one\n  [\n    2\n    3\n  ]\n)\n"
+    ; "nested containers"
+  )]
+  #[test_case("\"a  )\" 'b" => "\"a  )\" 'b\n" ; "keeps strings verbatim")]
+  #[test_case("{b 1   a 2}" => "{b: 1, a: 2}\n" ; "records keep their order")]
+  #[test_case(
+    "{\"a\" 1 b: 2 \"c d\" : 3 \"1\" 4}" => "{a: 1, b: 2, \"c d\": 3, \"1\": 4}\n"
+    ; "record keys"
+  )]
+  #[test_case(
+    "{b 1 ; keep me\n a 2}" => "{\n  b: 1 ; keep me\n  a: 2\n}\n"
+    ; "records with comments"
+  )]
+  #[test_case(
+    "{; first\na [1\n2] ; list\n; last\n}"
+      => "{\n  ; first\n  a: [\n    1\n    2\n  ] ; list\n  ; last\n}\n"
+    ; "nested records"
+  )]
+  #[test_case("" => "" ; "empty")]
+  fn formats(source: &str) -> String {
+    format(source, FormatOptions::default())
+  }
+
+  #[test]
+  fn wraps_long_lines() {
+    let options = FormatOptions {
+      max_width: 10,
+      ..Default::default()
+    };
+
+    assert_eq!(format("1 2 3 4 5 6 7 8", options), "1 2 3 4 5\n6 7 8\n");
+    assert_eq!(
+      format("[1 2 3 4 5 6 7 8]", options),
+      "[\n  1 2 3 4\n  5 6 7 8\n]\n"
+    );
+  }
+
+  #[test]
+  fn round_trips_records() {
+    let source = "{\"a \\\"b\\\" \\\\ c\" 1 d \"e\\\"f\" ; g\n h {i 2}}";
+    let exprs = parse(&mut Lexer::new(Source::new("", source))).unwrap();
+    let formatted = format(source, FormatOptions::default());
+
+    assert_eq!(
+      parse(&mut Lexer::new(Source::new("", &formatted))).unwrap(),
+      exprs
+    );
+
+    let exprs = vec![Expr {
+      kind: ExprKind::String("\"a\" \\ b".into()),
+      info: None,
+    }];
+    let formatted = format_exprs(&exprs, FormatOptions::default());
+
+    assert_eq!(formatted, "\"\\\"a\\\" \\\\ b\"\n");
+    assert_eq!(
+      parse(&mut Lexer::new(Source::new("", &formatted))).unwrap(),
+      exprs
+    );
+  }
+
+  #[test]
+  fn is_idempotent() {
+    let source = "0 'n def\n\n'(fn\n  n 1 + 'n set\n\n  ; loop until 10\n  n 10 < 'loop 'nil if\n) 'loop def\n\nloop\n";
+    let formatted = format(source, FormatOptions::default());
+
+    assert_eq!(formatted, source);
+    assert_eq!(format(&formatted, FormatOptions::default()), formatted);
+  }
+}
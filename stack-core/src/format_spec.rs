@@ -0,0 +1,311 @@
+//! Printf-style formatting of values, as used by the `fmt` intrinsic and
+//! `str:format`.
+//!
+//! A spec follows Rust's syntax, `[[fill]align][+][#][0][width][.precision][type]`:
+//!
+//! - `align` is `<`, `>` or `^`, and `fill` is the character to pad with,
+//!   which is a space by default. Numbers are aligned right by default, and
+//!   everything else left.
+//! - `+` always writes the sign of a number, and `#` prefixes a radix with
+//!   `0x`, `0o` or `0b`.
+//! - `0` pads a number with zeros after its sign, instead of with `fill`.
+//! - `precision` is the amount of digits after the point for a number, and
+//!   the most characters to keep of anything else.
+//! - `type` is `x` or `X` for hexadecimal, `o` for octal, `b` for binary, and
+//!   `e` for scientific notation.
+//!
+//! The width and precision can be at most [`MAX_WIDTH`], since scripts choose
+//! them and they're allocated up front.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::expr::{Expr, ExprKind};
+
+/// The largest width or precision of a spec.
+pub const MAX_WIDTH: usize = u16::MAX as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+  Left,
+  Right,
+  Center,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatKind {
+  Hex,
+  UpperHex,
+  Octal,
+  Binary,
+  Exponent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatSpec {
+  pub fill: char,
+  pub align: Option<Align>,
+  pub sign: bool,
+  pub alternate: bool,
+  pub zero: bool,
+  pub width: usize,
+  pub precision: Option<usize>,
+  pub kind: Option<FormatKind>,
+}
+
+impl Default for FormatSpec {
+  fn default() -> Self {
+    Self {
+      fill: ' ',
+      align: None,
+      sign: false,
+      alternate: false,
+      zero: false,
+      width: 0,
+      precision: None,
+      kind: None,
+    }
+  }
+}
+
+impl FormatSpec {
+  /// Parses a spec, such as `>8.2`, returning [`None`] if it's invalid or its
+  /// width or precision is more than [`MAX_WIDTH`].
+  pub fn parse(spec: &str) -> Option<Self> {
+    let mut this = Self::default();
+    let mut chars = spec.chars().peekable();
+
+    let align = |c| match c {
+      '<' => Some(Align::Left),
+      '>' => Some(Align::Right),
+      '^' => Some(Align::Center),
+      _ => None,
+    };
+
+    let mut lookahead = spec.chars();
+    match (lookahead.next(), lookahead.next().and_then(align)) {
+      (Some(fill), Some(x)) => {
+        this.fill = fill;
+        this.align = Some(x);
+        chars.nth(1);
+      }
+      (Some(c), None) if align(c).is_some() => {
+        this.align = align(c);
+        chars.next();
+      }
+      _ => {}
+    }
+
+    this.sign = chars.next_if_eq(&'+').is_some();
+    this.alternate = chars.next_if_eq(&'#').is_some();
+    this.zero = chars.next_if_eq(&'0').is_some();
+
+    let number = |chars: &mut core::iter::Peekable<core::str::Chars>| {
+      let mut digits = String::new();
+      while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+      }
+
+      match digits.is_empty() {
+        true => Some(None),
+        false => digits.parse().ok().filter(|x| *x <= MAX_WIDTH).map(Some),
+      }
+    };
+
+    this.width = number(&mut chars)?.unwrap_or(0);
+
+    if chars.next_if_eq(&'.').is_some() {
+      this.precision = Some(number(&mut chars)??);
+    }
+
+    this.kind = match chars.next() {
+      None => None,
+      Some('x') => Some(FormatKind::Hex),
+      Some('X') => Some(FormatKind::UpperHex),
+      Some('o') => Some(FormatKind::Octal),
+      Some('b') => Some(FormatKind::Binary),
+      Some('e') => Some(FormatKind::Exponent),
+      Some(_) => return None,
+    };
+
+    match chars.next() {
+      Some(_) => None,
+      None => Some(this),
+    }
+  }
+
+  /// Formats a value, returning [`None`] if the spec doesn't apply to it,
+  /// such as a radix for a string.
+  pub fn format(&self, kind: &ExprKind) -> Option<String> {
+    let (sign, body) = match (kind, self.kind, self.precision) {
+      (ExprKind::Integer(x), Some(FormatKind::Exponent), _) => {
+        self.float(*x as f64)
+      }
+      (ExprKind::Integer(x), Some(radix), _) => {
+        let (prefix, digits) = match radix {
+          FormatKind::Hex => ("0x", format!("{:x}", x.unsigned_abs())),
+          FormatKind::UpperHex => ("0x", format!("{:X}", x.unsigned_abs())),
+          FormatKind::Octal => ("0o", format!("{:o}", x.unsigned_abs())),
+          FormatKind::Binary => ("0b", format!("{:b}", x.unsigned_abs())),
+          FormatKind::Exponent => unreachable!(),
+        };
+
+        // The prefix goes with the sign, so that zeros are padded after it.
+        let prefix = if self.alternate { prefix } else { "" };
+        (format!("{}{prefix}", self.sign_of(*x < 0)), digits)
+      }
+      (ExprKind::Integer(x), None, Some(_)) => self.float(*x as f64),
+      (ExprKind::Integer(x), None, None) => {
+        (self.sign_of(*x < 0).into(), x.unsigned_abs().to_string())
+      }
+      (ExprKind::Float(x), None | Some(FormatKind::Exponent), _) => {
+        self.float(*x)
+      }
+      (_, Some(_), _) => return None,
+      (kind, None, precision) => {
+        let string = kind.to_string();
+        let string = match precision {
+          Some(x) => string.graphemes(true).take(x).collect(),
+          None => string,
+        };
+
+        return Some(self.pad("", string, Align::Left));
+      }
+    };
+
+    Some(self.pad(&sign, body, Align::Right))
+  }
+
+  fn sign_of(&self, is_negative: bool) -> &'static str {
+    match (is_negative, self.sign) {
+      (true, _) => "-",
+      (false, true) => "+",
+      (false, false) => "",
+    }
+  }
+
+  fn float(&self, x: f64) -> (String, String) {
+    let sign = self.sign_of(x.is_sign_negative() && x != 0.0);
+    let x = x.abs();
+
+    let body = match (self.kind, self.precision) {
+      (Some(FormatKind::Exponent), Some(p)) => format!("{x:.p$e}"),
+      (Some(FormatKind::Exponent), None) => format!("{x:e}"),
+      (_, Some(p)) => format!("{x:.p$}"),
+      (_, None) => x.to_string(),
+    };
+
+    (sign.into(), body)
+  }
+
+  /// Pads `sign` and `body` to the width. Numbers, which are aligned right by
+  /// default, are padded with zeros between them if the spec asks for it.
+  fn pad(&self, sign: &str, body: String, default: Align) -> String {
+    let len = sign.graphemes(true).count() + body.graphemes(true).count();
+    let padding = self.width.saturating_sub(len);
+
+    if self.zero && default == Align::Right {
+      return format!("{sign}{}{body}", "0".repeat(padding));
+    }
+
+    let fill = |n| self.fill.to_string().repeat(n);
+
+    match self.align.unwrap_or(default) {
+      Align::Left => format!("{sign}{body}{}", fill(padding)),
+      Align::Right => format!("{}{sign}{body}", fill(padding)),
+      Align::Center => format!(
+        "{}{sign}{body}{}",
+        fill(padding / 2),
+        fill(padding - padding / 2)
+      ),
+    }
+  }
+}
+
+/// Formats `template`, replacing each `{}` with the next of `args`, `{n}` with
+/// the `n`th, and either with a spec after a colon, such as `{:>8}` or
+/// `{1:.2}`. `{{` and `}}` are literal braces.
+///
+/// Returns [`None`] if the template or a spec is invalid, or if there aren't
+/// enough `args`.
+pub fn format(template: &str, args: &[Expr]) -> Option<String> {
+  let mut output = String::with_capacity(template.len());
+  let mut next = 0;
+  let mut rest = template;
+
+  while let Some(i) = rest.find(['{', '}']) {
+    output.push_str(&rest[..i]);
+
+    let brace = rest[i..].chars().next()?;
+    rest = &rest[i + 1..];
+
+    if let Some(x) = rest.strip_prefix(brace) {
+      output.push(brace);
+      rest = x;
+      continue;
+    }
+
+    if brace == '}' {
+      return None;
+    }
+
+    let end = rest.find('}')?;
+    let (index, spec) = match rest[..end].split_once(':') {
+      Some((index, spec)) => (index, FormatSpec::parse(spec)?),
+      None => (&rest[..end], FormatSpec::default()),
+    };
+    rest = &rest[end + 1..];
+
+    let index = match index {
+      "" => {
+        next += 1;
+        next - 1
+      }
+      index => index.parse().ok()?,
+    };
+
+    output.push_str(&spec.format(&args.get(index)?.kind)?);
+  }
+
+  output.push_str(rest);
+
+  Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::case;
+
+  use super::*;
+
+  #[case("", ExprKind::Integer(42) => Some("42".into()) ; "plain")]
+  #[case(">6", ExprKind::Integer(42) => Some("    42".into()) ; "align right")]
+  #[case("*^7", ExprKind::String("hi".into()) => Some("**hi***".into()) ; "center with fill")]
+  #[case("5", ExprKind::String("hi".into()) => Some("hi   ".into()) ; "strings align left")]
+  #[case("+06.2", ExprKind::Float(1.5) => Some("+01.50".into()) ; "sign and zeros")]
+  #[case("#x", ExprKind::Integer(255) => Some("0xff".into()) ; "hex")]
+  #[case("08b", ExprKind::Integer(-5) => Some("-0000101".into()) ; "negative binary")]
+  #[case(".1e", ExprKind::Integer(1500) => Some("1.5e3".into()) ; "exponent")]
+  #[case(".3", ExprKind::String("hello".into()) => Some("hel".into()) ; "truncate")]
+  #[case("x", ExprKind::String("a".into()) => None ; "radix of a string")]
+  #[case("8q", ExprKind::Integer(1) => None ; "invalid type")]
+  #[case("65535", ExprKind::Integer(1) => Some(format!("{:>65535}", 1)) ; "largest width")]
+  #[case("65536", ExprKind::Integer(1) => None ; "too wide")]
+  #[case("9999999999999", ExprKind::Integer(1) => None ; "far too wide")]
+  #[case(".99999999999", ExprKind::Float(1.5) => None ; "too precise")]
+  fn formats_values(spec: &str, kind: ExprKind) -> Option<String> {
+    FormatSpec::parse(spec).and_then(|spec| spec.format(&kind))
+  }
+
+  #[test]
+  fn formats_templates() {
+    let args = [ExprKind::Integer(7).into(), ExprKind::Float(0.5).into()];
+
+    assert_eq!(
+      format("{} is {1:.2} {{ok}} {0:>3}", &args),
+      Some("7 is 0.50 {ok}   7".into())
+    );
+    assert_eq!(format("{} {} {}", &args), None);
+    assert_eq!(format("{", &args), None);
+    assert_eq!(format("{:.99999999999}", &args), None);
+  }
+}
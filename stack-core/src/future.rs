@@ -0,0 +1,369 @@
+//! Native functions which return futures, for the `async` feature.
+//!
+//! An [`AsyncFunc`] is added to a [`Module`] with [`Module::add_async_func`].
+//! [`Engine::run_async`] awaits it when it's called at the top level of a
+//! program, so that a program waiting on I/O doesn't hold up a thread. This
+//! works with any runtime, since the engine only awaits the futures which the
+//! functions return.
+//!
+//! Anywhere else, such as within a function body or with [`Engine::run`], an
+//! async function blocks the calling thread until its future is ready. Within
+//! [`Engine::run_async`], blocking would park the thread which is polling the
+//! program, which deadlocks a single-threaded runtime whose own timers or I/O
+//! the future is waiting on, so how it blocks depends on the runtime:
+//!
+//! - With the `tokio` feature on a multi-threaded Tokio runtime, the future is
+//!   run with [`block_in_place`], which hands the thread's other work to
+//!   another one while it blocks.
+//! - On a current-thread Tokio runtime, or any other runtime without the
+//!   `tokio` feature, the call fails with
+//!   [`RunErrorReason::NestedAsyncCall`].
+//!
+//! With the `tokio` feature, an async function called outside of any Tokio
+//! runtime, such as by [`Engine::run`] on a plain thread, is run on a shared
+//! multi-threaded runtime, so that functions using Tokio's timers and I/O
+//! work there too.
+//!
+//! [`block_in_place`]: https://docs.rs/tokio/1/tokio/task/fn.block_in_place.html
+//! [`RunErrorReason::NestedAsyncCall`]: crate::engine::RunErrorReason::NestedAsyncCall
+//!
+//! [`Module`]: crate::module::Module
+//! [`Module::add_async_func`]: crate::module::Module::add_async_func
+//! [`Engine::run`]: crate::engine::Engine::run
+//! [`Engine::run_async`]: crate::engine::Engine::run_async
+
+use core::{
+  cell::Cell,
+  future::Future,
+  pin::{pin, Pin},
+  task::{Context as TaskContext, Poll, Waker},
+};
+use std::{
+  sync::Arc,
+  task::Wake,
+  thread::{self, Thread},
+};
+
+use crate::{
+  context::Context,
+  engine::{RunError, RunErrorReason},
+  expr::Expr,
+};
+
+/// The future returned by an [`AsyncFunc`].
+pub type AsyncResult =
+  Pin<Box<dyn Future<Output = Result<Context, RunError>> + Send>>;
+
+/// A native function which returns a future instead of blocking.
+pub type AsyncFunc = Arc<dyn Fn(Context, Expr) -> AsyncResult + Send + Sync>;
+
+/// Wakes the thread which is blocked on a future.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+  fn wake(self: Arc<Self>) {
+    self.0.unpark();
+  }
+
+  fn wake_by_ref(self: &Arc<Self>) {
+    self.0.unpark();
+  }
+}
+
+thread_local! {
+  /// Whether this thread is running the synchronous part of
+  /// [`Engine::run_async`](crate::engine::Engine::run_async).
+  static IS_POLLING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` while marking this thread as polling a program, so that async
+/// functions within it fail instead of blocking.
+pub(crate) fn polling<T>(f: impl FnOnce() -> T) -> T {
+  struct Reset(bool);
+
+  impl Drop for Reset {
+    fn drop(&mut self) {
+      IS_POLLING.set(self.0);
+    }
+  }
+
+  let _reset = Reset(IS_POLLING.replace(true));
+  f()
+}
+
+/// Returns whether this thread is polling a program, where blocking on a
+/// future could deadlock.
+#[inline]
+pub fn is_polling() -> bool {
+  IS_POLLING.get()
+}
+
+/// Runs `func` where it can't be awaited, blocking until it's finished, or
+/// fails with [`RunErrorReason::NestedAsyncCall`] if that could deadlock.
+pub(crate) fn wait(
+  func: &AsyncFunc,
+  context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  #[cfg(feature = "tokio")]
+  {
+    use tokio::runtime::{Handle, RuntimeFlavor};
+
+    match Handle::try_current() {
+      Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+        return tokio::task::block_in_place(|| {
+          handle.block_on(func(context, expr))
+        });
+      }
+      Ok(_) => {}
+      Err(_) => return runtime().block_on(func(context, expr)),
+    }
+  }
+
+  if !is_polling() {
+    return block_on(func(context, expr));
+  }
+
+  Err(RunError {
+    reason: RunErrorReason::NestedAsyncCall,
+    context: Box::new(context),
+    expr: Box::new(expr),
+  })
+}
+
+/// Returns the runtime which async functions called outside of a runtime are
+/// run on.
+#[cfg(feature = "tokio")]
+fn runtime() -> &'static tokio::runtime::Runtime {
+  static RUNTIME: std::sync::OnceLock<tokio::runtime::Runtime> =
+    std::sync::OnceLock::new();
+
+  RUNTIME.get_or_init(|| {
+    tokio::runtime::Builder::new_multi_thread()
+      .enable_all()
+      .build()
+      .expect("failed to start the async runtime")
+  })
+}
+
+/// Blocks the current thread until `future` is ready.
+pub fn block_on<F>(future: F) -> F::Output
+where
+  F: Future,
+{
+  let mut future = pin!(future);
+  let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+  let mut cx = TaskContext::from_waker(&waker);
+
+  loop {
+    match future.as_mut().poll(&mut cx) {
+      Poll::Ready(x) => return x,
+      Poll::Pending => thread::park(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use core::{future::Future, pin::Pin, task::Poll};
+  use std::{sync::Arc, time::Duration};
+
+  use super::*;
+  use crate::{prelude::*, profile::Profiler};
+
+  /// Resolves after a delay, without blocking the thread which polls it.
+  fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+    let mut started = false;
+    let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+    core::future::poll_fn(move |cx| {
+      if done.load(std::sync::atomic::Ordering::Acquire) {
+        return Poll::Ready(());
+      }
+
+      if !started {
+        started = true;
+        let done = done.clone();
+        let waker = cx.waker().clone();
+
+        thread::spawn(move || {
+          thread::sleep(duration);
+          done.store(true, std::sync::atomic::Ordering::Release);
+          waker.wake();
+        });
+      }
+
+      Poll::Pending
+    })
+  }
+
+  fn module() -> Module {
+    Module::new(Symbol::from_ref("time")).with_async_func(
+      Symbol::from_ref("sleep"),
+      Arc::new(|mut context, expr| {
+        Box::pin(async move {
+          let ms = match context.stack_pop(&expr)?.kind {
+            ExprKind::Integer(x) => x as u64,
+            _ => 0,
+          };
+          sleep(Duration::from_millis(ms)).await;
+
+          context.stack_push(ExprKind::Boolean(true).into())?;
+          Ok(context)
+        }) as Pin<Box<_>>
+      }),
+    )
+  }
+
+  /// Sleeps with the timer of the Tokio runtime which polls it.
+  fn tokio_module() -> Module {
+    Module::new(Symbol::from_ref("time")).with_async_func(
+      Symbol::from_ref("sleep"),
+      Arc::new(|mut context, _| {
+        Box::pin(async move {
+          tokio::time::sleep(Duration::from_millis(10)).await;
+
+          context.stack_push(ExprKind::Boolean(true).into())?;
+          Ok(context)
+        }) as Pin<Box<_>>
+      }),
+    )
+  }
+
+  fn exprs(code: &str) -> Vec<Expr> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    parse(&mut lexer).unwrap()
+  }
+
+  #[test]
+  fn awaits_async_functions() {
+    let engine = Engine::new().with_module(module());
+    let context =
+      block_on(engine.run_async(Context::new(), exprs("1 10 time:sleep 2")))
+        .unwrap();
+
+    assert_eq!(
+      context.stack(),
+      &[
+        ExprKind::Integer(1).into(),
+        ExprKind::Boolean(true).into(),
+        ExprKind::Integer(2).into(),
+      ]
+    );
+  }
+
+  #[test]
+  fn blocks_on_async_functions_when_run_synchronously() {
+    let engine = Engine::new().with_module(module());
+    let context = engine
+      .run(Context::new(), exprs("'(fn 10 time:sleep) 'nap def nap"))
+      .unwrap();
+
+    assert_eq!(context.stack(), &[ExprKind::Boolean(true).into()]);
+  }
+
+  #[test]
+  fn fails_on_nested_async_calls_on_a_current_thread_runtime() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+      .enable_time()
+      .build()
+      .unwrap();
+    let engine = Engine::new().with_module(tokio_module());
+
+    let context = runtime
+      .block_on(engine.run_async(Context::new(), exprs("time:sleep")))
+      .unwrap();
+    assert_eq!(context.stack(), &[ExprKind::Boolean(true).into()]);
+
+    // Blocking on the sleep would park the only thread which drives its
+    // timer, so this would never finish.
+    let error = runtime
+      .block_on(
+        engine
+          .run_async(Context::new(), exprs("'(fn time:sleep) 'nap def nap")),
+      )
+      .unwrap_err();
+    assert_eq!(error.reason, RunErrorReason::NestedAsyncCall);
+  }
+
+  #[test]
+  fn traces_profiles_and_counts_async_calls() {
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let profiler = Profiler::new();
+
+    let tracer_events = events.clone();
+    let engine = Engine::new()
+      .with_module(module())
+      .with_profiler(Some(profiler.clone()))
+      .with_tracer(Some(Arc::new(move |event| {
+        tracer_events
+          .lock()
+          .unwrap()
+          .push((event.expr.to_string(), event.after.len()));
+      })));
+
+    let (result, stats) = block_on(
+      engine.run_async_with_stats(Context::new(), exprs("1 time:sleep")),
+    );
+    result.unwrap();
+
+    assert_eq!(
+      *events.lock().unwrap(),
+      vec![("1".into(), 1), ("time:sleep".into(), 1)]
+    );
+    assert_eq!(stats.exprs_evaluated, 2);
+
+    let profile = profiler.profile();
+    let entry = profile
+      .entries
+      .iter()
+      .find(|x| x.name == Symbol::from_ref("time:sleep"))
+      .unwrap();
+    assert_eq!(entry.calls, 1);
+  }
+
+  #[cfg(feature = "tokio")]
+  #[test]
+  fn blocks_in_place_on_a_multi_thread_runtime() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+      .worker_threads(1)
+      .enable_time()
+      .build()
+      .unwrap();
+    let engine = Engine::new().with_module(tokio_module());
+
+    let context = runtime
+      .block_on(
+        engine
+          .run_async(Context::new(), exprs("'(fn time:sleep) 'nap def nap")),
+      )
+      .unwrap();
+    assert_eq!(context.stack(), &[ExprKind::Boolean(true).into()]);
+
+    // Within a task on a worker thread, too.
+    let context = runtime
+      .block_on(runtime.spawn({
+        let engine = engine.clone();
+        async move {
+          engine
+            .run_async(Context::new(), exprs("'(fn time:sleep) 'nap def nap"))
+            .await
+        }
+      }))
+      .unwrap()
+      .unwrap();
+    assert_eq!(context.stack(), &[ExprKind::Boolean(true).into()]);
+  }
+
+  #[cfg(feature = "tokio")]
+  #[test]
+  fn runs_tokio_functions_outside_of_a_runtime() {
+    let engine = Engine::new().with_module(tokio_module());
+    let context = engine
+      .run(Context::new(), exprs("'(fn time:sleep) 'nap def nap"))
+      .unwrap();
+
+    assert_eq!(context.stack(), &[ExprKind::Boolean(true).into()]);
+  }
+}
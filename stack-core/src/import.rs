@@ -0,0 +1,409 @@
+//! Resolution and bookkeeping for the `import` intrinsic.
+//!
+//! An import path is resolved relative to the directory of the file doing the
+//! import, then against each search path of the [`Engine`] in order. A path
+//! of the form `package:path` is instead resolved within the directory of that
+//! package. Each file is only run once per [`Context`]; importing it again
+//! does nothing.
+//!
+//! A file can declare itself a module with `"name" module`, after which its
+//! definitions are kept out of the importer's scope. Only the names passed to
+//! `export` (or every definition, if nothing is exported) are made available
+//! to the importer, prefixed with the module name as `name:func`.
+//!
+//! A path which is the name of a module the [`Engine`] can load, as in
+//! `"str" import`, loads that module instead of a file. Its functions can only
+//! be called once it's been imported.
+//!
+//! [`Engine`]: crate::engine::Engine
+//! [`Context`]: crate::context::Context
+
+use std::{
+  collections::{HashMap, HashSet},
+  env,
+  path::{Path, PathBuf},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::symbol::Symbol;
+
+/// The environment variable holding extra search paths, separated like the
+/// `PATH` variable of the platform.
+pub const SEARCH_PATH_VAR: &str = "STACK_PATH";
+
+/// Returns the search paths from the [`SEARCH_PATH_VAR`] environment variable.
+pub fn env_search_paths() -> Vec<PathBuf> {
+  env::var_os(SEARCH_PATH_VAR)
+    .map(|x| env::split_paths(&x).collect())
+    .unwrap_or_default()
+}
+
+/// Resolves an import `path` to a canonical file path.
+///
+/// `from` is the path of the file doing the import, if there is one. Returns
+/// [`None`] if no file could be found.
+pub fn resolve(
+  path: &str,
+  from: Option<&Path>,
+  search_paths: &[PathBuf],
+  packages: &HashMap<String, PathBuf>,
+) -> Option<PathBuf> {
+  if let Some(dir) = path
+    .split_once(':')
+    .and_then(|(package, path)| Some((packages.get(package)?, path)))
+    .map(|(dir, path)| dir.join(path))
+  {
+    return dir.canonicalize().ok().filter(|path| path.is_file());
+  }
+
+  let path = Path::new(path);
+
+  if path.is_absolute() {
+    return path.canonicalize().ok();
+  }
+
+  let from = from
+    .and_then(Path::parent)
+    .map(Path::to_path_buf)
+    .unwrap_or_default();
+
+  core::iter::once(&from)
+    .chain(search_paths)
+    .map(|dir| dir.join(path))
+    .find(|path| path.is_file())
+    .and_then(|path| path.canonicalize().ok())
+}
+
+/// The files which have been imported into a [`Context`].
+///
+/// [`Context`]: crate::context::Context
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Imports {
+  imported: HashSet<PathBuf>,
+  #[cfg_attr(feature = "serde", serde(default))]
+  modules: HashSet<Symbol>,
+  #[cfg_attr(feature = "serde", serde(skip))]
+  importing: Vec<ImportFrame>,
+}
+
+/// A file which is still being imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportFrame {
+  pub path: PathBuf,
+  /// The name the file declared with `module`, if any.
+  pub module: Option<Symbol>,
+  /// The names passed to `export`.
+  pub exports: Vec<Symbol>,
+}
+
+impl Imports {
+  /// Returns whether `path` has finished being imported.
+  #[inline]
+  pub fn is_imported(&self, path: &Path) -> bool {
+    self.imported.contains(path)
+  }
+
+  /// Returns whether `path` is still being imported, i.e., importing it again
+  /// would be circular.
+  #[inline]
+  pub fn is_importing(&self, path: &Path) -> bool {
+    self.importing.iter().any(|x| x.path == path)
+  }
+
+  /// Returns the files which have finished being imported.
+  #[inline]
+  pub fn imported(&self) -> impl Iterator<Item = &Path> {
+    self.imported.iter().map(PathBuf::as_path)
+  }
+
+  /// Returns whether the loadable module `name` has been imported.
+  #[inline]
+  pub fn is_module_imported(&self, name: Symbol) -> bool {
+    self.modules.contains(&name)
+  }
+
+  #[inline]
+  pub(crate) fn import_module(&mut self, name: Symbol) {
+    self.modules.insert(name);
+  }
+
+  /// Returns the innermost file which is still being imported.
+  #[inline]
+  pub fn current(&self) -> Option<&ImportFrame> {
+    self.importing.last()
+  }
+
+  #[inline]
+  pub(crate) fn current_mut(&mut self) -> Option<&mut ImportFrame> {
+    self.importing.last_mut()
+  }
+
+  #[inline]
+  pub(crate) fn start(&mut self, path: PathBuf) {
+    self.importing.push(ImportFrame {
+      path,
+      module: None,
+      exports: Vec::new(),
+    });
+  }
+
+  /// Finishes the innermost import, marking it as imported if it succeeded.
+  #[inline]
+  pub(crate) fn finish(&mut self, succeeded: bool) -> Option<ImportFrame> {
+    let frame = self.importing.pop()?;
+
+    if succeeded {
+      self.imported.insert(frame.path.clone());
+    }
+
+    Some(frame)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    fs,
+    sync::{
+      atomic::{AtomicUsize, Ordering},
+      Arc,
+    },
+  };
+
+  use super::*;
+  use crate::prelude::*;
+
+  fn run_file(engine: &Engine, path: &Path) -> Result<Context, RunError> {
+    let source = Source::from_path(path).unwrap();
+    let mut lexer = Lexer::new(source.clone());
+    let exprs = parse(&mut lexer).unwrap();
+
+    let mut context = Context::new();
+    context.add_source(source);
+
+    engine.run(context, exprs)
+  }
+
+  #[test]
+  fn resolves_relative_and_search_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let lib = dir.path().join("lib");
+    fs::create_dir(&lib).unwrap();
+
+    fs::write(dir.path().join("a.stack"), "").unwrap();
+    fs::write(lib.join("b.stack"), "").unwrap();
+
+    let from = dir.path().join("main.stack");
+    let search_paths = [lib.clone()];
+    let packages = HashMap::new();
+
+    assert_eq!(
+      resolve("a.stack", Some(&from), &search_paths, &packages),
+      dir.path().join("a.stack").canonicalize().ok()
+    );
+    assert_eq!(
+      resolve("b.stack", Some(&from), &search_paths, &packages),
+      lib.join("b.stack").canonicalize().ok()
+    );
+    assert_eq!(
+      resolve("c.stack", Some(&from), &search_paths, &packages),
+      None
+    );
+  }
+
+  #[test]
+  fn resolves_package_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let package = dir.path().join("json");
+    fs::create_dir(&package).unwrap();
+    fs::write(package.join("lib.stack"), "").unwrap();
+
+    let packages = HashMap::from_iter([("json".into(), package.clone())]);
+
+    assert_eq!(
+      resolve("json:lib.stack", None, &[], &packages),
+      package.join("lib.stack").canonicalize().ok()
+    );
+    assert_eq!(resolve("json:missing.stack", None, &[], &packages), None);
+    assert_eq!(resolve("yaml:lib.stack", None, &[], &packages), None);
+  }
+
+  #[test]
+  fn imports_files_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let lib = dir.path().join("lib");
+    fs::create_dir(&lib).unwrap();
+
+    fs::write(lib.join("one.stack"), "1 'one def 0").unwrap();
+    fs::write(
+      dir.path().join("main.stack"),
+      "\"one.stack\" import \"one.stack\" import one",
+    )
+    .unwrap();
+
+    let engine = Engine::new().with_search_paths(vec![lib.clone()]);
+    let context = run_file(&engine, &dir.path().join("main.stack")).unwrap();
+
+    assert_eq!(
+      context.stack(),
+      &[ExprKind::Integer(0).into(), ExprKind::Integer(1).into()]
+    );
+    assert!(context
+      .imports()
+      .is_imported(&lib.join("one.stack").canonicalize().unwrap()));
+    assert_eq!(context.sources().count(), 2);
+  }
+
+  #[test]
+  fn errors_on_circular_imports() {
+    let dir = tempfile::tempdir().unwrap();
+
+    fs::write(dir.path().join("a.stack"), "\"b.stack\" import").unwrap();
+    fs::write(dir.path().join("b.stack"), "\"a.stack\" import").unwrap();
+
+    let engine = Engine::new();
+    let err = run_file(&engine, &dir.path().join("a.stack")).unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::CircularImport);
+  }
+
+  #[test]
+  fn errors_on_forbidden_imports() {
+    let dir = tempfile::tempdir().unwrap();
+    let secret = dir.path().join("secret");
+    fs::create_dir(&secret).unwrap();
+
+    fs::write(secret.join("s.stack"), "1").unwrap();
+    fs::write(dir.path().join("a.stack"), "\"secret/s.stack\" import").unwrap();
+
+    let secret = secret.canonicalize().unwrap();
+    let engine = Engine::new().with_import_guard(Some(std::sync::Arc::new(
+      move |path: &Path| !path.starts_with(&secret),
+    )));
+    let err = run_file(&engine, &dir.path().join("a.stack")).unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::ForbiddenImport);
+    assert!(err.context.stack().is_empty());
+  }
+
+  #[test]
+  fn errors_on_missing_imports() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.stack"), "\"b.stack\" import").unwrap();
+
+    let engine = Engine::new();
+    let err = run_file(&engine, &dir.path().join("a.stack")).unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::ImportNotFound);
+  }
+
+  #[test]
+  fn namespaces_module_exports() {
+    let dir = tempfile::tempdir().unwrap();
+
+    fs::write(
+      dir.path().join("math.stack"),
+      "\"math\" module 'double export \
+       2 'two def \
+       '(fn two *) 'double def",
+    )
+    .unwrap();
+    fs::write(
+      dir.path().join("main.stack"),
+      "\"math.stack\" import 4 math:double",
+    )
+    .unwrap();
+
+    let engine = Engine::new();
+    let context = run_file(&engine, &dir.path().join("main.stack")).unwrap();
+
+    assert_eq!(context.stack(), &[ExprKind::Integer(8).into()]);
+    assert_eq!(context.scope_item(Symbol::from_ref("two")), None);
+    assert_eq!(context.scope_item(Symbol::from_ref("double")), None);
+    assert_eq!(context.scope_item(Symbol::from_ref("math:two")), None);
+  }
+
+  #[test]
+  fn exports_every_definition_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+
+    fs::write(
+      dir.path().join("consts.stack"),
+      "'consts module 1 'one def 2 'two def",
+    )
+    .unwrap();
+    fs::write(
+      dir.path().join("main.stack"),
+      "1 'one def \"consts.stack\" import consts:two one",
+    )
+    .unwrap();
+
+    let engine = Engine::new();
+    let context = run_file(&engine, &dir.path().join("main.stack")).unwrap();
+
+    assert_eq!(
+      context.stack(),
+      &[ExprKind::Integer(2).into(), ExprKind::Integer(1).into()]
+    );
+    assert_eq!(
+      context.scope_item(Symbol::from_ref("consts:one")),
+      Some(ExprKind::Integer(1).into())
+    );
+  }
+
+  #[test]
+  fn errors_on_missing_exports() {
+    let dir = tempfile::tempdir().unwrap();
+
+    fs::write(dir.path().join("a.stack"), "'a module 'missing export").unwrap();
+    fs::write(dir.path().join("main.stack"), "\"a.stack\" import").unwrap();
+
+    let engine = Engine::new();
+    let err = run_file(&engine, &dir.path().join("main.stack")).unwrap_err();
+
+    assert_eq!(err.reason, RunErrorReason::InvalidModule);
+  }
+
+  #[test]
+  fn loads_modules_once_imported() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+      dir.path().join("main.stack"),
+      "\"consts\" import \"consts\" import consts:one",
+    )
+    .unwrap();
+    fs::write(dir.path().join("missing.stack"), "consts:one").unwrap();
+
+    let loads = Arc::new(AtomicUsize::new(0));
+    let counter = loads.clone();
+
+    let engine = Engine::new().with_module_loader(
+      Symbol::from_ref("consts"),
+      Arc::new(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+
+        Module::new(Symbol::from_ref("consts")).with_func(
+          Symbol::from_ref("one"),
+          Arc::new(|_, mut context, _| {
+            context.stack_push(ExprKind::Integer(1).into())?;
+            Ok(context)
+          }),
+        )
+      }),
+    );
+
+    let err = run_file(&engine, &dir.path().join("missing.stack")).unwrap_err();
+    assert_eq!(err.reason, RunErrorReason::UnknownCall);
+    assert_eq!(loads.load(Ordering::SeqCst), 0);
+
+    let context = run_file(&engine, &dir.path().join("main.stack")).unwrap();
+    assert_eq!(context.stack(), &[ExprKind::Integer(1).into()]);
+    assert!(context
+      .imports()
+      .is_module_imported(Symbol::from_ref("consts")));
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+  }
+}
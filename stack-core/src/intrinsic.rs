@@ -1,22 +1,45 @@
-use core::{fmt, num::FpCategory, str::FromStr};
-use std::collections::HashMap;
+use core::{cmp::Ordering, fmt, num::FpCategory, str::FromStr};
+use std::{
+  collections::{HashMap, HashSet},
+  path::Path,
+  sync::OnceLock,
+};
 
 use compact_str::ToCompactString;
+use indexmap::{IndexMap, IndexSet};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
   context::Context,
-  expr::{Expr, ExprKind},
+  expr::{Expr, ExprInfo, ExprKind, MapKey},
+  float::FloatFormat,
+  format_spec::FormatSpec,
+  import,
   journal::JournalOp,
   lexer::Lexer,
+  pattern::{self, Bindings},
   prelude::{parse, Engine, RunError, RunErrorReason},
+  pretty::{self, PrettyOptions},
+  scope::Meta,
+  seq::Seq,
   source::Source,
   symbol::Symbol,
+  visit::{walk_expr, ExprVisitor},
+  watchpoint::{WatchEvent, WatchKind},
+};
+#[cfg(feature = "sync")]
+use crate::{
+  coroutine::{self, Generator, YieldError},
+  foreign::Foreign,
 };
 
 macro_rules! intrinsics {
   ($($ident:ident => ($s:literal, $b:literal)),* $(,)?) => {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[non_exhaustive]
     pub enum Intrinsic {
       $($ident),*
     }
@@ -43,10 +66,30 @@ macro_rules! intrinsics {
       }
     }
 
+    impl Intrinsic {
+      /// Returns the [`Intrinsic`] named by `symbol`.
+      ///
+      /// This is quicker than parsing the name, since symbols are compared by
+      /// their interned pointer, which matters as every call is looked up.
+      pub fn from_symbol(symbol: Symbol) -> Option<Self> {
+        static INTRINSICS: OnceLock<HashMap<Symbol, Intrinsic>> =
+          OnceLock::new();
+
+        INTRINSICS
+          .get_or_init(|| {
+            HashMap::from_iter([
+              $((Symbol::from_ref($s), Self::$ident)),*
+            ])
+          })
+          .get(&symbol)
+          .copied()
+      }
+    }
+
     impl FromStr for Intrinsic {
       type Err = ParseIntrinsicError;
 
-      fn from_str(s: &str) -> Result<Self, Self::Err> {
+      fn from_str(s: &str) -> Result<Self, ParseIntrinsicError> {
         match s {
           $($s => Ok(Self::$ident),)*
           _ => Err(ParseIntrinsicError),
@@ -81,6 +124,7 @@ intrinsics! {
   Not => ("not", false),
 
   Assert => ("assert", false),
+  AssertEq => ("assert-eq", false),
 
   Drop => ("drop", false),
   Dupe => ("dupe", false),
@@ -90,40 +134,112 @@ intrinsics! {
   Len => ("len", false),
   Nth => ("nth", false),
   Split => ("split", false),
+  Slice => ("slice", false),
   Concat => ("concat", false),
   Push => ("push", true),
   Pop => ("pop", false),
 
+  Chars => ("chars", false),
+  FromChars => ("from-chars", false),
+  Codepoint => ("codepoint", false),
+  FromCodepoint => ("from-codepoint", false),
+
+  Encode => ("encode", false),
+  Decode => ("decode", false),
+
   Insert => ("insert", true),
   Prop => ("prop", false),
   Has => ("has", false),
   Remove => ("remove", false),
   Keys => ("keys", false),
   Values => ("values", false),
+  Entries => ("entries", false),
+
+  MapNew => ("map-new", false),
+  Contains => ("contains", false),
+
+  SetNew => ("set-new", false),
+  Union => ("union", false),
+  Intersect => ("intersect", false),
+  Difference => ("difference", false),
+  ToList => ("to-list", false),
+
+  Range => ("range", false),
+  RangeBy => ("range-by", false),
+  Map => ("map", false),
+  Filter => ("filter", false),
+  Take => ("take", false),
+  Zip => ("zip", false),
+  Collect => ("collect", false),
+
+  Each => ("each", false),
+  Fold => ("fold", false),
+  Any => ("any", false),
+  All => ("all", false),
+  Reverse => ("reverse", false),
+
+  Sort => ("sort", false),
+  SortBy => ("sort-by", false),
+  Min => ("min", false),
+  Max => ("max", false),
+  MinBy => ("min-by", false),
+  MaxBy => ("max-by", false),
 
   Cast => ("cast", false),
   TypeOf => ("typeof", false),
   Lazy => ("lazy", false),
 
   If => ("if", false),
+  While => ("while", false),
+  Until => ("until", false),
+  Times => ("times", false),
+  Loop => ("loop", false),
+  Break => ("break", false),
+  Continue => ("continue", false),
   Halt => ("halt", false),
+  Exit => ("exit", false),
+
+  Generator => ("generator", false),
+  Yield => ("yield", false),
+  Resume => ("resume", false),
+  IsDone => ("done?", false),
 
   Call => ("call", false),
 
   Let => ("let", true),
   Def => ("def", true),
+  DefTest => ("def-test", true),
   Set => ("set", true),
   Get => ("get", false),
 
   Debug => ("debug", false),
+  Breakpoint => ("breakpoint", false),
   // TODO: These will become STD module items.
   Print => ("print", false),
+  Fmt => ("fmt", false),
   Pretty => ("pretty", false),
+  Table => ("table", false),
+  SetPrecision => ("set-precision", false),
   Recur => ("recur", false),
 
   OrElse => ("orelse", false),
 
+  Error => ("error", false),
+  Throw => ("throw", false),
+  Try => ("try", false),
+
+  Ok => ("ok", false),
+  Err => ("err", false),
+  Unwrap => ("unwrap", false),
+  UnwrapOr => ("unwrap-or", false),
+  MapOk => ("map-ok", false),
+  AndThen => ("and-then", false),
+
+  Match => ("match", false),
+
   Import => ("import", false),
+  Module => ("module", false),
+  Export => ("export", false),
 }
 
 impl Intrinsic {
@@ -177,33 +293,7 @@ impl Intrinsic {
         Ok(context)
       }
       // MARK: Div
-      Self::Div => {
-        let rhs = context.stack_pop(&expr)?;
-        let lhs = context.stack_pop(&expr)?;
-
-        let kind = match lhs.kind.clone() / rhs.kind.clone() {
-          Ok(res) => res,
-          Err(_) => ExprKind::Nil,
-        };
-
-        context.stack_push(kind.into())?;
-
-        Ok(context)
-      }
-      // MARK: Rem
-      Self::Rem => {
-        let rhs = context.stack_pop(&expr)?;
-        let lhs = context.stack_pop(&expr)?;
-
-        let kind = match lhs.kind.clone() % rhs.kind.clone() {
-          Ok(res) => res,
-          Err(_) => ExprKind::Nil,
-        };
-
-        context.stack_push(kind.into())?;
-
-        Ok(context)
-      }
+      Self::Div | Self::Rem => run_division(self, context, expr),
 
       // MARK: Eq
       Self::Eq => {
@@ -317,8 +407,28 @@ impl Intrinsic {
         } else {
           Err(RunError {
             reason: RunErrorReason::AssertionFailed,
-            context,
-            expr: message.kind.into(),
+            context: Box::new(context),
+            expr: Box::new(message.kind.into()),
+          })
+        }
+      }
+
+      // MARK: AssertEq
+      Self::AssertEq => {
+        let expected = context.stack_pop(&expr)?;
+        let actual = context.stack_pop(&expr)?;
+
+        if actual.kind == expected.kind {
+          Ok(context)
+        } else {
+          // Both are left on the stack, so that they can be reported.
+          context.stack_push(actual)?;
+          context.stack_push(expected)?;
+
+          Err(RunError {
+            reason: RunErrorReason::AssertionFailed,
+            context: Box::new(context),
+            expr: Box::new(expr),
           })
         }
       }
@@ -332,8 +442,8 @@ impl Intrinsic {
       Self::Dupe => {
         let item = context.stack().last().cloned().ok_or_else(|| RunError {
           reason: RunErrorReason::StackUnderflow,
-          context: context.clone(),
-          expr,
+          context: Box::new(context.clone()),
+          expr: Box::new(expr),
         })?;
 
         context.stack_push(item)?;
@@ -349,8 +459,8 @@ impl Intrinsic {
         } else {
           Err(RunError {
             reason: RunErrorReason::StackUnderflow,
-            context,
-            expr,
+            context: Box::new(context),
+            expr: Box::new(expr),
           })
         }
       }
@@ -366,8 +476,8 @@ impl Intrinsic {
         } else {
           Err(RunError {
             reason: RunErrorReason::StackUnderflow,
-            context,
-            expr,
+            context: Box::new(context),
+            expr: Box::new(expr),
           })
         }
       }
@@ -390,6 +500,18 @@ impl Intrinsic {
             debug_assert!(x.len() <= i64::MAX as usize);
             ExprKind::Integer(x.len() as i64)
           }
+          ExprKind::Bytes(ref x) => {
+            debug_assert!(x.len() <= i64::MAX as usize);
+            ExprKind::Integer(x.len() as i64)
+          }
+          ExprKind::Map(ref x) => {
+            debug_assert!(x.len() <= i64::MAX as usize);
+            ExprKind::Integer(x.len() as i64)
+          }
+          ExprKind::Set(ref x) => {
+            debug_assert!(x.len() <= i64::MAX as usize);
+            ExprKind::Integer(x.len() as i64)
+          }
           _ => ExprKind::Nil,
         };
 
@@ -415,6 +537,10 @@ impl Intrinsic {
             .nth(i as usize)
             .map(|x| ExprKind::String(x.into()))
             .unwrap_or(ExprKind::Nil),
+          (ExprKind::Bytes(x), ExprKind::Integer(i)) if i >= 0 => x
+            .get(i as usize)
+            .map(|x| ExprKind::Integer(*x as i64))
+            .unwrap_or(ExprKind::Nil),
           _ => ExprKind::Nil,
         };
 
@@ -425,49 +551,9 @@ impl Intrinsic {
         Ok(context)
       }
       // MARK: Split
-      Self::Split => {
-        let index = context.stack_pop(&expr)?;
-        let item = context.stack_pop(&expr)?;
-
-        match (item.kind, index.kind) {
-          (ExprKind::List(mut x), ExprKind::Integer(i)) if i >= 0 => {
-            if (i as usize) < x.len() {
-              let rest = x.split_off(i as usize);
-
-              context.stack_push(ExprKind::List(x).into())?;
-
-              context.stack_push(ExprKind::List(rest).into())?;
-            } else {
-              context.stack_push(ExprKind::List(x).into())?;
-
-              context.stack_push(ExprKind::Nil.into())?;
-            }
-          }
-          (ExprKind::String(mut x), ExprKind::Integer(i)) if i >= 0 => {
-            match x.as_str().grapheme_indices(true).nth(i as usize) {
-              Some((i, _)) => {
-                let rest = x.split_off(i);
-
-                context.stack_push(ExprKind::String(x).into())?;
-
-                context.stack_push(ExprKind::String(rest).into())?;
-              }
-              None => {
-                context.stack_push(ExprKind::String(x).into())?;
-
-                context.stack_push(ExprKind::Nil.into())?;
-              }
-            }
-          }
-          _ => {
-            context.stack_push(ExprKind::Nil.into())?;
-
-            context.stack_push(ExprKind::Nil.into())?;
-          }
-        }
-
-        Ok(context)
-      }
+      Self::Split => run_split(context, expr),
+      // MARK: Slice
+      Self::Slice => run_slice(context, expr),
       // MARK: Concat
       Self::Concat => {
         let rhs = context.stack_pop(&expr)?;
@@ -482,6 +568,10 @@ impl Intrinsic {
             lhs.push_str(&rhs);
             ExprKind::String(lhs)
           }
+          (ExprKind::Bytes(mut lhs), ExprKind::Bytes(rhs)) => {
+            lhs.extend(rhs);
+            ExprKind::Bytes(lhs)
+          }
           _ => ExprKind::Nil,
         };
 
@@ -490,31 +580,66 @@ impl Intrinsic {
         Ok(context)
       }
       // MARK: Push
-      Self::Push => {
-        let list = context.stack_pop(&expr)?;
+      Self::Push => run_push(context, expr),
+      // MARK: Pop
+      Self::Pop => run_pop(context, expr),
+
+      // MARK: Records
+      Self::Insert
+      | Self::Prop
+      | Self::Has
+      | Self::Remove
+      | Self::Keys
+      | Self::Values
+      | Self::Entries => run_record(self, context, expr),
+      // MARK: MapNew
+      Self::MapNew => {
+        context.stack_push(ExprKind::Map(IndexMap::new()).into())?;
+
+        Ok(context)
+      }
+      // MARK: Contains
+      Self::Contains => run_contains(context, expr),
+      // MARK: Sets
+      Self::SetNew
+      | Self::Union
+      | Self::Intersect
+      | Self::Difference
+      | Self::ToList => run_set(self, context, expr),
+      // MARK: Seqs
+      Self::Range
+      | Self::RangeBy
+      | Self::Map
+      | Self::Filter
+      | Self::Take
+      | Self::Zip
+      | Self::Collect => run_seq(self, engine, context, expr),
+      // MARK: Iteration
+      Self::Each | Self::Fold | Self::Any | Self::All => {
+        run_iter(self, engine, context, expr)
+      }
+      // MARK: Sorting
+      Self::Sort
+      | Self::SortBy
+      | Self::Min
+      | Self::Max
+      | Self::MinBy
+      | Self::MaxBy => run_sort(self, engine, context, expr),
+      // MARK: Reverse
+      Self::Reverse => {
         let item = context.stack_pop(&expr)?;
 
-        let kind = match (list.kind.clone(), item.kind.clone()) {
-          (ExprKind::List(mut x), i) => {
-            x.push(Expr {
-              kind: i,
-              info: item.info.clone(),
-            });
+        let kind = match item.kind {
+          ExprKind::List(mut x) => {
+            x.reverse();
             ExprKind::List(x)
           }
-          (ExprKind::String(mut x), ExprKind::String(s)) => {
-            x.push_str(&s);
-            ExprKind::String(x)
+          ExprKind::String(x) => {
+            ExprKind::String(x.as_str().graphemes(true).rev().collect())
           }
-          (ExprKind::String(mut x), ExprKind::Integer(c))
-            if c >= 0 && c <= u32::MAX as i64 =>
-          {
-            if let Some(c) = char::from_u32(c as u32) {
-              x.push(c);
-              ExprKind::String(x)
-            } else {
-              ExprKind::Nil
-            }
+          ExprKind::Bytes(mut x) => {
+            x.reverse();
+            ExprKind::Bytes(x)
           }
           _ => ExprKind::Nil,
         };
@@ -523,255 +648,17 @@ impl Intrinsic {
 
         Ok(context)
       }
-      // MARK: Pop
-      Self::Pop => {
-        let list = context.stack_pop(&expr)?;
-
-        match list.kind.clone() {
-          ExprKind::List(mut x) => {
-            let e = x.pop().unwrap_or(ExprKind::Nil.into());
-
-            context.stack_push(ExprKind::List(x).into())?;
-            context.stack_push(e)?;
-          }
-          ExprKind::String(mut x) => {
-            let e = x
-              .pop()
-              .map(|e| ExprKind::String(e.to_compact_string()).into())
-              .unwrap_or(ExprKind::Nil.into());
-
-            context.stack_push(ExprKind::String(x).into())?;
-            context.stack_push(e)?;
-          }
-          _ => {
-            context.stack_push(list.clone())?;
-            context.stack_push(ExprKind::Nil.into())?;
-          }
-        }
-
-        Ok(context)
-      }
-
-      // MARK: Insert
-      Self::Insert => {
-        let record = context.stack_pop(&expr)?;
-        let name = context.stack_pop(&expr)?;
-        let value = context.stack_pop(&expr)?;
-
-        match record.kind {
-          ExprKind::Record(ref record) => {
-            let symbol: Symbol = name.kind.into();
-
-            let mut new_record = record.clone();
-            new_record.insert(symbol, value);
-
-            context.stack_push(ExprKind::Record(new_record).into())?;
-
-            Ok(())
-          }
-          _ => context.stack_push(ExprKind::Nil.into()),
-        }
-        .map(|_| context)
-      }
-      // MARK: Prop
-      Self::Prop => {
-        let name = context.stack_pop(&expr)?;
-        let record = context.stack_pop(&expr)?;
-
-        match record.kind {
-          ExprKind::Record(ref r) => {
-            let symbol: Symbol = name.kind.into();
-
-            let result = r.get(&symbol).unwrap_or_else(|| &Expr {
-              info: None,
-              kind: ExprKind::Nil,
-            });
-
-            context.stack_push(record.clone())?;
-            context.stack_push(result.clone())?;
-
-            Ok(())
-          }
-          _ => context.stack_push(ExprKind::Nil.into()),
-        }
-        .map(|_| context)
-      }
-      // MARK: Has
-      Self::Has => {
-        let name = context.stack_pop(&expr)?;
-        let record = context.stack_pop(&expr)?;
-
-        match record.kind {
-          ExprKind::Record(ref r) => {
-            let symbol: Symbol = name.kind.into();
-
-            let result = r.contains_key(&symbol);
-
-            context.stack_push(record.clone())?;
-            context.stack_push(ExprKind::Boolean(result).into())?;
-
-            Ok(())
-          }
-          _ => context.stack_push(ExprKind::Nil.into()),
-        }
-        .map(|_| context)
-      }
-      // MARK: Remove
-      Self::Remove => {
-        let name = context.stack_pop(&expr)?;
-        let record = context.stack_pop(&expr)?;
-
-        match record.kind {
-          ExprKind::Record(ref record) => {
-            let symbol: Symbol = name.kind.into();
-
-            let mut new_record = record.clone();
-            new_record.remove(&symbol);
-
-            context.stack_push(ExprKind::Record(new_record).into())?;
-
-            Ok(())
-          }
-          _ => context.stack_push(ExprKind::Nil.into()),
-        }
-        .map(|_| context)
-      }
-      // MARK: Keys
-      Self::Keys => {
-        let record = context.stack_pop(&expr)?;
-
-        match record.kind {
-          ExprKind::Record(ref r) => {
-            let result = r
-              .keys()
-              .copied()
-              .map(|s| Expr {
-                info: None,
-                kind: ExprKind::Symbol(s),
-              })
-              .collect::<Vec<_>>();
-
-            context.stack_push(record.clone())?;
-            context.stack_push(ExprKind::List(result).into())?;
 
-            Ok(())
-          }
-          _ => context.stack_push(ExprKind::Nil.into()),
-        }
-        .map(|_| context)
+      // MARK: Chars
+      Self::Chars | Self::FromChars | Self::Codepoint | Self::FromCodepoint => {
+        run_char(self, context, expr)
       }
-      // MARK: Values
-      Self::Values => {
-        let record = context.stack_pop(&expr)?;
 
-        match record.kind {
-          ExprKind::Record(ref r) => {
-            let result = r.values().cloned().collect::<Vec<_>>();
-
-            context.stack_push(record.clone())?;
-            context.stack_push(ExprKind::List(result).into())?;
-
-            Ok(())
-          }
-          _ => context.stack_push(ExprKind::Nil.into()),
-        }
-        .map(|_| context)
-      }
+      // MARK: Encode
+      Self::Encode | Self::Decode => run_encoding(self, context, expr),
 
       // MARK: Cast
-      Self::Cast => {
-        let ty = context.stack_pop(&expr)?;
-        let item = context.stack_pop(&expr)?;
-
-        // TODO: Can these eager clones be removed?
-        let kind = match ty.kind {
-          ExprKind::String(ref x) => match (item.kind.clone(), x.as_str()) {
-            (ExprKind::Nil, "boolean") => ExprKind::Boolean(false),
-            (ExprKind::Boolean(x), "boolean") => ExprKind::Boolean(x),
-            (ExprKind::Integer(x), "boolean") => ExprKind::Boolean(x != 0),
-            (ExprKind::Float(x), "boolean") => ExprKind::Boolean(x == 0.0),
-
-            (ExprKind::Nil, "integer") => ExprKind::Integer(0),
-            (ExprKind::Boolean(x), "integer") => ExprKind::Integer(x as i64),
-            (ExprKind::Integer(x), "integer") => ExprKind::Integer(x),
-            (ExprKind::Float(x), "integer") => {
-              let x = x.floor();
-
-              match x.classify() {
-                FpCategory::Zero => ExprKind::Integer(0),
-                FpCategory::Normal
-                  if x >= i64::MIN as f64 && x <= i64::MAX as f64 =>
-                {
-                  ExprKind::Integer(x as i64)
-                }
-                _ => ExprKind::Nil,
-              }
-            }
-
-            (ExprKind::Nil, "float") => ExprKind::Float(0.0),
-            (ExprKind::Boolean(x), "float") => ExprKind::Float(x as i64 as f64),
-            (ExprKind::Integer(x), "float") => ExprKind::Float(x as f64),
-            (ExprKind::Float(x), "float") => ExprKind::Float(x),
-
-            (ExprKind::Nil, "string") => ExprKind::String("nil".into()),
-            (ExprKind::Boolean(x), "string") => {
-              ExprKind::String(x.to_compact_string())
-            }
-            (ExprKind::Integer(x), "string") => {
-              ExprKind::String(x.to_compact_string())
-            }
-            (ExprKind::Float(x), "string") => {
-              ExprKind::String(x.to_compact_string())
-            }
-            (ExprKind::String(x), "string") => ExprKind::String(x),
-            (ExprKind::Symbol(x), "string") => {
-              ExprKind::String(x.as_str().into())
-            }
-
-            // TODO: Make sure these are correct, because the logic is pretty
-            //       nuanced in terms of when to choose a Symbol or Intrinsic.
-            (ExprKind::Nil, "symbol") => ExprKind::Nil,
-            (ExprKind::Boolean(x), "symbol") => ExprKind::Boolean(x),
-            // TODO: Handle conversion into `fn` and `fn!`.
-            (ExprKind::String(x), "symbol") => ExprKind::Symbol(Symbol::new(x)),
-            (ExprKind::Symbol(x), "symbol") => ExprKind::Symbol(x),
-
-            (ExprKind::Record(x), "record") => ExprKind::Record(x),
-            (ExprKind::Record(x), "list") => {
-              let mut list: Vec<Expr> = Vec::new();
-              x.into_iter().for_each(|(key, value)| {
-                list.push(
-                  ExprKind::List(vec![ExprKind::Symbol(key).into(), value])
-                    .into(),
-                );
-              });
-
-              ExprKind::List(list)
-            }
-
-            (ExprKind::List(x), "record") => {
-              let mut record: HashMap<Symbol, Expr> = HashMap::new();
-              x.into_iter().for_each(|item| {
-                if let ExprKind::List(chunk) = item.kind {
-                  let key =
-                    Symbol::from_ref(chunk[0].kind.to_string().as_str());
-                  let value = &chunk[1];
-                  record.insert(key, value.clone());
-                }
-              });
-
-              ExprKind::Record(record)
-            }
-
-            _ => ExprKind::Nil,
-          },
-          _ => ExprKind::Nil,
-        };
-
-        context.stack_push(kind.into())?;
-
-        Ok(context)
-      }
+      Self::Cast => run_cast(context, expr),
       // MARK: TypeOf
       Self::TypeOf => {
         let expr = context.stack_pop(&expr)?;
@@ -802,12 +689,35 @@ impl Intrinsic {
 
         Ok(context)
       }
+      // MARK: Loops
+      Self::While | Self::Until | Self::Times | Self::Loop => {
+        run_loop(self, engine, context, expr)
+      }
+      Self::Break => Err(RunError {
+        reason: RunErrorReason::Break,
+        context: Box::new(context),
+        expr: Box::new(expr),
+      }),
+      Self::Continue => Err(RunError {
+        reason: RunErrorReason::Continue,
+        context: Box::new(context),
+        expr: Box::new(expr),
+      }),
+      // MARK: Match
+      Self::Match => run_match(engine, context, expr),
       // MARK: Halt
       Self::Halt => Err(RunError {
         reason: RunErrorReason::Halt,
-        context,
-        expr,
+        context: Box::new(context),
+        expr: Box::new(expr),
       }),
+      // MARK: Exit
+      Self::Exit => run_exit(context, expr),
+
+      // MARK: Generators
+      Self::Generator | Self::Yield | Self::Resume | Self::IsDone => {
+        run_generator(self, engine, context, expr)
+      }
 
       // MARK: Call
       Self::Call => {
@@ -820,57 +730,53 @@ impl Intrinsic {
         let names = context.stack_pop(&expr)?;
         let body = context.stack_pop(&expr)?;
 
-        match names.kind {
-          ExprKind::List(x) => {
-            let x_len = x.len();
-
-            let n = x.into_iter().try_fold(
-              Vec::with_capacity(x_len),
-              |mut v, x| match x.kind {
-                ExprKind::Symbol(x) => {
-                  v.push(x);
-                  Ok(v)
-                }
-                _ => Err(RunError {
-                  reason: RunErrorReason::InvalidLet,
-                  context: context.clone(),
-                  expr: expr.clone(),
-                }),
-              },
-            )?;
-
-            let mut scope = context.scope().duplicate();
-            for name in n.into_iter().rev() {
-              let expr = context.stack_pop(&expr)?;
-              scope.define(name, expr);
-            }
+        let ExprKind::List(patterns) = names.kind else {
+          return Err(RunError {
+            reason: RunErrorReason::InvalidLet,
+            context: Box::new(context),
+            expr: Box::new(expr),
+          });
+        };
 
-            if let Some(journal) = context.journal_mut() {
-              journal.commit();
-              journal.push_op(JournalOp::ScopelessFnStart(expr.info.clone()));
-            }
+        // Each name is either a symbol or a list or record pattern, which
+        // destructures its value.
+        let is_valid = patterns.iter().all(|x| {
+          matches!(
+            x.kind,
+            ExprKind::Symbol(_)
+              | ExprKind::List(_)
+              | ExprKind::Record(_)
+              | ExprKind::Underscore
+          ) && pattern::is_valid(x)
+        });
+        if !is_valid {
+          return Err(RunError {
+            reason: RunErrorReason::InvalidLet,
+            context: Box::new(context),
+            expr: Box::new(expr),
+          });
+        }
 
-            context.push_scope(scope);
-            context = engine.call_expr(context, body)?;
+        let mut values = Vec::with_capacity(patterns.len());
+        for _ in 0..patterns.len() {
+          values.push(context.stack_pop(&expr)?);
+        }
 
-            if context.journal().is_some() {
-              let scope = context.scope().clone();
-              let journal = context.journal_mut().as_mut().unwrap();
-              journal.commit();
-              journal
-                .push_op(JournalOp::FnEnd(expr.info.clone(), scope.into()));
+        let mut bindings = Vec::with_capacity(patterns.len());
+        for (pattern, value) in patterns.iter().zip(values.into_iter().rev()) {
+          match pattern::match_pattern(pattern, &value) {
+            Some(x) => bindings.extend(x),
+            None => {
+              return Err(RunError {
+                reason: RunErrorReason::PatternMismatch,
+                context: Box::new(context),
+                expr: Box::new(expr),
+              })
             }
-
-            context.pop_scope();
-
-            Ok(context)
           }
-          _ => Err(RunError {
-            reason: RunErrorReason::InvalidLet,
-            context: context.clone(),
-            expr: expr.clone(),
-          }),
         }
+
+        run_with_bindings(engine, context, &expr, bindings, body)
       }
 
       // MARK: Def
@@ -880,18 +786,64 @@ impl Intrinsic {
 
         match name.kind {
           ExprKind::Symbol(symbol) => {
+            let old = engine
+              .is_watched(&symbol)
+              .then(|| context.scope_item(symbol));
+
+            // The docs of `(def 'name ...)` are attached to the whole call.
+            let doc = [&value, &expr]
+              .into_iter()
+              .find_map(|x| x.info.as_ref().and_then(ExprInfo::doc));
+
             context.def_scope_item(symbol, value);
+            context
+              .scope_mut()
+              .update_meta(symbol, |x| *x = Meta::from_doc(doc));
+
+            if let Some(old) = old {
+              let event = WatchEvent {
+                name: symbol,
+                kind: WatchKind::Def,
+                old,
+                new: context.scope_item(symbol).unwrap_or(ExprKind::Nil.into()),
+                expr,
+              };
+              engine.watch(&context, event);
+            }
 
             Ok(context)
           }
           _ => Err(RunError {
             reason: RunErrorReason::InvalidDefinition,
-            context: context.clone(),
-            expr: expr.clone(),
+            context: Box::new(context.clone()),
+            expr: Box::new(expr.clone()),
           }),
         }
       }
 
+      // MARK: DefTest
+      Self::DefTest => {
+        let name = context.stack_pop(&expr)?;
+        let body = context.stack_pop(&expr)?;
+
+        let name = match name.kind {
+          ExprKind::Symbol(x) => x.as_str().to_owned(),
+          ExprKind::String(x) => x.to_string(),
+          _ => {
+            return Err(RunError {
+              reason: RunErrorReason::InvalidDefinition,
+              context: Box::new(context.clone()),
+              expr: Box::new(expr.clone()),
+            })
+          }
+        };
+
+        let name = format!("test:{name}");
+        context.def_scope_item(Symbol::from_ref(name.as_str()), body);
+
+        Ok(context)
+      }
+
       // MARK: Set
       Self::Set => {
         let name = context.stack_pop(&expr)?;
@@ -899,12 +851,29 @@ impl Intrinsic {
 
         match name.kind {
           ExprKind::Symbol(symbol) => {
-            context.set_scope_item(symbol, value).map(|_| context)
+            let old = engine
+              .is_watched(&symbol)
+              .then(|| context.scope_item(symbol));
+
+            context.set_scope_item(symbol, value)?;
+
+            if let Some(old) = old {
+              let event = WatchEvent {
+                name: symbol,
+                kind: WatchKind::Set,
+                old,
+                new: context.scope_item(symbol).unwrap_or(ExprKind::Nil.into()),
+                expr,
+              };
+              engine.watch(&context, event);
+            }
+
+            Ok(context)
           }
           _ => Err(RunError {
             reason: RunErrorReason::InvalidDefinition,
-            context: context.clone(),
-            expr: expr.clone(),
+            context: Box::new(context.clone()),
+            expr: Box::new(expr.clone()),
           }),
         }
       }
@@ -920,16 +889,16 @@ impl Intrinsic {
 
             context
               .stack_push(item.ok_or_else(|| RunError {
-                context: context.clone(),
-                expr,
+                context: Box::new(context.clone()),
+                expr: Box::new(expr),
                 reason: RunErrorReason::UnknownCall,
               })?)
               .map(|_| context)
           }
           _ => Err(RunError {
             reason: RunErrorReason::UnknownCall,
-            context: context.clone(),
-            expr: expr.clone(),
+            context: Box::new(context.clone()),
+            expr: Box::new(expr.clone()),
           }),
         }
       }
@@ -951,11 +920,43 @@ impl Intrinsic {
         }
         Ok(context)
       }
+      // MARK: Breakpoint
+      Self::Breakpoint => {
+        let name = context.stack_pop(&expr)?;
+
+        if let Some(breakpoint_hook) = engine.breakpoint_hook() {
+          let name = match name.kind {
+            ExprKind::Symbol(x) => Some(x),
+            ExprKind::String(ref x) => Some(Symbol::from_ref(x.as_str())),
+            _ => None,
+          };
+
+          breakpoint_hook(name, &context, &expr);
+        }
+
+        Ok(context)
+      }
       // MARK: Print
       Self::Print => {
         let val = context.stack_pop(&expr)?;
 
-        println!("{}", val);
+        println!("{}", context.float_format().display(&val.kind));
+
+        Ok(context)
+      }
+      // MARK: Fmt
+      Self::Fmt => {
+        let spec = context.stack_pop(&expr)?;
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match spec.kind {
+          ExprKind::String(ref x) => FormatSpec::parse(x)
+            .and_then(|spec| spec.format(&item.kind))
+            .map_or(ExprKind::Nil, |x| ExprKind::String(x.into())),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
 
         Ok(context)
       }
@@ -967,6 +968,10 @@ impl Intrinsic {
 
         Ok(context)
       }
+      // MARK: Table
+      Self::Table => run_table(context, expr),
+      // MARK: SetPrecision
+      Self::SetPrecision => run_set_precision(context, expr),
       // MARK: Recur
       // Functionality is implemented in [`Engine::call_fn`]
       Self::Recur => {
@@ -989,43 +994,108 @@ impl Intrinsic {
         Ok(context)
       }
 
+      // MARK: Error
+      Self::Error => {
+        let value = context.stack_pop(&expr)?;
+        context.stack_push(error_value(value))?;
+
+        Ok(context)
+      }
+
+      // MARK: Throw
+      Self::Throw => {
+        let value = context.stack_pop(&expr)?;
+
+        Err(RunError {
+          reason: RunErrorReason::Thrown,
+          context: Box::new(context),
+          expr: Box::new(Expr {
+            info: expr.info,
+            ..error_value(value)
+          }),
+        })
+      }
+
+      // MARK: Try
+      Self::Try => run_try(engine, context, expr),
+
+      // MARK: Results
+      Self::Ok
+      | Self::Err
+      | Self::Unwrap
+      | Self::UnwrapOr
+      | Self::MapOk
+      | Self::AndThen => run_result(self, engine, context, expr),
+
       // MARK: Import
-      Self::Import => {
-        let path = context.stack_pop(&expr)?;
+      Self::Import => run_import(engine, context, expr),
 
-        // Imports should trigger a new commit
-        if let Some(journal) = context.journal_mut() {
-          journal.commit();
-          journal.push_op(JournalOp::ScopelessFnStart(expr.info.clone()));
-        }
+      // MARK: Module
+      Self::Module => {
+        let name = context.stack_pop(&expr)?;
 
-        match path.kind {
-          ExprKind::String(str) => {
-            if let Ok(source) = Source::from_path(str.as_str()) {
-              context.add_source(source.clone());
-              let mut lexer = Lexer::new(source);
-              if let Ok(exprs) = parse(&mut lexer) {
-                let mut result = engine.run(context, exprs);
-
-                if let Ok(ref mut context) = result {
-                  if context.journal().is_some() {
-                    let scope = context.scope().clone();
-                    let journal = context.journal_mut().as_mut().unwrap();
-                    journal.commit();
-                    journal.push_op(JournalOp::FnEnd(
-                      expr.info.clone(),
-                      scope.into(),
-                    ));
-                  }
-                }
-
-                return result;
-              }
-            }
+        let name = match name.kind {
+          ExprKind::String(ref x) => Symbol::from_ref(x.as_str()),
+          ExprKind::Symbol(x) => x,
+          _ => {
+            return Err(RunError {
+              reason: RunErrorReason::InvalidModule,
+              context: Box::new(context),
+              expr: Box::new(expr),
+            })
           }
+        };
+
+        // Modules only matter to importers, so running the file directly
+        // defines everything as usual.
+        let Some(frame) = context.imports_mut().current_mut() else {
+          return Ok(context);
+        };
+
+        if frame.module.is_some() {
+          return Err(RunError {
+            reason: RunErrorReason::InvalidModule,
+            context: Box::new(context),
+            expr: Box::new(expr),
+          });
+        }
+
+        frame.module = Some(name);
+        let scope = context.scope().duplicate();
+        context.push_scope(scope);
+
+        Ok(context)
+      }
+
+      // MARK: Export
+      Self::Export => {
+        let names = context.stack_pop(&expr)?;
+
+        let names = match names.kind {
+          ExprKind::Symbol(x) => vec![x],
+          ExprKind::List(ref x) => x
+            .iter()
+            .map(|x| match x.kind {
+              ExprKind::Symbol(x) => Some(x),
+              _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| RunError {
+              reason: RunErrorReason::InvalidModule,
+              context: Box::new(context.clone()),
+              expr: Box::new(expr.clone()),
+            })?,
           _ => {
-            todo!()
+            return Err(RunError {
+              reason: RunErrorReason::InvalidModule,
+              context: Box::new(context),
+              expr: Box::new(expr),
+            })
           }
+        };
+
+        if let Some(frame) = context.imports_mut().current_mut() {
+          frame.exports.extend(names);
         }
 
         Ok(context)
@@ -1034,8 +1104,1763 @@ impl Intrinsic {
   }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct ParseIntrinsicError;
+/// Runs one of the intrinsics for converting between strings, chars and code
+/// points.
+fn run_char(
+  intrinsic: &Intrinsic,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let item = context.stack_pop(&expr)?;
+
+  let kind = match (intrinsic, item.kind) {
+    (Intrinsic::Chars, ExprKind::String(ref x)) => {
+      ExprKind::List(x.chars().map(|c| ExprKind::Char(c).into()).collect())
+    }
+    (Intrinsic::FromChars, ExprKind::List(ref x)) => x
+      .iter()
+      .map(|x| match x.kind {
+        ExprKind::Char(c) => Some(c),
+        _ => None,
+      })
+      .collect::<Option<String>>()
+      .map(|x| ExprKind::String(x.into()))
+      .unwrap_or(ExprKind::Nil),
+    (Intrinsic::Codepoint, ExprKind::Char(c)) => ExprKind::Integer(c as i64),
+    (Intrinsic::FromCodepoint, ExprKind::Integer(x)) => u32::try_from(x)
+      .ok()
+      .and_then(char::from_u32)
+      .map(ExprKind::Char)
+      .unwrap_or(ExprKind::Nil),
+    _ => ExprKind::Nil,
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+/// Casts the value on the stack to the type named by the string above it, or
+/// nil if it can't be.
+fn run_cast(mut context: Context, expr: Expr) -> Result<Context, RunError> {
+  let ty = context.stack_pop(&expr)?;
+  let item = context.stack_pop(&expr)?;
+
+  // TODO: Can these eager clones be removed?
+  let kind = match ty.kind {
+    ExprKind::String(ref x) => match (item.kind.clone(), x.as_str()) {
+      (ExprKind::Nil, "boolean") => ExprKind::Boolean(false),
+      (ExprKind::Boolean(x), "boolean") => ExprKind::Boolean(x),
+      (ExprKind::Integer(x), "boolean") => ExprKind::Boolean(x != 0),
+      (ExprKind::Float(x), "boolean") => ExprKind::Boolean(x == 0.0),
+
+      (ExprKind::Nil, "integer") => ExprKind::Integer(0),
+      (ExprKind::Boolean(x), "integer") => ExprKind::Integer(x as i64),
+      (ExprKind::Integer(x), "integer") => ExprKind::Integer(x),
+      (ExprKind::Float(x), "integer") => {
+        let x = x.floor();
+
+        match x.classify() {
+          FpCategory::Zero => ExprKind::Integer(0),
+          FpCategory::Normal
+            if x >= i64::MIN as f64 && x <= i64::MAX as f64 =>
+          {
+            ExprKind::Integer(x as i64)
+          }
+          _ => ExprKind::Nil,
+        }
+      }
+
+      (ExprKind::Nil, "float") => ExprKind::Float(0.0),
+      (ExprKind::Boolean(x), "float") => ExprKind::Float(x as i64 as f64),
+      (ExprKind::Integer(x), "float") => ExprKind::Float(x as f64),
+      (ExprKind::Float(x), "float") => ExprKind::Float(x),
+
+      (ExprKind::Nil, "string") => ExprKind::String("nil".into()),
+      (ExprKind::Boolean(x), "string") => {
+        ExprKind::String(x.to_compact_string())
+      }
+      (ExprKind::Integer(x), "string") => {
+        ExprKind::String(x.to_compact_string())
+      }
+      (ExprKind::Float(x), "string") => ExprKind::String(x.to_compact_string()),
+      (ExprKind::String(x), "string") => ExprKind::String(x),
+      (ExprKind::Char(x), "string") => ExprKind::String(x.to_compact_string()),
+
+      (ExprKind::Char(x), "char") => ExprKind::Char(x),
+      (ExprKind::Char(x), "integer") => ExprKind::Integer(x as i64),
+      (ExprKind::Integer(x), "char") => u32::try_from(x)
+        .ok()
+        .and_then(char::from_u32)
+        .map(ExprKind::Char)
+        .unwrap_or(ExprKind::Nil),
+      (ExprKind::String(x), "char") => {
+        let mut chars = x.chars();
+
+        match (chars.next(), chars.next()) {
+          (Some(c), None) => ExprKind::Char(c),
+          _ => ExprKind::Nil,
+        }
+      }
+      (ExprKind::Bytes(x), "string") => String::from_utf8(x)
+        .map(|x| ExprKind::String(x.into()))
+        .unwrap_or(ExprKind::Nil),
+      (ExprKind::Bytes(x), "bytes") => ExprKind::Bytes(x),
+      (ExprKind::String(x), "bytes") => ExprKind::Bytes(x.as_bytes().to_vec()),
+      (ExprKind::List(x), "bytes") => x
+        .iter()
+        .map(|x| match x.kind {
+          ExprKind::Integer(b) => u8::try_from(b).ok(),
+          _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(ExprKind::Bytes)
+        .unwrap_or(ExprKind::Nil),
+      (ExprKind::Set(x), "set") => ExprKind::Set(x),
+      (ExprKind::Set(x), "list") => ExprKind::List(
+        x.into_iter().map(|x| ExprKind::from(x).into()).collect(),
+      ),
+      (ExprKind::List(x), "set") => x
+        .iter()
+        .map(|x| MapKey::from_kind(&x.kind))
+        .collect::<Option<IndexSet<_>>>()
+        .map(ExprKind::Set)
+        .unwrap_or(ExprKind::Nil),
+      (ExprKind::Map(x), "map") => ExprKind::Map(x),
+      (ExprKind::Map(x), "list") => ExprKind::List(
+        x.into_iter()
+          .map(|(key, value)| {
+            ExprKind::List(vec![ExprKind::from(key).into(), value].into())
+              .into()
+          })
+          .collect(),
+      ),
+      (ExprKind::Record(x), "map") => ExprKind::Map(
+        x.into_iter()
+          .map(|(key, value)| (MapKey::Symbol(key), value))
+          .collect(),
+      ),
+      (ExprKind::List(x), "map") => x
+        .into_iter()
+        .map(|item| match item.kind {
+          ExprKind::List(mut pair) if pair.len() == 2 => {
+            let value = pair.pop()?;
+            let key = MapKey::from_kind(&pair.pop()?.kind)?;
+
+            Some((key, value))
+          }
+          _ => None,
+        })
+        .collect::<Option<IndexMap<_, _>>>()
+        .map(ExprKind::Map)
+        .unwrap_or(ExprKind::Nil),
+      (ExprKind::Bytes(x), "list") => ExprKind::List(
+        x.into_iter()
+          .map(|b| ExprKind::Integer(b as i64).into())
+          .collect(),
+      ),
+      (ExprKind::Symbol(x), "string") => ExprKind::String(x.as_str().into()),
+
+      // TODO: Make sure these are correct, because the logic is pretty
+      //       nuanced in terms of when to choose a Symbol or Intrinsic.
+      (ExprKind::Nil, "symbol") => ExprKind::Nil,
+      (ExprKind::Boolean(x), "symbol") => ExprKind::Boolean(x),
+      // TODO: Handle conversion into `fn` and `fn!`.
+      (ExprKind::String(x), "symbol") => ExprKind::Symbol(Symbol::new(x)),
+      (ExprKind::Symbol(x), "symbol") => ExprKind::Symbol(x),
+
+      (ExprKind::Record(x), "record") => ExprKind::Record(x),
+      (ExprKind::Record(x), "list") => {
+        let mut list: Vec<Expr> = Vec::new();
+        x.into_iter().for_each(|(key, value)| {
+          list.push(
+            ExprKind::List(vec![ExprKind::Symbol(key).into(), value].into())
+              .into(),
+          );
+        });
+
+        ExprKind::List(list.into())
+      }
+
+      (ExprKind::List(x), "record") => {
+        let mut record: HashMap<Symbol, Expr> = HashMap::new();
+        x.into_iter().for_each(|item| {
+          if let ExprKind::List(chunk) = item.kind {
+            let key = Symbol::from_ref(chunk[0].kind.to_string().as_str());
+            let value = &chunk[1];
+            record.insert(key, value.clone());
+          }
+        });
+
+        ExprKind::Record(record.into())
+      }
+
+      _ => ExprKind::Nil,
+    },
+    _ => ExprKind::Nil,
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+/// Splits the list, string or bytes on the stack at the index above it.
+fn run_table(mut context: Context, expr: Expr) -> Result<Context, RunError> {
+  let columns = context.stack_pop(&expr)?;
+  let rows = context.stack_pop(&expr)?;
+
+  let options = PrettyOptions {
+    color: yansi::is_enabled(),
+    float: context.float_format(),
+    ..Default::default()
+  };
+
+  match pretty::table(&rows, &columns, options) {
+    Some(table) => {
+      println!("{table}");
+      Ok(context)
+    }
+    None => Err(RunError {
+      reason: RunErrorReason::InvalidArgument,
+      context: Box::new(context),
+      expr: Box::new(expr),
+    }),
+  }
+}
+
+/// Runs `/` or `%`, where dividing an integer by zero is an error, which
+/// `try` can catch.
+fn run_division(
+  intrinsic: &Intrinsic,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let rhs = context.stack_pop(&expr)?;
+  let lhs = context.stack_pop(&expr)?;
+
+  if let (ExprKind::Integer(_), ExprKind::Integer(0)) = (&lhs.kind, &rhs.kind) {
+    return Err(RunError {
+      reason: RunErrorReason::DivideByZero,
+      context: Box::new(context),
+      expr: Box::new(expr),
+    });
+  }
+
+  let result = match intrinsic {
+    Intrinsic::Div => lhs.kind / rhs.kind,
+    Intrinsic::Rem => lhs.kind % rhs.kind,
+    _ => unreachable!(),
+  };
+  context.stack_push(result.unwrap_or(ExprKind::Nil).into())?;
+
+  Ok(context)
+}
+
+/// Ends the run with the exit code on the stack, which must be from 0 to 255,
+/// since that's all a process can exit with.
+fn run_exit(mut context: Context, expr: Expr) -> Result<Context, RunError> {
+  let code = context.stack_pop(&expr)?;
+
+  match code.kind {
+    ExprKind::Integer(0..=255) => Err(RunError {
+      reason: RunErrorReason::Exit,
+      context: Box::new(context),
+      expr: Box::new(Expr {
+        info: expr.info,
+        ..code
+      }),
+    }),
+    _ => Err(RunError {
+      reason: RunErrorReason::InvalidArgument,
+      context: Box::new(context),
+      expr: Box::new(expr),
+    }),
+  }
+}
+
+fn run_set_precision(
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let digits = context.stack_pop(&expr)?;
+
+  let digits = match digits.kind {
+    ExprKind::Nil => None,
+    // Floats don't have anywhere near this many digits anyway.
+    ExprKind::Integer(x) if x > 0 => Some(u8::try_from(x).unwrap_or(u8::MAX)),
+    _ => {
+      return Err(RunError {
+        reason: RunErrorReason::InvalidArgument,
+        context: Box::new(context),
+        expr: Box::new(expr),
+      })
+    }
+  };
+
+  let float_format = FloatFormat {
+    digits,
+    ..context.float_format()
+  };
+  context.set_float_format(float_format);
+
+  Ok(context)
+}
+
+fn run_split(mut context: Context, expr: Expr) -> Result<Context, RunError> {
+  let index = context.stack_pop(&expr)?;
+  let item = context.stack_pop(&expr)?;
+
+  match (item.kind, index.kind) {
+    (ExprKind::List(mut x), ExprKind::Integer(i)) if i >= 0 => {
+      if (i as usize) < x.len() {
+        let rest = x.split_off(i as usize);
+
+        context.stack_push(ExprKind::List(x).into())?;
+
+        context.stack_push(ExprKind::List(rest.into()).into())?;
+      } else {
+        context.stack_push(ExprKind::List(x).into())?;
+
+        context.stack_push(ExprKind::Nil.into())?;
+      }
+    }
+    (ExprKind::String(mut x), ExprKind::Integer(i)) if i >= 0 => {
+      match x.as_str().grapheme_indices(true).nth(i as usize) {
+        Some((i, _)) => {
+          let rest = x.split_off(i);
+
+          context.stack_push(ExprKind::String(x).into())?;
+
+          context.stack_push(ExprKind::String(rest).into())?;
+        }
+        None => {
+          context.stack_push(ExprKind::String(x).into())?;
+
+          context.stack_push(ExprKind::Nil.into())?;
+        }
+      }
+    }
+    (ExprKind::Bytes(mut x), ExprKind::Integer(i)) if i >= 0 => {
+      if (i as usize) < x.len() {
+        let rest = x.split_off(i as usize);
+
+        context.stack_push(ExprKind::Bytes(x).into())?;
+
+        context.stack_push(ExprKind::Bytes(rest).into())?;
+      } else {
+        context.stack_push(ExprKind::Bytes(x).into())?;
+
+        context.stack_push(ExprKind::Nil.into())?;
+      }
+    }
+    _ => {
+      context.stack_push(ExprKind::Nil.into())?;
+
+      context.stack_push(ExprKind::Nil.into())?;
+    }
+  }
+
+  Ok(context)
+}
+
+/// Pushes the value on the stack onto the list, string or bytes above it.
+fn run_push(mut context: Context, expr: Expr) -> Result<Context, RunError> {
+  let list = context.stack_pop(&expr)?;
+  let item = context.stack_pop(&expr)?;
+
+  let kind = match (list.kind.clone(), item.kind.clone()) {
+    (ExprKind::List(mut x), i) => {
+      x.push(Expr {
+        kind: i,
+        info: item.info.clone(),
+      });
+      ExprKind::List(x)
+    }
+    (ExprKind::String(mut x), ExprKind::String(s)) => {
+      x.push_str(&s);
+      ExprKind::String(x)
+    }
+    (ExprKind::String(mut x), ExprKind::Char(c)) => {
+      x.push(c);
+      ExprKind::String(x)
+    }
+    (ExprKind::String(mut x), ExprKind::Integer(c))
+      if c >= 0 && c <= u32::MAX as i64 =>
+    {
+      if let Some(c) = char::from_u32(c as u32) {
+        x.push(c);
+        ExprKind::String(x)
+      } else {
+        ExprKind::Nil
+      }
+    }
+    (ExprKind::Bytes(mut x), ExprKind::Bytes(b)) => {
+      x.extend(b);
+      ExprKind::Bytes(x)
+    }
+    (ExprKind::Bytes(mut x), ExprKind::Integer(b)) => match u8::try_from(b) {
+      Ok(b) => {
+        x.push(b);
+        ExprKind::Bytes(x)
+      }
+      Err(_) => ExprKind::Nil,
+    },
+    _ => ExprKind::Nil,
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+/// Pops the last item from the list, string or bytes on the stack.
+fn run_pop(mut context: Context, expr: Expr) -> Result<Context, RunError> {
+  let list = context.stack_pop(&expr)?;
+
+  match list.kind.clone() {
+    ExprKind::List(mut x) => {
+      let e = x.pop().unwrap_or(ExprKind::Nil.into());
+
+      context.stack_push(ExprKind::List(x).into())?;
+      context.stack_push(e)?;
+    }
+    ExprKind::String(mut x) => {
+      let e = x
+        .pop()
+        .map(|e| ExprKind::String(e.to_compact_string()).into())
+        .unwrap_or(ExprKind::Nil.into());
+
+      context.stack_push(ExprKind::String(x).into())?;
+      context.stack_push(e)?;
+    }
+    ExprKind::Bytes(mut x) => {
+      let e = x
+        .pop()
+        .map(|e| ExprKind::Integer(e as i64).into())
+        .unwrap_or(ExprKind::Nil.into());
+
+      context.stack_push(ExprKind::Bytes(x).into())?;
+      context.stack_push(e)?;
+    }
+    _ => {
+      context.stack_push(list.clone())?;
+      context.stack_push(ExprKind::Nil.into())?;
+    }
+  }
+
+  Ok(context)
+}
+
+/// Pops `start` and `end` indices and pushes the part of the list, string or
+/// bytes between them, or nil if they're out of bounds.
+fn run_slice(mut context: Context, expr: Expr) -> Result<Context, RunError> {
+  let end = context.stack_pop(&expr)?;
+  let start = context.stack_pop(&expr)?;
+  let item = context.stack_pop(&expr)?;
+
+  let range = match (start.kind, end.kind) {
+    (ExprKind::Integer(start), ExprKind::Integer(end))
+      if 0 <= start && start <= end =>
+    {
+      Some(start as usize..end as usize)
+    }
+    _ => None,
+  };
+
+  let kind = match (item.kind, range) {
+    (ExprKind::List(x), Some(range)) => x
+      .get(range)
+      .map(|x| ExprKind::List(x.to_vec().into()))
+      .unwrap_or(ExprKind::Nil),
+    (ExprKind::String(x), Some(range)) => {
+      let graphemes = x.as_str().graphemes(true).collect::<Vec<_>>();
+
+      graphemes
+        .get(range)
+        .map(|x| ExprKind::String(x.concat().into()))
+        .unwrap_or(ExprKind::Nil)
+    }
+    (ExprKind::Bytes(x), Some(range)) => x
+      .get(range)
+      .map(|x| ExprKind::Bytes(x.to_vec()))
+      .unwrap_or(ExprKind::Nil),
+    _ => ExprKind::Nil,
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+/// Converts between strings and bytes with the encoding on the stack, which
+/// is one of `utf-8`, `utf-16le`, `utf-16be`, `latin-1` or `ascii`.
+///
+/// Unknown encodings push nil, and text which can't be converted pushes an
+/// error value.
+fn run_encoding(
+  intrinsic: &Intrinsic,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let encoding = context.stack_pop(&expr)?;
+  let item = context.stack_pop(&expr)?;
+
+  let encoding = match encoding.kind {
+    ExprKind::String(ref x) => x.to_ascii_lowercase().to_string(),
+    _ => String::new(),
+  };
+
+  let invalid = || {
+    error_value(
+      ExprKind::String(format!("invalid {encoding}").to_compact_string())
+        .into(),
+    )
+  };
+
+  let expr = match (intrinsic, item.kind, encoding.as_str()) {
+    (Intrinsic::Encode, ExprKind::String(x), "utf-8" | "utf8") => {
+      ExprKind::Bytes(x.as_bytes().to_vec()).into()
+    }
+    (Intrinsic::Encode, ExprKind::String(x), "utf-16le" | "utf-16be") => {
+      let little = encoding == "utf-16le";
+
+      ExprKind::Bytes(
+        x.encode_utf16()
+          .flat_map(|x| {
+            if little {
+              x.to_le_bytes()
+            } else {
+              x.to_be_bytes()
+            }
+          })
+          .collect(),
+      )
+      .into()
+    }
+    (Intrinsic::Encode, ExprKind::String(x), "latin-1" | "latin1") => x
+      .chars()
+      .map(|c| u8::try_from(c).ok())
+      .collect::<Option<Vec<_>>>()
+      .map(|x| ExprKind::Bytes(x).into())
+      .unwrap_or_else(invalid),
+    (Intrinsic::Encode, ExprKind::String(x), "ascii") if x.is_ascii() => {
+      ExprKind::Bytes(x.as_bytes().to_vec()).into()
+    }
+    (Intrinsic::Encode, ExprKind::String(_), "ascii") => invalid(),
+
+    (Intrinsic::Decode, ExprKind::Bytes(x), "utf-8" | "utf8") => {
+      String::from_utf8(x)
+        .map(|x| ExprKind::String(x.into()).into())
+        .unwrap_or_else(|_| invalid())
+    }
+    (Intrinsic::Decode, ExprKind::Bytes(x), "utf-16le" | "utf-16be") => {
+      let little = encoding == "utf-16le";
+      let units = x
+        .chunks(2)
+        .map(|x| match *x {
+          [a, b] if little => Some(u16::from_le_bytes([a, b])),
+          [a, b] => Some(u16::from_be_bytes([a, b])),
+          _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+
+      units
+        .and_then(|x| String::from_utf16(&x).ok())
+        .map(|x| ExprKind::String(x.into()).into())
+        .unwrap_or_else(invalid)
+    }
+    (Intrinsic::Decode, ExprKind::Bytes(x), "latin-1" | "latin1") => {
+      ExprKind::String(x.into_iter().map(char::from).collect()).into()
+    }
+    (Intrinsic::Decode, ExprKind::Bytes(x), "ascii") if x.is_ascii() => {
+      ExprKind::String(x.into_iter().map(char::from).collect()).into()
+    }
+    (Intrinsic::Decode, ExprKind::Bytes(_), "ascii") => invalid(),
+    _ => ExprKind::Nil.into(),
+  };
+
+  context.stack_push(expr)?;
+
+  Ok(context)
+}
+
+/// Runs one of the intrinsics for records and maps. Records are keyed by
+/// symbols, or by lists of symbols for nested records, while maps are keyed by
+/// strings, integers or symbols.
+fn run_record(
+  intrinsic: &Intrinsic,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  match intrinsic {
+    Intrinsic::Insert | Intrinsic::Remove => {
+      // Unlike `remove`, `insert` takes the record last.
+      let (record, name, value) = match intrinsic {
+        Intrinsic::Insert => {
+          let record = context.stack_pop(&expr)?;
+          let name = context.stack_pop(&expr)?;
+          // Sets only take an item, rather than a key and a value.
+          let value = match record.kind {
+            ExprKind::Set(_) => None,
+            _ => Some(context.stack_pop(&expr)?),
+          };
+
+          (record, name, value)
+        }
+        _ => {
+          let name = context.stack_pop(&expr)?;
+          (context.stack_pop(&expr)?, name, None)
+        }
+      };
+
+      let kind = match (record.kind, value) {
+        (ExprKind::Set(mut set), _) => match MapKey::from_kind(&name.kind) {
+          Some(item) => {
+            match intrinsic {
+              Intrinsic::Insert => set.insert(item),
+              _ => set.shift_remove(&item),
+            };
+
+            ExprKind::Set(set)
+          }
+          None => ExprKind::Nil,
+        },
+        (ExprKind::Record(mut record), Some(value)) => {
+          insert_path(&mut record, &record_path(name), value);
+          ExprKind::Record(record)
+        }
+        (ExprKind::Record(mut record), None) => {
+          remove_path(&mut record, &record_path(name));
+          ExprKind::Record(record)
+        }
+        (ExprKind::Map(mut map), value) => {
+          match MapKey::from_kind(&name.kind) {
+            Some(key) => {
+              match value {
+                Some(value) => map.insert(key, value),
+                None => map.shift_remove(&key),
+              };
+
+              ExprKind::Map(map)
+            }
+            None => ExprKind::Nil,
+          }
+        }
+        _ => ExprKind::Nil,
+      };
+
+      context.stack_push(kind.into())?;
+    }
+    Intrinsic::Prop | Intrinsic::Has => {
+      let name = context.stack_pop(&expr)?;
+      let record = context.stack_pop(&expr)?;
+
+      let value = match record.kind {
+        ExprKind::Record(ref r) => get_path(r, &record_path(name)).cloned(),
+        ExprKind::Map(ref m) => {
+          MapKey::from_kind(&name.kind).and_then(|key| m.get(&key).cloned())
+        }
+        _ => {
+          context.stack_push(ExprKind::Nil.into())?;
+          return Ok(context);
+        }
+      };
+
+      let result = match intrinsic {
+        Intrinsic::Prop => value.unwrap_or(ExprKind::Nil.into()),
+        _ => ExprKind::Boolean(value.is_some()).into(),
+      };
+
+      context.stack_push(record)?;
+      context.stack_push(result)?;
+    }
+    _ => {
+      let record = context.stack_pop(&expr)?;
+
+      let entries = match record.kind {
+        ExprKind::Record(ref r) => r
+          .iter()
+          .map(|(key, value)| (ExprKind::Symbol(*key), value.clone()))
+          .collect::<Vec<_>>(),
+        ExprKind::Map(ref m) => m
+          .iter()
+          .map(|(key, value)| (key.clone().into(), value.clone()))
+          .collect::<Vec<_>>(),
+        _ => {
+          context.stack_push(ExprKind::Nil.into())?;
+          return Ok(context);
+        }
+      };
+
+      let result = entries
+        .into_iter()
+        .map(|(key, value)| match intrinsic {
+          Intrinsic::Keys => key.into(),
+          Intrinsic::Values => value,
+          _ => ExprKind::List(vec![key.into(), value].into()).into(),
+        })
+        .collect::<Vec<_>>();
+
+      context.stack_push(record)?;
+      context.stack_push(ExprKind::List(result.into()).into())?;
+    }
+  }
+
+  Ok(context)
+}
+
+/// Pushes whether the list, string, map or set on the stack contains the
+/// value above it, as an item, a substring, a key or an item respectively.
+fn run_contains(mut context: Context, expr: Expr) -> Result<Context, RunError> {
+  let item = context.stack_pop(&expr)?;
+  let collection = context.stack_pop(&expr)?;
+
+  let kind = match (&collection.kind, &item.kind) {
+    (ExprKind::List(x), item) => {
+      ExprKind::Boolean(x.iter().any(|x| x.kind == *item))
+    }
+    (ExprKind::String(x), ExprKind::String(item)) => {
+      ExprKind::Boolean(x.contains(item.as_str()))
+    }
+    (ExprKind::String(x), ExprKind::Char(item)) => {
+      ExprKind::Boolean(x.contains(*item))
+    }
+    (ExprKind::Map(x), item) => ExprKind::Boolean(
+      MapKey::from_kind(item).is_some_and(|key| x.contains_key(&key)),
+    ),
+    (ExprKind::Set(x), item) => ExprKind::Boolean(
+      MapKey::from_kind(item).is_some_and(|item| x.contains(&item)),
+    ),
+    _ => ExprKind::Nil,
+  };
+
+  context.stack_push(collection)?;
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+/// Runs one of the intrinsics for sets. The results of the set operations keep
+/// the order of the first set, followed by any new items from the second.
+fn run_set(
+  intrinsic: &Intrinsic,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let kind = match intrinsic {
+    Intrinsic::SetNew => ExprKind::Set(IndexSet::new()),
+    Intrinsic::ToList => match context.stack_pop(&expr)?.kind {
+      ExprKind::Set(x) => ExprKind::List(
+        x.into_iter().map(|x| ExprKind::from(x).into()).collect(),
+      ),
+      _ => ExprKind::Nil,
+    },
+    _ => {
+      let rhs = context.stack_pop(&expr)?;
+      let lhs = context.stack_pop(&expr)?;
+
+      match (lhs.kind, rhs.kind) {
+        (ExprKind::Set(lhs), ExprKind::Set(rhs)) => {
+          ExprKind::Set(match intrinsic {
+            Intrinsic::Union => lhs.union(&rhs).cloned().collect(),
+            Intrinsic::Intersect => lhs.intersection(&rhs).cloned().collect(),
+            _ => lhs.difference(&rhs).cloned().collect(),
+          })
+        }
+        _ => ExprKind::Nil,
+      }
+    }
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+/// Runs one of the intrinsics for lazy sequences. The adapters, such as `map`,
+/// return a sequence when given one, but run straight away and return a list
+/// when given a list or set.
+fn run_seq(
+  intrinsic: &Intrinsic,
+  engine: &Engine,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let rhs = match intrinsic {
+    Intrinsic::Collect => None,
+    _ => Some(context.stack_pop(&expr)?),
+  };
+  let lhs = context.stack_pop(&expr)?;
+
+  if let Intrinsic::Range | Intrinsic::RangeBy = intrinsic {
+    let (start, end, step) = match intrinsic {
+      Intrinsic::RangeBy => (context.stack_pop(&expr)?, Some(lhs), rhs),
+      _ => (lhs, rhs, None),
+    };
+
+    let end = match end.map(|x| x.kind) {
+      Some(ExprKind::Integer(end)) => Some(Some(end)),
+      Some(ExprKind::Nil) => Some(None),
+      _ => None,
+    };
+
+    let kind = match (start.kind, end, step.map(|x| x.kind)) {
+      (ExprKind::Integer(next), Some(end), None) => {
+        ExprKind::Seq(Box::new(Seq::range(next, end)))
+      }
+      (ExprKind::Integer(next), Some(end), Some(ExprKind::Integer(step)))
+        if step != 0 =>
+      {
+        ExprKind::Seq(Box::new(Seq::Range { next, end, step }))
+      }
+      _ => ExprKind::Nil,
+    };
+
+    context.stack_push(kind.into())?;
+    return Ok(context);
+  }
+
+  let is_lazy = matches!(lhs.kind, ExprKind::Seq(_))
+    || matches!(
+      rhs,
+      Some(Expr {
+        kind: ExprKind::Seq(_),
+        ..
+      })
+    );
+
+  let Some(lhs) = Seq::from_kind(lhs.kind) else {
+    context.stack_push(ExprKind::Nil.into())?;
+    return Ok(context);
+  };
+
+  let seq = match (intrinsic, rhs) {
+    (Intrinsic::Map, Some(block)) => Some(Seq::Map {
+      seq: Box::new(lhs),
+      block: Box::new(block),
+    }),
+    (Intrinsic::Filter, Some(block)) => Some(Seq::Filter {
+      seq: Box::new(lhs),
+      block: Box::new(block),
+    }),
+    (
+      Intrinsic::Take,
+      Some(Expr {
+        kind: ExprKind::Integer(count),
+        ..
+      }),
+    ) if count >= 0 => Some(Seq::Take {
+      seq: Box::new(lhs),
+      count: count as usize,
+    }),
+    (Intrinsic::Zip, Some(rhs)) => {
+      Seq::from_kind(rhs.kind).map(|rhs| Seq::Zip {
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+      })
+    }
+    (Intrinsic::Collect, None) => Some(lhs),
+    _ => None,
+  };
+
+  let kind = match seq {
+    Some(seq) if is_lazy && *intrinsic != Intrinsic::Collect => {
+      ExprKind::Seq(Box::new(seq))
+    }
+    // An infinite sequence would never finish collecting.
+    Some(seq) if seq.is_finite() => {
+      let items;
+      (context, items) = seq.collect(engine, context)?;
+      ExprKind::List(items.into())
+    }
+    _ => ExprKind::Nil,
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+/// Runs one of the looping intrinsics. `while` and `until` call the condition
+/// block before each run of the body, and stop once it leaves `false` (or
+/// `true`, for `until`) on top of the stack. Any of them can be stopped early
+/// with `break`.
+fn run_loop(
+  intrinsic: &Intrinsic,
+  engine: &Engine,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let body = context.stack_pop(&expr)?;
+
+  match intrinsic {
+    Intrinsic::Loop => loop {
+      let flow;
+      (context, flow) = run_body(engine, context, &body)?;
+
+      if let Flow::Break = flow {
+        return Ok(context);
+      }
+    },
+    Intrinsic::Times => {
+      let count = context.stack_pop(&expr)?;
+
+      if let ExprKind::Integer(count) = count.kind {
+        for _ in 0..count {
+          let flow;
+          (context, flow) = run_body(engine, context, &body)?;
+
+          if let Flow::Break = flow {
+            break;
+          }
+        }
+      }
+
+      Ok(context)
+    }
+    _ => {
+      let cond = context.stack_pop(&expr)?;
+
+      loop {
+        context = engine.call_expr(context, cond.clone())?;
+        let result = context.stack_pop(&expr)?.kind.is_truthy();
+
+        if result != matches!(intrinsic, Intrinsic::While) {
+          return Ok(context);
+        }
+
+        let flow;
+        (context, flow) = run_body(engine, context, &body)?;
+
+        if let Flow::Break = flow {
+          return Ok(context);
+        }
+      }
+    }
+  }
+}
+
+/// What a loop does after running its body.
+enum Flow {
+  Next,
+  Break,
+}
+
+/// Runs the body of a loop, catching a `break` or `continue` from within it.
+fn run_body(
+  engine: &Engine,
+  context: Context,
+  body: &Expr,
+) -> Result<(Context, Flow), RunError> {
+  let depth = context.scope_depth();
+
+  match engine.call_expr(context, body.clone()) {
+    Ok(context) => Ok((context, Flow::Next)),
+    Err(RunError {
+      reason: reason @ (RunErrorReason::Break | RunErrorReason::Continue),
+      mut context,
+      ..
+    }) => {
+      // Leave any `let` that was broken out of.
+      while context.scope_depth() > depth {
+        context.pop_scope();
+      }
+
+      match reason {
+        RunErrorReason::Break => Ok((*context, Flow::Break)),
+        _ => Ok((*context, Flow::Next)),
+      }
+    }
+    Err(err) => Err(err),
+  }
+}
+
+/// Runs one of the intrinsics which call a block on each item of a list, set
+/// or sequence. Sequences are stepped one item at a time, so `any` and `all`
+/// stop as soon as they know the answer.
+fn run_iter(
+  intrinsic: &Intrinsic,
+  engine: &Engine,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let block = context.stack_pop(&expr)?;
+  let mut acc = match intrinsic {
+    Intrinsic::Fold => Some(context.stack_pop(&expr)?),
+    _ => None,
+  };
+  let items = context.stack_pop(&expr)?;
+
+  let Some(mut seq) = Seq::from_kind(items.kind) else {
+    context.stack_push(ExprKind::Nil.into())?;
+    return Ok(context);
+  };
+
+  loop {
+    let item;
+    (context, item) = seq.next(engine, context)?;
+
+    let Some(item) = item else {
+      break;
+    };
+
+    if let Some(acc) = acc.take() {
+      context.stack_push(acc)?;
+    }
+
+    context.stack_push(item)?;
+    context = engine.call_expr(context, block.clone())?;
+
+    match intrinsic {
+      Intrinsic::Fold => acc = Some(context.stack_pop(&expr)?),
+      Intrinsic::Any | Intrinsic::All => {
+        let result = context.stack_pop(&expr)?.kind.is_truthy();
+
+        if result == matches!(intrinsic, Intrinsic::Any) {
+          context.stack_push(ExprKind::Boolean(result).into())?;
+          return Ok(context);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  match intrinsic {
+    Intrinsic::Fold => {
+      context.stack_push(acc.unwrap_or_else(|| ExprKind::Nil.into()))?
+    }
+    Intrinsic::Any => context.stack_push(ExprKind::Boolean(false).into())?,
+    Intrinsic::All => context.stack_push(ExprKind::Boolean(true).into())?,
+    _ => {}
+  }
+
+  Ok(context)
+}
+
+/// Runs one of the sorting intrinsics. The `-by` variants take a block, which
+/// is either a comparator, taking two items and leaving whether the first goes
+/// before the second, or a key block, taking one item and leaving the key to
+/// sort it by.
+fn run_sort(
+  intrinsic: &Intrinsic,
+  engine: &Engine,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let block = match intrinsic {
+    Intrinsic::SortBy | Intrinsic::MinBy | Intrinsic::MaxBy => {
+      Some(context.stack_pop(&expr)?)
+    }
+    _ => None,
+  };
+  let items = context.stack_pop(&expr)?;
+
+  let Some(seq) = Seq::from_kind(items.kind) else {
+    context.stack_push(ExprKind::Nil.into())?;
+    return Ok(context);
+  };
+
+  let mut items;
+  (context, items) = seq.collect(engine, context)?;
+
+  match block {
+    Some(block) if items.len() >= 2 => {
+      // Tell the kind of block apart by how much of the stack it uses.
+      let len = context.stack().len();
+      context.stack_push(items[0].clone())?;
+      context.stack_push(items[1].clone())?;
+      context = engine.call_expr(context, block.clone())?;
+
+      if context.stack().len() == len + 1 {
+        context.stack_pop(&expr)?;
+        (context, items) = merge_sort(engine, context, &block, items)?;
+      } else {
+        context.stack_mut().truncate(len);
+
+        let mut keyed = Vec::with_capacity(items.len());
+        for item in items {
+          context.stack_push(item.clone())?;
+          context = engine.call_expr(context, block.clone())?;
+          keyed.push((context.stack_pop(&expr)?, item));
+        }
+
+        keyed.sort_by(|(a, _), (b, _)| natural_cmp(&a.kind, &b.kind));
+        items = keyed.into_iter().map(|(_, item)| item).collect();
+      }
+    }
+    Some(_) => {}
+    None => items.sort_by(|a, b| natural_cmp(&a.kind, &b.kind)),
+  }
+
+  // Both sorts are stable, so this matches `min_by` and `max_by` in picking
+  // the first of the smallest items and the last of the largest.
+  let kind = match intrinsic {
+    Intrinsic::Min | Intrinsic::MinBy => {
+      items.into_iter().next().map_or(ExprKind::Nil, |x| x.kind)
+    }
+    Intrinsic::Max | Intrinsic::MaxBy => {
+      items.pop().map_or(ExprKind::Nil, |x| x.kind)
+    }
+    _ => ExprKind::List(items.into()),
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+/// Sorts `items` with a comparator `block`, stably. The comparator leaves
+/// `true` or a negative integer if the first item goes before the second.
+fn merge_sort(
+  engine: &Engine,
+  mut context: Context,
+  block: &Expr,
+  mut items: Vec<Expr>,
+) -> Result<(Context, Vec<Expr>), RunError> {
+  if items.len() < 2 {
+    return Ok((context, items));
+  }
+
+  let tail = items.split_off(items.len() / 2);
+  let (lhs, rhs);
+  (context, lhs) = merge_sort(engine, context, block, items)?;
+  (context, rhs) = merge_sort(engine, context, block, tail)?;
+
+  let mut merged = Vec::with_capacity(lhs.len() + rhs.len());
+  let mut lhs = lhs.into_iter().peekable();
+  let mut rhs = rhs.into_iter().peekable();
+
+  while let (Some(l), Some(r)) = (lhs.peek(), rhs.peek()) {
+    // Only take from the right if it strictly goes first, which keeps equal
+    // items in order.
+    context.stack_push(r.clone())?;
+    context.stack_push(l.clone())?;
+    context = engine.call_expr(context, block.clone())?;
+
+    let before = match context.stack_pop(block)?.kind {
+      ExprKind::Boolean(x) => x,
+      ExprKind::Integer(x) => x < 0,
+      _ => false,
+    };
+
+    match before {
+      true => merged.extend(rhs.next()),
+      false => merged.extend(lhs.next()),
+    }
+  }
+
+  merged.extend(lhs);
+  merged.extend(rhs);
+
+  Ok((context, merged))
+}
+
+/// Compares values for sorting. Numbers compare by value, even between
+/// integers and floats, strings and chars by code point, and lists item by
+/// item. Values of different types compare by type, in the order nil,
+/// booleans, numbers, chars, strings, symbols and then lists, with anything
+/// else last.
+pub(crate) fn natural_cmp(lhs: &ExprKind, rhs: &ExprKind) -> Ordering {
+  fn rank(kind: &ExprKind) -> u8 {
+    match kind {
+      ExprKind::Nil => 0,
+      ExprKind::Boolean(_) => 1,
+      ExprKind::Integer(_) | ExprKind::Float(_) => 2,
+      ExprKind::Char(_) => 3,
+      ExprKind::String(_) => 4,
+      ExprKind::Symbol(_) => 5,
+      ExprKind::List(_) => 6,
+      _ => 7,
+    }
+  }
+
+  match (lhs, rhs) {
+    (ExprKind::Boolean(lhs), ExprKind::Boolean(rhs)) => lhs.cmp(rhs),
+    (ExprKind::Integer(lhs), ExprKind::Integer(rhs)) => lhs.cmp(rhs),
+    (ExprKind::Float(lhs), ExprKind::Float(rhs)) => lhs.total_cmp(rhs),
+    (ExprKind::Integer(lhs), ExprKind::Float(rhs)) => {
+      (*lhs as f64).total_cmp(rhs)
+    }
+    (ExprKind::Float(lhs), ExprKind::Integer(rhs)) => {
+      lhs.total_cmp(&(*rhs as f64))
+    }
+    (ExprKind::Char(lhs), ExprKind::Char(rhs)) => lhs.cmp(rhs),
+    (ExprKind::String(lhs), ExprKind::String(rhs)) => lhs.cmp(rhs),
+    (ExprKind::Symbol(lhs), ExprKind::Symbol(rhs)) => {
+      lhs.as_str().cmp(rhs.as_str())
+    }
+    (ExprKind::List(lhs), ExprKind::List(rhs)) => lhs
+      .iter()
+      .zip(rhs.iter())
+      .map(|(lhs, rhs)| natural_cmp(&lhs.kind, &rhs.kind))
+      .find(|x| x.is_ne())
+      .unwrap_or_else(|| lhs.len().cmp(&rhs.len())),
+    (lhs, rhs) => rank(lhs).cmp(&rank(rhs)),
+  }
+}
+
+/// Wraps `value` in an error, unless it's already one.
+fn error_value(value: Expr) -> Expr {
+  match value.kind {
+    ExprKind::Error(_) => value,
+    _ => ExprKind::Error(Box::new(value)).into(),
+  }
+}
+
+/// Runs the block on the stack, replacing everything it did with an error
+/// value if it fails.
+fn run_try(
+  engine: &Engine,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let block = context.stack_pop(&expr)?;
+  let before = context.clone();
+
+  match engine.call_expr(context, block) {
+    Ok(context) => Ok(context),
+    // Limits, halts, exits and cancellations end the whole run, so they can't
+    // be caught, and breaking out of a loop isn't an error.
+    Err(err)
+      if matches!(
+        err.reason,
+        RunErrorReason::Halt
+          | RunErrorReason::Exit
+          | RunErrorReason::Timeout
+          | RunErrorReason::OutOfFuel
+          | RunErrorReason::StackOverflow
+          | RunErrorReason::Cancelled
+          | RunErrorReason::Break
+          | RunErrorReason::Continue
+      ) =>
+    {
+      Err(err)
+    }
+    Err(err) => {
+      let mut context = before;
+
+      let value = match err.reason {
+        RunErrorReason::Thrown => *err.expr,
+        reason => error_value(
+          ExprKind::Record(
+            HashMap::from_iter([
+              (
+                Symbol::from_ref("reason"),
+                ExprKind::String(reason.to_string().into()).into(),
+              ),
+              (
+                Symbol::from_ref("message"),
+                ExprKind::String(err.to_string().into()).into(),
+              ),
+            ])
+            .into(),
+          )
+          .into(),
+        ),
+      };
+      context.stack_push(value)?;
+
+      Ok(context)
+    }
+  }
+}
+
+/// Runs one of the intrinsics for generators, which need the `sync` feature.
+fn run_generator(
+  intrinsic: &Intrinsic,
+  engine: &Engine,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  match intrinsic {
+    Intrinsic::Generator => {
+      let block = context.stack_pop(&expr)?;
+
+      #[cfg(feature = "sync")]
+      {
+        match Generator::new(engine, &context, block) {
+          Ok(generator) => {
+            context
+              .stack_push(ExprKind::Foreign(Foreign::new(generator)).into())?;
+
+            Ok(context)
+          }
+          Err(_) => Err(RunError {
+            reason: RunErrorReason::ThreadSpawnFailed,
+            context: Box::new(context),
+            expr: Box::new(expr),
+          }),
+        }
+      }
+
+      #[cfg(not(feature = "sync"))]
+      {
+        let _ = (engine, block);
+        Err(RunError {
+          reason: RunErrorReason::NeedsSync,
+          context: Box::new(context),
+          expr: Box::new(expr),
+        })
+      }
+    }
+    Intrinsic::Yield => {
+      let value = context.stack_pop(&expr)?;
+
+      #[cfg(feature = "sync")]
+      let result = coroutine::yield_value(value);
+      #[cfg(not(feature = "sync"))]
+      let result = {
+        let _ = value;
+        Err(())
+      };
+
+      match result {
+        Ok(()) => Ok(context),
+        // Nothing will resume the generator, so it's stopped.
+        #[cfg(feature = "sync")]
+        Err(YieldError::Dropped) => Err(RunError {
+          reason: RunErrorReason::Halt,
+          context: Box::new(context),
+          expr: Box::new(expr),
+        }),
+        Err(_) => Err(RunError {
+          reason: RunErrorReason::InvalidYield,
+          context: Box::new(context),
+          expr: Box::new(expr),
+        }),
+      }
+    }
+    Intrinsic::Resume => {
+      let generator = context.stack_pop(&expr)?;
+
+      let kind = match generator.kind {
+        #[cfg(feature = "sync")]
+        ExprKind::Foreign(ref x) => match x.downcast_ref::<Generator>() {
+          Some(x) => match x.resume() {
+            Ok(value) => value.map_or(ExprKind::Nil, |x| x.kind),
+            Err(e) => {
+              return Err(RunError {
+                reason: e.reason,
+                context: Box::new(context),
+                expr: e.expr,
+              })
+            }
+          },
+          None => ExprKind::Nil,
+        },
+        _ => ExprKind::Nil,
+      };
+
+      context.stack_push(kind.into())?;
+
+      Ok(context)
+    }
+    Intrinsic::IsDone => {
+      let generator = context.stack_pop(&expr)?;
+
+      let kind = match generator.kind {
+        #[cfg(feature = "sync")]
+        ExprKind::Foreign(ref x) => x
+          .downcast_ref::<Generator>()
+          .map_or(ExprKind::Nil, |x| ExprKind::Boolean(x.is_done())),
+        _ => ExprKind::Nil,
+      };
+
+      context.stack_push(kind.into())?;
+
+      Ok(context)
+    }
+    _ => unreachable!(),
+  }
+}
+
+/// Runs one of the intrinsics for results, which are errors if they failed
+/// and any other value if they succeeded.
+fn run_result(
+  intrinsic: &Intrinsic,
+  engine: &Engine,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  match intrinsic {
+    Intrinsic::Ok => {
+      let result = context.stack_pop(&expr)?;
+
+      match result.kind {
+        ExprKind::Error(_) => context.stack_push(ExprKind::Nil.into())?,
+        _ => context.stack_push(result)?,
+      }
+
+      Ok(context)
+    }
+    Intrinsic::Err => {
+      let result = context.stack_pop(&expr)?;
+
+      match result.kind {
+        ExprKind::Error(x) => context.stack_push(*x)?,
+        _ => context.stack_push(ExprKind::Nil.into())?,
+      }
+
+      Ok(context)
+    }
+    Intrinsic::Unwrap => {
+      let result = context.stack_pop(&expr)?;
+
+      let error = match result.kind {
+        ExprKind::Error(_) => result,
+        ExprKind::Nil => {
+          error_value(ExprKind::String("called unwrap on nil".into()).into())
+        }
+        _ => {
+          context.stack_push(result)?;
+          return Ok(context);
+        }
+      };
+
+      Err(RunError {
+        reason: RunErrorReason::Thrown,
+        context: Box::new(context),
+        expr: Box::new(Expr {
+          info: expr.info,
+          ..error
+        }),
+      })
+    }
+    Intrinsic::UnwrapOr => {
+      let default = context.stack_pop(&expr)?;
+      let result = context.stack_pop(&expr)?;
+
+      match result.kind {
+        ExprKind::Error(_) | ExprKind::Nil => context.stack_push(default)?,
+        _ => context.stack_push(result)?,
+      }
+
+      Ok(context)
+    }
+    Intrinsic::MapOk | Intrinsic::AndThen => {
+      let block = context.stack_pop(&expr)?;
+      let result = context.stack_pop(&expr)?;
+
+      let skip = match result.kind {
+        ExprKind::Error(_) => true,
+        ExprKind::Nil => *intrinsic == Intrinsic::AndThen,
+        _ => false,
+      };
+
+      context.stack_push(result)?;
+
+      if skip {
+        Ok(context)
+      } else {
+        engine.call_expr(context, block)
+      }
+    }
+    _ => unreachable!(),
+  }
+}
+
+/// Runs the file at the path on the stack, unless it's already been imported,
+/// or imports the module it names.
+fn run_import(
+  engine: &Engine,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let path = context.stack_pop(&expr)?;
+
+  let path = match path.kind {
+    ExprKind::String(ref x)
+      if engine.load_module(Symbol::from_ref(x.as_str())) =>
+    {
+      context
+        .imports_mut()
+        .import_module(Symbol::from_ref(x.as_str()));
+      return Ok(context);
+    }
+    ExprKind::String(ref x) => {
+      let from = expr.info.as_ref().map(|x| Path::new(x.source.name()));
+      import::resolve(
+        x.as_str(),
+        from,
+        engine.search_paths(),
+        engine.packages(),
+      )
+      .ok_or_else(|| RunError {
+        reason: RunErrorReason::ImportNotFound,
+        context: Box::new(context.clone()),
+        expr: Box::new(expr.clone()),
+      })?
+    }
+    _ => {
+      return Err(RunError {
+        reason: RunErrorReason::InvalidImport,
+        context: Box::new(context),
+        expr: Box::new(expr),
+      })
+    }
+  };
+
+  if !engine.can_import(&path) {
+    return Err(RunError {
+      reason: RunErrorReason::ForbiddenImport,
+      context: Box::new(context),
+      expr: Box::new(expr),
+    });
+  }
+
+  if context.imports().is_importing(&path) {
+    return Err(RunError {
+      reason: RunErrorReason::CircularImport,
+      context: Box::new(context),
+      expr: Box::new(expr),
+    });
+  }
+
+  if context.imports().is_imported(&path) {
+    return Ok(context);
+  }
+
+  let source = Source::from_path(&path).map_err(|_| RunError {
+    reason: RunErrorReason::ImportNotFound,
+    context: Box::new(context.clone()),
+    expr: Box::new(expr.clone()),
+  })?;
+  let mut lexer = Lexer::new(source.clone());
+  let exprs = parse(&mut lexer).map_err(|_| RunError {
+    reason: RunErrorReason::InvalidImport,
+    context: Box::new(context.clone()),
+    expr: Box::new(expr.clone()),
+  })?;
+
+  // Imports should trigger a new commit
+  if let Some(journal) = context.journal_mut() {
+    journal.commit();
+    journal.push_op(JournalOp::ScopelessFnStart(expr.info.clone()));
+  }
+
+  context.add_source(source);
+  context.imports_mut().start(path);
+
+  match engine.run(context, exprs) {
+    Ok(mut context) => {
+      let frame = context.imports_mut().finish(true);
+
+      if let Some(import::ImportFrame {
+        module: Some(module),
+        exports,
+        ..
+      }) = frame
+      {
+        // Everything the module defined lives in its own scope, which is
+        // replaced by its exports once it's finished.
+        let scope = context.scope().clone();
+        context.pop_scope();
+
+        let names: Vec<Symbol> = if exports.is_empty() {
+          scope
+            .items
+            .iter()
+            .filter(|(_, item)| item.borrow().is_root())
+            .map(|(name, _)| *name)
+            .collect()
+        } else {
+          exports
+        };
+
+        for name in names {
+          let item = scope.get_val(name).ok_or_else(|| RunError {
+            reason: RunErrorReason::InvalidModule,
+            context: Box::new(context.clone()),
+            expr: Box::new(expr.clone()),
+          })?;
+
+          let meta = scope.meta(name).cloned().unwrap_or_default();
+          let name = Symbol::from_ref(format!("{module}:{name}").as_str());
+          context.def_scope_item(name, item);
+          context.scope_mut().update_meta(name, |x| *x = meta);
+        }
+      }
+
+      if context.journal().is_some() {
+        let scope = context.scope().clone();
+        let journal = context.journal_mut().as_mut().unwrap();
+        journal.commit();
+        journal.push_op(JournalOp::FnEnd(expr.info.clone(), scope.into()));
+      }
+
+      Ok(context)
+    }
+    Err(mut err) => {
+      err.context.imports_mut().finish(false);
+      Err(err)
+    }
+  }
+}
+
+/// Runs the body of the first case whose pattern matches the value.
+fn run_match(
+  engine: &Engine,
+  mut context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  let cases = context.stack_pop(&expr)?;
+  let value = context.stack_pop(&expr)?;
+
+  let ExprKind::List(cases) = cases.kind else {
+    return Err(RunError {
+      reason: RunErrorReason::InvalidMatch,
+      context: Box::new(context),
+      expr: Box::new(expr),
+    });
+  };
+
+  // Every case is checked before any is tried, so that a malformed case is
+  // an error even when an earlier case matches.
+  let mut arms = Vec::with_capacity(cases.len());
+  for case in cases.iter() {
+    match case.kind {
+      ExprKind::List(ref x) if x.len() == 2 && pattern::is_valid(&x[0]) => {
+        arms.push((&x[0], &x[1]));
+      }
+      _ => {
+        return Err(RunError {
+          reason: RunErrorReason::InvalidMatch,
+          context: Box::new(context),
+          expr: Box::new(expr),
+        })
+      }
+    }
+  }
+
+  for (pattern, body) in arms {
+    if let Some(bindings) = pattern::match_pattern(pattern, &value) {
+      return run_with_bindings(engine, context, &expr, bindings, body.clone());
+    }
+  }
+
+  context.stack_push(ExprKind::Nil.into())?;
+
+  Ok(context)
+}
+
+/// Calls `body` in a new scope with each of the `bindings` defined, as `let`
+/// does.
+fn run_with_bindings(
+  engine: &Engine,
+  mut context: Context,
+  expr: &Expr,
+  bindings: Bindings,
+  body: Expr,
+) -> Result<Context, RunError> {
+  let mut scope = context.scope().duplicate();
+  for (name, value) in bindings {
+    if engine.is_watched(&name) {
+      let event = WatchEvent {
+        name,
+        kind: WatchKind::Let,
+        old: context.scope_item(name),
+        new: value.clone(),
+        expr: expr.clone(),
+      };
+      engine.watch(&context, event);
+    }
+
+    scope.define(name, value);
+  }
+
+  if let Some(journal) = context.journal_mut() {
+    journal.commit();
+    journal.push_op(JournalOp::ScopelessFnStart(expr.info.clone()));
+  }
+
+  context.push_scope(scope);
+  context = engine.call_expr(context, body)?;
+
+  if context.journal().is_some() {
+    let scope = context.scope().clone();
+    let journal = context.journal_mut().as_mut().unwrap();
+    journal.commit();
+    journal.push_op(JournalOp::FnEnd(expr.info.clone(), scope.into()));
+  }
+
+  context.pop_scope();
+
+  Ok(context)
+}
+
+/// Returns the keys of a record path, which is either a single key or a list
+/// of keys into nested records.
+fn record_path(name: Expr) -> Vec<Symbol> {
+  match name.kind {
+    ExprKind::List(x) => x.into_iter().map(|x| x.kind.into()).collect(),
+    kind => vec![kind.into()],
+  }
+}
+
+fn get_path<'a>(
+  record: &'a HashMap<Symbol, Expr>,
+  path: &[Symbol],
+) -> Option<&'a Expr> {
+  let (last, init) = path.split_last()?;
+
+  let mut record = record;
+  for key in init {
+    match &record.get(key)?.kind {
+      ExprKind::Record(x) => record = x,
+      _ => return None,
+    }
+  }
+
+  record.get(last)
+}
+
+/// Inserts `value` at `path`, replacing any missing or non-record values along
+/// the way with empty records.
+fn insert_path(
+  record: &mut HashMap<Symbol, Expr>,
+  path: &[Symbol],
+  value: Expr,
+) {
+  match path {
+    [] => {}
+    [key] => {
+      record.insert(*key, value);
+    }
+    [key, rest @ ..] => {
+      let entry = record
+        .entry(*key)
+        .or_insert_with(|| ExprKind::Record(HashMap::new().into()).into());
+
+      if !matches!(entry.kind, ExprKind::Record(_)) {
+        *entry = ExprKind::Record(HashMap::new().into()).into();
+      }
+
+      if let ExprKind::Record(ref mut x) = entry.kind {
+        insert_path(x, rest, value);
+      }
+    }
+  }
+}
+
+fn remove_path(record: &mut HashMap<Symbol, Expr>, path: &[Symbol]) {
+  match path {
+    [] => {}
+    [key] => {
+      record.remove(key);
+    }
+    [key, rest @ ..] => {
+      if let Some(ExprKind::Record(x)) =
+        record.get_mut(key).map(|x| &mut x.kind)
+      {
+        remove_path(x, rest);
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParseIntrinsicError;
 
 impl std::error::Error for ParseIntrinsicError {}
 
@@ -1044,3 +2869,66 @@ impl fmt::Display for ParseIntrinsicError {
     write!(f, "unknown intrinsic")
   }
 }
+
+/// Which [`Intrinsic`]s a [`Context`] is allowed to use.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IntrinsicFilter {
+  /// Allows every intrinsic.
+  #[default]
+  All,
+  /// Only allows these intrinsics.
+  Allow(HashSet<Intrinsic>),
+  /// Allows every intrinsic except these.
+  Deny(HashSet<Intrinsic>),
+}
+
+impl IntrinsicFilter {
+  /// Returns whether `intrinsic` is allowed.
+  pub fn allows(&self, intrinsic: Intrinsic) -> bool {
+    match self {
+      Self::All => true,
+      Self::Allow(x) => x.contains(&intrinsic),
+      Self::Deny(x) => !x.contains(&intrinsic),
+    }
+  }
+
+  /// Returns the first expression in `exprs` which calls a forbidden
+  /// intrinsic, including within lists, functions and lazy expressions.
+  ///
+  /// Lazy symbols (e.g., `'set`) are only names, so they aren't checked until
+  /// they're called.
+  pub fn find_forbidden(&self, exprs: &[Expr]) -> Option<Expr> {
+    struct Finder<'a> {
+      filter: &'a IntrinsicFilter,
+      found: Option<Expr>,
+    }
+
+    impl ExprVisitor for Finder<'_> {
+      fn visit_expr(&mut self, expr: &Expr) {
+        if self.found.is_some() {
+          return;
+        }
+
+        match &expr.kind {
+          ExprKind::Symbol(x) | ExprKind::SExpr { call: x, .. }
+            if Intrinsic::from_symbol(*x)
+              .is_some_and(|x| !self.filter.allows(x)) =>
+          {
+            self.found = Some(expr.clone());
+          }
+          ExprKind::Lazy(x) if matches!(x.kind, ExprKind::Symbol(_)) => {}
+          _ => walk_expr(self, expr),
+        }
+      }
+    }
+
+    let mut finder = Finder {
+      filter: self,
+      found: None,
+    };
+    finder.visit_exprs(exprs);
+
+    finder.found
+  }
+}
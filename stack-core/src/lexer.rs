@@ -1,10 +1,12 @@
 use core::{fmt, ops::Range};
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use crate::source::Source;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Token {
   pub kind: TokenKind,
   pub span: Span,
@@ -16,7 +18,8 @@ impl fmt::Display for Token {
   }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Span {
   /// The lower byte bound (inclusive).
   pub start: usize,
@@ -35,7 +38,8 @@ impl Span {
   }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TokenKind {
   Invalid,
   Eof,
@@ -55,6 +59,8 @@ pub enum TokenKind {
   Integer,
   Float,
   String,
+  Bytes,
+  Char,
   Symbol,
 }
 
@@ -74,6 +80,8 @@ impl fmt::Display for TokenKind {
       Self::Integer => write!(f, "an integer literal"),
       Self::Float => write!(f, "a float literal"),
       Self::String => write!(f, "a string literal"),
+      Self::Bytes => write!(f, "a bytes literal"),
+      Self::Char => write!(f, "a char literal"),
       Self::Symbol => write!(f, "a symbol literal"),
     }
   }
@@ -85,31 +93,56 @@ pub struct Lexer {
   source: Source,
   cursor: usize,
   peeked: Option<Token>,
+  comments: Vec<Span>,
 }
 
 impl Lexer {
   /// Creates a [`Lexer`] from a [`Source`].
   pub fn new(source: Source) -> Self {
+    let text = source.source();
+
+    // Skip the UTF-8 BOM, if present.
+    let mut cursor = if text.starts_with('\u{feff}') { 3 } else { 0 };
+
+    // Skip a `#!` line, so that scripts can be executed directly.
+    if text[cursor..].starts_with("#!") {
+      cursor = text[cursor..]
+        .find('\n')
+        .map_or(text.len(), |end| cursor + end);
+    }
+
     Self {
-      // Skip the UTF-8 BOM, if present.
-      #[allow(clippy::obfuscated_if_else)]
-      cursor: source
-        .source()
-        .as_bytes()
-        .starts_with(b"\xef\xbb\xbf")
-        .then_some(3)
-        .unwrap_or(0),
+      cursor,
       source,
       peeked: None,
+      comments: Vec::new(),
     }
   }
 
+  /// Moves the cursor to a byte index, from which the next [`Token`] is lexed.
+  ///
+  /// The index must be on a UTF-8 sequence boundary.
+  #[inline]
+  pub fn with_cursor(mut self, cursor: usize) -> Self {
+    self.cursor = cursor.min(self.source.source().len());
+    self.peeked = None;
+    self
+  }
+
   /// Returns a clone of the [`Source`].
   #[inline]
   pub fn source(&self) -> Source {
     self.source.clone()
   }
 
+  /// Returns the [`Span`]s of the comments that have been lexed so far.
+  ///
+  /// A comment's [`Span`] starts at the `;` and ends before the newline.
+  #[inline]
+  pub fn comments(&self) -> &[Span] {
+    &self.comments
+  }
+
   /// Returns the next [`Token`] in the stream without consuming it.
   #[inline]
   pub fn peek(&mut self) -> Token {
@@ -140,6 +173,8 @@ impl Lexer {
     let mut state = State::Start;
     let mut start = self.cursor;
     let mut chars = source[self.cursor..].chars();
+    // Bytes literals are lexed like strings, and only differ in the token.
+    let mut string_kind = TokenKind::String;
 
     loop {
       let c = chars.next().unwrap_or('\0');
@@ -159,6 +194,18 @@ impl Lexer {
           '\'' => {
             self.cursor += c_len;
 
+            if let Some(len) = char_literal_len(&source[self.cursor..]) {
+              self.cursor += len;
+
+              break Token {
+                kind: TokenKind::Char,
+                span: Span {
+                  start,
+                  end: self.cursor,
+                },
+              };
+            }
+
             break Token {
               kind: TokenKind::Apostrophe,
               span: Span {
@@ -234,9 +281,16 @@ impl Lexer {
             };
           }
           ';' => state = State::Comment,
+          // Doc comments start with `##`.
+          '#' if source[self.cursor + c_len..].starts_with('#') => {
+            state = State::Comment
+          }
           '-' => state = State::Minus,
           '0'..='9' => state = State::Integer,
           '"' => state = State::String,
+          'b' if source[self.cursor + c_len..].starts_with('"') => {
+            state = State::BytesQuote
+          }
           // NOTE: If this is modified, remember to change the other instances
           //       in the other State matches.
           '_'
@@ -245,6 +299,7 @@ impl Lexer {
           | '/'
           | '%'
           | ':'
+          | '.'
           | '!'
           | '='
           | '<'
@@ -253,11 +308,14 @@ impl Lexer {
           | 'a'..='z'
           | 'A'..='Z' => state = State::Symbol,
           // TODO: Square brackets should be checked in the parsing step.
-          ' ' | '\n' | '\t' | '\r' => start = self.cursor + c_len,
+          // Commas are whitespace, so that records can be written as
+          // `{a: 1, b: 2}`.
+          ' ' | '\n' | '\t' | '\r' | ',' => start = self.cursor + c_len,
           _ => state = State::Invalid,
         },
         State::Invalid => match c {
-          '\0' | ' ' | '\n' | '\t' | '\r' | '(' | ')' | '[' | ']' | '"' => {
+          '\0' | ' ' | '\n' | '\t' | '\r' | ',' | '(' | ')' | '[' | ']'
+          | '"' => {
             break Token {
               kind: TokenKind::Invalid,
               span: Span {
@@ -269,12 +327,23 @@ impl Lexer {
           _ => {}
         },
         State::Comment => match c {
-          '\0' => {
+          // A NUL within the comment is part of it, rather than the end.
+          '\0' if self.cursor == source.len() => {
+            self.comments.push(Span {
+              start,
+              end: self.cursor,
+            });
+
             state = State::Start;
             start = self.cursor;
             self.cursor -= c_len;
           }
           '\n' => {
+            self.comments.push(Span {
+              start,
+              end: self.cursor,
+            });
+
             state = State::Start;
             start = self.cursor + c_len;
           }
@@ -307,8 +376,14 @@ impl Lexer {
           }
         },
         State::Integer => match c {
-          '0'..='9' => {}
+          '0'..='9' | '_' => {}
           '.' => state = State::Float,
+          'e' | 'E' => state = State::ExponentStart,
+          'x' | 'X' | 'o' | 'O' | 'b' | 'B'
+            if matches!(&source[start..self.cursor], "0" | "-0") =>
+          {
+            state = State::Radix
+          }
           _ => {
             break Token {
               kind: TokenKind::Integer,
@@ -320,7 +395,34 @@ impl Lexer {
           }
         },
         State::Float => match c {
-          '0'..='9' => {}
+          '0'..='9' | '_' => {}
+          'e' | 'E' => state = State::ExponentStart,
+          _ => {
+            break Token {
+              kind: TokenKind::Float,
+              span: Span {
+                start,
+                end: self.cursor,
+              },
+            };
+          }
+        },
+        // A malformed exponent, such as in `1e`, is still lexed as a float so
+        // that the parser can report it.
+        State::ExponentStart => match c {
+          '+' | '-' | '0'..='9' => state = State::Exponent,
+          _ => {
+            break Token {
+              kind: TokenKind::Float,
+              span: Span {
+                start,
+                end: self.cursor,
+              },
+            };
+          }
+        },
+        State::Exponent => match c {
+          '0'..='9' | '_' => {}
           _ => {
             break Token {
               kind: TokenKind::Float,
@@ -331,6 +433,24 @@ impl Lexer {
             };
           }
         },
+        // Any alphanumeric characters are included, so that the parser can
+        // report invalid digits, such as in `0b12`.
+        State::Radix => match c {
+          '0'..='9' | 'a'..='z' | 'A'..='Z' | '_' => {}
+          _ => {
+            break Token {
+              kind: TokenKind::Integer,
+              span: Span {
+                start,
+                end: self.cursor,
+              },
+            };
+          }
+        },
+        State::BytesQuote => {
+          string_kind = TokenKind::Bytes;
+          state = State::String;
+        }
         State::String => match c {
           '\0' if self.cursor == source.len() => {
             break Token {
@@ -355,7 +475,7 @@ impl Lexer {
             self.cursor += c_len;
 
             break Token {
-              kind: TokenKind::String,
+              kind: string_kind,
               span: Span {
                 start,
                 end: self.cursor,
@@ -384,6 +504,7 @@ impl Lexer {
           | '/'
           | '%'
           | ':'
+          | '.'
           | '!'
           | '='
           | '<'
@@ -409,13 +530,44 @@ impl Lexer {
   }
 }
 
+/// Returns the length of the rest of a char literal, such as `a'` or `\n'`,
+/// which follows its opening apostrophe.
+///
+/// A char literal must be followed by whitespace or the end of a list, so that
+/// lazy expressions such as `'['(fn x)]` aren't lexed as chars. For the same
+/// reason, an apostrophe on its own is written as `'\''`.
+fn char_literal_len(rest: &str) -> Option<usize> {
+  let mut chars = rest.chars();
+
+  let len = match chars.next()? {
+    '\\' => match chars.next()? {
+      c @ ('n' | 't' | 'r' | '0' | '\\' | '\'') => 1 + c.len_utf8(),
+      _ => return None,
+    },
+    '\'' | '\n' => return None,
+    c => c.len_utf8(),
+  };
+
+  let is_closed = chars.next()? == '\'';
+  let is_end = matches!(
+    chars.next(),
+    None | Some(' ' | '\n' | '\t' | '\r' | ',' | ';' | ')' | ']' | '}')
+  );
+
+  (is_closed && is_end).then_some(len + 1)
+}
+
 enum State {
   Start,
   Invalid,
   Comment,
   Minus,
   Integer,
+  Radix,
   Float,
+  ExponentStart,
+  Exponent,
+  BytesQuote,
   String,
   StringBackslash,
   Symbol,
@@ -430,6 +582,10 @@ mod test {
   #[case(" \n\t\r" => vec![Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "whitespace eof")]
   #[case("; comment" => vec![Token { kind: TokenKind::Eof, span: Span { start: 9, end: 9 } }] ; "comment eof")]
   #[case("; comment\n" => vec![Token { kind: TokenKind::Eof, span: Span { start: 10, end: 10 } }] ; "comment whitespace eof")]
+  #[case("; \0é\né" => vec![Token { kind: TokenKind::Invalid, span: Span { start: 6, end: 8 } }, Token { kind: TokenKind::Eof, span: Span { start: 8, end: 8 } }] ; "comment with nul")]
+  #[case("#!/usr/bin/env stack run\n1" => vec![Token { kind: TokenKind::Integer, span: Span { start: 25, end: 26 } }, Token { kind: TokenKind::Eof, span: Span { start: 26, end: 26 } }] ; "shebang")]
+  #[case("#!stack" => vec![Token { kind: TokenKind::Eof, span: Span { start: 7, end: 7 } }] ; "shebang eof")]
+  #[case("1 #!" => vec![Token { kind: TokenKind::Integer, span: Span { start: 0, end: 1 } }, Token { kind: TokenKind::Invalid, span: Span { start: 2, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "shebang not at start")]
   #[case("+" => vec![Token { kind: TokenKind::Symbol, span: Span { start: 0, end: 1 } }, Token { kind: TokenKind::Eof, span: Span { start: 1, end: 1 } }] ; "plus only")]
   #[case("-" => vec![Token { kind: TokenKind::Symbol, span: Span { start: 0, end: 1 } }, Token { kind: TokenKind::Eof, span: Span { start: 1, end: 1 } }] ; "minus only")]
   #[case("*" => vec![Token { kind: TokenKind::Symbol, span: Span { start: 0, end: 1 } }, Token { kind: TokenKind::Eof, span: Span { start: 1, end: 1 } }] ; "asterisk only")]
@@ -449,6 +605,14 @@ mod test {
   #[case("-123" => vec![Token { kind: TokenKind::Integer, span: Span { start: 0, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "negative integer")]
   #[case("1.2" => vec![Token { kind: TokenKind::Float, span: Span { start: 0, end: 3 } }, Token { kind: TokenKind::Eof, span: Span { start: 3, end: 3 } }] ; "float")]
   #[case("-1.2" => vec![Token { kind: TokenKind::Float, span: Span { start: 0, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "negative float")]
+  #[case("0xFF" => vec![Token { kind: TokenKind::Integer, span: Span { start: 0, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "hex integer")]
+  #[case("-0o77" => vec![Token { kind: TokenKind::Integer, span: Span { start: 0, end: 5 } }, Token { kind: TokenKind::Eof, span: Span { start: 5, end: 5 } }] ; "negative octal integer")]
+  #[case("0b1010" => vec![Token { kind: TokenKind::Integer, span: Span { start: 0, end: 6 } }, Token { kind: TokenKind::Eof, span: Span { start: 6, end: 6 } }] ; "binary integer")]
+  #[case("1_000_000" => vec![Token { kind: TokenKind::Integer, span: Span { start: 0, end: 9 } }, Token { kind: TokenKind::Eof, span: Span { start: 9, end: 9 } }] ; "underscore integer")]
+  #[case("0b12" => vec![Token { kind: TokenKind::Integer, span: Span { start: 0, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "invalid binary integer")]
+  #[case("1.5e-3" => vec![Token { kind: TokenKind::Float, span: Span { start: 0, end: 6 } }, Token { kind: TokenKind::Eof, span: Span { start: 6, end: 6 } }] ; "exponent float")]
+  #[case("2E10" => vec![Token { kind: TokenKind::Float, span: Span { start: 0, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "exponent integer float")]
+  #[case("1e" => vec![Token { kind: TokenKind::Float, span: Span { start: 0, end: 2 } }, Token { kind: TokenKind::Eof, span: Span { start: 2, end: 2 } }] ; "missing exponent float")]
   #[case("hello" => vec![Token { kind: TokenKind::Symbol, span: Span { start: 0, end: 5 } }, Token { kind: TokenKind::Eof, span: Span { start: 5, end: 5 } }] ; "symbol")]
   #[case("h3l10" => vec![Token { kind: TokenKind::Symbol, span: Span { start: 0, end: 5 } }, Token { kind: TokenKind::Eof, span: Span { start: 5, end: 5 } }] ; "alphanumeric symbol")]
   #[case("he_lo" => vec![Token { kind: TokenKind::Symbol, span: Span { start: 0, end: 5 } }, Token { kind: TokenKind::Eof, span: Span { start: 5, end: 5 } }] ; "underscore symbol")]
@@ -464,6 +628,13 @@ mod test {
   #[case("fn" => vec![Token { kind: TokenKind::Symbol, span: Span { start: 0, end: 2 } }, Token { kind: TokenKind::Eof, span: Span { start: 2, end: 2 } }] ; "fn_")]
   #[case("fn!" => vec![Token { kind: TokenKind::Symbol, span: Span { start: 0, end: 3 } }, Token { kind: TokenKind::Eof, span: Span { start: 3, end: 3 } }] ; "fn exclamation")]
   #[case("\"hello\"" => vec![Token { kind: TokenKind::String, span: Span { start: 0, end: 7 } }, Token { kind: TokenKind::Eof, span: Span { start: 7, end: 7 } }] ; "string")]
+  #[case("b\"a\\xFF\"" => vec![Token { kind: TokenKind::Bytes, span: Span { start: 0, end: 8 } }, Token { kind: TokenKind::Eof, span: Span { start: 8, end: 8 } }] ; "bytes")]
+  #[case("b \"\"" => vec![Token { kind: TokenKind::Symbol, span: Span { start: 0, end: 1 } }, Token { kind: TokenKind::String, span: Span { start: 2, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "symbol b then string")]
+  #[case("'a'" => vec![Token { kind: TokenKind::Char, span: Span { start: 0, end: 3 } }, Token { kind: TokenKind::Eof, span: Span { start: 3, end: 3 } }] ; "char")]
+  #[case("'\\''" => vec![Token { kind: TokenKind::Char, span: Span { start: 0, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "escaped char")]
+  #[case("'a b" => vec![Token { kind: TokenKind::Apostrophe, span: Span { start: 0, end: 1 } }, Token { kind: TokenKind::Symbol, span: Span { start: 1, end: 2 } }, Token { kind: TokenKind::Symbol, span: Span { start: 3, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "lazy symbol not char")]
+  #[case("'''a" => vec![Token { kind: TokenKind::Apostrophe, span: Span { start: 0, end: 1 } }, Token { kind: TokenKind::Apostrophe, span: Span { start: 1, end: 2 } }, Token { kind: TokenKind::Apostrophe, span: Span { start: 2, end: 3 } }, Token { kind: TokenKind::Symbol, span: Span { start: 3, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "triple lazy not char")]
+  #[case("'['(" => vec![Token { kind: TokenKind::Apostrophe, span: Span { start: 0, end: 1 } }, Token { kind: TokenKind::LeftSquare, span: Span { start: 1, end: 2 } }, Token { kind: TokenKind::Apostrophe, span: Span { start: 2, end: 3 } }, Token { kind: TokenKind::LeftParen, span: Span { start: 3, end: 4 } }, Token { kind: TokenKind::Eof, span: Span { start: 4, end: 4 } }] ; "lazy list not char")]
   fn lexer(source: &str) -> Vec<Token> {
     let source = Source::new("", source);
     let mut lexer = Lexer::new(source);
@@ -1,30 +1,116 @@
+//! The core of Stack: the lexer, parser and engine.
+//!
+//! # Stability
+//!
+//! The [`prelude`] is the stable surface of this crate. Items in it only
+//! change in breaking ways with a new major version (or minor version, while
+//! the crate is `0.x`), and enums which are likely to grow, such as
+//! [`ExprKind`](expr::ExprKind) and
+//! [`RunErrorReason`](engine::RunErrorReason), are `#[non_exhaustive]` so that
+//! adding a variant isn't a breaking change.
+//!
+//! The other public modules are stable where they're re-exported by the
+//! prelude. Everything in [`internal`] is an implementation detail, which is
+//! exposed for tools like the debugger and can change in any release.
+//!
+//! Code written against the old `stack` crate can use [`compat`] while it's
+//! migrated, which is deprecated and will be removed.
+//!
+//! # Threads
+//!
+//! By default, an [`Engine`](engine::Engine) and
+//! [`Context`](context::Context) stay on the thread which made them. With the
+//! `sync` feature they're [`Send`] and [`Sync`], so programs can be run on
+//! worker threads, in exchange for native functions and hooks needing to be
+//! [`Send`] and [`Sync`] too. [`Engine::run_many`](engine::Engine::run_many)
+//! runs many independent programs at once this way.
+//!
+//! The `async` feature, which implies `sync`, lets native functions return
+//! futures, which [`Engine::run_async`](engine::Engine::run_async) awaits.
+//! The `tokio` feature, which implies `async`, lets them be called where they
+//! can't be awaited, such as within a function body, on a multi-threaded
+//! Tokio runtime. See [`future`] for more.
+
+pub mod arena;
+pub mod breakpoint;
+pub mod bundle;
+#[doc(hidden)]
 pub mod chain;
+pub mod compat;
 pub mod context;
+pub mod convert;
+#[cfg(feature = "sync")]
+pub mod coroutine;
+pub mod effect;
 pub mod engine;
 pub mod expr;
+pub mod float;
+pub mod foreign;
+pub mod format;
+pub mod format_spec;
+#[cfg(feature = "async")]
+pub mod future;
+pub mod import;
 pub mod intrinsic;
+#[doc(hidden)]
 pub mod journal;
 pub mod lexer;
+pub mod lint;
 pub mod module;
 pub mod parser;
+pub mod pattern;
+pub mod persistent;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+pub mod pretty;
+pub mod profile;
+pub mod rng;
+pub mod scheduler;
+#[doc(hidden)]
 pub mod scope;
+pub mod seq;
+#[doc(hidden)]
+pub mod shared;
+pub mod snapshot;
 pub mod source;
+pub mod stats;
 pub mod symbol;
+pub mod trace;
+pub mod visit;
+pub mod watchpoint;
 
 pub mod prelude {
-  //! Re-exports commonly used items.
+  //! Re-exports the stable API, which is enough to embed Stack.
 
   use super::*;
 
+  pub use bundle::ReportBundle;
   pub use context::Context;
-  pub use engine::{Engine, RunError, RunErrorReason};
-  pub use expr::{Error, Expr, ExprInfo, ExprKind};
-  pub use intrinsic::Intrinsic;
-  pub use lexer::Lexer;
+  pub use convert::{FromExpr, IntoExpr};
+  pub use engine::{Engine, EngineBuilder, RunError, RunErrorReason};
+  pub use expr::{Error, Expr, ExprInfo, ExprKind, MapKey};
+  pub use foreign::Foreign;
+  pub use intrinsic::{Intrinsic, IntrinsicFilter};
+  pub use lexer::{Lexer, Span};
+  pub use lint::{Lint, LintKind};
   pub use module::Module;
-  pub use parser::{parse, ParseError, ParseErrorKind};
-  pub use source::Source;
+  pub use parser::{parse, NumberError, ParseError, ParseErrorKind};
+  pub use persistent::Persistent;
+  pub use scheduler::{Checkpoint, Scheduler, TaskId, TaskStatus};
+  pub use seq::Seq;
+  pub use snapshot::Snapshot;
+  pub use source::{Location, Source};
+  pub use stats::RunStats;
   pub use symbol::Symbol;
 }
 
+pub mod internal {
+  //! Unstable internals, which can change in any release.
+  //!
+  //! These are used by the tools in this workspace, such as the debugger, but
+  //! embedders shouldn't rely on them.
+
+  pub use super::{chain, journal, scope, shared};
+}
+
 mod vec_one;
@@ -0,0 +1,367 @@
+//! A linearity lint, which finds values that are made and then thrown away.
+//!
+//! Each function body (and the top-level of a program) is simulated with an
+//! abstract stack, which tracks where each value came from and whether it's
+//! been used. It reports:
+//!
+//! - [`LintKind::UnusedDupe`], when either copy made by `dupe` is dropped
+//!   without being used, such as in `dupe drop` or `dupe print drop`, since the
+//!   `dupe` wasn't needed.
+//! - [`LintKind::UnusedPush`], when a pushed value is dropped without being
+//!   used, such as in `1 drop`.
+//!
+//! Only intrinsics with a fixed stack effect are simulated. Anything else,
+//! such as calling a function, could use any of the values on the stack, so
+//! the simulation forgets them and carries on.
+
+use core::fmt;
+
+use crate::{
+  expr::{Expr, ExprKind},
+  intrinsic::Intrinsic,
+  visit::{walk_expr, ExprVisitor},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LintKind {
+  UnusedDupe,
+  UnusedPush,
+}
+
+impl fmt::Display for LintKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::UnusedDupe => {
+        write!(f, "value is duplicated and then dropped without being used")
+      }
+      Self::UnusedPush => {
+        write!(f, "value is pushed and then dropped without being used")
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+  pub kind: LintKind,
+  /// The expression which made the unused value.
+  pub expr: Expr,
+}
+
+impl fmt::Display for Lint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} caused by ", self.kind)?;
+
+    match self.expr.info {
+      Some(ref info) => write!(f, "{info}"),
+      None => write!(f, "{}", self.expr),
+    }
+  }
+}
+
+/// Lints `exprs` and every function within them.
+pub fn lint(exprs: &[Expr]) -> Vec<Lint> {
+  let mut linter = Linter { lints: Vec::new() };
+  linter.visit_program(exprs);
+
+  linter.lints
+}
+
+struct Linter {
+  lints: Vec<Lint>,
+}
+
+impl ExprVisitor for Linter {
+  fn visit_expr(&mut self, expr: &Expr) {
+    if let ExprKind::Function { ref body, .. } = expr.kind {
+      self.lints.extend(simulate(body));
+    }
+
+    walk_expr(self, expr);
+  }
+}
+
+impl Linter {
+  fn visit_program(&mut self, exprs: &[Expr]) {
+    self.lints.extend(simulate(exprs));
+    self.visit_exprs(exprs);
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+  Push,
+  Dupe,
+  Other,
+}
+
+#[derive(Debug, Clone)]
+struct Value<'a> {
+  origin: Origin,
+  expr: &'a Expr,
+}
+
+/// Runs `exprs` on an abstract stack, returning the lints for the values that
+/// are dropped without being used.
+fn simulate<'a>(exprs: &'a [Expr]) -> Vec<Lint> {
+  let mut lints = Vec::new();
+  let mut stack: Vec<Value<'a>> = Vec::new();
+
+  for expr in exprs {
+    let push = |stack: &mut Vec<Value<'a>>, origin| {
+      stack.push(Value { origin, expr });
+    };
+
+    match expr.kind {
+      ExprKind::Nil
+      | ExprKind::Boolean(_)
+      | ExprKind::Integer(_)
+      | ExprKind::Float(_)
+      | ExprKind::String(_)
+      | ExprKind::Char(_)
+      | ExprKind::Bytes(_)
+      | ExprKind::List(_)
+      | ExprKind::Record(_)
+      | ExprKind::Map(_)
+      | ExprKind::Set(_)
+      | ExprKind::Seq(_)
+      | ExprKind::Lazy(_)
+      | ExprKind::Error(_)
+      | ExprKind::Foreign(_) => push(&mut stack, Origin::Push),
+
+      ExprKind::Symbol(x) => match Intrinsic::from_symbol(x) {
+        Some(Intrinsic::Drop) => match stack.pop() {
+          Some(Value {
+            origin: Origin::Push,
+            expr,
+          }) => lints.push(Lint {
+            kind: LintKind::UnusedPush,
+            expr: expr.clone(),
+          }),
+          // Dropping both copies would report the same `dupe` twice.
+          Some(Value {
+            origin: Origin::Dupe,
+            expr,
+          }) if !lints.last().is_some_and(|lint: &Lint| lint.expr == *expr) => {
+            lints.push(Lint {
+              kind: LintKind::UnusedDupe,
+              expr: expr.clone(),
+            })
+          }
+          _ => {}
+        },
+        // Both copies are only needed if both are used.
+        Some(Intrinsic::Dupe) => {
+          stack.pop();
+          push(&mut stack, Origin::Dupe);
+          push(&mut stack, Origin::Dupe);
+        }
+        Some(Intrinsic::Swap) if stack.len() >= 2 => {
+          let len = stack.len();
+          stack.swap(len - 1, len - 2);
+        }
+        Some(Intrinsic::Rot) if stack.len() >= 3 => {
+          let len = stack.len();
+          stack[len - 3..].rotate_left(1);
+        }
+        Some(intrinsic) => match effect(intrinsic) {
+          Some((pops, pushes)) => {
+            stack.truncate(stack.len().saturating_sub(pops));
+            for _ in 0..pushes {
+              push(&mut stack, Origin::Other);
+            }
+          }
+          None => stack.clear(),
+        },
+        None => stack.clear(),
+      },
+
+      ExprKind::Function { .. }
+      | ExprKind::SExpr { .. }
+      | ExprKind::Underscore => stack.clear(),
+    }
+  }
+
+  lints
+}
+
+/// Returns how many values `exprs` pop from the stack they're run on and how
+/// many they push onto it, if only literals and intrinsics with a fixed stack
+/// effect are used.
+///
+/// This is used to describe a function by its body, such as `(fn 2 *)`,
+/// which pops one value and pushes one.
+pub fn stack_effect(exprs: &[Expr]) -> Option<(usize, usize)> {
+  // The height of the stack relative to where it started, and the lowest it
+  // has been.
+  let mut height = 0isize;
+  let mut lowest = 0isize;
+
+  for expr in exprs {
+    let (pops, pushes) = match expr.kind {
+      ExprKind::Nil
+      | ExprKind::Boolean(_)
+      | ExprKind::Integer(_)
+      | ExprKind::Float(_)
+      | ExprKind::String(_)
+      | ExprKind::Char(_)
+      | ExprKind::Bytes(_)
+      | ExprKind::List(_)
+      | ExprKind::Record(_)
+      | ExprKind::Map(_)
+      | ExprKind::Set(_)
+      | ExprKind::Seq(_)
+      | ExprKind::Lazy(_)
+      | ExprKind::Error(_)
+      | ExprKind::Foreign(_) => (0, 1),
+      ExprKind::Symbol(x) => match Intrinsic::from_symbol(x)? {
+        Intrinsic::Drop => (1, 0),
+        Intrinsic::Dupe => (1, 2),
+        Intrinsic::Swap => (2, 2),
+        Intrinsic::Rot => (3, 3),
+        intrinsic => effect(intrinsic)?,
+      },
+      ExprKind::Function { .. }
+      | ExprKind::SExpr { .. }
+      | ExprKind::Underscore => return None,
+    };
+
+    height -= pops as isize;
+    lowest = lowest.min(height);
+    height += pushes as isize;
+  }
+
+  Some((-lowest as usize, (height - lowest) as usize))
+}
+
+/// Returns how many values an intrinsic pops and pushes, if that's always the
+/// same.
+fn effect(intrinsic: Intrinsic) -> Option<(usize, usize)> {
+  match intrinsic {
+    Intrinsic::Add
+    | Intrinsic::Sub
+    | Intrinsic::Mul
+    | Intrinsic::Div
+    | Intrinsic::Rem
+    | Intrinsic::Eq
+    | Intrinsic::Ne
+    | Intrinsic::Lt
+    | Intrinsic::Le
+    | Intrinsic::Gt
+    | Intrinsic::Ge
+    | Intrinsic::Or
+    | Intrinsic::And
+    | Intrinsic::Concat
+    | Intrinsic::Cast => Some((2, 1)),
+    Intrinsic::Not
+    | Intrinsic::TypeOf
+    | Intrinsic::Lazy
+    | Intrinsic::Error
+    | Intrinsic::Chars
+    | Intrinsic::FromChars
+    | Intrinsic::Codepoint
+    | Intrinsic::FromCodepoint => Some((1, 1)),
+    Intrinsic::Len
+    | Intrinsic::Keys
+    | Intrinsic::Values
+    | Intrinsic::Entries => Some((1, 2)),
+    Intrinsic::Nth | Intrinsic::Prop | Intrinsic::Has | Intrinsic::Contains => {
+      Some((2, 2))
+    }
+    Intrinsic::MapNew | Intrinsic::SetNew => Some((0, 1)),
+    Intrinsic::Union | Intrinsic::Intersect | Intrinsic::Difference => {
+      Some((2, 1))
+    }
+    Intrinsic::ToList | Intrinsic::Collect => Some((1, 1)),
+    Intrinsic::Range
+    | Intrinsic::Map
+    | Intrinsic::Filter
+    | Intrinsic::Take
+    | Intrinsic::Zip
+    | Intrinsic::Any
+    | Intrinsic::All
+    | Intrinsic::SortBy
+    | Intrinsic::MinBy
+    | Intrinsic::MaxBy => Some((2, 1)),
+    Intrinsic::Sort | Intrinsic::Min | Intrinsic::Max => Some((1, 1)),
+    Intrinsic::Fold | Intrinsic::RangeBy => Some((3, 1)),
+    Intrinsic::Fmt => Some((2, 1)),
+    Intrinsic::Reverse => Some((1, 1)),
+    Intrinsic::Push | Intrinsic::Remove | Intrinsic::UnwrapOr => Some((2, 1)),
+    Intrinsic::Insert => Some((3, 1)),
+    Intrinsic::Pop => Some((1, 2)),
+    Intrinsic::Split => Some((2, 2)),
+    Intrinsic::Slice => Some((3, 1)),
+    Intrinsic::Encode | Intrinsic::Decode => Some((2, 1)),
+    Intrinsic::Def | Intrinsic::DefTest | Intrinsic::Set => Some((2, 0)),
+    Intrinsic::AssertEq => Some((2, 0)),
+    Intrinsic::Get => Some((1, 1)),
+    Intrinsic::Print | Intrinsic::Pretty => Some((1, 0)),
+    Intrinsic::Table => Some((2, 0)),
+    Intrinsic::SetPrecision => Some((1, 0)),
+    Intrinsic::Assert => Some((2, 0)),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::prelude::*;
+
+  fn lints(code: &str) -> Vec<(LintKind, String)> {
+    let mut lexer = Lexer::new(Source::new("", code));
+    let exprs = parse(&mut lexer).unwrap();
+
+    lint(&exprs)
+      .into_iter()
+      .map(|lint| (lint.kind, lint.expr.to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn finds_unused_values() {
+    assert_eq!(
+      lints("1 2 drop dupe drop"),
+      vec![
+        (LintKind::UnusedPush, "2".into()),
+        (LintKind::UnusedDupe, "dupe".into())
+      ]
+    );
+    assert_eq!(
+      lints("'(fn 1 2 swap drop)"),
+      vec![(LintKind::UnusedPush, "1".into())]
+    );
+    assert_eq!(
+      lints("1 dupe print drop"),
+      vec![(LintKind::UnusedDupe, "dupe".into())]
+    );
+    assert_eq!(
+      lints("1 dupe drop drop"),
+      vec![(LintKind::UnusedDupe, "dupe".into())]
+    );
+  }
+
+  #[test]
+  fn finds_stack_effects() {
+    let effect = |code: &str| {
+      let mut lexer = Lexer::new(Source::new("", code));
+      stack_effect(&parse(&mut lexer).unwrap())
+    };
+
+    assert_eq!(effect("2 *"), Some((1, 1)));
+    assert_eq!(effect("swap drop"), Some((2, 1)));
+    assert_eq!(effect("1 2 +"), Some((0, 1)));
+    assert_eq!(effect("dupe f"), None);
+  }
+
+  #[test]
+  fn ignores_used_values() {
+    assert_eq!(lints("1 dupe + drop"), vec![]);
+    assert_eq!(lints("1 2 3 rot + + drop"), vec![]);
+    assert_eq!(lints("\"a\" \"b\" swap concat print"), vec![]);
+    assert_eq!(lints("1 dupe f drop"), vec![]);
+  }
+}
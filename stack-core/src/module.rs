@@ -1,20 +1,37 @@
 use std::{collections::HashMap, sync::Arc};
 
+#[cfg(feature = "async")]
+use crate::future::{self, AsyncFunc};
 use crate::{
   context::Context,
+  convert::HostFn,
   engine::{Engine, RunError},
   expr::Expr,
   symbol::Symbol,
 };
 
 // pub type Func = fn(&Engine, Context, Expr) -> Result<Context, RunError>;
+#[cfg(not(feature = "sync"))]
 pub type Func =
   Arc<dyn Fn(&Engine, Context, Expr) -> Result<Context, RunError>>;
+#[cfg(feature = "sync")]
+pub type Func = Arc<
+  dyn Fn(&Engine, Context, Expr) -> Result<Context, RunError> + Send + Sync,
+>;
+
+/// Creates a [`Module`] the first time it's imported.
+#[cfg(not(feature = "sync"))]
+pub type ModuleLoader = Arc<dyn Fn() -> Module>;
+#[cfg(feature = "sync")]
+pub type ModuleLoader = Arc<dyn Fn() -> Module + Send + Sync>;
 
 #[derive(Clone)]
 pub struct Module {
   name: Symbol,
   funcs: HashMap<Symbol, Func>,
+  #[cfg(feature = "async")]
+  async_funcs: HashMap<Symbol, AsyncFunc>,
+  docs: HashMap<Symbol, String>,
 }
 
 impl Module {
@@ -23,6 +40,9 @@ impl Module {
     Self {
       name,
       funcs: HashMap::new(),
+      #[cfg(feature = "async")]
+      async_funcs: HashMap::new(),
+      docs: HashMap::new(),
     }
   }
 
@@ -38,6 +58,68 @@ impl Module {
     self
   }
 
+  #[inline]
+  pub fn with_fn<Args>(mut self, name: Symbol, f: impl HostFn<Args>) -> Self {
+    self.add_fn(name, f);
+    self
+  }
+
+  /// Adds a Rust function, converting its arguments from the stack and its
+  /// result back onto it. See [`convert`](crate::convert) for how.
+  #[inline]
+  pub fn add_fn<Args>(
+    &mut self,
+    name: Symbol,
+    f: impl HostFn<Args>,
+  ) -> &mut Self {
+    self.add_func(
+      name,
+      Arc::new(move |_, context, expr| f.call(context, &expr)),
+    )
+  }
+
+  #[cfg(feature = "async")]
+  #[inline]
+  pub fn with_async_func(mut self, name: Symbol, func: AsyncFunc) -> Self {
+    self.add_async_func(name, func);
+    self
+  }
+
+  /// Adds a function which returns a future. [`Engine::run_async`] awaits it
+  /// at the top level, and anywhere else blocks until it's ready where that
+  /// can't deadlock. See [`future`] for more.
+  ///
+  /// [`Engine::run_async`]: crate::engine::Engine::run_async
+  #[cfg(feature = "async")]
+  pub fn add_async_func(&mut self, name: Symbol, func: AsyncFunc) -> &mut Self {
+    let blocking = func.clone();
+    self.add_func(
+      name,
+      Arc::new(move |_, context, expr| future::wait(&blocking, context, expr)),
+    );
+    self.async_funcs.insert(name, func);
+    self
+  }
+
+  #[inline]
+  pub fn with_doc(mut self, name: Symbol, doc: impl Into<String>) -> Self {
+    self.add_doc(name, doc);
+    self
+  }
+
+  /// Documents the function `name`, which `stack doc` shows. Like a doc
+  /// comment, a line such as `( a b -- c )` declares its stack effect.
+  #[inline]
+  pub fn add_doc(&mut self, name: Symbol, doc: impl Into<String>) -> &mut Self {
+    self.docs.insert(name, doc.into());
+    self
+  }
+
+  #[inline]
+  pub fn doc(&self, name: Symbol) -> Option<&str> {
+    self.docs.get(&name).map(String::as_str)
+  }
+
   #[inline]
   pub const fn name(&self) -> Symbol {
     self.name
@@ -47,4 +129,16 @@ impl Module {
   pub fn func(&self, name: Symbol) -> Option<Func> {
     self.funcs.get(&name).cloned()
   }
+
+  #[cfg(feature = "async")]
+  #[inline]
+  pub fn async_func(&self, name: Symbol) -> Option<AsyncFunc> {
+    self.async_funcs.get(&name).cloned()
+  }
+
+  /// Returns the names of the functions in this [`Module`].
+  #[inline]
+  pub fn func_names(&self) -> impl Iterator<Item = Symbol> + '_ {
+    self.funcs.keys().copied()
+  }
 }
@@ -1,5 +1,6 @@
-use compact_str::ToCompactString;
-use core::fmt;
+use compact_str::CompactString;
+use core::{fmt, iter::Peekable, ops::Range};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -18,10 +19,309 @@ pub fn parse(lexer: &mut Lexer) -> Result<Vec<Expr>, ParseError> {
     let token = lexer.peek();
 
     match token.kind {
-      TokenKind::Eof => break Ok(exprs),
+      TokenKind::Eof => break,
       _ => exprs.push(parse_expr(lexer)?),
     }
   }
+
+  let source = lexer.source();
+  let mut comments = lexer.comments().iter().copied().peekable();
+  let dangling = attach_comments(
+    None,
+    exprs.iter_mut(),
+    &mut comments,
+    source.source(),
+    usize::MAX,
+  );
+
+  if let Some(info) = exprs.last_mut().and_then(|expr| expr.info.as_mut()) {
+    info.comments.dangling.extend(dangling);
+  }
+
+  Ok(exprs)
+}
+
+/// A [`Source`] along with the [`Expr`]s parsed from it, which can be edited
+/// and reparsed incrementally.
+///
+/// Only the top-level expressions touched by an edit are reparsed. Parsing
+/// stops as soon as it is back in step with a top-level expression after the
+/// edit, from which point the previously parsed [`Expr`]s are reused.
+#[derive(Debug, Clone)]
+pub struct ParsedSource {
+  source: Source,
+  exprs: Vec<Expr>,
+  error: Option<ParseError>,
+}
+
+impl ParsedSource {
+  /// Creates a [`ParsedSource`] by parsing all of a [`Source`].
+  pub fn new(source: Source) -> Self {
+    let mut lexer = Lexer::new(source.clone());
+
+    match parse(&mut lexer) {
+      Ok(exprs) => Self {
+        source,
+        exprs,
+        error: None,
+      },
+      Err(error) => Self {
+        source,
+        exprs: Vec::new(),
+        error: Some(error),
+      },
+    }
+  }
+
+  #[inline]
+  pub fn source(&self) -> &Source {
+    &self.source
+  }
+
+  /// Returns the parsed [`Expr`]s.
+  ///
+  /// If there is an error, these are the [`Expr`]s parsed before it.
+  #[inline]
+  pub fn exprs(&self) -> &[Expr] {
+    &self.exprs
+  }
+
+  #[inline]
+  pub fn error(&self) -> Option<&ParseError> {
+    self.error.as_ref()
+  }
+
+  /// Replaces the text within `range` with `text`, and reparses it.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `range` is out-of-bounds, or is not on UTF-8 sequence
+  /// boundaries.
+  pub fn edit(&mut self, range: Range<usize>, text: &str) {
+    self.source.apply_edit(range.clone(), text);
+
+    // There is nothing to reuse if the previous parse failed part-way.
+    if self.error.is_some() {
+      *self = Self::new(self.source.clone());
+      return;
+    }
+
+    let span = |expr: &Expr| expr.info.as_ref().map(|info| info.span);
+    let delta = text.len() as isize - range.len() as isize;
+    let edit_end = range.start + text.len();
+
+    // The expressions which touch the edit are reparsed, along with the
+    // whitespace and comments before them.
+    let first = self
+      .exprs
+      .partition_point(|x| span(x).is_some_and(|x| x.end < range.start));
+    let last = self
+      .exprs
+      .partition_point(|x| span(x).is_some_and(|x| x.start <= range.end));
+
+    let mut tail = self.exprs.split_off(last);
+    self.exprs.truncate(first);
+
+    for expr in self.exprs.iter_mut() {
+      relocate(expr, &self.source, 0);
+    }
+
+    let start = match self.exprs.last_mut().and_then(|x| x.info.as_mut()) {
+      Some(info) => {
+        // The comments after this are lexed again.
+        let end = info.span.end;
+        info.comments.trailing = None;
+        info.comments.dangling.retain(|x| x.start < end);
+
+        end
+      }
+      None => 0,
+    };
+
+    let mut lexer = Lexer::new(self.source.clone());
+    if start > 0 {
+      lexer = lexer.with_cursor(start);
+    }
+    let mut reused = None;
+
+    loop {
+      let token = lexer.peek();
+
+      if token.kind == TokenKind::Eof {
+        break;
+      }
+
+      if token.span.start >= edit_end {
+        let old_start = token.span.start.wrapping_add_signed(-delta);
+
+        if let Ok(i) = tail
+          .binary_search_by_key(&Some(old_start), |x| span(x).map(|x| x.start))
+        {
+          reused = Some(i);
+          break;
+        }
+      }
+
+      match parse_expr(&mut lexer) {
+        Ok(expr) => self.exprs.push(expr),
+        Err(error) => {
+          self.error = Some(error);
+          return;
+        }
+      }
+    }
+
+    let source = self.source.source();
+    let mut comments = lexer.comments().iter().copied().peekable();
+    let (prefix, region) = self.exprs.split_at_mut(first);
+    let prev = prefix.last_mut().and_then(|x| x.info.as_mut());
+
+    match reused {
+      Some(i) => {
+        let leading = attach_comments(
+          prev,
+          region.iter_mut(),
+          &mut comments,
+          source,
+          usize::MAX,
+        );
+
+        let mut tail = tail.split_off(i);
+        for expr in tail.iter_mut() {
+          relocate(expr, &self.source, delta);
+        }
+
+        if let Some(info) = tail.first_mut().and_then(|x| x.info.as_mut()) {
+          info.comments.leading = leading;
+        }
+
+        self.exprs.append(&mut tail);
+      }
+      None => {
+        let dangling = attach_comments(
+          prev,
+          region.iter_mut(),
+          &mut comments,
+          source,
+          usize::MAX,
+        );
+
+        if let Some(info) =
+          self.exprs.last_mut().and_then(|expr| expr.info.as_mut())
+        {
+          info.comments.dangling.extend(dangling);
+        }
+      }
+    }
+  }
+}
+
+/// Moves the spans of an [`Expr`] by `delta` bytes, and points it at `source`.
+fn relocate(expr: &mut Expr, source: &Source, delta: isize) {
+  let shift = |span: &mut Span| {
+    span.start = span.start.wrapping_add_signed(delta);
+    span.end = span.end.wrapping_add_signed(delta);
+  };
+
+  if let Some(info) = expr.info.as_mut() {
+    info.source = source.clone();
+    shift(&mut info.span);
+
+    let comments = &mut info.comments;
+    comments.leading.iter_mut().for_each(shift);
+    comments.trailing.iter_mut().for_each(shift);
+    comments.dangling.iter_mut().for_each(shift);
+  }
+
+  match &mut expr.kind {
+    ExprKind::Lazy(x) => relocate(x, source, delta),
+    ExprKind::List(x) => x.iter_mut().for_each(|x| relocate(x, source, delta)),
+    ExprKind::Function { body: x, .. } | ExprKind::SExpr { body: x, .. } => {
+      x.iter_mut().for_each(|x| relocate(x, source, delta))
+    }
+    ExprKind::Record(x) => {
+      x.values_mut().for_each(|x| relocate(x, source, delta))
+    }
+    _ => {}
+  }
+}
+
+/// Attaches the comments before `end` to a sequence of sibling [`Expr`]s,
+/// returning the comments after the last one.
+///
+/// A comment on the same line as the end of an [`Expr`] trails it, otherwise
+/// it leads the next [`Expr`]. `prev` is the sibling before `exprs`, if any.
+fn attach_comments<'a, I>(
+  mut prev: Option<&'a mut ExprInfo>,
+  exprs: impl IntoIterator<Item = &'a mut Expr>,
+  comments: &mut Peekable<I>,
+  source: &str,
+  end: usize,
+) -> Vec<Span>
+where
+  I: Iterator<Item = Span>,
+{
+  let mut leading = Vec::new();
+
+  for Expr { kind, info } in exprs {
+    let Some(info) = info.as_mut() else {
+      continue;
+    };
+
+    take_comments(&mut prev, &mut leading, comments, source, info.span.start);
+    info.comments.leading = core::mem::take(&mut leading);
+
+    let end = info.span.end;
+    info.comments.dangling = match kind {
+      ExprKind::Lazy(x) => {
+        attach_comments(None, [x.as_mut()], comments, source, end)
+      }
+      ExprKind::List(x) => {
+        attach_comments(None, x.iter_mut(), comments, source, end)
+      }
+      ExprKind::Function { body: x, .. } | ExprKind::SExpr { body: x, .. } => {
+        attach_comments(None, x.iter_mut(), comments, source, end)
+      }
+      ExprKind::Record(x) => {
+        let mut values = x.values_mut().collect::<Vec<_>>();
+        values.sort_by_key(|x| x.info.as_ref().map(|info| info.span.start));
+
+        attach_comments(None, values, comments, source, end)
+      }
+      _ => Vec::new(),
+    };
+
+    prev = Some(info);
+  }
+
+  take_comments(&mut prev, &mut leading, comments, source, end);
+  leading
+}
+
+fn take_comments<I>(
+  prev: &mut Option<&mut ExprInfo>,
+  leading: &mut Vec<Span>,
+  comments: &mut Peekable<I>,
+  source: &str,
+  end: usize,
+) where
+  I: Iterator<Item = Span>,
+{
+  while let Some(comment) = comments.next_if(|x| x.start < end) {
+    match prev {
+      Some(info)
+        if info.comments.trailing.is_none()
+          && leading.is_empty()
+          && !source
+            .get(info.span.end..comment.start)
+            .unwrap_or_default()
+            .contains('\n') =>
+      {
+        info.comments.trailing = Some(comment);
+      }
+      _ => leading.push(comment),
+    }
+  }
 }
 
 fn parse_expr(lexer: &mut Lexer) -> Result<Expr, ParseError> {
@@ -29,42 +329,37 @@ fn parse_expr(lexer: &mut Lexer) -> Result<Expr, ParseError> {
   let token = lexer.next();
 
   match token.kind {
-    TokenKind::Invalid
-    | TokenKind::Eof
-    | TokenKind::RightSquare
-    | TokenKind::RightParen
-    | TokenKind::RightCurly => Err(ParseError {
-      source,
-      kind: ParseErrorKind::UnexpectedToken(token),
-    }),
-
     TokenKind::Apostrophe => {
-      let next_token = lexer.peek();
       let expr = parse_expr(lexer)?;
+      let end = expr
+        .info
+        .as_ref()
+        .map(|info| info.span.end)
+        .unwrap_or(token.span.end);
 
       Ok(Expr {
         kind: ExprKind::Lazy(Box::new(expr)),
-        info: Some(ExprInfo {
+        info: Some(ExprInfo::new(
           source,
-          span: Span {
+          Span {
             start: token.span.start,
-            end: next_token.span.end,
+            end,
           },
-        }),
+        )),
       })
     }
     TokenKind::LeftSquare => {
       let (list, end_span) = parse_list(lexer)?;
 
       Ok(Expr {
-        kind: ExprKind::List(list),
-        info: Some(ExprInfo {
+        kind: ExprKind::List(list.into()),
+        info: Some(ExprInfo::new(
           source,
-          span: Span {
+          Span {
             start: token.span.start,
             end: end_span.end,
           },
-        }),
+        )),
       })
     }
     TokenKind::LeftParen => {
@@ -101,13 +396,13 @@ fn parse_expr(lexer: &mut Lexer) -> Result<Expr, ParseError> {
 
       Ok(Expr {
         kind,
-        info: Some(ExprInfo {
+        info: Some(ExprInfo::new(
           source,
-          span: Span {
+          Span {
             start: token.span.start,
             end: end_span.end,
           },
-        }),
+        )),
       })
     }
 
@@ -115,83 +410,96 @@ fn parse_expr(lexer: &mut Lexer) -> Result<Expr, ParseError> {
       let (record, end_span) = parse_record(lexer)?;
 
       Ok(Expr {
-        kind: ExprKind::Record(record),
-        info: Some(ExprInfo {
+        kind: ExprKind::Record(record.into()),
+        info: Some(ExprInfo::new(
           source,
-          span: Span {
+          Span {
             start: token.span.start,
             end: end_span.end,
           },
-        }),
+        )),
       })
     }
 
+    _ => Ok(Expr {
+      kind: parse_atom(&source, token)?,
+      info: Some(ExprInfo::new(source, token.span)),
+    }),
+  }
+}
+
+/// Parses a token which is an [`Expr`] on its own, such as a number or a
+/// symbol.
+pub(crate) fn parse_atom(
+  source: &Source,
+  token: Token,
+) -> Result<ExprKind, ParseError> {
+  match token.kind {
     TokenKind::Integer => {
       let slice = &source.source()[token.span.start..token.span.end];
-      let literal = slice.parse().map_err(|_| ParseError {
+      let literal = parse_integer(slice).map_err(|e| ParseError {
         source: source.clone(),
-        kind: ParseErrorKind::InvalidLiteral(token),
+        kind: ParseErrorKind::InvalidNumber(token, e),
       })?;
 
-      Ok(Expr {
-        kind: ExprKind::Integer(literal),
-        info: Some(ExprInfo {
-          source,
-          span: token.span,
-        }),
-      })
+      Ok(ExprKind::Integer(literal))
     }
     TokenKind::Float => {
       let slice = &source.source()[token.span.start..token.span.end];
-      let literal = slice.parse().map_err(|_| ParseError {
+      let literal = parse_float(slice).map_err(|e| ParseError {
         source: source.clone(),
-        kind: ParseErrorKind::InvalidLiteral(token),
+        kind: ParseErrorKind::InvalidNumber(token, e),
       })?;
 
-      Ok(Expr {
-        kind: ExprKind::Float(literal),
-        info: Some(ExprInfo {
-          source,
-          span: token.span,
-        }),
-      })
+      Ok(ExprKind::Float(literal))
     }
     TokenKind::String => {
       //   // Discard the quotation marks from the slice.
       let slice = &source.source()[token.span.start + 1..token.span.end - 1];
 
-      Ok(Expr {
-        kind: ExprKind::String(
-          slice
-            .replace("\\n", "\n")
-            .replace("\\t", "\t")
-            .replace("\\r", "\r")
-            .replace("\\0", "\0")
-            .to_compact_string(),
-        ),
-        info: Some(ExprInfo {
-          source,
-          span: token.span,
-        }),
-      })
+      Ok(ExprKind::String(parse_string(slice)))
+    }
+    TokenKind::Bytes => {
+      // Discard the prefix and quotation marks from the slice.
+      let slice = &source.source()[token.span.start + 2..token.span.end - 1];
+      let literal = parse_bytes(slice).ok_or_else(|| ParseError {
+        source: source.clone(),
+        kind: ParseErrorKind::InvalidLiteral(token),
+      })?;
+
+      Ok(ExprKind::Bytes(literal))
+    }
+    TokenKind::Char => {
+      // Discard the apostrophes from the slice.
+      let slice = &source.source()[token.span.start + 1..token.span.end - 1];
+      let literal = match slice {
+        "\\n" => '\n',
+        "\\t" => '\t',
+        "\\r" => '\r',
+        "\\0" => '\0',
+        "\\\\" => '\\',
+        "\\'" => '\'',
+        slice => slice.chars().next().unwrap_or_default(),
+      };
+
+      Ok(ExprKind::Char(literal))
     }
     TokenKind::Symbol => {
       let slice = &source.source()[token.span.start..token.span.end];
 
-      Ok(Expr {
-        kind: match slice {
-          "nil" => ExprKind::Nil,
-          "true" => ExprKind::Boolean(true),
-          "false" => ExprKind::Boolean(false),
-          "_" => ExprKind::Underscore,
-          slice => ExprKind::Symbol(Symbol::from_ref(slice)),
-        },
-        info: Some(ExprInfo {
-          source,
-          span: token.span,
-        }),
+      Ok(match slice {
+        "nil" => ExprKind::Nil,
+        "true" => ExprKind::Boolean(true),
+        "false" => ExprKind::Boolean(false),
+        "_" => ExprKind::Underscore,
+        slice => ExprKind::Symbol(Symbol::from_ref(slice)),
       })
     }
+
+    _ => Err(ParseError {
+      source: source.clone(),
+      kind: ParseErrorKind::UnexpectedToken(token),
+    }),
   }
 }
 
@@ -227,8 +535,6 @@ fn parse_record(
   lexer: &mut Lexer,
 ) -> Result<(HashMap<Symbol, Expr>, Span), ParseError> {
   let mut record = HashMap::new();
-  let mut key: Option<Symbol> = None;
-  let mut val: Option<Expr> = None;
 
   loop {
     let token = lexer.peek();
@@ -236,28 +542,53 @@ fn parse_record(
     match token.kind {
       TokenKind::RightCurly => break Ok((record, lexer.next().span)),
       _ => {
-        if key.is_none() {
-          key = Some(parse_expr(lexer)?.kind.into());
+        let key = parse_record_key(lexer)?;
 
+        // A key without a value is ignored.
+        if lexer.peek().kind == TokenKind::RightCurly {
           continue;
         }
 
-        if val.is_none() {
-          val = Some(parse_expr(lexer)?)
-        }
+        record.insert(key, parse_expr(lexer)?);
+      }
+    }
+  }
+}
 
-        if let (Some(k), Some(v)) = (key, val.clone()) {
-          record.insert(k, v);
+/// Parses a record key, which can be followed by a colon, as in `{a: 1}` or
+/// `{"a" : 1}`.
+fn parse_record_key(lexer: &mut Lexer) -> Result<Symbol, ParseError> {
+  let key = record_key(parse_expr(lexer)?.kind);
+  skip_record_colon(lexer);
 
-          key = None;
-          val = None;
-        }
-      }
+  Ok(key)
+}
+
+/// Returns the key of a record for `kind`, without its trailing colon.
+pub(crate) fn record_key(kind: ExprKind) -> Symbol {
+  match kind {
+    ExprKind::Symbol(x)
+      if x.as_str().len() > 1 && x.as_str().ends_with(':') =>
+    {
+      Symbol::from_ref(&x.as_str()[..x.as_str().len() - 1])
     }
+    kind => kind.into(),
+  }
+}
+
+/// Skips a colon after a record key which is separated from it.
+pub(crate) fn skip_record_colon(lexer: &mut Lexer) {
+  let token = lexer.peek();
+  let source = lexer.source();
+  if token.kind == TokenKind::Symbol
+    && &source.source()[token.span.start..token.span.end] == ":"
+  {
+    lexer.next();
   }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParseError {
   pub source: Source,
   pub kind: ParseErrorKind,
@@ -281,18 +612,169 @@ impl fmt::Display for ParseError {
   }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Parses the contents of a string literal, where an unknown escape is kept
+/// as it was written.
+fn parse_string(slice: &str) -> CompactString {
+  let mut string = CompactString::with_capacity(slice.len());
+  let mut chars = slice.chars();
+
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      string.push(c);
+      continue;
+    }
+
+    match chars.next() {
+      Some('n') => string.push('\n'),
+      Some('t') => string.push('\t'),
+      Some('r') => string.push('\r'),
+      Some('0') => string.push('\0'),
+      Some('\\') => string.push('\\'),
+      Some('"') => string.push('"'),
+      Some(c) => {
+        string.push('\\');
+        string.push(c);
+      }
+      None => string.push('\\'),
+    }
+  }
+
+  string
+}
+
+/// Parses the contents of a bytes literal, where any character is its UTF-8
+/// bytes and `\xHH` is a single byte.
+fn parse_bytes(slice: &str) -> Option<Vec<u8>> {
+  let mut bytes = Vec::with_capacity(slice.len());
+  let mut chars = slice.chars();
+
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      let mut buf = [0; 4];
+      bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+      continue;
+    }
+
+    let byte = match chars.next()? {
+      'n' => b'\n',
+      't' => b'\t',
+      'r' => b'\r',
+      '0' => b'\0',
+      '\\' => b'\\',
+      '"' => b'"',
+      'x' => {
+        let hex = [chars.next()?, chars.next()?];
+        let hex = hex.iter().collect::<String>();
+        u8::from_str_radix(&hex, 16).ok()?
+      }
+      _ => return None,
+    };
+
+    bytes.push(byte);
+  }
+
+  Some(bytes)
+}
+
+/// Parses an integer literal, such as `-12`, `1_000`, `0xFF`, `0o77` or
+/// `0b1010`.
+fn parse_integer(slice: &str) -> Result<i64, NumberError> {
+  let digits = slice.replace('_', "");
+  let (sign, digits) = match digits.strip_prefix('-') {
+    Some(digits) => ("-", digits),
+    None => ("", digits.as_str()),
+  };
+
+  let (radix, digits) = match digits.get(..2) {
+    Some("0x" | "0X") => (16, &digits[2..]),
+    Some("0o" | "0O") => (8, &digits[2..]),
+    Some("0b" | "0B") => (2, &digits[2..]),
+    _ => (10, digits),
+  };
+
+  if digits.is_empty() {
+    return Err(NumberError::MissingDigits);
+  }
+
+  if !digits.chars().all(|c| c.is_digit(radix)) {
+    return Err(NumberError::InvalidDigit(radix));
+  }
+
+  i64::from_str_radix(&format!("{sign}{digits}"), radix)
+    .map_err(|_| NumberError::OutOfRange)
+}
+
+/// Parses a float literal, such as `1.5`, `1_000.0` or `1.5e-3`.
+fn parse_float(slice: &str) -> Result<f64, NumberError> {
+  let digits = slice.replace('_', "");
+
+  match digits.split_once(['e', 'E']) {
+    Some((_, exponent))
+      if !exponent
+        .trim_start_matches(['+', '-'])
+        .starts_with(|c: char| c.is_ascii_digit()) =>
+    {
+      Err(NumberError::MissingExponent)
+    }
+    _ => digits.parse().map_err(|_| NumberError::InvalidDigit(10)),
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
 pub enum ParseErrorKind {
   UnexpectedToken(Token),
   InvalidLiteral(Token),
+  InvalidNumber(Token, NumberError),
   Parenthetical(Token),
 }
 
+/// Why a number literal is invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum NumberError {
+  /// There are no digits after the prefix, such as in `0x`.
+  MissingDigits,
+  /// A digit isn't valid in the base, such as in `0b12`.
+  InvalidDigit(u32),
+  /// There are no digits in the exponent, such as in `1e`.
+  MissingExponent,
+  /// The integer doesn't fit in 64 bits.
+  OutOfRange,
+}
+
+impl fmt::Display for NumberError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::MissingDigits => write!(f, "has no digits"),
+      Self::InvalidDigit(radix) => {
+        write!(f, "has a digit which isn't valid in base {radix}")
+      }
+      Self::MissingExponent => write!(f, "has no digits in its exponent"),
+      Self::OutOfRange => write!(f, "is too large for an integer"),
+    }
+  }
+}
+
 impl ParseErrorKind {
+  /// Returns the [`Token`] that caused the error.
+  #[inline]
+  pub const fn token(self) -> Token {
+    match self {
+      Self::UnexpectedToken(x) => x,
+      Self::InvalidLiteral(x) => x,
+      Self::InvalidNumber(x, _) => x,
+      Self::Parenthetical(x) => x,
+    }
+  }
+
   pub fn location(self, source: &Source) -> Option<Location> {
     match self {
       Self::UnexpectedToken(x) => source.location(x.span.start),
       Self::InvalidLiteral(x) => source.location(x.span.start),
+      Self::InvalidNumber(x, _) => source.location(x.span.start),
       Self::Parenthetical(x) => source.location(x.span.start),
     }
   }
@@ -303,6 +785,7 @@ impl fmt::Display for ParseErrorKind {
     match self {
       Self::UnexpectedToken(x) => write!(f, "unexpected token {x}"),
       Self::InvalidLiteral(x) => write!(f, "invalid literal {x}"),
+      Self::InvalidNumber(_, e) => write!(f, "number literal {e}"),
       Self::Parenthetical(x) => {
         write!(f, "mismatched/unknown usage of parenthesis {x}")
       }
@@ -320,9 +803,191 @@ mod test {
   }
 
   #[case("" => Ok(Vec::<Expr>::new()) ; "empty")]
-  #[case("1" => Ok(vec![Expr { kind: ExprKind::Integer(1), info: Some(ExprInfo { source: s("1"), span: Span { start: 0, end: 1 } }) }]))]
+  #[case("1" => Ok(vec![Expr { kind: ExprKind::Integer(1), info: Some(ExprInfo::new(s("1"), Span { start: 0, end: 1 })) }]))]
   fn parse(source: &str) -> Result<Vec<Expr>, ParseError> {
     let mut lexer = Lexer::new(s(source));
     super::parse(&mut lexer)
   }
+
+  #[test]
+  fn attaches_comments() {
+    let source = "; a\n; b\n1 ; c\n'(fn\n  ; d\n  2 ; e\n  ; f\n)\n; g";
+    let mut lexer = Lexer::new(s(source));
+    let exprs = super::parse(&mut lexer).unwrap();
+
+    let texts = |info: &ExprInfo, spans: &[Span]| {
+      spans
+        .iter()
+        .map(|span| info.comment_text(*span).to_owned())
+        .collect::<Vec<_>>()
+    };
+
+    let one = exprs[0].info.as_ref().unwrap();
+    assert_eq!(one.leading_comments().collect::<Vec<_>>(), vec!["a", "b"]);
+    assert_eq!(one.comment_text(one.comments.trailing.unwrap()), "c");
+
+    let lazy = exprs[1].info.as_ref().unwrap();
+    assert!(lazy.comments.leading.is_empty());
+    assert_eq!(texts(lazy, &lazy.comments.dangling), vec!["g"]);
+
+    let ExprKind::Lazy(ref func) = exprs[1].kind else {
+      panic!("expected a lazy expression");
+    };
+    let ExprKind::Function { ref body, .. } = func.kind else {
+      panic!("expected a function");
+    };
+
+    let func = func.info.as_ref().unwrap();
+    assert_eq!(texts(func, &func.comments.dangling), vec!["f"]);
+
+    let two = body[0].info.as_ref().unwrap();
+    assert_eq!(two.leading_comments().collect::<Vec<_>>(), vec!["d"]);
+    assert_eq!(two.comment_text(two.comments.trailing.unwrap()), "e");
+  }
+
+  #[test]
+  fn attaches_doc_comments() {
+    let source =
+      "; not a doc\n## Doubles a number.\n##\n##   - indented\n'(fn 2 *)";
+    let mut lexer = Lexer::new(s(source));
+    let exprs = super::parse(&mut lexer).unwrap();
+
+    let info = exprs[0].info.as_ref().unwrap();
+    assert_eq!(
+      info.doc().as_deref(),
+      Some("Doubles a number.\n\n  - indented")
+    );
+    assert_eq!(exprs.len(), 1);
+  }
+
+  #[test]
+  fn parses_number_literals() {
+    let kinds = |source| {
+      parse(source)
+        .unwrap()
+        .into_iter()
+        .map(|x| x.kind)
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      kinds("0xFF -0o17 0b1010 1_000_000"),
+      vec![
+        ExprKind::Integer(255),
+        ExprKind::Integer(-15),
+        ExprKind::Integer(10),
+        ExprKind::Integer(1_000_000),
+      ]
+    );
+    assert_eq!(
+      kinds("1.5e-3 2E2 1_0.2_5"),
+      vec![
+        ExprKind::Float(1.5e-3),
+        ExprKind::Float(200.0),
+        ExprKind::Float(10.25),
+      ]
+    );
+  }
+
+  #[case("0x" => NumberError::MissingDigits ; "missing digits")]
+  #[case("0b102" => NumberError::InvalidDigit(2) ; "invalid binary digit")]
+  #[case("0o8" => NumberError::InvalidDigit(8) ; "invalid octal digit")]
+  #[case("1e" => NumberError::MissingExponent ; "missing exponent")]
+  #[case("1.5e+" => NumberError::MissingExponent ; "signed missing exponent")]
+  #[case("9223372036854775808" => NumberError::OutOfRange ; "out of range")]
+  fn invalid_numbers(source: &str) -> NumberError {
+    match parse(source).unwrap_err().kind {
+      ParseErrorKind::InvalidNumber(_, e) => e,
+      kind => panic!("expected an invalid number, found {kind:?}"),
+    }
+  }
+
+  #[test]
+  fn parses_bytes_literals() {
+    assert_eq!(
+      parse("b\"a\\n\\x00\\xff\\\"é\"").unwrap()[0].kind,
+      ExprKind::Bytes(vec![b'a', b'\n', 0, 255, b'"', 0xC3, 0xA9])
+    );
+    assert!(parse("b\"\\xZZ\"").is_err());
+    assert!(parse("b\"\\x1\"").is_err());
+  }
+
+  #[test]
+  fn parses_record_literals() {
+    let expected = parse("{a 1 b {c 2}}").unwrap();
+
+    assert_eq!(parse("{a: 1, b: {c: 2}}").unwrap(), expected);
+    assert_eq!(parse("{\"a\" : 1 b: {c: 2,}}").unwrap(), expected);
+  }
+
+  fn shape(exprs: &[Expr], out: &mut Vec<String>) {
+    for expr in exprs {
+      let info = expr.info.as_ref().unwrap();
+      out.push(format!(
+        "{:?} {:?} {:?}",
+        expr.kind, info.span, info.comments
+      ));
+
+      match &expr.kind {
+        ExprKind::Lazy(x) => shape(core::slice::from_ref(x), out),
+        ExprKind::List(x) => shape(x, out),
+        ExprKind::Function { body: x, .. }
+        | ExprKind::SExpr { body: x, .. } => shape(x, out),
+        _ => {}
+      }
+    }
+  }
+
+  #[case("1 2 3", 2..3, "42" ; "replace atom")]
+  #[case("1 2 3", 1..1, "; hi\n" ; "insert comment")]
+  #[case("1 [2 3] 4", 0..0, "[" ; "unbalanced")]
+  #[case("1 [2 3 4", 3..3, "]" ; "rebalance")]
+  #[case("'(fn 1) 'a def ; one\n\n'(fn 2) 'b def", 5..6, "10 +" ; "inside function")]
+  #[case("1 ; a\n2 ; b\n3 ; c", 7..8, "4" ; "keeps comments")]
+  #[case("1 2 3 ; end", 4..5, "" ; "remove last")]
+  #[case("1 2 3", 0..5, "" ; "remove all")]
+  fn reparses_edits(source: &str, range: Range<usize>, text: &str) {
+    let mut parsed = ParsedSource::new(s(source));
+    parsed.edit(range.clone(), text);
+
+    let mut expected = source.to_owned();
+    expected.replace_range(range, text);
+    let expected = ParsedSource::new(s(&expected));
+
+    assert_eq!(parsed.source().source(), expected.source().source());
+    assert_eq!(parsed.error(), expected.error());
+
+    if expected.error().is_none() {
+      let (mut a, mut b) = (Vec::new(), Vec::new());
+      shape(parsed.exprs(), &mut a);
+      shape(expected.exprs(), &mut b);
+
+      assert_eq!(a, b);
+      assert!(parsed
+        .exprs()
+        .iter()
+        .all(|x| x.info.as_ref().unwrap().source == *parsed.source()));
+    }
+  }
+
+  #[test]
+  fn reparses_many_edits() {
+    let mut parsed = ParsedSource::new(s(""));
+    let mut text = String::new();
+
+    for (i, c) in "'(fn 1 2 +) 'f def ; call it\n[1 2] f\n".char_indices() {
+      parsed.edit(i..i, &c.to_string());
+      text.push(c);
+
+      let expected = ParsedSource::new(s(&text));
+      let (mut a, mut b) = (Vec::new(), Vec::new());
+      shape(parsed.exprs(), &mut a);
+      shape(expected.exprs(), &mut b);
+
+      assert_eq!(parsed.error(), expected.error(), "after {text:?}");
+      if expected.error().is_none() {
+        assert_eq!(a, b, "after {text:?}");
+      }
+    }
+  }
 }
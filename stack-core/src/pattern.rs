@@ -0,0 +1,238 @@
+//! Patterns, which are used by `match` to test and destructure values.
+//!
+//! A pattern is an unevaluated [`Expr`]:
+//!
+//! - `_` matches anything.
+//! - A symbol matches anything and binds it to that name.
+//! - A lazy expression, such as `'foo`, matches the expression it wraps.
+//! - Literals (`nil`, booleans, numbers, strings, chars and bytes) match equal
+//!   values.
+//! - `(type)` or `(type pattern)` matches a value of that type, such as
+//!   `(integer n)`, and matches the value against the inner pattern. For
+//!   `(error pattern)`, the inner pattern matches the error's value.
+//! - `[a b]` matches a list of the same length, element by element. If the
+//!   last element is a symbol starting with `..`, such as `..rest`, it matches
+//!   any remaining elements as a list (and `..` alone ignores them).
+//! - `{key: pattern}` matches a record which has each of the keys, with the
+//!   values matching their patterns. Other keys are ignored.
+//!
+//! A `(type)` pattern which doesn't name a type, such as `(foo n)`, or which
+//! has more than one inner pattern is malformed, as is a list pattern with a
+//! rest element which isn't last, such as `[a ..r b]`. [`is_valid`] checks
+//! for both.
+
+use crate::{
+  expr::{Expr, ExprKind},
+  symbol::Symbol,
+};
+
+/// The names bound by a successful match, in the order they were bound.
+pub type Bindings = Vec<(Symbol, Expr)>;
+
+/// Returns whether `pattern` is well-formed, which is whether each of its
+/// `(type)` patterns names a type and has at most one inner pattern, and each
+/// of its list patterns only has a rest element last.
+pub fn is_valid(pattern: &Expr) -> bool {
+  match &pattern.kind {
+    ExprKind::SExpr { call, body } => {
+      ExprKind::TYPES.contains(&call.as_str())
+        && body.len() <= 1
+        && body.iter().all(is_valid)
+    }
+    ExprKind::List(x) => {
+      // A rest pattern can only be the last element.
+      let init = x.split_last().map_or(&[][..], |(_, init)| init);
+      x.iter().all(is_valid) && !init.iter().any(|x| rest_name(x).is_some())
+    }
+    ExprKind::Record(x) => x.values().all(is_valid),
+    _ => true,
+  }
+}
+
+/// Returns the bindings if `value` matches `pattern`, or [`None`] if it
+/// doesn't.
+pub fn match_pattern(pattern: &Expr, value: &Expr) -> Option<Bindings> {
+  let mut bindings = Vec::new();
+  matches(pattern, value, &mut bindings).then_some(bindings)
+}
+
+fn matches(pattern: &Expr, value: &Expr, bindings: &mut Bindings) -> bool {
+  match &pattern.kind {
+    ExprKind::Underscore => true,
+    ExprKind::Symbol(x) => {
+      bindings.push((*x, value.clone()));
+      true
+    }
+    ExprKind::Lazy(x) => x.kind == value.kind,
+
+    ExprKind::Nil
+    | ExprKind::Boolean(_)
+    | ExprKind::Integer(_)
+    | ExprKind::Float(_)
+    | ExprKind::String(_)
+    | ExprKind::Char(_)
+    | ExprKind::Bytes(_) => pattern.kind == value.kind,
+
+    ExprKind::SExpr { call, body } => {
+      call.as_str() == value.kind.type_of()
+        && match body.as_slice() {
+          [] => true,
+          [inner] => match value.kind {
+            ExprKind::Error(ref x) => matches(inner, x, bindings),
+            _ => matches(inner, value, bindings),
+          },
+          _ => false,
+        }
+    }
+
+    ExprKind::List(patterns) => {
+      let ExprKind::List(ref values) = value.kind else {
+        return false;
+      };
+
+      let (patterns, rest) = match patterns.split_last() {
+        Some((last, init)) => match rest_name(last) {
+          Some(rest) => (init, Some(rest)),
+          None => (patterns.as_slice(), None),
+        },
+        None => (patterns.as_slice(), None),
+      };
+
+      let len_matches = match rest {
+        Some(_) => values.len() >= patterns.len(),
+        None => values.len() == patterns.len(),
+      };
+
+      if !len_matches
+        || !patterns
+          .iter()
+          .zip(values)
+          .all(|(pattern, value)| matches(pattern, value, bindings))
+      {
+        return false;
+      }
+
+      if let Some(Some(rest)) = rest {
+        let rest_values = values[patterns.len()..].to_vec();
+        bindings.push((rest, ExprKind::List(rest_values.into()).into()));
+      }
+
+      true
+    }
+
+    ExprKind::Record(patterns) => {
+      let ExprKind::Record(ref values) = value.kind else {
+        return false;
+      };
+
+      patterns.iter().all(|(key, pattern)| {
+        values
+          .get(key)
+          .is_some_and(|value| matches(pattern, value, bindings))
+      })
+    }
+
+    ExprKind::Function { .. } => false,
+    ExprKind::Map(_)
+    | ExprKind::Set(_)
+    | ExprKind::Seq(_)
+    | ExprKind::Error(_)
+    | ExprKind::Foreign(_) => pattern.kind == value.kind,
+  }
+}
+
+/// Returns the name of a rest pattern, such as `..rest`, which is [`None`]
+/// inside if the rest is ignored with `..`.
+fn rest_name(pattern: &Expr) -> Option<Option<Symbol>> {
+  match &pattern.kind {
+    ExprKind::Symbol(x) => {
+      x.as_str().strip_prefix("..").map(|name| match name {
+        "" | "_" => None,
+        name => Some(Symbol::from_ref(name)),
+      })
+    }
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::prelude::*;
+
+  fn expr(code: &str) -> Expr {
+    let mut lexer = Lexer::new(Source::new("", code));
+    parse(&mut lexer).unwrap().remove(0)
+  }
+
+  fn bindings(pattern: &str, value: &str) -> Option<Vec<(String, String)>> {
+    match_pattern(&expr(pattern), &expr(value)).map(|x| {
+      x.into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+    })
+  }
+
+  #[test]
+  fn matches_literals_and_types() {
+    assert_eq!(bindings("1", "1"), Some(vec![]));
+    assert_eq!(bindings("1", "2"), None);
+    assert_eq!(bindings("\"a\"", "\"a\""), Some(vec![]));
+    assert_eq!(bindings("'foo", "foo"), Some(vec![]));
+    assert_eq!(bindings("_", "[1 2]"), Some(vec![]));
+    assert_eq!(bindings("(integer)", "1"), Some(vec![]));
+    assert_eq!(bindings("(string)", "1"), None);
+    assert_eq!(
+      bindings("(integer n)", "1"),
+      Some(vec![("n".into(), "1".into())])
+    );
+  }
+
+  #[test]
+  fn validates_patterns() {
+    assert!(is_valid(&expr("(integer n)")));
+    assert!(is_valid(&expr("[a (string) {b: (error e)}]")));
+    assert!(!is_valid(&expr("(foo n)")));
+    assert!(!is_valid(&expr("(integer a b)")));
+    assert!(!is_valid(&expr("[a {b: (foo)}]")));
+
+    assert!(is_valid(&expr("[a ..r]")));
+    assert!(is_valid(&expr("[[..r] ..]")));
+    assert!(!is_valid(&expr("[a ..r ..s]")));
+    assert!(!is_valid(&expr("[..r a]")));
+    assert!(!is_valid(&expr("[.. a]")));
+    assert!(!is_valid(&expr("{a: [..r b]}")));
+  }
+
+  #[test]
+  fn destructures_lists() {
+    assert_eq!(
+      bindings("[a 2 c]", "[1 2 3]"),
+      Some(vec![("a".into(), "1".into()), ("c".into(), "3".into())])
+    );
+    assert_eq!(bindings("[a b]", "[1 2 3]"), None);
+    assert_eq!(
+      bindings("[a ..rest]", "[1 2 3]"),
+      Some(vec![
+        ("a".into(), "1".into()),
+        ("rest".into(), "[2 3]".into())
+      ])
+    );
+    assert_eq!(
+      bindings("[a ..]", "[1 2 3]"),
+      Some(vec![("a".into(), "1".into())])
+    );
+    assert_eq!(bindings("[a b ..rest]", "[1]"), None);
+  }
+
+  #[test]
+  fn destructures_records() {
+    assert_eq!(
+      bindings("{name: n, age: 30}", "{name: \"john\", age: 30, x: 1}"),
+      Some(vec![("n".into(), "john".into())])
+    );
+    assert_eq!(bindings("{name: n}", "{age: 30}"), None);
+    assert_eq!(bindings("{age: 31}", "{age: 30}"), None);
+  }
+}
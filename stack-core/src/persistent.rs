@@ -0,0 +1,204 @@
+//! Copy-on-write values, which lists and records are stored in.
+//!
+//! Cloning a [`Persistent`] only clones a pointer, so `dupe`ing a large list
+//! or passing it to a function is O(1). The value is cloned the first time
+//! it's changed while it's shared, so clones never see each other's changes.
+//!
+//! Without the `sync` feature it's an `Rc<T>`, and with it it's an `Arc<T>`,
+//! like [`Shared`](crate::shared::Shared).
+
+use core::{fmt, ops};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[cfg(not(feature = "sync"))]
+type Inner<T> = std::rc::Rc<T>;
+#[cfg(feature = "sync")]
+type Inner<T> = std::sync::Arc<T>;
+
+/// A value which is shared between its clones until it's changed.
+pub struct Persistent<T>(Inner<T>);
+
+impl<T> Persistent<T> {
+  #[inline]
+  pub fn new(value: T) -> Self {
+    Self(Inner::new(value))
+  }
+
+  /// Returns whether both share the same value.
+  #[inline]
+  pub fn ptr_eq(&self, other: &Self) -> bool {
+    Inner::ptr_eq(&self.0, &other.0)
+  }
+}
+
+impl<T> Persistent<T>
+where
+  T: Clone,
+{
+  /// Returns the value, only cloning it if it's shared.
+  #[inline]
+  pub fn into_inner(self) -> T {
+    Inner::try_unwrap(self.0).unwrap_or_else(|x| (*x).clone())
+  }
+
+  /// Returns a mutable reference to the value, cloning it first if it's
+  /// shared.
+  #[inline]
+  pub fn make_mut(&mut self) -> &mut T {
+    Inner::make_mut(&mut self.0)
+  }
+}
+
+impl<T> Clone for Persistent<T> {
+  #[inline]
+  fn clone(&self) -> Self {
+    Self(self.0.clone())
+  }
+}
+
+impl<T> Default for Persistent<T>
+where
+  T: Default,
+{
+  #[inline]
+  fn default() -> Self {
+    Self::new(T::default())
+  }
+}
+
+impl<T> ops::Deref for Persistent<T> {
+  type Target = T;
+
+  #[inline]
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl<T> ops::DerefMut for Persistent<T>
+where
+  T: Clone,
+{
+  #[inline]
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    self.make_mut()
+  }
+}
+
+impl<T> From<T> for Persistent<T> {
+  #[inline]
+  fn from(value: T) -> Self {
+    Self::new(value)
+  }
+}
+
+impl<T, A> FromIterator<A> for Persistent<T>
+where
+  T: FromIterator<A>,
+{
+  #[inline]
+  fn from_iter<I>(iter: I) -> Self
+  where
+    I: IntoIterator<Item = A>,
+  {
+    Self::new(T::from_iter(iter))
+  }
+}
+
+impl<T> IntoIterator for Persistent<T>
+where
+  T: Clone + IntoIterator,
+{
+  type Item = T::Item;
+  type IntoIter = T::IntoIter;
+
+  #[inline]
+  fn into_iter(self) -> Self::IntoIter {
+    self.into_inner().into_iter()
+  }
+}
+
+impl<'a, T> IntoIterator for &'a Persistent<T>
+where
+  &'a T: IntoIterator,
+{
+  type Item = <&'a T as IntoIterator>::Item;
+  type IntoIter = <&'a T as IntoIterator>::IntoIter;
+
+  #[inline]
+  fn into_iter(self) -> Self::IntoIter {
+    (*self.0).into_iter()
+  }
+}
+
+impl<T> PartialEq for Persistent<T>
+where
+  T: PartialEq,
+{
+  #[inline]
+  fn eq(&self, other: &Self) -> bool {
+    self.ptr_eq(other) || *self.0 == *other.0
+  }
+}
+
+impl<T> fmt::Debug for Persistent<T>
+where
+  T: fmt::Debug,
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.0.fmt(f)
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<T> Serialize for Persistent<T>
+where
+  T: Serialize,
+{
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    self.0.serialize(serializer)
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> Deserialize<'de> for Persistent<T>
+where
+  T: Deserialize<'de>,
+{
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    T::deserialize(deserializer).map(Self::new)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn shares_until_changed() {
+    let lhs = Persistent::new(vec![1, 2, 3]);
+    let mut rhs = lhs.clone();
+    assert!(lhs.ptr_eq(&rhs));
+
+    rhs.push(4);
+    assert!(!lhs.ptr_eq(&rhs));
+    assert_eq!(*lhs, [1, 2, 3]);
+    assert_eq!(*rhs, [1, 2, 3, 4]);
+  }
+
+  #[test]
+  fn only_clones_shared_values_when_unwrapped() {
+    let value = Persistent::new(vec![1]);
+    let ptr = value.as_ptr();
+    let value = value.into_inner();
+    assert_eq!(value.as_ptr(), ptr);
+  }
+}
@@ -0,0 +1,398 @@
+//! Native modules loaded from dynamic libraries, for the `plugin` feature.
+//!
+//! A plugin is a dynamic library which exports a `stack_plugin` function
+//! returning a [`Plugin`], which names the module and lists its functions.
+//! The types are `#[repr(C)]`, and are declared for C in
+//! `include/stack_plugin.h`, so that plugins can be written in any language
+//! and built without depending on this crate's version of Rust.
+//!
+//! Values are passed as a [`Value`], which can be `nil`, a boolean, an
+//! integer, a float or a string. A function takes its arguments from the
+//! stack, the last being the top, and writes its result to `out`. If it
+//! returns anything other than `0`, it failed, and an error holding `out` is
+//! pushed instead. Strings written to `out` are copied and then given back to
+//! the plugin's `free_string`.
+
+use core::{
+  ffi::{c_char, c_int, CStr},
+  fmt,
+};
+use std::{path::Path, sync::Arc};
+
+use libloading::Library;
+
+use crate::{
+  engine::{RunError, RunErrorReason},
+  expr::ExprKind,
+  module::Module,
+  symbol::Symbol,
+};
+
+/// The version of the interface, which a [`Plugin`] must match.
+pub const ABI_VERSION: u32 = 1;
+
+/// The name of the function which a plugin exports.
+pub const ENTRY: &str = "stack_plugin";
+
+pub const KIND_NIL: u32 = 0;
+pub const KIND_BOOLEAN: u32 = 1;
+pub const KIND_INTEGER: u32 = 2;
+pub const KIND_FLOAT: u32 = 3;
+pub const KIND_STRING: u32 = 4;
+
+/// A UTF-8 string, which isn't nul-terminated.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RawStr {
+  pub ptr: *const c_char,
+  pub len: usize,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union ValueData {
+  pub boolean: bool,
+  pub integer: i64,
+  pub float: f64,
+  pub string: RawStr,
+}
+
+/// A value passed to or from a plugin, whose `kind` is one of the `KIND_*`
+/// constants.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Value {
+  pub kind: u32,
+  pub data: ValueData,
+}
+
+impl Value {
+  pub const NIL: Self = Self {
+    kind: KIND_NIL,
+    data: ValueData { integer: 0 },
+  };
+}
+
+pub type PluginCall = unsafe extern "C" fn(
+  args: *const Value,
+  len: usize,
+  out: *mut Value,
+) -> c_int;
+
+/// A function of a plugin, which takes `arity` arguments.
+#[repr(C)]
+pub struct PluginFunc {
+  pub name: *const c_char,
+  pub arity: usize,
+  pub call: PluginCall,
+}
+
+/// What a plugin's `stack_plugin` function returns.
+#[repr(C)]
+pub struct Plugin {
+  pub abi_version: u32,
+  /// The name of the module.
+  pub name: *const c_char,
+  pub funcs: *const PluginFunc,
+  pub funcs_len: usize,
+  /// Frees a string which a function wrote to `out`.
+  pub free_string: Option<unsafe extern "C" fn(ptr: *const c_char, len: usize)>,
+}
+
+#[derive(Debug)]
+pub enum PluginError {
+  /// The library couldn't be loaded.
+  Load(libloading::Error),
+  /// The library doesn't export a `stack_plugin` function.
+  MissingEntry,
+  /// The plugin was built for another version of the interface.
+  AbiVersion(u32),
+  /// The plugin has a null or invalid name.
+  InvalidName,
+}
+
+impl std::error::Error for PluginError {}
+
+impl fmt::Display for PluginError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Load(e) => write!(f, "couldn't load the plugin: {e}"),
+      Self::MissingEntry => {
+        write!(f, "the plugin doesn't export a {ENTRY} function")
+      }
+      Self::AbiVersion(x) => write!(
+        f,
+        "the plugin is for version {x} of the interface, not {ABI_VERSION}"
+      ),
+      Self::InvalidName => write!(f, "the plugin has an invalid name"),
+    }
+  }
+}
+
+/// Loads the plugin at `path` as a [`Module`].
+///
+/// # Safety
+///
+/// Loading a library runs its initialisation code, and the plugin must
+/// follow the interface described in this module.
+pub unsafe fn load(path: impl AsRef<Path>) -> Result<Module, PluginError> {
+  let library = Library::new(path.as_ref()).map_err(PluginError::Load)?;
+  let entry = library
+    .get::<unsafe extern "C" fn() -> *const Plugin>(ENTRY.as_bytes())
+    .map_err(|_| PluginError::MissingEntry)?;
+  let plugin = entry().as_ref().ok_or(PluginError::MissingEntry)?;
+
+  module(plugin, Some(Arc::new(library)))
+}
+
+/// Creates a [`Module`] from a plugin, keeping `library` loaded for as long as
+/// any of its functions are.
+///
+/// # Safety
+///
+/// The plugin must follow the interface described in this module.
+pub unsafe fn module(
+  plugin: &Plugin,
+  library: Option<Arc<Library>>,
+) -> Result<Module, PluginError> {
+  if plugin.abi_version != ABI_VERSION {
+    return Err(PluginError::AbiVersion(plugin.abi_version));
+  }
+
+  let name = c_str(plugin.name).ok_or(PluginError::InvalidName)?;
+  let mut module = Module::new(Symbol::from_ref(name));
+
+  let funcs = match plugin.funcs.is_null() {
+    true => &[],
+    false => core::slice::from_raw_parts(plugin.funcs, plugin.funcs_len),
+  };
+
+  for func in funcs {
+    let name = c_str(func.name).ok_or(PluginError::InvalidName)?;
+    let (arity, call) = (func.arity, func.call);
+    let free_string = plugin.free_string;
+    let library = library.clone();
+
+    module.add_func(
+      Symbol::from_ref(name),
+      Arc::new(move |_, mut context, expr| {
+        let _ = &library;
+
+        if context.stack().len() < arity {
+          return Err(RunError {
+            reason: RunErrorReason::StackUnderflow,
+            context: Box::new(context),
+            expr: Box::new(expr),
+          });
+        }
+
+        let start = context.stack().len() - arity;
+        let args = context.stack_mut().split_off(start);
+        let Some(values) = args
+          .iter()
+          .map(|x| to_value(&x.kind))
+          .collect::<Option<Vec<_>>>()
+        else {
+          context.stack_mut().extend(args);
+          return Err(RunError {
+            reason: RunErrorReason::InvalidArgument,
+            context: Box::new(context),
+            expr: Box::new(expr),
+          });
+        };
+
+        let mut out = Value::NIL;
+        // SAFETY: The arguments outlive the call, since `args` does.
+        let status = unsafe { call(values.as_ptr(), values.len(), &mut out) };
+        let kind = unsafe { from_value(out, free_string) };
+
+        let kind = match status {
+          0 => kind,
+          _ => ExprKind::Error(Box::new(kind.into())),
+        };
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    );
+  }
+
+  Ok(module)
+}
+
+unsafe fn c_str<'a>(ptr: *const c_char) -> Option<&'a str> {
+  match ptr.is_null() {
+    true => None,
+    false => CStr::from_ptr(ptr).to_str().ok(),
+  }
+}
+
+/// Converts an argument for a plugin, borrowing its string if it has one.
+fn to_value(kind: &ExprKind) -> Option<Value> {
+  let (kind, data) = match kind {
+    ExprKind::Nil => return Some(Value::NIL),
+    ExprKind::Boolean(x) => (KIND_BOOLEAN, ValueData { boolean: *x }),
+    ExprKind::Integer(x) => (KIND_INTEGER, ValueData { integer: *x }),
+    ExprKind::Float(x) => (KIND_FLOAT, ValueData { float: *x }),
+    ExprKind::String(x) => (
+      KIND_STRING,
+      ValueData {
+        string: RawStr {
+          ptr: x.as_ptr().cast(),
+          len: x.len(),
+        },
+      },
+    ),
+    _ => return None,
+  };
+
+  Some(Value { kind, data })
+}
+
+/// Converts a result from a plugin, giving its string back to be freed.
+unsafe fn from_value(
+  value: Value,
+  free_string: Option<unsafe extern "C" fn(*const c_char, usize)>,
+) -> ExprKind {
+  match value.kind {
+    KIND_BOOLEAN => ExprKind::Boolean(value.data.boolean),
+    KIND_INTEGER => ExprKind::Integer(value.data.integer),
+    KIND_FLOAT => ExprKind::Float(value.data.float),
+    KIND_STRING => {
+      let RawStr { ptr, len } = value.data.string;
+      if ptr.is_null() {
+        return ExprKind::Nil;
+      }
+
+      let bytes = core::slice::from_raw_parts(ptr.cast::<u8>(), len);
+      let string = String::from_utf8_lossy(bytes).into_owned();
+
+      if let Some(free_string) = free_string {
+        free_string(ptr, len);
+      }
+
+      ExprKind::String(string.into())
+    }
+    _ => ExprKind::Nil,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::prelude::*;
+
+  unsafe extern "C" fn add(
+    args: *const Value,
+    len: usize,
+    out: *mut Value,
+  ) -> c_int {
+    let args = core::slice::from_raw_parts(args, len);
+    if args.iter().any(|x| x.kind != KIND_INTEGER) {
+      *out = Value {
+        kind: KIND_STRING,
+        data: ValueData {
+          string: RawStr {
+            ptr: c"expected integers".as_ptr(),
+            len: 17,
+          },
+        },
+      };
+      return 1;
+    }
+
+    *out = Value {
+      kind: KIND_INTEGER,
+      data: ValueData {
+        integer: args[0].data.integer + args[1].data.integer,
+      },
+    };
+    0
+  }
+
+  unsafe extern "C" fn len(
+    args: *const Value,
+    _: usize,
+    out: *mut Value,
+  ) -> c_int {
+    let string = (*args).data.string;
+
+    *out = Value {
+      kind: KIND_INTEGER,
+      data: ValueData {
+        integer: string.len as i64,
+      },
+    };
+    0
+  }
+
+  const FUNCS: [PluginFunc; 2] = [
+    PluginFunc {
+      name: c"add".as_ptr(),
+      arity: 2,
+      call: add,
+    },
+    PluginFunc {
+      name: c"len".as_ptr(),
+      arity: 1,
+      call: len,
+    },
+  ];
+
+  fn plugin(abi_version: u32) -> Plugin {
+    Plugin {
+      abi_version,
+      name: c"math".as_ptr(),
+      funcs: FUNCS.as_ptr(),
+      funcs_len: FUNCS.len(),
+      free_string: None,
+    }
+  }
+
+  fn run(code: &str) -> Result<Context, RunError> {
+    let module = unsafe { module(&plugin(ABI_VERSION), None) }.unwrap();
+
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    Engine::new().with_module(module).run(Context::new(), exprs)
+  }
+
+  #[test]
+  fn calls_plugin_functions() {
+    let context =
+      run("1 2 math:add \"abc\" math:len 1 \"a\" math:add").unwrap();
+
+    assert_eq!(
+      context.stack(),
+      &[
+        ExprKind::Integer(3).into(),
+        ExprKind::Integer(3).into(),
+        ExprKind::Error(Box::new(
+          ExprKind::String("expected integers".into()).into()
+        ))
+        .into(),
+      ]
+    );
+  }
+
+  #[test]
+  fn errors_without_changing_the_stack() {
+    let err = run("1 math:add").unwrap_err();
+    assert_eq!(err.reason, RunErrorReason::StackUnderflow);
+    assert_eq!(err.context.stack(), &[ExprKind::Integer(1).into()]);
+
+    let err = run("[] 1 math:add").unwrap_err();
+    assert_eq!(err.reason, RunErrorReason::InvalidArgument);
+    assert_eq!(err.context.stack().len(), 2);
+  }
+
+  #[test]
+  fn checks_the_abi_version() {
+    assert!(matches!(
+      unsafe { module(&plugin(ABI_VERSION + 1), None) },
+      Err(PluginError::AbiVersion(_))
+    ));
+  }
+}
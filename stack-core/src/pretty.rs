@@ -0,0 +1,630 @@
+//! Pretty-printing values for people to read.
+//!
+//! Unlike [`format`](crate::format), the output isn't meant to be parsed
+//! again: very long strings and collections are cut short, and collections
+//! which don't fit on one line have their items indented on the following
+//! lines, with record keys sorted. A list of records can also be shown as a
+//! [`table`].
+
+use std::collections::HashMap;
+
+use yansi::{Color, Paint};
+
+use crate::{
+  expr::{bytes_literal, char_literal, Expr, ExprKind},
+  float::FloatFormat,
+  symbol::Symbol,
+};
+
+/// The narrowest a column of a [`table`] is made to fit within the width.
+const MIN_COLUMN: usize = 8;
+
+/// Options for [`pretty`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PrettyOptions {
+  /// Whether to color the values by their type.
+  pub color: bool,
+  /// The amount of spaces per indentation level.
+  pub indent: usize,
+  /// The width which collections are broken over lines to fit within.
+  pub max_width: usize,
+  /// The amount of characters of a string which are shown.
+  pub max_string: usize,
+  /// The amount of items of a collection which are shown.
+  pub max_items: usize,
+  /// How floats are shown.
+  pub float: FloatFormat,
+}
+
+impl Default for PrettyOptions {
+  #[inline]
+  fn default() -> Self {
+    Self {
+      color: true,
+      indent: 2,
+      max_width: 80,
+      max_string: 200,
+      max_items: 100,
+      float: FloatFormat::default(),
+    }
+  }
+}
+
+/// Pretty-prints an [`Expr`], which may take several lines.
+pub fn pretty(expr: &Expr, options: PrettyOptions) -> String {
+  let mut out = String::new();
+  Printer { options }
+    .node(&expr.kind)
+    .write(&mut out, 0, options);
+  out
+}
+
+/// Returns the width of an [`Expr`] pretty-printed on one line.
+pub fn width(expr: &Expr, options: PrettyOptions) -> usize {
+  Printer { options }.node(&expr.kind).width()
+}
+
+/// Renders a list of records as a table, with a column for each name in
+/// `columns`, a list of strings or symbols, or for every key if it's `nil`.
+///
+/// The columns are narrowed to fit within the width, cutting their values
+/// short, and rows after [`max_items`](PrettyOptions::max_items) are left
+/// out. Returns [`None`] if `rows` isn't a list of records or `columns` isn't
+/// a list of names or `nil`.
+pub fn table(
+  rows: &Expr,
+  columns: &Expr,
+  options: PrettyOptions,
+) -> Option<String> {
+  let ExprKind::List(rows) = &rows.kind else {
+    return None;
+  };
+  let rows = rows
+    .iter()
+    .map(|row| match &row.kind {
+      ExprKind::Record(x) => Some(&**x),
+      _ => None,
+    })
+    .collect::<Option<Vec<_>>>()?;
+
+  let columns = match &columns.kind {
+    ExprKind::Nil => {
+      let mut columns = rows
+        .iter()
+        .flat_map(|row| row.keys().copied())
+        .collect::<Vec<_>>();
+      columns.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+      columns.dedup();
+      columns
+    }
+    ExprKind::List(x) => x
+      .iter()
+      .map(|x| match &x.kind {
+        ExprKind::String(x) => Some(Symbol::from_ref(x.as_str())),
+        ExprKind::Symbol(x) => Some(*x),
+        _ => None,
+      })
+      .collect::<Option<Vec<_>>>()?,
+    _ => return None,
+  };
+
+  Some(render_table(&rows, &columns, options))
+}
+
+/// A value in a [`table`], which numbers are aligned to the right of.
+struct Cell {
+  text: String,
+  right: bool,
+}
+
+fn render_table(
+  rows: &[&HashMap<Symbol, Expr>],
+  columns: &[Symbol],
+  options: PrettyOptions,
+) -> String {
+  if columns.is_empty() {
+    return "(empty table)".into();
+  }
+
+  let printer = Printer {
+    options: PrettyOptions {
+      color: false,
+      ..options
+    },
+  };
+
+  let shown = rows.len().min(options.max_items);
+  let cells = rows[..shown]
+    .iter()
+    .map(|row| {
+      columns
+        .iter()
+        .map(|column| printer.cell(row.get(column)))
+        .collect::<Vec<_>>()
+    })
+    .collect::<Vec<_>>();
+
+  let mut widths = columns
+    .iter()
+    .enumerate()
+    .map(|(i, column)| {
+      cells
+        .iter()
+        .map(|row| row[i].text.chars().count())
+        .fold(column.as_str().chars().count(), usize::max)
+    })
+    .collect::<Vec<_>>();
+
+  // Each column has a border and a space either side, besides the border at
+  // the end of each line.
+  while 1 + widths.iter().map(|x| x + 3).sum::<usize>() > options.max_width {
+    match widths.iter_mut().max() {
+      Some(widest) if *widest > MIN_COLUMN => *widest -= 1,
+      _ => break,
+    }
+  }
+
+  let border = widths.iter().fold(String::from("+"), |mut out, width| {
+    out.extend(core::iter::repeat_n('-', width + 2));
+    out.push('+');
+    out
+  });
+
+  let line = |cells: Vec<(String, bool)>| {
+    let mut out = String::from("|");
+
+    for ((text, right), width) in cells.into_iter().zip(&widths) {
+      let text = truncate(&text, *width);
+      let padding = width - text.chars().count();
+      let padding = core::iter::repeat_n(' ', padding).collect::<String>();
+
+      match right {
+        true => out += &format!(" {padding}{text} |"),
+        false => out += &format!(" {text}{padding} |"),
+      }
+    }
+
+    out
+  };
+
+  let header = line(
+    columns
+      .iter()
+      .map(|column| (column.as_str().to_owned(), false))
+      .collect(),
+  );
+  let header = match options.color {
+    true => header.bold().to_string(),
+    false => header,
+  };
+
+  let mut out = format!("{border}\n{header}\n{border}");
+
+  for row in cells {
+    out.push('\n');
+    out += &line(row.into_iter().map(|x| (x.text, x.right)).collect());
+  }
+
+  out.push('\n');
+  out += &border;
+
+  if rows.len() > shown {
+    let more = rows.len() - shown;
+    let plural = if more == 1 { "" } else { "s" };
+    let more = format!("… {more} more row{plural}");
+    out.push('\n');
+    out += &printer.piece(more, Some(Color::BrightBlack)).text;
+  }
+
+  out
+}
+
+/// Cuts text short to fit within a width, ending it with `…`.
+fn truncate(text: &str, width: usize) -> String {
+  match text.chars().nth(width) {
+    Some(_) => {
+      let mut text = text.chars().take(width - 1).collect::<String>();
+      text.push('…');
+      text
+    }
+    None => text.into(),
+  }
+}
+
+/// Text with the width it takes up, which doesn't count any colors.
+#[derive(Debug, Clone)]
+struct Piece {
+  text: String,
+  width: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+  Atom(Piece),
+  Entry(Piece, Box<Node>),
+  Group {
+    open: Piece,
+    items: Vec<Node>,
+    separator: &'static str,
+    close: Piece,
+  },
+}
+
+impl Node {
+  /// Returns the width of the node on one line.
+  fn width(&self) -> usize {
+    match self {
+      Self::Atom(x) => x.width,
+      Self::Entry(key, value) => key.width + 2 + value.width(),
+      Self::Group {
+        open,
+        items,
+        separator,
+        close,
+      } => {
+        let separators = items.len().saturating_sub(1) * separator.len();
+
+        open.width
+          + items.iter().map(Node::width).sum::<usize>()
+          + separators
+          + close.width
+      }
+    }
+  }
+
+  fn write_flat(&self, out: &mut String) {
+    match self {
+      Self::Atom(x) => out.push_str(&x.text),
+      Self::Entry(key, value) => {
+        out.push_str(&key.text);
+        out.push_str(": ");
+        value.write_flat(out);
+      }
+      Self::Group {
+        open,
+        items,
+        separator,
+        close,
+      } => {
+        out.push_str(&open.text);
+
+        for (i, item) in items.iter().enumerate() {
+          if i != 0 {
+            out.push_str(separator);
+          }
+
+          item.write_flat(out);
+        }
+
+        out.push_str(&close.text);
+      }
+    }
+  }
+
+  /// Writes the node, starting at the column `indent`, breaking it over lines
+  /// if it doesn't fit on one.
+  fn write(&self, out: &mut String, indent: usize, options: PrettyOptions) {
+    if indent + self.width() <= options.max_width {
+      return self.write_flat(out);
+    }
+
+    match self {
+      Self::Atom(_) => self.write_flat(out),
+      Self::Entry(key, value) => {
+        out.push_str(&key.text);
+        out.push_str(": ");
+        value.write(out, indent + key.width + 2, options);
+      }
+      Self::Group {
+        open, items, close, ..
+      } => {
+        let inner = indent + options.indent;
+        out.push_str(&open.text);
+
+        for item in items {
+          out.push('\n');
+          out.extend(core::iter::repeat_n(' ', inner));
+          item.write(out, inner, options);
+        }
+
+        out.push('\n');
+        out.extend(core::iter::repeat_n(' ', indent));
+        out.push_str(&close.text);
+      }
+    }
+  }
+}
+
+struct Printer {
+  options: PrettyOptions,
+}
+
+impl Printer {
+  fn piece(&self, text: impl Into<String>, color: Option<Color>) -> Piece {
+    let text = text.into();
+    let width = text.chars().count();
+
+    let text = match color {
+      Some(color) if self.options.color => text.paint(color).to_string(),
+      _ => text,
+    };
+
+    Piece { text, width }
+  }
+
+  fn atom(&self, text: impl Into<String>, color: Color) -> Node {
+    Node::Atom(self.piece(text, Some(color)))
+  }
+
+  fn group(
+    &self,
+    open: &str,
+    items: impl ExactSizeIterator<Item = Node>,
+    separator: &'static str,
+    close: &str,
+  ) -> Node {
+    let len = items.len();
+    let mut items = items.take(self.options.max_items).collect::<Vec<_>>();
+
+    if len > items.len() {
+      let more = format!("… {} more", len - items.len());
+      items.push(Node::Atom(self.piece(more, Some(Color::BrightBlack))));
+    }
+
+    Node::Group {
+      open: self.piece(open, Some(Color::Yellow)),
+      items,
+      separator,
+      close: self.piece(close, Some(Color::Yellow)),
+    }
+  }
+
+  /// Returns the text of a [`table`] cell, where strings aren't quoted and
+  /// missing values are left empty.
+  fn cell(&self, value: Option<&Expr>) -> Cell {
+    let Some(value) = value else {
+      return Cell {
+        text: String::new(),
+        right: false,
+      };
+    };
+
+    let text = match &value.kind {
+      ExprKind::String(x) => x.escape_debug().to_string(),
+      kind => {
+        let mut text = String::new();
+        self.node(kind).write_flat(&mut text);
+        text
+      }
+    };
+
+    Cell {
+      text,
+      right: matches!(value.kind, ExprKind::Integer(_) | ExprKind::Float(_)),
+    }
+  }
+
+  fn node(&self, kind: &ExprKind) -> Node {
+    match kind {
+      ExprKind::Nil => self.atom("nil", Color::Green),
+      ExprKind::Boolean(x) => self.atom(x.to_string(), Color::Green),
+      ExprKind::Integer(x) => self.atom(x.to_string(), Color::Blue),
+      ExprKind::Float(x) => {
+        self.atom(self.options.float.format(*x), Color::Blue)
+      }
+      ExprKind::String(x) => {
+        let mut text =
+          x.chars().take(self.options.max_string).collect::<String>();
+
+        if x.chars().nth(self.options.max_string).is_some() {
+          text += "…";
+        }
+
+        self.atom(format!("\"{text}\""), Color::Green)
+      }
+      ExprKind::Char(x) => self.atom(char_literal(*x), Color::Green),
+      ExprKind::Bytes(x) => self.atom(bytes_literal(x), Color::Green),
+      ExprKind::Symbol(x) => self.atom(x.as_str(), Color::Blue),
+
+      ExprKind::Lazy(x) => {
+        let quote = self.piece("'", Some(Color::Yellow));
+        let join = |x: Piece| Piece {
+          text: quote.text.clone() + &x.text,
+          width: quote.width + x.width,
+        };
+
+        match self.node(&x.kind) {
+          Node::Atom(x) => Node::Atom(join(x)),
+          Node::Entry(key, value) => Node::Entry(join(key), value),
+          Node::Group {
+            open,
+            items,
+            separator,
+            close,
+          } => Node::Group {
+            open: join(open),
+            items,
+            separator,
+            close,
+          },
+        }
+      }
+      ExprKind::List(x) => {
+        self.group("[", x.iter().map(|x| self.node(&x.kind)), " ", "]")
+      }
+      ExprKind::Set(x) => self.group(
+        "#[",
+        x.iter().map(|x| self.node(&x.clone().into())),
+        " ",
+        "]",
+      ),
+      ExprKind::Record(x) => {
+        let mut entries = x.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(key, _)| key.as_str());
+
+        let entries = entries.into_iter().map(|(key, value)| {
+          let key = self.piece(key.as_str(), Some(Color::Blue));
+          Node::Entry(key, Box::new(self.node(&value.kind)))
+        });
+
+        self.group("{", entries, ", ", "}")
+      }
+      ExprKind::Map(x) => {
+        let mut entries = x
+          .iter()
+          .map(|(key, value)| (self.node(&key.clone().into()), value))
+          .collect::<Vec<_>>();
+        entries.sort_by_cached_key(|(key, _)| {
+          let mut text = String::new();
+          key.write_flat(&mut text);
+          text
+        });
+
+        let entries = entries.into_iter().map(|(key, value)| {
+          let Node::Atom(key) = key else {
+            unreachable!("map keys are atoms")
+          };
+          Node::Entry(key, Box::new(self.node(&value.kind)))
+        });
+
+        self.group("#{", entries, ", ", "}")
+      }
+      ExprKind::Error(x) => Node::Group {
+        open: self.piece("(error ", Some(Color::Red)),
+        items: vec![self.node(&x.kind)],
+        separator: "",
+        close: self.piece(")", Some(Color::Red)),
+      },
+
+      // These are shown as they're written.
+      ExprKind::Seq(_)
+      | ExprKind::Function { .. }
+      | ExprKind::SExpr { .. }
+      | ExprKind::Underscore
+      | ExprKind::Foreign(_) => {
+        let plain = kind.to_string();
+        let width = plain.chars().count();
+
+        let text = match self.options.color {
+          true => format!("{kind:#}"),
+          false => plain,
+        };
+
+        Node::Atom(Piece { text, width })
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::prelude::*;
+
+  fn pretty_str(code: &str, options: PrettyOptions) -> String {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+    let context = Engine::new().run(Context::new(), exprs).unwrap();
+
+    let options = PrettyOptions {
+      color: false,
+      ..options
+    };
+    pretty(&context.stack()[0], options)
+  }
+
+  #[test]
+  fn prints_values_on_one_line_if_they_fit() {
+    let options = PrettyOptions::default();
+
+    assert_eq!(pretty_str("[1 \"a\" nil]", options), "[1 \"a\" nil]");
+    assert_eq!(pretty_str("{b 2 a 1}", options), "{a: 1, b: 2}");
+    assert_eq!(pretty_str("''[1 2]", options), "'[1 2]");
+  }
+
+  #[test]
+  fn breaks_values_which_dont_fit() {
+    let options = PrettyOptions {
+      max_width: 12,
+      ..Default::default()
+    };
+
+    assert_eq!(
+      pretty_str("[[1 2] {name \"ferris\"} 3]", options),
+      "[\n  [1 2]\n  {\n    name: \"ferris\"\n  }\n  3\n]"
+    );
+  }
+
+  #[test]
+  fn truncates_long_values() {
+    let options = PrettyOptions {
+      max_string: 3,
+      max_items: 2,
+      ..Default::default()
+    };
+
+    assert_eq!(pretty_str("\"abcdef\"", options), "\"abc…\"");
+    assert_eq!(pretty_str("\"abc\"", options), "\"abc\"");
+    assert_eq!(pretty_str("[1 2 3 4]", options), "[1 2 … 2 more]");
+  }
+
+  fn table_str(code: &str, options: PrettyOptions) -> Option<String> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+    let context = Engine::new().run(Context::new(), exprs).unwrap();
+
+    let options = PrettyOptions {
+      color: false,
+      ..options
+    };
+    table(&context.stack()[0], &context.stack()[1], options)
+  }
+
+  #[test]
+  fn renders_lists_of_records_as_tables() {
+    let options = PrettyOptions::default();
+
+    assert_eq!(
+      table_str("[{name \"ferris\" age 7} {name \"corro\"}] nil", options),
+      Some(
+        "+-----+--------+\n\
+         | age | name   |\n\
+         +-----+--------+\n\
+         |   7 | ferris |\n\
+         |     | corro  |\n\
+         +-----+--------+"
+          .into()
+      )
+    );
+    assert_eq!(table_str("[1 2] nil", options), None);
+    assert_eq!(table_str("[{a 1}] [1]", options), None);
+  }
+
+  #[test]
+  fn narrows_and_cuts_tables_short() {
+    let options = PrettyOptions {
+      max_width: 20,
+      max_items: 1,
+      ..Default::default()
+    };
+
+    assert_eq!(
+      table_str(
+        "[{id 1 text \"a long piece of text\"} {id 2 text \"\"}] \
+         [\"text\" \"id\"]",
+        options
+      ),
+      Some(
+        "+-------------+----+\n\
+         | text        | id |\n\
+         +-------------+----+\n\
+         | a long pie… |  1 |\n\
+         +-------------+----+\n\
+         … 1 more row"
+          .into()
+      )
+    );
+  }
+}
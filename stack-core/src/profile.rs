@@ -0,0 +1,269 @@
+//! A profiler, which records how long each intrinsic, native function and
+//! defined function takes.
+//!
+//! A [`Profiler`] is given to an [`Engine`] with
+//! [`Engine::with_profiler`], and shares what it records with its clones,
+//! so it can be read once the run has finished.
+//!
+//! Each name gets a [`ProfileEntry`] with its call count, its total time
+//! including the calls it made, and its self time excluding them. Recursive
+//! calls only count towards the total time once. The time of each call stack
+//! is also recorded, which [`Profiler::folded`] writes in the folded format
+//! read by flame graph tools such as `inferno` and `flamegraph.pl`.
+//!
+//! [`Engine`]: crate::engine::Engine
+//! [`Engine::with_profiler`]: crate::engine::Engine::with_profiler
+
+use core::fmt;
+use std::{
+  collections::HashMap,
+  time::{Duration, Instant},
+};
+
+use crate::{shared::Shared, symbol::Symbol};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileEntry {
+  pub name: Symbol,
+  pub calls: usize,
+  /// The time spent in the calls, including the calls they made.
+  pub total: Duration,
+  /// The time spent in the calls, excluding the calls they made.
+  pub self_time: Duration,
+}
+
+/// The entries of a [`Profiler`], sorted by their self time, most first.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Profile {
+  pub entries: Vec<ProfileEntry>,
+}
+
+impl fmt::Display for Profile {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let ms = |x: Duration| x.as_secs_f64() * 1000.0;
+
+    write!(
+      f,
+      "{:>12} {:>12} {:>10}  name",
+      "self (ms)", "total (ms)", "calls"
+    )?;
+
+    for entry in self.entries.iter() {
+      write!(
+        f,
+        "\n{:>12.3} {:>12.3} {:>10}  {}",
+        ms(entry.self_time),
+        ms(entry.total),
+        entry.calls,
+        entry.name
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
+struct Frame {
+  name: Symbol,
+  start: Instant,
+  /// The time spent in the calls made from this one.
+  children: Duration,
+}
+
+#[derive(Default)]
+struct State {
+  frames: Vec<Frame>,
+  names: Vec<Symbol>,
+  entries: HashMap<Symbol, ProfileEntry>,
+  /// The self time of each call stack.
+  stacks: HashMap<Vec<Symbol>, Duration>,
+}
+
+#[derive(Clone, Default)]
+pub struct Profiler {
+  state: Shared<State>,
+}
+
+impl fmt::Debug for Profiler {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Profiler").finish_non_exhaustive()
+  }
+}
+
+impl Profiler {
+  #[inline]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Runs `f` as a call to `name`.
+  pub(crate) fn call<T>(&self, name: Symbol, f: impl FnOnce() -> T) -> T {
+    self.enter(name);
+    let result = f();
+    self.exit();
+
+    result
+  }
+
+  /// Awaits `future` as a call to `name`.
+  #[cfg(feature = "async")]
+  pub(crate) async fn call_async<F>(&self, name: Symbol, future: F) -> F::Output
+  where
+    F: core::future::Future,
+  {
+    self.enter(name);
+    let result = future.await;
+    self.exit();
+
+    result
+  }
+
+  fn enter(&self, name: Symbol) {
+    let mut state = self.state.borrow_mut();
+    state.frames.push(Frame {
+      name,
+      start: Instant::now(),
+      children: Duration::ZERO,
+    });
+    state.names.push(name);
+  }
+
+  fn exit(&self) {
+    let mut state = self.state.borrow_mut();
+    let State {
+      frames,
+      names,
+      entries,
+      stacks,
+    } = &mut *state;
+
+    let Some(frame) = frames.pop() else {
+      return;
+    };
+    let elapsed = frame.start.elapsed();
+    let self_time = elapsed.saturating_sub(frame.children);
+
+    if let Some(parent) = frames.last_mut() {
+      parent.children += elapsed;
+    }
+
+    match stacks.get_mut(names.as_slice()) {
+      Some(x) => *x += self_time,
+      None => {
+        stacks.insert(names.clone(), self_time);
+      }
+    }
+    names.pop();
+
+    let entry = entries.entry(frame.name).or_insert(ProfileEntry {
+      name: frame.name,
+      calls: 0,
+      total: Duration::ZERO,
+      self_time: Duration::ZERO,
+    });
+    entry.calls += 1;
+    entry.self_time += self_time;
+
+    // The time of a recursive call is already part of the outermost call.
+    if !frames.iter().any(|x| x.name == frame.name) {
+      entry.total += elapsed;
+    }
+  }
+
+  /// Returns what has been recorded so far.
+  pub fn profile(&self) -> Profile {
+    let mut entries = self
+      .state
+      .borrow()
+      .entries
+      .values()
+      .copied()
+      .collect::<Vec<_>>();
+    entries.sort_by(|lhs, rhs| {
+      rhs
+        .self_time
+        .cmp(&lhs.self_time)
+        .then_with(|| lhs.name.as_str().cmp(rhs.name.as_str()))
+    });
+
+    Profile { entries }
+  }
+
+  /// Returns the self time of each call stack in the folded format, with
+  /// one `a;b;c <microseconds>` line per stack.
+  pub fn folded(&self) -> String {
+    let state = self.state.borrow();
+    let mut lines = state
+      .stacks
+      .iter()
+      .map(|(names, time)| {
+        let names = names.iter().map(Symbol::as_str).collect::<Vec<_>>();
+        format!("{} {}", names.join(";"), time.as_micros())
+      })
+      .collect::<Vec<_>>();
+    lines.sort();
+
+    lines.join("\n")
+  }
+
+  /// Forgets everything that has been recorded.
+  pub fn clear(&self) {
+    *self.state.borrow_mut() = State::default();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::prelude::*;
+
+  fn run(code: &str) -> Profiler {
+    let profiler = Profiler::new();
+    let engine = Engine::new().with_profiler(Some(profiler.clone()));
+
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+    engine.run(Context::new(), exprs).unwrap();
+
+    profiler
+  }
+
+  fn entry(profile: &Profile, name: &str) -> ProfileEntry {
+    *profile
+      .entries
+      .iter()
+      .find(|x| x.name.as_str() == name)
+      .unwrap()
+  }
+
+  #[test]
+  fn counts_calls() {
+    let profiler = run(
+      "'(fn! dupe 1 > '[dupe 1 - fib swap 2 - fib +] if) 'fib def
+      2 fib
+      '(fn 2 *) 'double def
+      3 double double",
+    );
+    let profile = profiler.profile();
+
+    assert_eq!(entry(&profile, "fib").calls, 3);
+    assert_eq!(entry(&profile, "double").calls, 2);
+    assert_eq!(entry(&profile, "*").calls, 2);
+
+    let fib = entry(&profile, "fib");
+    assert!(fib.self_time <= fib.total);
+  }
+
+  #[test]
+  fn records_call_stacks() {
+    let profiler = run("'(fn 2 *) 'double def 3 double");
+    let folded = profiler.folded();
+    let stacks = folded
+      .lines()
+      .map(|x| x.rsplit_once(' ').unwrap().0)
+      .collect::<Vec<_>>();
+
+    assert_eq!(stacks, ["def", "double", "double;*"]);
+  }
+}
@@ -0,0 +1,122 @@
+//! The pseudo-random number generator owned by each [`Context`].
+//!
+//! [`Context`]: crate::context::Context
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A SplitMix64 generator, which is fast and small but not suitable for
+/// cryptography.
+///
+/// It's seeded from the clock the first time it's used, unless it has been
+/// [seeded](Self::seed) already, so that a run can be reproduced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rng {
+  state: Option<u64>,
+}
+
+impl Rng {
+  /// Creates an [`Rng`] with a seed.
+  #[inline]
+  pub const fn from_seed(seed: u64) -> Self {
+    Self { state: Some(seed) }
+  }
+
+  /// Resets the state to a seed.
+  #[inline]
+  pub fn seed(&mut self, seed: u64) {
+    self.state = Some(seed);
+  }
+
+  /// Returns the next random [`u64`].
+  pub fn next_u64(&mut self) -> u64 {
+    let state = self.state.get_or_insert_with(clock_seed);
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+  }
+
+  /// Returns a random [`f64`] in `0.0..1.0`.
+  #[inline]
+  pub fn next_f64(&mut self) -> f64 {
+    (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  /// Returns a random [`bool`].
+  #[inline]
+  pub fn next_bool(&mut self) -> bool {
+    self.next_u64() >> 63 == 1
+  }
+
+  /// Returns a random integer in `start..end`, or [`None`] if it's empty.
+  pub fn range(&mut self, start: i64, end: i64) -> Option<i64> {
+    if start >= end {
+      return None;
+    }
+
+    let len = end.abs_diff(start);
+
+    // Rejects the values which would make lower numbers more likely.
+    let zone = u64::MAX - (u64::MAX - len + 1) % len;
+    loop {
+      let x = self.next_u64();
+      if x <= zone {
+        return Some(start.wrapping_add((x % len) as i64));
+      }
+    }
+  }
+}
+
+/// Returns a seed from the clock, which differs for each call so that
+/// contexts created at the same time don't share it.
+fn clock_seed() -> u64 {
+  static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+  let nanos = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |x| x.as_nanos() as u64);
+
+  nanos
+    ^ COUNTER
+      .fetch_add(1, Ordering::Relaxed)
+      .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn seeds_are_reproducible() {
+    let mut a = Rng::from_seed(42);
+    let mut b = Rng::default();
+    b.seed(42);
+
+    for _ in 0..8 {
+      assert_eq!(a.next_u64(), b.next_u64());
+    }
+  }
+
+  #[test]
+  fn ranges_stay_in_bounds() {
+    let mut rng = Rng::from_seed(7);
+
+    for _ in 0..1000 {
+      let x = rng.range(-3, 4).unwrap();
+      assert!((-3..4).contains(&x));
+
+      let x = rng.next_f64();
+      assert!((0.0..1.0).contains(&x));
+    }
+
+    assert_eq!(rng.range(2, 2), None);
+    assert!(rng.range(i64::MIN, i64::MAX).is_some());
+  }
+}
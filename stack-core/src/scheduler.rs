@@ -0,0 +1,982 @@
+use core::fmt;
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "sync")]
+use std::{
+  cell::RefCell,
+  sync::mpsc::{self, Receiver, Sender},
+  thread::{self, JoinHandle},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "sync")]
+use crate::engine::{RunErrorReason, THREAD_STACK_SIZE};
+use crate::{
+  context::Context,
+  engine::{Engine, RunError},
+  expr::{Expr, ExprKind},
+  shared::Shared,
+  symbol::Symbol,
+};
+
+/// The default amount of operations a task may run before yielding.
+pub const DEFAULT_SLICE: usize = 1_000;
+
+/// Identifies a task spawned on a [`Scheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(transparent)]
+pub struct TaskId(usize);
+
+impl TaskId {
+  /// Creates a [`TaskId`].
+  #[inline]
+  pub const fn new(id: usize) -> Self {
+    Self(id)
+  }
+
+  /// Returns the <code>[usize]</code> for this [`TaskId`].
+  #[inline]
+  pub const fn get(self) -> usize {
+    self.0
+  }
+}
+
+impl fmt::Display for TaskId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+/// Returns the type of a message, which is the `type` field of a record.
+pub fn message_type(message: &Expr) -> Option<Symbol> {
+  match &message.kind {
+    ExprKind::Record(record) => {
+      match record.get(&Symbol::from_ref("type")).map(|x| &x.kind) {
+        Some(ExprKind::Symbol(x)) => Some(*x),
+        Some(ExprKind::String(x)) => Some(Symbol::from_ref(x.as_str())),
+        _ => None,
+      }
+    }
+    _ => None,
+  }
+}
+
+/// Holds the messages sent to, and from, a [`Context`].
+///
+/// Outgoing messages are delivered by the [`Scheduler`] once the sending task
+/// yields.
+///
+/// A [`Mailbox`] with handlers belongs to an actor. Once an actor has run all
+/// of its expressions, the [`Scheduler`] calls the handler for the
+/// [`message_type`] of each message it receives, with the message pushed onto
+/// the stack. Messages without a handler are discarded.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Mailbox {
+  id: Option<TaskId>,
+  inbox: VecDeque<Expr>,
+  outbox: Vec<(TaskId, Expr)>,
+  #[cfg_attr(feature = "serde", serde(default))]
+  handlers: HashMap<Symbol, Expr>,
+}
+
+impl Mailbox {
+  /// Returns the [`TaskId`] of the task that owns this [`Mailbox`], if it has
+  /// been spawned on a [`Scheduler`].
+  #[inline]
+  pub fn id(&self) -> Option<TaskId> {
+    self.id
+  }
+
+  /// Returns the amount of received messages that haven't been read.
+  #[inline]
+  pub fn pending(&self) -> usize {
+    self.inbox.len()
+  }
+
+  /// Returns the oldest received message.
+  #[inline]
+  pub fn receive(&mut self) -> Option<Expr> {
+    self.inbox.pop_front()
+  }
+
+  /// Adds a message to the inbox.
+  #[inline]
+  pub fn deliver(&mut self, message: Expr) {
+    self.inbox.push_back(message);
+  }
+
+  /// Queues a message to be sent to another task.
+  #[inline]
+  pub fn send(&mut self, to: TaskId, message: Expr) {
+    self.outbox.push((to, message));
+  }
+
+  /// Returns whether any handlers have been registered.
+  #[inline]
+  pub fn is_actor(&self) -> bool {
+    !self.handlers.is_empty()
+  }
+
+  /// Registers the handler for a message type, returning the previous one.
+  #[inline]
+  pub fn on(&mut self, ty: Symbol, handler: Expr) -> Option<Expr> {
+    self.handlers.insert(ty, handler)
+  }
+
+  /// Removes the handler for a message type.
+  #[inline]
+  pub fn off(&mut self, ty: &Symbol) -> Option<Expr> {
+    self.handlers.remove(ty)
+  }
+
+  #[inline]
+  pub fn handler(&self, ty: &Symbol) -> Option<&Expr> {
+    self.handlers.get(ty)
+  }
+
+  /// Returns the oldest received message which has a handler, along with the
+  /// handler, discarding any unhandled messages before it.
+  pub fn next_handled(&mut self) -> Option<(Expr, Expr)> {
+    while let Some(message) = self.inbox.pop_front() {
+      if let Some(handler) =
+        message_type(&message).and_then(|ty| self.handlers.get(&ty))
+      {
+        return Some((message, handler.clone()));
+      }
+    }
+
+    None
+  }
+}
+
+/// The status of a task spawned on a [`Scheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TaskStatus {
+  /// The task has expressions or messages left to run.
+  Ready,
+  /// The task is an actor which is waiting for messages.
+  Waiting,
+  /// The task has run all of its expressions.
+  Finished,
+  /// The task has stopped due to a [`RunError`].
+  Failed,
+}
+
+/// A copy of a task which can be saved, and later spawned to resume it.
+///
+/// Tasks are only checkpointed between top-level expressions, hence resuming
+/// re-runs the expression that was running when the checkpoint was taken.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Checkpoint {
+  pub context: Context,
+  pub exprs: Vec<Expr>,
+}
+
+/// The expressions a task has left to run, which its thread takes from while
+/// it runs a slice.
+type Jobs = Shared<VecDeque<Expr>>;
+
+/// The [`Engine`] shared by a [`Scheduler`] and the threads of its tasks.
+#[cfg(not(feature = "sync"))]
+type SharedEngine = std::rc::Rc<Engine>;
+#[cfg(feature = "sync")]
+type SharedEngine = std::sync::Arc<Engine>;
+
+#[derive(Debug)]
+struct Task {
+  /// The context of the task, or a copy of it as it was when the task was
+  /// preempted.
+  context: Context,
+  exprs: Jobs,
+  error: Option<RunError>,
+  #[cfg(feature = "sync")]
+  thread: Option<TaskThread>,
+  /// The messages sent to the task while it was preempted, which are
+  /// delivered once it's resumed.
+  #[cfg(feature = "sync")]
+  inbox: Vec<Expr>,
+}
+
+impl Task {
+  fn status(&self) -> TaskStatus {
+    if self.error.is_some() {
+      TaskStatus::Failed
+    } else if self.is_preempted() || !self.exprs.borrow().is_empty() {
+      TaskStatus::Ready
+    } else if self.context.mailbox().is_actor() {
+      if self.context.mailbox().pending() > 0 {
+        TaskStatus::Ready
+      } else {
+        TaskStatus::Waiting
+      }
+    } else {
+      TaskStatus::Finished
+    }
+  }
+
+  /// Returns whether the task is in the middle of an expression.
+  #[inline]
+  fn is_preempted(&self) -> bool {
+    #[cfg(feature = "sync")]
+    {
+      self.thread.as_ref().is_some_and(|x| x.is_preempted)
+    }
+
+    #[cfg(not(feature = "sync"))]
+    {
+      false
+    }
+  }
+}
+
+/// Runs top-level expressions, and then the handlers of received messages,
+/// until `budget` returns no more than the amount of operations that have
+/// been run. The [`RunError`] that the task failed with, if any, is returned
+/// alongside its context.
+fn run_slice(
+  engine: &Engine,
+  mut context: Context,
+  jobs: &Jobs,
+  budget: impl Fn() -> usize,
+) -> (Context, Option<RunError>) {
+  while context.ops() < budget() {
+    let job = jobs.borrow_mut().pop_front();
+    let result = if let Some(expr) = job {
+      engine.run_expr(context, expr)
+    } else if let Some((message, handler)) =
+      context.mailbox_mut().next_handled()
+    {
+      context
+        .stack_push(message)
+        .and_then(|_| engine.call_expr(context, handler))
+    } else {
+      break;
+    };
+
+    match result {
+      Ok(ctx) => context = ctx,
+      Err(error) => return ((*error.context).clone(), Some(error)),
+    }
+  }
+
+  (context, None)
+}
+
+/// Runs many [`Context`]s cooperatively, in round-robin order.
+///
+/// Each time a task is stepped it runs until it has used up its slice of
+/// operations, after which it yields to the next task.
+///
+/// With the `sync` feature, each task runs on a thread of its own, which is
+/// kept while the task has anything left to run. The slice is then charged as
+/// each expression is evaluated, so a long-running expression (such as a
+/// `loop`) is preempted in the middle, and carries on from there when the
+/// task is next stepped. [`Scheduler::run`] runs a slice of up to
+/// [`Scheduler::with_workers`] tasks at once.
+///
+/// Without `sync`, tasks run one at a time on the calling thread, and yield
+/// only between top-level expressions, so a slice may be overrun by the cost
+/// of the last expression that was run.
+///
+/// Tasks can pass messages to each other via their [`Mailbox`].
+pub struct Scheduler {
+  engine: SharedEngine,
+  slice: usize,
+  #[cfg(feature = "sync")]
+  workers: usize,
+  tasks: HashMap<TaskId, Task>,
+  queue: VecDeque<TaskId>,
+  next_id: usize,
+}
+
+impl Scheduler {
+  #[inline]
+  pub fn new(engine: Engine) -> Self {
+    Self {
+      engine: SharedEngine::new(engine),
+      slice: DEFAULT_SLICE,
+      #[cfg(feature = "sync")]
+      workers: std::thread::available_parallelism().map_or(1, |x| x.get()),
+      tasks: HashMap::new(),
+      queue: VecDeque::new(),
+      next_id: 0,
+    }
+  }
+
+  /// Sets the amount of operations a task may run before yielding.
+  #[inline]
+  pub fn with_slice(mut self, slice: usize) -> Self {
+    self.slice = slice.max(1);
+    self
+  }
+
+  /// Sets the amount of tasks which [`Scheduler::run`] runs at once, which
+  /// defaults to the amount of available cores.
+  #[cfg(feature = "sync")]
+  #[inline]
+  pub fn with_workers(mut self, workers: usize) -> Self {
+    self.workers = workers.max(1);
+    self
+  }
+
+  #[inline]
+  pub fn engine(&self) -> &Engine {
+    &self.engine
+  }
+
+  /// Returns the [`Engine`] to change it. Tasks which are in the middle of an
+  /// expression finish it with the engine they started it with.
+  #[inline]
+  pub fn engine_mut(&mut self) -> &mut Engine {
+    SharedEngine::make_mut(&mut self.engine)
+  }
+
+  /// Returns the amount of tasks, including finished and failed tasks.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.tasks.len()
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.tasks.is_empty()
+  }
+
+  /// Returns whether there are no tasks left to run.
+  ///
+  /// Actors waiting for messages aren't counted.
+  #[inline]
+  pub fn is_idle(&self) -> bool {
+    self.queue.is_empty()
+  }
+
+  /// Spawns a task which runs `exprs` with `context`.
+  pub fn spawn(&mut self, mut context: Context, exprs: Vec<Expr>) -> TaskId {
+    let id = TaskId(self.next_id);
+    self.next_id += 1;
+
+    context.mailbox_mut().id = Some(id);
+
+    let task = Task {
+      context,
+      exprs: Shared::new(exprs.into()),
+      error: None,
+      #[cfg(feature = "sync")]
+      thread: None,
+      #[cfg(feature = "sync")]
+      inbox: Vec::new(),
+    };
+
+    if task.status() == TaskStatus::Ready {
+      self.queue.push_back(id);
+    }
+
+    self.tasks.insert(id, task);
+
+    id
+  }
+
+  /// Spawns a task from a [`Checkpoint`].
+  #[inline]
+  pub fn resume(&mut self, checkpoint: Checkpoint) -> TaskId {
+    self.spawn(checkpoint.context, checkpoint.exprs)
+  }
+
+  /// Returns a [`Checkpoint`] of a task, unless it has failed or is in the
+  /// middle of an expression.
+  pub fn checkpoint(&self, id: TaskId) -> Option<Checkpoint> {
+    self
+      .tasks
+      .get(&id)
+      .filter(|task| task.error.is_none() && !task.is_preempted())
+      .map(|task| Checkpoint {
+        context: task.context.clone(),
+        exprs: task.exprs.borrow().iter().cloned().collect(),
+      })
+  }
+
+  /// Sends a message to a task.
+  ///
+  /// Returns `false` if the task doesn't exist or has failed.
+  pub fn send(&mut self, to: TaskId, message: Expr) -> bool {
+    match self.tasks.get_mut(&to) {
+      #[cfg(feature = "sync")]
+      Some(task) if task.error.is_none() && task.is_preempted() => {
+        task.inbox.push(message);
+        true
+      }
+      Some(task) if task.error.is_none() => {
+        let was_ready = task.status() == TaskStatus::Ready;
+        task.context.mailbox_mut().deliver(message);
+
+        if !was_ready && task.status() == TaskStatus::Ready {
+          self.queue.push_back(to);
+        }
+
+        true
+      }
+      _ => false,
+    }
+  }
+
+  #[inline]
+  pub fn status(&self, id: TaskId) -> Option<TaskStatus> {
+    self.tasks.get(&id).map(Task::status)
+  }
+
+  /// Returns the context of a task. If the task is in the middle of an
+  /// expression, this is a copy of the context from when it was preempted.
+  #[inline]
+  pub fn context(&self, id: TaskId) -> Option<&Context> {
+    self.tasks.get(&id).map(|task| &task.context)
+  }
+
+  #[inline]
+  pub fn error(&self, id: TaskId) -> Option<&RunError> {
+    self.tasks.get(&id).and_then(|task| task.error.as_ref())
+  }
+
+  /// Removes a task, returning its result.
+  ///
+  /// If the task hasn't finished, the [`Context`] is returned as it currently
+  /// is and the rest of its expressions are discarded. A task which is in the
+  /// middle of an expression is cancelled.
+  pub fn remove(&mut self, id: TaskId) -> Option<Result<Context, RunError>> {
+    self.queue.retain(|x| *x != id);
+    self.tasks.remove(&id).map(|task| match task.error {
+      Some(error) => Err(error),
+      None => Ok(task.context),
+    })
+  }
+
+  /// Runs a single slice of the next ready task, returning its [`TaskId`].
+  ///
+  /// Returns [`None`] when there are no tasks left to run.
+  pub fn step(&mut self) -> Option<TaskId> {
+    let id = self.next_ready()?;
+
+    let slice = self.start(id);
+    self.finish(id, slice);
+
+    Some(id)
+  }
+
+  /// Runs every task until there are none left to run.
+  pub fn run(&mut self) {
+    #[cfg(feature = "sync")]
+    loop {
+      let mut running = Vec::with_capacity(self.workers);
+      while running.len() < self.workers {
+        match self.next_ready() {
+          Some(id) => running.push((id, self.start(id))),
+          None => break,
+        }
+      }
+
+      if running.is_empty() {
+        break;
+      }
+
+      for (id, slice) in running {
+        self.finish(id, slice);
+      }
+    }
+
+    #[cfg(not(feature = "sync"))]
+    while self.step().is_some() {}
+  }
+
+  /// Pops the next task in the queue which still exists.
+  fn next_ready(&mut self) -> Option<TaskId> {
+    loop {
+      let id = self.queue.pop_front()?;
+
+      if self.tasks.contains_key(&id) {
+        break Some(id);
+      }
+    }
+  }
+
+  /// Starts running a slice of a task, which has finished already unless it
+  /// was started on the thread of the task.
+  fn start(&mut self, id: TaskId) -> Slice {
+    let task = self.tasks.get_mut(&id).expect("the task exists");
+    let context = core::mem::take(&mut task.context);
+
+    #[cfg(feature = "sync")]
+    {
+      if task.thread.is_none() {
+        task.thread = TaskThread::spawn();
+      }
+
+      if let Some(thread) = task.thread.as_mut() {
+        let command = Command {
+          engine: self.engine.clone(),
+          // A preempted task carries on with the context on its thread.
+          context: (!thread.is_preempted).then_some(context),
+          jobs: task.exprs.clone(),
+          inbox: core::mem::take(&mut task.inbox),
+          slice: self.slice,
+        };
+
+        if thread.commands.send(command).is_ok() {
+          return Slice::Started;
+        }
+
+        // The thread has stopped, so it's joined to find out why.
+        thread.join();
+        unreachable!("task thread stopped without panicking");
+      }
+    }
+
+    let budget = context.ops().saturating_add(self.slice);
+    let (context, error) =
+      run_slice(&self.engine, context, &task.exprs, || budget);
+
+    Slice::Finished(Box::new(context), error)
+  }
+
+  /// Waits for a slice of a task to finish, and delivers the messages it sent.
+  fn finish(&mut self, id: TaskId, slice: Slice) {
+    let task = self.tasks.get_mut(&id).expect("the task exists");
+
+    match slice {
+      Slice::Finished(context, error) => {
+        task.context = *context;
+        task.error = error;
+      }
+      #[cfg(feature = "sync")]
+      Slice::Started => {
+        let thread = task.thread.as_mut().expect("the task has a thread");
+
+        match thread.reports.recv() {
+          Ok(Report::Yielded(context, error)) => {
+            thread.is_preempted = false;
+            task.context = *context;
+            task.error = error.map(|x| *x);
+          }
+          Ok(Report::Preempted(context)) => {
+            thread.is_preempted = true;
+            task.context = *context;
+          }
+          Err(_) => {
+            thread.join();
+            unreachable!("task thread stopped without panicking");
+          }
+        }
+      }
+    }
+
+    let outbox = core::mem::take(&mut task.context.mailbox_mut().outbox);
+
+    if task.status() == TaskStatus::Ready {
+      self.queue.push_back(id);
+    } else {
+      // The thread is only kept while the task has anything to run.
+      #[cfg(feature = "sync")]
+      {
+        task.thread = None;
+      }
+    }
+
+    for (to, message) in outbox {
+      self.send(to, message);
+    }
+  }
+}
+
+/// A slice of a task which has been started.
+enum Slice {
+  Finished(Box<Context>, Option<RunError>),
+  /// The slice is running on the thread of the task.
+  #[cfg(feature = "sync")]
+  Started,
+}
+
+/// Tells the thread of a task to run a slice.
+#[cfg(feature = "sync")]
+struct Command {
+  engine: SharedEngine,
+  /// The context to run the slice with, or [`None`] if the task is carrying
+  /// on with the expression it was preempted in.
+  context: Option<Context>,
+  jobs: Jobs,
+  inbox: Vec<Expr>,
+  slice: usize,
+}
+
+/// What the thread of a task did with a slice.
+#[cfg(feature = "sync")]
+enum Report {
+  /// The slice ended between expressions, possibly with an error.
+  Yielded(Box<Context>, Option<Box<RunError>>),
+  /// The slice ended in the middle of an expression, with a copy of the
+  /// context as it was then.
+  Preempted(Box<Context>),
+}
+
+/// The thread which a task runs on.
+#[cfg(feature = "sync")]
+#[derive(Debug)]
+struct TaskThread {
+  commands: Sender<Command>,
+  reports: Receiver<Report>,
+  handle: Option<JoinHandle<()>>,
+  is_preempted: bool,
+}
+
+/// The channels and budget of the slice which is running on this thread.
+#[cfg(feature = "sync")]
+struct Current {
+  commands: Receiver<Command>,
+  reports: Sender<Report>,
+  budget: usize,
+}
+
+#[cfg(feature = "sync")]
+thread_local! {
+  static CURRENT: RefCell<Option<Current>> = const { RefCell::new(None) };
+}
+
+#[cfg(feature = "sync")]
+impl TaskThread {
+  /// Spawns a thread with a stack of [`THREAD_STACK_SIZE`], or returns
+  /// [`None`] if it couldn't be spawned, in which case the task runs on the
+  /// calling thread instead.
+  fn spawn() -> Option<Self> {
+    let (commands, commands_rx) = mpsc::channel::<Command>();
+    let (reports_tx, reports) = mpsc::channel();
+
+    let handle = thread::Builder::new()
+      .name("task".into())
+      .stack_size(THREAD_STACK_SIZE)
+      .spawn(move || {
+        CURRENT.with(|x| {
+          *x.borrow_mut() = Some(Current {
+            commands: commands_rx,
+            reports: reports_tx,
+            budget: usize::MAX,
+          })
+        });
+
+        while let Some(command) =
+          CURRENT.with(|x| x.borrow().as_ref()?.commands.recv().ok())
+        {
+          // A preempted task is only resumed from within `preempt`.
+          let Some(context) = command.context else {
+            break;
+          };
+
+          let budget = context.ops().saturating_add(command.slice);
+          set_budget(budget);
+
+          let (context, error) =
+            run_slice(&command.engine, context, &command.jobs, budget_of);
+          let report = Report::Yielded(Box::new(context), error.map(Box::new));
+
+          if CURRENT
+            .with(|x| x.borrow().as_ref()?.reports.send(report).ok())
+            .is_none()
+          {
+            break;
+          }
+        }
+      })
+      .ok()?;
+
+    Some(Self {
+      commands,
+      reports,
+      handle: Some(handle),
+      is_preempted: false,
+    })
+  }
+
+  /// Joins the thread, resuming the panic it stopped with.
+  fn join(&mut self) {
+    if let Some(Err(payload)) = self.handle.take().map(JoinHandle::join) {
+      std::panic::resume_unwind(payload);
+    }
+  }
+}
+
+#[cfg(feature = "sync")]
+fn set_budget(budget: usize) {
+  CURRENT.with(|x| {
+    if let Some(current) = x.borrow_mut().as_mut() {
+      current.budget = budget;
+    }
+  });
+}
+
+#[cfg(feature = "sync")]
+fn budget_of() -> usize {
+  CURRENT.with(|x| x.borrow().as_ref().map_or(usize::MAX, |x| x.budget))
+}
+
+/// Preempts the task running on this thread if it has used up its slice,
+/// waiting until it's resumed. This is called before each expression is
+/// evaluated, and does nothing outside of the thread of a task.
+///
+/// Fails with [`RunErrorReason::Cancelled`] if the task was removed, or the
+/// [`Scheduler`] dropped, while it was preempted.
+#[cfg(feature = "sync")]
+pub(crate) fn preempt(context: &mut Context) -> Result<(), RunErrorReason> {
+  CURRENT.with(|x| {
+    let mut current = x.borrow_mut();
+    let Some(current) = current.as_mut() else {
+      return Ok(());
+    };
+
+    if context.ops() < current.budget {
+      return Ok(());
+    }
+
+    // The copy carries the messages sent so far, so they're only delivered
+    // once.
+    let copy = Box::new(context.clone());
+    context.mailbox_mut().outbox.clear();
+
+    let command = current
+      .reports
+      .send(Report::Preempted(copy))
+      .ok()
+      .and_then(|_| current.commands.recv().ok())
+      .ok_or(RunErrorReason::Cancelled)?;
+
+    for message in command.inbox {
+      context.mailbox_mut().deliver(message);
+    }
+    current.budget = context.ops().saturating_add(command.slice);
+
+    Ok(())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::prelude::*;
+
+  fn exprs(source: &str) -> Vec<Expr> {
+    let mut lexer = Lexer::new(Source::new("", source));
+    crate::parser::parse(&mut lexer).unwrap()
+  }
+
+  #[test]
+  fn tasks_run_in_round_robin() {
+    let mut scheduler = Scheduler::new(Engine::new()).with_slice(1);
+
+    let a = scheduler.spawn(Context::new(), exprs("1 2 3"));
+    let b = scheduler.spawn(Context::new(), exprs("4"));
+
+    assert_eq!(scheduler.step(), Some(a));
+    assert_eq!(scheduler.step(), Some(b));
+    assert_eq!(scheduler.status(b), Some(TaskStatus::Finished));
+    assert_eq!(scheduler.step(), Some(a));
+    assert_eq!(scheduler.step(), Some(a));
+    assert_eq!(scheduler.step(), None);
+
+    assert_eq!(scheduler.status(a), Some(TaskStatus::Finished));
+    assert_eq!(
+      scheduler
+        .context(a)
+        .unwrap()
+        .stack()
+        .iter()
+        .map(|expr| &expr.kind)
+        .collect::<Vec<_>>(),
+      vec![
+        &ExprKind::Integer(1),
+        &ExprKind::Integer(2),
+        &ExprKind::Integer(3)
+      ]
+    );
+  }
+
+  #[test]
+  fn failed_tasks_keep_their_error() {
+    let mut scheduler = Scheduler::new(Engine::new());
+
+    let a = scheduler.spawn(Context::new(), exprs("drop 1"));
+    scheduler.run();
+
+    assert_eq!(scheduler.status(a), Some(TaskStatus::Failed));
+    assert_eq!(
+      scheduler.error(a).map(|error| error.reason),
+      Some(RunErrorReason::StackUnderflow)
+    );
+    assert!(!scheduler.send(a, ExprKind::Nil.into()));
+  }
+
+  #[test]
+  fn messages_are_delivered_after_yielding() {
+    let mut scheduler = Scheduler::new(Engine::new());
+
+    let mut context = Context::new();
+    context
+      .mailbox_mut()
+      .send(TaskId::new(1), ExprKind::Integer(1).into());
+
+    let a = scheduler.spawn(context, exprs("0"));
+    let b = scheduler.spawn(Context::new(), exprs("0"));
+
+    assert_eq!(scheduler.step(), Some(a));
+    assert_eq!(scheduler.context(b).unwrap().mailbox().pending(), 1);
+
+    assert!(scheduler.send(a, ExprKind::Integer(2).into()));
+    assert_eq!(scheduler.context(a).unwrap().mailbox().pending(), 1);
+  }
+
+  #[test]
+  fn actors_handle_messages_by_type() {
+    let mut scheduler = Scheduler::new(Engine::new());
+
+    let mut context = Context::new();
+    context.mailbox_mut().on(
+      Symbol::from_ref("add"),
+      exprs("(fn \"value\" prop swap drop 1 +)").remove(0),
+    );
+
+    let a = scheduler.spawn(context, exprs("0 drop"));
+    scheduler.run();
+
+    assert_eq!(scheduler.status(a), Some(TaskStatus::Waiting));
+    assert!(scheduler.is_idle());
+
+    let message = |ty: &str| {
+      let mut record = HashMap::new();
+      record
+        .insert(Symbol::from_ref("type"), ExprKind::String(ty.into()).into());
+      record.insert(Symbol::from_ref("value"), ExprKind::Integer(1).into());
+
+      Expr::from(ExprKind::Record(record.into()))
+    };
+
+    assert!(scheduler.send(a, message("add")));
+    assert!(scheduler.send(a, message("unknown")));
+    assert_eq!(scheduler.status(a), Some(TaskStatus::Ready));
+
+    scheduler.run();
+
+    assert_eq!(scheduler.status(a), Some(TaskStatus::Waiting));
+    assert_eq!(
+      scheduler
+        .context(a)
+        .unwrap()
+        .stack()
+        .iter()
+        .map(|expr| &expr.kind)
+        .collect::<Vec<_>>(),
+      vec![&ExprKind::Integer(2)]
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "serde")]
+  fn checkpoints_resume_where_they_left_off() {
+    let mut scheduler = Scheduler::new(Engine::new()).with_slice(1);
+
+    let a = scheduler.spawn(Context::new(), exprs("1 2 +"));
+    scheduler.step();
+
+    let checkpoint = scheduler.checkpoint(a).unwrap();
+    assert_eq!(checkpoint.exprs.len(), 2);
+
+    let checkpoint: Checkpoint =
+      serde_json::from_str(&serde_json::to_string(&checkpoint).unwrap())
+        .unwrap();
+
+    let mut scheduler = Scheduler::new(Engine::new());
+    let b = scheduler.resume(checkpoint);
+    scheduler.run();
+
+    let context = scheduler.context(b).unwrap();
+    assert_eq!(context.ops(), 3);
+    assert_eq!(
+      context.stack().iter().map(|x| &x.kind).collect::<Vec<_>>(),
+      vec![&ExprKind::Integer(3)]
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "sync")]
+  fn long_expressions_are_preempted() {
+    let mut scheduler = Scheduler::new(Engine::new()).with_slice(10);
+
+    let mut context = Context::new();
+    context.mailbox_mut().on(
+      Symbol::from_ref("add"),
+      exprs("(fn \"value\" prop swap drop 1 +)").remove(0),
+    );
+
+    let a = scheduler.spawn(context, exprs("0 1000 '[1 +] times"));
+    let b = scheduler.spawn(Context::new(), exprs("1"));
+
+    assert_eq!(scheduler.step(), Some(a));
+    assert_eq!(scheduler.step(), Some(b));
+
+    // The loop has only just started, and can't be checkpointed in the middle.
+    assert_eq!(scheduler.status(a), Some(TaskStatus::Ready));
+    assert_eq!(scheduler.status(b), Some(TaskStatus::Finished));
+    assert!(scheduler.checkpoint(a).is_none());
+    assert!(scheduler.context(a).unwrap().ops() < 100);
+
+    // Messages sent in the meantime are handled once the loop has finished.
+    let message = HashMap::from([
+      (
+        Symbol::from_ref("type"),
+        ExprKind::String("add".into()).into(),
+      ),
+      (Symbol::from_ref("value"), ExprKind::Integer(1).into()),
+    ]);
+    assert!(scheduler.send(a, ExprKind::Record(message.into()).into()));
+    scheduler.run();
+
+    assert_eq!(scheduler.status(a), Some(TaskStatus::Waiting));
+    assert_eq!(
+      scheduler.context(a).unwrap().stack(),
+      &[ExprKind::Integer(1000).into(), ExprKind::Integer(2).into()]
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "sync")]
+  fn runs_tasks_on_worker_threads() {
+    let mut scheduler = Scheduler::new(Engine::new())
+      .with_slice(100)
+      .with_workers(4);
+
+    let ids = (0..16)
+      .map(|i| {
+        let code = format!("0 {} '[1 +] times", 1000 + i);
+        scheduler.spawn(Context::new(), exprs(&code))
+      })
+      .collect::<Vec<_>>();
+    scheduler.run();
+
+    for (i, id) in ids.into_iter().enumerate() {
+      assert_eq!(
+        scheduler.remove(id).unwrap().unwrap().stack(),
+        &[ExprKind::Integer(1000 + i as i64).into()]
+      );
+    }
+  }
+
+  #[test]
+  #[cfg(feature = "sync")]
+  fn removing_a_preempted_task_cancels_it() {
+    let mut scheduler = Scheduler::new(Engine::new()).with_slice(10);
+
+    let a = scheduler.spawn(Context::new(), exprs("'['[1] loop] try"));
+    scheduler.step();
+    assert_eq!(scheduler.status(a), Some(TaskStatus::Ready));
+
+    assert!(scheduler.remove(a).unwrap().is_ok());
+    assert!(scheduler.step().is_none());
+  }
+}
@@ -1,18 +1,68 @@
 use core::fmt;
-use std::{cell::RefCell, collections::HashMap, fmt::Formatter, rc::Rc};
+use std::{collections::HashMap, fmt::Formatter};
 
+#[cfg(feature = "serde")]
 use serde::{
   ser::{Serialize, SerializeMap},
   Deserialize, Deserializer,
 };
 
-use crate::{chain::Chain, expr::FnScope, prelude::*};
+use crate::{
+  chain::Chain, effect::StackEffect, expr::FnScope, prelude::*, shared::Shared,
+};
 
-pub type Val = Rc<RefCell<Chain<Option<Expr>>>>;
+pub type Val = Shared<Chain<Option<Expr>>>;
 
 #[derive(Default)]
 pub struct Scope {
   pub items: HashMap<Symbol, Val>,
+  /// The metadata of the items which have any. It's shared with the scopes
+  /// duplicated from this one until either changes it.
+  pub meta: Persistent<HashMap<Symbol, Meta>>,
+}
+
+/// What's known about an item besides its value.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Meta {
+  /// The text of the `##` doc comments before its definition.
+  pub doc: Option<String>,
+  /// The stack effect declared in its doc comments.
+  pub effect: Option<StackEffect>,
+}
+
+impl Meta {
+  /// Creates the metadata from the text of doc comments, taking the first
+  /// line which is a stack effect, such as `( a b -- c )`, as the declared
+  /// effect.
+  pub fn from_doc(doc: Option<String>) -> Self {
+    let Some(doc) = doc else {
+      return Self::default();
+    };
+
+    let mut effect = None;
+    let lines = doc
+      .lines()
+      .filter(|line| {
+        if effect.is_none() {
+          effect = line.parse::<StackEffect>().ok();
+          effect.is_none()
+        } else {
+          true
+        }
+      })
+      .collect::<Vec<_>>();
+    let doc = lines.join("\n").trim().to_owned();
+
+    Self {
+      doc: (!doc.is_empty()).then_some(doc),
+      effect,
+    }
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.doc.is_none() && self.effect.is_none()
+  }
 }
 
 impl PartialEq for Scope {
@@ -35,6 +85,7 @@ impl PartialEq for Scope {
   }
 }
 
+#[cfg(feature = "serde")]
 impl Serialize for Scope {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
   where
@@ -42,14 +93,13 @@ impl Serialize for Scope {
   {
     let mut map = serializer.serialize_map(Some(self.items.len()))?;
     for (k, v) in self.items.iter() {
-      // TODO: don't unwrap and handle the error somehow
-      let expr: Expr = v.borrow().val().unwrap();
-      map.serialize_entry(k, &expr)?;
+      map.serialize_entry(k, &v.borrow().val())?;
     }
     map.end()
   }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for Scope {
   fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
   where
@@ -79,10 +129,8 @@ impl<'de> Deserialize<'de> for Scope {
       .into_iter()
       .map(|(k, v)| {
         let val = match v {
-          DeserializeVal::Some(expr) => {
-            Rc::new(RefCell::new(Chain::new(Some(expr))))
-          }
-          DeserializeVal::None => Rc::new(RefCell::new(Chain::new(None))),
+          DeserializeVal::Some(expr) => Shared::new(Chain::new(Some(expr))),
+          DeserializeVal::None => Shared::new(Chain::new(None)),
         };
         (k, val)
       })
@@ -92,7 +140,10 @@ impl<'de> Deserialize<'de> for Scope {
       scope.insert(Symbol::from_ref(k.as_str()), v);
     }
 
-    Ok(Scope { items: scope })
+    Ok(Scope {
+      items: scope,
+      meta: Persistent::default(),
+    })
   }
 }
 
@@ -112,7 +163,10 @@ impl Clone for Scope {
       items.insert(*name, item.clone());
     }
 
-    Self { items }
+    Self {
+      items,
+      meta: self.meta.clone(),
+    }
   }
 }
 
@@ -122,12 +176,15 @@ impl Scope {
   }
 
   pub fn from(items: HashMap<Symbol, Val>) -> Self {
-    Self { items }
+    Self {
+      items,
+      meta: Persistent::default(),
+    }
   }
 
   pub fn define(&mut self, name: Symbol, item: Expr) -> Val {
     if let Some(c) = self.items.get(&name) {
-      let mut chain = RefCell::borrow_mut(c);
+      let mut chain = c.borrow_mut();
       match chain.is_root() {
         true => {
           chain.set(Some(item));
@@ -139,7 +196,7 @@ impl Scope {
 
       c.clone()
     } else {
-      let val = Rc::new(RefCell::new(Chain::new(Some(item))));
+      let val = Shared::new(Chain::new(Some(item)));
       self.items.insert(name, val.clone());
 
       val
@@ -150,7 +207,7 @@ impl Scope {
     self
       .items
       .entry(name)
-      .or_insert_with(|| Rc::new(RefCell::new(Chain::new(None))));
+      .or_insert_with(|| Shared::new(Chain::new(None)));
   }
 
   pub fn set(
@@ -159,7 +216,7 @@ impl Scope {
     item: Expr,
   ) -> Result<Val, RunErrorReason> {
     if let Some(c) = self.items.get_mut(&name) {
-      let mut chain = RefCell::borrow_mut(c);
+      let mut chain = c.borrow_mut();
       chain.set(Some(item));
 
       Ok(c.clone())
@@ -193,6 +250,12 @@ impl Scope {
         self.items.insert(name, item);
       }
     }
+
+    for (name, meta) in other.meta.iter() {
+      if !self.meta.contains_key(name) {
+        self.meta.insert(*name, meta.clone());
+      }
+    }
   }
 
   /// Creates a new scope, linking the new symbols to that of self (such as for a function call)
@@ -200,11 +263,47 @@ impl Scope {
     let mut items = HashMap::new();
 
     for (name, item) in self.items.iter() {
-      let mut item = RefCell::borrow_mut(item);
+      let mut item = item.borrow_mut();
       items.insert(*name, item.link());
     }
 
-    Self { items }
+    Self {
+      items,
+      meta: self.meta.clone(),
+    }
+  }
+
+  /// Returns the names which are bound in this scope itself, such as those
+  /// of a `let`, rather than linked from the scope it was duplicated from.
+  pub fn local_names(&self) -> Vec<Symbol> {
+    self
+      .items
+      .iter()
+      .filter(|(_, item)| item.borrow().is_root())
+      .map(|(name, _)| *name)
+      .collect()
+  }
+
+  /// Returns the metadata of `name`, if it has any.
+  #[inline]
+  pub fn meta(&self, name: Symbol) -> Option<&Meta> {
+    self.meta.get(&name)
+  }
+
+  /// Changes the metadata of `name` with `f`.
+  pub fn update_meta(&mut self, name: Symbol, f: impl FnOnce(&mut Meta)) {
+    let mut meta = self.meta(name).cloned().unwrap_or_default();
+    f(&mut meta);
+
+    // The metadata is only copied if it's shared and actually changes.
+    if self.meta(name).unwrap_or(&Meta::default()) == &meta {
+      return;
+    }
+
+    match meta.is_empty() {
+      true => self.meta.remove(&name),
+      false => self.meta.insert(name, meta),
+    };
   }
 }
 
@@ -218,6 +317,8 @@ impl<'s> Scanner<'s> {
     Self { scope }
   }
 
+  // The expression is handed back on errors, rather than being boxed.
+  #[allow(clippy::result_large_err)]
   pub fn scan(&mut self, expr: Expr) -> Result<Expr, (Expr, RunErrorReason)> {
     if expr.kind.is_function() {
       let expr = expr;
@@ -262,6 +363,116 @@ impl<'s> Scanner<'s> {
       Err((expr, RunErrorReason::InvalidFunction))
     }
   }
+
+  /// Like [`Scanner::capture`], but also turns the lists within `expr` which
+  /// use any of `names` into functions which capture this scope, so that
+  /// blocks such as `'[x 1 +]` can still be called once they leave it.
+  pub fn capture_blocks(&mut self, expr: Expr, names: &[Symbol]) -> Expr {
+    if !uses_names(&expr.kind, names) {
+      return self.capture(expr);
+    }
+
+    let kind = match expr.kind {
+      ExprKind::List(x) => {
+        let function = Expr {
+          kind: ExprKind::Function {
+            scope: FnScope::Scoped(Scope::new()),
+            body: x.into_inner(),
+          },
+          info: expr.info,
+        };
+
+        return self.scan(function).unwrap_or_else(|(expr, _)| expr);
+      }
+      ExprKind::Lazy(x) => {
+        ExprKind::Lazy(Box::new(self.capture_blocks(*x, names)))
+      }
+      ExprKind::Record(x) => ExprKind::Record(
+        x.into_iter()
+          .map(|(key, x)| (key, self.capture_blocks(x, names)))
+          .collect(),
+      ),
+      ExprKind::Map(x) => ExprKind::Map(
+        x.into_iter()
+          .map(|(key, x)| (key, self.capture_blocks(x, names)))
+          .collect(),
+      ),
+      kind => {
+        return self.capture(Expr {
+          kind,
+          info: expr.info,
+        })
+      }
+    };
+
+    Expr {
+      kind,
+      info: expr.info,
+    }
+  }
+
+  /// Scans each function within `expr`, including those nested in lists,
+  /// records and lazy expressions, so that they capture this scope.
+  pub fn capture(&mut self, expr: Expr) -> Expr {
+    if !contains_function(&expr.kind) {
+      return expr;
+    }
+
+    let kind = match expr.kind {
+      ExprKind::Function { .. } => {
+        return self.scan(expr).unwrap_or_else(|(expr, _)| expr)
+      }
+      ExprKind::Lazy(x) => ExprKind::Lazy(Box::new(self.capture(*x))),
+      ExprKind::List(x) => {
+        ExprKind::List(x.into_iter().map(|x| self.capture(x)).collect())
+      }
+      ExprKind::Record(x) => ExprKind::Record(
+        x.into_iter()
+          .map(|(key, x)| (key, self.capture(x)))
+          .collect(),
+      ),
+      ExprKind::Map(x) => ExprKind::Map(
+        x.into_iter()
+          .map(|(key, x)| (key, self.capture(x)))
+          .collect(),
+      ),
+      kind => kind,
+    };
+
+    Expr {
+      kind,
+      info: expr.info,
+    }
+  }
+}
+
+/// Returns whether `kind` uses any of `names`, including within nested lists,
+/// records, lazy expressions and s-expressions.
+pub(crate) fn uses_names(kind: &ExprKind, names: &[Symbol]) -> bool {
+  match kind {
+    ExprKind::Symbol(x) => names.contains(x),
+    ExprKind::Lazy(x) => uses_names(&x.kind, names),
+    ExprKind::List(x) => x.iter().any(|x| uses_names(&x.kind, names)),
+    ExprKind::Record(x) => x.values().any(|x| uses_names(&x.kind, names)),
+    ExprKind::Map(x) => x.values().any(|x| uses_names(&x.kind, names)),
+    ExprKind::SExpr { call, body } => {
+      names.contains(call) || body.iter().any(|x| uses_names(&x.kind, names))
+    }
+    _ => false,
+  }
+}
+
+/// Returns whether `kind` is or contains a function which could capture a
+/// scope.
+pub(crate) fn contains_function(kind: &ExprKind) -> bool {
+  match kind {
+    ExprKind::Function { .. } => true,
+    ExprKind::Lazy(x) => contains_function(&x.kind),
+    ExprKind::List(x) => x.iter().any(|x| contains_function(&x.kind)),
+    ExprKind::Record(x) => x.values().any(|x| contains_function(&x.kind)),
+    ExprKind::Map(x) => x.values().any(|x| contains_function(&x.kind)),
+    _ => false,
+  }
 }
 
 #[cfg(test)]
@@ -286,6 +497,47 @@ mod tests {
     );
   }
 
+  #[test]
+  fn records_doc_comments() {
+    let source = Source::new(
+      "",
+      "## Doubles a number.\n'(fn 2 *) 'double def
+      ## The answer.\n(def 'answer 42)
+      1 'a def
+      ## Redefined without one.\n2 'b def 3 'b def",
+    );
+    let mut lexer = Lexer::new(source);
+    let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+    let engine = Engine::new();
+    let context = engine.run(Context::new(), exprs).unwrap();
+    let doc = |name: &str| {
+      context
+        .scope()
+        .meta(Symbol::from_ref(name))
+        .and_then(|x| x.doc.clone())
+    };
+
+    assert_eq!(doc("double").as_deref(), Some("Doubles a number."));
+    assert_eq!(doc("answer").as_deref(), Some("The answer."));
+    assert_eq!(doc("a"), None);
+    assert_eq!(doc("b"), None);
+  }
+
+  #[test]
+  fn takes_stack_effects_from_docs() {
+    let meta = Meta::from_doc(Some("( a b -- c )\nAdds two numbers.".into()));
+    assert_eq!(
+      meta.effect.map(|x| x.to_string()).as_deref(),
+      Some("( a b -- c )")
+    );
+    assert_eq!(meta.doc.as_deref(), Some("Adds two numbers."));
+
+    let meta = Meta::from_doc(Some("( -- )".into()));
+    assert_eq!(meta.effect, Some(StackEffect::default()));
+    assert_eq!(meta.doc, None);
+  }
+
   #[test]
   fn function_scopes_are_isolated() {
     let source = Source::new("", "'(fn 0 'a def) call");
@@ -541,4 +793,32 @@ mod tests {
       vec![&ExprKind::Integer(0),]
     );
   }
+
+  #[test]
+  fn functions_in_lists_capture_scope() {
+    let source =
+      Source::new("", "'(fn 0 'a def [(fn a)] {b: (fn a)}) call 'b prop call");
+    let mut lexer = Lexer::new(source);
+    let exprs = crate::parser::parse(&mut lexer).unwrap();
+
+    let engine = Engine::new();
+    let mut context = Context::new().with_stack_capacity(32);
+    context = engine.run(context, exprs).unwrap();
+
+    assert_eq!(context.scope_item(Symbol::new("a".into())), None);
+    assert_eq!(
+      context.stack().last().map(|expr| &expr.kind),
+      Some(&ExprKind::Integer(0))
+    );
+
+    let ExprKind::List(ref items) = context.stack()[0].kind else {
+      panic!("expected a list");
+    };
+    context = engine.call_expr(context.clone(), items[0].clone()).unwrap();
+
+    assert_eq!(
+      context.stack().last().map(|expr| &expr.kind),
+      Some(&ExprKind::Integer(0))
+    );
+  }
 }
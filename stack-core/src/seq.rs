@@ -0,0 +1,288 @@
+//! Lazy sequences, which produce their items one at a time.
+//!
+//! A [`Seq`] is a source, such as a range or a list, wrapped in any number of
+//! adapters, such as `map` or `take`. Nothing runs until the sequence is
+//! stepped with [`Seq::next`], which runs just enough of the pipeline to
+//! produce the next item, so an infinite range can be mapped and then taken
+//! from without ever building a list.
+
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  context::Context,
+  engine::{Engine, RunError},
+  expr::{Expr, ExprKind},
+  persistent::Persistent,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Seq {
+  /// The integers from `next` up to (but not including) `end`, counting by
+  /// `step`, or forever if there's no `end`.
+  Range {
+    next: i64,
+    end: Option<i64>,
+    step: i64,
+  },
+  /// The items of a list, from `index` onwards.
+  List {
+    items: Persistent<Vec<Expr>>,
+    index: usize,
+  },
+
+  /// Calls `block` on each item, and produces what it leaves on top of the
+  /// stack.
+  Map { seq: Box<Seq>, block: Box<Expr> },
+  /// Produces the items for which `block` leaves `true` on top of the stack.
+  Filter { seq: Box<Seq>, block: Box<Expr> },
+  /// Produces at most `count` more items.
+  Take { seq: Box<Seq>, count: usize },
+  /// Produces `[lhs rhs]` lists, until either sequence ends.
+  Zip { lhs: Box<Seq>, rhs: Box<Seq> },
+}
+
+impl Seq {
+  /// Returns a range from `next` to `end` which counts down by one if `end` is
+  /// lower, and up by one otherwise.
+  pub fn range(next: i64, end: Option<i64>) -> Self {
+    let step = match end {
+      Some(end) if end < next => -1,
+      _ => 1,
+    };
+
+    Self::Range { next, end, step }
+  }
+
+  /// Returns a sequence over the items of `kind`, which can be a list, set or
+  /// sequence, or [`None`] if it can't be iterated.
+  pub fn from_kind(kind: ExprKind) -> Option<Self> {
+    match kind {
+      ExprKind::Seq(x) => Some(*x),
+      ExprKind::List(items) => Some(Self::List { items, index: 0 }),
+      ExprKind::Set(x) => Some(Self::List {
+        items: x.into_iter().map(|x| ExprKind::from(x).into()).collect(),
+        index: 0,
+      }),
+      _ => None,
+    }
+  }
+
+  /// Returns whether the sequence ends, which is `false` for an unbounded
+  /// range that's never taken from.
+  pub fn is_finite(&self) -> bool {
+    match self {
+      Self::Range { end, .. } => end.is_some(),
+      Self::List { .. } | Self::Take { .. } => true,
+      Self::Map { seq, .. } | Self::Filter { seq, .. } => seq.is_finite(),
+      Self::Zip { lhs, rhs } => lhs.is_finite() || rhs.is_finite(),
+    }
+  }
+
+  /// Runs the pipeline until it produces the next item, or [`None`] once the
+  /// sequence has ended.
+  pub fn next(
+    &mut self,
+    engine: &Engine,
+    mut context: Context,
+  ) -> Result<(Context, Option<Expr>), RunError> {
+    match self {
+      Self::Range { next, end, step } => {
+        let is_done = match *end {
+          Some(end) if *step > 0 => *next >= end,
+          Some(end) => *next <= end,
+          None => false,
+        };
+
+        if is_done {
+          return Ok((context, None));
+        }
+
+        let item = ExprKind::Integer(*next).into();
+        *next = next.saturating_add(*step);
+
+        Ok((context, Some(item)))
+      }
+      Self::List { items, index } => {
+        let item = items.get(*index).cloned();
+        *index += 1;
+
+        Ok((context, item))
+      }
+
+      Self::Map { seq, block } => match seq.next(engine, context)? {
+        (context, Some(item)) => {
+          let (context, item) = call_block(engine, context, block, item)?;
+          Ok((context, Some(item)))
+        }
+        (context, None) => Ok((context, None)),
+      },
+      Self::Filter { seq, block } => loop {
+        let item;
+        (context, item) = seq.next(engine, context)?;
+
+        let Some(item) = item else {
+          return Ok((context, None));
+        };
+
+        let keep;
+        (context, keep) = call_block(engine, context, block, item.clone())?;
+
+        if keep.kind.is_truthy() {
+          return Ok((context, Some(item)));
+        }
+      },
+      Self::Take { seq, count } => {
+        if *count == 0 {
+          return Ok((context, None));
+        }
+
+        *count -= 1;
+        seq.next(engine, context)
+      }
+      Self::Zip { lhs, rhs } => {
+        let (context, lhs) = lhs.next(engine, context)?;
+        let Some(lhs) = lhs else {
+          return Ok((context, None));
+        };
+
+        let (context, rhs) = rhs.next(engine, context)?;
+        let Some(rhs) = rhs else {
+          return Ok((context, None));
+        };
+
+        Ok((context, Some(ExprKind::List(vec![lhs, rhs].into()).into())))
+      }
+    }
+  }
+
+  /// Steps the sequence until it ends, returning every item it produced.
+  ///
+  /// This never returns for a sequence which isn't [finite](Self::is_finite),
+  /// unless the engine runs out of fuel.
+  pub fn collect(
+    mut self,
+    engine: &Engine,
+    mut context: Context,
+  ) -> Result<(Context, Vec<Expr>), RunError> {
+    let mut items = Vec::new();
+
+    loop {
+      let item;
+      (context, item) = self.next(engine, context)?;
+
+      match item {
+        Some(item) => items.push(item),
+        None => return Ok((context, items)),
+      }
+    }
+  }
+}
+
+/// Calls `block` with `item` on top of the stack, and pops what it leaves.
+fn call_block(
+  engine: &Engine,
+  mut context: Context,
+  block: &Expr,
+  item: Expr,
+) -> Result<(Context, Expr), RunError> {
+  context.stack_push(item)?;
+  let mut context = engine.call_expr(context, block.clone())?;
+  let item = context.stack_pop(block)?;
+
+  Ok((context, item))
+}
+
+impl fmt::Display for Seq {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Range { next, end, step } => {
+        let end = match end {
+          Some(end) => end.to_string(),
+          None => "nil".into(),
+        };
+
+        match *self == Self::range(*next, end.parse().ok()) {
+          true => write!(f, "{next} {end} range"),
+          false => write!(f, "{next} {end} {step} range-by"),
+        }
+      }
+      Self::List { items, index } => {
+        write!(f, "[")?;
+
+        core::iter::once("")
+          .chain(core::iter::repeat(" "))
+          .zip(items.iter().skip(*index))
+          .try_for_each(|(sep, x)| write!(f, "{sep}{x}"))?;
+
+        write!(f, "]")
+      }
+
+      Self::Map { seq, block } => write!(f, "{seq} {block} map"),
+      Self::Filter { seq, block } => write!(f, "{seq} {block} filter"),
+      Self::Take { seq, count } => write!(f, "{seq} {count} take"),
+      Self::Zip { lhs, rhs } => write!(f, "{lhs} {rhs} zip"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn integers(items: Vec<Expr>) -> Vec<i64> {
+    items
+      .into_iter()
+      .map(|x| match x.kind {
+        ExprKind::Integer(x) => x,
+        kind => panic!("expected an integer, found {kind:?}"),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn takes_from_infinite_ranges() {
+    let seq = Seq::Take {
+      seq: Box::new(Seq::range(0, None)),
+      count: 4,
+    };
+    assert!(seq.is_finite());
+
+    let (_, items) = seq.collect(&Engine::new(), Context::new()).unwrap();
+    assert_eq!(integers(items), vec![0, 1, 2, 3]);
+  }
+
+  #[test]
+  fn ranges_count_down() {
+    let seq = Seq::range(3, Some(0));
+
+    let (_, items) = seq.collect(&Engine::new(), Context::new()).unwrap();
+    assert_eq!(integers(items), vec![3, 2, 1]);
+  }
+
+  #[test]
+  fn ranges_count_by_steps() {
+    let seq = Seq::Range {
+      next: 0,
+      end: Some(7),
+      step: 3,
+    };
+    assert_eq!(seq.to_string(), "0 7 3 range-by");
+
+    let (_, items) = seq.collect(&Engine::new(), Context::new()).unwrap();
+    assert_eq!(integers(items), vec![0, 3, 6]);
+
+    // A step which counts away from the end never gets there.
+    let seq = Seq::Range {
+      next: 0,
+      end: Some(7),
+      step: -1,
+    };
+
+    let (_, items) = seq.collect(&Engine::new(), Context::new()).unwrap();
+    assert!(items.is_empty());
+  }
+}
@@ -0,0 +1,186 @@
+//! Shared mutable values, which are thread-safe with the `sync` feature.
+//!
+//! Without `sync`, a [`Shared`] is an `Rc<RefCell<T>>`. With it, it's an
+//! `Arc<RwLock<T>>`, and the functions held by [`Engine`]s and [`Module`]s
+//! must be [`Send`] and [`Sync`], so that the [`Engine`], [`Context`] and
+//! [`Expr`] are too. This is a cost for code which stays on one thread, which
+//! is why it's opt-in.
+//!
+//! [`Engine`]: crate::engine::Engine
+//! [`Module`]: crate::module::Module
+//! [`Context`]: crate::context::Context
+//! [`Expr`]: crate::expr::Expr
+
+use core::fmt;
+
+#[cfg(not(feature = "sync"))]
+mod inner {
+  pub type Inner<T> = std::rc::Rc<core::cell::RefCell<T>>;
+  pub type Ref<'a, T> = core::cell::Ref<'a, T>;
+  pub type RefMut<'a, T> = core::cell::RefMut<'a, T>;
+
+  #[inline]
+  pub fn new<T>(value: T) -> Inner<T> {
+    std::rc::Rc::new(core::cell::RefCell::new(value))
+  }
+
+  #[inline]
+  pub fn borrow<T>(inner: &Inner<T>) -> Ref<'_, T> {
+    inner.borrow()
+  }
+
+  #[inline]
+  pub fn borrow_mut<T>(inner: &Inner<T>) -> RefMut<'_, T> {
+    inner.borrow_mut()
+  }
+
+  #[inline]
+  pub fn ptr_eq<T>(lhs: &Inner<T>, rhs: &Inner<T>) -> bool {
+    std::rc::Rc::ptr_eq(lhs, rhs)
+  }
+}
+
+#[cfg(feature = "sync")]
+mod inner {
+  use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+  pub type Inner<T> = Arc<RwLock<T>>;
+  pub type Ref<'a, T> = RwLockReadGuard<'a, T>;
+  pub type RefMut<'a, T> = RwLockWriteGuard<'a, T>;
+
+  #[inline]
+  pub fn new<T>(value: T) -> Inner<T> {
+    Arc::new(RwLock::new(value))
+  }
+
+  // A panic while holding the lock can't leave the value half-changed, since
+  // it's only ever replaced whole, so poisoning is ignored.
+  #[inline]
+  pub fn borrow<T>(inner: &Inner<T>) -> Ref<'_, T> {
+    inner.read().unwrap_or_else(|x| x.into_inner())
+  }
+
+  #[inline]
+  pub fn borrow_mut<T>(inner: &Inner<T>) -> RefMut<'_, T> {
+    inner.write().unwrap_or_else(|x| x.into_inner())
+  }
+
+  #[inline]
+  pub fn ptr_eq<T>(lhs: &Inner<T>, rhs: &Inner<T>) -> bool {
+    Arc::ptr_eq(lhs, rhs)
+  }
+}
+
+pub use inner::{Ref, RefMut};
+
+/// A value which is shared between its clones, and can be changed through any
+/// of them.
+pub struct Shared<T>(inner::Inner<T>);
+
+impl<T> Shared<T> {
+  #[inline]
+  pub fn new(value: T) -> Self {
+    Self(inner::new(value))
+  }
+
+  #[inline]
+  pub fn borrow(&self) -> Ref<'_, T> {
+    inner::borrow(&self.0)
+  }
+
+  #[inline]
+  pub fn borrow_mut(&self) -> RefMut<'_, T> {
+    inner::borrow_mut(&self.0)
+  }
+
+  /// Returns whether both share the same value.
+  #[inline]
+  pub fn ptr_eq(&self, other: &Self) -> bool {
+    inner::ptr_eq(&self.0, &other.0)
+  }
+}
+
+impl<T> Clone for Shared<T> {
+  #[inline]
+  fn clone(&self) -> Self {
+    Self(self.0.clone())
+  }
+}
+
+impl<T> Default for Shared<T>
+where
+  T: Default,
+{
+  #[inline]
+  fn default() -> Self {
+    Self::new(T::default())
+  }
+}
+
+impl<T> PartialEq for Shared<T>
+where
+  T: PartialEq,
+{
+  fn eq(&self, other: &Self) -> bool {
+    self.ptr_eq(other) || *self.borrow() == *other.borrow()
+  }
+}
+
+impl<T> fmt::Debug for Shared<T>
+where
+  T: fmt::Debug,
+{
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.borrow().fmt(f)
+  }
+}
+
+/// [`Send`] and [`Sync`] with the `sync` feature, and nothing without it.
+#[cfg(feature = "sync")]
+pub trait MaybeSync: Send + Sync {}
+
+#[cfg(feature = "sync")]
+impl<T> MaybeSync for T where T: Send + Sync {}
+
+/// [`Send`] and [`Sync`] with the `sync` feature, and nothing without it.
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSync {}
+
+#[cfg(not(feature = "sync"))]
+impl<T> MaybeSync for T {}
+
+#[cfg(all(test, feature = "sync"))]
+mod tests {
+  use crate::prelude::*;
+
+  fn assert_send_sync<T>()
+  where
+    T: Send + Sync,
+  {
+  }
+
+  #[test]
+  fn runtime_types_are_send_and_sync() {
+    assert_send_sync::<Engine>();
+    assert_send_sync::<Module>();
+    assert_send_sync::<Context>();
+    assert_send_sync::<Expr>();
+    assert_send_sync::<Snapshot>();
+  }
+
+  #[test]
+  fn runs_on_other_threads() {
+    let engine = Engine::new();
+    let source = Source::new("", "'(fn 2 *) 'double def 21 double");
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    let context = std::thread::scope(|s| {
+      s.spawn(|| engine.run(Context::new(), exprs).unwrap())
+        .join()
+        .unwrap()
+    });
+
+    assert_eq!(context.stack(), &[ExprKind::Integer(42).into()]);
+  }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::{
+  fs,
+  io::{self, BufReader, BufWriter, Write},
+  path::Path,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{expr::Expr, scope::Scope, symbol::Symbol};
+
+/// A copy of the stack and scopes of a [`Context`].
+///
+/// Unlike a [`Context`], this only holds plain values, so it can be persisted
+/// or inspected by external tools (e.g., as JSON with the `serde` feature).
+/// It's created with [`Context::snapshot`] and applied with
+/// [`Context::restore`].
+///
+/// [`Context`]: crate::context::Context
+/// [`Context::snapshot`]: crate::context::Context::snapshot
+/// [`Context::restore`]: crate::context::Context::restore
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot {
+  pub stack: Vec<Expr>,
+  /// The values of the items in the outermost scope. Items which have been
+  /// reserved but not yet defined are left out.
+  pub scope: HashMap<Symbol, Expr>,
+  /// The values of the items in each scope created by a `let` or function
+  /// call, from the outermost to the innermost.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub lets: Vec<HashMap<Symbol, Expr>>,
+}
+
+impl Snapshot {
+  /// Writes this [`Snapshot`] to `path` as JSON.
+  #[cfg(feature = "serde")]
+  pub fn save<P>(&self, path: P) -> io::Result<()>
+  where
+    P: AsRef<Path>,
+  {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    serde_json::to_writer(&mut writer, self)?;
+    writer.flush()
+  }
+
+  /// Reads a [`Snapshot`] written by [`Snapshot::save`] from `path`.
+  #[cfg(feature = "serde")]
+  pub fn load<P>(path: P) -> io::Result<Self>
+  where
+    P: AsRef<Path>,
+  {
+    let reader = BufReader::new(fs::File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+  }
+}
+
+pub(crate) fn scope_values(scope: &Scope) -> HashMap<Symbol, Expr> {
+  scope
+    .items
+    .iter()
+    .filter_map(|(name, val)| val.borrow().val().map(|x| (*name, x)))
+    .collect()
+}
+
+pub(crate) fn scope_from_values(values: HashMap<Symbol, Expr>) -> Scope {
+  let mut scope = Scope::new();
+  for (name, value) in values {
+    scope.define(name, value);
+  }
+
+  scope
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::prelude::*;
+
+  fn run(context: Context, code: &str) -> Context {
+    let mut lexer = Lexer::new(Source::new("", code));
+    let exprs = parse(&mut lexer).unwrap();
+
+    Engine::new().run(context, exprs).unwrap()
+  }
+
+  #[test]
+  fn captures_stack_and_scope() {
+    let snapshot = run(Context::new(), "1 'a def 2 a '(fn 'b)").snapshot();
+
+    assert_eq!(
+      snapshot
+        .stack
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>(),
+      vec!["2", "1", "(fn b)"]
+    );
+    assert_eq!(
+      snapshot.scope,
+      HashMap::from_iter([(
+        Symbol::from_ref("a"),
+        ExprKind::Integer(1).into()
+      )])
+    );
+    assert!(snapshot.lets.is_empty());
+  }
+
+  #[test]
+  fn captures_lets() {
+    let mut lexer =
+      Lexer::new(Source::new("", "1 'a def 2 '[a b halt] '[b] let"));
+    let exprs = parse(&mut lexer).unwrap();
+    let err = Engine::new().run(Context::new(), exprs).unwrap_err();
+
+    let snapshot = err.context.snapshot();
+
+    assert_eq!(
+      snapshot.stack,
+      vec![ExprKind::Integer(1).into(), ExprKind::Integer(2).into()]
+    );
+    assert_eq!(snapshot.lets.len(), 1);
+    assert_eq!(
+      snapshot.lets[0].get(&Symbol::from_ref("b")),
+      Some(&ExprKind::Integer(2).into())
+    );
+
+    let mut context = Context::new();
+    context.restore(snapshot.clone());
+    assert_eq!(context.snapshot(), snapshot);
+  }
+
+  #[test]
+  fn restores_into_a_context() {
+    let snapshot = run(Context::new(), "1 'a def 2").snapshot();
+
+    let mut context = Context::new();
+    context.restore(snapshot);
+    let context = run(context, "a 3 'a set a +");
+
+    assert_eq!(
+      context.stack(),
+      &[ExprKind::Integer(2).into(), ExprKind::Integer(4).into()]
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "serde")]
+  fn round_trips_through_json() {
+    let snapshot = run(Context::new(), "1 'a def [2 \"b\"] '(fn 3)").snapshot();
+
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let de: Snapshot = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(de, snapshot);
+  }
+}
@@ -1,8 +1,9 @@
 // See the file footer for the licence text.
 
 use core::{fmt, num::NonZeroUsize};
-use std::{fs, io, path::Path, sync::Arc};
+use std::{fs, io, ops::Range, path::Path, sync::Arc};
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Deserializer, Serialize};
 use unicode_segmentation::UnicodeSegmentation;
 
@@ -12,6 +13,7 @@ use unicode_segmentation::UnicodeSegmentation;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Source(Arc<SourceInner>);
 
+#[cfg(feature = "serde")]
 impl Serialize for Source {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
   where
@@ -21,6 +23,7 @@ impl Serialize for Source {
   }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for Source {
   fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
   where
@@ -113,6 +116,36 @@ impl Source {
     }
   }
 
+  /// Replaces the text within `range` with `text`.
+  ///
+  /// Only the line starts after the start of `range` are recalculated. Other
+  /// clones of this [`Source`] are left unchanged.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `range` is out-of-bounds, or is not on UTF-8 sequence
+  /// boundaries.
+  pub fn apply_edit(&mut self, range: Range<usize>, text: &str) {
+    let inner = Arc::make_mut(&mut self.0);
+    inner.source.replace_range(range.clone(), text);
+
+    let delta = text.len() as isize - range.len() as isize;
+    let first = inner.line_starts.partition_point(|&x| x <= range.start);
+    let last = inner.line_starts.partition_point(|&x| x <= range.end);
+
+    let inserted = text
+      .char_indices()
+      .filter(|&(_, c)| c == '\n')
+      .map(|(i, _)| range.start + i + 1);
+    let shifted = inner.line_starts[last..]
+      .iter()
+      .map(|&x| x.wrapping_add_signed(delta));
+
+    let tail = inserted.chain(shifted).collect::<Vec<_>>();
+    inner.line_starts.truncate(first);
+    inner.line_starts.extend(tail);
+  }
+
   /// Returns the line <code>&[str]</code> from a line number.
   ///
   /// The line number can be calculated via [`location`].
@@ -149,8 +182,9 @@ impl fmt::Display for Location {
   }
 }
 
-#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[derive(Debug, Clone, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 struct SourceInner {
   name: String,
   source: String,
@@ -169,6 +203,21 @@ mod test {
   use super::*;
   use test_case::case;
 
+  #[case("hello\nworld\n", 2..8, "y\nthe\nw" => "hey\nthe\nwrld\n" ; "replace")]
+  #[case("a\nb\nc\n", 1..4, "" => "ac\n" ; "remove lines")]
+  #[case("", 0..0, "\n\n" => "\n\n" ; "insert into empty")]
+  fn apply_edit(source: &str, range: Range<usize>, text: &str) -> String {
+    let mut source = Source::new("", source);
+    source.apply_edit(range, text);
+
+    assert_eq!(
+      source.0.line_starts,
+      Source::new("", source.source()).0.line_starts
+    );
+
+    source.source().into()
+  }
+
   #[case("" => vec![0] ; "empty")]
   #[case("hello\n" => vec![0, 6] ; "one")]
   #[case("hello\nthere\r\nworld\n" => vec![0, 6, 13, 19] ; "multiple")]
@@ -0,0 +1,115 @@
+//! Statistics about a run, such as how many expressions it evaluated.
+//!
+//! [`Engine::run_with_stats`] returns a [`RunStats`] alongside the result of
+//! the run, which is useful for asserting how much work a program does in
+//! tests, as well as for reporting it.
+//!
+//! [`Engine::run_with_stats`]: crate::engine::Engine::run_with_stats
+
+use core::fmt;
+use std::collections::HashMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{context::Context, intrinsic::Intrinsic};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RunStats {
+  pub exprs_evaluated: usize,
+  /// How many times each intrinsic was called.
+  pub intrinsic_counts: HashMap<Intrinsic, usize>,
+  /// The largest length the stack reached.
+  pub max_stack_depth: usize,
+  /// The most items which were in scope at once.
+  pub peak_scope_entries: usize,
+}
+
+impl RunStats {
+  #[inline]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Returns how many times `intrinsic` was called.
+  #[inline]
+  pub fn intrinsic_count(&self, intrinsic: Intrinsic) -> usize {
+    self.intrinsic_counts.get(&intrinsic).copied().unwrap_or(0)
+  }
+
+  #[inline]
+  pub(crate) fn record_intrinsic(&mut self, intrinsic: Intrinsic) {
+    *self.intrinsic_counts.entry(intrinsic).or_insert(0) += 1;
+  }
+
+  #[inline]
+  pub(crate) fn record_scope(&mut self, context: &Context) {
+    self.peak_scope_entries =
+      self.peak_scope_entries.max(context.scope().items.len());
+  }
+}
+
+impl fmt::Display for RunStats {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "exprs evaluated     {}", self.exprs_evaluated)?;
+    writeln!(f, "max stack depth     {}", self.max_stack_depth)?;
+    write!(f, "peak scope entries  {}", self.peak_scope_entries)?;
+
+    let mut counts = self.intrinsic_counts.iter().collect::<Vec<_>>();
+    counts.sort_by(|(lhs, lhs_count), (rhs, rhs_count)| {
+      rhs_count
+        .cmp(lhs_count)
+        .then_with(|| lhs.as_str().cmp(rhs.as_str()))
+    });
+
+    for (intrinsic, count) in counts {
+      write!(f, "\n{count:>10}  {intrinsic}")?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::prelude::*;
+
+  fn run(code: &str) -> RunStats {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    let (result, stats) = Engine::new().run_with_stats(Context::new(), exprs);
+    result.unwrap();
+
+    stats
+  }
+
+  #[test]
+  fn counts_exprs_and_intrinsics() {
+    let stats = run("1 2 + 3 4 + +");
+
+    assert_eq!(stats.exprs_evaluated, 7);
+    assert_eq!(stats.intrinsic_count(Intrinsic::Add), 3);
+    assert_eq!(stats.intrinsic_count(Intrinsic::Sub), 0);
+    assert_eq!(stats.max_stack_depth, 3);
+  }
+
+  #[test]
+  fn counts_work_which_grows_with_the_input() {
+    let times = |n: usize| {
+      run(&format!("0 {n} '[1 +] times")).intrinsic_count(Intrinsic::Add)
+    };
+
+    assert_eq!(times(10), 10);
+    assert_eq!(times(100), 100);
+  }
+
+  #[test]
+  fn records_peak_scope_entries() {
+    let stats = run("1 'a def 2 'b def");
+    assert_eq!(stats.peak_scope_entries, 2);
+  }
+}
@@ -1,7 +1,13 @@
-use core::{borrow::Borrow, fmt, hash::Hash};
+use core::{
+  borrow::Borrow,
+  fmt,
+  hash::{Hash, Hasher},
+};
+use std::sync::OnceLock;
 
 use compact_str::{CompactString, ToCompactString};
 use internment::Intern;
+#[cfg(feature = "serde")]
 use serde::{
   de::{self, Visitor},
   Deserialize, Deserializer, Serialize,
@@ -11,8 +17,66 @@ use crate::expr::ExprKind;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(transparent)]
-pub struct Symbol(Intern<CompactString>);
+pub struct Symbol(Intern<Name>);
+
+/// The interned value of a [`Symbol`], which is compared and hashed by its
+/// name alone.
+struct Name {
+  name: CompactString,
+  /// The namespace and function of a namespaced symbol, such as `str:len`,
+  /// which are split and interned the first time they're needed.
+  parts: OnceLock<Option<(Symbol, Symbol)>>,
+}
+
+impl fmt::Debug for Name {
+  #[inline]
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Debug::fmt(&self.name, f)
+  }
+}
+
+impl PartialEq for Name {
+  #[inline]
+  fn eq(&self, other: &Self) -> bool {
+    self.name == other.name
+  }
+}
+
+impl Eq for Name {}
+
+impl Hash for Name {
+  #[inline]
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    // This must hash the same as the `str` it borrows as.
+    self.name.as_str().hash(state);
+  }
+}
+
+impl Borrow<str> for Name {
+  #[inline]
+  fn borrow(&self) -> &str {
+    self.name.as_str()
+  }
+}
 
+impl From<CompactString> for Name {
+  #[inline]
+  fn from(name: CompactString) -> Self {
+    Self {
+      name,
+      parts: OnceLock::new(),
+    }
+  }
+}
+
+impl From<&str> for Name {
+  #[inline]
+  fn from(name: &str) -> Self {
+    Self::from(CompactString::new(name))
+  }
+}
+
+#[cfg(feature = "serde")]
 impl Serialize for Symbol {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
   where
@@ -23,6 +87,7 @@ impl Serialize for Symbol {
   }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for Symbol {
   fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
   where
@@ -43,7 +108,7 @@ impl<'de> Deserialize<'de> for Symbol {
       {
         // Convert the &str to CompactString and intern it
         let compact_string = CompactString::new(value);
-        Ok(Symbol(Intern::new(compact_string)))
+        Ok(Symbol::new(compact_string))
       }
 
       fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
@@ -52,7 +117,7 @@ impl<'de> Deserialize<'de> for Symbol {
       {
         // Convert the String to CompactString and intern it
         let compact_string = CompactString::new(value);
-        Ok(Symbol(Intern::new(compact_string)))
+        Ok(Symbol::new(compact_string))
       }
     }
 
@@ -64,23 +129,36 @@ impl Symbol {
   /// Creates a [`Symbol`].
   #[inline]
   pub fn new(value: CompactString) -> Self {
-    Self(Intern::new(value))
+    Self(Intern::new(value.into()))
   }
 
   /// Creates a [`Symbol`] from a reference.
   #[inline]
-  pub fn from_ref<'q, Q>(value: &'q Q) -> Self
+  pub fn from_ref<Q>(value: &Q) -> Self
   where
-    Q: 'q + ?Sized + Eq + Hash,
-    CompactString: Borrow<Q> + From<&'q Q>,
+    Q: ?Sized + AsRef<str>,
   {
-    Self(Intern::from_ref(value))
+    Self(Intern::from_ref(value.as_ref()))
   }
 
   /// Returns the <code>&[str]</code> for this [`Symbol`].
   #[inline]
   pub fn as_str(&self) -> &str {
-    self.0.as_str()
+    self.0.as_ref().name.as_str()
+  }
+
+  /// Splits a namespaced symbol, such as `str:len`, into its namespace and
+  /// function at the first `:`, or returns [`None`] if it has no `:`.
+  ///
+  /// The parts are only interned the first time, after which they're cached
+  /// on the symbol.
+  #[inline]
+  pub fn split_namespace(self) -> Option<(Symbol, Symbol)> {
+    *self.0.as_ref().parts.get_or_init(|| {
+      self.as_str().split_once(':').map(|(namespace, func)| {
+        (Self::from_ref(namespace), Self::from_ref(func))
+      })
+    })
   }
 }
 
@@ -95,3 +173,41 @@ impl From<ExprKind> for Symbol {
     Self::from_ref(value.to_compact_string().as_str())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn splits_namespaces() {
+    let symbol = Symbol::from_ref("str:len");
+    assert_eq!(
+      symbol.split_namespace(),
+      Some((Symbol::from_ref("str"), Symbol::from_ref("len")))
+    );
+    // The cached parts are shared by every copy of the symbol.
+    assert_eq!(
+      Symbol::new("str:len".into()).split_namespace(),
+      symbol.split_namespace()
+    );
+
+    assert_eq!(
+      Symbol::from_ref("a:b:c").split_namespace(),
+      Some((Symbol::from_ref("a"), Symbol::from_ref("b:c")))
+    );
+    assert_eq!(Symbol::from_ref("len").split_namespace(), None);
+  }
+
+  #[test]
+  fn equals_and_hashes_by_name() {
+    use std::collections::HashSet;
+
+    let symbol = Symbol::from_ref("foo");
+    assert_eq!(symbol, Symbol::new("foo".into()));
+    assert_ne!(symbol, Symbol::from_ref("bar"));
+    assert_eq!(symbol.as_str(), "foo");
+
+    let set = HashSet::from([symbol]);
+    assert!(set.contains(&Symbol::from_ref(&String::from("foo"))));
+  }
+}
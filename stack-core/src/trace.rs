@@ -0,0 +1,141 @@
+//! Tracing, which reports every expression the engine evaluates.
+//!
+//! A [`Tracer`] set with [`Engine::with_tracer`] is called with a
+//! [`TraceEvent`] once each expression has been evaluated, holding the stack
+//! from before and after it. Since an expression is reported once it's
+//! finished, the expressions it evaluated, such as the body of a function,
+//! are reported before it, one level deeper.
+//!
+//! [`Engine::with_tracer`]: crate::engine::Engine::with_tracer
+
+use core::{cell::Cell, fmt};
+use std::sync::Arc;
+
+use crate::{engine::RunErrorReason, expr::Expr};
+
+/// Called with each expression the engine evaluates.
+#[cfg(not(feature = "sync"))]
+pub type Tracer = Arc<dyn Fn(&TraceEvent<'_>)>;
+#[cfg(feature = "sync")]
+pub type Tracer = Arc<dyn Fn(&TraceEvent<'_>) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceEvent<'a> {
+  /// How many expressions were being evaluated around this one.
+  pub depth: usize,
+  pub expr: &'a Expr,
+  pub before: &'a [Expr],
+  /// The stack afterwards, which is the stack of the error if it failed.
+  pub after: &'a [Expr],
+  pub error: Option<RunErrorReason>,
+}
+
+impl fmt::Display for TraceEvent<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let stack = |f: &mut fmt::Formatter<'_>, stack: &[Expr]| {
+      write!(f, "[")?;
+      core::iter::once("")
+        .chain(core::iter::repeat(" "))
+        .zip(stack)
+        .try_for_each(|(sep, x)| write!(f, "{sep}{x}"))?;
+      write!(f, "]")
+    };
+
+    write!(f, "{:indent$}{}", "", self.expr, indent = self.depth * 2)?;
+
+    if let Some(info) = &self.expr.info {
+      write!(f, " ({info})")?;
+    }
+
+    write!(f, ": ")?;
+    stack(f, self.before)?;
+    write!(f, " -> ")?;
+    stack(f, self.after)?;
+
+    if let Some(error) = self.error {
+      write!(f, " ! {error}")?;
+    }
+
+    Ok(())
+  }
+}
+
+thread_local! {
+  static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Returns how many expressions are being evaluated on this thread.
+#[cfg(feature = "async")]
+#[inline]
+pub(crate) fn depth() -> usize {
+  DEPTH.get()
+}
+
+/// Runs `f` one level deeper, returning the depth it ran at.
+pub(crate) fn nested<T>(f: impl FnOnce() -> T) -> (usize, T) {
+  let depth = DEPTH.with(|x| x.replace(x.get() + 1));
+  let result = f();
+  DEPTH.with(|x| x.set(depth));
+
+  (depth, result)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+
+  use super::*;
+  use crate::prelude::*;
+
+  fn trace(code: &str) -> Vec<String> {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+
+    let tracer_lines = lines.clone();
+    let engine = Engine::new().with_tracer(Some(Arc::new(move |event| {
+      let mut expr = event.expr.clone();
+      expr.info = None;
+
+      tracer_lines.lock().unwrap().push(
+        TraceEvent {
+          expr: &expr,
+          ..*event
+        }
+        .to_string(),
+      );
+    })));
+
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+    let _ = engine.run(Context::new(), exprs);
+
+    let lines = lines.lock().unwrap();
+    lines.clone()
+  }
+
+  #[test]
+  fn traces_each_expression() {
+    assert_eq!(
+      trace("1 2 + '(fn 2 *) 'double def double"),
+      [
+        "1: [] -> [1]",
+        "2: [1] -> [1 2]",
+        "+: [1 2] -> [3]",
+        "(fn 2 *): [3] -> [3 (fn 2 *)]",
+        "double: [3 (fn 2 *)] -> [3 (fn 2 *) double]",
+        "def: [3 (fn 2 *) double] -> [3]",
+        "  2: [3] -> [3 2]",
+        "  *: [3 2] -> [6]",
+        "double: [3] -> [6]",
+      ]
+    );
+  }
+
+  #[test]
+  fn traces_errors() {
+    assert_eq!(
+      trace("1 +"),
+      ["1: [] -> [1]", "+: [1] -> [] ! stack underflow"]
+    );
+  }
+}
@@ -3,21 +3,12 @@
 use core::slice::{Iter, IterMut, SliceIndex};
 use std::vec::IntoIter;
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 /// A [`Vec`] with at least one element.
-#[derive(
-  Debug,
-  Clone,
-  PartialEq,
-  Eq,
-  PartialOrd,
-  Ord,
-  Hash,
-  Default,
-  Serialize,
-  Deserialize,
-)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub(crate) struct VecOne<T> {
   vec: Vec<T>,
 }
@@ -122,12 +113,12 @@ impl<T> VecOne<T> {
   }
 
   #[inline]
-  pub fn iter(&self) -> Iter<T> {
+  pub fn iter(&self) -> Iter<'_, T> {
     self.vec.iter()
   }
 
   #[inline]
-  pub fn iter_mut(&mut self) -> IterMut<T> {
+  pub fn iter_mut(&mut self) -> IterMut<'_, T> {
     self.vec.iter_mut()
   }
 }
@@ -0,0 +1,197 @@
+//! Traversal of [`Expr`] trees.
+//!
+//! [`ExprVisitor`] walks a tree by reference and [`ExprMut`] walks it by
+//! mutable reference so that it can be rewritten in place. Both have default
+//! methods which recurse into every child expression, so implementors only
+//! need to override the methods they're interested in and call the matching
+//! `walk_*` function to keep descending.
+
+use crate::expr::{Expr, ExprKind};
+
+/// Visits an [`Expr`] tree by reference.
+pub trait ExprVisitor {
+  /// Visits an expression. Defaults to [`walk_expr`].
+  fn visit_expr(&mut self, expr: &Expr) {
+    walk_expr(self, expr);
+  }
+
+  /// Visits a sequence of expressions, such as the top-level of a program or
+  /// the body of a function. Defaults to [`walk_exprs`].
+  fn visit_exprs(&mut self, exprs: &[Expr]) {
+    walk_exprs(self, exprs);
+  }
+}
+
+/// Visits each expression in `exprs` in order.
+pub fn walk_exprs<V>(visitor: &mut V, exprs: &[Expr])
+where
+  V: ExprVisitor + ?Sized,
+{
+  for expr in exprs {
+    visitor.visit_expr(expr);
+  }
+}
+
+/// Visits the children of `expr`.
+///
+/// Record values are visited in the record's iteration order, which is
+/// unspecified, and map values in insertion order.
+pub fn walk_expr<V>(visitor: &mut V, expr: &Expr)
+where
+  V: ExprVisitor + ?Sized,
+{
+  match &expr.kind {
+    ExprKind::Lazy(x) | ExprKind::Error(x) => visitor.visit_expr(x),
+    ExprKind::List(x) => visitor.visit_exprs(x),
+    ExprKind::Record(x) => {
+      for value in x.values() {
+        visitor.visit_expr(value);
+      }
+    }
+    ExprKind::Map(x) => {
+      for value in x.values() {
+        visitor.visit_expr(value);
+      }
+    }
+    ExprKind::Function { body, .. } | ExprKind::SExpr { body, .. } => {
+      visitor.visit_exprs(body)
+    }
+    ExprKind::Nil
+    | ExprKind::Boolean(_)
+    | ExprKind::Integer(_)
+    | ExprKind::Float(_)
+    | ExprKind::String(_)
+    | ExprKind::Char(_)
+    | ExprKind::Bytes(_)
+    | ExprKind::Set(_)
+    | ExprKind::Seq(_)
+    | ExprKind::Symbol(_)
+    | ExprKind::Underscore
+    | ExprKind::Foreign(_) => {}
+  }
+}
+
+/// Visits an [`Expr`] tree by mutable reference, allowing it to be rewritten.
+pub trait ExprMut {
+  /// Visits an expression. Defaults to [`walk_expr_mut`].
+  fn visit_expr_mut(&mut self, expr: &mut Expr) {
+    walk_expr_mut(self, expr);
+  }
+
+  /// Visits a sequence of expressions, such as the top-level of a program or
+  /// the body of a function. Defaults to [`walk_exprs_mut`].
+  ///
+  /// Override this to insert or remove expressions.
+  fn visit_exprs_mut(&mut self, exprs: &mut Vec<Expr>) {
+    walk_exprs_mut(self, exprs);
+  }
+}
+
+/// Visits each expression in `exprs` in order.
+pub fn walk_exprs_mut<V>(visitor: &mut V, exprs: &mut [Expr])
+where
+  V: ExprMut + ?Sized,
+{
+  for expr in exprs.iter_mut() {
+    visitor.visit_expr_mut(expr);
+  }
+}
+
+/// Visits the children of `expr`.
+///
+/// Record values are visited in the record's iteration order, which is
+/// unspecified, and map values in insertion order.
+pub fn walk_expr_mut<V>(visitor: &mut V, expr: &mut Expr)
+where
+  V: ExprMut + ?Sized,
+{
+  match &mut expr.kind {
+    ExprKind::Lazy(x) | ExprKind::Error(x) => visitor.visit_expr_mut(x),
+    ExprKind::List(x) => visitor.visit_exprs_mut(x),
+    ExprKind::Record(x) => {
+      for value in x.values_mut() {
+        visitor.visit_expr_mut(value);
+      }
+    }
+    ExprKind::Map(x) => {
+      for value in x.values_mut() {
+        visitor.visit_expr_mut(value);
+      }
+    }
+    ExprKind::Function { body, .. } | ExprKind::SExpr { body, .. } => {
+      visitor.visit_exprs_mut(body)
+    }
+    ExprKind::Nil
+    | ExprKind::Boolean(_)
+    | ExprKind::Integer(_)
+    | ExprKind::Float(_)
+    | ExprKind::String(_)
+    | ExprKind::Char(_)
+    | ExprKind::Bytes(_)
+    | ExprKind::Set(_)
+    | ExprKind::Seq(_)
+    | ExprKind::Symbol(_)
+    | ExprKind::Underscore
+    | ExprKind::Foreign(_) => {}
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::prelude::*;
+
+  fn parse_str(code: &str) -> Vec<Expr> {
+    let mut lexer = Lexer::new(Source::new("", code));
+    parse(&mut lexer).unwrap()
+  }
+
+  #[derive(Default)]
+  struct Symbols(Vec<String>);
+
+  impl ExprVisitor for Symbols {
+    fn visit_expr(&mut self, expr: &Expr) {
+      if let ExprKind::Symbol(x) = &expr.kind {
+        self.0.push(x.to_string());
+      }
+
+      walk_expr(self, expr);
+    }
+  }
+
+  #[test]
+  fn visits_nested_exprs() {
+    let exprs = parse_str("a '(b [c (fn d)]) (e f)");
+
+    let mut symbols = Symbols::default();
+    symbols.visit_exprs(&exprs);
+
+    assert_eq!(symbols.0, vec!["a", "c", "d", "f"]);
+  }
+
+  struct Double;
+
+  impl ExprMut for Double {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+      if let ExprKind::Integer(x) = &mut expr.kind {
+        *x *= 2;
+      }
+
+      walk_expr_mut(self, expr);
+    }
+
+    fn visit_exprs_mut(&mut self, exprs: &mut Vec<Expr>) {
+      exprs.retain(|expr| !matches!(expr.kind, ExprKind::Nil));
+      walk_exprs_mut(self, exprs);
+    }
+  }
+
+  #[test]
+  fn rewrites_exprs() {
+    let mut exprs = parse_str("1 nil [2 nil '3] (fn 4 nil)");
+    Double.visit_exprs_mut(&mut exprs);
+
+    assert_eq!(exprs, parse_str("2 [4 '6] (fn 8)"));
+  }
+}
@@ -0,0 +1,128 @@
+use core::fmt;
+use std::sync::Arc;
+
+use crate::{context::Context, expr::Expr, symbol::Symbol};
+
+/// Called when a watched name is changed by `def`, `let` or `set`, with the
+/// [`WatchEvent`] and the [`Context`] after the change.
+#[cfg(not(feature = "sync"))]
+pub type WatchHook = Arc<dyn Fn(&WatchEvent, &Context)>;
+#[cfg(feature = "sync")]
+pub type WatchHook = Arc<dyn Fn(&WatchEvent, &Context) + Send + Sync>;
+
+/// How a watched name was changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatchKind {
+  Def,
+  Let,
+  Set,
+}
+
+impl fmt::Display for WatchKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Def => write!(f, "def"),
+      Self::Let => write!(f, "let"),
+      Self::Set => write!(f, "set"),
+    }
+  }
+}
+
+/// A change to a watched name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchEvent {
+  pub name: Symbol,
+  pub kind: WatchKind,
+  /// The value visible before the change, if the name was defined.
+  pub old: Option<Expr>,
+  /// The new value, which keeps the info of where it was created.
+  pub new: Expr,
+  /// The `def`, `let` or `set` expression that made the change.
+  pub expr: Expr,
+}
+
+impl fmt::Display for WatchEvent {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} '{}'", self.kind, self.name)?;
+
+    if let Some(info) = &self.expr.info {
+      write!(f, " ({info})")?;
+    }
+
+    // The alternate flag is passed on to the values, which colors them.
+    match (&self.old, f.alternate()) {
+      (Some(old), true) => write!(f, ": {old:#} -> {:#}", self.new),
+      (Some(old), false) => write!(f, ": {old} -> {}", self.new),
+      (None, true) => write!(f, ": {:#}", self.new),
+      (None, false) => write!(f, ": {}", self.new),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::Mutex;
+
+  use crate::{lexer::Span, prelude::*};
+
+  type Change = (WatchKind, Option<String>, String, Span, Span);
+
+  fn run_watched(code: &str, name: &str) -> Vec<Change> {
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let hook_changes = changes.clone();
+
+    let engine = Engine::new()
+      .with_watchpoint(Symbol::from_ref(name))
+      .with_watch_hook(Some(Arc::new(move |event, _| {
+        hook_changes.lock().unwrap().push((
+          event.kind,
+          event.old.as_ref().map(|x| x.to_string()),
+          event.new.to_string(),
+          event.expr.info.as_ref().unwrap().span,
+          event.new.info.as_ref().unwrap().span,
+        ));
+      })));
+
+    let mut lexer = Lexer::new(Source::new("", code));
+    let exprs = parse(&mut lexer).unwrap();
+    engine.run(Context::new(), exprs).unwrap();
+
+    let changes = changes.lock().unwrap().clone();
+    changes
+  }
+
+  #[test]
+  fn watches_changes() {
+    let changes =
+      run_watched("1 'a def 2 'b def 3 'a set 4 '(a) '[a] let 5 'b set", "a");
+
+    assert_eq!(
+      changes
+        .into_iter()
+        .map(|(kind, old, new, ..)| (kind, old, new))
+        .collect::<Vec<_>>(),
+      vec![
+        (WatchKind::Def, None, "1".into()),
+        (WatchKind::Set, Some("1".into()), "3".into()),
+        (WatchKind::Let, Some("3".into()), "4".into()),
+      ]
+    );
+  }
+
+  #[test]
+  fn keeps_spans() {
+    let changes = run_watched("1 'a def\n2 'a set", "a");
+
+    assert_eq!(
+      changes
+        .into_iter()
+        .map(|(.., expr, new)| (expr, new))
+        .collect::<Vec<_>>(),
+      vec![
+        (Span { start: 5, end: 8 }, Span { start: 0, end: 1 }),
+        (Span { start: 14, end: 17 }, Span { start: 9, end: 10 }),
+      ]
+    );
+  }
+}
@@ -17,11 +17,29 @@ fn e(kind: ExprKind) -> Expr {
 // TODO: Fix this.
 // #[case("intrinsics/assert_fail.stack" => Err(RunError { reason: RunErrorReason::AssertionFailed, expr: e(ExprKind::Integer(123)) }) ; "assert fail")]
 #[case("intrinsics/assert_okay.stack" => Ok(vec![]) ; "assert okay")]
+#[case("intrinsics/assert_eq.stack" => Ok(vec![e(ExprKind::String("done".into()))]) ; "assert eq")]
+#[case("intrinsics/def_test.stack" => Ok(vec![e(ExprKind::Integer(2)), e(ExprKind::Integer(2))]) ; "def test")]
 #[case("intrinsics/stack.stack" => Ok(vec![e(ExprKind::Integer(1)), e(ExprKind::Integer(3)), e(ExprKind::Integer(3)), e(ExprKind::Integer(5)), e(ExprKind::Integer(4)), e(ExprKind::Integer(7)), e(ExprKind::Integer(8)), e(ExprKind::Integer(6))]) ; "stack")]
 #[case("intrinsics/orelse.stack" => Ok(vec![e(ExprKind::Integer(1)), e(ExprKind::Integer(2)), e(ExprKind::Integer(1)), e(ExprKind::Nil)]) ; "orelse")]
-#[case("intrinsics/push.stack" => Ok(vec![e(ExprKind::List(vec![e(ExprKind::Integer(1)), e(ExprKind::Integer(2)), e(ExprKind::Integer(3))])), e(ExprKind::String("he".into())), e(ExprKind::String("he".into()))]) ; "push")]
+#[case("intrinsics/push.stack" => Ok(vec![e(ExprKind::List(vec![e(ExprKind::Integer(1)), e(ExprKind::Integer(2)), e(ExprKind::Integer(3))].into())), e(ExprKind::String("he".into())), e(ExprKind::String("he".into()))]) ; "push")]
 #[case("intrinsics/record.stack" => Ok(vec![e(ExprKind::Boolean(true)),e(ExprKind::Boolean(true)),e(ExprKind::Boolean(true)),e(ExprKind::Boolean(true)),e(ExprKind::Boolean(true)),e(ExprKind::Boolean(true)),e(ExprKind::Boolean(true)),e(ExprKind::Boolean(true)),e(ExprKind::Boolean(false)),e(ExprKind::Boolean(false)),e(ExprKind::Integer(2)), e(ExprKind::Integer(2))]) ; "record")]
-#[case("intrinsics/pop.stack" => Ok(vec![e(ExprKind::List(vec![e(ExprKind::Integer(1)), e(ExprKind::Integer(2))])), e(ExprKind::Integer(3)), e(ExprKind::String("h".into())), e(ExprKind::String("e".into()))]) ; "pop")]
+#[case("intrinsics/record_nested.stack" => Ok(vec![e(ExprKind::Boolean(true)), e(ExprKind::Boolean(true)), e(ExprKind::Boolean(true)), e(ExprKind::Boolean(true)), e(ExprKind::Boolean(false)), e(ExprKind::Boolean(true)), e(ExprKind::Boolean(false)), e(ExprKind::Boolean(true))]) ; "record nested")]
+#[case("intrinsics/match.stack" => Ok(vec![e(ExprKind::Integer(20)), e(ExprKind::String("other".into())), e(ExprKind::List(vec![e(ExprKind::Integer(2)), e(ExprKind::Integer(3)), e(ExprKind::Integer(1))].into())), e(ExprKind::String("john".into())), e(ExprKind::Nil), e(ExprKind::Nil)]) ; "match expression")]
+#[case("intrinsics/let.stack" => Ok(vec![e(ExprKind::Integer(-1)), e(ExprKind::List(vec![e(ExprKind::Integer(2)), e(ExprKind::Integer(3)), e(ExprKind::Integer(5))].into())), e(ExprKind::String("john".into()))]) ; "let bindings")]
+#[case("intrinsics/closures.stack" => Ok(vec![e(ExprKind::Integer(6)), e(ExprKind::Integer(3)), e(ExprKind::Integer(1)), e(ExprKind::Integer(1)), e(ExprKind::Integer(6)), e(ExprKind::Integer(3))]) ; "closures")]
+#[case("intrinsics/try.stack" => Ok(vec![e(ExprKind::Integer(3)), e(ExprKind::Error(Box::new(e(ExprKind::String("boom".into()))))), e(ExprKind::String("error".into())), e(ExprKind::String("bad".into())), e(ExprKind::String("divide by zero".into())), e(ExprKind::String("divide by zero".into()))]) ; "try and throw")]
+#[case("intrinsics/result.stack" => Ok(vec![e(ExprKind::Integer(1)), e(ExprKind::Nil), e(ExprKind::String("e".into())), e(ExprKind::Integer(5)), e(ExprKind::Integer(7)), e(ExprKind::Integer(8)), e(ExprKind::Integer(4)), e(ExprKind::String("e".into())), e(ExprKind::Nil), e(ExprKind::String("e".into()))]) ; "results")]
+#[case("intrinsics/chars.stack" => Ok(vec![e(ExprKind::Char('é')), e(ExprKind::String("héllo".into())), e(ExprKind::Integer(97)), e(ExprKind::Char('b')), e(ExprKind::String("hi!".into())), e(ExprKind::String("x".into()))]) ; "chars")]
+#[case("intrinsics/bytes.stack" => Ok(vec![e(ExprKind::Integer(255)), e(ExprKind::Bytes(vec![1, 255])), e(ExprKind::Integer(2)), e(ExprKind::String("hé".into())), e(ExprKind::Error(Box::new(e(ExprKind::String("invalid utf-8".into()))))), e(ExprKind::Bytes(vec![b'a', 255])), e(ExprKind::String("hi".into()))]) ; "bytes")]
+#[case("intrinsics/map.stack" => Ok(vec![e(ExprKind::Integer(1)), e(ExprKind::Boolean(true)), e(ExprKind::Boolean(false)), e(ExprKind::List(vec![e(ExprKind::Integer(5)), e(ExprKind::String("a".into()))].into())), e(ExprKind::List(vec![e(ExprKind::List(vec![e(ExprKind::Integer(5)), e(ExprKind::Integer(2))].into())), e(ExprKind::List(vec![e(ExprKind::String("a".into())), e(ExprKind::Integer(1))].into()))].into())), e(ExprKind::Integer(1))]) ; "map")]
+#[case("intrinsics/set.stack" => Ok(vec![e(ExprKind::Integer(2)), e(ExprKind::Boolean(true)), e(ExprKind::List(vec![e(ExprKind::Integer(1)), e(ExprKind::Integer(2)), e(ExprKind::Integer(3))].into())), e(ExprKind::List(vec![e(ExprKind::Integer(2))].into())), e(ExprKind::List(vec![e(ExprKind::Integer(1))].into())), e(ExprKind::List(vec![e(ExprKind::Integer(1)), e(ExprKind::Integer(2))].into()))]) ; "set")]
+#[case("intrinsics/seq.stack" => Ok(vec![e(ExprKind::List(vec![e(ExprKind::Integer(0)), e(ExprKind::Integer(4)), e(ExprKind::Integer(16))].into())), e(ExprKind::List(vec![e(ExprKind::Integer(2)), e(ExprKind::Integer(3)), e(ExprKind::Integer(4))].into())), e(ExprKind::List(vec![e(ExprKind::List(vec![e(ExprKind::Integer(1)), e(ExprKind::Symbol(Symbol::from_ref("a")))].into())), e(ExprKind::List(vec![e(ExprKind::Integer(2)), e(ExprKind::Symbol(Symbol::from_ref("b")))].into()))].into())), e(ExprKind::List(vec![e(ExprKind::Integer(3)), e(ExprKind::Integer(2)), e(ExprKind::Integer(1))].into()))]) ; "seq")]
+#[case("intrinsics/iter.stack" => Ok(vec![e(ExprKind::Integer(10)), e(ExprKind::Integer(10)), e(ExprKind::Integer(20)), e(ExprKind::Boolean(true)), e(ExprKind::Boolean(false)), e(ExprKind::List(vec![e(ExprKind::String("a".into())), e(ExprKind::String("bb".into())), e(ExprKind::String("ccc".into()))].into())), e(ExprKind::List(vec![e(ExprKind::Integer(3)), e(ExprKind::Integer(2)), e(ExprKind::Integer(1))].into()))]) ; "iter")]
+#[case("intrinsics/sort.stack" => Ok(vec![e(ExprKind::List(vec![e(ExprKind::Nil), e(ExprKind::Float(1.5)), e(ExprKind::Integer(2)), e(ExprKind::Integer(3)), e(ExprKind::String("a".into())), e(ExprKind::String("b".into()))].into())), e(ExprKind::List(vec![e(ExprKind::Integer(3)), e(ExprKind::Integer(2)), e(ExprKind::Integer(1))].into())), e(ExprKind::List(vec![e(ExprKind::List(vec![e(ExprKind::Symbol(Symbol::from_ref("a"))), e(ExprKind::Integer(0))].into())), e(ExprKind::List(vec![e(ExprKind::Symbol(Symbol::from_ref("b"))), e(ExprKind::Integer(1))].into())), e(ExprKind::List(vec![e(ExprKind::Symbol(Symbol::from_ref("c"))), e(ExprKind::Integer(1))].into()))].into())), e(ExprKind::Integer(1)), e(ExprKind::String("ccc".into())), e(ExprKind::Nil)]) ; "sort")]
+#[case("intrinsics/range.stack" => Ok(vec![e(ExprKind::List(vec![e(ExprKind::Integer(0)), e(ExprKind::Integer(3)), e(ExprKind::Integer(6)), e(ExprKind::Integer(9))].into())), e(ExprKind::List(vec![e(ExprKind::Integer(10)), e(ExprKind::Integer(5))].into())), e(ExprKind::Nil), e(ExprKind::Integer(10))]) ; "range")]
+#[case("intrinsics/loops.stack" => Ok(vec![e(ExprKind::Integer(0)), e(ExprKind::Integer(1)), e(ExprKind::Integer(2)), e(ExprKind::Integer(128)), e(ExprKind::Integer(8))]) ; "loops")]
+#[case("intrinsics/break.stack" => Ok(vec![e(ExprKind::Integer(3)), e(ExprKind::Integer(1)), e(ExprKind::Integer(3)), e(ExprKind::Integer(5)), e(ExprKind::Integer(10)), e(ExprKind::Integer(10)), e(ExprKind::Integer(6))]) ; "break and continue")]
+#[case("intrinsics/pop.stack" => Ok(vec![e(ExprKind::List(vec![e(ExprKind::Integer(1)), e(ExprKind::Integer(2))].into())), e(ExprKind::Integer(3)), e(ExprKind::String("h".into())), e(ExprKind::String("e".into()))]) ; "pop")]
 fn integration(subpath: &str) -> Result<Vec<Expr>, RunError> {
   let mut path = PathBuf::from_str("tests").unwrap();
   path.push(subpath);
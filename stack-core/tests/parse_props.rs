@@ -0,0 +1,146 @@
+//! Property tests for the lexer, parser and formatter.
+//!
+//! Arbitrary input must never make them panic, and formatting well-formed
+//! source must give source which parses back into the same expressions.
+//! `stack-core/fuzz` has a `cargo fuzz` target for the same properties.
+
+use proptest::prelude::*;
+use stack_core::{
+  format::{format_exprs, FormatOptions},
+  lexer::TokenKind,
+  prelude::*,
+};
+
+fn parse_str(source: &str) -> Result<Vec<Expr>, ParseError> {
+  let mut lexer = Lexer::new(Source::new("", source));
+  parse(&mut lexer)
+}
+
+/// Lexes all of `source`, which must end.
+fn lex_all(source: &str) {
+  let mut lexer = Lexer::new(Source::new("", source));
+
+  // Each token takes at least one byte, besides the end of the file.
+  for _ in 0..=source.len() {
+    if lexer.next().kind == TokenKind::Eof {
+      return;
+    }
+  }
+
+  panic!("the lexer didn't reach the end of {source:?}");
+}
+
+fn symbol() -> impl Strategy<Value = String> {
+  prop_oneof![
+    "[a-z][a-z0-9?!-]{0,6}",
+    "[a-z]{1,4}:[a-z]{1,4}",
+    Just("+".to_owned()),
+    Just("-".to_owned()),
+    Just("<=".to_owned()),
+  ]
+}
+
+fn literal() -> impl Strategy<Value = String> {
+  prop_oneof![
+    any::<i64>().prop_map(|x| x.to_string()),
+    any::<u32>().prop_map(|x| format!("{x:#x}")),
+    any::<u16>().prop_map(|x| format!("{x:#b}")),
+    (any::<i32>(), 0..1000u32).prop_map(|(a, b)| format!("{a}.{b}")),
+    r#""([a-z0-9 ]|\\n|\\t|\\\\|\\")*""#,
+    r#"b"([a-z0-9 ]|\\x[0-9a-f]{2})*""#,
+    r"'([a-z0-9]|\\n|\\')'",
+    Just("nil".to_owned()),
+    Just("true".to_owned()),
+    Just("false".to_owned()),
+    Just("_".to_owned()),
+    symbol(),
+  ]
+}
+
+/// What can go between two expressions.
+fn separator() -> BoxedStrategy<String> {
+  prop_oneof![
+    4 => Just(" ".to_owned()),
+    2 => Just("\n".to_owned()),
+    1 => Just("\n\n".to_owned()),
+    1 => "; [a-z ]{0,10}\n",
+    1 => "\n## [a-z ]{0,10}\n",
+  ]
+  .boxed()
+}
+
+fn join(exprs: Vec<(String, String)>) -> String {
+  exprs
+    .into_iter()
+    .map(|(expr, separator)| expr + &separator)
+    .collect::<String>()
+    // Comments need the newline after them.
+    .trim_end_matches(' ')
+    .to_owned()
+}
+
+/// Generates well-formed source, nesting lists, records, functions and
+/// s-expressions a few levels deep.
+fn well_formed() -> impl Strategy<Value = String> {
+  let expr = literal().prop_recursive(4, 32, 6, |inner| {
+    let items = prop::collection::vec((inner.clone(), separator()), 0..6);
+
+    prop_oneof![
+      inner.clone().prop_map(|x| format!("'{x}")),
+      items.clone().prop_map(|x| format!("[{}]", join(x))),
+      items.clone().prop_map(|x| format!("(fn {})", join(x))),
+      (symbol(), items.clone())
+        .prop_map(|(call, x)| format!("({call} {})", join(x))),
+      prop::collection::vec(("[a-z]{1,4}", inner), 0..4).prop_map(|x| {
+        let fields = x
+          .into_iter()
+          .map(|(key, value)| format!("{key} {value}"))
+          .collect::<Vec<_>>();
+        format!("{{{}}}", fields.join(" "))
+      }),
+    ]
+  });
+
+  prop::collection::vec((expr, separator()), 0..8).prop_map(join)
+}
+
+proptest! {
+  #[test]
+  fn never_panics_on_bytes(bytes in any::<Vec<u8>>()) {
+    let source = String::from_utf8_lossy(&bytes);
+
+    lex_all(&source);
+    let _ = parse_str(&source);
+  }
+
+  #[test]
+  fn never_panics_on_syntax(source in r##"[\[\](){}'"#;\\a-z0-9.x_ :,\n-]*"##) {
+    lex_all(&source);
+
+    // Whatever parses has to survive formatting, not only well-formed source.
+    if let Ok(exprs) = parse_str(&source) {
+      let formatted = format_exprs(&exprs, FormatOptions::default());
+      prop_assert_eq!(parse_str(&formatted).ok(), Some(exprs), "{}", formatted);
+    }
+  }
+
+  #[test]
+  fn parses_well_formed_source(source in well_formed()) {
+    lex_all(&source);
+    prop_assert!(parse_str(&source).is_ok(), "failed to parse {source:?}");
+  }
+
+  #[test]
+  fn formatting_round_trips(source in well_formed()) {
+    let exprs = parse_str(&source).unwrap();
+    let formatted = format_exprs(&exprs, FormatOptions::default());
+
+    let reparsed = parse_str(&formatted);
+    prop_assert_eq!(reparsed.as_ref().ok(), Some(&exprs), "{}", formatted);
+
+    // Formatting is stable once the source is formatted.
+    let reformatted =
+      format_exprs(&reparsed.unwrap(), FormatOptions::default());
+    prop_assert_eq!(reformatted, formatted);
+  }
+}
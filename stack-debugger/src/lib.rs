@@ -5,8 +5,8 @@ use eframe::egui::{
 };
 use itertools::Itertools;
 use stack_core::{
-  expr::display_fn_scope,
-  journal::{Journal, JournalOp, JournalScope},
+  expr::{bytes_literal, char_literal, display_fn_scope},
+  internal::journal::{Journal, JournalOp, JournalScope},
   prelude::*,
 };
 
@@ -15,6 +15,7 @@ pub enum IOHookEvent {
   Marker(usize),
   GoTo(usize),
   Note(usize, String),
+  Watch(usize, String),
 }
 
 pub fn append_to_job(text: RichText, layout_job: &mut LayoutJob) {
@@ -56,6 +57,12 @@ pub fn paint_expr(expr: &Expr, layout_job: &mut LayoutJob) {
     ExprKind::String(x) => {
       append_to_job(RichText::new(format!("\"{x}\"")).color(green), layout_job)
     }
+    ExprKind::Char(x) => {
+      append_to_job(RichText::new(char_literal(*x)).color(green), layout_job)
+    }
+    ExprKind::Bytes(x) => {
+      append_to_job(RichText::new(bytes_literal(x)).color(green), layout_job)
+    }
 
     ExprKind::Symbol(x) => {
       append_to_job(RichText::new(x.to_string()).color(blue), layout_job)
@@ -78,6 +85,20 @@ pub fn paint_expr(expr: &Expr, layout_job: &mut LayoutJob) {
 
       append_to_job(RichText::new("]"), layout_job);
     }
+    ExprKind::Set(x) => {
+      append_to_job(RichText::new("#["), layout_job);
+
+      for (sep, x) in core::iter::once("")
+        .chain(core::iter::repeat(" "))
+        .zip(x.iter())
+      {
+        let x: Expr = ExprKind::from(x.clone()).into();
+        append_to_job(RichText::new(sep), layout_job);
+        paint_expr(&x, layout_job);
+      }
+
+      append_to_job(RichText::new("]"), layout_job);
+    }
     ExprKind::Record(x) => {
       append_to_job(RichText::new("{"), layout_job);
 
@@ -94,6 +115,22 @@ pub fn paint_expr(expr: &Expr, layout_job: &mut LayoutJob) {
 
       append_to_job(RichText::new("}"), layout_job);
     }
+    ExprKind::Map(x) => {
+      append_to_job(RichText::new("#{"), layout_job);
+
+      for (sep, (key, value)) in core::iter::once("")
+        .chain(core::iter::repeat(", "))
+        .zip(x.iter())
+      {
+        let key: Expr = ExprKind::from(key.clone()).into();
+        append_to_job(RichText::new(sep), layout_job);
+        paint_expr(&key, layout_job);
+        append_to_job(RichText::new(": "), layout_job);
+        paint_expr(value, layout_job);
+      }
+
+      append_to_job(RichText::new("}"), layout_job);
+    }
 
     ExprKind::Function { scope, body } => {
       // append_to_job(RichText::new(x.to_string()).color(yellow), layout_job)
@@ -137,6 +174,7 @@ pub fn paint_expr(expr: &Expr, layout_job: &mut LayoutJob) {
       append_to_job(RichText::new(")"), layout_job);
     }
     ExprKind::Underscore => append_string("_".to_string(), layout_job),
+    kind => append_string(kind.to_string(), layout_job),
   }
 }
 
@@ -256,6 +294,8 @@ pub fn paint_journal(journal: &Journal, layout_job: &mut LayoutJob) {
 pub fn string_with_quotes(expr: &Expr) -> String {
   match &expr.kind {
     ExprKind::String(x) => format!("\"{x}\""),
+    ExprKind::Char(x) => char_literal(*x),
+    ExprKind::Bytes(x) => bytes_literal(x),
 
     ExprKind::Lazy(x) => string_with_quotes(x),
 
@@ -301,6 +341,36 @@ pub fn string_with_quotes(expr: &Expr) -> String {
 
       string
     }
+    ExprKind::Set(x) => {
+      let mut string = String::from("#[");
+      core::iter::once("")
+        .chain(core::iter::repeat(" "))
+        .zip(x.iter())
+        .for_each(|(sep, x)| {
+          let x: Expr = ExprKind::from(x.clone()).into();
+          string.push_str(&format!("{sep}{}", string_with_quotes(&x)))
+        });
+      string.push(']');
+
+      string
+    }
+    ExprKind::Map(x) => {
+      let mut string = String::from("#{");
+      core::iter::once("")
+        .chain(core::iter::repeat(" "))
+        .zip(x.iter())
+        .for_each(|(sep, (key, value))| {
+          let key: Expr = ExprKind::from(key.clone()).into();
+          string.push_str(&format!(
+            "{sep}{}: {}",
+            string_with_quotes(&key),
+            string_with_quotes(value)
+          ))
+        });
+      string.push('}');
+
+      string
+    }
 
     kind => kind.to_string(),
   }
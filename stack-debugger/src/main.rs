@@ -12,8 +12,10 @@ use eframe::egui::{self, text::LayoutJob, Color32, RichText};
 use notify::{
   Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
 };
-use stack_core::{journal::JournalScope, prelude::*};
+use stack_core::{internal::journal::JournalScope, prelude::*};
 use stack_debugger::*;
+#[cfg(feature = "stack-std")]
+use stack_std::sandbox::SandboxPolicy;
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, clap::Parser)]
 #[command(author, version, about, long_about = None)]
@@ -22,11 +24,46 @@ struct Cli {
   /// The input file path.
   input: PathBuf,
 
-  /// Whether to run a sandbox variant of the enabled standard modules.
+  /// The arguments to pass to the script, after `--`.
+  #[arg(last = true)]
+  args: Vec<String>,
+
+  /// Add a jump location whenever `def`, `let` or `set` changes this name.
+  #[arg(long = "watchpoint", value_name = "NAME")]
+  watchpoints: Vec<String>,
+
+  /// Whether to run a sandbox variant of the enabled standard modules, which
+  /// can only do what the `--allow-*` flags grant.
   #[arg(short, long)]
   #[cfg(feature = "stack-std")]
   sandbox: bool,
 
+  /// Allow reading these paths in a sandbox, or any path if none are given.
+  #[arg(long, value_name = "PATH", num_args = 0.., require_equals = true, value_delimiter = ',')]
+  #[cfg(feature = "stack-std")]
+  allow_read: Option<Vec<PathBuf>>,
+
+  /// Allow writing these paths in a sandbox, or any path if none are given.
+  #[arg(long, value_name = "PATH", num_args = 0.., require_equals = true, value_delimiter = ',')]
+  #[cfg(feature = "stack-std")]
+  allow_write: Option<Vec<PathBuf>>,
+
+  /// Allow connecting to these hosts in a sandbox, or any host if none are
+  /// given.
+  #[arg(long, value_name = "HOST", num_args = 0.., require_equals = true, value_delimiter = ',')]
+  #[cfg(feature = "stack-std")]
+  allow_net: Option<Vec<String>>,
+
+  /// Allow running other programs in a sandbox.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  allow_run: bool,
+
+  /// Allow reading and changing environment variables in a sandbox.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  allow_env: bool,
+
   /// Enable all standard modules.
   #[arg(long)]
   #[cfg(feature = "stack-std")]
@@ -43,6 +80,74 @@ struct Cli {
   #[arg(long)]
   #[cfg(feature = "stack-std")]
   enable_scope: bool,
+  /// Enable the string builder standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_sb: bool,
+  /// Enable the rope standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_rope: bool,
+  /// Enable the template standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_template: bool,
+  /// Enable the Markdown standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_md: bool,
+  /// Enable the JSON standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_json: bool,
+  /// Enable the TOML standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_toml: bool,
+  /// Enable the YAML standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_yaml: bool,
+  /// Enable the CSV standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_csv: bool,
+  /// Enable the process standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_proc: bool,
+  /// Enable the environment standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_env: bool,
+  /// Enable the input/output standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_io: bool,
+  /// Enable the time standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_time: bool,
+  /// Enable the random number standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_rand: bool,
+  /// Enable the math standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_math: bool,
+  /// Enable the hashing standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_hash: bool,
+  /// Enable the encoding standard module.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_encode: bool,
+  /// Enable the task and channel standard modules.
+  #[arg(long)]
+  #[cfg(feature = "stack-std")]
+  enable_task: bool,
 }
 
 pub fn main() {
@@ -52,25 +157,122 @@ pub fn main() {
 
   let (print_tx, print_rx) = mpsc::channel();
   let debug_tx = print_tx.clone();
-
-  let mut engine = Engine::new().with_debug_hook(Some(Arc::new(move |s| {
-    debug_tx.send(IOHookEvent::Print(s)).unwrap()
-  })));
+  let watch_tx = print_tx.clone();
+
+  let mut engine = Engine::new()
+    .with_debug_hook(Some(Arc::new(move |s| {
+      debug_tx.send(IOHookEvent::Print(s)).unwrap()
+    })))
+    .with_watch_hook(Some(Arc::new(move |event, context| {
+      watch_tx
+        .send(IOHookEvent::Watch(
+          context
+            .journal()
+            .as_ref()
+            .map(|j| j.entries().len())
+            .unwrap_or_default(),
+          event.to_string(),
+        ))
+        .unwrap()
+    })));
+  for name in cli.watchpoints.iter() {
+    engine.add_watchpoint(Symbol::from_ref(name.as_str()));
+  }
   engine.add_module(module::module(print_tx));
 
   #[cfg(feature = "stack-std")]
   {
+    let policy = sandbox_policy(&cli);
+
     if cli.enable_all || cli.enable_str {
       engine.add_module(stack_std::str::module());
     }
 
     if cli.enable_all || cli.enable_fs {
-      engine.add_module(stack_std::fs::module(cli.sandbox));
+      engine.add_module(stack_std::fs::module(&policy));
     }
 
     if cli.enable_all || cli.enable_scope {
       engine.add_module(stack_std::scope::module());
     }
+
+    if cli.enable_all || cli.enable_sb {
+      engine.add_module(stack_std::sb::module());
+    }
+
+    if cli.enable_all || cli.enable_rope {
+      engine.add_module(stack_std::rope::module());
+    }
+
+    if cli.enable_all || cli.enable_template {
+      engine.add_module(stack_std::template::module());
+    }
+
+    if cli.enable_all || cli.enable_md {
+      engine.add_module(stack_std::md::module());
+    }
+
+    if cli.enable_all || cli.enable_json {
+      engine.add_module(stack_std::json::module());
+    }
+
+    if cli.enable_all || cli.enable_toml {
+      engine.add_module(stack_std::toml::module());
+    }
+
+    if cli.enable_all || cli.enable_yaml {
+      engine.add_module(stack_std::yaml::module());
+    }
+
+    if cli.enable_all || cli.enable_csv {
+      engine.add_module(stack_std::csv::module());
+    }
+
+    if cli.enable_all || cli.enable_proc {
+      engine.add_module(stack_std::proc::module(&policy));
+    }
+
+    if cli.enable_all || cli.enable_env {
+      engine.add_module(stack_std::env::module(&policy, cli.args.clone()));
+    }
+
+    if cli.enable_all || cli.enable_io {
+      engine.add_module(stack_std::io::module());
+    }
+
+    if cli.enable_all || cli.enable_time {
+      engine.add_module(stack_std::time::module());
+    }
+
+    if cli.enable_all || cli.enable_rand {
+      engine.add_module(stack_std::rand::module());
+    }
+
+    if cli.enable_all || cli.enable_math {
+      engine.add_module(stack_std::math::module());
+    }
+
+    if cli.enable_all || cli.enable_hash {
+      engine.add_module(stack_std::hash::module());
+    }
+
+    if cli.enable_all || cli.enable_encode {
+      engine.add_module(stack_std::encode::module());
+    }
+
+    if cli.enable_all || cli.enable_task {
+      engine.add_module(stack_std::task::module());
+      engine.add_module(stack_std::chan::module());
+    }
+
+    // Imports are run by the engine rather than a module, so they're kept
+    // within the sandbox separately.
+    engine.set_import_guard(policy.import_guard());
+
+    // The modules which aren't enabled can still be imported by name.
+    for (name, loader) in stack_std::loaders(&policy, cli.args.clone()) {
+      engine.add_module_loader(name, loader);
+    }
   }
 
   let (tx, rx) = mpsc::channel();
@@ -123,6 +325,37 @@ pub fn main() {
   .unwrap();
 }
 
+/// Returns the capabilities granted by the CLI flags. Without a sandbox,
+/// everything is allowed.
+#[cfg(feature = "stack-std")]
+fn sandbox_policy(cli: &Cli) -> SandboxPolicy {
+  if !cli.sandbox {
+    return SandboxPolicy::unrestricted();
+  }
+
+  let mut policy = SandboxPolicy::new()
+    .with_run(cli.allow_run)
+    .with_env(cli.allow_env);
+
+  policy = match cli.allow_read {
+    Some(ref paths) if paths.is_empty() => policy.with_read_all(),
+    Some(ref paths) => paths.iter().fold(policy, |x, path| x.with_read(path)),
+    None => policy,
+  };
+
+  policy = match cli.allow_write {
+    Some(ref paths) if paths.is_empty() => policy.with_write_all(),
+    Some(ref paths) => paths.iter().fold(policy, |x, path| x.with_write(path)),
+    None => policy,
+  };
+
+  match cli.allow_net {
+    Some(ref hosts) if hosts.is_empty() => policy.with_net_all(),
+    Some(ref hosts) => hosts.iter().fold(policy, |x, host| x.with_net(host)),
+    None => policy,
+  }
+}
+
 fn ok_or_exit<T, E>(result: Result<T, E>) -> T
 where
   E: fmt::Display,
@@ -181,7 +414,7 @@ impl DebuggerApp {
       }
       Err(err) => {
         self.error = Some(err.to_string().clone());
-        self.context = err.context;
+        self.context = *err.context;
       }
     }
 
@@ -297,8 +530,10 @@ impl eframe::App for DebuggerApp {
         append_to_job(RichText::new("debug").code(), &mut layout_job);
         append_to_job(RichText::new(", "), &mut layout_job);
         append_to_job(RichText::new("dbg:mark").code(), &mut layout_job);
-        append_to_job(RichText::new(", or "), &mut layout_job);
+        append_to_job(RichText::new(", "), &mut layout_job);
         append_to_job(RichText::new("dbg:note").code(), &mut layout_job);
+        append_to_job(RichText::new(", or "), &mut layout_job);
+        append_to_job(RichText::new("--watchpoint").code(), &mut layout_job);
         append_to_job(
           RichText::new(" to create jump locations"),
           &mut layout_job,
@@ -331,6 +566,15 @@ impl eframe::App for DebuggerApp {
                     self.index = *op;
                   }
                 }
+                IOHookEvent::Watch(op, text) => {
+                  if ui
+                    .link(format!("watch at {op}"))
+                    .on_hover_text(text)
+                    .clicked()
+                  {
+                    self.index = *op;
+                  }
+                }
                 IOHookEvent::GoTo(op) => {
                   if ui.link(format!("goto at {op}")).clicked() {
                     self.index = *op;
@@ -0,0 +1,350 @@
+//! Derive macros for the `FromExpr` and `IntoExpr` traits of `stack-core`.
+//!
+//! These are re-exported by `stack_core::convert` with the `derive` feature,
+//! which is how they should be used.
+//!
+//! - A struct with named fields is a record, keyed by the field names in
+//!   kebab-case. A missing key is read as `nil`, so [`Option`] fields can be
+//!   left out.
+//! - A tuple struct is a list of its fields, except one with a single field,
+//!   which is that field.
+//! - A unit struct is `nil`.
+//! - An enum is a record whose `type` is the name of the variant in
+//!   kebab-case, along with the fields of the variant. Only unit variants and
+//!   those with named fields are supported.
+//!
+//! A field or variant can be given another name with
+//! `#[stack(rename = "name")]`.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+  parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Error, Fields,
+  Generics, Ident, LitStr,
+};
+
+/// The key holding the name of an enum variant.
+const TAG: &str = "type";
+
+#[proc_macro_derive(FromExpr, attributes(stack))]
+pub fn derive_from_expr(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+
+  from_expr(input)
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+#[proc_macro_derive(IntoExpr, attributes(stack))]
+pub fn derive_into_expr(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+
+  into_expr(input)
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+fn from_expr(input: DeriveInput) -> syn::Result<TokenStream2> {
+  let name = &input.ident;
+  let generics = with_bounds(
+    input.generics.clone(),
+    quote!(::stack_core::convert::FromExpr),
+  );
+  let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+  let body = match input.data {
+    Data::Struct(ref data) => from_fields(quote!(Self), &data.fields)?,
+    Data::Enum(ref data) => {
+      let arms = data
+        .variants
+        .iter()
+        .map(|variant| {
+          let ident = &variant.ident;
+          let tag = name_of(&variant.attrs, ident, to_kebab_case)?;
+
+          if let Fields::Unnamed(_) = variant.fields {
+            return Err(Error::new_spanned(
+              variant,
+              "tuple variants aren't supported",
+            ));
+          }
+
+          let body = from_fields(quote!(Self::#ident), &variant.fields)?;
+
+          Ok(quote!(#tag => #body))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+      quote! {
+        let ::stack_core::expr::ExprKind::Record(ref record) = expr.kind else {
+          return ::core::option::Option::None;
+        };
+
+        match record.get(&::stack_core::symbol::Symbol::from_ref(#TAG))?.kind {
+          ::stack_core::expr::ExprKind::String(ref tag) => match tag.as_str() {
+            #(#arms,)*
+            _ => ::core::option::Option::None,
+          },
+          _ => ::core::option::Option::None,
+        }
+      }
+    }
+    Data::Union(_) => {
+      return Err(Error::new(Span::call_site(), "unions aren't supported"))
+    }
+  };
+
+  Ok(quote! {
+    impl #impl_generics ::stack_core::convert::FromExpr for #name #ty_generics
+    #where_clause
+    {
+      fn from_expr(
+        expr: ::stack_core::expr::Expr,
+      ) -> ::core::option::Option<Self> {
+        #body
+      }
+    }
+  })
+}
+
+/// Returns the expression which reads `fields` from `expr` into `path`.
+fn from_fields(
+  path: TokenStream2,
+  fields: &Fields,
+) -> syn::Result<TokenStream2> {
+  Ok(match fields {
+    Fields::Named(fields) => {
+      let fields = fields
+        .named
+        .iter()
+        .map(|field| {
+          let ident = field.ident.as_ref().unwrap();
+          let key = name_of(&field.attrs, ident, |x| x.replace('_', "-"))?;
+
+          Ok(quote! {
+            #ident: ::stack_core::convert::FromExpr::from_expr(
+              record
+                .get(&::stack_core::symbol::Symbol::from_ref(#key))
+                .cloned()
+                .unwrap_or_else(|| ::stack_core::expr::ExprKind::Nil.into()),
+            )?
+          })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+      quote! {{
+        let ::stack_core::expr::ExprKind::Record(ref record) = expr.kind else {
+          return ::core::option::Option::None;
+        };
+
+        ::core::option::Option::Some(#path { #(#fields,)* })
+      }}
+    }
+    Fields::Unnamed(fields) if fields.unnamed.len() == 1 => quote! {
+      ::core::option::Option::Some(#path(
+        ::stack_core::convert::FromExpr::from_expr(expr)?,
+      ))
+    },
+    Fields::Unnamed(fields) => {
+      let len = fields.unnamed.len();
+      let fields = fields.unnamed.iter().map(|_| {
+        quote!(::stack_core::convert::FromExpr::from_expr(items.next()?)?)
+      });
+
+      quote! {{
+        let ::stack_core::expr::ExprKind::List(items) = expr.kind else {
+          return ::core::option::Option::None;
+        };
+
+        if items.len() != #len {
+          return ::core::option::Option::None;
+        }
+
+        let mut items = items.into_iter();
+        ::core::option::Option::Some(#path(#(#fields,)*))
+      }}
+    }
+    Fields::Unit => quote! {
+      ::core::option::Option::Some(#path)
+    },
+  })
+}
+
+fn into_expr(input: DeriveInput) -> syn::Result<TokenStream2> {
+  let name = &input.ident;
+  let generics = with_bounds(
+    input.generics.clone(),
+    quote!(::stack_core::convert::IntoExpr),
+  );
+  let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+  let body = match input.data {
+    Data::Struct(ref data) => match data.fields {
+      Fields::Named(ref fields) => {
+        let idents = fields.named.iter().map(|x| x.ident.clone().unwrap());
+        let inserts = into_record(&fields.named)?;
+
+        quote! {
+          let Self { #(#idents,)* } = self;
+          let mut record = ::std::collections::HashMap::new();
+          #(#inserts)*
+
+          ::stack_core::expr::ExprKind::Record(record.into()).into()
+        }
+      }
+      Fields::Unnamed(ref fields) if fields.unnamed.len() == 1 => quote! {
+        ::stack_core::convert::IntoExpr::into_expr(self.0)
+      },
+      Fields::Unnamed(ref fields) => {
+        let items = (0..fields.unnamed.len())
+          .map(syn::Index::from)
+          .map(|i| quote!(::stack_core::convert::IntoExpr::into_expr(self.#i)));
+
+        quote! {
+          ::stack_core::expr::ExprKind::List(::std::vec![#(#items,)*].into()).into()
+        }
+      }
+      Fields::Unit => quote! {
+        ::stack_core::expr::ExprKind::Nil.into()
+      },
+    },
+    Data::Enum(ref data) => {
+      let arms = data
+        .variants
+        .iter()
+        .map(|variant| {
+          let ident = &variant.ident;
+          let tag = name_of(&variant.attrs, ident, to_kebab_case)?;
+
+          let (pattern, inserts) = match variant.fields {
+            Fields::Named(ref fields) => {
+              let idents =
+                fields.named.iter().map(|x| x.ident.clone().unwrap());
+
+              (
+                quote!(Self::#ident { #(#idents,)* }),
+                into_record(&fields.named)?,
+              )
+            }
+            Fields::Unit => (quote!(Self::#ident), Vec::new()),
+            Fields::Unnamed(_) => {
+              return Err(Error::new_spanned(
+                variant,
+                "tuple variants aren't supported",
+              ))
+            }
+          };
+
+          Ok(quote! {
+            #pattern => {
+              let mut record = ::std::collections::HashMap::new();
+              record.insert(
+                ::stack_core::symbol::Symbol::from_ref(#TAG),
+                ::stack_core::convert::IntoExpr::into_expr(#tag),
+              );
+              #(#inserts)*
+
+              ::stack_core::expr::ExprKind::Record(record.into()).into()
+            }
+          })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+      quote! {
+        match self {
+          #(#arms)*
+        }
+      }
+    }
+    Data::Union(_) => {
+      return Err(Error::new(Span::call_site(), "unions aren't supported"))
+    }
+  };
+
+  Ok(quote! {
+    impl #impl_generics ::stack_core::convert::IntoExpr for #name #ty_generics
+    #where_clause
+    {
+      fn into_expr(self) -> ::stack_core::expr::Expr {
+        #body
+      }
+    }
+  })
+}
+
+/// Returns the statements which insert the bound `fields` into `record`.
+fn into_record<'a>(
+  fields: impl IntoIterator<Item = &'a syn::Field>,
+) -> syn::Result<Vec<TokenStream2>> {
+  fields
+    .into_iter()
+    .map(|field| {
+      let ident = field.ident.as_ref().unwrap();
+      let key = name_of(&field.attrs, ident, |x| x.replace('_', "-"))?;
+
+      Ok(quote! {
+        record.insert(
+          ::stack_core::symbol::Symbol::from_ref(#key),
+          ::stack_core::convert::IntoExpr::into_expr(#ident),
+        );
+      })
+    })
+    .collect()
+}
+
+/// Adds `bound` to each of the type parameters.
+fn with_bounds(mut generics: Generics, bound: TokenStream2) -> Generics {
+  let params = generics
+    .type_params()
+    .map(|x| x.ident.clone())
+    .collect::<Vec<_>>();
+  let where_clause = generics.make_where_clause();
+
+  for param in params {
+    where_clause.predicates.push(parse_quote!(#param: #bound));
+  }
+
+  generics
+}
+
+/// Returns the name given with `#[stack(rename = "...")]`, or else the
+/// identifier converted with `convert`.
+fn name_of(
+  attrs: &[Attribute],
+  ident: &Ident,
+  convert: fn(&str) -> String,
+) -> syn::Result<LitStr> {
+  let mut name = None;
+
+  for attr in attrs.iter().filter(|x| x.path().is_ident("stack")) {
+    attr.parse_nested_meta(|meta| {
+      if meta.path.is_ident("rename") {
+        name = Some(meta.value()?.parse::<LitStr>()?);
+        Ok(())
+      } else {
+        Err(meta.error("unknown attribute"))
+      }
+    })?;
+  }
+
+  let ident = ident.to_string();
+  let ident = ident.strip_prefix("r#").unwrap_or(&ident);
+
+  Ok(name.unwrap_or_else(|| LitStr::new(&convert(ident), Span::call_site())))
+}
+
+/// Converts a `PascalCase` name into `kebab-case`.
+fn to_kebab_case(name: &str) -> String {
+  let mut out = String::with_capacity(name.len());
+
+  for (i, c) in name.chars().enumerate() {
+    if c.is_uppercase() && i != 0 {
+      out.push('-');
+    }
+
+    out.extend(c.to_lowercase());
+  }
+
+  out
+}
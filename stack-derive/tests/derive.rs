@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use stack_core::{
+  convert::{FromExpr, IntoExpr},
+  prelude::*,
+};
+
+#[derive(Debug, Clone, PartialEq, FromExpr, IntoExpr)]
+struct Point {
+  x: i64,
+  y: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, FromExpr, IntoExpr)]
+struct User {
+  user_name: String,
+  #[stack(rename = "years")]
+  age: i64,
+  email: Option<String>,
+  points: Vec<Point>,
+}
+
+#[derive(Debug, Clone, PartialEq, FromExpr, IntoExpr)]
+struct Pair(i64, String);
+
+#[derive(Debug, Clone, PartialEq, FromExpr, IntoExpr)]
+struct Meters(f64);
+
+#[derive(Debug, Clone, PartialEq, FromExpr, IntoExpr)]
+enum Shape {
+  Circle { radius: f64 },
+  Rect { width: f64, height: f64 },
+  Empty,
+}
+
+#[derive(Debug, Clone, PartialEq, FromExpr, IntoExpr)]
+struct Wrapper<T> {
+  value: T,
+}
+
+fn record(items: &[(&str, ExprKind)]) -> Expr {
+  ExprKind::Record(
+    HashMap::from_iter(
+      items
+        .iter()
+        .map(|(key, value)| (Symbol::from_ref(*key), value.clone().into())),
+    )
+    .into(),
+  )
+  .into()
+}
+
+fn string(x: &str) -> ExprKind {
+  ExprKind::String(x.into())
+}
+
+#[test]
+fn converts_structs_into_records() {
+  let user = User {
+    user_name: "ada".into(),
+    age: 36,
+    email: None,
+    points: vec![Point { x: 1, y: 2 }],
+  };
+  let expr = user.clone().into_expr();
+
+  assert_eq!(
+    expr,
+    record(&[
+      ("user-name", string("ada")),
+      ("years", ExprKind::Integer(36)),
+      ("email", ExprKind::Nil),
+      (
+        "points",
+        ExprKind::List(
+          vec![record(&[
+            ("x", ExprKind::Integer(1)),
+            ("y", ExprKind::Integer(2)),
+          ])]
+          .into()
+        ),
+      ),
+    ])
+  );
+  assert_eq!(User::from_expr(expr), Some(user));
+}
+
+#[test]
+fn reads_missing_keys_as_nil() {
+  let expr = record(&[
+    ("user-name", string("ada")),
+    ("years", ExprKind::Integer(36)),
+    ("points", ExprKind::List(Vec::new().into())),
+  ]);
+
+  assert_eq!(User::from_expr(expr).unwrap().email, None);
+  assert_eq!(
+    Point::from_expr(record(&[("x", ExprKind::Integer(1))])),
+    None
+  );
+}
+
+#[test]
+fn converts_tuple_structs() {
+  let expr = Pair(1, "a".into()).into_expr();
+
+  assert_eq!(
+    expr,
+    ExprKind::List(
+      vec![ExprKind::Integer(1).into(), string("a").into()].into()
+    )
+    .into()
+  );
+  assert_eq!(Pair::from_expr(expr), Some(Pair(1, "a".into())));
+
+  assert_eq!(Meters(2.0).into_expr(), ExprKind::Float(2.0).into());
+  assert_eq!(
+    Meters::from_expr(ExprKind::Integer(2).into()),
+    Some(Meters(2.0))
+  );
+}
+
+#[test]
+fn converts_enums_into_tagged_records() {
+  let expr = Shape::Rect {
+    width: 1.0,
+    height: 2.0,
+  }
+  .into_expr();
+
+  assert_eq!(
+    expr,
+    record(&[
+      ("type", string("rect")),
+      ("width", ExprKind::Float(1.0)),
+      ("height", ExprKind::Float(2.0)),
+    ])
+  );
+  assert_eq!(
+    Shape::from_expr(expr),
+    Some(Shape::Rect {
+      width: 1.0,
+      height: 2.0
+    })
+  );
+
+  assert_eq!(
+    Shape::from_expr(record(&[("type", string("empty"))])),
+    Some(Shape::Empty)
+  );
+  assert_eq!(Shape::from_expr(record(&[("type", string("line"))])), None);
+}
+
+#[test]
+fn converts_generic_structs() {
+  let expr = Wrapper { value: 1i64 }.into_expr();
+
+  assert_eq!(expr, record(&[("value", ExprKind::Integer(1))]));
+  assert_eq!(Wrapper::<i64>::from_expr(expr), Some(Wrapper { value: 1 }));
+}
+
+#[test]
+fn works_with_host_functions() {
+  let module = Module::new(Symbol::from_ref("geo")).with_fn(
+    Symbol::from_ref("area"),
+    |shape: Shape| match shape {
+      Shape::Circle { radius } => core::f64::consts::PI * radius * radius,
+      Shape::Rect { width, height } => width * height,
+      Shape::Empty => 0.0,
+    },
+  );
+
+  let source = Source::new("", "{type \"rect\" width 2.0 height 3.0} geo:area");
+  let mut lexer = Lexer::new(source);
+  let exprs = parse(&mut lexer).unwrap();
+
+  let context = Engine::new()
+    .with_module(module)
+    .run(Context::new(), exprs)
+    .unwrap();
+
+  assert_eq!(context.stack(), &[ExprKind::Float(6.0).into()]);
+}
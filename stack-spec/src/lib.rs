@@ -0,0 +1,411 @@
+//! A conformance suite for the Stack language.
+//!
+//! The suite is a set of small programs, grouped by feature, along with the
+//! stack each should leave behind or the error it should fail with. Anything
+//! that runs Stack code can implement [`Backend`] and be checked against it
+//! with [`run`], so alternative implementations can show that they behave like
+//! the [`Engine`].
+//!
+//! The cases live in `spec/<feature>.stack`. Each starts with a `;;; case:`
+//! line naming it and ends with the expected outcome:
+//!
+//! ```text
+//! ;;; case: add
+//! 1 2 +
+//! ;;; stack: 3
+//! ```
+//!
+//! The expected stack is written as data, bottom first, so `;;; stack: 1 "a"`
+//! expects the integer `1` beneath the string `"a"`, and `(error x)` is an
+//! error value. A failing case instead ends with `;;; error:` and the reason
+//! it fails with, as [`RunErrorReason`] displays it, such as
+//! `;;; error: stack underflow`.
+//!
+//! [`compare`] runs the suite on two backends and reports where they disagree,
+//! whatever the expected outcome is, so that a new way of running code can't
+//! silently diverge from an existing one while it's incomplete.
+//! [`SchedulerBackend`] is checked against [`EngineBackend`] this way.
+
+use core::fmt;
+
+use stack_core::prelude::*;
+
+/// The features covered by the suite, with the contents of their files.
+pub const SUITES: &[(&str, &str)] = &[
+  ("arithmetic", include_str!("../spec/arithmetic.stack")),
+  ("comparison", include_str!("../spec/comparison.stack")),
+  ("logic", include_str!("../spec/logic.stack")),
+  ("stack", include_str!("../spec/stack.stack")),
+  ("literals", include_str!("../spec/literals.stack")),
+  ("lists", include_str!("../spec/lists.stack")),
+  ("strings", include_str!("../spec/strings.stack")),
+  ("chars", include_str!("../spec/chars.stack")),
+  ("bytes", include_str!("../spec/bytes.stack")),
+  ("records", include_str!("../spec/records.stack")),
+  ("maps", include_str!("../spec/maps.stack")),
+  ("sets", include_str!("../spec/sets.stack")),
+  ("seqs", include_str!("../spec/seqs.stack")),
+  ("types", include_str!("../spec/types.stack")),
+  ("control", include_str!("../spec/control.stack")),
+  ("match", include_str!("../spec/match.stack")),
+  ("variables", include_str!("../spec/variables.stack")),
+  ("functions", include_str!("../spec/functions.stack")),
+  ("let", include_str!("../spec/let.stack")),
+  ("errors", include_str!("../spec/errors.stack")),
+  ("results", include_str!("../spec/results.stack")),
+];
+
+/// Runs Stack code for the suite.
+pub trait Backend {
+  /// Returns the name shown in failures, such as `"engine"`.
+  fn name(&self) -> &str;
+
+  /// Runs `code` from an empty stack and scope, returning the stack it leaves
+  /// behind, or the reason it fails as [`RunErrorReason`] displays it.
+  fn run(&self, code: &str) -> Result<Vec<Expr>, String>;
+}
+
+/// Runs the suite on an [`Engine`] with no modules.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EngineBackend;
+
+impl Backend for EngineBackend {
+  #[inline]
+  fn name(&self) -> &str {
+    "engine"
+  }
+
+  fn run(&self, code: &str) -> Result<Vec<Expr>, String> {
+    let mut lexer = Lexer::new(Source::new("spec", code));
+    let exprs = parse(&mut lexer).map_err(|e| e.kind.to_string())?;
+
+    let mut context = Engine::new()
+      .run(Context::new(), exprs)
+      .map_err(|e| e.reason.to_string())?;
+
+    Ok(core::mem::take(context.stack_mut()))
+  }
+}
+
+/// Runs the suite as a task on a [`Scheduler`], which yields after every
+/// operation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SchedulerBackend;
+
+impl Backend for SchedulerBackend {
+  #[inline]
+  fn name(&self) -> &str {
+    "scheduler"
+  }
+
+  fn run(&self, code: &str) -> Result<Vec<Expr>, String> {
+    let mut lexer = Lexer::new(Source::new("spec", code));
+    let exprs = parse(&mut lexer).map_err(|e| e.kind.to_string())?;
+
+    let mut scheduler = Scheduler::new(Engine::new()).with_slice(1);
+    let id = scheduler.spawn(Context::new(), exprs);
+    scheduler.run();
+
+    let mut context = scheduler
+      .remove(id)
+      .expect("the task exists")
+      .map_err(|e| e.reason.to_string())?;
+
+    Ok(core::mem::take(context.stack_mut()))
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+  /// The stack left behind, bottom first.
+  Stack(Vec<Expr>),
+  /// The reason the code fails.
+  Error(String),
+}
+
+impl fmt::Display for Expected {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Stack(stack) => write_stack(f, stack),
+      Self::Error(reason) => write!(f, "error: {reason}"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+  pub feature: &'static str,
+  pub name: String,
+  pub code: String,
+  pub expected: Expected,
+}
+
+/// A case that a [`Backend`] didn't pass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+  pub case: Case,
+  pub actual: Result<Vec<Expr>, String>,
+}
+
+impl fmt::Display for Failure {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}/{}: expected {}, found ",
+      self.case.feature, self.case.name, self.case.expected
+    )?;
+
+    match self.actual {
+      Ok(ref stack) => write_stack(f, stack),
+      Err(ref reason) => write!(f, "error: {reason}"),
+    }
+  }
+}
+
+/// A case on which two [`Backend`]s didn't have the same outcome.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+  pub case: Case,
+  /// The name and outcome of each backend.
+  pub outcomes: [(String, Result<Vec<Expr>, String>); 2],
+}
+
+impl fmt::Display for Divergence {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}/{}:", self.case.feature, self.case.name)?;
+
+    for (name, outcome) in self.outcomes.iter() {
+      write!(f, " {name} found ")?;
+
+      match outcome {
+        Ok(ref stack) => write_stack(f, stack)?,
+        Err(ref reason) => write!(f, "error: {reason}")?,
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn write_stack(f: &mut fmt::Formatter<'_>, stack: &[Expr]) -> fmt::Result {
+  write!(f, "[")?;
+
+  for (i, expr) in stack.iter().enumerate() {
+    if i != 0 {
+      write!(f, " ")?;
+    }
+
+    write!(f, "{expr}")?;
+  }
+
+  write!(f, "]")
+}
+
+/// Returns every case in the suite.
+///
+/// # Panics
+///
+/// Panics if a suite file is malformed.
+pub fn cases() -> Vec<Case> {
+  SUITES
+    .iter()
+    .flat_map(|&(feature, source)| parse_suite(feature, source))
+    .collect()
+}
+
+/// Runs every case on `backend`, returning the ones it fails.
+pub fn run(backend: &dyn Backend) -> Vec<Failure> {
+  cases()
+    .into_iter()
+    .filter_map(|case| {
+      let actual = backend.run(&case.code);
+
+      let passed = match (&case.expected, &actual) {
+        (Expected::Stack(expected), Ok(actual)) => expected == actual,
+        (Expected::Error(expected), Err(actual)) => expected == actual,
+        _ => false,
+      };
+
+      (!passed).then_some(Failure { case, actual })
+    })
+    .collect()
+}
+
+/// Runs every case on both `lhs` and `rhs`, returning the ones on which they
+/// leave a different stack or fail for a different reason.
+///
+/// Unlike [`run`], this ignores the expected outcome, so the backends are
+/// still compared on cases which both of them fail.
+pub fn compare(lhs: &dyn Backend, rhs: &dyn Backend) -> Vec<Divergence> {
+  cases()
+    .into_iter()
+    .filter_map(|case| {
+      let lhs_outcome = lhs.run(&case.code);
+      let rhs_outcome = rhs.run(&case.code);
+
+      (lhs_outcome != rhs_outcome).then(|| Divergence {
+        case,
+        outcomes: [
+          (lhs.name().into(), lhs_outcome),
+          (rhs.name().into(), rhs_outcome),
+        ],
+      })
+    })
+    .collect()
+}
+
+fn parse_suite(feature: &'static str, source: &str) -> Vec<Case> {
+  let mut cases = Vec::new();
+  let mut current: Option<(String, String)> = None;
+
+  for line in source.lines() {
+    let Some(directive) = line.strip_prefix(";;;") else {
+      if let Some((_, ref mut code)) = current {
+        code.push_str(line);
+        code.push('\n');
+      }
+
+      continue;
+    };
+
+    let (key, value) = directive
+      .split_once(':')
+      .map(|(key, value)| (key.trim(), value.trim()))
+      .unwrap_or_else(|| panic!("{feature}: invalid directive `{line}`"));
+
+    match key {
+      "case" => {
+        if let Some((name, _)) = current {
+          panic!("{feature}/{name}: missing expected outcome");
+        }
+
+        current = Some((value.into(), String::new()));
+      }
+      "stack" | "error" => {
+        let (name, code) = current
+          .take()
+          .unwrap_or_else(|| panic!("{feature}: `{line}` outside of a case"));
+
+        let expected = match key {
+          "stack" => Expected::Stack(parse_stack(feature, &name, value)),
+          _ => Expected::Error(value.into()),
+        };
+
+        cases.push(Case {
+          feature,
+          name,
+          code,
+          expected,
+        });
+      }
+      _ => panic!("{feature}: unknown directive `{key}`"),
+    }
+  }
+
+  if let Some((name, _)) = current {
+    panic!("{feature}/{name}: missing expected outcome");
+  }
+
+  cases
+}
+
+/// Parses an expected stack, which is written as data rather than code.
+fn parse_stack(feature: &str, name: &str, value: &str) -> Vec<Expr> {
+  let mut lexer = Lexer::new(Source::new("spec", value));
+
+  parse(&mut lexer)
+    .unwrap_or_else(|e| panic!("{feature}/{name}: invalid stack: {e}"))
+    .into_iter()
+    .map(normalize)
+    .collect()
+}
+
+/// Turns `(error x)` into an error value, throughout `expr`.
+fn normalize(expr: Expr) -> Expr {
+  let kind = match expr.kind {
+    ExprKind::SExpr { call, mut body }
+      if call.as_str() == "error" && body.len() == 1 =>
+    {
+      ExprKind::Error(Box::new(normalize(body.remove(0))))
+    }
+    ExprKind::Lazy(x) => ExprKind::Lazy(Box::new(normalize(*x))),
+    ExprKind::List(x) => ExprKind::List(x.into_iter().map(normalize).collect()),
+    ExprKind::Record(x) => ExprKind::Record(
+      x.into_iter()
+        .map(|(key, value)| (key, normalize(value)))
+        .collect(),
+    ),
+    kind => kind,
+  };
+
+  Expr { kind, info: None }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_suites() {
+    let cases = parse_suite(
+      "test",
+      ";;; case: a\n1 2 +\n;;; stack: 3\n\n;;; case: b\ndrop\n;;; error: stack underflow\n",
+    );
+
+    assert_eq!(
+      cases,
+      vec![
+        Case {
+          feature: "test",
+          name: "a".into(),
+          code: "1 2 +\n".into(),
+          expected: Expected::Stack(vec![ExprKind::Integer(3).into()]),
+        },
+        Case {
+          feature: "test",
+          name: "b".into(),
+          code: "drop\n".into(),
+          expected: Expected::Error("stack underflow".into()),
+        },
+      ]
+    );
+  }
+
+  /// Leaves nothing behind, whatever the code.
+  struct EmptyBackend;
+
+  impl Backend for EmptyBackend {
+    fn name(&self) -> &str {
+      "empty"
+    }
+
+    fn run(&self, _: &str) -> Result<Vec<Expr>, String> {
+      Ok(Vec::new())
+    }
+  }
+
+  #[test]
+  fn compares_backends() {
+    assert_eq!(compare(&EngineBackend, &EngineBackend), vec![]);
+
+    let divergences = compare(&EngineBackend, &EmptyBackend);
+    assert!(!divergences.is_empty());
+    assert!(divergences
+      .iter()
+      .all(|x| x.outcomes[1].1 == Ok(Vec::new())));
+  }
+
+  #[test]
+  fn case_names_are_unique() {
+    let mut names = std::collections::HashSet::new();
+
+    for case in cases() {
+      assert!(
+        names.insert((case.feature, case.name.clone())),
+        "duplicate case {}/{}",
+        case.feature,
+        case.name
+      );
+    }
+  }
+}
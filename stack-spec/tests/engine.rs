@@ -0,0 +1,27 @@
+use stack_spec::{compare, run, EngineBackend, SchedulerBackend};
+
+#[test]
+fn engine_conforms() {
+  let failures = run(&EngineBackend);
+
+  for failure in failures.iter() {
+    eprintln!("{failure}");
+  }
+
+  assert!(failures.is_empty(), "{} cases failed", failures.len());
+}
+
+#[test]
+fn scheduler_matches_engine() {
+  let divergences = compare(&EngineBackend, &SchedulerBackend);
+
+  for divergence in divergences.iter() {
+    eprintln!("{divergence}");
+  }
+
+  assert!(
+    divergences.is_empty(),
+    "{} cases diverged",
+    divergences.len()
+  );
+}
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use stack_core::prelude::*;
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "on",
+    "( handler type -- )\n\nHandles the messages of a type with a block.",
+  ),
+  (
+    "off",
+    "( type -- )\n\nStops handling the messages of a type.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("actor"));
+
+  module
+    .add_func(
+      Symbol::from_ref("on"),
+      Arc::new(|_, mut context, expr| {
+        let ty = context.stack_pop(&expr)?;
+        let handler = context.stack_pop(&expr)?;
+
+        match ty.kind {
+          ExprKind::Symbol(ty) => {
+            context.mailbox_mut().on(ty, handler);
+          }
+          ExprKind::String(ref ty) => {
+            context
+              .mailbox_mut()
+              .on(Symbol::from_ref(ty.as_str()), handler);
+          }
+          _ => {}
+        }
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("off"),
+      Arc::new(|_, mut context, expr| {
+        let ty = context.stack_pop(&expr)?;
+
+        match ty.kind {
+          ExprKind::Symbol(ty) => {
+            context.mailbox_mut().off(&ty);
+          }
+          ExprKind::String(ref ty) => {
+            context.mailbox_mut().off(&Symbol::from_ref(ty.as_str()));
+          }
+          _ => {}
+        }
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
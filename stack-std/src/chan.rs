@@ -0,0 +1,100 @@
+//! Channels, for sending values between tasks.
+//!
+//! `chan:new` pushes a channel, which any number of tasks can send to and
+//! receive from. `chan:recv` waits until there's a value to take, or pushes
+//! `nil` once the channel has been closed with `chan:close` and emptied.
+//!
+//! Channels are foreign values, so they can only be made with this module.
+
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Condvar, Mutex},
+};
+
+use stack_core::prelude::*;
+
+#[derive(Debug, Default)]
+struct Queue {
+  items: VecDeque<Expr>,
+  is_closed: bool,
+}
+
+/// An unbounded queue of values, shared between the tasks which hold it.
+#[derive(Debug, Default)]
+pub struct Chan {
+  queue: Mutex<Queue>,
+  ready: Condvar,
+}
+
+impl Chan {
+  /// Adds a value to the end of the queue, returning `false` if the channel
+  /// has been closed.
+  pub fn send(&self, value: Expr) -> bool {
+    let mut queue = self.queue.lock().unwrap();
+
+    if queue.is_closed {
+      return false;
+    }
+
+    queue.items.push_back(value);
+    self.ready.notify_one();
+
+    true
+  }
+
+  /// Takes the value at the front of the queue, waiting for one to be sent.
+  /// Returns [`None`] once the channel is closed and empty.
+  pub fn recv(&self) -> Option<Expr> {
+    let mut queue = self.queue.lock().unwrap();
+
+    loop {
+      if let Some(value) = queue.items.pop_front() {
+        return Some(value);
+      }
+
+      if queue.is_closed {
+        return None;
+      }
+
+      queue = self.ready.wait(queue).unwrap();
+    }
+  }
+
+  /// Stops any more values from being sent, waking the tasks waiting on it.
+  pub fn close(&self) {
+    self.queue.lock().unwrap().is_closed = true;
+    self.ready.notify_all();
+  }
+}
+
+const DOCS: &[(&str, &str)] = &[
+  ("new", "( -- chan )\n\nPushes a new channel."),
+  (
+    "send",
+    "( chan value -- b )\n\nSends a value on a channel, pushing `false` if it \
+     has been closed.",
+  ),
+  (
+    "recv",
+    "( chan -- value )\n\nWaits for a value from a channel, pushing `nil` once \
+     it has been closed and emptied.",
+  ),
+  (
+    "close",
+    "( chan -- nil )\n\nCloses a channel, waking the tasks waiting on it.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("chan"))
+    .with_fn(Symbol::from_ref("new"), || Arc::new(Chan::default()))
+    .with_fn(Symbol::from_ref("send"), |chan: Arc<Chan>, value: Expr| {
+      chan.send(value)
+    })
+    .with_fn(Symbol::from_ref("recv"), |chan: Arc<Chan>| chan.recv())
+    .with_fn(Symbol::from_ref("close"), |chan: Arc<Chan>| chan.close());
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
@@ -0,0 +1,476 @@
+//! Parsing and writing CSV.
+//!
+//! By default, the first row is a header, and each row after it becomes a
+//! record keyed by the header's fields. Fields are always strings. The
+//! `-with` variants take a record of options:
+//!
+//! - `delimiter`, the character between fields, which is `,` by default.
+//! - `quote`, the character which fields containing a delimiter, quote or
+//!   newline are wrapped in, which is `"` by default. It's escaped within a
+//!   field by doubling it.
+//! - `headers`, whether there's a header row, which is `true` by default.
+//!   Without one, each row is a list of fields.
+//! - `columns`, only for `csv:write-with`, a list of the keys to write from
+//!   each record or map, in order.
+//!
+//! Records don't keep the order of their keys, so without `columns` they're
+//! written with their keys sorted. To write rows in the order they were
+//! parsed in, pass the header row as the `columns`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use stack_core::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+  pub delimiter: char,
+  pub quote: char,
+  pub headers: bool,
+}
+
+impl Default for Options {
+  fn default() -> Self {
+    Self {
+      delimiter: ',',
+      quote: '"',
+      headers: true,
+    }
+  }
+}
+
+impl Options {
+  /// Reads options from a record, returning [`None`] if one is invalid.
+  pub fn from_kind(kind: &ExprKind) -> Option<Self> {
+    let ExprKind::Record(x) = kind else {
+      return None;
+    };
+
+    let char_of = |key: &str| match x.get(&Symbol::from_ref(key)) {
+      None => Some(None),
+      Some(Expr {
+        kind: ExprKind::Char(x),
+        ..
+      }) => Some(Some(*x)),
+      Some(Expr {
+        kind: ExprKind::String(x),
+        ..
+      }) => {
+        let mut chars = x.chars();
+        match (chars.next(), chars.next()) {
+          (Some(x), None) => Some(Some(x)),
+          _ => None,
+        }
+      }
+      Some(_) => None,
+    };
+
+    let mut options = Self::default();
+
+    if let Some(x) = char_of("delimiter")? {
+      options.delimiter = x;
+    }
+
+    if let Some(x) = char_of("quote")? {
+      options.quote = x;
+    }
+
+    match x.get(&Symbol::from_ref("headers")) {
+      None => {}
+      Some(Expr {
+        kind: ExprKind::Boolean(x),
+        ..
+      }) => options.headers = *x,
+      Some(_) => return None,
+    }
+
+    Some(options)
+  }
+}
+
+/// Splits CSV into rows of fields, returning [`None`] if a quoted field isn't
+/// closed.
+pub fn parse_rows(source: &str, options: Options) -> Option<Vec<Vec<String>>> {
+  let mut rows = Vec::new();
+  let mut row = Vec::new();
+  let mut field = String::new();
+  let mut chars = source.chars().peekable();
+  let mut is_quoted = false;
+  let mut is_empty = true;
+
+  while let Some(c) = chars.next() {
+    if is_quoted {
+      match c {
+        c if c == options.quote => {
+          if chars.next_if_eq(&options.quote).is_some() {
+            field.push(c);
+          } else {
+            is_quoted = false;
+          }
+        }
+        c => field.push(c),
+      }
+
+      continue;
+    }
+
+    match c {
+      c if c == options.quote && field.is_empty() => {
+        is_quoted = true;
+        is_empty = false;
+      }
+      c if c == options.delimiter => {
+        row.push(core::mem::take(&mut field));
+        is_empty = false;
+      }
+      '\r' if chars.peek() == Some(&'\n') => {}
+      '\n' => {
+        if !is_empty || !field.is_empty() {
+          row.push(core::mem::take(&mut field));
+          rows.push(core::mem::take(&mut row));
+        }
+
+        is_empty = true;
+      }
+      c => {
+        field.push(c);
+        is_empty = false;
+      }
+    }
+  }
+
+  if is_quoted {
+    return None;
+  }
+
+  if !is_empty || !field.is_empty() {
+    row.push(field);
+    rows.push(row);
+  }
+
+  Some(rows)
+}
+
+/// Parses CSV into a list of records, or of lists without headers.
+pub fn parse(source: &str, options: Options) -> Option<ExprKind> {
+  let mut rows = parse_rows(source, options)?.into_iter();
+  let string = |x: String| Expr::from(ExprKind::String(x.into()));
+
+  if !options.headers {
+    return Some(ExprKind::List(
+      rows
+        .map(|row| ExprKind::List(row.into_iter().map(string).collect()).into())
+        .collect(),
+    ));
+  }
+
+  let headers = rows
+    .next()
+    .unwrap_or_default()
+    .into_iter()
+    .map(|x| Symbol::from_ref(x.as_str()))
+    .collect::<Vec<_>>();
+
+  Some(ExprKind::List(
+    rows
+      .map(|row| {
+        ExprKind::Record(
+          headers
+            .iter()
+            .copied()
+            .zip(row.into_iter().map(string))
+            .collect::<HashMap<_, _>>()
+            .into(),
+        )
+        .into()
+      })
+      .collect(),
+  ))
+}
+
+fn write_field(out: &mut String, kind: &ExprKind, options: Options) {
+  let field = match kind {
+    ExprKind::Nil => String::new(),
+    kind => kind.to_string(),
+  };
+
+  let needs_quotes =
+    field.contains([options.delimiter, options.quote, '\n', '\r']);
+
+  if needs_quotes {
+    out.push(options.quote);
+    for c in field.chars() {
+      if c == options.quote {
+        out.push(c);
+      }
+
+      out.push(c);
+    }
+    out.push(options.quote);
+  } else {
+    out.push_str(&field);
+  }
+}
+
+fn write_row<'a, I>(out: &mut String, fields: I, options: Options)
+where
+  I: IntoIterator<Item = &'a ExprKind>,
+{
+  for (i, field) in fields.into_iter().enumerate() {
+    if i != 0 {
+      out.push(options.delimiter);
+    }
+
+    write_field(out, field, options);
+  }
+
+  out.push('\n');
+}
+
+/// Reads the `columns` option from a record, returning [`None`] if it isn't a
+/// list of strings or symbols.
+pub fn columns_from_kind(kind: &ExprKind) -> Option<Option<Vec<ExprKind>>> {
+  let ExprKind::Record(x) = kind else {
+    return None;
+  };
+
+  match x.get(&Symbol::from_ref("columns")).map(|x| &x.kind) {
+    None => Some(None),
+    Some(ExprKind::List(x)) => x
+      .iter()
+      .map(|x| match x.kind {
+        ExprKind::String(_) | ExprKind::Symbol(_) => Some(x.kind.clone()),
+        _ => None,
+      })
+      .collect::<Option<_>>()
+      .map(Some),
+    Some(_) => None,
+  }
+}
+
+/// Writes a list of rows as CSV, returning [`None`] if a row isn't a list,
+/// record or map.
+///
+/// The header is `columns` if there are any, and otherwise comes from the
+/// first row if it's a record, with its keys sorted, or a map, with its keys
+/// in order. Rows which are lists are written as they are.
+pub fn write(
+  rows: &[Expr],
+  options: Options,
+  columns: Option<Vec<ExprKind>>,
+) -> Option<String> {
+  let mut out = String::new();
+
+  let headers = match (columns, rows.first().map(|x| &x.kind)) {
+    (Some(columns), _) => Some(columns),
+    (None, Some(ExprKind::Record(x))) => {
+      let mut keys = x.keys().copied().collect::<Vec<_>>();
+      keys.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+      Some(keys.into_iter().map(ExprKind::Symbol).collect())
+    }
+    (None, Some(ExprKind::Map(x))) => {
+      Some(x.keys().cloned().map(ExprKind::from).collect())
+    }
+    _ => None,
+  };
+
+  if let Some(ref headers) = headers {
+    if options.headers {
+      write_row(&mut out, headers, options);
+    }
+  }
+
+  for row in rows {
+    match row.kind {
+      ExprKind::List(ref x) => {
+        write_row(&mut out, x.iter().map(|x| &x.kind), options)
+      }
+      ExprKind::Record(ref x) => {
+        let fields = headers
+          .iter()
+          .flatten()
+          .map(|key| match key {
+            ExprKind::Symbol(key) => x.get(key).map(|x| x.kind.clone()),
+            ExprKind::String(key) => {
+              x.get(&Symbol::from_ref(key)).map(|x| x.kind.clone())
+            }
+            _ => None,
+          })
+          .map(|x| x.unwrap_or(ExprKind::Nil))
+          .collect::<Vec<_>>();
+
+        write_row(&mut out, &fields, options);
+      }
+      ExprKind::Map(ref x) => {
+        let fields = headers
+          .iter()
+          .flatten()
+          .map(|key| {
+            MapKey::from_kind(key)
+              .and_then(|key| x.get(&key))
+              .map_or(ExprKind::Nil, |x| x.kind.clone())
+          })
+          .collect::<Vec<_>>();
+
+        write_row(&mut out, &fields, options);
+      }
+      _ => return None,
+    }
+  }
+
+  Some(out)
+}
+
+fn run_parse(
+  mut context: Context,
+  expr: Expr,
+  has_options: bool,
+) -> Result<Context, RunError> {
+  let options = match has_options {
+    true => Options::from_kind(&context.stack_pop(&expr)?.kind),
+    false => Some(Options::default()),
+  };
+  let item = context.stack_pop(&expr)?;
+
+  let kind = match (item.kind, options) {
+    (ExprKind::String(ref x), Some(options)) => parse(x, options)
+      .unwrap_or_else(|| {
+        ExprKind::Error(Box::new(
+          ExprKind::String("unclosed quote".into()).into(),
+        ))
+      }),
+    _ => ExprKind::Nil,
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+fn run_write(
+  mut context: Context,
+  expr: Expr,
+  has_options: bool,
+) -> Result<Context, RunError> {
+  let options = match has_options {
+    true => {
+      let options = context.stack_pop(&expr)?;
+      Options::from_kind(&options.kind).zip(columns_from_kind(&options.kind))
+    }
+    false => Some((Options::default(), None)),
+  };
+  let item = context.stack_pop(&expr)?;
+
+  let kind = match (item.kind, options) {
+    (ExprKind::List(ref x), Some((options, columns))) => {
+      write(x, options, columns)
+        .map_or(ExprKind::Nil, |x| ExprKind::String(x.into()))
+    }
+    _ => ExprKind::Nil,
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "parse",
+    "( s -- rows )\n\nParses CSV with a header row into a list of records.",
+  ),
+  (
+    "parse-with",
+    "( s options -- rows )\n\nParses CSV with a record of options.",
+  ),
+  (
+    "write",
+    "( rows -- s )\n\nWrites a list of records, maps or lists as CSV.",
+  ),
+  (
+    "write-with",
+    "( rows options -- s )\n\nWrites rows as CSV with a record of options.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("csv"));
+
+  module
+    .add_func(
+      Symbol::from_ref("parse"),
+      Arc::new(|_, context, expr| run_parse(context, expr, false)),
+    )
+    .add_func(
+      Symbol::from_ref("parse-with"),
+      Arc::new(|_, context, expr| run_parse(context, expr, true)),
+    )
+    .add_func(
+      Symbol::from_ref("write"),
+      Arc::new(|_, context, expr| run_write(context, expr, false)),
+    )
+    .add_func(
+      Symbol::from_ref("write-with"),
+      Arc::new(|_, context, expr| run_write(context, expr, true)),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(code: &str) -> Vec<Expr> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = stack_core::parser::parse(&mut lexer).unwrap();
+
+    let mut context = Engine::new()
+      .with_module(module())
+      .run(Context::new(), exprs)
+      .unwrap();
+
+    core::mem::take(context.stack_mut())
+  }
+
+  fn string(x: &str) -> Expr {
+    ExprKind::String(x.into()).into()
+  }
+
+  #[test]
+  fn round_trips_in_column_order() {
+    assert_eq!(
+      run(
+        r#""z,y,x,w\n1,2,3,4" csv:parse
+        {columns: ["z" "y" "x" "w"]} csv:write-with"#
+      ),
+      vec![string("z,y,x,w\n1,2,3,4\n")]
+    );
+  }
+
+  #[test]
+  fn sorts_record_keys_without_columns() {
+    assert_eq!(
+      run(r#""z,y,x,w\n1,2,3,4" csv:parse csv:write"#),
+      vec![string("w,x,y,z\n4,3,2,1\n")]
+    );
+  }
+
+  #[test]
+  fn writes_only_the_columns() {
+    assert_eq!(
+      run(
+        r#"[{a: 1 b: "x,y"} {b: 2}]
+        {columns: ["b" "a"] headers: false} csv:write-with"#
+      ),
+      vec![string("\"x,y\",1\n2,\n")]
+    );
+    assert_eq!(
+      run(r#"[{a: 1}] {columns: [1]} csv:write-with"#),
+      vec![ExprKind::Nil.into()]
+    );
+  }
+}
@@ -0,0 +1,290 @@
+//! Encoding and decoding text for the web.
+//!
+//! The encoders take a string (as UTF-8) or bytes. Base64 and hex decode into
+//! bytes, which can be turned back into a string with `"utf-8" decode`, and
+//! URL and HTML decode into strings. Invalid input decodes into an error.
+//!
+//! `encode:base64-url` uses the URL-safe alphabet without padding, as in JWTs,
+//! and its decoder accepts input with or without padding.
+
+use std::sync::Arc;
+
+use compact_str::{CompactString, ToCompactString};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC};
+use stack_core::prelude::*;
+
+const STANDARD: &[u8; 64] =
+  b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE: &[u8; 64] =
+  b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// The characters which are percent-encoded, which is everything except the
+/// unreserved characters of RFC 3986.
+const URL_ENCODED: &AsciiSet = &NON_ALPHANUMERIC
+  .remove(b'-')
+  .remove(b'.')
+  .remove(b'_')
+  .remove(b'~');
+
+pub fn base64_encode(
+  data: &[u8],
+  alphabet: &[u8; 64],
+  is_padded: bool,
+) -> String {
+  let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+  for chunk in data.chunks(3) {
+    let n = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |n, (i, x)| n | (*x as u32) << (16 - i * 8));
+
+    for i in 0..=chunk.len() {
+      out.push(alphabet[(n >> (18 - i * 6)) as usize & 0x3f] as char);
+    }
+
+    if is_padded {
+      for _ in chunk.len()..3 {
+        out.push('=');
+      }
+    }
+  }
+
+  out
+}
+
+/// Decodes base64, returning [`None`] if it's invalid.
+pub fn base64_decode(string: &str, alphabet: &[u8; 64]) -> Option<Vec<u8>> {
+  let string = string.trim_end_matches('=');
+  let mut out = Vec::with_capacity(string.len() * 3 / 4);
+
+  for chunk in string.as_bytes().chunks(4) {
+    if chunk.len() == 1 {
+      return None;
+    }
+
+    let mut n = 0u32;
+    for (i, c) in chunk.iter().enumerate() {
+      let x = alphabet.iter().position(|x| x == c)? as u32;
+      n |= x << (18 - i * 6);
+    }
+
+    for i in 0..chunk.len() - 1 {
+      out.push((n >> (16 - i * 8)) as u8);
+    }
+  }
+
+  Some(out)
+}
+
+pub fn hex_encode(data: &[u8]) -> String {
+  data.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+/// Decodes hex of either case, returning [`None`] if it's invalid.
+pub fn hex_decode(string: &str) -> Option<Vec<u8>> {
+  if !string.len().is_multiple_of(2)
+    || !string.bytes().all(|x| x.is_ascii_hexdigit())
+  {
+    return None;
+  }
+
+  (0..string.len())
+    .step_by(2)
+    .map(|i| u8::from_str_radix(&string[i..i + 2], 16).ok())
+    .collect()
+}
+
+pub fn html_escape(string: &str) -> String {
+  let mut out = String::with_capacity(string.len());
+
+  for c in string.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&#39;"),
+      c => out.push(c),
+    }
+  }
+
+  out
+}
+
+/// Unescapes the entities which [`html_escape`] writes, along with `&apos;`,
+/// `&nbsp;` and numeric references. Anything else is kept as it is.
+pub fn html_unescape(string: &str) -> String {
+  let mut out = String::with_capacity(string.len());
+  let mut rest = string;
+
+  while let Some(i) = rest.find('&') {
+    out.push_str(&rest[..i]);
+    rest = &rest[i..];
+
+    let entity = rest.find(';').map(|end| (&rest[1..end], end));
+    let c = entity.and_then(|(name, _)| match name {
+      "amp" => Some('&'),
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      "nbsp" => Some('\u{a0}'),
+      name => match name.strip_prefix('#') {
+        Some(x) => match x.strip_prefix(['x', 'X']) {
+          Some(x) => u32::from_str_radix(x, 16).ok(),
+          None => x.parse().ok(),
+        }
+        .and_then(char::from_u32),
+        None => None,
+      },
+    });
+
+    match (c, entity) {
+      (Some(c), Some((_, end))) => {
+        out.push(c);
+        rest = &rest[end + 1..];
+      }
+      _ => {
+        out.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+
+  out.push_str(rest);
+
+  out
+}
+
+fn bytes(kind: &ExprKind) -> Option<&[u8]> {
+  match kind {
+    ExprKind::String(x) => Some(x.as_bytes()),
+    ExprKind::Bytes(x) => Some(x),
+    _ => None,
+  }
+}
+
+fn invalid(name: &str) -> ExprKind {
+  ExprKind::Error(Box::new(
+    ExprKind::String(format!("invalid {name}").to_compact_string()).into(),
+  ))
+}
+
+/// Adds a function which encodes a string or bytes into a string.
+fn add_encoder(module: &mut Module, name: &str, f: fn(&[u8]) -> String) {
+  module.add_func(
+    Symbol::from_ref(name),
+    Arc::new(move |_, mut context, expr| {
+      let item = context.stack_pop(&expr)?;
+
+      let kind = bytes(&item.kind)
+        .map_or(ExprKind::Nil, |x| ExprKind::String(f(x).into()));
+      context.stack_push(kind.into())?;
+
+      Ok(context)
+    }),
+  );
+}
+
+/// Adds a function which decodes a string, pushing an error if it's invalid.
+fn add_decoder(
+  module: &mut Module,
+  name: &'static str,
+  f: fn(&str) -> Option<ExprKind>,
+) {
+  module.add_func(
+    Symbol::from_ref(name),
+    Arc::new(move |_, mut context, expr| {
+      let item = context.stack_pop(&expr)?;
+
+      let kind = match item.kind {
+        ExprKind::String(ref x) => {
+          f(x).unwrap_or_else(|| invalid(name.trim_end_matches("-decode")))
+        }
+        _ => ExprKind::Nil,
+      };
+      context.stack_push(kind.into())?;
+
+      Ok(context)
+    }),
+  );
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "base64",
+    "( data -- s )\n\nEncodes a string or bytes as Base64.",
+  ),
+  ("base64-decode", "( s -- bytes )\n\nDecodes Base64."),
+  (
+    "base64-url",
+    "( data -- s )\n\nEncodes a string or bytes as URL-safe Base64, without \
+     padding.",
+  ),
+  (
+    "base64-url-decode",
+    "( s -- bytes )\n\nDecodes URL-safe Base64, with or without padding.",
+  ),
+  ("hex", "( data -- s )\n\nEncodes a string or bytes as hex."),
+  ("hex-decode", "( s -- bytes )\n\nDecodes hex."),
+  (
+    "url",
+    "( data -- s )\n\nPercent-encodes a string or bytes for a URL.",
+  ),
+  (
+    "url-decode",
+    "( s -- s )\n\nDecodes a percent-encoded string.",
+  ),
+  (
+    "html-escape",
+    "( data -- s )\n\nEscapes the characters of a string which are special in \
+     HTML.",
+  ),
+  (
+    "html-unescape",
+    "( s -- s )\n\nUnescapes the entities of an HTML string.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("encode"));
+
+  add_encoder(&mut module, "base64", |x| base64_encode(x, STANDARD, true));
+  add_decoder(&mut module, "base64-decode", |x| {
+    base64_decode(x, STANDARD).map(ExprKind::Bytes)
+  });
+
+  add_encoder(&mut module, "base64-url", |x| {
+    base64_encode(x, URL_SAFE, false)
+  });
+  add_decoder(&mut module, "base64-url-decode", |x| {
+    base64_decode(x, URL_SAFE).map(ExprKind::Bytes)
+  });
+
+  add_encoder(&mut module, "hex", hex_encode);
+  add_decoder(&mut module, "hex-decode", |x| {
+    hex_decode(x).map(ExprKind::Bytes)
+  });
+
+  add_encoder(&mut module, "url", |x| {
+    percent_encoding::percent_encode(x, URL_ENCODED).to_string()
+  });
+  add_decoder(&mut module, "url-decode", |x| {
+    percent_encoding::percent_decode_str(x)
+      .decode_utf8()
+      .ok()
+      .map(|x| ExprKind::String(x.to_compact_string()))
+  });
+
+  add_encoder(&mut module, "html-escape", |x| {
+    html_escape(&String::from_utf8_lossy(x))
+  });
+  add_decoder(&mut module, "html-unescape", |x| {
+    Some(ExprKind::String(CompactString::from(html_unescape(x))))
+  });
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
@@ -0,0 +1,127 @@
+//! Reading the environment the script was run in.
+//!
+//! `env:args` pushes the arguments given to the script, which for `stack run`
+//! are the ones after `--`. Unless the [`SandboxPolicy`] allows using the
+//! environment, it's the only function, so that environment variables can't be
+//! read or changed.
+
+use std::sync::Arc;
+
+use compact_str::ToCompactString;
+use stack_core::prelude::*;
+
+use crate::sandbox::SandboxPolicy;
+
+fn string(x: impl ToCompactString) -> Expr {
+  ExprKind::String(x.to_compact_string()).into()
+}
+
+const DOCS: &[(&str, &str)] = &[
+  ("args", "( -- list )\n\nPushes the arguments given to the script."),
+  (
+    "get",
+    "( name -- s )\n\nPushes the value of an environment variable, or `nil` if \
+     it isn't set.",
+  ),
+  (
+    "set",
+    "( name value -- )\n\nSets an environment variable, or removes it if the \
+     value is `nil`.",
+  ),
+  ("vars", "( -- record )\n\nPushes a record of every environment variable."),
+  ("cwd", "( -- path )\n\nPushes the current directory."),
+];
+
+pub fn module(policy: &SandboxPolicy, args: Vec<String>) -> Module {
+  let mut module = Module::new(Symbol::from_ref("env"));
+  crate::add_docs(&mut module, DOCS);
+
+  module.add_func(
+    Symbol::from_ref("args"),
+    Arc::new(move |_, mut context, _| {
+      context
+        .stack_push(ExprKind::List(args.iter().map(string).collect()).into())?;
+
+      Ok(context)
+    }),
+  );
+
+  if !policy.can_env() {
+    return module;
+  }
+
+  module
+    .add_func(
+      Symbol::from_ref("get"),
+      Arc::new(|_, mut context, expr| {
+        let name = context.stack_pop(&expr)?;
+
+        let kind = match name.kind {
+          ExprKind::String(ref x) => std::env::var(x.as_str())
+            .map_or(ExprKind::Nil, |x| ExprKind::String(x.into())),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("set"),
+      Arc::new(|_, mut context, expr| {
+        let value = context.stack_pop(&expr)?;
+        let name = context.stack_pop(&expr)?;
+
+        // Names which the OS would reject are ignored, rather than panicking.
+        match name.kind {
+          ExprKind::String(ref x)
+            if !x.is_empty() && !x.contains(['=', '\0']) =>
+          {
+            match value.kind {
+              ExprKind::Nil => std::env::remove_var(x.as_str()),
+              ExprKind::String(ref value) if !value.contains('\0') => {
+                std::env::set_var(x.as_str(), value.as_str())
+              }
+              ExprKind::String(_) => {}
+              ref kind => std::env::set_var(x.as_str(), kind.to_string()),
+            }
+          }
+          _ => {}
+        }
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("vars"),
+      Arc::new(|_, mut context, _| {
+        context.stack_push(
+          ExprKind::Record(
+            std::env::vars()
+              .map(|(key, value)| {
+                (Symbol::from_ref(key.as_str()), string(value))
+              })
+              .collect(),
+          )
+          .into(),
+        )?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("cwd"),
+      Arc::new(|_, mut context, _| {
+        let kind = std::env::current_dir().map_or(ExprKind::Nil, |x| {
+          ExprKind::String(x.to_string_lossy().to_compact_string())
+        });
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    );
+
+  module
+}
@@ -1,49 +1,814 @@
-use std::sync::Arc;
+//! Reading and changing the file system.
+//!
+//! Functions which change something push `true` if they succeed, and those
+//! which fail push an error. Paths are checked against the [`SandboxPolicy`],
+//! so in a sandbox only those which have been allowed can be read or written.
+//!
+//! Large files can be streamed by opening a handle with `fs:open`, which is
+//! closed with `fs:close`, or along with the last copy of the handle. It's
+//! opened with a mode of `"r"` to read, `"w"` to write over the file, or `"a"`
+//! to append to it. A handle is a foreign value, so it can't be forged from
+//! other values, and using one which has been closed pushes `nil`.
+//!
+//! With the `async` feature, `fs:read-file` and `fs:read-bytes` are async
+//! functions, so [`Engine::run_async`] awaits them rather than blocking.
+//!
+//! [`Engine::run_async`]: stack_core::engine::Engine::run_async
+
+use std::{
+  fs::{File, OpenOptions},
+  io::{BufRead, BufReader, BufWriter, Read, Write},
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+  time::UNIX_EPOCH,
+};
 
 use compact_str::ToCompactString;
 use stack_core::prelude::*;
 
-pub fn module(sandbox: bool) -> Module {
-  let mut module = Module::new(Symbol::from_ref("fs"));
+use crate::sandbox::SandboxPolicy;
+
+/// Whether a path is used to read from or to write to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Use {
+  Read,
+  Write,
+}
+
+/// Checks paths against the [`SandboxPolicy`].
+#[derive(Debug, Clone)]
+struct Access(Arc<SandboxPolicy>);
+
+impl Access {
+  fn allows(&self, path: &Path, using: Use) -> bool {
+    match using {
+      Use::Read => self.0.can_read(path),
+      Use::Write => self.0.can_write(path),
+    }
+  }
+
+  /// Returns the path of a string, or an error if it isn't allowed.
+  fn path(
+    &self,
+    kind: &ExprKind,
+    using: Use,
+  ) -> Option<Result<PathBuf, ExprKind>> {
+    match kind {
+      ExprKind::String(x) => {
+        let path = PathBuf::from(x.as_str());
+
+        Some(match self.allows(&path, using) {
+          true => Ok(path),
+          false => Err(error(match using {
+            Use::Read => "reading this path isn't allowed",
+            Use::Write => "writing this path isn't allowed",
+          })),
+        })
+      }
+      _ => None,
+    }
+  }
+}
+
+fn error(message: impl ToCompactString) -> ExprKind {
+  ExprKind::Error(Box::new(
+    ExprKind::String(message.to_compact_string()).into(),
+  ))
+}
+
+fn string(path: &Path) -> ExprKind {
+  ExprKind::String(path.to_string_lossy().to_compact_string())
+}
+
+fn done(result: std::io::Result<()>) -> ExprKind {
+  match result {
+    Ok(()) => ExprKind::Boolean(true),
+    Err(e) => error(e),
+  }
+}
 
-  if !sandbox {
-    module
-      .add_func(
-        Symbol::from_ref("cwd"),
-        Arc::new(|_, mut context, _| {
-          context.stack_push(
-            std::env::current_dir()
-              .map(|x| {
-                ExprKind::String(
-                  x.to_string_lossy().into_owned().to_compact_string(),
-                )
-              })
-              .unwrap_or(ExprKind::Nil)
-              .into(),
-          )?;
-
-          Ok(context)
-        }),
+/// Adds a function which takes a path.
+fn add_path_func(
+  module: &mut Module,
+  access: &Access,
+  name: &str,
+  using: Use,
+  f: fn(&Path) -> ExprKind,
+) {
+  let access = access.clone();
+
+  module.add_func(
+    Symbol::from_ref(name),
+    Arc::new(move |_, mut context, expr| {
+      let path = context.stack_pop(&expr)?;
+
+      let kind = match access.path(&path.kind, using) {
+        Some(Ok(path)) => f(&path),
+        Some(Err(e)) => e,
+        None => ExprKind::Nil,
+      };
+
+      context.stack_push(kind.into())?;
+
+      Ok(context)
+    }),
+  );
+}
+
+/// Adds an async function which takes a path.
+#[cfg(feature = "async")]
+fn add_async_path_func<F>(
+  module: &mut Module,
+  access: &Access,
+  name: &str,
+  using: Use,
+  f: fn(PathBuf) -> F,
+) where
+  F: std::future::Future<Output = ExprKind> + Send + 'static,
+{
+  let access = access.clone();
+
+  module.add_async_func(
+    Symbol::from_ref(name),
+    Arc::new(move |mut context, expr| {
+      let access = access.clone();
+
+      Box::pin(async move {
+        let path = context.stack_pop(&expr)?;
+
+        let kind = match access.path(&path.kind, using) {
+          Some(Ok(path)) => f(path).await,
+          Some(Err(e)) => e,
+          None => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      })
+    }),
+  );
+}
+
+/// Adds a function which takes a path to move or copy from and one to write
+/// to.
+fn add_paths_func(
+  module: &mut Module,
+  access: &Access,
+  name: &str,
+  using: Use,
+  f: fn(&Path, &Path) -> std::io::Result<()>,
+) {
+  let access = access.clone();
+
+  module.add_func(
+    Symbol::from_ref(name),
+    Arc::new(move |_, mut context, expr| {
+      let to = context.stack_pop(&expr)?;
+      let from = context.stack_pop(&expr)?;
+
+      let kind = match (
+        access.path(&from.kind, using),
+        access.path(&to.kind, Use::Write),
+      ) {
+        (Some(Ok(from)), Some(Ok(to))) => done(f(&from, &to)),
+        (Some(Err(e)), _) | (_, Some(Err(e))) => e,
+        _ => ExprKind::Nil,
+      };
+
+      context.stack_push(kind.into())?;
+
+      Ok(context)
+    }),
+  );
+}
+
+fn read_dir(path: &Path) -> ExprKind {
+  let entries = std::fs::read_dir(path).and_then(|entries| {
+    entries
+      .map(|entry| entry.map(|x| x.path()))
+      .collect::<std::io::Result<Vec<_>>>()
+  });
+
+  match entries {
+    Ok(mut x) => {
+      x.sort();
+      ExprKind::List(x.iter().map(|x| string(x).into()).collect())
+    }
+    Err(e) => error(e),
+  }
+}
+
+fn metadata(path: &Path) -> ExprKind {
+  match std::fs::metadata(path) {
+    Ok(x) => {
+      let modified = x
+        .modified()
+        .ok()
+        .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+        .map_or(ExprKind::Nil, |x| ExprKind::Integer(x.as_millis() as i64));
+
+      ExprKind::Record(
+        [
+          ("size", ExprKind::Integer(x.len() as i64)),
+          ("modified", modified),
+          ("is-dir", ExprKind::Boolean(x.is_dir())),
+          ("is-file", ExprKind::Boolean(x.is_file())),
+        ]
+        .into_iter()
+        .map(|(key, value)| (Symbol::from_ref(key), value.into()))
+        .collect(),
       )
-      .add_func(
-        Symbol::from_ref("read-file"),
-        Arc::new(|_, mut context, expr| {
-          let path = context.stack_pop(&expr)?;
+    }
+    Err(e) => error(e),
+  }
+}
+
+fn remove(path: &Path) -> ExprKind {
+  done(match path.is_dir() {
+    true => std::fs::remove_dir_all(path),
+    false => std::fs::remove_file(path),
+  })
+}
+
+/// Returns whether a path component matches a pattern with `*`, `?` and
+/// character classes, such as `[abc]` or `[!a-z]`.
+pub fn matches(pattern: &str, text: &str) -> bool {
+  let pattern = pattern.chars().collect::<Vec<_>>();
+  let text = text.chars().collect::<Vec<_>>();
+
+  // The position to go back to after a `*`, and the text it has matched up to.
+  let mut backtrack = None;
+  let (mut p, mut t) = (0, 0);
+
+  while t < text.len() {
+    match pattern.get(p) {
+      Some('*') => {
+        backtrack = Some((p, t));
+        p += 1;
+        continue;
+      }
+      Some('?') => {
+        p += 1;
+        t += 1;
+        continue;
+      }
+      Some('[') => {
+        if let Some((is_match, len)) = class(&pattern[p..], text[t]) {
+          if is_match {
+            p += len;
+            t += 1;
+            continue;
+          }
+        } else if text[t] == '[' {
+          p += 1;
+          t += 1;
+          continue;
+        }
+      }
+      Some(c) if *c == text[t] => {
+        p += 1;
+        t += 1;
+        continue;
+      }
+      _ => {}
+    }
+
+    match backtrack {
+      Some((bp, bt)) => {
+        backtrack = Some((bp, bt + 1));
+        p = bp + 1;
+        t = bt + 1;
+      }
+      None => return false,
+    }
+  }
+
+  pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Matches a character against a class at the start of a pattern, returning
+/// whether it matched and the length of the class, or [`None`] if it isn't
+/// closed.
+fn class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+  let mut i = 1;
+  let is_negated = matches!(pattern.get(i), Some('!' | '^'));
+  if is_negated {
+    i += 1;
+  }
+
+  let mut is_match = false;
+  let start = i;
+
+  while pattern.get(i).is_some_and(|x| *x != ']' || i == start) {
+    let lo = pattern[i];
+
+    match (pattern.get(i + 1), pattern.get(i + 2)) {
+      (Some('-'), Some(hi)) if *hi != ']' => {
+        is_match |= (lo..=*hi).contains(&c);
+        i += 3;
+      }
+      _ => {
+        is_match |= lo == c;
+        i += 1;
+      }
+    }
+  }
+
+  pattern.get(i)?;
+
+  Some((is_match != is_negated, i + 1))
+}
+
+/// Finds the paths which match a pattern, where `**` matches any amount of
+/// directories.
+pub fn glob(pattern: &str) -> Vec<PathBuf> {
+  let is_absolute = pattern.starts_with('/');
+  let components = pattern
+    .split('/')
+    .filter(|x| !x.is_empty())
+    .collect::<Vec<_>>();
+
+  let base = match is_absolute {
+    true => PathBuf::from("/"),
+    false => PathBuf::new(),
+  };
+
+  let mut found = Vec::new();
+  glob_from(&base, &components, &mut found);
+  found.sort();
+  found.dedup();
+
+  found
+}
+
+fn glob_from(base: &Path, components: &[&str], found: &mut Vec<PathBuf>) {
+  let Some((first, rest)) = components.split_first() else {
+    found.push(base.to_path_buf());
+    return;
+  };
+
+  let dir = match base.as_os_str().is_empty() {
+    true => Path::new("."),
+    false => base,
+  };
+
+  // Components without a pattern don't need the directory to be listed.
+  if !first.contains(['*', '?', '[']) {
+    let path = base.join(first);
+    if path.exists() || path.is_symlink() {
+      glob_from(&path, rest, found);
+    }
+
+    return;
+  }
+
+  if *first == "**" {
+    glob_from(base, rest, found);
+  }
+
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return;
+  };
+
+  for entry in entries.flatten() {
+    let name = entry.file_name().to_string_lossy().into_owned();
+
+    // Like a shell, hidden files are only matched explicitly.
+    if name.starts_with('.') && !first.starts_with('.') {
+      continue;
+    }
+
+    let path = base.join(&name);
+
+    if *first == "**" {
+      if entry.file_type().is_ok_and(|x| x.is_dir()) {
+        glob_from(&path, components, found);
+      }
+    } else if matches(first, &name) {
+      glob_from(&path, rest, found);
+    }
+  }
+}
+
+/// An open file, which is buffered in the direction it was opened for.
+#[derive(Debug)]
+enum Stream {
+  Read(BufReader<File>),
+  Write(BufWriter<File>),
+}
+
+/// A file opened with `fs:open`, or [`None`] once it has been closed. The
+/// file is closed along with the last handle to it, too.
+#[derive(Debug)]
+pub struct FileHandle {
+  stream: Mutex<Option<Stream>>,
+}
+
+impl FileHandle {
+  fn open(path: &Path, mode: &str) -> Option<std::io::Result<Self>> {
+    let mut options = OpenOptions::new();
+
+    match mode {
+      "r" => options.read(true),
+      "w" => options.write(true).create(true).truncate(true),
+      "a" => options.append(true).create(true),
+      _ => return None,
+    };
+
+    Some(options.open(path).map(|x| {
+      let stream = match mode {
+        "r" => Stream::Read(BufReader::new(x)),
+        _ => Stream::Write(BufWriter::new(x)),
+      };
+
+      Self {
+        stream: Mutex::new(Some(stream)),
+      }
+    }))
+  }
 
-          let kind = match path.kind {
-            ExprKind::String(ref x) => std::fs::read_to_string(x.as_str())
-              .map(|x| x.to_compact_string())
-              .map(ExprKind::String)
-              .unwrap_or(ExprKind::Nil),
-            _ => ExprKind::Nil,
-          };
+  /// Reads up to `len` bytes, returning [`None`] at the end of the file, or
+  /// if it isn't open for reading.
+  pub fn read_chunk(&self, len: u64) -> Option<std::io::Result<Vec<u8>>> {
+    let mut stream = self.stream.lock().unwrap();
+    let Some(Stream::Read(reader)) = stream.as_mut() else {
+      return None;
+    };
 
-          context.stack_push(kind.into())?;
+    let mut chunk = Vec::new();
+    match reader.take(len).read_to_end(&mut chunk) {
+      Ok(0) => None,
+      Ok(_) => Some(Ok(chunk)),
+      Err(e) => Some(Err(e)),
+    }
+  }
 
-          Ok(context)
-        }),
-      );
+  /// Writes `data`, returning [`None`] if the file isn't open for writing.
+  pub fn write_chunk(&self, data: &[u8]) -> Option<std::io::Result<()>> {
+    match self.stream.lock().unwrap().as_mut() {
+      Some(Stream::Write(writer)) => Some(writer.write_all(data)),
+      _ => None,
+    }
   }
 
+  /// Reads the next line, without its line ending, returning [`None`] at the
+  /// end of the file, or if it isn't open for reading.
+  pub fn read_line(&self) -> Option<std::io::Result<String>> {
+    let mut stream = self.stream.lock().unwrap();
+    let Some(Stream::Read(reader)) = stream.as_mut() else {
+      return None;
+    };
+
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+      Ok(0) => None,
+      Ok(_) => {
+        let len = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(len);
+
+        Some(Ok(line))
+      }
+      Err(e) => Some(Err(e)),
+    }
+  }
+
+  /// Closes the file, flushing anything written to it, or returns [`None`]
+  /// if it has already been closed.
+  pub fn close(&self) -> Option<std::io::Result<()>> {
+    match self.stream.lock().unwrap().take()? {
+      Stream::Write(mut writer) => Some(writer.flush()),
+      Stream::Read(_) => Some(Ok(())),
+    }
+  }
+}
+
+fn add_streams(module: &mut Module, access: Access) {
   module
+    .add_func(
+      Symbol::from_ref("open"),
+      Arc::new(move |_, mut context, expr| {
+        let mode = context.stack_pop(&expr)?;
+        let path = context.stack_pop(&expr)?;
+
+        let using = match mode.kind {
+          ExprKind::String(ref x) if x.as_str() == "r" => Use::Read,
+          _ => Use::Write,
+        };
+
+        let expr = match (access.path(&path.kind, using), mode.kind) {
+          (Some(Ok(path)), ExprKind::String(ref mode)) => {
+            match FileHandle::open(&path, mode) {
+              Some(Ok(file)) => Arc::new(file).into_expr(),
+              Some(Err(e)) => error(e).into(),
+              None => ExprKind::Nil.into(),
+            }
+          }
+          (Some(Err(e)), _) => e.into(),
+          _ => ExprKind::Nil.into(),
+        };
+
+        context.stack_push(expr)?;
+
+        Ok(context)
+      }),
+    )
+    // The end of the file is nil, so that a loop can stop at it.
+    .add_fn(
+      Symbol::from_ref("read-chunk"),
+      |file: Arc<FileHandle>, len: i64| match len > 0 {
+        true => {
+          file
+            .read_chunk(len as u64)
+            .map_or(ExprKind::Nil, |x| match x {
+              Ok(x) => ExprKind::Bytes(x),
+              Err(e) => error(e),
+            })
+        }
+        false => ExprKind::Nil,
+      },
+    )
+    .add_fn(
+      Symbol::from_ref("write-chunk"),
+      |file: Arc<FileHandle>, data: Expr| {
+        let result = match data.kind {
+          ExprKind::String(ref x) => file.write_chunk(x.as_bytes()),
+          ExprKind::Bytes(ref x) => file.write_chunk(x),
+          _ => None,
+        };
+
+        result.map_or(ExprKind::Nil, done)
+      },
+    )
+    .add_func(
+      Symbol::from_ref("lines"),
+      Arc::new(move |engine, mut context, expr| {
+        let block = context.stack_pop(&expr)?;
+        let handle = context.stack_pop(&expr)?;
+
+        let Some(file) = Arc::<FileHandle>::from_expr(handle) else {
+          return Err(RunError {
+            reason: RunErrorReason::InvalidArgument,
+            context: Box::new(context),
+            expr: Box::new(expr),
+          });
+        };
+
+        loop {
+          match file.read_line() {
+            Some(Ok(line)) => {
+              context.stack_push(ExprKind::String(line.into()).into())?;
+              context = engine.call_expr(context, block.clone())?;
+            }
+            Some(Err(e)) => {
+              context.stack_push(error(e).into())?;
+              break;
+            }
+            None => break,
+          }
+        }
+
+        Ok(context)
+      }),
+    )
+    .add_fn(Symbol::from_ref("close"), |file: Arc<FileHandle>| {
+      file.close().map_or(ExprKind::Nil, done)
+    });
+}
+
+const DOCS: &[(&str, &str)] = &[
+  ("read-file", "( path -- s )\n\nReads a file as a string."),
+  ("read-bytes", "( path -- bytes )\n\nReads a file as bytes."),
+  (
+    "read-dir",
+    "( path -- list )\n\nPushes the sorted paths of the entries of a \
+     directory.",
+  ),
+  (
+    "create-dir",
+    "( path -- b )\n\nCreates a directory, along with any missing parents.",
+  ),
+  (
+    "remove",
+    "( path -- b )\n\nRemoves a file, or a directory along with everything in \
+     it.",
+  ),
+  ("exists", "( path -- b )\n\nWhether a path exists."),
+  (
+    "metadata",
+    "( path -- record )\n\nPushes the `size`, `modified` time, `is-dir` and \
+     `is-file` of a path.",
+  ),
+  ("copy", "( from to -- b )\n\nCopies a file."),
+  ("rename", "( from to -- b )\n\nMoves a file or directory."),
+  (
+    "open",
+    "( path mode -- file )\n\nOpens a file to stream, with a mode of `\"r\"` \
+     to read, `\"w\"` to write over it, or `\"a\"` to append to it.",
+  ),
+  (
+    "read-chunk",
+    "( file n -- bytes )\n\nReads up to `n` bytes from a file, pushing `nil` \
+     at the end of it.",
+  ),
+  (
+    "write-chunk",
+    "( file data -- b )\n\nWrites a string or bytes to a file.",
+  ),
+  (
+    "lines",
+    "( file block -- )\n\nCalls a block with each line of a file, without its \
+     line ending.",
+  ),
+  (
+    "close",
+    "( file -- b )\n\nCloses a file, flushing what's been written to it.",
+  ),
+  (
+    "cwd",
+    "( -- path )\n\nPushes the current directory, or `nil` if it can't be \
+     read.",
+  ),
+  (
+    "glob",
+    "( pattern -- list )\n\nPushes the sorted paths which match a pattern, \
+     where `*` matches within a component and `**` matches any amount of \
+     directories.",
+  ),
+];
+
+pub fn module(policy: &SandboxPolicy) -> Module {
+  let mut module = Module::new(Symbol::from_ref("fs"));
+  let access = Access(Arc::new(policy.clone()));
+
+  #[cfg(not(feature = "async"))]
+  {
+    add_path_func(&mut module, &access, "read-file", Use::Read, |path| {
+      match std::fs::read_to_string(path) {
+        Ok(x) => ExprKind::String(x.to_compact_string()),
+        Err(e) => error(e),
+      }
+    });
+    add_path_func(&mut module, &access, "read-bytes", Use::Read, |path| {
+      match std::fs::read(path) {
+        Ok(x) => ExprKind::Bytes(x),
+        Err(e) => error(e),
+      }
+    });
+  }
+  #[cfg(feature = "async")]
+  {
+    add_async_path_func(
+      &mut module,
+      &access,
+      "read-file",
+      Use::Read,
+      |path| async move {
+        match tokio::fs::read_to_string(path).await {
+          Ok(x) => ExprKind::String(x.to_compact_string()),
+          Err(e) => error(e),
+        }
+      },
+    );
+    add_async_path_func(
+      &mut module,
+      &access,
+      "read-bytes",
+      Use::Read,
+      |path| async move {
+        match tokio::fs::read(path).await {
+          Ok(x) => ExprKind::Bytes(x),
+          Err(e) => error(e),
+        }
+      },
+    );
+  }
+  add_path_func(&mut module, &access, "read-dir", Use::Read, read_dir);
+  add_path_func(&mut module, &access, "create-dir", Use::Write, |path| {
+    done(std::fs::create_dir_all(path))
+  });
+  add_path_func(&mut module, &access, "remove", Use::Write, remove);
+  add_path_func(&mut module, &access, "exists", Use::Read, |path| {
+    ExprKind::Boolean(path.exists())
+  });
+  add_path_func(&mut module, &access, "metadata", Use::Read, metadata);
+
+  add_paths_func(&mut module, &access, "copy", Use::Read, |from, to| {
+    std::fs::copy(from, to).map(|_| ())
+  });
+  add_paths_func(&mut module, &access, "rename", Use::Write, |from, to| {
+    std::fs::rename(from, to)
+  });
+
+  add_streams(&mut module, access.clone());
+
+  let glob_access = access.clone();
+
+  module
+    .add_func(
+      Symbol::from_ref("cwd"),
+      Arc::new(move |_, mut context, _| {
+        let kind = match std::env::current_dir() {
+          Ok(x) if access.allows(&x, Use::Read) => string(&x),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("glob"),
+      Arc::new(move |_, mut context, expr| {
+        let pattern = context.stack_pop(&expr)?;
+
+        let kind = match pattern.kind {
+          ExprKind::String(ref x) => ExprKind::List(
+            glob(x)
+              .iter()
+              .filter(|x| glob_access.allows(x, Use::Read))
+              .map(|x| string(x).into())
+              .collect(),
+          ),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(code: &str) -> Result<Context, RunError> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    Engine::new()
+      .with_module(module(&SandboxPolicy::unrestricted()))
+      .run(Context::new(), exprs)
+  }
+
+  #[test]
+  fn streams_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.txt").to_string_lossy().into_owned();
+
+    let context = run(&format!(
+      r#""{path}" "w" fs:open 'file def
+      file "one\ntwo" fs:write-chunk drop
+      file fs:close
+      file fs:close
+      "{path}" "r" fs:open '[] fs:lines
+      "{path}" "r" fs:open 3 fs:read-chunk"#
+    ))
+    .unwrap();
+
+    assert_eq!(
+      context.stack(),
+      &[
+        ExprKind::Boolean(true).into(),
+        ExprKind::Nil.into(),
+        ExprKind::String("one".into()).into(),
+        ExprKind::String("two".into()).into(),
+        ExprKind::Bytes(b"one".to_vec()).into(),
+      ]
+    );
+  }
+
+  #[test]
+  fn rejects_forged_handles() {
+    let error = run(r#"{type: "file", id: 0} fs:close"#).unwrap_err();
+    assert_eq!(error.reason, RunErrorReason::InvalidArgument);
+
+    let error = run(r#"{type: "file", id: 0} '[] fs:lines"#).unwrap_err();
+    assert_eq!(error.reason, RunErrorReason::InvalidArgument);
+  }
+
+  #[test]
+  fn closes_files_with_their_last_handle() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.txt");
+
+    // Without closing it, the write is only flushed when the handle drops.
+    let context = run(&format!(
+      r#""{}" "w" fs:open 'file def file "abc" fs:write-chunk drop"#,
+      path.display()
+    ))
+    .unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+    drop(context);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "abc");
+  }
 }
@@ -0,0 +1,326 @@
+//! Hashing strings and bytes.
+//!
+//! Each hash function takes a string (as UTF-8) or bytes and pushes the
+//! digest as bytes, which `hash:hex` turns into a lowercase hex string.
+//! `hash:hmac` takes the data, the key and the name of a hash function, such
+//! as `"sha256"`.
+//!
+//! MD5 and SHA-1 are broken for signatures, and are only meant for checking
+//! against existing digests.
+
+use std::sync::Arc;
+
+use compact_str::CompactString;
+use hmac::{
+  digest::{common::BlockSizeUser, Digest},
+  KeyInit, Mac, SimpleHmac,
+};
+use stack_core::prelude::*;
+
+pub type HashFn = fn(&[u8]) -> Vec<u8>;
+
+fn digest<D: Digest>(data: &[u8]) -> Vec<u8> {
+  D::digest(data).to_vec()
+}
+
+fn mac<D: Digest + BlockSizeUser>(key: &[u8], data: &[u8]) -> Vec<u8> {
+  // HMAC takes keys of any length, hashing those longer than a block.
+  let mut mac = SimpleHmac::<D>::new_from_slice(key).unwrap();
+  mac.update(data);
+
+  mac.finalize().into_bytes().to_vec()
+}
+
+pub fn md5(data: &[u8]) -> Vec<u8> {
+  digest::<md5::Md5>(data)
+}
+
+pub fn sha1(data: &[u8]) -> Vec<u8> {
+  digest::<sha1::Sha1>(data)
+}
+
+pub fn sha256(data: &[u8]) -> Vec<u8> {
+  digest::<sha2::Sha256>(data)
+}
+
+pub fn blake3(data: &[u8]) -> Vec<u8> {
+  blake3::hash(data).as_bytes().to_vec()
+}
+
+/// A hash function which HMAC can be used with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+  Md5,
+  Sha1,
+  Sha256,
+  Blake3,
+}
+
+impl Algorithm {
+  /// Returns the hash function with a name.
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name.to_ascii_lowercase().as_str() {
+      "md5" => Some(Self::Md5),
+      "sha1" | "sha-1" => Some(Self::Sha1),
+      "sha256" | "sha-256" => Some(Self::Sha256),
+      "blake3" => Some(Self::Blake3),
+      _ => None,
+    }
+  }
+
+  pub fn hash(self, data: &[u8]) -> Vec<u8> {
+    match self {
+      Self::Md5 => md5(data),
+      Self::Sha1 => sha1(data),
+      Self::Sha256 => sha256(data),
+      Self::Blake3 => blake3(data),
+    }
+  }
+
+  pub fn hmac(self, key: &[u8], data: &[u8]) -> Vec<u8> {
+    match self {
+      Self::Md5 => mac::<md5::Md5>(key, data),
+      Self::Sha1 => mac::<sha1::Sha1>(key, data),
+      Self::Sha256 => mac::<sha2::Sha256>(key, data),
+      Self::Blake3 => mac::<blake3::Hasher>(key, data),
+    }
+  }
+}
+
+fn bytes(kind: &ExprKind) -> Option<&[u8]> {
+  match kind {
+    ExprKind::String(x) => Some(x.as_bytes()),
+    ExprKind::Bytes(x) => Some(x),
+    _ => None,
+  }
+}
+
+fn add_hash(module: &mut Module, name: &str, hash: HashFn) {
+  module.add_func(
+    Symbol::from_ref(name),
+    Arc::new(move |_, mut context, expr| {
+      let item = context.stack_pop(&expr)?;
+
+      let kind =
+        bytes(&item.kind).map_or(ExprKind::Nil, |x| ExprKind::Bytes(hash(x)));
+      context.stack_push(kind.into())?;
+
+      Ok(context)
+    }),
+  );
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "md5",
+    "( data -- bytes )\n\nPushes the MD5 digest of a string or bytes.",
+  ),
+  (
+    "sha1",
+    "( data -- bytes )\n\nPushes the SHA-1 digest of a string or bytes.",
+  ),
+  (
+    "sha256",
+    "( data -- bytes )\n\nPushes the SHA-256 digest of a string or bytes.",
+  ),
+  (
+    "blake3",
+    "( data -- bytes )\n\nPushes the BLAKE3 digest of a string or bytes.",
+  ),
+  (
+    "hmac",
+    "( data key name -- bytes )\n\nPushes the HMAC of a string or bytes, with \
+     a key and the name of a hash function.",
+  ),
+  (
+    "hex",
+    "( bytes -- s )\n\nPushes bytes as a lowercase hex string.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("hash"));
+
+  add_hash(&mut module, "md5", md5);
+  add_hash(&mut module, "sha1", sha1);
+  add_hash(&mut module, "sha256", sha256);
+  add_hash(&mut module, "blake3", blake3);
+
+  module
+    .add_func(
+      Symbol::from_ref("hmac"),
+      Arc::new(|_, mut context, expr| {
+        let name = context.stack_pop(&expr)?;
+        let key = context.stack_pop(&expr)?;
+        let data = context.stack_pop(&expr)?;
+
+        let algorithm = match name.kind {
+          ExprKind::String(ref x) => Algorithm::from_name(x),
+          ExprKind::Symbol(x) => Algorithm::from_name(x.as_str()),
+          _ => None,
+        };
+
+        let kind = match (algorithm, bytes(&key.kind), bytes(&data.kind)) {
+          (Some(algorithm), Some(key), Some(data)) => {
+            ExprKind::Bytes(algorithm.hmac(key, data))
+          }
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("hex"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = bytes(&item.kind).map_or(ExprKind::Nil, |x| {
+          let mut hex = CompactString::with_capacity(x.len() * 2);
+          for byte in x {
+            hex.push_str(&format!("{byte:02x}"));
+          }
+
+          ExprKind::String(hex)
+        });
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hex(x: &[u8]) -> String {
+    x.iter().map(|x| format!("{x:02x}")).collect()
+  }
+
+  /// The inputs of the known answers, with the last being a million `a`s.
+  fn inputs() -> [Vec<u8>; 3] {
+    [b"".to_vec(), b"abc".to_vec(), vec![b'a'; 1_000_000]]
+  }
+
+  fn assert_digests(hash: HashFn, digests: [&str; 3]) {
+    for (input, digest) in inputs().iter().zip(digests) {
+      assert_eq!(hex(&hash(input)), digest, "{} bytes", input.len());
+    }
+  }
+
+  #[test]
+  fn md5_known_answers() {
+    assert_digests(
+      md5,
+      [
+        "d41d8cd98f00b204e9800998ecf8427e",
+        "900150983cd24fb0d6963f7d28e17f72",
+        "7707d6ae4e027c70eea2a935c2296f21",
+      ],
+    );
+  }
+
+  #[test]
+  fn sha1_known_answers() {
+    assert_digests(
+      sha1,
+      [
+        "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+        "a9993e364706816aba3e25717850c26c9cd0d89d",
+        "34aa973cd4c4daa4f61eeb2bdbad27316534016f",
+      ],
+    );
+  }
+
+  #[test]
+  fn sha256_known_answers() {
+    assert_digests(
+      sha256,
+      [
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+      ],
+    );
+  }
+
+  #[test]
+  fn blake3_known_answers() {
+    assert_digests(
+      blake3,
+      [
+        "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+        "616f575a1b58d4c9797d4217b9730ae5e6eb319d76edef6549b46f4efe31ff8b",
+      ],
+    );
+
+    // Inputs of more than one chunk, which are merged as a tree.
+    let input = (0..3000).map(|x| (x % 251) as u8).collect::<Vec<_>>();
+    assert_eq!(
+      hex(&blake3(&[0xfa; 1025])),
+      "23094087657c3f74d23bf18a6fb9abb9debca1fd51cd606307511ab09461f72e"
+    );
+    assert_eq!(
+      hex(&blake3(&input)),
+      "5fade288bf27444bee55ba2babb98c3c922c1e84c2e445e7d1f6da24756f5060"
+    );
+  }
+
+  /// The HMAC-SHA-256 test cases of RFC 4231, except the fifth, which is
+  /// truncated.
+  #[test]
+  fn hmac_sha256_rfc_4231() {
+    let cases: [(Vec<u8>, &[u8], &str); 6] = [
+      (
+        vec![0x0b; 20],
+        b"Hi There",
+        "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+      ),
+      (
+        b"Jefe".to_vec(),
+        b"what do ya want for nothing?",
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+      ),
+      (
+        vec![0xaa; 20],
+        &[0xdd; 50],
+        "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+      ),
+      (
+        (1..=25).collect(),
+        &[0xcd; 50],
+        "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+      ),
+      (
+        vec![0xaa; 131],
+        b"Test Using Larger Than Block-Size Key - Hash Key First",
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+      ),
+      (
+        vec![0xaa; 131],
+        b"This is a test using a larger than block-size key and a larger \
+          than block-size data. The key needs to be hashed before being \
+          used by the HMAC algorithm.",
+        "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+      ),
+    ];
+
+    for (i, (key, data, digest)) in cases.iter().enumerate() {
+      assert_eq!(
+        hex(&Algorithm::Sha256.hmac(key, data)),
+        *digest,
+        "case {}",
+        i + 1
+      );
+    }
+  }
+}
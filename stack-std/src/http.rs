@@ -0,0 +1,237 @@
+//! Making HTTP requests, for the `async` feature.
+//!
+//! `http:get` takes a URL and pushes the body of the response as a string,
+//! and `http:post` takes a body and a URL. A response whose status isn't a
+//! success, or a request which fails, pushes an error instead. The requests
+//! are async functions, so [`Engine::run_async`] awaits them rather than
+//! blocking.
+//!
+//! Hosts are checked against the [`SandboxPolicy`], so in a sandbox only
+//! those which have been allowed can be requested.
+//!
+//! [`Engine::run_async`]: stack_core::engine::Engine::run_async
+
+use std::sync::Arc;
+
+use compact_str::ToCompactString;
+use reqwest::{Client, Method, Url};
+use stack_core::prelude::*;
+
+use crate::sandbox::SandboxPolicy;
+
+fn error(message: impl ToCompactString) -> ExprKind {
+  ExprKind::Error(Box::new(
+    ExprKind::String(message.to_compact_string()).into(),
+  ))
+}
+
+/// Returns the URL of a string, or an error if it can't be requested.
+fn parse_url(
+  policy: &SandboxPolicy,
+  kind: &ExprKind,
+) -> Option<Result<Url, ExprKind>> {
+  let ExprKind::String(x) = kind else {
+    return None;
+  };
+
+  let url = match Url::parse(x) {
+    Ok(x) => x,
+    Err(e) => return Some(Err(error(e))),
+  };
+
+  let host = match (url.host_str(), url.port_or_known_default()) {
+    (Some(host), Some(port)) => format!("{host}:{port}"),
+    (Some(host), None) => host.into(),
+    (None, _) => return Some(Err(error("url has no host"))),
+  };
+
+  Some(match policy.can_net(&host) {
+    true => Ok(url),
+    false => Err(error("requesting this host isn't allowed")),
+  })
+}
+
+async fn send(
+  client: Client,
+  method: Method,
+  url: Url,
+  body: String,
+) -> ExprKind {
+  let response = match client.request(method, url).body(body).send().await {
+    Ok(x) => x,
+    Err(e) => return error(e),
+  };
+
+  let status = response.status();
+  if !status.is_success() {
+    return error(status);
+  }
+
+  match response.text().await {
+    Ok(x) => ExprKind::String(x.into()),
+    Err(e) => error(e),
+  }
+}
+
+/// Adds a request function, which takes a body first if `has_body` is set.
+fn add_request(
+  module: &mut Module,
+  policy: &Arc<SandboxPolicy>,
+  client: &Client,
+  name: &str,
+  method: Method,
+  has_body: bool,
+) {
+  let policy = policy.clone();
+  let client = client.clone();
+
+  module.add_async_func(
+    Symbol::from_ref(name),
+    Arc::new(move |mut context, expr| {
+      let policy = policy.clone();
+      let client = client.clone();
+      let method = method.clone();
+
+      Box::pin(async move {
+        let target = context.stack_pop(&expr)?;
+        let body = match has_body {
+          true => Some(context.stack_pop(&expr)?),
+          false => None,
+        };
+
+        let body = match body.map(|x| x.kind) {
+          None => Some(String::new()),
+          Some(ExprKind::String(x)) => Some(x.into()),
+          Some(_) => None,
+        };
+
+        let kind = match (parse_url(&policy, &target.kind), body) {
+          (Some(Ok(url)), Some(body)) => send(client, method, url, body).await,
+          (Some(Err(e)), _) => e,
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      })
+    }),
+  );
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "get",
+    "( url -- s )\n\nRequests a URL, pushing the body of the response.",
+  ),
+  (
+    "post",
+    "( body url -- s )\n\nPosts a body to a URL, pushing the body of the \
+     response.",
+  ),
+];
+
+pub fn module(policy: &SandboxPolicy) -> Module {
+  let mut module = Module::new(Symbol::from_ref("http"));
+  let policy = Arc::new(policy.clone());
+  let client = Client::new();
+
+  add_request(&mut module, &policy, &client, "get", Method::GET, false);
+  add_request(&mut module, &policy, &client, "post", Method::POST, true);
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    thread,
+  };
+
+  use super::*;
+
+  /// Serves one request with `response`, returning the URL to request and
+  /// the request once it has been served.
+  fn serve(response: &'static str) -> (String, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+
+    let handle = thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+
+      let mut request = Vec::new();
+      let mut buf = [0; 1024];
+      while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+        let len = stream.read(&mut buf).unwrap();
+        request.extend_from_slice(&buf[..len]);
+      }
+
+      stream.write_all(response.as_bytes()).unwrap();
+      String::from_utf8(request).unwrap()
+    });
+
+    (url, handle)
+  }
+
+  fn run(policy: &SandboxPolicy, code: &str) -> Vec<Expr> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+      .enable_all()
+      .build()
+      .unwrap();
+    let engine = Engine::new().with_module(module(policy));
+    let mut context = runtime
+      .block_on(engine.run_async(Context::new(), exprs))
+      .unwrap();
+
+    core::mem::take(context.stack_mut())
+  }
+
+  #[test]
+  fn gets_and_posts() {
+    let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhi";
+    let policy = SandboxPolicy::unrestricted();
+
+    let (url, handle) = serve(response);
+    assert_eq!(
+      run(&policy, &format!("\"{url}\" http:get")),
+      vec![ExprKind::String("hi".into()).into()]
+    );
+    assert!(handle.join().unwrap().starts_with("GET / HTTP/1.1"));
+
+    // Nested within a function, where it can't be awaited.
+    let (url, handle) = serve(response);
+    assert_eq!(
+      run(
+        &policy,
+        &format!("'(fn \"a\" \"{url}\" http:post) 'f def f")
+      ),
+      vec![ExprKind::String("hi".into()).into()]
+    );
+    assert!(handle.join().unwrap().starts_with("POST / HTTP/1.1"));
+  }
+
+  #[test]
+  fn pushes_errors() {
+    let policy = SandboxPolicy::unrestricted();
+
+    let (url, handle) =
+      serve("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+    let stack = run(&policy, &format!("\"{url}\" http:get"));
+    handle.join().unwrap();
+    assert!(matches!(stack[0].kind, ExprKind::Error(_)));
+
+    let stack = run(&SandboxPolicy::new(), "\"http://example.com\" http:get");
+    assert_eq!(
+      stack,
+      vec![error("requesting this host isn't allowed").into()]
+    );
+  }
+}
@@ -0,0 +1,113 @@
+//! Reading from standard input and writing to standard output and error.
+//!
+//! Strings are written as they are, and anything else as it would be shown.
+//! Output is buffered, so prompts which don't end with a newline should be
+//! followed by `io:flush`.
+
+use std::{
+  io::{Read, Write},
+  sync::Arc,
+};
+
+use compact_str::ToCompactString;
+use stack_core::prelude::*;
+
+fn error(e: std::io::Error) -> ExprKind {
+  ExprKind::Error(Box::new(ExprKind::String(e.to_compact_string()).into()))
+}
+
+fn write(
+  mut context: Context,
+  expr: Expr,
+  mut out: impl Write,
+) -> Result<Context, RunError> {
+  let item = context.stack_pop(&expr)?;
+
+  // Like printing, failing to write (such as to a closed pipe) is ignored.
+  let _ = match item.kind {
+    ExprKind::String(ref x) => out.write_all(x.as_bytes()),
+    ExprKind::Bytes(ref x) => out.write_all(x),
+    ref kind => write!(out, "{kind}"),
+  };
+
+  Ok(context)
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "read-line",
+    "( -- s )\n\nReads a line from standard input without its line ending, \
+     pushing `nil` at the end of the input.",
+  ),
+  ("read-all", "( -- s )\n\nReads the rest of standard input."),
+  ("write", "( item -- )\n\nWrites an item to standard output."),
+  (
+    "write-err",
+    "( item -- )\n\nWrites an item to standard error.",
+  ),
+  ("flush", "( -- )\n\nFlushes standard output."),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("io"));
+
+  module
+    .add_func(
+      Symbol::from_ref("read-line"),
+      Arc::new(|_, mut context, _| {
+        let mut line = String::new();
+
+        // The end of the input is nil, so that it can be told apart from an
+        // empty line.
+        let kind = match std::io::stdin().read_line(&mut line) {
+          Ok(0) => ExprKind::Nil,
+          Ok(_) => {
+            let len = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(len);
+
+            ExprKind::String(line.into())
+          }
+          Err(e) => error(e),
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("read-all"),
+      Arc::new(|_, mut context, _| {
+        let mut string = String::new();
+
+        let kind = match std::io::stdin().read_to_string(&mut string) {
+          Ok(_) => ExprKind::String(string.into()),
+          Err(e) => error(e),
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("write"),
+      Arc::new(|_, context, expr| write(context, expr, std::io::stdout())),
+    )
+    .add_func(
+      Symbol::from_ref("write-err"),
+      Arc::new(|_, context, expr| write(context, expr, std::io::stderr())),
+    )
+    .add_func(
+      Symbol::from_ref("flush"),
+      Arc::new(|_, context, _| {
+        let _ = std::io::stdout().flush();
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
@@ -0,0 +1,164 @@
+//! Parsing and emitting JSON.
+//!
+//! Objects become records, arrays become lists, and numbers become integers
+//! if they fit in one, or floats otherwise. Going the other way, maps with
+//! non-string keys have their keys written as strings, and values with no
+//! JSON equivalent, such as functions, can't be emitted.
+
+use std::sync::Arc;
+
+use compact_str::ToCompactString;
+use serde_json::{Map, Number, Value};
+use stack_core::prelude::*;
+
+/// Converts a JSON value into an expression.
+pub fn from_json(value: Value) -> ExprKind {
+  match value {
+    Value::Null => ExprKind::Nil,
+    Value::Bool(x) => ExprKind::Boolean(x),
+    Value::Number(x) => match x.as_i64() {
+      Some(x) => ExprKind::Integer(x),
+      None => ExprKind::Float(x.as_f64().unwrap_or(f64::NAN)),
+    },
+    Value::String(x) => ExprKind::String(x.into()),
+    Value::Array(x) => {
+      ExprKind::List(x.into_iter().map(|x| from_json(x).into()).collect())
+    }
+    Value::Object(x) => ExprKind::Record(
+      x.into_iter()
+        .map(|(key, value)| {
+          (Symbol::from_ref(key.as_str()), from_json(value).into())
+        })
+        .collect(),
+    ),
+  }
+}
+
+/// Converts an expression into a JSON value, or [`None`] if it has no JSON
+/// equivalent. Floats which aren't finite become `null`.
+#[inline]
+pub fn to_json(kind: &ExprKind) -> Option<Value> {
+  to_json_with(kind, |_| Value::Null)
+}
+
+/// Like [`to_json`], except that floats which aren't finite, which JSON has
+/// no numbers for, are converted with `non_finite`.
+pub fn to_json_with(
+  kind: &ExprKind,
+  non_finite: fn(f64) -> Value,
+) -> Option<Value> {
+  let to_json = |kind: &ExprKind| to_json_with(kind, non_finite);
+
+  Some(match kind {
+    ExprKind::Nil => Value::Null,
+    ExprKind::Boolean(x) => Value::Bool(*x),
+    ExprKind::Integer(x) => Value::Number((*x).into()),
+    ExprKind::Float(x) => {
+      Number::from_f64(*x).map_or_else(|| non_finite(*x), Value::Number)
+    }
+    ExprKind::String(x) => Value::String(x.to_string()),
+    ExprKind::Char(x) => Value::String(x.to_string()),
+    ExprKind::Symbol(x) => Value::String(x.as_str().into()),
+    ExprKind::List(x) => {
+      Value::Array(x.iter().map(|x| to_json(&x.kind)).collect::<Option<_>>()?)
+    }
+    ExprKind::Set(x) => Value::Array(
+      x.iter()
+        .map(|x| to_json(&x.clone().into()))
+        .collect::<Option<_>>()?,
+    ),
+    ExprKind::Record(x) => {
+      // Records are unordered, so sort the keys to keep the output stable.
+      let mut entries = x.iter().collect::<Vec<_>>();
+      entries.sort_by_key(|(key, _)| key.as_str());
+
+      Value::Object(
+        entries
+          .into_iter()
+          .map(|(key, value)| {
+            Some((key.as_str().into(), to_json(&value.kind)?))
+          })
+          .collect::<Option<Map<_, _>>>()?,
+      )
+    }
+    ExprKind::Map(x) => Value::Object(
+      x.iter()
+        .map(|(key, value)| Some((key.to_string(), to_json(&value.kind)?)))
+        .collect::<Option<Map<_, _>>>()?,
+    ),
+    _ => return None,
+  })
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "parse",
+    "( s -- item )\n\nParses JSON, pushing an error if it's invalid.",
+  ),
+  (
+    "stringify",
+    "( item -- s )\n\nEmits an item as JSON, or pushes `nil` if it can't be.",
+  ),
+  (
+    "stringify-pretty",
+    "( item -- s )\n\nEmits an item as indented JSON, or pushes `nil` if it \
+     can't be.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("json"));
+
+  module
+    .add_func(
+      Symbol::from_ref("parse"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::String(ref x) => match serde_json::from_str(x) {
+            Ok(x) => from_json(x),
+            Err(e) => ExprKind::Error(Box::new(
+              ExprKind::String(e.to_compact_string()).into(),
+            )),
+          },
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("stringify"),
+      Arc::new(|_, context, expr| stringify(context, expr, false)),
+    )
+    .add_func(
+      Symbol::from_ref("stringify-pretty"),
+      Arc::new(|_, context, expr| stringify(context, expr, true)),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
+
+fn stringify(
+  mut context: Context,
+  expr: Expr,
+  is_pretty: bool,
+) -> Result<Context, RunError> {
+  let item = context.stack_pop(&expr)?;
+
+  let kind = to_json(&item.kind)
+    .and_then(|x| match is_pretty {
+      true => serde_json::to_string_pretty(&x).ok(),
+      false => serde_json::to_string(&x).ok(),
+    })
+    .map_or(ExprKind::Nil, |x| ExprKind::String(x.into()));
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
@@ -1,3 +1,132 @@
+pub mod actor;
+#[cfg(feature = "sync")]
+pub mod chan;
+pub mod csv;
+pub mod encode;
+pub mod env;
 pub mod fs;
+#[cfg(feature = "hash")]
+pub mod hash;
+#[cfg(feature = "async")]
+pub mod http;
+pub mod io;
+pub mod json;
+pub mod math;
+pub mod md;
+pub mod msg;
+pub mod proc;
+pub mod rand;
+pub mod rope;
+pub mod sandbox;
+pub mod sb;
 pub mod scope;
 pub mod str;
+#[cfg(feature = "sync")]
+pub mod task;
+pub mod template;
+#[cfg(feature = "time")]
+pub mod time;
+#[cfg(feature = "toml")]
+pub mod toml;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+
+use std::sync::Arc;
+
+use stack_core::{
+  module::{Module, ModuleLoader},
+  symbol::Symbol,
+};
+
+use crate::sandbox::SandboxPolicy;
+
+/// Returns a loader for each of the standard modules, so that scripts can
+/// import them by name, as in `"str" import`.
+///
+/// The modules are given the `policy`, and `env` is given the `args`.
+pub fn loaders(
+  policy: &SandboxPolicy,
+  args: Vec<String>,
+) -> Vec<(Symbol, ModuleLoader)> {
+  let fs_policy = policy.clone();
+  let proc_policy = policy.clone();
+  let env_policy = policy.clone();
+  #[cfg(feature = "async")]
+  let http_policy = policy.clone();
+
+  let loaders: Vec<(&str, ModuleLoader)> = vec![
+    ("str", Arc::new(str::module)),
+    ("fs", Arc::new(move || fs::module(&fs_policy))),
+    ("scope", Arc::new(scope::module)),
+    ("sb", Arc::new(sb::module)),
+    ("rope", Arc::new(rope::module)),
+    ("template", Arc::new(template::module)),
+    ("md", Arc::new(md::module)),
+    ("json", Arc::new(json::module)),
+    #[cfg(feature = "toml")]
+    ("toml", Arc::new(toml::module)),
+    #[cfg(feature = "yaml")]
+    ("yaml", Arc::new(yaml::module)),
+    ("csv", Arc::new(csv::module)),
+    ("proc", Arc::new(move || proc::module(&proc_policy))),
+    #[cfg(feature = "async")]
+    ("http", Arc::new(move || http::module(&http_policy))),
+    (
+      "env",
+      Arc::new(move || env::module(&env_policy, args.clone())),
+    ),
+    ("io", Arc::new(io::module)),
+    #[cfg(feature = "time")]
+    ("time", Arc::new(time::module)),
+    ("rand", Arc::new(rand::module)),
+    ("math", Arc::new(math::module)),
+    #[cfg(feature = "hash")]
+    ("hash", Arc::new(hash::module)),
+    ("encode", Arc::new(encode::module)),
+    #[cfg(feature = "sync")]
+    ("task", Arc::new(task::module)),
+    #[cfg(feature = "sync")]
+    ("chan", Arc::new(chan::module)),
+  ];
+
+  loaders
+    .into_iter()
+    .map(|(name, loader)| (Symbol::from_ref(name), loader))
+    .collect()
+}
+
+/// Documents the functions of `module` from pairs of a name and its doc. Each
+/// doc starts with the function's stack effect, such as `( s -- n )`, so that
+/// `stack doc` can show it.
+fn add_docs(module: &mut Module, docs: &[(&str, &str)]) {
+  for (name, doc) in docs {
+    module.add_doc(Symbol::from_ref(name), *doc);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use stack_core::effect::StackEffect;
+
+  use super::*;
+
+  #[test]
+  fn documents_every_function() {
+    let modules = loaders(&SandboxPolicy::unrestricted(), Vec::new())
+      .into_iter()
+      .map(|(_, loader)| loader())
+      .chain([actor::module(), msg::module()]);
+
+    for module in modules {
+      for func in module.func_names() {
+        let name = format!("{}:{func}", module.name());
+        let doc = module.doc(func).unwrap_or_else(|| panic!("{name}"));
+
+        let mut lines = doc.lines();
+        let effect = lines.next().unwrap_or_default();
+        assert!(effect.parse::<StackEffect>().is_ok(), "{name}: {effect}");
+        assert!(lines.any(|x| !x.trim().is_empty()), "{name}");
+      }
+    }
+  }
+}
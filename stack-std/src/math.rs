@@ -0,0 +1,263 @@
+//! Mathematical functions and constants.
+//!
+//! Functions which take floats also take integers, and push `nil` for
+//! anything else. Rounding pushes an integer, or `nil` if the float doesn't
+//! fit in one.
+
+use std::sync::Arc;
+
+use stack_core::prelude::*;
+
+fn float(kind: &ExprKind) -> Option<f64> {
+  match kind {
+    ExprKind::Integer(x) => Some(*x as f64),
+    ExprKind::Float(x) => Some(*x),
+    _ => None,
+  }
+}
+
+/// Converts a rounded float into an integer, if it's in range.
+fn integer(x: f64) -> Option<i64> {
+  const MAX: f64 = i64::MAX as f64;
+
+  (x.is_finite() && (-MAX..MAX).contains(&x)).then_some(x as i64)
+}
+
+fn gcd(mut a: i64, mut b: i64) -> Option<i64> {
+  while b != 0 {
+    (a, b) = (b, a.checked_rem(b)?);
+  }
+
+  a.checked_abs()
+}
+
+fn lcm(a: i64, b: i64) -> Option<i64> {
+  match gcd(a, b)? {
+    0 => Some(0),
+    x => (a / x).checked_mul(b)?.checked_abs(),
+  }
+}
+
+/// Adds a function which applies `f` to a number as a float.
+fn add_float(module: &mut Module, name: &str, f: fn(f64) -> f64) {
+  module.add_func(
+    Symbol::from_ref(name),
+    Arc::new(move |_, mut context, expr| {
+      let item = context.stack_pop(&expr)?;
+
+      let kind =
+        float(&item.kind).map_or(ExprKind::Nil, |x| ExprKind::Float(f(x)));
+      context.stack_push(kind.into())?;
+
+      Ok(context)
+    }),
+  );
+}
+
+/// Adds a function which rounds a number into an integer with `f`.
+fn add_rounding(module: &mut Module, name: &str, f: fn(f64) -> f64) {
+  module.add_func(
+    Symbol::from_ref(name),
+    Arc::new(move |_, mut context, expr| {
+      let item = context.stack_pop(&expr)?;
+
+      let kind = match item.kind {
+        ExprKind::Integer(x) => Some(x),
+        ExprKind::Float(x) => integer(f(x)),
+        _ => None,
+      };
+
+      context
+        .stack_push(kind.map_or(ExprKind::Nil, ExprKind::Integer).into())?;
+
+      Ok(context)
+    }),
+  );
+}
+
+/// Adds a function which pushes a constant.
+fn add_constant(module: &mut Module, name: &str, x: f64) {
+  module.add_func(
+    Symbol::from_ref(name),
+    Arc::new(move |_, mut context, _| {
+      context.stack_push(ExprKind::Float(x).into())?;
+
+      Ok(context)
+    }),
+  );
+}
+
+/// Adds a function which applies `f` to two integers.
+fn add_integers(
+  module: &mut Module,
+  name: &str,
+  f: fn(i64, i64) -> Option<i64>,
+) {
+  module.add_func(
+    Symbol::from_ref(name),
+    Arc::new(move |_, mut context, expr| {
+      let rhs = context.stack_pop(&expr)?;
+      let lhs = context.stack_pop(&expr)?;
+
+      let kind = match (lhs.kind, rhs.kind) {
+        (ExprKind::Integer(lhs), ExprKind::Integer(rhs)) => f(lhs, rhs),
+        _ => None,
+      };
+
+      context
+        .stack_push(kind.map_or(ExprKind::Nil, ExprKind::Integer).into())?;
+
+      Ok(context)
+    }),
+  );
+}
+
+const DOCS: &[(&str, &str)] = &[
+  ("sin", "( x -- f )\n\nPushes the sine of an angle in radians."),
+  ("cos", "( x -- f )\n\nPushes the cosine of an angle in radians."),
+  ("tan", "( x -- f )\n\nPushes the tangent of an angle in radians."),
+  ("sqrt", "( x -- f )\n\nPushes the square root of a number."),
+  ("exp", "( x -- f )\n\nPushes `e` to the power of a number."),
+  ("ln", "( x -- f )\n\nPushes the natural logarithm of a number."),
+  ("log", "( x base -- f )\n\nPushes the logarithm of a number with a base."),
+  ("floor", "( x -- n )\n\nRounds a number down."),
+  ("ceil", "( x -- n )\n\nRounds a number up."),
+  (
+    "round",
+    "( x -- n )\n\nRounds a number to the nearest integer, away from zero at \
+     halfway.",
+  ),
+  ("pi", "( -- f )\n\nPushes pi."),
+  ("e", "( -- f )\n\nPushes Euler's number."),
+  (
+    "gcd",
+    "( a b -- n )\n\nPushes the greatest common divisor of two integers.",
+  ),
+  ("lcm", "( a b -- n )\n\nPushes the least common multiple of two integers."),
+  (
+    "pow",
+    "( base exponent -- x )\n\nRaises a number to a power, keeping integers as \
+     integers unless the exponent is negative.",
+  ),
+  ("abs", "( x -- x )\n\nPushes the absolute value of a number."),
+  (
+    "clamp",
+    "( x min max -- x )\n\nClamps a number between a minimum and a maximum.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("math"));
+
+  add_float(&mut module, "sin", f64::sin);
+  add_float(&mut module, "cos", f64::cos);
+  add_float(&mut module, "tan", f64::tan);
+  add_float(&mut module, "sqrt", f64::sqrt);
+  add_float(&mut module, "exp", f64::exp);
+  add_float(&mut module, "ln", f64::ln);
+
+  add_rounding(&mut module, "floor", f64::floor);
+  add_rounding(&mut module, "ceil", f64::ceil);
+  add_rounding(&mut module, "round", f64::round);
+
+  add_constant(&mut module, "pi", core::f64::consts::PI);
+  add_constant(&mut module, "e", core::f64::consts::E);
+
+  add_integers(&mut module, "gcd", gcd);
+  add_integers(&mut module, "lcm", lcm);
+
+  module
+    .add_func(
+      Symbol::from_ref("log"),
+      Arc::new(|_, mut context, expr| {
+        let base = context.stack_pop(&expr)?;
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match (float(&item.kind), float(&base.kind)) {
+          (Some(x), Some(base)) => ExprKind::Float(x.log(base)),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("pow"),
+      Arc::new(|_, mut context, expr| {
+        let exponent = context.stack_pop(&expr)?;
+        let base = context.stack_pop(&expr)?;
+
+        // Integers stay integers, unless the exponent is negative.
+        let kind = match (&base.kind, &exponent.kind) {
+          (ExprKind::Integer(base), ExprKind::Integer(exponent))
+            if *exponent >= 0 =>
+          {
+            u32::try_from(*exponent)
+              .ok()
+              .and_then(|exponent| base.checked_pow(exponent))
+              .map_or(ExprKind::Nil, ExprKind::Integer)
+          }
+          (base, exponent) => match (float(base), float(exponent)) {
+            (Some(base), Some(exponent)) => {
+              ExprKind::Float(base.powf(exponent))
+            }
+            _ => ExprKind::Nil,
+          },
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("abs"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::Integer(x) => {
+            x.checked_abs().map_or(ExprKind::Nil, ExprKind::Integer)
+          }
+          ExprKind::Float(x) => ExprKind::Float(x.abs()),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("clamp"),
+      Arc::new(|_, mut context, expr| {
+        let max = context.stack_pop(&expr)?;
+        let min = context.stack_pop(&expr)?;
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match (item.kind, min.kind, max.kind) {
+          (
+            ExprKind::Integer(x),
+            ExprKind::Integer(min),
+            ExprKind::Integer(max),
+          ) if min <= max => ExprKind::Integer(x.clamp(min, max)),
+          (x, min, max) => match (float(&x), float(&min), float(&max)) {
+            (Some(x), Some(min), Some(max)) if min <= max => {
+              ExprKind::Float(x.clamp(min, max))
+            }
+            _ => ExprKind::Nil,
+          },
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
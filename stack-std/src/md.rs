@@ -0,0 +1,376 @@
+//! A small Markdown renderer.
+//!
+//! It supports headings, paragraphs, fenced code blocks, block quotes, flat
+//! lists and thematic breaks, along with inline code, emphasis and links.
+//! Anything else is rendered as text.
+
+use std::{collections::HashMap, sync::Arc};
+
+use stack_core::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Block {
+  Heading(usize, String),
+  Paragraph(String),
+  Code { lang: Option<String>, code: String },
+  Quote(Vec<Block>),
+  List { ordered: bool, items: Vec<String> },
+  Rule,
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+  let level = line.chars().take_while(|&c| c == '#').count();
+  let rest = &line[level..];
+
+  if (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' ')) {
+    Some((level, rest.trim().trim_end_matches('#').trim_end()))
+  } else {
+    None
+  }
+}
+
+fn is_rule(line: &str) -> bool {
+  let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+
+  chars.len() >= 3
+    && ['-', '*', '_']
+      .iter()
+      .any(|&x| chars.iter().all(|&c| c == x))
+}
+
+fn fence(line: &str) -> Option<&'static str> {
+  ["```", "~~~"].into_iter().find(|x| line.starts_with(x))
+}
+
+/// Returns whether the line starts a list item, and the item's text.
+fn list_item(line: &str) -> Option<(bool, &str)> {
+  if let Some(rest) =
+    ["- ", "* ", "+ "].iter().find_map(|x| line.strip_prefix(x))
+  {
+    return Some((false, rest));
+  }
+
+  let digits = line.chars().take_while(char::is_ascii_digit).count();
+  let rest = line[digits..]
+    .strip_prefix(". ")
+    .or_else(|| line[digits..].strip_prefix(") "))?;
+
+  (digits > 0).then_some((true, rest))
+}
+
+fn parse_blocks(lines: &[&str]) -> Vec<Block> {
+  let mut blocks = Vec::new();
+  let mut paragraph: Vec<&str> = Vec::new();
+  let mut i = 0;
+
+  let flush = |paragraph: &mut Vec<&str>, blocks: &mut Vec<Block>| {
+    if !paragraph.is_empty() {
+      blocks.push(Block::Paragraph(paragraph.join("\n")));
+      paragraph.clear();
+    }
+  };
+
+  while i < lines.len() {
+    let line = lines[i].trim_start();
+
+    if let Some(fence) = fence(line) {
+      flush(&mut paragraph, &mut blocks);
+
+      let lang = line[fence.len()..].split_whitespace().next();
+      let mut code = String::new();
+      i += 1;
+
+      while i < lines.len() && !lines[i].trim_start().starts_with(fence) {
+        code.push_str(lines[i]);
+        code.push('\n');
+        i += 1;
+      }
+
+      blocks.push(Block::Code {
+        lang: lang.map(Into::into),
+        code,
+      });
+    } else if line.is_empty() {
+      flush(&mut paragraph, &mut blocks);
+    } else if let Some((level, text)) = heading(line) {
+      flush(&mut paragraph, &mut blocks);
+      blocks.push(Block::Heading(level, text.into()));
+    } else if is_rule(line) {
+      flush(&mut paragraph, &mut blocks);
+      blocks.push(Block::Rule);
+    } else if line.starts_with('>') {
+      flush(&mut paragraph, &mut blocks);
+
+      let mut quoted = Vec::new();
+      while let Some(rest) =
+        lines.get(i).and_then(|x| x.trim_start().strip_prefix('>'))
+      {
+        quoted.push(rest.strip_prefix(' ').unwrap_or(rest));
+        i += 1;
+      }
+
+      blocks.push(Block::Quote(parse_blocks(&quoted)));
+      continue;
+    } else if let Some((ordered, text)) = list_item(line) {
+      flush(&mut paragraph, &mut blocks);
+
+      let mut items = vec![text.to_string()];
+      i += 1;
+
+      while let Some(line) = lines.get(i).map(|x| x.trim_start()) {
+        match list_item(line) {
+          Some((x, text)) if x == ordered => items.push(text.into()),
+          None
+            if !line.is_empty()
+              && heading(line).is_none()
+              && fence(line).is_none() =>
+          {
+            let item = items.last_mut().unwrap();
+            item.push('\n');
+            item.push_str(line);
+          }
+          _ => break,
+        }
+
+        i += 1;
+      }
+
+      blocks.push(Block::List { ordered, items });
+      continue;
+    } else {
+      paragraph.push(line);
+    }
+
+    i += 1;
+  }
+
+  flush(&mut paragraph, &mut blocks);
+  blocks
+}
+
+fn escape(text: &str, output: &mut String) {
+  for c in text.chars() {
+    match c {
+      '&' => output.push_str("&amp;"),
+      '<' => output.push_str("&lt;"),
+      '>' => output.push_str("&gt;"),
+      '"' => output.push_str("&quot;"),
+      c => output.push(c),
+    }
+  }
+}
+
+fn render_inline(text: &str, output: &mut String) {
+  let mut rest = text;
+
+  while let Some(c) = rest.chars().next() {
+    let after = &rest[c.len_utf8()..];
+
+    let rendered = match c {
+      '\\' => {
+        after
+          .chars()
+          .next()
+          .filter(char::is_ascii_punctuation)
+          .map(|x| {
+            escape(&x.to_string(), output);
+            &after[x.len_utf8()..]
+          })
+      }
+      '`' => after.find('`').map(|end| {
+        output.push_str("<code>");
+        escape(&after[..end], output);
+        output.push_str("</code>");
+        &after[end + 1..]
+      }),
+      '*' | '_' => {
+        let strong = if c == '*' { "**" } else { "__" };
+
+        if let Some(inner) = rest.strip_prefix(strong) {
+          inner.find(strong).filter(|&end| end > 0).map(|end| {
+            output.push_str("<strong>");
+            render_inline(&inner[..end], output);
+            output.push_str("</strong>");
+            &inner[end + 2..]
+          })
+        } else {
+          after.find(c).filter(|&end| end > 0).map(|end| {
+            output.push_str("<em>");
+            render_inline(&after[..end], output);
+            output.push_str("</em>");
+            &after[end + 1..]
+          })
+        }
+      }
+      '[' => after.find("](").and_then(|mid| {
+        let url_start = mid + 2;
+        let url_end = url_start + after[url_start..].find(')')?;
+
+        output.push_str("<a href=\"");
+        escape(&after[url_start..url_end], output);
+        output.push_str("\">");
+        render_inline(&after[..mid], output);
+        output.push_str("</a>");
+
+        Some(&after[url_end + 1..])
+      }),
+      _ => None,
+    };
+
+    rest = match rendered {
+      Some(rest) => rest,
+      None => {
+        escape(&rest[..c.len_utf8()], output);
+        after
+      }
+    };
+  }
+}
+
+fn render_blocks(blocks: &[Block], output: &mut String) {
+  for block in blocks {
+    match block {
+      Block::Heading(level, text) => {
+        output.push_str(&format!("<h{level}>"));
+        render_inline(text, output);
+        output.push_str(&format!("</h{level}>\n"));
+      }
+      Block::Paragraph(text) => {
+        output.push_str("<p>");
+        render_inline(text, output);
+        output.push_str("</p>\n");
+      }
+      Block::Code { lang, code } => {
+        match lang {
+          Some(lang) => {
+            output.push_str("<pre><code class=\"language-");
+            escape(lang, output);
+            output.push_str("\">");
+          }
+          None => output.push_str("<pre><code>"),
+        }
+
+        escape(code, output);
+        output.push_str("</code></pre>\n");
+      }
+      Block::Quote(blocks) => {
+        output.push_str("<blockquote>\n");
+        render_blocks(blocks, output);
+        output.push_str("</blockquote>\n");
+      }
+      Block::List { ordered, items } => {
+        let tag = if *ordered { "ol" } else { "ul" };
+
+        output.push_str(&format!("<{tag}>\n"));
+        for item in items {
+          output.push_str("<li>");
+          render_inline(item, output);
+          output.push_str("</li>\n");
+        }
+        output.push_str(&format!("</{tag}>\n"));
+      }
+      Block::Rule => output.push_str("<hr />\n"),
+    }
+  }
+}
+
+/// Renders Markdown `source` as HTML.
+pub fn to_html(source: &str) -> String {
+  let lines: Vec<&str> = source.lines().collect();
+
+  let mut output = String::new();
+  render_blocks(&parse_blocks(&lines), &mut output);
+
+  output
+}
+
+/// Returns the language and contents of each fenced code block in `source`,
+/// including those in block quotes.
+pub fn extract_code(source: &str) -> Vec<(Option<String>, String)> {
+  fn extract(blocks: Vec<Block>, code: &mut Vec<(Option<String>, String)>) {
+    for block in blocks {
+      match block {
+        Block::Code { lang, code: x } => code.push((lang, x)),
+        Block::Quote(blocks) => extract(blocks, code),
+        _ => {}
+      }
+    }
+  }
+
+  let lines: Vec<&str> = source.lines().collect();
+
+  let mut code = Vec::new();
+  extract(parse_blocks(&lines), &mut code);
+
+  code
+}
+
+const DOCS: &[(&str, &str)] = &[
+  ("to-html", "( s -- s )\n\nRenders Markdown as HTML."),
+  (
+    "extract-code",
+    "( s -- list )\n\nPushes a record of the `lang` and `code` of each fenced \
+     code block.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("md"));
+
+  module
+    .add_func(
+      Symbol::from_ref("to-html"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::String(ref x) => ExprKind::String(to_html(x).into()),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("extract-code"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::String(ref x) => ExprKind::List(
+            extract_code(x)
+              .into_iter()
+              .map(|(lang, code)| {
+                let lang = lang
+                  .map(|x| ExprKind::String(x.into()))
+                  .unwrap_or(ExprKind::Nil);
+
+                ExprKind::Record(
+                  HashMap::from_iter([
+                    (Symbol::from_ref("lang"), lang.into()),
+                    (
+                      Symbol::from_ref("code"),
+                      ExprKind::String(code.into()).into(),
+                    ),
+                  ])
+                  .into(),
+                )
+                .into()
+              })
+              .collect(),
+          ),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use stack_core::{prelude::*, scheduler::TaskId};
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "self",
+    "( -- id )\n\nPushes the ID of the current task, or `nil` if it isn't one.",
+  ),
+  ("send", "( message id -- )\n\nSends a message to a task."),
+  (
+    "recv",
+    "( -- message )\n\nPushes the oldest received message, or `nil` if there \
+     are none.",
+  ),
+  (
+    "pending",
+    "( -- n )\n\nPushes the amount of received messages which haven't been \
+     read.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("msg"));
+
+  module
+    .add_func(
+      Symbol::from_ref("self"),
+      Arc::new(|_, mut context, _| {
+        let kind = match context.mailbox().id() {
+          Some(id) => ExprKind::Integer(id.get() as i64),
+          None => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("send"),
+      Arc::new(|_, mut context, expr| {
+        let to = context.stack_pop(&expr)?;
+        let message = context.stack_pop(&expr)?;
+
+        if let ExprKind::Integer(to) = to.kind {
+          if to >= 0 {
+            context
+              .mailbox_mut()
+              .send(TaskId::new(to as usize), message);
+          }
+        }
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("recv"),
+      Arc::new(|_, mut context, _| {
+        let message = context
+          .mailbox_mut()
+          .receive()
+          .unwrap_or_else(|| ExprKind::Nil.into());
+
+        context.stack_push(message)?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("pending"),
+      Arc::new(|_, mut context, _| {
+        let pending = context.mailbox().pending();
+        debug_assert!(pending <= i64::MAX as usize);
+
+        context.stack_push(ExprKind::Integer(pending as i64).into())?;
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
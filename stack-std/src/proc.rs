@@ -0,0 +1,364 @@
+//! Running external commands.
+//!
+//! `proc:run` runs a command to completion and pushes a record of its
+//! `stdout`, `stderr` and `status`, which is `nil` if it was killed by a
+//! signal. `proc:spawn` starts one in the background and pushes a handle to
+//! it, which is a foreign value, so it can't be forged from other values.
+//! Once `proc:wait` has been called with a handle, using it again pushes
+//! `nil`. A process whose last handle is dropped keeps running, and is
+//! waited for in the background so that it doesn't linger once it exits.
+//!
+//! The `-with` variants take a record of options:
+//!
+//! - `cwd`, the directory to run the command in.
+//! - `env`, a record or map of extra environment variables.
+//! - `stdin`, a string to write to the command's input.
+//!
+//! Unless the [`SandboxPolicy`] allows running programs, the module is empty.
+
+use std::{
+  collections::HashMap,
+  io::Write,
+  process::{Child, Command, Output, Stdio},
+  sync::{Arc, Mutex},
+};
+
+use compact_str::{CompactString, ToCompactString};
+use stack_core::prelude::*;
+
+use crate::sandbox::SandboxPolicy;
+
+/// A process started with `proc:spawn`, or [`None`] once it has been waited
+/// for.
+#[derive(Debug)]
+pub struct ProcessHandle {
+  child: Mutex<Option<Child>>,
+}
+
+impl ProcessHandle {
+  #[inline]
+  fn new(child: Child) -> Self {
+    Self {
+      child: Mutex::new(Some(child)),
+    }
+  }
+
+  /// Waits for the process to exit, returning [`None`] if it has already
+  /// been waited for.
+  pub fn wait(&self) -> Option<std::io::Result<Output>> {
+    let child = self.child.lock().unwrap().take()?;
+    Some(child.wait_with_output())
+  }
+
+  /// Kills the process, returning [`None`] if it has been waited for.
+  pub fn kill(&self) -> Option<std::io::Result<()>> {
+    self.child.lock().unwrap().as_mut().map(Child::kill)
+  }
+
+  /// Returns the ID of the process, or [`None`] if it has been waited for.
+  pub fn id(&self) -> Option<u32> {
+    self.child.lock().unwrap().as_ref().map(Child::id)
+  }
+}
+
+impl Drop for ProcessHandle {
+  fn drop(&mut self) {
+    let Some(mut child) = self.child.get_mut().unwrap().take() else {
+      return;
+    };
+
+    // Closing the pipes means that the process can't block on writing to
+    // them once nothing reads them.
+    drop(child.stdout.take());
+    drop(child.stderr.take());
+
+    if !matches!(child.try_wait(), Ok(Some(_))) {
+      std::thread::spawn(move || child.wait());
+    }
+  }
+}
+
+fn error(message: impl ToCompactString) -> ExprKind {
+  ExprKind::Error(Box::new(
+    ExprKind::String(message.to_compact_string()).into(),
+  ))
+}
+
+fn output(output: Output) -> ExprKind {
+  let string = |x: Vec<u8>| {
+    Expr::from(ExprKind::String(
+      String::from_utf8_lossy(&x).to_compact_string(),
+    ))
+  };
+
+  ExprKind::Record(
+    HashMap::from_iter([
+      (Symbol::from_ref("stdout"), string(output.stdout)),
+      (Symbol::from_ref("stderr"), string(output.stderr)),
+      (
+        Symbol::from_ref("status"),
+        output
+          .status
+          .code()
+          .map_or(ExprKind::Nil, |x| ExprKind::Integer(x as i64))
+          .into(),
+      ),
+    ])
+    .into(),
+  )
+}
+
+/// Builds a command from the program, its arguments and the options, returning
+/// [`None`] if any are invalid. The string to write to its input is returned
+/// alongside it.
+fn command(
+  program: &ExprKind,
+  args: &ExprKind,
+  options: Option<&ExprKind>,
+) -> Option<(Command, Option<CompactString>)> {
+  let text = |kind: &ExprKind| match kind {
+    ExprKind::String(x) => Some(x.to_string()),
+    ExprKind::Symbol(x) => Some(x.as_str().to_string()),
+    ExprKind::Integer(_) | ExprKind::Float(_) => Some(kind.to_string()),
+    _ => None,
+  };
+
+  let mut command = Command::new(text(program)?);
+
+  match args {
+    ExprKind::List(x) => {
+      for arg in x {
+        command.arg(text(&arg.kind)?);
+      }
+    }
+    _ => return None,
+  }
+
+  let mut stdin = None;
+
+  match options {
+    None => {}
+    Some(ExprKind::Record(x)) => {
+      for (key, value) in x {
+        match (key.as_str(), &value.kind) {
+          ("cwd", kind) => {
+            command.current_dir(text(kind)?);
+          }
+          ("env", ExprKind::Record(x)) => {
+            for (key, value) in x {
+              command.env(key.as_str(), text(&value.kind)?);
+            }
+          }
+          ("env", ExprKind::Map(x)) => {
+            for (key, value) in x {
+              command.env(key.to_string(), text(&value.kind)?);
+            }
+          }
+          ("stdin", ExprKind::String(x)) => stdin = Some(x.clone()),
+          _ => return None,
+        }
+      }
+    }
+    Some(_) => return None,
+  }
+
+  Some((command, stdin))
+}
+
+/// Spawns a command with its output piped, writing `stdin` to it if there is
+/// one.
+fn spawn(
+  mut command: Command,
+  stdin: Option<CompactString>,
+) -> std::io::Result<Child> {
+  let mut child = command
+    .stdin(match stdin {
+      Some(_) => Stdio::piped(),
+      None => Stdio::null(),
+    })
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+  if let (Some(stdin), Some(mut pipe)) = (stdin, child.stdin.take()) {
+    // The pipe is dropped afterwards, so that the command sees the end of it.
+    pipe.write_all(stdin.as_bytes())?;
+  }
+
+  Ok(child)
+}
+
+fn run_run(
+  mut context: Context,
+  expr: Expr,
+  has_options: bool,
+) -> Result<Context, RunError> {
+  let options = match has_options {
+    true => Some(context.stack_pop(&expr)?),
+    false => None,
+  };
+  let args = context.stack_pop(&expr)?;
+  let program = context.stack_pop(&expr)?;
+
+  let kind = match command(
+    &program.kind,
+    &args.kind,
+    options.map(|x| x.kind).as_ref(),
+  ) {
+    Some((command, stdin)) => {
+      match spawn(command, stdin).and_then(Child::wait_with_output) {
+        Ok(x) => output(x),
+        Err(e) => error(e),
+      }
+    }
+    None => ExprKind::Nil,
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+fn run_spawn(
+  mut context: Context,
+  expr: Expr,
+  has_options: bool,
+) -> Result<Context, RunError> {
+  let options = match has_options {
+    true => Some(context.stack_pop(&expr)?),
+    false => None,
+  };
+  let args = context.stack_pop(&expr)?;
+  let program = context.stack_pop(&expr)?;
+
+  let expr = match command(
+    &program.kind,
+    &args.kind,
+    options.map(|x| x.kind).as_ref(),
+  ) {
+    Some((command, stdin)) => match spawn(command, stdin) {
+      Ok(child) => Arc::new(ProcessHandle::new(child)).into_expr(),
+      Err(e) => error(e).into(),
+    },
+    None => ExprKind::Nil.into(),
+  };
+
+  context.stack_push(expr)?;
+
+  Ok(context)
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "run",
+    "( program args -- record )\n\nRuns a program with a list of arguments, \
+     pushing its `stdout`, `stderr` and `status`.",
+  ),
+  (
+    "run-with",
+    "( program args options -- record )\n\nRuns a program with a record of \
+     options.",
+  ),
+  (
+    "spawn",
+    "( program args -- process )\n\nStarts a program in the background, \
+     pushing a handle to it.",
+  ),
+  (
+    "spawn-with",
+    "( program args options -- process )\n\nStarts a program in the background \
+     with a record of options.",
+  ),
+  (
+    "wait",
+    "( process -- record )\n\nWaits for a process to exit, pushing its \
+     `stdout`, `stderr` and `status`.",
+  ),
+  ("kill", "( process -- b )\n\nKills a process."),
+  ("pid", "( process -- n )\n\nPushes the ID of a process."),
+];
+
+pub fn module(policy: &SandboxPolicy) -> Module {
+  let mut module = Module::new(Symbol::from_ref("proc"));
+  crate::add_docs(&mut module, DOCS);
+
+  if !policy.can_run() {
+    return module;
+  }
+
+  module
+    .add_func(
+      Symbol::from_ref("run"),
+      Arc::new(|_, context, expr| run_run(context, expr, false)),
+    )
+    .add_func(
+      Symbol::from_ref("run-with"),
+      Arc::new(|_, context, expr| run_run(context, expr, true)),
+    )
+    .add_func(
+      Symbol::from_ref("spawn"),
+      Arc::new(|_, context, expr| run_spawn(context, expr, false)),
+    )
+    .add_func(
+      Symbol::from_ref("spawn-with"),
+      Arc::new(|_, context, expr| run_spawn(context, expr, true)),
+    )
+    .add_fn(Symbol::from_ref("wait"), |process: Arc<ProcessHandle>| {
+      process.wait().map(|x| match x {
+        Ok(x) => output(x),
+        Err(e) => error(e),
+      })
+    })
+    .add_fn(Symbol::from_ref("kill"), |process: Arc<ProcessHandle>| {
+      process.kill().map(|x| x.is_ok())
+    })
+    .add_fn(Symbol::from_ref("pid"), |process: Arc<ProcessHandle>| {
+      process.id().map(i64::from)
+    });
+
+  module
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(code: &str) -> Result<Context, RunError> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    Engine::new()
+      .with_module(module(&SandboxPolicy::unrestricted()))
+      .run(Context::new(), exprs)
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn waits_for_spawned_processes() {
+    let context = run(
+      r#""sh" ["-c" "echo hi"] proc:spawn 'p def
+      p proc:pid typeof
+      p proc:wait typeof
+      p proc:wait
+      p proc:kill"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+      context.stack(),
+      &[
+        ExprKind::String("integer".into()).into(),
+        ExprKind::String("record".into()).into(),
+        ExprKind::Nil.into(),
+        ExprKind::Nil.into(),
+      ]
+    );
+  }
+
+  #[test]
+  fn rejects_forged_handles() {
+    let error = run(r#"{type: "process", id: 0} proc:wait"#).unwrap_err();
+    assert_eq!(error.reason, RunErrorReason::InvalidArgument);
+  }
+}
@@ -0,0 +1,133 @@
+//! Generating random numbers, with the generator owned by the [`Context`].
+//!
+//! Each context has its own generator, so contexts run in parallel don't
+//! affect each other, and `rand:seed` makes a run reproducible.
+
+use std::sync::Arc;
+
+use stack_core::prelude::*;
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "seed",
+    "( n -- )\n\nSeeds the generator, so that the numbers after it are \
+     reproducible.",
+  ),
+  (
+    "int",
+    "( start end -- n )\n\nPushes a random integer from `start` up to, but not \
+     including, `end`.",
+  ),
+  (
+    "float",
+    "( -- f )\n\nPushes a random float from 0 up to, but not including, 1.",
+  ),
+  ("bool", "( -- b )\n\nPushes a random boolean."),
+  ("shuffle", "( list -- list )\n\nShuffles a list."),
+  (
+    "choice",
+    "( list -- item )\n\nPushes a random item of a list, or `nil` if it's \
+     empty.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("rand"));
+
+  module
+    .add_func(
+      Symbol::from_ref("seed"),
+      Arc::new(|_, mut context, expr| {
+        let seed = context.stack_pop(&expr)?;
+
+        if let ExprKind::Integer(x) = seed.kind {
+          context.rng_mut().seed(x as u64);
+        }
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("int"),
+      Arc::new(|_, mut context, expr| {
+        let end = context.stack_pop(&expr)?;
+        let start = context.stack_pop(&expr)?;
+
+        // Like `range`, the end is excluded.
+        let kind = match (start.kind, end.kind) {
+          (ExprKind::Integer(start), ExprKind::Integer(end)) => context
+            .rng_mut()
+            .range(start, end)
+            .map_or(ExprKind::Nil, ExprKind::Integer),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("float"),
+      Arc::new(|_, mut context, _| {
+        let x = context.rng_mut().next_f64();
+        context.stack_push(ExprKind::Float(x).into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("bool"),
+      Arc::new(|_, mut context, _| {
+        let x = context.rng_mut().next_bool();
+        context.stack_push(ExprKind::Boolean(x).into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("shuffle"),
+      Arc::new(|_, mut context, expr| {
+        let list = context.stack_pop(&expr)?;
+
+        let kind = match list.kind {
+          ExprKind::List(mut x) => {
+            // A Fisher-Yates shuffle.
+            for i in (1..x.len()).rev() {
+              let j = context.rng_mut().range(0, i as i64 + 1).unwrap_or(0);
+              x.swap(i, j as usize);
+            }
+
+            ExprKind::List(x)
+          }
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("choice"),
+      Arc::new(|_, mut context, expr| {
+        let list = context.stack_pop(&expr)?;
+
+        let expr = match list.kind {
+          ExprKind::List(mut x) => context
+            .rng_mut()
+            .range(0, x.len() as i64)
+            .map(|i| x.swap_remove(i as usize)),
+          _ => None,
+        };
+
+        context.stack_push(expr.unwrap_or_else(|| ExprKind::Nil.into()))?;
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
@@ -0,0 +1,523 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
+
+use compact_str::CompactString;
+use stack_core::prelude::*;
+
+const TYPE: &str = "rope";
+
+/// The largest amount of bytes in a leaf. Smaller appends are merged into the
+/// last leaf, so that pushing many short strings doesn't create a leaf each.
+const CHUNK_LEN: usize = 1024;
+/// The depth at which a rope is rebuilt to be balanced.
+const MAX_DEPTH: usize = 48;
+
+#[derive(Debug, Clone)]
+enum Node {
+  Leaf {
+    text: Arc<str>,
+    chars: usize,
+  },
+  Branch {
+    left: Arc<Node>,
+    right: Arc<Node>,
+    chars: usize,
+    depth: usize,
+  },
+}
+
+impl Node {
+  fn leaf(text: &str) -> Arc<Self> {
+    Arc::new(Self::Leaf {
+      text: text.into(),
+      chars: text.chars().count(),
+    })
+  }
+
+  fn branch(left: Arc<Self>, right: Arc<Self>) -> Arc<Self> {
+    Arc::new(Self::Branch {
+      chars: left.chars() + right.chars(),
+      depth: left.depth().max(right.depth()) + 1,
+      left,
+      right,
+    })
+  }
+
+  fn chars(&self) -> usize {
+    match self {
+      Self::Leaf { chars, .. } | Self::Branch { chars, .. } => *chars,
+    }
+  }
+
+  fn depth(&self) -> usize {
+    match self {
+      Self::Leaf { .. } => 0,
+      Self::Branch { depth, .. } => *depth,
+    }
+  }
+
+  /// Joins `left` and `right`, merging small leaves together.
+  fn concat(left: &Arc<Self>, right: &Arc<Self>) -> Arc<Self> {
+    if left.chars() == 0 {
+      return right.clone();
+    }
+
+    if right.chars() == 0 {
+      return left.clone();
+    }
+
+    match (&**left, &**right) {
+      (Self::Leaf { text: a, .. }, Self::Leaf { text: b, .. })
+        if a.len() + b.len() <= CHUNK_LEN =>
+      {
+        Self::leaf(&format!("{a}{b}"))
+      }
+      (
+        Self::Branch {
+          left: left_left,
+          right: left_right,
+          ..
+        },
+        Self::Leaf { text: b, .. },
+      ) if matches!(&**left_right, Self::Leaf { text: a, .. } if a.len() + b.len() <= CHUNK_LEN) => {
+        Self::branch(left_left.clone(), Self::concat(left_right, right))
+      }
+      _ => Self::branch(left.clone(), right.clone()),
+    }
+  }
+
+  /// Returns the characters from `start` up to `end`.
+  fn slice(node: &Arc<Self>, start: usize, end: usize) -> Arc<Self> {
+    if start == 0 && end == node.chars() {
+      return node.clone();
+    }
+
+    match &**node {
+      Self::Leaf { text, .. } => {
+        let byte =
+          |n| text.char_indices().nth(n).map_or(text.len(), |(i, _)| i);
+        Self::leaf(&text[byte(start)..byte(end)])
+      }
+      Self::Branch { left, right, .. } => {
+        let mid = left.chars();
+
+        if end <= mid {
+          Self::slice(left, start, end)
+        } else if start >= mid {
+          Self::slice(right, start - mid, end - mid)
+        } else {
+          Self::concat(
+            &Self::slice(left, start, mid),
+            &Self::slice(right, 0, end - mid),
+          )
+        }
+      }
+    }
+  }
+
+  fn leaves<'a>(node: &'a Arc<Self>, leaves: &mut Vec<&'a Arc<Self>>) {
+    match &**node {
+      Self::Leaf { .. } => leaves.push(node),
+      Self::Branch { left, right, .. } => {
+        Self::leaves(left, leaves);
+        Self::leaves(right, leaves);
+      }
+    }
+  }
+
+  /// Builds a balanced tree from `leaves`.
+  fn balanced(leaves: &[&Arc<Self>]) -> Arc<Self> {
+    match leaves {
+      [] => Self::leaf(""),
+      [leaf] => (*leaf).clone(),
+      leaves => {
+        let (left, right) = leaves.split_at(leaves.len() / 2);
+        Self::branch(Self::balanced(left), Self::balanced(right))
+      }
+    }
+  }
+
+  fn write_to(&self, string: &mut String) {
+    match self {
+      Self::Leaf { text, .. } => string.push_str(text),
+      Self::Branch { left, right, .. } => {
+        left.write_to(string);
+        right.write_to(string);
+      }
+    }
+  }
+}
+
+/// An immutable string made of shared chunks.
+///
+/// Concatenating and slicing create a new rope which shares most of its
+/// chunks with the old ones, rather than copying the whole string. All
+/// lengths and indices are in characters.
+#[derive(Debug, Clone)]
+pub struct Rope {
+  root: Arc<Node>,
+}
+
+impl Default for Rope {
+  fn default() -> Self {
+    Self {
+      root: Node::leaf(""),
+    }
+  }
+}
+
+impl From<&str> for Rope {
+  fn from(value: &str) -> Self {
+    let mut leaves = Vec::new();
+    let mut rest = value;
+
+    while !rest.is_empty() {
+      let mut end = rest.len().min(CHUNK_LEN);
+      while !rest.is_char_boundary(end) {
+        end -= 1;
+      }
+
+      let (chunk, tail) = rest.split_at(end);
+      leaves.push(Node::leaf(chunk));
+      rest = tail;
+    }
+
+    Self {
+      root: Node::balanced(&leaves.iter().collect::<Vec<_>>()),
+    }
+  }
+}
+
+impl core::fmt::Display for Rope {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    let mut string = String::with_capacity(self.len());
+    self.root.write_to(&mut string);
+
+    f.write_str(&string)
+  }
+}
+
+impl Rope {
+  /// Returns the length of this rope in characters.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.root.chars()
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Returns a rope of this one followed by `other`.
+  pub fn concat(&self, other: &Self) -> Self {
+    let root = Node::concat(&self.root, &other.root);
+
+    if root.depth() > MAX_DEPTH {
+      let mut leaves = Vec::new();
+      Node::leaves(&root, &mut leaves);
+
+      Self {
+        root: Node::balanced(&leaves),
+      }
+    } else {
+      Self { root }
+    }
+  }
+
+  /// Returns the characters from `start` up to `end`, or [`None`] if the range
+  /// is out of bounds.
+  pub fn slice(&self, start: usize, end: usize) -> Option<Self> {
+    if start > end || end > self.len() {
+      return None;
+    }
+
+    Some(Self {
+      root: Node::slice(&self.root, start, end),
+    })
+  }
+
+  /// Splits this rope by `separator`, sharing the chunks of the pieces with
+  /// this rope.
+  pub fn split_by(&self, separator: &str) -> Vec<Self> {
+    let string = self.to_string();
+
+    let separator_chars = separator.chars().count();
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut last_byte = 0;
+    let mut chars = 0;
+
+    for (byte, _) in string.match_indices(separator) {
+      chars += string[last_byte..byte].chars().count();
+      pieces.push(self.slice(start, chars).unwrap_or_default());
+
+      chars += separator_chars;
+      last_byte = byte + separator.len();
+      start = chars;
+    }
+
+    pieces.push(self.slice(start, self.len()).unwrap_or_default());
+    pieces
+  }
+}
+
+/// The ropes which are in use, which are referred to by their ID.
+#[derive(Debug, Default)]
+struct Ropes {
+  next_id: i64,
+  ropes: HashMap<i64, Rope>,
+}
+
+impl Ropes {
+  fn insert(&mut self, rope: Rope) -> Expr {
+    let id = self.next_id;
+    self.next_id += 1;
+    self.ropes.insert(id, rope);
+
+    handle(id)
+  }
+
+  fn get(&self, expr: &Expr) -> Option<&Rope> {
+    rope_id(expr).and_then(|id| self.ropes.get(&id))
+  }
+}
+
+/// Returns the ID of a rope value, which is a record with a `type` of
+/// `"rope"` and an `id`.
+fn rope_id(expr: &Expr) -> Option<i64> {
+  match &expr.kind {
+    ExprKind::Record(x) => {
+      let ty = x.get(&Symbol::from_ref("type"))?;
+      let id = x.get(&Symbol::from_ref("id"))?;
+
+      match (&ty.kind, &id.kind) {
+        (ExprKind::String(ty), ExprKind::Integer(id))
+          if ty.as_str() == TYPE =>
+        {
+          Some(*id)
+        }
+        _ => None,
+      }
+    }
+    _ => None,
+  }
+}
+
+fn handle(id: i64) -> Expr {
+  ExprKind::Record(
+    HashMap::from_iter([
+      (
+        Symbol::from_ref("type"),
+        ExprKind::String(CompactString::new(TYPE)).into(),
+      ),
+      (Symbol::from_ref("id"), ExprKind::Integer(id).into()),
+    ])
+    .into(),
+  )
+  .into()
+}
+
+fn text(kind: &ExprKind) -> CompactString {
+  match kind {
+    ExprKind::String(x) => x.clone(),
+    kind => CompactString::new(kind.to_string()),
+  }
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "new",
+    "( item -- rope )\n\nPushes a rope of an item, as it would be displayed \
+     unless it's a string.",
+  ),
+  ("push", "( rope item -- rope )\n\nAppends an item to a rope."),
+  (
+    "concat",
+    "( lhs rhs -- rope )\n\nPushes a rope of two ropes joined together.",
+  ),
+  ("len", "( rope -- n )\n\nPushes the length of a rope in characters."),
+  (
+    "slice",
+    "( rope start end -- rope )\n\nPushes a rope of the characters from \
+     `start` up to `end`, or `nil` if they're out of bounds.",
+  ),
+  (
+    "split-by",
+    "( rope separator -- list )\n\nSplits a rope by a separator into a list of \
+     ropes.",
+  ),
+  ("to-string", "( rope -- s )\n\nPushes the string of a rope."),
+  ("free", "( rope -- )\n\nFrees a rope, after which it can't be used."),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("rope"));
+  let ropes = Arc::new(Mutex::new(Ropes::default()));
+
+  let new_ropes = ropes.clone();
+  let push_ropes = ropes.clone();
+  let concat_ropes = ropes.clone();
+  let len_ropes = ropes.clone();
+  let slice_ropes = ropes.clone();
+  let split_ropes = ropes.clone();
+  let to_string_ropes = ropes.clone();
+  let free_ropes = ropes;
+
+  module
+    .add_func(
+      Symbol::from_ref("new"),
+      Arc::new(move |_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let rope = Rope::from(text(&item.kind).as_str());
+        let handle = new_ropes.lock().unwrap().insert(rope);
+        context.stack_push(handle)?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("push"),
+      Arc::new(move |_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+        let rope = context.stack_pop(&expr)?;
+
+        let mut ropes = push_ropes.lock().unwrap();
+        let entry = rope_id(&rope).and_then(|id| ropes.ropes.get_mut(&id));
+
+        match entry {
+          Some(entry) => {
+            *entry = entry.concat(&Rope::from(text(&item.kind).as_str()));
+            context.stack_push(rope)?;
+          }
+          None => context.stack_push(ExprKind::Nil.into())?,
+        }
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("concat"),
+      Arc::new(move |_, mut context, expr| {
+        let rhs = context.stack_pop(&expr)?;
+        let lhs = context.stack_pop(&expr)?;
+
+        let mut ropes = concat_ropes.lock().unwrap();
+        let rope = match (ropes.get(&lhs), ropes.get(&rhs)) {
+          (Some(lhs), Some(rhs)) => Some(lhs.concat(rhs)),
+          _ => None,
+        };
+
+        match rope {
+          Some(rope) => context.stack_push(ropes.insert(rope))?,
+          None => context.stack_push(ExprKind::Nil.into())?,
+        }
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("len"),
+      Arc::new(move |_, mut context, expr| {
+        let rope = context.stack_pop(&expr)?;
+
+        let ropes = len_ropes.lock().unwrap();
+        let kind = ropes
+          .get(&rope)
+          .map(|x| ExprKind::Integer(x.len() as i64))
+          .unwrap_or(ExprKind::Nil);
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("slice"),
+      Arc::new(move |_, mut context, expr| {
+        let end = context.stack_pop(&expr)?;
+        let start = context.stack_pop(&expr)?;
+        let rope = context.stack_pop(&expr)?;
+
+        let mut ropes = slice_ropes.lock().unwrap();
+        let slice = match (ropes.get(&rope), start.kind, end.kind) {
+          (Some(rope), ExprKind::Integer(start), ExprKind::Integer(end)) => {
+            usize::try_from(start)
+              .ok()
+              .zip(usize::try_from(end).ok())
+              .and_then(|(start, end)| rope.slice(start, end))
+          }
+          _ => None,
+        };
+
+        match slice {
+          Some(slice) => context.stack_push(ropes.insert(slice))?,
+          None => context.stack_push(ExprKind::Nil.into())?,
+        }
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("split-by"),
+      Arc::new(move |_, mut context, expr| {
+        let patt = context.stack_pop(&expr)?;
+        let rope = context.stack_pop(&expr)?;
+
+        let mut ropes = split_ropes.lock().unwrap();
+        let pieces = match (ropes.get(&rope), patt.kind) {
+          (Some(rope), ExprKind::String(ref patt)) => {
+            Some(rope.split_by(patt.as_str()))
+          }
+          _ => None,
+        };
+
+        let kind = match pieces {
+          Some(pieces) => ExprKind::List(
+            pieces.into_iter().map(|x| ropes.insert(x)).collect(),
+          ),
+          None => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("to-string"),
+      Arc::new(move |_, mut context, expr| {
+        let rope = context.stack_pop(&expr)?;
+
+        let ropes = to_string_ropes.lock().unwrap();
+        let kind = ropes
+          .get(&rope)
+          .map(|x| ExprKind::String(x.to_string().into()))
+          .unwrap_or(ExprKind::Nil);
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("free"),
+      Arc::new(move |_, mut context, expr| {
+        let rope = context.stack_pop(&expr)?;
+
+        if let Some(id) = rope_id(&rope) {
+          free_ropes.lock().unwrap().ropes.remove(&id);
+        }
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
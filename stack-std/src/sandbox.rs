@@ -0,0 +1,339 @@
+//! The capabilities which the standard modules are given.
+//!
+//! A [`SandboxPolicy`] starts out denying everything, and is built up with
+//! the capabilities a script needs, such as
+//! `SandboxPolicy::new().with_read("data").with_run(true)`.
+//!
+//! [`SandboxPolicy::unrestricted`] allows everything, which is what's used
+//! when a script isn't sandboxed.
+//!
+//! The modules check the policy themselves, but `import` is part of the
+//! engine, so the engine needs the policy's
+//! [`import_guard`](SandboxPolicy::import_guard) too, so that scripts can only
+//! import the files they can read.
+
+use std::{
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+use stack_core::engine::ImportGuard;
+
+/// Which of something a capability is granted for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Grant<T> {
+  All,
+  Only(Vec<T>),
+}
+
+impl<T> Grant<T> {
+  /// Adds an item to the grant, unless it's already for everything.
+  fn with(self, item: T) -> Self {
+    match self {
+      Self::All => Self::All,
+      Self::Only(mut x) => {
+        x.push(item);
+        Self::Only(x)
+      }
+    }
+  }
+}
+
+impl<T> Default for Grant<T> {
+  #[inline]
+  fn default() -> Self {
+    Self::Only(Vec::new())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SandboxPolicy {
+  read: Grant<PathBuf>,
+  write: Grant<PathBuf>,
+  net: Grant<String>,
+  run: bool,
+  env: bool,
+}
+
+impl SandboxPolicy {
+  /// Creates a [`SandboxPolicy`] which denies everything.
+  #[inline]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Creates a [`SandboxPolicy`] which allows everything.
+  #[inline]
+  pub fn unrestricted() -> Self {
+    Self {
+      read: Grant::All,
+      write: Grant::All,
+      net: Grant::All,
+      run: true,
+      env: true,
+    }
+  }
+
+  /// Allows reading the files within a directory, or a file itself.
+  #[inline]
+  pub fn with_read(mut self, path: impl Into<PathBuf>) -> Self {
+    self.read = self.read.with(path.into());
+    self
+  }
+
+  /// Allows reading any file.
+  #[inline]
+  pub fn with_read_all(mut self) -> Self {
+    self.read = Grant::All;
+    self
+  }
+
+  /// Allows writing the files within a directory, or a file itself.
+  #[inline]
+  pub fn with_write(mut self, path: impl Into<PathBuf>) -> Self {
+    self.write = self.write.with(path.into());
+    self
+  }
+
+  /// Allows writing any file.
+  #[inline]
+  pub fn with_write_all(mut self) -> Self {
+    self.write = Grant::All;
+    self
+  }
+
+  /// Allows connecting to a host.
+  #[inline]
+  pub fn with_net(mut self, host: impl Into<String>) -> Self {
+    self.net = self.net.with(host.into());
+    self
+  }
+
+  /// Allows connecting to any host.
+  #[inline]
+  pub fn with_net_all(mut self) -> Self {
+    self.net = Grant::All;
+    self
+  }
+
+  /// Sets whether running other programs is allowed.
+  #[inline]
+  pub fn with_run(mut self, run: bool) -> Self {
+    self.run = run;
+    self
+  }
+
+  /// Sets whether reading and changing environment variables is allowed.
+  #[inline]
+  pub fn with_env(mut self, env: bool) -> Self {
+    self.env = env;
+    self
+  }
+
+  #[inline]
+  pub fn can_read(&self, path: &Path) -> bool {
+    is_within(&self.read, path)
+  }
+
+  #[inline]
+  pub fn can_write(&self, path: &Path) -> bool {
+    is_within(&self.write, path)
+  }
+
+  /// Returns whether a host can be connected to. A port can be given, as in
+  /// `example.com:443`, in which case a grant without a port allows any.
+  pub fn can_net(&self, host: &str) -> bool {
+    match &self.net {
+      Grant::All => true,
+      Grant::Only(hosts) => hosts.iter().any(|x| {
+        x == host
+          || host
+            .rsplit_once(':')
+            .is_some_and(|(name, _)| !x.contains(':') && x == name)
+      }),
+    }
+  }
+
+  /// Returns a guard for [`Engine::with_import_guard`] which only allows
+  /// importing the files which can be read, or [`None`] if any can be.
+  ///
+  /// [`Engine::with_import_guard`]: stack_core::engine::Engine::with_import_guard
+  pub fn import_guard(&self) -> Option<ImportGuard> {
+    match self.read {
+      Grant::All => None,
+      ref read => {
+        let read = read.clone();
+        Some(Arc::new(move |path: &Path| is_within(&read, path)))
+      }
+    }
+  }
+
+  #[inline]
+  pub fn can_run(&self) -> bool {
+    self.run
+  }
+
+  #[inline]
+  pub fn can_env(&self) -> bool {
+    self.env
+  }
+}
+
+/// Returns whether a path is within one of the granted paths, resolving links
+/// and `..` first so that they can't be used to escape.
+fn is_within(grant: &Grant<PathBuf>, path: &Path) -> bool {
+  match grant {
+    Grant::All => true,
+    Grant::Only(roots) => resolve(path).is_some_and(|path| {
+      roots
+        .iter()
+        .filter_map(|root| resolve(root))
+        .any(|root| path.starts_with(root))
+    }),
+  }
+}
+
+/// Returns the absolute path of a path which may not exist yet, or [`None`] if
+/// the part which doesn't exist has a `..` in it.
+fn resolve(path: &Path) -> Option<PathBuf> {
+  let mut ancestor = path;
+  let mut missing = Vec::new();
+
+  loop {
+    let dir = match ancestor.as_os_str().is_empty() {
+      true => Path::new("."),
+      false => ancestor,
+    };
+
+    if let Ok(x) = dir.canonicalize() {
+      return Some(missing.into_iter().rev().fold(x, |x, name| x.join(name)));
+    }
+
+    missing.push(ancestor.file_name()?);
+    ancestor = ancestor.parent()?;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use stack_core::prelude::*;
+
+  use super::*;
+
+  #[test]
+  fn allows_paths_within_the_granted_ones() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = dir.path().join("data");
+    fs::create_dir(&data).unwrap();
+    fs::write(data.join("a.txt"), "").unwrap();
+    fs::write(dir.path().join("secret.txt"), "").unwrap();
+
+    let policy = SandboxPolicy::new().with_read(&data);
+
+    assert!(policy.can_read(&data));
+    assert!(policy.can_read(&data.join("a.txt")));
+    assert!(!policy.can_read(&dir.path().join("secret.txt")));
+    assert!(!policy.can_write(&data.join("a.txt")));
+    assert!(SandboxPolicy::unrestricted().can_read(&dir.path().join("x")));
+  }
+
+  #[test]
+  fn resolves_parent_dirs_before_checking() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = dir.path().join("data");
+    fs::create_dir(&data).unwrap();
+    fs::write(dir.path().join("secret.txt"), "").unwrap();
+
+    let policy = SandboxPolicy::new().with_read(&data);
+
+    assert!(!policy.can_read(&data.join("../secret.txt")));
+    assert!(policy.can_read(&data.join("../data/a.txt")));
+    // A `..` in the part which doesn't exist yet can't be resolved.
+    assert!(!policy.can_read(&data.join("new/../../secret.txt")));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn resolves_links_before_checking() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = dir.path().join("data");
+    fs::create_dir(&data).unwrap();
+    fs::write(dir.path().join("secret.txt"), "").unwrap();
+
+    std::os::unix::fs::symlink(
+      dir.path().join("secret.txt"),
+      data.join("link.txt"),
+    )
+    .unwrap();
+    std::os::unix::fs::symlink(dir.path(), data.join("up")).unwrap();
+
+    let policy = SandboxPolicy::new().with_read(&data);
+
+    assert!(!policy.can_read(&data.join("link.txt")));
+    assert!(!policy.can_read(&data.join("up/secret.txt")));
+  }
+
+  #[test]
+  fn allows_writing_paths_which_dont_exist_yet() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    fs::create_dir(&out).unwrap();
+
+    let policy = SandboxPolicy::new().with_write(&out);
+
+    assert!(policy.can_write(&out.join("new.txt")));
+    assert!(policy.can_write(&out.join("new/dir/file.txt")));
+    assert!(!policy.can_write(&dir.path().join("new.txt")));
+    assert!(!policy.can_write(&out.join("new/../../new.txt")));
+  }
+
+  #[test]
+  fn checks_hosts_and_ports() {
+    let policy = SandboxPolicy::new()
+      .with_net("example.com")
+      .with_net("localhost:8080");
+
+    assert!(policy.can_net("example.com"));
+    assert!(policy.can_net("example.com:443"));
+    assert!(policy.can_net("localhost:8080"));
+    assert!(!policy.can_net("localhost:9090"));
+    assert!(!policy.can_net("localhost"));
+    assert!(!policy.can_net("example.org"));
+    assert!(!policy.can_net("sub.example.com"));
+    assert!(SandboxPolicy::unrestricted().can_net("example.org:80"));
+  }
+
+  #[test]
+  fn only_imports_files_which_can_be_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let allowed = dir.path().join("allowed");
+    let secret = dir.path().join("secret");
+    fs::create_dir(&allowed).unwrap();
+    fs::create_dir(&secret).unwrap();
+    fs::write(allowed.join("lib.stack"), "1").unwrap();
+    fs::write(secret.join("s.stack"), "2").unwrap();
+
+    let policy = SandboxPolicy::new().with_read(&allowed);
+    let engine = Engine::new().with_import_guard(policy.import_guard());
+
+    let run = |code: &str| {
+      let exprs = parse(&mut Lexer::new(Source::new("", code))).unwrap();
+      engine.run(Context::new(), exprs)
+    };
+
+    let import = |path: &Path| format!("{:?} import", path.to_str().unwrap());
+
+    let context = run(&import(&allowed.join("lib.stack"))).unwrap();
+    assert_eq!(context.stack(), &[ExprKind::Integer(1).into()]);
+
+    let err = run(&import(&secret.join("s.stack"))).unwrap_err();
+    assert_eq!(err.reason, RunErrorReason::ForbiddenImport);
+
+    let err = run(&import(&allowed.join("../secret/s.stack"))).unwrap_err();
+    assert_eq!(err.reason, RunErrorReason::ForbiddenImport);
+
+    assert!(SandboxPolicy::unrestricted().import_guard().is_none());
+  }
+}
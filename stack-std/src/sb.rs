@@ -0,0 +1,194 @@
+//! Building strings piece by piece.
+//!
+//! `sb:new` pushes a handle to an empty string builder, `sb:push` appends an
+//! item to it (as it would be displayed, unless it's a string) and pushes the
+//! builder back, `sb:len` pushes the length in bytes, and `sb:build` pushes the
+//! finished string. Once a builder has been built, `sb:push`, `sb:len` and
+//! `sb:build` push `nil` for it.
+//!
+//! A builder is a foreign value, so pushing to one is cheap even when it's
+//! stored in a variable, and its string is freed along with the last handle
+//! to it.
+
+use std::sync::{Arc, Mutex};
+
+use stack_core::prelude::*;
+
+/// The string of a builder, or [`None`] once it has been built.
+#[derive(Debug)]
+pub struct StringBuilder {
+  string: Mutex<Option<String>>,
+}
+
+impl StringBuilder {
+  #[inline]
+  pub fn new() -> Self {
+    Self {
+      string: Mutex::new(Some(String::new())),
+    }
+  }
+
+  /// Appends an item, returning `false` if the builder has been built.
+  pub fn push(&self, item: &ExprKind) -> bool {
+    match self.string.lock().unwrap().as_mut() {
+      Some(string) => {
+        match item {
+          ExprKind::String(x) => string.push_str(x),
+          kind => string.push_str(&kind.to_string()),
+        }
+
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Returns the length in bytes, or [`None`] if the builder has been built.
+  #[inline]
+  pub fn len(&self) -> Option<usize> {
+    self.string.lock().unwrap().as_ref().map(String::len)
+  }
+
+  /// Returns whether the string is empty, or [`None`] if the builder has been
+  /// built.
+  #[inline]
+  pub fn is_empty(&self) -> Option<bool> {
+    self.len().map(|x| x == 0)
+  }
+
+  /// Takes the string, or returns [`None`] if it has already been taken.
+  #[inline]
+  pub fn build(&self) -> Option<String> {
+    self.string.lock().unwrap().take()
+  }
+}
+
+impl Default for StringBuilder {
+  #[inline]
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+const DOCS: &[(&str, &str)] = &[
+  ("new", "( -- sb )\n\nPushes an empty string builder."),
+  (
+    "push",
+    "( sb item -- sb )\n\nAppends an item to a builder, as it would be \
+     displayed unless it's a string.",
+  ),
+  (
+    "len",
+    "( sb -- n )\n\nPushes the length of a builder's string in bytes.",
+  ),
+  (
+    "build",
+    "( sb -- s )\n\nPushes the finished string of a builder, after which it \
+     can't be used.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("sb"));
+
+  module
+    .add_fn(Symbol::from_ref("new"), || Arc::new(StringBuilder::new()))
+    .add_fn(
+      Symbol::from_ref("push"),
+      |sb: Arc<StringBuilder>, item: Expr| sb.push(&item.kind).then_some(sb),
+    )
+    .add_fn(Symbol::from_ref("len"), |sb: Arc<StringBuilder>| {
+      sb.len().map(|x| x as i64)
+    })
+    .add_fn(Symbol::from_ref("build"), |sb: Arc<StringBuilder>| {
+      sb.build()
+    });
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(code: &str) -> Result<Context, RunError> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    Engine::new()
+      .with_module(module())
+      .run(Context::new(), exprs)
+  }
+
+  #[test]
+  fn builds_strings() {
+    let context =
+      run(r#"sb:new "a" sb:push 1 sb:push '[b] sb:push sb:build"#).unwrap();
+    assert_eq!(context.stack(), &[ExprKind::String("a1[b]".into()).into()]);
+  }
+
+  #[test]
+  fn pushes_to_stored_builders() {
+    let context = run(
+      r#"sb:new 'sb def
+      sb "ab" sb:push drop
+      sb "c" sb:push drop
+      sb sb:len
+      sb sb:build"#,
+    )
+    .unwrap();
+    assert_eq!(
+      context.stack(),
+      &[
+        ExprKind::Integer(3).into(),
+        ExprKind::String("abc".into()).into(),
+      ]
+    );
+  }
+
+  #[test]
+  fn built_builders_push_nil() {
+    let context = run(
+      r#"sb:new 'sb def
+      sb sb:build drop
+      sb "a" sb:push
+      sb sb:len
+      sb sb:build"#,
+    )
+    .unwrap();
+    assert_eq!(
+      context.stack(),
+      &[
+        ExprKind::Nil.into(),
+        ExprKind::Nil.into(),
+        ExprKind::Nil.into()
+      ]
+    );
+  }
+
+  #[test]
+  fn rejects_forged_builders() {
+    let error =
+      run(r#"{type: "string-builder", id: 0} "a" sb:push"#).unwrap_err();
+    assert_eq!(error.reason, RunErrorReason::InvalidArgument);
+  }
+
+  #[test]
+  fn frees_builders_with_their_last_handle() {
+    let context = run(r#"sb:new "abc" sb:push"#).unwrap();
+
+    let builder = match &context.stack()[0].kind {
+      ExprKind::Foreign(x) => x.downcast::<StringBuilder>().unwrap(),
+      kind => panic!("expected a builder, found {kind}"),
+    };
+    let weak = Arc::downgrade(&builder);
+    drop(builder);
+
+    assert!(weak.upgrade().is_some());
+    drop(context);
+    assert!(weak.upgrade().is_none());
+  }
+}
@@ -3,6 +3,22 @@ use std::sync::Arc;
 
 use stack_core::prelude::*;
 
+const DOCS: &[(&str, &str)] = &[
+  (
+    "where",
+    "( symbol -- s )\n\nPushes where a symbol is defined, as `\"intrinsic\"`, \
+     `\"scope\"` or `\"module\"`, or `nil` if it isn't.",
+  ),
+  (
+    "dump",
+    "( -- list )\n\nPushes a list of the name and value of each item in scope.",
+  ),
+  (
+    "is-loaded",
+    "( symbol -- b )\n\nWhether a module has been loaded.",
+  ),
+];
+
 pub fn module() -> Module {
   let mut module = Module::new(Symbol::from_ref("scope"));
 
@@ -62,12 +78,12 @@ pub fn module() -> Module {
                 .into(),
             ];
 
-            ExprKind::List(list).into()
+            ExprKind::List(list.into()).into()
           })
           .collect();
 
         context
-          .stack_push(ExprKind::List(items).into())
+          .stack_push(ExprKind::List(items.into()).into())
           .map(|_| context)
       }),
     )
@@ -85,5 +101,7 @@ pub fn module() -> Module {
       }),
     );
 
+  crate::add_docs(&mut module, DOCS);
+
   module
 }
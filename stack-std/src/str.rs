@@ -1,12 +1,191 @@
 use std::sync::Arc;
 
 use compact_str::{CompactString, ToCompactString};
-use stack_core::prelude::*;
+use stack_core::{format_spec, module::Func, prelude::*};
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 // TODO: Add str:escape and str:unescape.
 
+const DOCS: &[(&str, &str)] = &[
+  (
+    "trim",
+    "( s -- s )\n\nRemoves the whitespace around a string.",
+  ),
+  (
+    "trim-start",
+    "( s -- s )\n\nRemoves the whitespace at the start of a string.",
+  ),
+  (
+    "trim-end",
+    "( s -- s )\n\nRemoves the whitespace at the end of a string.",
+  ),
+  (
+    "starts-with",
+    "( s prefix -- b )\n\nWhether a string starts with a prefix.",
+  ),
+  (
+    "ends-with",
+    "( s suffix -- b )\n\nWhether a string ends with a suffix.",
+  ),
+  (
+    "contains",
+    "( s pattern -- b )\n\nWhether a string contains a pattern.",
+  ),
+  (
+    "replace",
+    "( s from to -- s )\n\nReplaces each occurrence of `from` with `to`. \
+     Pushes `nil` if `from` is empty.",
+  ),
+  (
+    "repeat",
+    "( s n -- s )\n\nRepeats a string `n` times. Pushes `nil` if the result \
+     would be too long.",
+  ),
+  (
+    "pad-left",
+    "( s width fill -- s )\n\nPads the start of a string with a character \
+     until it's `width` graphemes long.",
+  ),
+  (
+    "pad-right",
+    "( s width fill -- s )\n\nPads the end of a string with a character \
+     until it's `width` graphemes long.",
+  ),
+  (
+    "format",
+    "( template args -- s )\n\nFills in the `{}` placeholders of a \
+     template, which can have a spec such as `{:>8.2}`, from a list.",
+  ),
+  (
+    "split-by",
+    "( s separator -- list )\n\nSplits a string by a separator.",
+  ),
+  (
+    "split-whitespace",
+    "( s -- list )\n\nSplits a string by whitespace.",
+  ),
+  ("lower", "( s -- s )\n\nConverts a string to lowercase."),
+  (
+    "to-lowercase",
+    "( s -- s )\n\nConverts a string to lowercase.",
+  ),
+  ("upper", "( s -- s )\n\nConverts a string to uppercase."),
+  (
+    "to-uppercase",
+    "( s -- s )\n\nConverts a string to uppercase.",
+  ),
+  ("is-ascii", "( s -- b )\n\nWhether a string is only ASCII."),
+  (
+    "is-char",
+    "( s -- b )\n\nWhether a string is a single grapheme.",
+  ),
+  (
+    "to-bytes",
+    "( s -- list )\n\nPushes the UTF-8 bytes of a string as integers.",
+  ),
+  (
+    "from-bytes",
+    "( list -- s )\n\nDecodes a list of byte integers as UTF-8, pushing an \
+     error if it's invalid.",
+  ),
+  (
+    "to-chars",
+    "( s -- list )\n\nSplits a string into its graphemes.",
+  ),
+  (
+    "graphemes",
+    "( s -- list )\n\nSplits a string into its graphemes.",
+  ),
+  (
+    "chars",
+    "( s -- list )\n\nSplits a string into its characters.",
+  ),
+  (
+    "bytes",
+    "( s -- bytes )\n\nPushes the UTF-8 bytes of a string.",
+  ),
+  (
+    "width",
+    "( s -- n )\n\nPushes the amount of columns a string takes up.",
+  ),
+  (
+    "from-chars",
+    "( list -- s )\n\nJoins a list of strings together.",
+  ),
+];
+
+#[cfg(feature = "regex")]
+const REGEX_DOCS: &[(&str, &str)] = &[
+  (
+    "regex-test",
+    "( s regex -- b )\n\nWhether a regex matches a string.",
+  ),
+  (
+    "regex-match",
+    "( s regex -- s )\n\nPushes the first match of a regex, or `nil`.",
+  ),
+  (
+    "match",
+    "( s regex -- s )\n\nPushes the first match of a regex, or `nil`.",
+  ),
+  (
+    "regex-match-all",
+    "( s regex -- list )\n\nPushes every match of a regex.",
+  ),
+  (
+    "find-all",
+    "( s regex -- list )\n\nPushes every match of a regex.",
+  ),
+  (
+    "captures",
+    "( s regex -- map )\n\nPushes the groups of the first match of a \
+     regex, keyed by their number and name, or `nil`.",
+  ),
+  (
+    "replace-regex",
+    "( s regex to -- s )\n\nReplaces each match of a regex with `to`, \
+     which can refer to groups as `$1` or `$name`.",
+  ),
+];
+
+/// The most bytes a string made by `repeat` or padding can have, above which
+/// they push `nil`, rather than failing to allocate it.
+const MAX_LEN: usize = 1 << 30;
+
+/// Returns the length of `len` bytes repeated `count` times, if it's no more
+/// than [`MAX_LEN`].
+fn repeated_len(len: usize, count: usize) -> Option<usize> {
+  len.checked_mul(count).filter(|x| *x <= MAX_LEN)
+}
+
 pub fn module() -> Module {
+  let lower: Func = Arc::new(|_, mut context, expr| {
+    let item = context.stack_pop(&expr)?;
+
+    let kind = match item.kind {
+      ExprKind::String(ref x) => ExprKind::String(x.to_lowercase()),
+      _ => ExprKind::Nil,
+    };
+
+    context.stack_push(kind.into())?;
+
+    Ok(context)
+  });
+
+  let upper: Func = Arc::new(|_, mut context, expr| {
+    let item = context.stack_pop(&expr)?;
+
+    let kind = match item.kind {
+      ExprKind::String(ref x) => ExprKind::String(x.to_uppercase()),
+      _ => ExprKind::Nil,
+    };
+
+    context.stack_push(kind.into())?;
+
+    Ok(context)
+  });
+
   let mut module = Module::new(Symbol::from_ref("str"))
     .with_func(
       Symbol::from_ref("trim-start"),
@@ -90,17 +269,15 @@ pub fn module() -> Module {
       }),
     )
     .with_func(
-      Symbol::from_ref("split-by"),
+      Symbol::from_ref("contains"),
       Arc::new(|_, mut context, expr| {
         let patt = context.stack_pop(&expr)?;
         let item = context.stack_pop(&expr)?;
 
-        let kind = match (item.kind.clone(), patt.kind.clone()) {
-          (ExprKind::String(ref x), ExprKind::String(ref y)) => ExprKind::List(
-            x.split(y.as_str())
-              .map(|x| ExprKind::String(x.into()).into())
-              .collect(),
-          ),
+        let kind = match (item.kind, patt.kind) {
+          (ExprKind::String(ref x), ExprKind::String(ref y)) => {
+            ExprKind::Boolean(x.contains(y.as_str()))
+          }
           _ => ExprKind::Nil,
         };
 
@@ -110,16 +287,20 @@ pub fn module() -> Module {
       }),
     )
     .with_func(
-      Symbol::from_ref("split-whitespace"),
+      Symbol::from_ref("replace"),
       Arc::new(|_, mut context, expr| {
+        let to = context.stack_pop(&expr)?;
+        let from = context.stack_pop(&expr)?;
         let item = context.stack_pop(&expr)?;
 
-        let kind = match item.kind {
-          ExprKind::String(ref x) => ExprKind::List(
-            x.split_whitespace()
-              .map(|x| ExprKind::String(x.into()).into())
-              .collect(),
-          ),
+        let kind = match (item.kind, from.kind, to.kind) {
+          (
+            ExprKind::String(ref x),
+            ExprKind::String(ref from),
+            ExprKind::String(ref to),
+          ) if !from.is_empty() => {
+            ExprKind::String(x.replace(from.as_str(), to.as_str()).into())
+          }
           _ => ExprKind::Nil,
         };
 
@@ -129,12 +310,21 @@ pub fn module() -> Module {
       }),
     )
     .with_func(
-      Symbol::from_ref("to-lowercase"),
+      Symbol::from_ref("repeat"),
       Arc::new(|_, mut context, expr| {
+        let count = context.stack_pop(&expr)?;
         let item = context.stack_pop(&expr)?;
 
-        let kind = match item.kind {
-          ExprKind::String(ref x) => ExprKind::String(x.to_lowercase()),
+        let kind = match (item.kind, count.kind) {
+          (ExprKind::String(ref x), ExprKind::Integer(count)) => {
+            match usize::try_from(count)
+              .ok()
+              .and_then(|count| repeated_len(x.len(), count).map(|_| count))
+            {
+              Some(count) => ExprKind::String(x.repeat(count)),
+              None => ExprKind::Nil,
+            }
+          }
           _ => ExprKind::Nil,
         };
 
@@ -144,12 +334,63 @@ pub fn module() -> Module {
       }),
     )
     .with_func(
-      Symbol::from_ref("to-uppercase"),
+      Symbol::from_ref("pad-left"),
+      Arc::new(|_, context, expr| pad(context, expr, true)),
+    )
+    .with_func(
+      Symbol::from_ref("pad-right"),
+      Arc::new(|_, context, expr| pad(context, expr, false)),
+    )
+    .with_func(
+      Symbol::from_ref("format"),
+      Arc::new(|_, mut context, expr| {
+        let args = context.stack_pop(&expr)?;
+        let template = context.stack_pop(&expr)?;
+
+        let kind = match (template.kind, args.kind) {
+          (ExprKind::String(ref x), ExprKind::List(ref args)) => {
+            format_spec::format(x, args)
+              .map_or(ExprKind::Nil, |x| ExprKind::String(x.into()))
+          }
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .with_func(
+      Symbol::from_ref("split-by"),
+      Arc::new(|_, mut context, expr| {
+        let patt = context.stack_pop(&expr)?;
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match (item.kind.clone(), patt.kind.clone()) {
+          (ExprKind::String(ref x), ExprKind::String(ref y)) => ExprKind::List(
+            x.split(y.as_str())
+              .map(|x| ExprKind::String(x.into()).into())
+              .collect(),
+          ),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .with_func(
+      Symbol::from_ref("split-whitespace"),
       Arc::new(|_, mut context, expr| {
         let item = context.stack_pop(&expr)?;
 
         let kind = match item.kind {
-          ExprKind::String(ref x) => ExprKind::String(x.to_uppercase()),
+          ExprKind::String(ref x) => ExprKind::List(
+            x.split_whitespace()
+              .map(|x| ExprKind::String(x.into()).into())
+              .collect(),
+          ),
           _ => ExprKind::Nil,
         };
 
@@ -158,6 +399,10 @@ pub fn module() -> Module {
         Ok(context)
       }),
     )
+    .with_func(Symbol::from_ref("lower"), lower.clone())
+    .with_func(Symbol::from_ref("to-lowercase"), lower)
+    .with_func(Symbol::from_ref("upper"), upper.clone())
+    .with_func(Symbol::from_ref("to-uppercase"), upper)
     .with_func(
       Symbol::from_ref("is-ascii"),
       Arc::new(|_, mut context, expr| {
@@ -226,10 +471,11 @@ pub fn module() -> Module {
               }
               _ => Err(()),
             })
-            .map(|x| {
-              CompactString::from_utf8(x)
-                .map(ExprKind::String)
-                .unwrap_or(ExprKind::Nil)
+            .map(|x| match CompactString::from_utf8(x) {
+              Ok(x) => ExprKind::String(x),
+              Err(e) => ExprKind::Error(Box::new(
+                ExprKind::String(e.to_compact_string()).into(),
+              )),
             })
             .unwrap_or(ExprKind::Nil),
           _ => ExprKind::Nil,
@@ -260,6 +506,72 @@ pub fn module() -> Module {
         Ok(context)
       }),
     )
+    .with_func(
+      Symbol::from_ref("graphemes"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::String(ref x) => ExprKind::List(
+            x.graphemes(true)
+              .map(|x| ExprKind::String(x.into()).into())
+              .collect(),
+          ),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .with_func(
+      Symbol::from_ref("chars"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::String(ref x) => ExprKind::List(
+            x.chars().map(|x| ExprKind::Char(x).into()).collect(),
+          ),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .with_func(
+      Symbol::from_ref("bytes"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::String(ref x) => ExprKind::Bytes(x.as_bytes().to_vec()),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .with_func(
+      Symbol::from_ref("width"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::String(ref x) => ExprKind::Integer(x.width() as i64),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
     .with_func(
       Symbol::from_ref("from-chars"),
       Arc::new(|_, mut context, expr| {
@@ -289,108 +601,233 @@ pub fn module() -> Module {
 
   #[cfg(feature = "regex")]
   {
-    use regex::Regex;
-
     module = module
       .with_func(
         Symbol::from_ref("regex-test"),
-        Arc::new(|_, mut context, expr| {
-          let pattern = context.stack_pop(&expr)?;
-          let string = context.stack_pop(&expr)?;
-
-          let kind = match (string.kind, pattern.kind) {
-            (ExprKind::String(ref string), ExprKind::String(ref pattern)) => {
-              let re = Regex::new(pattern);
-              match re {
-                Ok(re) => ExprKind::Boolean(re.captures(string).is_some()),
-                Err(err) => {
-                  todo!()
-                }
-              }
-            }
-            (_, _) => ExprKind::Nil,
-          };
-
-          context.stack_push(kind.into())?;
-
-          Ok(context)
+        Arc::new(|_, context, expr| {
+          with_regex(context, expr, |string, re| {
+            ExprKind::Boolean(re.is_match(string))
+          })
         }),
       )
+      .with_func(Symbol::from_ref("regex-match"), Arc::new(regex_match))
+      .with_func(Symbol::from_ref("match"), Arc::new(regex_match))
+      .with_func(Symbol::from_ref("regex-match-all"), Arc::new(find_all))
+      .with_func(Symbol::from_ref("find-all"), Arc::new(find_all))
       .with_func(
-        Symbol::from_ref("regex-match"),
-        Arc::new(|_, mut context, expr| {
-          let pattern = context.stack_pop(&expr)?;
-          let string = context.stack_pop(&expr)?;
-
-          let kind = match (string.kind, pattern.kind) {
-            (ExprKind::String(ref string), ExprKind::String(ref pattern)) => {
-              let re = Regex::new(pattern);
-              match re {
-                Ok(re) => re
-                  .find(string)
-                  .map(|m| {
-                    ExprKind::String(
-                      string
-                        .chars()
-                        .skip(m.start())
-                        .take(m.end() - m.start())
-                        .collect::<String>()
-                        .into(),
-                    )
-                  })
-                  .unwrap_or(ExprKind::Nil),
-                Err(err) => {
-                  todo!()
-                }
-              }
-            }
-            (_, _) => ExprKind::Nil,
-          };
-
-          context.stack_push(kind.into())?;
-
-          Ok(context)
+        Symbol::from_ref("captures"),
+        Arc::new(|_, context, expr| {
+          with_regex(context, expr, |string, re| {
+            let Some(captures) = re.captures(string) else {
+              return ExprKind::Nil;
+            };
+
+            let group = |x: Option<regex::Match>| match x {
+              Some(x) => ExprKind::String(x.as_str().into()).into(),
+              None => ExprKind::Nil.into(),
+            };
+
+            let numbered = captures
+              .iter()
+              .enumerate()
+              .map(|(i, x)| (MapKey::Integer(i as i64), group(x)));
+            let named = re.capture_names().flatten().map(|name| {
+              (MapKey::String(name.into()), group(captures.name(name)))
+            });
+
+            ExprKind::Map(numbered.chain(named).collect())
+          })
         }),
       )
       .with_func(
-        Symbol::from_ref("regex-match-all"),
+        Symbol::from_ref("replace-regex"),
         Arc::new(|_, mut context, expr| {
-          let pattern = context.stack_pop(&expr)?;
-          let string = context.stack_pop(&expr)?;
-
-          let kind = match (string.kind, pattern.kind) {
-            (ExprKind::String(ref string), ExprKind::String(ref pattern)) => {
-              let re = Regex::new(pattern);
-              match re {
-                Ok(re) => ExprKind::List(
-                  re.find_iter(string)
-                    .map(|m| {
-                      ExprKind::String(
-                        string
-                          .chars()
-                          .skip(m.start())
-                          .take(m.end() - m.start())
-                          .collect::<String>()
-                          .into(),
-                      )
-                      .into()
-                    })
-                    .collect::<Vec<_>>(),
-                ),
-                Err(err) => {
-                  todo!()
-                }
-              }
-            }
-            (_, _) => ExprKind::Nil,
-          };
+          let to = context.stack_pop(&expr)?;
 
-          context.stack_push(kind.into())?;
+          match to.kind {
+            ExprKind::String(to) => {
+              with_regex(context, expr, move |string, re| {
+                ExprKind::String(re.replace_all(string, to.as_str()).into())
+              })
+            }
+            _ => {
+              context.stack_pop(&expr)?;
+              context.stack_pop(&expr)?;
+              context.stack_push(ExprKind::Nil.into())?;
 
-          Ok(context)
+              Ok(context)
+            }
+          }
         }),
-      )
+      );
+
+    crate::add_docs(&mut module, REGEX_DOCS);
   }
 
+  crate::add_docs(&mut module, DOCS);
+
   module
 }
+
+/// Pads a string to a width with a fill, which must be a single character.
+/// The width is counted in graphemes, so that accented characters and the
+/// like only count once. Pushes `nil` if the result would be longer than
+/// [`MAX_LEN`].
+fn pad(
+  mut context: Context,
+  expr: Expr,
+  is_left: bool,
+) -> Result<Context, RunError> {
+  let fill = context.stack_pop(&expr)?;
+  let width = context.stack_pop(&expr)?;
+  let item = context.stack_pop(&expr)?;
+
+  let fill = match fill.kind {
+    ExprKind::Char(x) => Some(CompactString::from(x.to_string())),
+    ExprKind::String(x) if x.graphemes(true).count() == 1 => Some(x),
+    _ => None,
+  };
+
+  let kind = match (item.kind, width.kind, fill) {
+    (ExprKind::String(x), ExprKind::Integer(width), Some(fill)) => {
+      let len = x.graphemes(true).count();
+      let count = usize::try_from(width).unwrap_or(0).saturating_sub(len);
+
+      match repeated_len(fill.len(), count)
+        .and_then(|padding| padding.checked_add(x.len()))
+        .filter(|x| *x <= MAX_LEN)
+      {
+        Some(_) => {
+          let padding = fill.repeat(count);
+
+          match is_left {
+            true => ExprKind::String(format!("{padding}{x}").into()),
+            false => ExprKind::String(format!("{x}{padding}").into()),
+          }
+        }
+        None => ExprKind::Nil,
+      }
+    }
+    _ => ExprKind::Nil,
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+/// Pops a string and a pattern, and pushes what `f` returns for them, or an
+/// error if the pattern isn't a valid regex.
+#[cfg(feature = "regex")]
+fn with_regex<F>(
+  mut context: Context,
+  expr: Expr,
+  f: F,
+) -> Result<Context, RunError>
+where
+  F: FnOnce(&str, &regex::Regex) -> ExprKind,
+{
+  let pattern = context.stack_pop(&expr)?;
+  let string = context.stack_pop(&expr)?;
+
+  let kind = match (string.kind, pattern.kind) {
+    (ExprKind::String(ref string), ExprKind::String(ref pattern)) => {
+      match regex::Regex::new(pattern) {
+        Ok(re) => f(string, &re),
+        Err(e) => ExprKind::Error(Box::new(
+          ExprKind::String(e.to_compact_string()).into(),
+        )),
+      }
+    }
+    _ => ExprKind::Nil,
+  };
+
+  context.stack_push(kind.into())?;
+
+  Ok(context)
+}
+
+/// Pushes the first match of a regex, or nil if there isn't one.
+#[cfg(feature = "regex")]
+fn regex_match(
+  _: &Engine,
+  context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  with_regex(context, expr, |string, re| match re.find(string) {
+    Some(x) => ExprKind::String(x.as_str().into()),
+    None => ExprKind::Nil,
+  })
+}
+
+/// Pushes a list of every match of a regex.
+#[cfg(feature = "regex")]
+fn find_all(
+  _: &Engine,
+  context: Context,
+  expr: Expr,
+) -> Result<Context, RunError> {
+  with_regex(context, expr, |string, re| {
+    ExprKind::List(
+      re.find_iter(string)
+        .map(|x| ExprKind::String(x.as_str().into()).into())
+        .collect(),
+    )
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(code: &str) -> Vec<Expr> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    let mut context = Engine::new()
+      .with_module(module())
+      .run(Context::new(), exprs)
+      .unwrap();
+
+    core::mem::take(context.stack_mut())
+  }
+
+  #[test]
+  fn repeats_strings() {
+    assert_eq!(
+      run(r#""ab" 3 str:repeat "ab" -1 str:repeat"#),
+      vec![
+        ExprKind::String("ababab".into()).into(),
+        ExprKind::Nil.into()
+      ]
+    );
+  }
+
+  #[test]
+  fn pads_strings() {
+    assert_eq!(
+      run(
+        r#""ab" 4 '-' str:pad-left "ab" 4 "-" str:pad-right "ab" 1 '-' str:pad-left"#
+      ),
+      vec![
+        ExprKind::String("--ab".into()).into(),
+        ExprKind::String("ab--".into()).into(),
+        ExprKind::String("ab".into()).into(),
+      ]
+    );
+  }
+
+  #[test]
+  fn rejects_huge_lengths() {
+    assert_eq!(
+      run(
+        r#""a" 4611686018427387904 str:repeat
+        "a" 4611686018427387904 '-' str:pad-left
+        "a" 4611686018427387904 '-' str:pad-right
+        "ab" 1073741824 str:repeat"#
+      ),
+      vec![ExprKind::Nil.into(); 4]
+    );
+  }
+}
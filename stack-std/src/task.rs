@@ -0,0 +1,137 @@
+//! Running blocks concurrently, on other threads.
+//!
+//! `task:spawn` runs a block in a new context on another thread and pushes a
+//! handle to it. The new context starts with an empty stack, but shares the
+//! definitions and imports of the one which spawned it. `task:join` waits for
+//! the task to finish and pushes the top of its stack, `nil` if it's empty or
+//! the task has already been joined, or an error if the task failed.
+//!
+//! Each task's thread has a stack of [`THREAD_STACK_SIZE`], like generators,
+//! so that tasks can make deeply nested calls.
+//!
+//! Tasks can pass values to each other with the `chan` module.
+
+use std::{
+  sync::{Arc, Mutex},
+  thread::{self, JoinHandle},
+};
+
+use stack_core::{engine::THREAD_STACK_SIZE, prelude::*, scheduler::Mailbox};
+
+/// A task which is running, or has finished but hasn't been joined yet.
+#[derive(Debug)]
+pub struct Task {
+  handle: Mutex<Option<JoinHandle<Result<Context, RunError>>>>,
+}
+
+impl Task {
+  /// Waits for the task to finish, returning the top of its stack. Returns
+  /// [`None`] if it has already been joined.
+  pub fn join(&self) -> Option<Expr> {
+    let handle = self.handle.lock().unwrap().take()?;
+
+    let kind = match handle.join() {
+      Ok(Ok(mut context)) => {
+        return Some(context.stack_mut().pop().unwrap_or(ExprKind::Nil.into()))
+      }
+      // A thrown error is passed on as it is.
+      Ok(Err(RunError {
+        reason: RunErrorReason::Thrown,
+        expr,
+        ..
+      }))
+        if matches!(expr.kind, ExprKind::Error(_)) =>
+      {
+        return Some(*expr)
+      }
+      Ok(Err(e)) => ExprKind::String(e.reason.to_string().into()),
+      Err(_) => ExprKind::String("task panicked".into()),
+    };
+
+    Some(ExprKind::Error(Box::new(kind.into())).into())
+  }
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "spawn",
+    "( block -- task )\n\nRuns a block on another thread, pushing a handle to \
+     it.",
+  ),
+  (
+    "join",
+    "( task -- item )\n\nWaits for a task to finish, pushing the top of its \
+     stack.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("task"));
+
+  module
+    .add_func(
+      Symbol::from_ref("spawn"),
+      Arc::new(|engine, mut context, expr| {
+        let block = context.stack_pop(&expr)?;
+
+        let mut task = context.clone();
+        task.stack_mut().clear();
+        *task.journal_mut() = None;
+        *task.mailbox_mut() = Mailbox::default();
+
+        // Otherwise, the task would pick the same random numbers.
+        let seed = context.rng_mut().next_u64();
+        task.rng_mut().seed(seed);
+
+        let engine = engine.clone();
+        let handle = thread::Builder::new()
+          .name("task".into())
+          .stack_size(THREAD_STACK_SIZE)
+          .spawn(move || engine.call_expr(task, block))
+          .expect("failed to spawn a task thread");
+
+        context.stack_push(
+          ExprKind::Foreign(Foreign::new(Task {
+            handle: Mutex::new(Some(handle)),
+          }))
+          .into(),
+        )?;
+
+        Ok(context)
+      }),
+    )
+    .add_fn(Symbol::from_ref("join"), |task: Arc<Task>| task.join());
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(code: &str) -> Result<Context, RunError> {
+    let source = Source::new("", code);
+    let mut lexer = Lexer::new(source);
+    let exprs = parse(&mut lexer).unwrap();
+
+    Engine::new()
+      .with_module(module())
+      .run(Context::new(), exprs)
+  }
+
+  #[test]
+  fn joins_the_top_of_the_stack() {
+    let context = run("'[1 2 +] task:spawn task:join").unwrap();
+    assert_eq!(context.stack(), &[ExprKind::Integer(3).into()]);
+  }
+
+  #[test]
+  fn runs_nested_calls() {
+    let context =
+      run("'[[[[[[1] call] call] call] call] call] task:spawn task:join")
+        .unwrap();
+    assert_eq!(context.stack(), &[ExprKind::Integer(1).into()]);
+  }
+}
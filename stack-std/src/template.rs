@@ -0,0 +1,212 @@
+//! Templates with `{{name}}` placeholders, which are filled in from a record.
+//!
+//! - `{{name}}` inserts a value, where `name` can be a path such as `a.b`.
+//! - `{{#each name}}...{{/each}}` repeats its body for each item of a list.
+//!   Inside, `{{.}}` is the item, `{{@index}}` is its index, and the fields of
+//!   a record item can be used directly.
+//! - `{{#if name}}...{{else}}...{{/if}}` renders its body if the value is
+//!   truthy, i.e., not `nil`, `false`, or an empty string or list.
+
+use std::sync::Arc;
+
+use stack_core::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+  Text(String),
+  Var(String),
+  Each(String, Vec<Node>),
+  If(String, Vec<Node>, Vec<Node>),
+}
+
+/// Parses nodes until the end of the template or the closing tag `end`.
+///
+/// Returns the nodes and the tag that ended them.
+fn parse_nodes<'a>(
+  source: &mut &'a str,
+  end: &[&str],
+) -> Option<(Vec<Node>, Option<&'a str>)> {
+  let mut nodes = Vec::new();
+
+  loop {
+    let Some(start) = source.find("{{") else {
+      if !source.is_empty() {
+        nodes.push(Node::Text(source.to_string()));
+      }
+      *source = "";
+
+      return end.is_empty().then_some((nodes, None));
+    };
+
+    if start > 0 {
+      nodes.push(Node::Text(source[..start].to_string()));
+    }
+
+    let rest = &source[start + 2..];
+    let close = rest.find("}}")?;
+    let tag = rest[..close].trim();
+    *source = &rest[close + 2..];
+
+    if end.contains(&tag) {
+      return Some((nodes, Some(tag)));
+    }
+
+    if let Some(name) = tag.strip_prefix("#each ") {
+      let (body, _) = parse_nodes(source, &["/each"])?;
+      nodes.push(Node::Each(name.trim().into(), body));
+    } else if let Some(name) = tag.strip_prefix("#if ") {
+      let (then, tag) = parse_nodes(source, &["else", "/if"])?;
+      let otherwise = match tag {
+        Some("else") => parse_nodes(source, &["/if"])?.0,
+        _ => Vec::new(),
+      };
+
+      nodes.push(Node::If(name.trim().into(), then, otherwise));
+    } else if tag.starts_with(['#', '/']) || tag == "else" {
+      return None;
+    } else {
+      nodes.push(Node::Var(tag.into()));
+    }
+  }
+}
+
+fn parse_template(mut source: &str) -> Option<Vec<Node>> {
+  parse_nodes(&mut source, &[]).map(|(nodes, _)| nodes)
+}
+
+/// A level of values that names are looked up in, from the inside out.
+struct Frame {
+  value: Expr,
+  index: Option<usize>,
+}
+
+fn lookup(frames: &[Frame], path: &str) -> Option<Expr> {
+  let frame = frames.last()?;
+
+  match path {
+    "." | "this" => return Some(frame.value.clone()),
+    "@index" => return frame.index.map(|x| ExprKind::Integer(x as i64).into()),
+    _ => {}
+  }
+
+  let mut segments = path.split('.');
+  let first = Symbol::from_ref(segments.next()?);
+
+  let mut value =
+    frames
+      .iter()
+      .rev()
+      .find_map(|frame| match &frame.value.kind {
+        ExprKind::Record(x) => x.get(&first).cloned(),
+        _ => None,
+      })?;
+
+  for segment in segments {
+    value = match value.kind {
+      ExprKind::Record(ref x) => x.get(&Symbol::from_ref(segment))?.clone(),
+      _ => return None,
+    };
+  }
+
+  Some(value)
+}
+
+fn is_truthy(expr: &Expr) -> bool {
+  match &expr.kind {
+    ExprKind::Nil | ExprKind::Boolean(false) => false,
+    ExprKind::String(x) => !x.is_empty(),
+    ExprKind::List(x) => !x.is_empty(),
+    _ => true,
+  }
+}
+
+fn render_nodes(nodes: &[Node], frames: &mut Vec<Frame>, output: &mut String) {
+  for node in nodes {
+    match node {
+      Node::Text(x) => output.push_str(x),
+      Node::Var(path) => {
+        if let Some(value) = lookup(frames, path) {
+          match value.kind {
+            ExprKind::String(ref x) => output.push_str(x),
+            ref kind => output.push_str(&kind.to_string()),
+          }
+        }
+      }
+      Node::Each(path, body) => {
+        if let Some(ExprKind::List(items)) =
+          lookup(frames, path).map(|x| x.kind)
+        {
+          for (index, item) in items.into_iter().enumerate() {
+            frames.push(Frame {
+              value: item,
+              index: Some(index),
+            });
+            render_nodes(body, frames, output);
+            frames.pop();
+          }
+        }
+      }
+      Node::If(path, then, otherwise) => {
+        let branch = match lookup(frames, path) {
+          Some(ref value) if is_truthy(value) => then,
+          _ => otherwise,
+        };
+
+        render_nodes(branch, frames, output);
+      }
+    }
+  }
+}
+
+/// Renders `template` with the values of `context`, or returns [`None`] if the
+/// template is invalid.
+pub fn render(template: &str, context: &Expr) -> Option<String> {
+  let nodes = parse_template(template)?;
+
+  let mut output = String::new();
+  render_nodes(
+    &nodes,
+    &mut vec![Frame {
+      value: context.clone(),
+      index: None,
+    }],
+    &mut output,
+  );
+
+  Some(output)
+}
+
+const DOCS: &[(&str, &str)] = &[(
+  "render",
+  "( template values -- s )\n\nRenders a template with the values of a \
+     record, or pushes `nil` if it's invalid.",
+)];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("template"));
+
+  module.add_func(
+    Symbol::from_ref("render"),
+    Arc::new(|_, mut context, expr| {
+      let values = context.stack_pop(&expr)?;
+      let template = context.stack_pop(&expr)?;
+
+      let kind = match (&template.kind, &values.kind) {
+        (ExprKind::String(template), ExprKind::Record(_)) => {
+          render(template, &values)
+            .map(|x| ExprKind::String(x.into()))
+            .unwrap_or(ExprKind::Nil)
+        }
+        _ => ExprKind::Nil,
+      };
+
+      context.stack_push(kind.into())?;
+
+      Ok(context)
+    }),
+  );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
@@ -0,0 +1,245 @@
+//! Reading the clock, sleeping, and formatting and parsing dates.
+//!
+//! Times are integers of milliseconds since the Unix epoch, and durations are
+//! integers of milliseconds, so they can be added and subtracted with the
+//! usual intrinsics. `time:seconds`, `time:minutes`, `time:hours` and
+//! `time:days` turn an amount of each into a duration.
+//!
+//! `time:format` and `time:parse` use strftime-like patterns, such as
+//! `"%Y-%m-%d %H:%M:%S"`, in UTC. Parsing a pattern without a time gives
+//! midnight, and one with an offset (`%z`) is converted to UTC.
+
+use std::{
+  sync::Arc,
+  time::{Duration, Instant},
+};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use compact_str::ToCompactString;
+use stack_core::prelude::*;
+
+fn now() -> i64 {
+  Utc::now().timestamp_millis()
+}
+
+fn error(message: impl ToCompactString) -> ExprKind {
+  ExprKind::Error(Box::new(
+    ExprKind::String(message.to_compact_string()).into(),
+  ))
+}
+
+/// Formats a time with a pattern, returning [`None`] if the time is out of
+/// range or the pattern is invalid.
+pub fn format(millis: i64, pattern: &str) -> Option<String> {
+  use std::fmt::Write;
+
+  let time = DateTime::from_timestamp_millis(millis)?;
+
+  // An invalid pattern is only found when it's written.
+  let mut string = String::new();
+  write!(string, "{}", time.format(pattern)).ok()?;
+
+  Some(string)
+}
+
+/// Parses a time with a pattern.
+pub fn parse(string: &str, pattern: &str) -> Result<i64, chrono::ParseError> {
+  DateTime::parse_from_str(string, pattern)
+    .map(|x| x.timestamp_millis())
+    .or_else(|_| {
+      NaiveDateTime::parse_from_str(string, pattern)
+        .map(|x| x.and_utc().timestamp_millis())
+    })
+    .or_else(|e| {
+      NaiveDate::parse_from_str(string, pattern)
+        .map(|x| x.and_time(Default::default()).and_utc().timestamp_millis())
+        .map_err(|_| e)
+    })
+}
+
+/// Adds a function which turns an amount of a unit into a duration.
+fn add_unit(module: &mut Module, name: &str, millis: i64) {
+  module.add_func(
+    Symbol::from_ref(name),
+    Arc::new(move |_, mut context, expr| {
+      let item = context.stack_pop(&expr)?;
+
+      let kind = match item.kind {
+        ExprKind::Integer(x) => x
+          .checked_mul(millis)
+          .map_or(ExprKind::Nil, ExprKind::Integer),
+        ExprKind::Float(x) => ExprKind::Integer((x * millis as f64) as i64),
+        _ => ExprKind::Nil,
+      };
+
+      context.stack_push(kind.into())?;
+
+      Ok(context)
+    }),
+  );
+}
+
+/// Adds `time:sleep`, which doesn't block the runtime with the `async`
+/// feature.
+fn add_sleep(module: &mut Module) {
+  #[cfg(not(feature = "async"))]
+  module.add_func(
+    Symbol::from_ref("sleep"),
+    Arc::new(|_, mut context, expr| {
+      let item = context.stack_pop(&expr)?;
+
+      if let ExprKind::Integer(x) = item.kind {
+        std::thread::sleep(Duration::from_millis(x.max(0) as u64));
+      }
+
+      Ok(context)
+    }),
+  );
+
+  #[cfg(feature = "async")]
+  module.add_async_func(
+    Symbol::from_ref("sleep"),
+    Arc::new(|mut context, expr| {
+      Box::pin(async move {
+        let item = context.stack_pop(&expr)?;
+
+        if let ExprKind::Integer(x) = item.kind {
+          tokio::time::sleep(Duration::from_millis(x.max(0) as u64)).await;
+        }
+
+        Ok(context)
+      })
+    }),
+  );
+}
+
+const DOCS: &[(&str, &str)] = &[
+  ("now", "( -- time )\n\nPushes the current time."),
+  (
+    "monotonic",
+    "( -- n )\n\nPushes the milliseconds since the module was loaded, from a \
+     clock which never goes backwards.",
+  ),
+  (
+    "since",
+    "( time -- duration )\n\nPushes the time elapsed since a time.",
+  ),
+  (
+    "format",
+    "( time pattern -- s )\n\nFormats a time with a pattern, such as \
+     `\"%Y-%m-%d\"`.",
+  ),
+  (
+    "parse",
+    "( s pattern -- time )\n\nParses a time with a pattern, pushing an error \
+     if it doesn't match.",
+  ),
+  (
+    "seconds",
+    "( n -- duration )\n\nPushes the duration of an amount of seconds.",
+  ),
+  (
+    "minutes",
+    "( n -- duration )\n\nPushes the duration of an amount of minutes.",
+  ),
+  (
+    "hours",
+    "( n -- duration )\n\nPushes the duration of an amount of hours.",
+  ),
+  (
+    "days",
+    "( n -- duration )\n\nPushes the duration of an amount of days.",
+  ),
+  ("sleep", "( duration -- )\n\nWaits for a duration."),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("time"));
+  let start = Instant::now();
+
+  module
+    .add_func(
+      Symbol::from_ref("now"),
+      Arc::new(|_, mut context, _| {
+        context.stack_push(ExprKind::Integer(now()).into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("monotonic"),
+      Arc::new(move |_, mut context, _| {
+        // Only the difference between two readings is meaningful.
+        let millis = start.elapsed().as_millis() as i64;
+        context.stack_push(ExprKind::Integer(millis).into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("since"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::Integer(x) => ExprKind::Integer(now().saturating_sub(x)),
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("format"),
+      Arc::new(|_, mut context, expr| {
+        let pattern = context.stack_pop(&expr)?;
+        let time = context.stack_pop(&expr)?;
+
+        let kind = match (time.kind, pattern.kind) {
+          (ExprKind::Integer(time), ExprKind::String(ref pattern)) => {
+            format(time, pattern)
+              .map_or(ExprKind::Nil, |x| ExprKind::String(x.into()))
+          }
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("parse"),
+      Arc::new(|_, mut context, expr| {
+        let pattern = context.stack_pop(&expr)?;
+        let string = context.stack_pop(&expr)?;
+
+        let kind = match (string.kind, pattern.kind) {
+          (ExprKind::String(ref string), ExprKind::String(ref pattern)) => {
+            match parse(string, pattern) {
+              Ok(x) => ExprKind::Integer(x),
+              Err(e) => error(e),
+            }
+          }
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    );
+
+  add_unit(&mut module, "seconds", 1000);
+  add_unit(&mut module, "minutes", 60 * 1000);
+  add_unit(&mut module, "hours", 60 * 60 * 1000);
+  add_unit(&mut module, "days", 24 * 60 * 60 * 1000);
+
+  add_sleep(&mut module);
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
@@ -0,0 +1,169 @@
+//! Parsing and emitting TOML, with the same API as the `json` module.
+//!
+//! Tables become records, arrays become lists, and dates and times become
+//! strings. A document can only be emitted from a record or map, and values
+//! with no TOML equivalent, such as `nil`, can't be emitted.
+
+use std::sync::Arc;
+
+use compact_str::ToCompactString;
+use stack_core::prelude::*;
+use toml_edit::{Array, Document, InlineTable, Item, Table, Value};
+
+fn from_item(item: &Item) -> ExprKind {
+  match item {
+    Item::None => ExprKind::Nil,
+    Item::Value(x) => from_value(x),
+    Item::Table(x) => ExprKind::Record(
+      x.iter()
+        .map(|(key, item)| (Symbol::from_ref(key), from_item(item).into()))
+        .collect(),
+    ),
+    Item::ArrayOfTables(x) => ExprKind::List(
+      x.iter()
+        .map(|x| from_item(&Item::Table(x.clone())).into())
+        .collect(),
+    ),
+  }
+}
+
+fn from_value(value: &Value) -> ExprKind {
+  match value {
+    Value::String(x) => ExprKind::String(x.value().into()),
+    Value::Integer(x) => ExprKind::Integer(*x.value()),
+    Value::Float(x) => ExprKind::Float(*x.value()),
+    Value::Boolean(x) => ExprKind::Boolean(*x.value()),
+    Value::Datetime(x) => ExprKind::String(x.value().to_compact_string()),
+    Value::Array(x) => {
+      ExprKind::List(x.iter().map(|x| from_value(x).into()).collect())
+    }
+    Value::InlineTable(x) => ExprKind::Record(
+      x.iter()
+        .map(|(key, value)| (Symbol::from_ref(key), from_value(value).into()))
+        .collect(),
+    ),
+  }
+}
+
+/// Returns the entries of a record or map, with a record's keys sorted so
+/// that the output is stable.
+fn entries(kind: &ExprKind) -> Option<Vec<(String, &Expr)>> {
+  match kind {
+    ExprKind::Record(x) => {
+      let mut entries = x
+        .iter()
+        .map(|(key, value)| (key.as_str().to_string(), value))
+        .collect::<Vec<_>>();
+      entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+      Some(entries)
+    }
+    ExprKind::Map(x) => Some(
+      x.iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect(),
+    ),
+    _ => None,
+  }
+}
+
+fn to_table(kind: &ExprKind) -> Option<Table> {
+  let mut table = Table::new();
+
+  for (key, value) in entries(kind)? {
+    let item = match entries(&value.kind) {
+      Some(_) => Item::Table(to_table(&value.kind)?),
+      None => Item::Value(to_value(&value.kind)?),
+    };
+
+    table.insert(&key, item);
+  }
+
+  Some(table)
+}
+
+fn to_value(kind: &ExprKind) -> Option<Value> {
+  Some(match kind {
+    ExprKind::Boolean(x) => (*x).into(),
+    ExprKind::Integer(x) => (*x).into(),
+    ExprKind::Float(x) => (*x).into(),
+    ExprKind::String(x) => x.as_str().into(),
+    ExprKind::Char(x) => x.to_string().into(),
+    ExprKind::Symbol(x) => x.as_str().into(),
+    ExprKind::List(x) => Value::Array(
+      x.iter()
+        .map(|x| to_value(&x.kind))
+        .collect::<Option<Array>>()?,
+    ),
+    ExprKind::Set(x) => Value::Array(
+      x.iter()
+        .map(|x| to_value(&x.clone().into()))
+        .collect::<Option<Array>>()?,
+    ),
+    ExprKind::Record(_) | ExprKind::Map(_) => {
+      let mut table = InlineTable::new();
+      for (key, value) in entries(kind)? {
+        table.insert(&key, to_value(&value.kind)?);
+      }
+
+      Value::InlineTable(table)
+    }
+    _ => return None,
+  })
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "parse",
+    "( s -- record )\n\nParses TOML, pushing an error if it's invalid.",
+  ),
+  (
+    "stringify",
+    "( record -- s )\n\nEmits a record as TOML, or pushes `nil` if it can't \
+     be.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("toml"));
+
+  module
+    .add_func(
+      Symbol::from_ref("parse"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::String(ref x) => match x.parse::<Document>() {
+            Ok(x) => from_item(x.as_item()),
+            Err(e) => ExprKind::Error(Box::new(
+              ExprKind::String(e.to_compact_string()).into(),
+            )),
+          },
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("stringify"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = to_table(&item.kind).map_or(ExprKind::Nil, |table| {
+          ExprKind::String(Document::from(table).to_compact_string())
+        });
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
@@ -0,0 +1,346 @@
+//! Parsing and emitting YAML, with the same API as the `json` module.
+//!
+//! Mappings become records, and sequences become lists. Keys which aren't
+//! strings become strings of their YAML, so `1: a` has the key `"1"`. Tags
+//! are ignored, and aliases are expanded. A document can only be emitted from
+//! values with a YAML equivalent, which doesn't include functions or bytes.
+
+use std::sync::Arc;
+
+use compact_str::ToCompactString;
+use serde_yaml::{Mapping, Number, Value};
+use stack_core::prelude::*;
+
+pub use serde_yaml::Error as YamlError;
+
+fn from_value(value: &Value) -> ExprKind {
+  match value {
+    Value::Null => ExprKind::Nil,
+    Value::Bool(x) => ExprKind::Boolean(*x),
+    Value::Number(x) => from_number(x),
+    Value::String(x) => ExprKind::String(x.into()),
+    Value::Sequence(x) => {
+      ExprKind::List(x.iter().map(|x| from_value(x).into()).collect())
+    }
+    Value::Mapping(x) => ExprKind::Record(
+      x.iter()
+        .map(|(key, value)| (key_symbol(key), from_value(value).into()))
+        .collect(),
+    ),
+    Value::Tagged(x) => from_value(&x.value),
+  }
+}
+
+fn from_number(number: &Number) -> ExprKind {
+  match number.as_i64() {
+    Some(x) => ExprKind::Integer(x),
+    None => ExprKind::Float(number.as_f64().unwrap_or(f64::NAN)),
+  }
+}
+
+fn key_symbol(key: &Value) -> Symbol {
+  match key {
+    Value::String(x) => Symbol::from_ref(x),
+    key => Symbol::from_ref(
+      serde_yaml::to_string(key).unwrap_or_default().trim_end(),
+    ),
+  }
+}
+
+/// Returns the entries of a record or map, with a record's keys sorted so
+/// that the output is stable.
+fn entries(kind: &ExprKind) -> Option<Vec<(String, &Expr)>> {
+  match kind {
+    ExprKind::Record(x) => {
+      let mut entries = x
+        .iter()
+        .map(|(key, value)| (key.as_str().to_string(), value))
+        .collect::<Vec<_>>();
+      entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+      Some(entries)
+    }
+    ExprKind::Map(x) => Some(
+      x.iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect(),
+    ),
+    _ => None,
+  }
+}
+
+fn to_value(kind: &ExprKind) -> Option<Value> {
+  Some(match kind {
+    ExprKind::Nil => Value::Null,
+    ExprKind::Boolean(x) => Value::Bool(*x),
+    ExprKind::Integer(x) => Value::Number((*x).into()),
+    ExprKind::Float(x) => Value::Number((*x).into()),
+    ExprKind::String(x) => Value::String(x.to_string()),
+    ExprKind::Char(x) => Value::String(x.to_string()),
+    ExprKind::Symbol(x) => Value::String(x.as_str().to_string()),
+    ExprKind::List(x) => Value::Sequence(
+      x.iter().map(|x| to_value(&x.kind)).collect::<Option<_>>()?,
+    ),
+    ExprKind::Set(x) => Value::Sequence(
+      x.iter()
+        .map(|x| to_value(&x.clone().into()))
+        .collect::<Option<_>>()?,
+    ),
+    ExprKind::Record(_) | ExprKind::Map(_) => Value::Mapping(
+      entries(kind)?
+        .into_iter()
+        .map(|(key, value)| Some((Value::String(key), to_value(&value.kind)?)))
+        .collect::<Option<Mapping>>()?,
+    ),
+    _ => return None,
+  })
+}
+
+/// Parses a YAML document.
+pub fn parse(source: &str) -> Result<ExprKind, YamlError> {
+  serde_yaml::from_str::<Value>(source).map(|x| from_value(&x))
+}
+
+/// Emits a value as a YAML document, or [`None`] if it has no YAML
+/// equivalent.
+pub fn stringify(kind: &ExprKind) -> Option<String> {
+  serde_yaml::to_string(&to_value(kind)?).ok()
+}
+
+const DOCS: &[(&str, &str)] = &[
+  (
+    "parse",
+    "( s -- item )\n\nParses YAML, pushing an error if it's invalid.",
+  ),
+  (
+    "stringify",
+    "( item -- s )\n\nEmits an item as YAML, or pushes `nil` if it can't be.",
+  ),
+];
+
+pub fn module() -> Module {
+  let mut module = Module::new(Symbol::from_ref("yaml"));
+
+  module
+    .add_func(
+      Symbol::from_ref("parse"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = match item.kind {
+          ExprKind::String(ref x) => match parse(x) {
+            Ok(x) => x,
+            Err(e) => ExprKind::Error(Box::new(
+              ExprKind::String(e.to_compact_string()).into(),
+            )),
+          },
+          _ => ExprKind::Nil,
+        };
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    )
+    .add_func(
+      Symbol::from_ref("stringify"),
+      Arc::new(|_, mut context, expr| {
+        let item = context.stack_pop(&expr)?;
+
+        let kind = stringify(&item.kind)
+          .map_or(ExprKind::Nil, |x| ExprKind::String(x.into()));
+
+        context.stack_push(kind.into())?;
+
+        Ok(context)
+      }),
+    );
+
+  crate::add_docs(&mut module, DOCS);
+
+  module
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use super::*;
+
+  fn list(items: impl IntoIterator<Item = ExprKind>) -> ExprKind {
+    ExprKind::List(items.into_iter().map(Expr::from).collect())
+  }
+
+  fn record<'a>(
+    entries: impl IntoIterator<Item = (&'a str, ExprKind)>,
+  ) -> ExprKind {
+    ExprKind::Record(
+      entries
+        .into_iter()
+        .map(|(k, v)| (Symbol::from_ref(k), v.into()))
+        .collect::<HashMap<_, _>>()
+        .into(),
+    )
+  }
+
+  fn string(x: &str) -> ExprKind {
+    ExprKind::String(x.into())
+  }
+
+  #[test]
+  fn parses_block_collections() {
+    let source = "\
+---
+# A comment
+name: stack # trailing
+tags:
+  - a
+  - b
+same-indent:
+- 1
+- 2
+people:
+  - name: ada
+    age: 36
+  -
+    name: bob
+empty:
+";
+
+    assert_eq!(
+      parse(source).ok(),
+      Some(record([
+        ("name", string("stack")),
+        ("tags", list([string("a"), string("b")])),
+        (
+          "same-indent",
+          list([ExprKind::Integer(1), ExprKind::Integer(2)])
+        ),
+        (
+          "people",
+          list([
+            record([("name", string("ada")), ("age", ExprKind::Integer(36))]),
+            record([("name", string("bob"))]),
+          ])
+        ),
+        ("empty", ExprKind::Nil),
+      ]))
+    );
+  }
+
+  #[test]
+  fn parses_flow_collections() {
+    assert_eq!(
+      parse("[1, [2, 3], {a: x, 'b c': []}, \"d, e\"]").ok(),
+      Some(list([
+        ExprKind::Integer(1),
+        list([ExprKind::Integer(2), ExprKind::Integer(3)]),
+        record([("a", string("x")), ("b c", list([]))]),
+        string("d, e"),
+      ]))
+    );
+    assert_eq!(
+      parse("a: {b: [1, 2]}\nc: {}").ok(),
+      Some(record([
+        (
+          "a",
+          record([("b", list([ExprKind::Integer(1), ExprKind::Integer(2)]))])
+        ),
+        ("c", record([])),
+      ]))
+    );
+  }
+
+  #[test]
+  fn types_scalars() {
+    let cases = [
+      ("null", ExprKind::Nil),
+      ("~", ExprKind::Nil),
+      ("True", ExprKind::Boolean(true)),
+      ("false", ExprKind::Boolean(false)),
+      ("-12", ExprKind::Integer(-12)),
+      ("+7", ExprKind::Integer(7)),
+      ("0x1F", ExprKind::Integer(31)),
+      ("0o17", ExprKind::Integer(15)),
+      ("1.5", ExprKind::Float(1.5)),
+      ("-2e3", ExprKind::Float(-2000.0)),
+      (".inf", ExprKind::Float(f64::INFINITY)),
+      ("-.inf", ExprKind::Float(f64::NEG_INFINITY)),
+      ("1.2.3", string("1.2.3")),
+      ("'123'", string("123")),
+      ("\"true\"", string("true")),
+      ("'it''s'", string("it's")),
+      ("\"a\\tb\\n\"", string("a\tb\n")),
+      ("hello world", string("hello world")),
+    ];
+
+    for (source, expected) in cases {
+      assert_eq!(parse(source).ok(), Some(expected), "{source}");
+    }
+
+    assert!(matches!(parse(".nan"), Ok(ExprKind::Float(x)) if x.is_nan()));
+  }
+
+  #[test]
+  fn parses_multi_line_strings() {
+    let source = "\
+literal: |
+  line one
+    indented
+
+  line three
+folded: >
+  one
+  two
+after: 1
+";
+
+    assert_eq!(
+      parse(source).ok(),
+      Some(record([
+        ("literal", string("line one\n  indented\n\nline three\n")),
+        ("folded", string("one two\n")),
+        ("after", ExprKind::Integer(1)),
+      ]))
+    );
+  }
+
+  #[test]
+  fn parses_aliases_tags_and_keys() {
+    assert_eq!(
+      parse("base: &base {a: 1}\ncopy: *base\ntagged: !x 2\n1: one").ok(),
+      Some(record([
+        ("base", record([("a", ExprKind::Integer(1))])),
+        ("copy", record([("a", ExprKind::Integer(1))])),
+        ("tagged", ExprKind::Integer(2)),
+        ("1", string("one")),
+      ]))
+    );
+  }
+
+  #[test]
+  fn rejects_invalid_documents() {
+    let line = |source| parse(source).unwrap_err().location().map(|x| x.line());
+
+    assert_eq!(line("a:\n\tb: 1"), Some(2));
+    assert_eq!(line("a: |\n\tx\n"), Some(2));
+    assert_eq!(line("a: [1, 2"), Some(2));
+    assert_eq!(line("a: 1\n  b: 2"), Some(2));
+    assert_eq!(line("a: b: c"), Some(1));
+    assert!(parse("a: 1\n---\nb: 2").is_err());
+
+    // A tab which isn't in the indentation is fine.
+    assert_eq!(parse("a:\tb\n").ok(), Some(record([("a", string("b"))])));
+  }
+
+  #[test]
+  fn round_trips_through_stringify() {
+    let value = record([
+      ("name", string("stack")),
+      ("list", list([ExprKind::Integer(1), string("two: 2")])),
+      ("nested", record([("x", ExprKind::Float(1.0))])),
+    ]);
+
+    assert_eq!(parse(&stringify(&value).unwrap()).ok(), Some(value));
+  }
+}
@@ -0,0 +1,154 @@
+//! A WebAssembly build of Stack, for running programs in the browser.
+//!
+//! Build it with `wasm-pack build stack-wasm --target web`, or with
+//! `cargo build --target wasm32-unknown-unknown` and `wasm-bindgen`. It only
+//! includes the intrinsics, since the standard modules need a file system and
+//! processes.
+//!
+//! ```js
+//! import init, { StackInterpreter } from "./pkg/stack_wasm.js";
+//!
+//! await init();
+//! const interpreter = new StackInterpreter();
+//! const { stack, output, error } = interpreter.eval("1 2 + print 'a");
+//! ```
+//!
+//! Each [`StackInterpreter`] keeps its stack and definitions between calls to
+//! [`eval`](StackInterpreter::eval), like a REPL.
+
+use std::sync::{Arc, Mutex};
+
+use js_sys::{Array, Object, Reflect};
+use stack_core::prelude::*;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct StackInterpreter {
+  engine: Engine,
+  context: Context,
+  output: Arc<Mutex<String>>,
+}
+
+#[wasm_bindgen]
+impl StackInterpreter {
+  #[wasm_bindgen(constructor)]
+  #[allow(clippy::new_without_default)]
+  pub fn new() -> Self {
+    let output = Arc::new(Mutex::new(String::new()));
+
+    // There's no terminal to print to, so the output is collected instead.
+    let print_output = output.clone();
+    let engine = Engine::builder()
+      .override_intrinsic(
+        Intrinsic::Print,
+        Arc::new(move |_, mut context, expr| {
+          let value = context.stack_pop(&expr)?;
+
+          let mut output = print_output.lock().unwrap();
+          output.push_str(&value.to_string());
+          output.push('\n');
+
+          Ok(context)
+        }),
+      )
+      .build();
+
+    Self {
+      engine,
+      context: Context::new(),
+      output,
+    }
+  }
+
+  /// Runs `source`, returning an object with the `stack` as an array of
+  /// strings, the `output` of `print`, and the `error` if it failed.
+  pub fn eval(&mut self, source: &str) -> JsValue {
+    let (stack, error) = self.run(source);
+    let output = core::mem::take(&mut *self.output.lock().unwrap());
+
+    let result = Object::new();
+    let set = |key: &str, value: JsValue| {
+      Reflect::set(&result, &JsValue::from_str(key), &value).unwrap();
+    };
+
+    set(
+      "stack",
+      stack
+        .iter()
+        .map(|x| JsValue::from_str(x))
+        .collect::<Array>()
+        .into(),
+    );
+    set("output", JsValue::from_str(&output));
+    set(
+      "error",
+      error.map_or(JsValue::UNDEFINED, |x| JsValue::from_str(&x)),
+    );
+
+    result.into()
+  }
+
+  /// Clears the stack and definitions.
+  pub fn reset(&mut self) {
+    self.context = Context::new();
+  }
+}
+
+impl StackInterpreter {
+  /// Runs `source`, returning the stack as strings and the error, if any.
+  fn run(&mut self, source: &str) -> (Vec<String>, Option<String>) {
+    let source = Source::new("playground", source);
+    self.context.add_source(source.clone());
+
+    let mut lexer = Lexer::new(source);
+    let exprs = match parse(&mut lexer) {
+      Ok(x) => x,
+      Err(e) => return (self.stack(), Some(e.to_string())),
+    };
+
+    let context = core::mem::take(&mut self.context);
+    let error = match self.engine.run(context, exprs) {
+      Ok(context) => {
+        self.context = context;
+        None
+      }
+      Err(e) => {
+        let message = e.to_string();
+        self.context = *e.context;
+        Some(message)
+      }
+    };
+
+    (self.stack(), error)
+  }
+
+  fn stack(&self) -> Vec<String> {
+    self.context.stack().iter().map(|x| x.to_string()).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_definitions_between_runs() {
+    let mut interpreter = StackInterpreter::new();
+
+    assert_eq!(interpreter.run("2 'x def"), (Vec::new(), None));
+    assert_eq!(
+      interpreter.run("x 3 * print x"),
+      (vec!["2".to_string()], None)
+    );
+    assert_eq!(*interpreter.output.lock().unwrap(), "6\n");
+  }
+
+  #[test]
+  fn keeps_the_stack_on_errors() {
+    let mut interpreter = StackInterpreter::new();
+    let (stack, error) = interpreter.run("1 +");
+
+    assert_eq!(stack, Vec::<String>::new());
+    assert!(error.unwrap().starts_with("stack underflow"));
+  }
+}